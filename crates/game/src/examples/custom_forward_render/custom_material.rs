@@ -9,15 +9,19 @@ pub struct CustomMaterialAsset {
     pub label: String,
     /// The color of the material instance.
     pub color: (f32, f32, f32),
-    /// The texture of the material instance. If none, a dummy texture is used.
-    pub texture: Option<Handle<Texture>>,
+    /// The texture layers of the material instance (up to 4). The layers must have the same size and format,
+    /// and the COPY_SRC usage. If empty, the material is not textured.
+    pub textures: Vec<Handle<Texture>>,
+    /// The blend weight of each texture layer.
+    pub layer_blend_weights: [f32; 4],
 }
 impl Default for CustomMaterialAsset {
     fn default() -> Self {
         CustomMaterialAsset {
             label: "custom-material".to_string(),
             color: (1.0, 1.0, 1.0),
-            texture: None,
+            textures: Vec::new(),
+            layer_blend_weights: [1.0, 0.0, 0.0, 0.0],
         }
     }
 }
@@ -27,27 +31,37 @@ impl Default for CustomMaterialAsset {
 pub(crate) struct CustomMaterialUniform {
     /// RGB color of the material.
     pub color: [f32; 3],
-    /// Whether the material has a texture (1.0) or not (0.0).
-    pub has_texture: f32,
+    /// The number of texture layers of the material.
+    pub layer_count: f32,
 }
+
+/// The maximum number of texture layers of a custom material.
+pub const CUSTOM_MATERIAL_MAX_LAYERS: usize = 4;
 impl Material for CustomMaterialAsset {
     fn describe(&self, builder: &mut MaterialBuilder) {
         // Create the uniform buffer
         let uniform = CustomMaterialUniform {
             color: [self.color.0, self.color.1, self.color.2],
-            has_texture: if self.texture.is_some() { 1.0 } else { 0.0 },
+            layer_count: self.textures.len().min(CUSTOM_MATERIAL_MAX_LAYERS) as f32,
         };
+        if self.textures.len() > CUSTOM_MATERIAL_MAX_LAYERS {
+            warn!("The custom material {} has {} texture layers, only the first {} will be used.",
+                self.label, self.textures.len(), CUSTOM_MATERIAL_MAX_LAYERS);
+        }
 
         // Build the material
         builder.add_buffer(
             0, WShaderStages::FRAGMENT, WBufferBindingType::Uniform,
             size_of::<CustomMaterialUniform>(), Some(bytemuck::cast_slice(&[uniform]).to_vec()));
-        builder.add_texture_view(1, WShaderStages::FRAGMENT, self.texture.clone());
-        builder.add_texture_sampler( 2, WShaderStages::FRAGMENT, self.texture.clone());
+        builder.add_texture_array_view(1, WShaderStages::FRAGMENT,
+            self.textures.iter().take(CUSTOM_MATERIAL_MAX_LAYERS).cloned().collect());
     }
     fn label(&self) -> String {
         self.label.clone()
     }
+    fn push_constants(&self) -> Vec<u8> {
+        bytemuck::cast_slice(&self.layer_blend_weights).to_vec()
+    }
 }
 
 #[derive(Component)]
@@ -1,6 +1,6 @@
 use bevy::{ecs::system::lifetimeless::{SRes, SResMut}, prelude::*};
-use wde_wgpu::render_pipeline::WDepthStencilDescriptor;
-use wde_render::{assets::{GpuMaterial, PrepareAssetError, RenderAsset, RenderAssets}, features::CameraFeatureRender, pipelines::{CachedPipelineIndex, PipelineManager, RenderPipelineDescriptor}};
+use wde_wgpu::render_pipeline::{WDepthStencilDescriptor, WShaderStages};
+use wde_render::{assets::{GpuMaterial, PrepareAssetError, RenderAsset, RenderAssets}, core::msaa::MsaaTextures, features::CameraFeatureRender, pipelines::{CachedPipelineIndex, PipelineManager, PushConstantDescriptor, RenderPipelineDescriptor}};
 
 use super::{CustomMaterialAsset, CustomSsbo};
 
@@ -15,20 +15,38 @@ pub struct CustomRenderPipeline(pub Handle<CustomRenderPipelineAsset>);
 
 /// Represents the gpu custom render pipeline.
 pub struct GpuCustomRenderPipeline {
-    pub cached_pipeline_index: CachedPipelineIndex
+    pub cached_pipeline_index: CachedPipelineIndex,
+    /// The descriptor of the pipeline, to create it again when the sample count of the MSAA targets changes.
+    pub descriptor: RenderPipelineDescriptor
+}
+impl GpuCustomRenderPipeline {
+    /// Create the pipeline again with the sample count of the MSAA targets when it changes.
+    pub fn update_sample_count(
+        mut pipelines: ResMut<RenderAssets<GpuCustomRenderPipeline>>, mut pipeline_manager: ResMut<PipelineManager>,
+        msaa_textures: Res<MsaaTextures>
+    ) {
+        for (_, pipeline) in pipelines.iter_mut() {
+            if pipeline.descriptor.sample_count != msaa_textures.sample_count {
+                pipeline.descriptor.sample_count = msaa_textures.sample_count;
+                pipeline.cached_pipeline_index = pipeline_manager.create_render_pipeline(pipeline.descriptor.clone());
+            }
+        }
+    }
 }
 impl RenderAsset for GpuCustomRenderPipeline {
     type SourceAsset = CustomRenderPipelineAsset;
     type Param = (
         SRes<AssetServer>, SResMut<PipelineManager>,
-        SRes<CameraFeatureRender>, SRes<RenderAssets<GpuMaterial<CustomMaterialAsset>>>, SRes<CustomSsbo>
+        SRes<CameraFeatureRender>, SRes<RenderAssets<GpuMaterial<CustomMaterialAsset>>>, SRes<CustomSsbo>,
+        SRes<MsaaTextures>
     );
 
     fn prepare_asset(
             asset: Self::SourceAsset,
             (
                 assets_server, pipeline_manager,
-                camera_feature, materials, ssbo
+                camera_feature, materials, ssbo,
+                msaa_textures
             ): &mut bevy::ecs::system::SystemParamItem<Self::Param>
         ) -> Result<Self, PrepareAssetError<Self::SourceAsset>> {
         // Get the ssbo layout
@@ -54,12 +72,19 @@ impl RenderAsset for GpuCustomRenderPipeline {
                 ..Default::default()
             },
             render_targets: None,
+            push_constants: vec![PushConstantDescriptor {
+                stages: WShaderStages::FRAGMENT,
+                offset: 0,
+                size: std::mem::size_of::<[f32; 4]>() as u32
+            }],
+            sample_count: msaa_textures.sample_count,
             ..Default::default()
         };
-        let cached_index = pipeline_manager.create_render_pipeline(pipeline_desc);
+        let cached_index = pipeline_manager.create_render_pipeline(pipeline_desc.clone());
 
         Ok(GpuCustomRenderPipeline {
-            cached_pipeline_index: cached_index
+            cached_pipeline_index: cached_index,
+            descriptor: pipeline_desc
         })
     }
 
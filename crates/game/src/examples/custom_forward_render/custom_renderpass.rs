@@ -1,6 +1,6 @@
 use bevy::prelude::*;
-use wde_render::{assets::{GpuBuffer, GpuMaterial, GpuMesh, GpuTexture, Mesh, MeshAsset, RenderAssets}, components::TransformUniform, core::{extract_macros::ExtractWorld, SwapchainFrame}, features::CameraFeatureRender, pipelines::{CachedPipelineStatus, PipelineManager}, passes::depth::DepthTexture};
-use wde_wgpu::{command_buffer::{RenderPassBuilder, RenderPassColorAttachment, RenderPassDepth, WCommandBuffer}, instance::WRenderInstance};
+use wde_render::{assets::{GpuBuffer, GpuMaterial, GpuMesh, GpuTexture, Mesh, MeshAsset, RenderAssets}, components::TransformUniform, core::{extract_macros::ExtractWorld, msaa::MsaaTextures, SwapchainFrame}, features::CameraFeatureRender, pipelines::{CachedPipelineStatus, PipelineManager}, passes::depth::DepthTexture};
+use wde_wgpu::{command_buffer::{RenderPassBuilder, RenderPassColorAttachment, RenderPassDepth, WCommandBuffer}, instance::WRenderInstance, render_pipeline::WShaderStages};
 
 use super::{CustomMaterial, CustomMaterialAsset, CustomSsbo, GpuCustomRenderPipeline};
 
@@ -137,8 +137,8 @@ impl CustomRenderPass {
         (meshes, textures, materials): (
             Res<RenderAssets<GpuMesh>>, Res<RenderAssets<GpuTexture>>, Res<RenderAssets<GpuMaterial<CustomMaterialAsset>>>
         ),
-        (mesh_pipeline, render_mesh_pass, depth_texture): (
-            Res<RenderAssets<GpuCustomRenderPipeline>>, Res<CustomRenderPass>, Res<DepthTexture>
+        (mesh_pipeline, render_mesh_pass, depth_texture, msaa_textures): (
+            Res<RenderAssets<GpuCustomRenderPipeline>>, Res<CustomRenderPass>, Res<DepthTexture>, Res<MsaaTextures>
         )
     ) {
         // Get the render instance and swapchain frame
@@ -156,10 +156,20 @@ impl CustomRenderPass {
             None => return
         };
 
-        // Check if pipeline is ready
+        // Check if pipeline is ready, with the sample count of the MSAA targets
         let mesh_pipeline = match mesh_pipeline.iter().next() {
-            Some((_, pipeline)) => pipeline,
-            None => return
+            Some((_, pipeline)) if pipeline.descriptor.sample_count == msaa_textures.sample_count => pipeline,
+            _ => return
+        };
+
+        // Render to the multisampled targets resolved into the swapchain if the MSAA is enabled
+        let (color_view, resolve_view, depth_view) = match (&msaa_textures.color, &msaa_textures.depth) {
+            (Some(color), Some(depth)) => match (textures.get(color), textures.get(depth)) {
+                (Some(color), Some(depth)) if color.texture.size == depth_texture.texture.size =>
+                    (&color.texture.view, Some(&swapchain_frame.view), &depth.texture.view),
+                _ => return
+            },
+            _ => (&swapchain_frame.view, None, &depth_texture.texture.view)
         };
 
         // Create the render pass
@@ -168,11 +178,12 @@ impl CustomRenderPass {
             let mut render_pass = command_buffer.create_render_pass("custom",
             |builder: &mut RenderPassBuilder| {
                 builder.set_depth_texture(RenderPassDepth {
-                    texture: Some(&depth_texture.texture.view),
+                    texture: Some(depth_view),
                     ..Default::default()
                 });
                 builder.add_color_attachment(RenderPassColorAttachment {
-                    texture: Some(&swapchain_frame.view),
+                    texture: Some(color_view),
+                    msaa_resolve_target: resolve_view,
                     ..Default::default()
                 });
             });
@@ -205,8 +216,9 @@ impl CustomRenderPass {
                                 None => continue // Should not happen
                             };
 
-                            // Set the material bind group
+                            // Set the material bind group and layer blend weights
                             render_pass.set_bind_group(2, &material.bind_group);
+                            render_pass.set_push_constants(WShaderStages::FRAGMENT, &material.push_constants);
                             old_material_id = Some(batch.material.id());
                         }
 
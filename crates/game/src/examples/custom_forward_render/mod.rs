@@ -29,17 +29,18 @@ fn create_scene(mut commands: Commands, asset_server: Res<AssetServer>, mut mate
     |settings: &mut TextureLoaderSettings| {
         settings.label = "custom-box".to_string();
         settings.format = WTextureFormat::Rgba8Unorm;
-        settings.usages = WTextureUsages::TEXTURE_BINDING;
+        settings.usages = WTextureUsages::TEXTURE_BINDING | WTextureUsages::COPY_SRC;
     });
     let red_box = materials.add(CustomMaterialAsset {
         label: "custom-material-red-box".to_string(),
         color: (1.0, 0.0, 0.0),
-        texture: Some(box_texture),
+        textures: vec![box_texture],
+        layer_blend_weights: [1.0, 0.0, 0.0, 0.0],
     });
     let blue = materials.add(CustomMaterialAsset {
         label: "custom-material-blue".to_string(),
         color: (0.0, 0.0, 1.0),
-        texture: None,
+        ..Default::default()
     });
     let suzanne = asset_server.load("examples/custom_forward_render/suzanne.obj");
     let cube = asset_server.load("examples/custom_forward_render/cube.obj");
@@ -91,6 +92,7 @@ impl Plugin for CustomFeaturesPlugin {
         // Add the custom render pass
         app.get_sub_app_mut(RenderApp).unwrap()
             .add_systems(Extract, CustomRenderPass::create_batches)
+            .add_systems(Render, GpuCustomRenderPipeline::update_sample_count.in_set(RenderSet::Prepare))
             .add_systems(Render, CustomRenderPass::render.in_set(RenderSet::Render));
     }
 
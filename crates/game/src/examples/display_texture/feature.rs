@@ -67,10 +67,10 @@ impl Plugin for DisplayTextureFeature {
         let post_process_mesh: Handle<MeshAsset> = app.world_mut().add_asset(MeshAsset {
             label: "PostProcessQuad".to_string(),
             vertices: vec![
-                WVertex { position: [-1.0, 1.0, 0.0], uv: [0.0, 1.0], normal: [0.0, 0.0, 0.0] },
-                WVertex { position: [-1.0, -1.0, 0.0], uv: [0.0, 0.0], normal: [0.0, 0.0, 0.0] },
-                WVertex { position: [1.0, -1.0, 0.0], uv: [1.0, 0.0], normal: [0.0, 0.0, 0.0] },
-                WVertex { position: [1.0, 1.0, 0.0], uv: [1.0, 1.0], normal: [0.0, 0.0, 0.0] },
+                WVertex { position: [-1.0, 1.0, 0.0], uv: [0.0, 1.0], normal: [0.0, 0.0, 0.0], uv1: [0.0, 1.0], tangent: [0.0; 4] },
+                WVertex { position: [-1.0, -1.0, 0.0], uv: [0.0, 0.0], normal: [0.0, 0.0, 0.0], uv1: [0.0, 0.0], tangent: [0.0; 4] },
+                WVertex { position: [1.0, -1.0, 0.0], uv: [1.0, 0.0], normal: [0.0, 0.0, 0.0], uv1: [1.0, 0.0], tangent: [0.0; 4] },
+                WVertex { position: [1.0, 1.0, 0.0], uv: [1.0, 1.0], normal: [0.0, 0.0, 0.0], uv1: [1.0, 1.0], tangent: [0.0; 4] },
             ],
             indices: vec![0, 1, 2, 0, 2, 3],
             bounding_box: ModelBoundingBox {
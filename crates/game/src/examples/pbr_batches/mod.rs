@@ -1,11 +1,14 @@
 use bevy::prelude::*;
-use wde_render::{assets::{materials::{PbrMaterial, PbrMaterialAsset}, Mesh, TextureLoaderSettings}, components::{Camera, CameraController}};
-use wde_wgpu::texture::{WTextureFormat, WTextureUsages};
+use wde_render::{assets::{materials::{PbrMaterial, PbrMaterialAsset}, Mesh, TextureLoaderSettings}, components::{Camera, CameraController}, passes::pbr::GpuDrivenPlugin};
+use wde_wgpu::texture::{WSamplerDescriptor, WTextureFormat, WTextureUsages};
 
 pub struct PbrBatchesPlugin;
 impl Plugin for PbrBatchesPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Startup, init);
+        // Draw the batches of the thousands of entities with indirect draw calls
+        app
+            .add_plugins(GpuDrivenPlugin)
+            .add_systems(Startup, init);
     }
 }
 
@@ -24,6 +27,8 @@ fn init(mut commands: Commands, asset_server: Res<AssetServer>, mut materials: R
         settings.label = "pbr-box".to_string();
         settings.format = WTextureFormat::Rgba8Unorm;
         settings.usages = WTextureUsages::TEXTURE_BINDING;
+        settings.generate_mipmaps = true;
+        settings.sampler = WSamplerDescriptor::TRILINEAR_ANISOTROPIC;
     });
     let red_box = materials.add(PbrMaterialAsset {
         label: "pbr-material-red-box".to_string(),
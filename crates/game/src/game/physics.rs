@@ -1,14 +1,79 @@
+use std::time::Duration;
+
 use bevy::prelude::*;
 use physx::prelude::*;
 
+/** The settings of the physics simulation. */
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct PhysicsSettings {
+    /** The duration of a physics step in seconds. */
+    pub fixed_dt: f32,
+    /** The maximum number of physics steps run in a frame, bounding the time the virtual clock advances in a frame
+     * to avoid a spiral of death. The remaining time is dropped. */
+    pub max_substeps: u32,
+    /** The gravity applied to the rigid bodies. */
+    pub gravity: Vec3,
+}
+impl Default for PhysicsSettings {
+    fn default() -> Self {
+        PhysicsSettings {
+            fixed_dt: 1.0 / 60.0,
+            max_substeps: 8,
+            gravity: Vec3::new(0.0, -9.81, 0.0),
+        }
+    }
+}
+
+/** The system sets of the physics simulation. */
+#[derive(SystemSet, Hash, PartialEq, Eq, Clone, Copy, Debug)]
+pub enum PhysicsSet {
+    /** Runs once per fixed physics step, in the `FixedUpdate` schedule. */
+    Step,
+}
+
+/** A body moved by the physics simulation. */
+#[derive(Component, Default, Clone, Copy, Debug)]
+pub struct RigidBody {
+    /** The linear velocity of the body in meters per second. */
+    pub velocity: Vec3,
+}
+
 pub struct PhysicsPlugin;
 impl Plugin for PhysicsPlugin {
     fn build(&self, app: &mut App) {
+        // Run the physics steps in the fixed timestep schedule, whose accumulator is `Time<Fixed>`
+        app
+            .init_resource::<PhysicsSettings>()
+            .add_systems(Update, update_fixed_timestep.run_if(resource_changed::<PhysicsSettings>))
+            .add_systems(FixedUpdate, integrate_rigid_bodies.in_set(PhysicsSet::Step));
+
         // Add the physics system
         app.add_systems(Startup, init);
     }
 }
 
+/** Match the rate of the `FixedUpdate` schedule with the physics timestep, and bound the time the virtual clock
+ * advances in a frame to `max_substeps` physics steps. */
+fn update_fixed_timestep(
+    settings: Res<PhysicsSettings>, mut fixed_time: ResMut<Time<Fixed>>, mut virtual_time: ResMut<Time<Virtual>>
+) {
+    let fixed_dt = Duration::from_secs_f32(settings.fixed_dt);
+    fixed_time.set_timestep(fixed_dt);
+    virtual_time.set_max_delta(fixed_dt * settings.max_substeps.max(1));
+}
+
+/** Integrate the velocity and position of the rigid bodies over a physics step. */
+fn integrate_rigid_bodies(
+    settings: Res<PhysicsSettings>, fixed_time: Res<Time<Fixed>>,
+    mut bodies: Query<(&mut RigidBody, &mut Transform)>
+) {
+    let dt = fixed_time.timestep().as_secs_f32();
+    for (mut body, mut transform) in bodies.iter_mut() {
+        body.velocity += settings.gravity * dt;
+        transform.translation += body.velocity * dt;
+    }
+}
+
 type PxMaterial = physx::material::PxMaterial<()>;
 type PxShape = physx::shape::PxShape<(), PxMaterial>;
 type PxArticulationLink = physx::articulation_link::PxArticulationLink<(), PxShape>;
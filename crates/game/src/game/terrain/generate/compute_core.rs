@@ -1,16 +1,60 @@
 use bevy::prelude::*;
-use wde_render::{assets::{GpuBuffer, RenderAssets}, pipelines::{CachedPipelineStatus, PipelineManager}};
-use wde_wgpu::{bind_group::BindGroup, command_buffer::WCommandBuffer, instance::WRenderInstance};
+use wde_render::{assets::{GpuBuffer, GpuTexture, RenderAssets}, pipelines::{CachedPipelineStatus, PipelineManager}};
+use wde_wgpu::{bind_group::BindGroup, buffer::WBufferMapStatus, command_buffer::WCommandBuffer, instance::WRenderInstance};
 
-use crate::terrain::{mc_chunk::{MCChunksListRender, MCLoadingChunk, MCPendingChunk}, mc_compute_main::{GpuMCDescription, MCComputeHandlerGPU}, MC_MAX_CHUNKS_PROCESS_PER_FRAME, MC_MAX_TRIANGLES};
+use crate::terrain::{mc_chunk::{MCActiveChunk, MCChunksListRender, MCCopyBackStage, MCCopyingBackChunk, MCLoadingChunk, MCPendingChunk}, mc_compute_main::{GpuMCDescription, MCComputeHandlerGPU}, TerrainIsoLevel, MC_MAX_TRIANGLES};
 
 use super::compute_pipeline::GpuMCComputePipelineGenerate;
 
 pub struct MCComputeCorePoints;
 impl MCComputeCorePoints {
+    /**
+     * Apply a new iso level to the chunks, and generate again the triangles of the chunks already generated.
+     * The active chunks stay rendered until their regenerated version replaces them.
+     */
+    pub fn refresh_iso_level(
+        iso_level: Res<TerrainIsoLevel>, mut chunks_list: ResMut<MCChunksListRender>, mut commands: Commands,
+        (pending_chunks, active_chunks, mut copying_back_chunks): (
+            Query<(Entity, &MCPendingChunk)>, Query<&MCActiveChunk>, Query<&mut MCCopyingBackChunk>
+        )
+    ) {
+        // Update the descriptions, read before each compute dispatch
+        let mut changed = false;
+        for desc in chunks_list.chunks.values_mut() {
+            if desc.iso_level != iso_level.0 {
+                desc.iso_level = iso_level.0;
+                changed = true;
+            }
+        }
+        if !changed {
+            return;
+        }
+        debug!("Regenerating the terrain chunks with the iso level {}.", iso_level.0);
+
+        // Generate again the chunks whose triangles were generated with the old iso level
+        for (entity, chunk) in pending_chunks.iter() {
+            commands.entity(entity).despawn();
+            commands.spawn(MCLoadingChunk {
+                index: chunk.index,
+                points_gpu: chunk.points_gpu.clone(),
+                points_gpu_group: None
+            });
+        }
+        for chunk in active_chunks.iter() {
+            commands.spawn(MCLoadingChunk {
+                index: chunk.index,
+                points_gpu: chunk.points_gpu.clone(),
+                points_gpu_group: None
+            });
+        }
+        for mut chunk in copying_back_chunks.iter_mut() {
+            chunk.dirty = true;
+        }
+    }
+
     /** Create the bind groups if they are not already created. */
     pub fn create_bind_groups(
-        handler: Res<MCComputeHandlerGPU>, buffers: Res<RenderAssets<GpuBuffer>>,
+        handler: Res<MCComputeHandlerGPU>, (buffers, textures): (Res<RenderAssets<GpuBuffer>>, Res<RenderAssets<GpuTexture>>),
         render_instance: Res<WRenderInstance<'static>>, mut pipeline: ResMut<RenderAssets<GpuMCComputePipelineGenerate>>,
         mut loading_chunks: Query<&mut MCLoadingChunk>
     ) {
@@ -58,6 +102,24 @@ impl MCComputeCorePoints {
             compute_pipeline.triangles_gpu_group = Some(triangles_bind_group);
         }
 
+        // Create the triangulation table bind group if it is not already created
+        if let (None, Some(triangles_table)) = (&compute_pipeline.triangles_table_group, &handler.triangles_table) {
+            // Get the layout and the texture
+            let (triangles_table_layout, triangles_table) = match (
+                &compute_pipeline.triangles_table_layout,
+                textures.get(triangles_table)
+            ) {
+                (Some(triangles_table_layout), Some(triangles_table)) => (triangles_table_layout, triangles_table),
+                _ => return
+            };
+
+            // Create the bind group
+            let render_instance = render_instance.data.read().unwrap();
+            compute_pipeline.triangles_table_group = Some(BindGroup::build(
+                "marching-cubes-generate-triangles-table", &render_instance, &triangles_table_layout.build(&render_instance),
+                &vec![BindGroup::texture_view(0, &triangles_table.texture)]));
+        }
+
         // Create the bind groups for the chunks if they are not already created
         let render_instance = render_instance.data.read().unwrap();
         for mut chunk in loading_chunks.iter_mut() {
@@ -85,9 +147,13 @@ impl MCComputeCorePoints {
         }
     }
     
-    /** Run the compute pass to generate the chunks. */
+    /**
+     * Run the compute pass to generate the triangles of the chunks, and start copying them back to the CPU.
+     * The generated description and triangles are copied to the readback buffers of the chunk right after its dispatch,
+     * so that up to `MC_MAX_CHUNKS_PROCESS_PER_FRAME` chunks are copied back at the same time.
+     */
     pub fn compute(
-        (query, mut commands): (Query<(Entity, &MCLoadingChunk)>, Commands),
+        (query, copying_back, mut commands): (Query<(Entity, &MCLoadingChunk)>, Query<&MCCopyingBackChunk>, Commands),
         (chunks_list, handler): (Res<MCChunksListRender>, Res<MCComputeHandlerGPU>),
         mut buffers: ResMut<RenderAssets<GpuBuffer>>,
         render_instance: Res<WRenderInstance<'static>>,
@@ -102,22 +168,28 @@ impl MCComputeCorePoints {
         };
 
         // Check if the handler is ready
-        let (desc_buffer_cpu, desc_buffer_gpu, desc_buffer_group, triangles_cpu, triangles_gpu, triangles_group) = match (
-            &handler.desc_cpu, &handler.desc_gpu, &compute_pipeline.desc_gpu_group, &handler.triangles_cpu, &handler.triangles_gpu, &compute_pipeline.triangles_gpu_group
+        let (desc_buffer_gpu, triangles_gpu, desc_buffer_group, triangles_group, triangles_table_group) = match (
+            &handler.desc_gpu, &handler.triangles_gpu, &compute_pipeline.desc_gpu_group, &compute_pipeline.triangles_gpu_group,
+            &compute_pipeline.triangles_table_group
         ) {
-            (Some(desc_buffer_cpu), Some(desc_buffer_gpu), Some(desc_buffer_group), Some(triangles_cpu), Some(triangles_gpu), Some(triangles_group)) => (
-                desc_buffer_cpu, desc_buffer_gpu, desc_buffer_group, triangles_cpu, triangles_gpu, triangles_group
+            (Some(desc_buffer_gpu), Some(triangles_gpu), Some(desc_buffer_group), Some(triangles_group), Some(triangles_table_group)) => (
+                desc_buffer_gpu, triangles_gpu, desc_buffer_group, triangles_group, triangles_table_group
             ),
             _ => return
         };
 
-        // Generate the chunks
-        let mut process_count = 0;
+        // Get the readback buffers not used by the chunks being copied back
+        let mut free_slots = (0..handler.desc_cpu.len().min(handler.triangles_cpu.len()))
+            .filter(|slot| copying_back.iter().all(|chunk| chunk.slot != *slot))
+            .filter(|slot| buffers.get(&handler.desc_cpu[*slot]).is_some() && buffers.get(&handler.triangles_cpu[*slot]).is_some())
+            .collect::<Vec<_>>().into_iter().peekable();
+
+        // Generate the chunks, one per free readback buffer
         for (entity, chunk) in query.iter() {
-            process_count += 1;
-            if process_count >= MC_MAX_CHUNKS_PROCESS_PER_FRAME {
-                break;
-            }
+            let slot = match free_slots.peek() {
+                Some(slot) => *slot,
+                None => break
+            };
             let desc = chunks_list.chunks.get(&chunk.index).unwrap();
 
             // Update the description buffer
@@ -155,6 +227,7 @@ impl MCComputeCorePoints {
                     compute_pass.set_bind_group(0, desc_buffer_group);
                     compute_pass.set_bind_group(1, points_group);
                     compute_pass.set_bind_group(2, triangles_group);
+                    compute_pass.set_bind_group(3, triangles_table_group);
 
                     // Dispatch the compute pass
                     const NUM_THREADS: i32 = 10;
@@ -176,41 +249,150 @@ impl MCComputeCorePoints {
             // Submit the command buffer
             command_buffer.submit(&render_instance);
 
-            // Read the indices counter
-            let mut triangles_counter = 0;
-            let mut c_sub_count = [0, 0, 0];
-            let cpu_buff = &buffers.get(desc_buffer_cpu).unwrap().buffer;
-            cpu_buff.copy_from_buffer(&render_instance, &buffers.get(desc_buffer_gpu).unwrap().buffer);
-            cpu_buff.map_read(&render_instance, |data| {
-                let desc = bytemuck::from_bytes::<GpuMCDescription>(&data);
-                triangles_counter = desc.triangles_counter;
-                c_sub_count = [desc.chunk_sub_count[0] as usize, desc.chunk_sub_count[1] as usize, desc.chunk_sub_count[2] as usize];
-            });
-
-            // Warn if the indices counter is too high
-            if triangles_counter > MC_MAX_TRIANGLES {
-                error!("In the marching cubes algorithm, there is too much triangles overflowing the triangles buffer. The counter is {} while the maximum is {}.", triangles_counter, MC_MAX_TRIANGLES);
-                continue;
-            }
+            // Copy the description and the triangles to the readback buffers of the chunk, before the next chunk
+            // overwrites them, and start reading the triangles counter
+            free_slots.next();
+            let desc_buffer_cpu = &buffers.get(&handler.desc_cpu[slot]).unwrap().buffer;
+            desc_buffer_cpu.copy_from_buffer(&render_instance, &buffers.get(desc_buffer_gpu).unwrap().buffer);
+            buffers.get(&handler.triangles_cpu[slot]).unwrap().buffer
+                .copy_from_buffer(&render_instance, &buffers.get(triangles_gpu).unwrap().buffer);
+            let read = desc_buffer_cpu.map_read_async(std::mem::size_of::<GpuMCDescription>());
 
-            // Read and process the triangles
-            let mut raw_triangles: Vec<f32> = Vec::new();
-            let triangles_buff = &buffers.get(triangles_cpu).unwrap().buffer;
-            triangles_buff.copy_from_buffer(&render_instance, &buffers.get(triangles_gpu).unwrap().buffer);
-            triangles_buff.map_read(&render_instance, |data| {
-                let triangles: &[f32] = bytemuck::cast_slice(&data);
-                raw_triangles.extend_from_slice(&triangles[..triangles_counter as usize * 12]);
-            });
-
-            // Spawn the chunk entity
-            trace!("Spawning the chunk entity {:?} with {} triangles.", entity, triangles_counter);
+            // Wait for the copy back of the chunk
+            trace!("Copying back the triangles of the chunk {:?}.", entity);
             commands.entity(entity).despawn();
-            commands.spawn(MCPendingChunk {
+            commands.spawn(MCCopyingBackChunk {
                 index: chunk.index,
-                raw_triangles,
-                triangles_counter,
+                stage: MCCopyBackStage::Counter(read),
+                slot,
+                dirty: false,
                 points_gpu: chunk.points_gpu.clone()
             });
         }
     }
+
+    /**
+     * Advance the copy back of the generated triangles to the CPU without blocking.
+     * The counter and the triangles are read in separate frames.
+     */
+    pub fn copy_back(
+        (mut query, mut commands): (Query<(Entity, &mut MCCopyingBackChunk)>, Commands),
+        (chunks_list, handler): (Res<MCChunksListRender>, Res<MCComputeHandlerGPU>),
+        buffers: Res<RenderAssets<GpuBuffer>>,
+        render_instance: Res<WRenderInstance<'static>>
+    ) {
+        let render_instance = render_instance.data.read().unwrap();
+        for (entity, mut chunk) in query.iter_mut() {
+            // Get the readback buffers of the chunk
+            let (desc_buffer_cpu, triangles_cpu) = match (
+                handler.desc_cpu.get(chunk.slot).and_then(|buffer| buffers.get(buffer)),
+                handler.triangles_cpu.get(chunk.slot).and_then(|buffer| buffers.get(buffer))
+            ) {
+                (Some(desc_buffer_cpu), Some(triangles_cpu)) => (&desc_buffer_cpu.buffer, &triangles_cpu.buffer),
+                _ => continue
+            };
+
+            // Retry the generation of the chunk if the mapping failed or the chunk is dirty
+            let retry = |commands: &mut Commands, chunk: &MCCopyingBackChunk| {
+                commands.entity(entity).despawn();
+                if !chunks_list.chunks.contains_key(&chunk.index) {
+                    return;
+                }
+                commands.spawn(MCLoadingChunk {
+                    index: chunk.index,
+                    points_gpu: chunk.points_gpu.clone(),
+                    points_gpu_group: None
+                });
+            };
+
+            match &chunk.stage {
+                MCCopyBackStage::Counter(read) => {
+                    // Read the triangles counter
+                    let mut triangles_counter = 0;
+                    match desc_buffer_cpu.poll_read_async(&render_instance, read, |data| {
+                        triangles_counter = bytemuck::from_bytes::<GpuMCDescription>(&data).triangles_counter;
+                    }) {
+                        WBufferMapStatus::Pending => continue,
+                        WBufferMapStatus::Failed => {
+                            retry(&mut commands, &chunk);
+                            continue;
+                        },
+                        WBufferMapStatus::Done => {}
+                    }
+
+                    // Drop the chunk if it was removed while being copied back
+                    if !chunks_list.chunks.contains_key(&chunk.index) {
+                        commands.entity(entity).despawn();
+                        continue;
+                    }
+
+                    // Generate the chunk again if its description changed while being copied back
+                    if chunk.dirty {
+                        retry(&mut commands, &chunk);
+                        continue;
+                    }
+
+                    // Warn if the triangles counter is too high
+                    if triangles_counter > MC_MAX_TRIANGLES {
+                        error!("In the marching cubes algorithm, there is too much triangles overflowing the triangles buffer. The counter is {} while the maximum is {}.", triangles_counter, MC_MAX_TRIANGLES);
+                        retry(&mut commands, &chunk);
+                        continue;
+                    }
+
+                    // Start reading the generated triangles only, already copied to the readback buffer of the chunk
+                    if triangles_counter > 0 {
+                        let triangles_size = triangles_counter as usize * 12 * std::mem::size_of::<f32>();
+                        let read = triangles_cpu.map_read_async(triangles_size);
+                        chunk.stage = MCCopyBackStage::Triangles { triangles_counter, read };
+                        continue;
+                    }
+
+                    // Nothing to read back
+                    trace!("Spawning the chunk entity {:?} without triangles.", entity);
+                    commands.entity(entity).despawn();
+                    commands.spawn(MCPendingChunk {
+                        index: chunk.index,
+                        raw_triangles: Vec::new(),
+                        triangles_counter: 0,
+                        points_gpu: chunk.points_gpu.clone()
+                    });
+                },
+                MCCopyBackStage::Triangles { triangles_counter, read } => {
+                    // Read the triangles
+                    let mut raw_triangles: Vec<f32> = Vec::new();
+                    match triangles_cpu.poll_read_async(&render_instance, read, |data| {
+                        raw_triangles.extend_from_slice(bytemuck::cast_slice(&data));
+                    }) {
+                        WBufferMapStatus::Pending => continue,
+                        WBufferMapStatus::Failed => {
+                            retry(&mut commands, &chunk);
+                            continue;
+                        },
+                        WBufferMapStatus::Done => {}
+                    }
+
+                    // Generate the chunk again if its description changed while being copied back
+                    if chunk.dirty {
+                        retry(&mut commands, &chunk);
+                        continue;
+                    }
+
+                    // Drop the chunk if it was removed while being copied back
+                    commands.entity(entity).despawn();
+                    if !chunks_list.chunks.contains_key(&chunk.index) {
+                        continue;
+                    }
+
+                    // Spawn the chunk entity
+                    trace!("Spawning the chunk entity {:?} with {} triangles.", entity, triangles_counter);
+                    commands.spawn(MCPendingChunk {
+                        index: chunk.index,
+                        raw_triangles,
+                        triangles_counter: *triangles_counter,
+                        points_gpu: chunk.points_gpu.clone()
+                    });
+                }
+            }
+        }
+    }
 }
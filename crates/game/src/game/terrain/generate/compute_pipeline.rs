@@ -14,10 +14,12 @@ pub struct GpuMCComputePipelineGenerate {
     pub desc_gpu_layout: Option<BindGroupLayout>,
     pub points_gpu_layout: Option<BindGroupLayout>,
     pub triangles_gpu_layout: Option<BindGroupLayout>,
+    pub triangles_table_layout: Option<BindGroupLayout>,
 
     // Bind groups
     pub desc_gpu_group: Option<WgpuBindGroup>,
-    pub triangles_gpu_group: Option<WgpuBindGroup>
+    pub triangles_gpu_group: Option<WgpuBindGroup>,
+    pub triangles_table_group: Option<WgpuBindGroup>
 }
 impl RenderAsset for GpuMCComputePipelineGenerate {
     type SourceAsset = MCComputePipelineGenerateAsset;
@@ -47,12 +49,15 @@ impl RenderAsset for GpuMCComputePipelineGenerate {
                 WShaderStages::COMPUTE,
                 BufferBindingType::Storage { read_only: false });
         });
+        let triangles_table_layout = BindGroupLayout::new("marching-cubes-generate-triangles-table", |builder| {
+            builder.add_uint_texture_view(0, WShaderStages::COMPUTE);
+        });
 
         // Create the pipeline
         let pipeline_desc = ComputePipelineDescriptor {
             label: "marching-cubes-generate",
             comp: Some(assets_server.load("marching-cubes/marching_cube.comp.wgsl")),
-            bind_group_layouts: vec![desc_gpu_layout.clone(), points_gpu_layout.clone(), vertices_gpu_layout.clone()],
+            bind_group_layouts: vec![desc_gpu_layout.clone(), points_gpu_layout.clone(), vertices_gpu_layout.clone(), triangles_table_layout.clone()],
             ..Default::default()
         };
         let cached_index = pipeline_manager.create_compute_pipeline(pipeline_desc);
@@ -62,8 +67,10 @@ impl RenderAsset for GpuMCComputePipelineGenerate {
             desc_gpu_layout: Some(desc_gpu_layout),
             points_gpu_layout: Some(points_gpu_layout),
             triangles_gpu_layout: Some(vertices_gpu_layout),
+            triangles_table_layout: Some(triangles_table_layout),
             desc_gpu_group: None,
-            triangles_gpu_group: None
+            triangles_gpu_group: None,
+            triangles_table_group: None
         })
     }
 
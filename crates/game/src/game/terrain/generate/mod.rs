@@ -17,8 +17,9 @@ impl Plugin for MCGeneratePlugin {
         // Compute pass
         app.get_sub_app_mut(RenderApp).unwrap()
             .add_systems(Render, (
+                MCComputeCorePoints::refresh_iso_level.in_set(RenderSet::Prepare),
                 MCComputeCorePoints::create_bind_groups.in_set(RenderSet::BindGroups),
-                MCComputeCorePoints::compute.in_set(RenderSet::Process),
+                (MCComputeCorePoints::copy_back, MCComputeCorePoints::compute).chain().in_set(RenderSet::Process),
             ));
     }
 
@@ -1,6 +1,6 @@
-use bevy::{prelude::*, utils::HashMap};
+use bevy::{prelude::*, utils::{HashMap, HashSet}};
 use wde_render::assets::Buffer;
-use wde_wgpu::bind_group::WgpuBindGroup;
+use wde_wgpu::{bind_group::WgpuBindGroup, buffer::WPendingBufferRead};
 
 
 // =========== CHUNK INDEX ===========
@@ -32,8 +32,8 @@ pub struct MCChunkDescription {
 /** List of all chunks. */
 #[derive(Resource, Default)]
 pub struct MCChunksListMain {
-    /** List of all currently alive chunks. */
-    pub current_chunks: HashMap<MCChunkIndex, MCChunkIndex>,
+    /** Grid positions of all currently alive chunks. */
+    pub occupied: HashSet<MCChunkIndex>,
     /** List of new chunks to spawn. */
     pub new_chunks: Vec<(MCChunkIndex, MCChunkDescription)>,
     /** List of old chunks to delete. */
@@ -48,7 +48,8 @@ pub struct MCChunksListRender {
 
 
 // =========== MARCHING CUBES CHUNK STATES IN RENDER THREAD ============
-// ============ Registered => Loading => Pending => Active =============
+// ===== Registered => Loading => CopyingBack => Pending => Active =====
+// ========= Each chunk advances at most one state per frame ==========
 
 /** Chunk waiting to generate its points. */
 #[derive(Component, Default)]
@@ -82,6 +83,31 @@ impl Clone for MCLoadingChunk {
     }
 }
 
+/** Reading back the triangles generated for a chunk, over several frames without blocking. */
+pub enum MCCopyBackStage {
+    /** Reading the number of generated triangles. */
+    Counter(WPendingBufferRead),
+    /** Reading the generated triangles. */
+    Triangles {
+        triangles_counter: u32,
+        read: WPendingBufferRead
+    }
+}
+
+/** Chunk waiting for its generated triangles to be copied back to the CPU. */
+#[derive(Component)]
+pub struct MCCopyingBackChunk {
+    pub index: MCChunkIndex,
+    pub stage: MCCopyBackStage,
+    /** The index of the readback buffers of the chunk in the compute handler, used until the chunk is copied back. */
+    pub slot: usize,
+    /** Whether the chunk must be generated again, as its description changed during the copy back. */
+    pub dirty: bool,
+
+    // List of points
+    pub points_gpu: Handle<Buffer>
+}
+
 /** Chunk waiting for the mesh and physics collision generation. */
 #[derive(Component)]
 pub struct MCPendingChunk {
@@ -101,10 +127,15 @@ pub struct MCPendingChunk {
 pub struct MCActiveChunk {
     pub index: MCChunkIndex,
 
-    // Vertices and indices buffers
-    pub vertices: Handle<Buffer>,
-    pub indices: Handle<Buffer>,
+    // Vertices and indices buffers, used when rendering with smooth normals
+    pub vertices: Option<Handle<Buffer>>,
+    pub indices: Option<Handle<Buffer>>,
     pub indices_counter: u32,
+
+    // Raw triangles buffer, used when rendering with flat normals
+    pub triangles: Option<Handle<Buffer>>,
+    pub triangles_counter: u32,
+    pub triangles_group: Option<WgpuBindGroup>,
     
     // List of points
     pub points_gpu: Handle<Buffer>
@@ -1,8 +1,8 @@
 use bevy::prelude::*;
-use wde_render::{assets::Buffer, core::{extract_macros::ExtractWorld, DeviceLimits}};
-use wde_wgpu::buffer::BufferUsage;
+use wde_render::{assets::{Buffer, Texture}, core::{extract_macros::ExtractWorld, DeviceLimits}};
+use wde_wgpu::{buffer::BufferUsage, texture::{WTextureFormat, WTextureUsages}};
 
-use super::{MC_MAX_POINTS, MC_MAX_TRIANGLES};
+use super::{mc_table::{mc_triangles_table_texture_data, MC_TABLE_CASES, MC_TABLE_TRIANGLES_PER_CASE}, MC_MAX_CHUNKS_PROCESS_PER_FRAME, MC_MAX_POINTS, MC_MAX_TRIANGLES};
 
 /**
  * Description of the noise to generate the terrain.
@@ -46,23 +46,27 @@ pub struct GpuMCDescription {
 
 #[derive(Resource, Default)]
 pub struct MCComputeHandler {
-    // Buffers
-    pub desc_cpu: Option<Handle<Buffer>>,
+    // Buffers, with a description and triangles readback buffer per chunk copied back at the same time
+    pub desc_cpu: Vec<Handle<Buffer>>,
     pub desc_gpu: Option<Handle<Buffer>>,
     pub points_cpu: Option<Handle<Buffer>>,
-    pub triangles_cpu: Option<Handle<Buffer>>,
+    pub triangles_cpu: Vec<Handle<Buffer>>,
     pub triangles_gpu: Option<Handle<Buffer>>,
-    pub noise_parameters: Option<Handle<Buffer>>
+    pub noise_parameters: Option<Handle<Buffer>>,
+    // Textures
+    pub triangles_table: Option<Handle<Texture>>
 }
 #[derive(Resource, Default)]
 pub struct MCComputeHandlerGPU {
-    // Buffers
-    pub desc_cpu: Option<Handle<Buffer>>,
+    // Buffers, with a description and triangles readback buffer per chunk copied back at the same time
+    pub desc_cpu: Vec<Handle<Buffer>>,
     pub desc_gpu: Option<Handle<Buffer>>,
     pub points_cpu: Option<Handle<Buffer>>,
-    pub triangles_cpu: Option<Handle<Buffer>>,
+    pub triangles_cpu: Vec<Handle<Buffer>>,
     pub triangles_gpu: Option<Handle<Buffer>>,
-    pub noise_parameters: Option<Handle<Buffer>>
+    pub noise_parameters: Option<Handle<Buffer>>,
+    // Textures
+    pub triangles_table: Option<Handle<Texture>>
 }
 impl MCComputeHandler {
     /** Creates the asset handler buffers and instance (runs once). */
@@ -73,12 +77,12 @@ impl MCComputeHandler {
     ) {
         // Create the buffers
         let max_buffer_size = device_limits.0.max_storage_buffer_binding_size as usize;
-        let desc_cpu = Buffer {
-            label: "marching-cubes-desc-cpu".to_string(),
+        let desc_cpu = (0..MC_MAX_CHUNKS_PROCESS_PER_FRAME).map(|i| asset_server.add(Buffer {
+            label: format!("marching-cubes-desc-cpu-{}", i),
             size: std::mem::size_of::<GpuMCDescription>(),
             usage: BufferUsage::MAP_READ | BufferUsage::COPY_DST,
             content: None
-        };
+        })).collect();
         let desc_gpu = Buffer {
             label: "marching-cubes-desc-gpu".to_string(),
             size: std::mem::size_of::<GpuMCDescription>(),
@@ -91,12 +95,12 @@ impl MCComputeHandler {
             usage: BufferUsage::MAP_READ | BufferUsage::COPY_DST,
             content: None
         };
-        let triangles_cpu = Buffer {
-            label: "marching-cubes-triangles-cpu".to_string(),
+        let triangles_cpu = (0..MC_MAX_CHUNKS_PROCESS_PER_FRAME).map(|i| asset_server.add(Buffer {
+            label: format!("marching-cubes-triangles-cpu-{}", i),
             size: std::cmp::min(std::mem::size_of::<[f32; 12]>() * MC_MAX_TRIANGLES as usize, max_buffer_size),
             usage: BufferUsage::MAP_READ | BufferUsage::COPY_DST,
             content: None
-        };
+        })).collect();
         let triangles_gpu = Buffer {
             label: "marching-cubes-triangles-gpu".to_string(),
             size: std::cmp::min(std::mem::size_of::<[f32; 12]>() * MC_MAX_TRIANGLES as usize, max_buffer_size),
@@ -110,14 +114,25 @@ impl MCComputeHandler {
             content: None
         };
 
+        // Create the triangulation table texture
+        let triangles_table = Texture {
+            label: "marching-cubes-triangles-table".to_string(),
+            size: (MC_TABLE_TRIANGLES_PER_CASE, MC_TABLE_CASES),
+            format: WTextureFormat::R32Uint,
+            usages: WTextureUsages::TEXTURE_BINDING,
+            data: mc_triangles_table_texture_data(),
+            ..Default::default()
+        };
+
         // Create the handler
         commands.insert_resource(MCComputeHandler {
-            desc_cpu: Some(asset_server.add(desc_cpu)),
+            desc_cpu,
             desc_gpu: Some(asset_server.add(desc_gpu)),
             points_cpu: Some(asset_server.add(points_cpu)),
-            triangles_cpu: Some(asset_server.add(triangles_cpu)),
+            triangles_cpu,
             triangles_gpu: Some(asset_server.add(triangles_gpu)),
-            noise_parameters: Some(asset_server.add(noise_parameters))
+            noise_parameters: Some(asset_server.add(noise_parameters)),
+            triangles_table: Some(asset_server.add(triangles_table))
         });
     }
 
@@ -128,13 +143,14 @@ impl MCComputeHandler {
         mut handler_render: ResMut<MCComputeHandlerGPU>,
     ) {
         // Extract the buffers if they are not already extracted
-        if handler_render.desc_cpu.is_none() && handler_update.desc_cpu.is_some() {
+        if handler_render.desc_gpu.is_none() && handler_update.desc_gpu.is_some() {
             handler_render.desc_cpu = handler_update.desc_cpu.clone();
             handler_render.desc_gpu = handler_update.desc_gpu.clone();
             handler_render.points_cpu = handler_update.points_cpu.clone();
             handler_render.triangles_cpu = handler_update.triangles_cpu.clone();
             handler_render.triangles_gpu = handler_update.triangles_gpu.clone();
             handler_render.noise_parameters = handler_update.noise_parameters.clone();
+            handler_render.triangles_table = handler_update.triangles_table.clone();
         }
     }
 }
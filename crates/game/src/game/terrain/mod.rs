@@ -1,14 +1,15 @@
 use bevy::prelude::*;
 use generate::MCGeneratePlugin;
-use mc_chunk::MCChunkDescription;
+use mc_chunk::{MCChunkDescription, MCChunkIndex};
 use mc_compute_main::{MCComputeHandler, MCComputeHandlerGPU};
 use process::MCProcessPlugin;
 use render::MCRenderPlugin;
 use spawn::MCSpawnPlugin;
-use wde_render::core::{Extract, RenderApp};
+use wde_render::core::{extract_macros::{ExtractChangedResourcePlugin, RenderEventPlugin}, Extract, RenderApp};
 
 mod mc_chunk;
 mod mc_compute_main;
+mod mc_table;
 mod spawn;
 mod generate;
 mod process;
@@ -32,24 +33,61 @@ pub const MC_MAX_TRIANGLES: u32 = 20_000;
 pub struct TerrainSpawner {
     /** The number of chunks to spawn in each direction (in a circle). */
     pub chunk_radius_count: i32,
+    /** The number of chunks in each direction beyond which the chunks are despawned (in a circle).
+     * It is larger than the spawn radius, so that the chunks at the border are not despawned and spawned again when the spawner moves back and forth. */
+    pub chunk_despawn_radius_count: i32,
     /** The real length of the chunk in each axis. */
     pub chunk_length: [f32; 3],
     /** The number of sub chunks to spawn in each chunk. */
-    pub chunk_sub_count: [u32; 3],
-    /** The iso level for the terrain generation. */
-    pub iso_level: f32
+    pub chunk_sub_count: [u32; 3]
 }
 impl Default for TerrainSpawner {
     fn default() -> Self {
         TerrainSpawner {
             chunk_radius_count: 6,
+            chunk_despawn_radius_count: 8,
             chunk_length: [200.0, 200.0, 200.0],
-            chunk_sub_count: MC_MAX_SUB_COUNT,
-            iso_level: 0.0
+            chunk_sub_count: MC_MAX_SUB_COUNT
         }
     }
 }
 
+/**
+ * The iso level of the terrain surface, the noise values below it are inside the terrain.
+ * When it changes, the already generated chunks are regenerated with the new iso level.
+ */
+#[derive(Resource, Clone, Copy, Default, Reflect)]
+#[reflect(Resource)]
+pub struct TerrainIsoLevel(pub f32);
+
+/**
+ * Settings of the rendering of the terrain chunks.
+ * The settings are applied to the chunks processed after they are changed.
+ */
+#[derive(Resource, Clone, Default, Reflect)]
+#[reflect(Resource)]
+pub struct TerrainRenderSettings {
+    /** If true, the chunks are rendered with smooth normals precomputed per vertex.
+     * Otherwise, the chunks are rendered with flat normals per triangle unpacked in the vertex shader (faster). */
+    pub smooth_normals: bool
+}
+
+/** A triangle of a terrain chunk, given by the world space positions of its three vertices. */
+pub type Triangle = [[f32; 3]; 3];
+
+/**
+ * Event sent in the main world when the triangles of a terrain chunk were read back and processed.
+ * It is sent again each time the chunk is regenerated, for instance when the iso level changes.
+ */
+#[derive(Event, Debug, Clone)]
+#[allow(dead_code)]
+pub struct MCChunkReadyEvent {
+    /** Index of the chunk. */
+    pub index: MCChunkIndex,
+    /** Triangles of the chunk. */
+    pub triangles: Vec<Triangle>
+}
+
 pub struct TerrainPlugin;
 impl Plugin for TerrainPlugin {
     fn build(&self, app: &mut App) {
@@ -63,14 +101,24 @@ impl Plugin for TerrainPlugin {
         // Add the compute main plugin
         app
             .init_resource::<MCComputeHandler>()
+            .init_resource::<TerrainRenderSettings>()
+            .init_resource::<TerrainIsoLevel>()
             .add_systems(Startup, MCComputeHandler::init);
         app.get_sub_app_mut(RenderApp).unwrap()
             .init_resource::<MCComputeHandlerGPU>()
+            .init_resource::<TerrainRenderSettings>()
+            .init_resource::<TerrainIsoLevel>()
             .add_systems(Extract, MCComputeHandler::extract);
+        app
+            .add_plugins(ExtractChangedResourcePlugin::<TerrainRenderSettings>::default())
+            .add_plugins(ExtractChangedResourcePlugin::<TerrainIsoLevel>::default())
+            .add_plugins(RenderEventPlugin::<MCChunkReadyEvent>::default());
 
         // Register the components to the reflect system
         app
             .register_type::<MCChunkDescription>()
-            .register_type::<TerrainSpawner>();
+            .register_type::<TerrainSpawner>()
+            .register_type::<TerrainRenderSettings>()
+            .register_type::<TerrainIsoLevel>();
     }
 }
@@ -1,5 +1,5 @@
 use bevy::prelude::*;
-use process_chunks::MCProcessTaskManager;
+use process_chunks::{MCPendingTasks, MCProcessTaskManager};
 use wde_render::core::{Render, RenderApp, RenderSet};
 
 mod process_chunks;
@@ -8,6 +8,7 @@ pub struct MCProcessPlugin;
 impl Plugin for MCProcessPlugin {
     fn build(&self, app: &mut App) {
         app.get_sub_app_mut(RenderApp).unwrap()
+            .init_resource::<MCPendingTasks>()
             .add_systems(Render, (
                 MCProcessTaskManager::process_chunks,
                 MCProcessTaskManager::handle_tasks
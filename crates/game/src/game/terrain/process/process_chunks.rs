@@ -1,10 +1,10 @@
 use std::hash::Hash;
 
 use bevy::{ecs::world::CommandQueue, prelude::*, tasks::{block_on, futures_lite::future, AsyncComputeTaskPool, Task}, utils::HashMap};
-use wde_render::assets::Buffer;
+use wde_render::{assets::Buffer, core::extract_macros::RenderEvents};
 use wde_wgpu::{buffer::BufferUsage, vertex::WVertex};
 
-use crate::terrain::{mc_chunk::{MCActiveChunk, MCChunksListRender, MCPendingChunk}, MC_MAX_CHUNKS_PROCESS_PER_FRAME};
+use crate::terrain::{mc_chunk::{MCActiveChunk, MCChunksListRender, MCPendingChunk}, MCChunkReadyEvent, TerrainRenderSettings, Triangle, MC_MAX_CHUNKS_PROCESS_PER_FRAME};
 
 #[derive(Clone, Copy)]
 struct Vec3C { x: f32, y: f32, z: f32 }
@@ -28,14 +28,23 @@ impl PartialEq for Vec3C {
 impl Eq for Vec3C {}
 
 
-#[derive(Component)]
-pub struct MCProcessTaskManager(pub Task<CommandQueue>);
+/**
+ * Tasks processing the chunks on the async compute task pool.
+ * Each task returns the commands adding the processed chunk to the render world.
+ */
+#[derive(Resource, Default)]
+pub struct MCPendingTasks(pub Vec<Task<CommandQueue>>);
+
+pub struct MCProcessTaskManager;
 impl MCProcessTaskManager {
     /**
      * Process the chunks to generate the mesh data.
+     * At most `MC_MAX_CHUNKS_PROCESS_PER_FRAME` chunks are processed per frame, the other ones stay pending.
      */
     pub fn process_chunks(
         pending_chunks: Query<(Entity, &MCPendingChunk)>,
+        settings: Res<TerrainRenderSettings>,
+        mut pending_tasks: ResMut<MCPendingTasks>,
         mut commands: Commands
     ) {
         // If there are no chunks to process, return
@@ -46,7 +55,7 @@ impl MCProcessTaskManager {
         // Process the chunks
         let thread_pool = AsyncComputeTaskPool::get();
         let mut chunks = vec![];
-        for (entity, chunk) in pending_chunks.iter() {
+        for (entity, chunk) in pending_chunks.iter().take(MC_MAX_CHUNKS_PROCESS_PER_FRAME) {
             // Push the chunk to the list
             chunks.push(MCPendingChunk {
                 index: chunk.index,
@@ -60,51 +69,70 @@ impl MCProcessTaskManager {
         }
 
         // Process the chunks
-        let mut process_count = 0;
+        let smooth_normals = settings.smooth_normals;
         for chunk in chunks {
-            process_count += 1;
-            if process_count >= MC_MAX_CHUNKS_PROCESS_PER_FRAME {
-                break;
-            }
-            let task_entity = commands.spawn_empty().id();
-
             // Spawn a new task to process the chunk
             let task = thread_pool.spawn(async move {
                 // Mesh data
                 let mut vertices = Vec::new();
                 let mut indices = Vec::new();
-
-                // Extract the unique vertices and indices from the raw data
-                let mut vertices_map = HashMap::new();
-                let mut indices_counter = 0;
-                for i in 0..chunk.triangles_counter as usize {
-                    let triangle_normal = Vec3C::new(
-                        chunk.raw_triangles[12*i + 3],
-                        chunk.raw_triangles[12*i + 7],
-                        chunk.raw_triangles[12*i + 11],
-                    );
-                    for j in 0..3 {
-                        let vertex = Vec3C::new(
-                            chunk.raw_triangles[12*i + j * 4],
-                            chunk.raw_triangles[12*i + j * 4 + 1],
-                            chunk.raw_triangles[12*i + j * 4 + 2],
+                let mut triangles_buffer = None;
+
+                if smooth_normals {
+                    // Extract the unique vertices and indices from the raw data
+                    let mut vertices_map = HashMap::new();
+                    let mut indices_counter = 0;
+                    for i in 0..chunk.triangles_counter as usize {
+                        let triangle_normal = Vec3C::new(
+                            chunk.raw_triangles[12*i + 3],
+                            chunk.raw_triangles[12*i + 7],
+                            chunk.raw_triangles[12*i + 11],
                         );
+                        for j in 0..3 {
+                            let vertex = Vec3C::new(
+                                chunk.raw_triangles[12*i + j * 4],
+                                chunk.raw_triangles[12*i + j * 4 + 1],
+                                chunk.raw_triangles[12*i + j * 4 + 2],
+                            );
+
+                            // Add the vertex to the mesh data
+                            let vertex_index = match vertices_map.get(&vertex) {
+                                Some(vertex_index) => *vertex_index,
+                                None => {
+                                    vertices.push(WVertex {
+                                        position: [vertex.x, vertex.y, vertex.z],
+                                        normal: [0.0, 0.0, 0.0],
+                                        uv: [0.0, 0.0],
+                                        uv1: [0.0, 0.0],
+                                        tangent: [0.0; 4],
+                                    });
+                                    vertices_map.insert(vertex, indices_counter);
+                                    indices_counter += 1;
+                                    indices_counter - 1
+                                }
+                            };
+                            indices.push(vertex_index);
+
+                            // Accumulate the normals of the triangles sharing the vertex
+                            let normal = &mut vertices[vertex_index as usize].normal;
+                            normal[0] += triangle_normal.x;
+                            normal[1] += triangle_normal.y;
+                            normal[2] += triangle_normal.z;
+                        }
+                    }
 
-                        // Add the vertex to the mesh data
-                        indices.push(match vertices_map.get(&vertex) {
-                            Some(vertex_index) => *vertex_index,
-                            None => {
-                                vertices.push(WVertex {
-                                    position: [vertex.x, vertex.y, vertex.z],
-                                    normal: [triangle_normal.x, triangle_normal.y, triangle_normal.z],
-                                    uv: [0.0, 0.0],
-                                });
-                                vertices_map.insert(vertex, indices_counter);
-                                indices_counter += 1;
-                                indices_counter - 1
-                            }
-                        });
+                    // Compute the smooth normals
+                    for vertex in vertices.iter_mut() {
+                        vertex.normal = Vec3::from(vertex.normal).normalize_or_zero().to_array();
                     }
+                } else {
+                    // Keep the raw triangles, unpacked in the vertex shader
+                    triangles_buffer = Some(Buffer {
+                        label: "".to_string(),
+                        size: chunk.raw_triangles.len() * std::mem::size_of::<f32>(),
+                        usage: BufferUsage::STORAGE,
+                        content: Some(bytemuck::cast_slice(&chunk.raw_triangles).to_vec()),
+                    });
                 }
 
                 // Create the buffers
@@ -121,16 +149,26 @@ impl MCProcessTaskManager {
                     content: Some(bytemuck::cast_slice(&indices).to_vec()),
                 };
 
+                // Keep only the positions of the triangles for the chunk ready event
+                let triangles: Vec<Triangle> = chunk.raw_triangles.chunks_exact(12)
+                    .take(chunk.triangles_counter as usize)
+                    .map(|triangle| [
+                        [triangle[0], triangle[1], triangle[2]],
+                        [triangle[4], triangle[5], triangle[6]],
+                        [triangle[8], triangle[9], triangle[10]]
+                    ])
+                    .collect();
+
                 // Return the mesh data
                 let mut command_queue = CommandQueue::default();
                 command_queue.push(move |world: &mut World| {
-                    debug!("Registering chunk {:?} mesh data on the render thread with {} vertices and {} indices.", chunk.index, vertices.len(), indices.len());
+                    debug!("Registering chunk {:?} mesh data on the render thread with {} vertices, {} indices and {} raw triangles.",
+                        chunk.index, vertices.len(), indices.len(), if triangles_buffer.is_some() { chunk.triangles_counter } else { 0 });
 
                     // Get the chunk description
                     let desc = match world.get_resource::<MCChunksListRender>().unwrap().chunks.get(&chunk.index) {
                         Some(desc) => desc.clone(),
                         None => {
-                            world.commands().entity(task_entity).despawn();
                             error!("The chunk description for chunk {:?} is missing. The chunk was probably removed during a parallel task.", chunk.index);
                             return;
                         }
@@ -140,42 +178,67 @@ impl MCProcessTaskManager {
 
                     // Insert the mesh data
                     let asset_server = world.get_resource_mut::<AssetServer>().unwrap();
-                    let active_chunk = MCActiveChunk {
-                        index: chunk.index,
-                        vertices: asset_server.add(vertices_buffer),
-                        indices: asset_server.add(indices_buffer),
-                        indices_counter: indices.len() as u32,
-                        points_gpu: chunk.points_gpu,
+                    let active_chunk = match triangles_buffer {
+                        Some(mut triangles_buffer) => {
+                            triangles_buffer.label = format!("marching-cubes-triangles-{:?}", desc.index);
+                            MCActiveChunk {
+                                index: chunk.index,
+                                vertices: None,
+                                indices: None,
+                                indices_counter: 0,
+                                triangles: Some(asset_server.add(triangles_buffer)),
+                                triangles_counter: chunk.triangles_counter,
+                                triangles_group: None,
+                                points_gpu: chunk.points_gpu,
+                            }
+                        },
+                        None => MCActiveChunk {
+                            index: chunk.index,
+                            vertices: Some(asset_server.add(vertices_buffer)),
+                            indices: Some(asset_server.add(indices_buffer)),
+                            indices_counter: indices.len() as u32,
+                            triangles: None,
+                            triangles_counter: 0,
+                            triangles_group: None,
+                            points_gpu: chunk.points_gpu,
+                        }
                     };
+                    // Replace the previous version of the chunk, if it was regenerated
+                    let mut active_chunks = world.query::<(Entity, &MCActiveChunk)>();
+                    let previous: Vec<Entity> = active_chunks.iter(world)
+                        .filter(|(_, active)| active.index == chunk.index)
+                        .map(|(entity, _)| entity).collect();
+                    for entity in previous {
+                        world.commands().entity(entity).despawn();
+                    }
                     world.commands().spawn((active_chunk, desc));
-                    world.commands().entity(task_entity).despawn();
+
+                    // Notify the main world that the chunk is ready
+                    world.resource_mut::<RenderEvents<MCChunkReadyEvent>>().send(MCChunkReadyEvent {
+                        index: chunk.index,
+                        triangles
+                    });
                 });
                 command_queue
             });
 
-            // Spawn the task entity
-            commands.entity(task_entity).insert(MCProcessTaskManager(task));
+            pending_tasks.0.push(task);
         }
     }
 
 
 
     /**
-     * Check if the tasks are done.
-     * If so, run the command queue that will add the buffers and the chunk to the loading chunks.
-     * If the task is done, despawn the entity.
+     * Poll the tasks without blocking.
+     * The command queues of the finished tasks are applied to add the processed chunks to the active chunks.
      */
-    pub fn handle_tasks(mut commands: Commands, mut tasks: Query<&mut MCProcessTaskManager>) {
-        let mut process_count = 0;
-        for mut task in &mut tasks {
-            process_count += 1;
-            if process_count >= MC_MAX_CHUNKS_PROCESS_PER_FRAME {
-                break;
-            }
-            if let Some(mut commands_queue) = block_on(future::poll_once(&mut task.0)) {
-                // Add the commands to the main thread for the next frame
+    pub fn handle_tasks(mut commands: Commands, mut pending_tasks: ResMut<MCPendingTasks>) {
+        pending_tasks.0.retain_mut(|task| match block_on(future::poll_once(task)) {
+            Some(mut commands_queue) => {
                 commands.append(&mut commands_queue);
-            }
-        }
+                false
+            },
+            None => true
+        });
     }
 }
@@ -1,7 +1,7 @@
 use bevy::prelude::*;
 use render_core::MCRenderPass;
 use render_pipeline::{GpuMCRenderPipeline, MCRenderPipeline, MCRenderPipelineAsset};
-use wde_render::{assets::RenderAssetsPlugin, core::RenderApp, passes::render_graph::RenderGraph};
+use wde_render::{assets::RenderAssetsPlugin, core::{Render, RenderApp, RenderSet}, passes::render_graph::RenderGraph};
 
 mod render_core;
 mod render_pipeline;
@@ -14,10 +14,14 @@ impl Plugin for MCRenderPlugin {
             .init_asset::<MCRenderPipelineAsset>()
             .add_plugins(RenderAssetsPlugin::<GpuMCRenderPipeline>::default());
 
+        // Create the bind groups of the chunks
+        app.get_sub_app_mut(RenderApp).unwrap()
+            .add_systems(Render, MCRenderPass::create_bind_groups.in_set(RenderSet::BindGroups));
+
         // Render pass
         let mut render_graph = app.get_sub_app_mut(RenderApp).unwrap()
             .world_mut().get_resource_mut::<RenderGraph>().unwrap();
-        render_graph.add_pass::<MCRenderPass>(100);
+        render_graph.add_pass::<MCRenderPass>(8);
     }
 
     fn finish(&self, app: &mut App) {
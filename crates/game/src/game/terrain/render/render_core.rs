@@ -1,13 +1,46 @@
 use bevy::prelude::*;
-use wde_render::{assets::{GpuBuffer, GpuTexture, RenderAssets}, core::SwapchainFrame, features::{CameraFeatureRender, LightsFeatureBuffer}, passes::{depth::DepthTexture, render_graph::RenderPass}, pipelines::{CachedPipelineStatus, PipelineManager}};
-use wde_wgpu::{command_buffer::{RenderPassBuilder, RenderPassColorAttachment, RenderPassDepth, WCommandBuffer, WLoadOp}, instance::WRenderInstance};
+use wde_render::{assets::{GpuBuffer, GpuTexture, RenderAssets}, features::{CameraFeatureRender, LightsFeatureBuffer}, passes::{depth::DepthTexture, hdr::HdrTexture, render_graph::RenderPass}, pipelines::{CachedPipelineStatus, PipelineManager}};
+use wde_wgpu::{bind_group::BindGroup, command_buffer::{RenderPassBuilder, RenderPassColorAttachment, RenderPassDepth, WCommandBuffer, WLoadOp}, instance::WRenderInstance};
 
 use crate::terrain::mc_chunk::MCActiveChunk;
 
 use super::render_pipeline::GpuMCRenderPipeline;
 
+/// Renders the active chunks into the hdr color texture, after the lighting and reflections of the pbr entities
+/// and before the transparent entities, so that the fog, bloom and tone mapping are applied to the terrain.
 #[derive(Default)]
 pub struct MCRenderPass;
+impl MCRenderPass {
+    /** Create the triangles bind groups of the chunks rendered with flat normals if they are not already created. */
+    pub fn create_bind_groups(
+        buffers: Res<RenderAssets<GpuBuffer>>, render_instance: Res<WRenderInstance<'static>>,
+        pipeline: Res<RenderAssets<GpuMCRenderPipeline>>, mut active_chunks: Query<&mut MCActiveChunk>
+    ) {
+        // Get the render pipeline
+        let render_pipeline = match pipeline.iter().next() {
+            Some((_, pipeline)) => pipeline,
+            None => return
+        };
+
+        let render_instance = render_instance.data.read().unwrap();
+        for mut chunk in active_chunks.iter_mut() {
+            if chunk.triangles_group.is_some() {
+                continue;
+            }
+
+            // Get the buffer
+            let triangles = match chunk.triangles.as_ref().and_then(|triangles| buffers.get(triangles)) {
+                Some(triangles) => triangles,
+                None => continue
+            };
+
+            // Create the bind group
+            chunk.triangles_group = Some(BindGroup::build(
+                "marching-cubes-render-triangles", &render_instance, &render_pipeline.triangles_layout.build(&render_instance),
+                &vec![BindGroup::buffer(0, &triangles.buffer)]));
+        }
+    }
+}
 impl RenderPass for MCRenderPass {
     fn render(&self, render_world: &mut World) {
         // Get the active chunks
@@ -16,7 +49,7 @@ impl RenderPass for MCRenderPass {
             return;
         }
 
-        // Get the render instance and swapchain frame
+        // Get the render instance
         let render_instance = render_world.get_resource::<WRenderInstance<'static>>().unwrap();
         let render_instance = render_instance.data.read().unwrap();
 
@@ -38,11 +71,13 @@ impl RenderPass for MCRenderPass {
             None => return
         };
 
-        // Test if swapchain frame and depth texture have the same size
-        let swapchain_frame = render_world.get_resource::<SwapchainFrame>().unwrap();
-        let swapchain_frame = swapchain_frame.data.as_ref().unwrap();
-        if swapchain_frame.texture.texture.size().width != depth_texture.texture.size.0 || swapchain_frame.texture.texture.size().height != depth_texture.texture.size.1 {
-            warn!("Swapchain frame and depth texture have different sizes: {:?} vs {:?}.", swapchain_frame.texture.texture.size(), depth_texture.texture.size);
+        // Test if hdr and depth textures have the same size
+        let hdr = match render_world.get_resource::<HdrTexture>().and_then(|hdr_texture| textures.get(&hdr_texture.texture)) {
+            Some(hdr) => hdr,
+            None => return
+        };
+        if hdr.texture.size != depth_texture.texture.size {
+            warn!("Hdr and depth textures have different sizes: {:?} vs {:?}.", hdr.texture.size, depth_texture.texture.size);
             return;
         }
         
@@ -51,7 +86,7 @@ impl RenderPass for MCRenderPass {
         {
             let mut render_pass = command_buffer.create_render_pass("marching-cubes", |builder: &mut RenderPassBuilder| {
                 builder.add_color_attachment(RenderPassColorAttachment {
-                    texture: Some(&swapchain_frame.view),
+                    texture: Some(&hdr.texture.view),
                     load: WLoadOp::Load,
                     ..Default::default()
                 });
@@ -65,11 +100,9 @@ impl RenderPass for MCRenderPass {
             // Render the mesh
             let pipeline_manager = render_world.get_resource::<PipelineManager>().unwrap();
             if let (
-                CachedPipelineStatus::OkRender(pipeline),
                 Some(camera_bg),
                 Some(lights_bg)
             ) = (
-                pipeline_manager.get_pipeline(mcbuffer_pipeline.cached_pipeline_index),
                 &render_world.get_resource::<CameraFeatureRender>().unwrap().bind_group,
                 &render_world.get_resource::<LightsFeatureBuffer>().unwrap().bind_group
             ) {
@@ -77,33 +110,60 @@ impl RenderPass for MCRenderPass {
                 render_pass.set_bind_group(0, camera_bg);
                 render_pass.set_bind_group(1, lights_bg);
 
-                // Set the pipeline
-                if render_pass.set_pipeline(pipeline).is_ok() {
-                    let buffers = render_world.get_resource::<RenderAssets<GpuBuffer>>().unwrap();
-                    for chunk in active_chunks.iter(render_world) {
-                        // Get the vertex and index buffers
-                        let (vertex_buffer, index_buffer) = match (
-                            buffers.get(&chunk.vertices),
-                            buffers.get(&chunk.indices)
-                        ) {
-                            (Some(vertex_buffer), Some(index_buffer)) => (vertex_buffer, index_buffer),
-                            _ => continue
-                        };
-                        
-                        // Set the mesh buffers
-                        render_pass.set_vertex_buffer(0, &vertex_buffer.buffer);
-                        render_pass.set_index_buffer(&index_buffer.buffer);
-
-                        // Draw the mesh
-                        match render_pass.draw_indexed(0..chunk.indices_counter, 0..1) {
-                            Ok(_) => {},
-                            Err(e) => {
-                                error!("Failed to draw: {:?}.", e);
-                            }
-                        };
+                // Render the chunks with smooth normals
+                if let CachedPipelineStatus::OkRender(pipeline) = pipeline_manager.get_pipeline(mcbuffer_pipeline.cached_pipeline_index) {
+                    if render_pass.set_pipeline(pipeline).is_ok() {
+                        let buffers = render_world.get_resource::<RenderAssets<GpuBuffer>>().unwrap();
+                        for chunk in active_chunks.iter(render_world) {
+                            // Get the vertex and index buffers
+                            let (vertex_buffer, index_buffer) = match (
+                                chunk.vertices.as_ref().and_then(|vertices| buffers.get(vertices)),
+                                chunk.indices.as_ref().and_then(|indices| buffers.get(indices))
+                            ) {
+                                (Some(vertex_buffer), Some(index_buffer)) => (vertex_buffer, index_buffer),
+                                _ => continue
+                            };
+                            
+                            // Set the mesh buffers
+                            render_pass.set_vertex_buffer(0, &vertex_buffer.buffer);
+                            render_pass.set_index_buffer(&index_buffer.buffer);
+
+                            // Draw the mesh
+                            match render_pass.draw_indexed(0..chunk.indices_counter, 0..1) {
+                                Ok(_) => {},
+                                Err(e) => {
+                                    error!("Failed to draw: {:?}.", e);
+                                }
+                            };
+                        }
+                    } else {
+                        error!("Failed to set pipeline.");
+                    }
+                }
+
+                // Render the chunks with flat normals, unpacking the vertices from the raw triangles
+                if let CachedPipelineStatus::OkRender(pipeline) = pipeline_manager.get_pipeline(mcbuffer_pipeline.flat_cached_pipeline_index) {
+                    if render_pass.set_pipeline(pipeline).is_ok() {
+                        for chunk in active_chunks.iter(render_world) {
+                            let triangles_group = match &chunk.triangles_group {
+                                Some(triangles_group) => triangles_group,
+                                None => continue
+                            };
+
+                            // Set the triangles
+                            render_pass.set_bind_group(2, triangles_group);
+
+                            // Draw the triangles
+                            match render_pass.draw(0..chunk.triangles_counter * 3, 0..1) {
+                                Ok(_) => {},
+                                Err(e) => {
+                                    error!("Failed to draw: {:?}.", e);
+                                }
+                            };
+                        }
+                    } else {
+                        error!("Failed to set pipeline.");
                     }
-                } else {
-                    error!("Failed to set pipeline.");
                 }
             }
         }
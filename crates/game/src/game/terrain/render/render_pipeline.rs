@@ -1,6 +1,6 @@
 use bevy::{ecs::system::lifetimeless::{SRes, SResMut}, prelude::*};
-use wde_render::{assets::{PrepareAssetError, RenderAsset}, features::{CameraFeatureRender, LightsFeatureBuffer}, pipelines::{CachedPipelineIndex, PipelineManager, RenderPipelineDescriptor}};
-use wde_wgpu::render_pipeline::WDepthStencilDescriptor;
+use wde_render::{assets::{PrepareAssetError, RenderAsset}, features::{CameraFeatureRender, LightsFeatureBuffer}, passes::hdr::HDR_FORMAT, pipelines::{CachedPipelineIndex, PipelineManager, RenderPipelineDescriptor}};
+use wde_wgpu::{bind_group::BindGroupLayout, buffer::BufferBindingType, render_pipeline::{WDepthStencilDescriptor, WShaderStages}};
 
 
 #[derive(Default, Asset, Clone, TypePath)]
@@ -9,7 +9,11 @@ pub struct MCRenderPipelineAsset;
 #[allow(dead_code)]
 pub struct MCRenderPipeline(pub Handle<MCRenderPipelineAsset>);
 pub struct GpuMCRenderPipeline {
-    pub cached_pipeline_index: CachedPipelineIndex
+    /// Pipeline rendering the chunks with smooth normals from their vertex and index buffers.
+    pub cached_pipeline_index: CachedPipelineIndex,
+    /// Pipeline rendering the chunks with flat normals from their raw triangles.
+    pub flat_cached_pipeline_index: CachedPipelineIndex,
+    pub triangles_layout: BindGroupLayout
 }
 impl RenderAsset for GpuMCRenderPipeline {
     type SourceAsset = MCRenderPipelineAsset;
@@ -38,6 +42,7 @@ impl RenderAsset for GpuMCRenderPipeline {
             vert: Some(assets_server.load("marching-cubes/render.vert.wgsl")),
             frag: Some(assets_server.load("marching-cubes/render.frag.wgsl")),
             bind_group_layouts: vec![camera_feature.layout.clone(), lights_layout.clone()],
+            render_targets: Some(vec![HDR_FORMAT]),
             depth: WDepthStencilDescriptor {
                 enabled: true,
                 ..Default::default()
@@ -46,8 +51,31 @@ impl RenderAsset for GpuMCRenderPipeline {
         };
         let cached_index = pipeline_manager.create_render_pipeline(pipeline_desc);
 
+        // Create the flat normals pipeline
+        let triangles_layout = BindGroupLayout::new("marching-cubes-render-triangles", |builder| {
+            builder.add_buffer(0,
+                WShaderStages::VERTEX,
+                BufferBindingType::Storage { read_only: true });
+        });
+        let flat_pipeline_desc = RenderPipelineDescriptor {
+            label: "marching-cubes-flat",
+            vert: Some(assets_server.load("marching-cubes/render_flat.vert.wgsl")),
+            frag: Some(assets_server.load("marching-cubes/render.frag.wgsl")),
+            bind_group_layouts: vec![camera_feature.layout.clone(), lights_layout.clone(), triangles_layout.clone()],
+            render_targets: Some(vec![HDR_FORMAT]),
+            depth: WDepthStencilDescriptor {
+                enabled: true,
+                ..Default::default()
+            },
+            vertex_input: false,
+            ..Default::default()
+        };
+        let flat_cached_index = pipeline_manager.create_render_pipeline(flat_pipeline_desc);
+
         Ok(GpuMCRenderPipeline {
-            cached_pipeline_index: cached_index
+            cached_pipeline_index: cached_index,
+            flat_cached_pipeline_index: flat_cached_index,
+            triangles_layout
         })
     }
 
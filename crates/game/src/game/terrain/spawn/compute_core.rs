@@ -1,5 +1,5 @@
 use bevy::prelude::*;
-use wde_render::{assets::{Buffer, GpuBuffer, RenderAssets}, core::{extract_macros::ExtractWorld, DeviceLimits}, pipelines::{CachedPipelineStatus, PipelineManager}};
+use wde_render::{assets::{Buffer, GpuBuffer, RenderAssets}, core::{extract_macros::ExtractWorld, DeviceLimits}, features::CameraFrustum, pipelines::{CachedPipelineStatus, PipelineManager}};
 use wde_wgpu::{bind_group::BindGroup, buffer::BufferUsage, command_buffer::WCommandBuffer, instance::WRenderInstance};
 
 use crate::terrain::{mc_chunk::{MCActiveChunk, MCChunksListMain, MCChunksListRender, MCLoadingChunk, MCPendingChunk, MCRegisteredChunk}, mc_compute_main::{GpuMCDescription, MCComputeHandlerGPU, MCTerrainNoiseParameters}, MC_MAX_CHUNKS_PROCESS_PER_FRAME, MC_MAX_POINTS};
@@ -8,6 +8,18 @@ use super::compute_pipeline::GpuMCComputePipelineSpawn;
 
 pub struct MCComputePointsCore;
 impl MCComputePointsCore {
+    /** Number of threads per workgroup on each axis of the compute shaders. */
+    pub const WG_SIZE: u32 = 10;
+
+    /**
+     * Clamp the sub-count of a chunk so that the number of workgroups dispatched on each axis
+     * does not exceed the device limit `max_compute_workgroups_per_dimension`.
+     */
+    pub fn clamp_sub_count(sub_count: UVec3, max_workgroups_per_dimension: u32) -> UVec3 {
+        let max_sub_count = max_workgroups_per_dimension.saturating_mul(Self::WG_SIZE);
+        sub_count.min(UVec3::splat(max_sub_count))
+    }
+
     /** Process the spawn events to generate the chunks on the render thread. */
     pub fn extract(
         chunks_list_main: ExtractWorld<Res<MCChunksListMain>>,
@@ -24,7 +36,21 @@ impl MCComputePointsCore {
     ) {
         // Add the new chunks from the main thread to the render thread
         let max_buffer_size = device_limits.0.max_storage_buffer_binding_size as usize;
+        let max_workgroups = device_limits.0.max_compute_workgroups_per_dimension;
         for (index, desc) in chunks_list_main.new_chunks.iter() {
+            // Skip the chunks already spawned, as the list is extracted again until the spawner moves
+            if chunks_list_render.chunks.contains_key(index) {
+                continue;
+            }
+
+            // Clamp the sub-count to the device dispatch limits
+            let mut desc = desc.clone();
+            let sub_count = Self::clamp_sub_count(desc.sub_count, max_workgroups);
+            if sub_count != desc.sub_count {
+                warn!("The sub-count {:?} of the chunk {:?} exceeds the maximum number of compute workgroups per dimension ({}), clamping it to {:?}.",
+                    desc.sub_count, desc.index, max_workgroups, sub_count);
+                desc.sub_count = sub_count;
+            }
             chunks_list_render.chunks.insert(*index, desc.clone());
             
             // Create the points buffer
@@ -41,7 +67,7 @@ impl MCComputePointsCore {
                     points_gpu: asset_server.add(points_gpu),
                     points_gpu_group: None,
                 },
-                desc.clone()
+                desc
             ));
         }
 
@@ -154,11 +180,12 @@ impl MCComputePointsCore {
 
     /**
      * Generate the tasks for creating the points of each chunk.
+     * The chunks outside of the camera frustum are not generated and stay registered until they come into view.
      */
     pub fn compute(
         (query, mut commands): (Query<(Entity, &MCRegisteredChunk)>, Commands),
-        (chunks_list, handler): (Res<MCChunksListRender>, Res<MCComputeHandlerGPU>),
-        mut buffers: ResMut<RenderAssets<GpuBuffer>>,
+        (chunks_list, handler, frustum): (Res<MCChunksListRender>, Res<MCComputeHandlerGPU>, Res<CameraFrustum>),
+        (mut buffers, device_limits): (ResMut<RenderAssets<GpuBuffer>>, Res<DeviceLimits>),
         render_instance: Res<WRenderInstance<'static>>,
         (pipeline, pipeline_manager): (
             Res<RenderAssets<GpuMCComputePipelineSpawn>>, Res<PipelineManager>
@@ -183,11 +210,17 @@ impl MCComputePointsCore {
         // Generate the chunks
         let mut process_count = 0;
         for (entity, chunk) in query.iter() {
+            let desc = chunks_list.chunks.get(&chunk.index).unwrap();
+
+            // Skip the chunks outside of the camera frustum
+            if !frustum.intersects_aabb(desc.translation - desc.length / 2.0, desc.translation + desc.length / 2.0) {
+                continue;
+            }
+
             process_count += 1;
             if process_count >= MC_MAX_CHUNKS_PROCESS_PER_FRAME {
                 break;
             }
-            let desc = chunks_list.chunks.get(&chunk.index).unwrap();
 
             // Update the description buffer
             trace!("Running the compute shader to compute the points for the chunk {:?}.", chunk.index);
@@ -226,11 +259,16 @@ impl MCComputePointsCore {
                     compute_pass.set_bind_group(2, noise_buffer_group);
 
                     // Dispatch the compute pass
-                    const NUM_THREADS: i32 = 10;
-                    let dispatch_count_x = (desc.sub_count.x as f32 / NUM_THREADS as f32).ceil() as u32;
-                    let dispatch_count_y = (desc.sub_count.y as f32 / NUM_THREADS as f32).ceil() as u32;
-                    let dispatch_count_z = (desc.sub_count.z as f32 / NUM_THREADS as f32).ceil() as u32;
-                    trace!("Dispatching the compute pass for spawning the chunk points {:?} with marching cubes with {} threads and {:?} dispatches.", entity, NUM_THREADS, [dispatch_count_x, dispatch_count_y, dispatch_count_z]);
+                    let dispatch_count_x = desc.sub_count.x.div_ceil(Self::WG_SIZE);
+                    let dispatch_count_y = desc.sub_count.y.div_ceil(Self::WG_SIZE);
+                    let dispatch_count_z = desc.sub_count.z.div_ceil(Self::WG_SIZE);
+                    let max_workgroups = device_limits.0.max_compute_workgroups_per_dimension;
+                    if dispatch_count_x > max_workgroups || dispatch_count_y > max_workgroups || dispatch_count_z > max_workgroups {
+                        error!("The dispatch count {:?} of the chunk {:?} exceeds the maximum number of compute workgroups per dimension ({}).",
+                            [dispatch_count_x, dispatch_count_y, dispatch_count_z], entity, max_workgroups);
+                        continue;
+                    }
+                    trace!("Dispatching the compute pass for spawning the chunk points {:?} with marching cubes with {} threads and {:?} dispatches.", entity, Self::WG_SIZE, [dispatch_count_x, dispatch_count_y, dispatch_count_z]);
                     if let Err(e) = compute_pass.dispatch(dispatch_count_x, dispatch_count_y, dispatch_count_z) {
                         error!("Failed to dispatch the compute pass for spawning the chunk points {:?} with marching cubes: {:?}", entity, e);
                         continue;
@@ -1,6 +1,6 @@
 use bevy::{log::Level, prelude::*, utils::tracing::event};
 
-use crate::terrain::{mc_chunk::{MCChunkDescription, MCChunksListMain}, TerrainSpawner};
+use crate::terrain::{mc_chunk::{MCChunkDescription, MCChunkIndex, MCChunksListMain}, TerrainIsoLevel, TerrainSpawner};
 
 pub struct MarchingCubesSpawner;
 impl MarchingCubesSpawner {
@@ -12,6 +12,7 @@ impl MarchingCubesSpawner {
     pub fn manage_chunks(
         mut commands: Commands,
         chunks_list: Res<MCChunksListMain>,
+        iso_level: Res<TerrainIsoLevel>,
         chunk_spawner_query: Query<(&Transform, &TerrainSpawner), Changed<Transform>>
     ) {
         // Get the terrain spawner
@@ -25,10 +26,15 @@ impl MarchingCubesSpawner {
             }
         };
 
+        // Compute the index of the chunk of the spawner
+        let center_index = (
+            (cs_transform.translation.x / cs.chunk_length[0] + 0.5).round() as i32,
+            (cs_transform.translation.z / cs.chunk_length[2] + 0.5).round() as i32
+        );
+
         // Compute the list of chunks that should be spawned around the spawner
         let mut new_chunks = Vec::new();
-        let mut current_chunks = chunks_list.current_chunks.clone();
-        let mut delete_chunks = chunks_list.current_chunks.clone();
+        let mut occupied = chunks_list.occupied.clone();
         for i in -cs.chunk_radius_count..cs.chunk_radius_count {
             for k in -cs.chunk_radius_count..cs.chunk_radius_count {
                 // Check if in the circle
@@ -37,15 +43,10 @@ impl MarchingCubesSpawner {
                 }
 
                 // Compute the world index of the chunk
-                let chunk_global_index = (
-                    (cs_transform.translation.x / cs.chunk_length[0] + 0.5).round() as i32 + i,
-                    0,
-                    (cs_transform.translation.z / cs.chunk_length[2] + 0.5).round() as i32 + k
-                );
+                let chunk_global_index = (center_index.0 + i, 0, center_index.1 + k);
 
                 // Check if the chunk is already spawned
-                if current_chunks.contains_key(&chunk_global_index) {
-                    delete_chunks.remove(&chunk_global_index);
+                if occupied.contains(&chunk_global_index) {
                     continue;
                 }
 
@@ -60,25 +61,33 @@ impl MarchingCubesSpawner {
                     translation,
                     length: cs.chunk_length.into(),
                     sub_count: cs.chunk_sub_count.into(),
-                    iso_level: cs.iso_level
+                    iso_level: iso_level.0
                 };
                 event!(Level::TRACE, "Spawn chunk at index {:?}: {:?}", chunk_global_index, desc.clone());
-                current_chunks.insert(chunk_global_index, chunk_global_index);
+                occupied.insert(chunk_global_index);
                 new_chunks.push((chunk_global_index, desc));
             }
         }
 
-        // Remove the chunks that should be deleted
-        for chunk_index in delete_chunks.keys() {
+        // Remove the chunks outside of the despawn radius, which is never smaller than the spawn radius
+        let despawn_radius = cs.chunk_despawn_radius_count.max(cs.chunk_radius_count);
+        let delete_chunks: Vec<MCChunkIndex> = occupied.iter()
+            .filter(|(x, _, z)| {
+                let (i, k) = (x - center_index.0, z - center_index.1);
+                i * i + k * k > despawn_radius * despawn_radius
+            })
+            .cloned()
+            .collect();
+        for chunk_index in delete_chunks.iter() {
             event!(Level::TRACE, "Delete chunk at index {:?}.", chunk_index);
-            current_chunks.remove(chunk_index);
+            occupied.remove(chunk_index);
         }
 
         // Update the chunks list
         commands.insert_resource(MCChunksListMain {
-            current_chunks,
+            occupied,
             new_chunks,
-            delete_chunks: delete_chunks.keys().cloned().collect()
+            delete_chunks
         });
     }
 }
\ No newline at end of file
@@ -1,7 +1,7 @@
 use std::collections::HashMap;
 
 use bevy::{ecs::system::lifetimeless::{SRes, SResMut}, prelude::*};
-use wde_wgpu::{bind_group::{BindGroup, BindGroupLayout, WBufferBindingType, WgpuBindGroup}, buffer::BufferUsage, instance::WRenderInstance, render_pipeline::WShaderStages, texture::{WTextureFormat, WTextureUsages}};
+use wde_wgpu::{bind_group::{BindGroup, BindGroupLayout, WBufferBindingType, WgpuBindGroup}, buffer::BufferUsage, instance::WRenderInstance, render_pipeline::WShaderStages, texture::{WTexture, WTextureFormat, WTextureUsages, WTextureView}};
 
 use crate::core::RenderApp;
 
@@ -12,6 +12,18 @@ pub trait Material {
     fn describe(&self, builder: &mut MaterialBuilder);
     /// Get the label of the material
     fn label(&self) -> String;
+    /// Get the push constants data of the material, pushed by the render passes when the material is bound (default: none).
+    fn push_constants(&self) -> Vec<u8> {
+        Vec::new()
+    }
+    /// Whether the material is transparent and must be excluded from the opaque passes (default: false).
+    fn is_transparent(&self) -> bool {
+        false
+    }
+    /// Whether both faces of the material are rendered (default: false).
+    fn is_double_sided(&self) -> bool {
+        false
+    }
 }
 
 
@@ -33,11 +45,20 @@ struct MaterialBuilderTextureSampler {
     visibility: WShaderStages,
     texture: Option<Handle<Texture>>
 }
+struct MaterialBuilderTextureArrayView {
+    binding: u32,
+    visibility: WShaderStages,
+    layers: Vec<Handle<Texture>>,
+    texture: Option<WTexture>,
+    /// The 2D array view of the texture, which is an array even with a single layer.
+    view: Option<WTextureView>
+}
 
 enum MaterialBuilderType {
     Buffer,
     TextureView,
-    TextureSampler
+    TextureSampler,
+    TextureArrayView
 }
 
 #[derive(Default)]
@@ -47,7 +68,8 @@ pub struct MaterialBuilder {
 
     buffers: Vec<MaterialBuilderBuffer>,
     texture_views: Vec<MaterialBuilderTextureView>,
-    texture_samplers: Vec<MaterialBuilderTextureSampler>
+    texture_samplers: Vec<MaterialBuilderTextureSampler>,
+    texture_array_views: Vec<MaterialBuilderTextureArrayView>
 }
 impl MaterialBuilder {
     /// Add a buffer to the material. A buffer is a uniform or storage buffer that will be created by the material builder.
@@ -69,6 +91,15 @@ impl MaterialBuilder {
         });
         self.elements.push((MaterialBuilderType::TextureSampler, self.texture_samplers.len() as u32 - 1));
     }
+    /// Add a 2D texture array to the material. The array is built by copying each layer texture in a layer of the array,
+    /// so the layer textures must have the same size and format, and the COPY_SRC usage.
+    /// If there are no layers, a single black layer is used.
+    pub fn add_texture_array_view(&mut self, binding: u32, visibility: WShaderStages, layers: Vec<Handle<Texture>>) {
+        self.texture_array_views.push(MaterialBuilderTextureArrayView {
+            binding, visibility, layers, texture: None, view: None
+        });
+        self.elements.push((MaterialBuilderType::TextureArrayView, self.texture_array_views.len() as u32 - 1));
+    }
 }
 
 
@@ -92,7 +123,13 @@ pub struct GpuMaterial<M: Material + Sync + Send + Asset + Clone> {
     phantom: std::marker::PhantomData<M>,
     builder: MaterialBuilder,
     pub bind_group_layout: BindGroupLayout,
-    pub bind_group: WgpuBindGroup
+    pub bind_group: WgpuBindGroup,
+    /// The push constants data of the material.
+    pub push_constants: Vec<u8>,
+    /// Whether the material is transparent.
+    pub transparent: bool,
+    /// Whether both faces of the material are rendered.
+    pub double_sided: bool
 }
 impl<M: Material + Sync + Send + Asset + Clone> RenderAsset for GpuMaterial<M> {
     type SourceAsset = M;
@@ -119,6 +156,41 @@ impl<M: Material + Sync + Send + Asset + Clone> RenderAsset for GpuMaterial<M> {
             builder
         };
 
+        // Create the texture arrays once all their layers are loaded
+        for array in material_builder.texture_array_views.iter_mut() {
+            if array.texture.is_some() {
+                continue;
+            }
+
+            // Get the layers
+            let mut layers = Vec::with_capacity(array.layers.len());
+            for layer in array.layers.iter() {
+                match textures.get(layer) {
+                    Some(tex) => layers.push(&tex.texture),
+                    None => {
+                        materials_cache.insert(material_name.to_string(), material_builder);
+                        return Err(PrepareAssetError::RetryNextUpdate(asset));
+                    }
+                }
+            }
+
+            // Create the array and copy the layers
+            let (size, format) = match layers.first() {
+                Some(first) => (first.size, first.format),
+                None => ((1, 1), WTextureFormat::Rgba8Unorm)
+            };
+            if layers.iter().any(|layer| layer.size != size || layer.format != format) {
+                return Err(PrepareAssetError::Fatal(format!("The layers of the texture array of the material {} do not have the same size and format.", label)));
+            }
+            let texture = WTexture::new_array(&render_instance, &format!("{}-array", label),
+                size, layers.len().max(1) as u32, format, WTextureUsages::TEXTURE_BINDING);
+            for (i, layer) in layers.iter().enumerate() {
+                texture.copy_from_texture_layer(&render_instance, &layer.texture, size, i as u32);
+            }
+            array.view = Some(texture.view_array());
+            array.texture = Some(texture);
+        }
+
         // Create bind group entries
         // If a buffer or texture is not ready, retry next update
         let mut bg_entries = Vec::new();
@@ -186,6 +258,10 @@ impl<M: Material + Sync + Send + Asset + Clone> RenderAsset for GpuMaterial<M> {
                         return Err(PrepareAssetError::RetryNextUpdate(asset));
                     }
                 }
+                MaterialBuilderType::TextureArrayView => {
+                    let array = &material_builder.texture_array_views[*material_index as usize];
+                    bg_entries.push(BindGroup::view(array.binding, array.view.as_ref().unwrap()));
+                }
             }
         }
 
@@ -205,6 +281,10 @@ impl<M: Material + Sync + Send + Asset + Clone> RenderAsset for GpuMaterial<M> {
                         let sampler = &material_builder.texture_samplers[*material_index as usize];
                         builder.add_texture_sampler(sampler.binding, sampler.visibility);
                     }
+                    MaterialBuilderType::TextureArrayView => {
+                        let array = &material_builder.texture_array_views[*material_index as usize];
+                        builder.add_texture_array_view(array.binding, array.visibility);
+                    }
                 }
             }
         });
@@ -216,6 +296,9 @@ impl<M: Material + Sync + Send + Asset + Clone> RenderAsset for GpuMaterial<M> {
             phantom: std::marker::PhantomData,
             bind_group_layout: layout,
             bind_group,
+            push_constants: asset.push_constants(),
+            transparent: asset.is_transparent(),
+            double_sided: asset.is_double_sided(),
             builder: material_builder
         })
     }
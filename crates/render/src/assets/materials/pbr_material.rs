@@ -3,6 +3,19 @@ use wde_wgpu::{bind_group::WBufferBindingType, render_pipeline::WShaderStages};
 
 use crate::assets::{Material, MaterialBuilder, Texture};
 
+/// How the alpha channel of a material is handled.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum PbrAlphaMode {
+    /// The material is fully opaque and is rendered by the gbuffer pass.
+    #[default]
+    Opaque,
+    /// The material is rendered by the gbuffer pass, discarding the fragments whose albedo alpha is below the threshold.
+    AlphaCutout(f32),
+    /// The material is transparent and is excluded from the gbuffer pass to be rendered by the
+    /// order-independent transparency pass, using the albedo alpha as opacity.
+    Transparent
+}
+
 #[derive(Asset, Clone, TypePath)]
 /// Describes a physically based rendering material.
 pub struct PbrMaterialAsset {
@@ -18,6 +31,47 @@ pub struct PbrMaterialAsset {
     pub specular: f32,
     /// The specular texture of the material instance. If `None`, the material will use the specular intensity.
     pub specular_t: Option<Handle<Texture>>,
+
+    /// The perceptual roughness of the material instance, from 0.0 (smooth) to 1.0 (rough).
+    pub roughness: f32,
+    /// The metalness of the material instance, from 0.0 (dielectric) to 1.0 (metal).
+    pub metallic: f32,
+    /// The roughness (G channel) and metallic (B channel) texture of the material instance, as in glTF 2.0.
+    /// The sampled values are multiplied by the roughness and metallic factors.
+    /// If `None`, the material will use the roughness and metallic factors.
+    pub roughness_metallic_t: Option<Handle<Texture>>,
+
+    /// The baked ambient occlusion texture of the material instance, read from the R channel as in glTF 2.0.
+    /// It darkens the ambient lighting. If `None`, the ambient lighting is not occluded.
+    pub ao_t: Option<Handle<Texture>>,
+    /// The strength of the ambient occlusion, from 0.0 (no occlusion) to 1.0 (full occlusion).
+    pub ao_strength: f32,
+
+    /// The tangent space normal texture of the material instance. If `None`, the vertex normals are used.
+    /// The mesh must have tangents, see `MeshAsset::generate_tangents`.
+    pub normal_t: Option<Handle<Texture>>,
+
+    /// The color emitted by the material instance, added to the lit color. The components can exceed 1.0.
+    pub emissive: (f32, f32, f32),
+    /// The emissive texture of the material instance, multiplied by the emissive color.
+    /// If `None`, the material will use the emissive color.
+    pub emissive_t: Option<Handle<Texture>>,
+
+    /// The baked indirect lighting texture of the material instance, sampled with the second UV channel.
+    /// If `None`, the ambient lighting is not modified.
+    pub lightmap_t: Option<Handle<Texture>>,
+    /// The intensity of the lightmap.
+    pub lightmap_intensity: f32,
+
+    /// The scale applied to the UV coordinates of the albedo, specular and normal textures, to tile them over the mesh.
+    pub uv_scale: Vec2,
+    /// The offset added to the UV coordinates of the albedo, specular and normal textures after scaling.
+    pub uv_offset: Vec2,
+
+    /// How the alpha channel of the material is handled.
+    pub alpha_mode: PbrAlphaMode,
+    /// Whether both faces of the material are rendered, with the normal flipped on the back faces.
+    pub double_sided: bool,
 }
 impl Default for PbrMaterialAsset {
     fn default() -> Self {
@@ -29,6 +83,27 @@ impl Default for PbrMaterialAsset {
 
             specular:   1.0,
             specular_t: None,
+
+            roughness: 0.5,
+            metallic:  0.0,
+            roughness_metallic_t: None,
+
+            ao_t:        None,
+            ao_strength: 1.0,
+
+            normal_t: None,
+
+            emissive:   (0.0, 0.0, 0.0),
+            emissive_t: None,
+
+            lightmap_t:         None,
+            lightmap_intensity: 1.0,
+
+            uv_scale:  Vec2::ONE,
+            uv_offset: Vec2::ZERO,
+
+            alpha_mode: PbrAlphaMode::Opaque,
+            double_sided: false,
         }
     }
 }
@@ -45,25 +120,68 @@ pub(crate) struct PbrMaterialUniform {
     pub flags: [f32; 4],
     /// RGB albedo of the material.
     pub albedo: [f32; 4],
+    /// UV scale (xy) and offset (zw) of the material textures.
+    pub uv_transform: [f32; 4],
+    /// RGB emissive color of the material, and whether it has an emissive texture (w).
+    pub emissive: [f32; 4],
     /// Specular intensity of the material.
     pub specular: f32,
-    /// Unused padding.
-    _padding: [f32; 3]
+    /// Intensity of the lightmap.
+    pub lightmap_intensity: f32,
+    /// Perceptual roughness of the material.
+    pub roughness: f32,
+    /// Metalness of the material.
+    pub metallic: f32,
+    /// Whether the material has a roughness and metallic texture.
+    pub has_roughness_metallic: f32,
+    /// Whether the material has an ambient occlusion texture.
+    pub has_ao: f32,
+    /// Strength of the ambient occlusion.
+    pub ao_strength: f32,
+    /// Albedo alpha below which the fragments are discarded, 0 if the material is not cut out.
+    pub alpha_cutoff: f32
+}
+impl PbrMaterialUniform {
+    /// Create the uniform of a material.
+    pub fn new(material: &PbrMaterialAsset) -> Self {
+        PbrMaterialUniform {
+            flags: [
+                if material.albedo_t.is_some()   { 1.0 } else { 0.0 },
+                if material.specular_t.is_some() { 1.0 } else { 0.0 },
+                if material.lightmap_t.is_some() { 1.0 } else { 0.0 },
+                if material.normal_t.is_some()   { 1.0 } else { 0.0 },
+            ],
+            albedo: [material.albedo.0, material.albedo.1, material.albedo.2, material.albedo.3],
+            uv_transform: [material.uv_scale.x, material.uv_scale.y, material.uv_offset.x, material.uv_offset.y],
+            emissive: [
+                material.emissive.0, material.emissive.1, material.emissive.2,
+                if material.emissive_t.is_some() { 1.0 } else { 0.0 }
+            ],
+            specular: material.specular,
+            lightmap_intensity: material.lightmap_intensity,
+            roughness: material.roughness,
+            metallic: material.metallic,
+            has_roughness_metallic: if material.roughness_metallic_t.is_some() { 1.0 } else { 0.0 },
+            has_ao: if material.ao_t.is_some() { 1.0 } else { 0.0 },
+            ao_strength: material.ao_strength,
+            alpha_cutoff: match material.alpha_mode {
+                PbrAlphaMode::AlphaCutout(cutoff) => cutoff.max(0.0),
+                _ => 0.0
+            },
+        }
+    }
+}
+impl PbrMaterialAsset {
+    /// Whether the material samples at least one texture.
+    pub fn has_textures(&self) -> bool {
+        self.albedo_t.is_some() || self.specular_t.is_some() || self.normal_t.is_some() || self.emissive_t.is_some() || self.lightmap_t.is_some()
+            || self.roughness_metallic_t.is_some() || self.ao_t.is_some()
+    }
 }
 impl Material for PbrMaterialAsset {
     fn describe(&self, builder: &mut MaterialBuilder) {
         // Create the uniform buffer
-        let uniform = PbrMaterialUniform {
-            flags: [
-                if self.albedo_t.is_some()   { 1.0 } else { 0.0 },
-                if self.specular_t.is_some() { 1.0 } else { 0.0 },
-                0.0, // Unused
-                0.0, // Unused
-            ],
-            albedo: [self.albedo.0, self.albedo.1, self.albedo.2, self.albedo.3],
-            specular: self.specular,
-            _padding: [0.0; 3],
-        };
+        let uniform = PbrMaterialUniform::new(self);
 
         // Build the material
         builder.add_buffer(
@@ -73,9 +191,27 @@ impl Material for PbrMaterialAsset {
         builder.add_texture_sampler( 2, WShaderStages::FRAGMENT, self.albedo_t.clone());
         builder.add_texture_view(    3, WShaderStages::FRAGMENT, self.specular_t.clone());
         builder.add_texture_sampler( 4, WShaderStages::FRAGMENT, self.specular_t.clone());
+        builder.add_texture_view(    5, WShaderStages::FRAGMENT, self.lightmap_t.clone());
+        builder.add_texture_sampler( 6, WShaderStages::FRAGMENT, self.lightmap_t.clone());
+        builder.add_texture_view(    7, WShaderStages::FRAGMENT, self.normal_t.clone());
+        builder.add_texture_sampler( 8, WShaderStages::FRAGMENT, self.normal_t.clone());
+        builder.add_texture_view(    9, WShaderStages::FRAGMENT, self.emissive_t.clone());
+        builder.add_texture_sampler(10, WShaderStages::FRAGMENT, self.emissive_t.clone());
+        builder.add_texture_view(   11, WShaderStages::FRAGMENT, self.roughness_metallic_t.clone());
+        builder.add_texture_sampler(12, WShaderStages::FRAGMENT, self.roughness_metallic_t.clone());
+        builder.add_texture_view(   13, WShaderStages::FRAGMENT, self.ao_t.clone());
+        builder.add_texture_sampler(14, WShaderStages::FRAGMENT, self.ao_t.clone());
     }
 
     fn label(&self) -> String {
         self.label.to_string() + "-material"
     }
+
+    fn is_transparent(&self) -> bool {
+        self.alpha_mode == PbrAlphaMode::Transparent
+    }
+
+    fn is_double_sided(&self) -> bool {
+        self.double_sided
+    }
 }
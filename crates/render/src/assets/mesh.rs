@@ -1,12 +1,14 @@
 use std::{fs::File, io::BufReader};
 
-use bevy::{asset::{io::Reader, AssetLoader, LoadContext}, ecs::system::lifetimeless::SRes, prelude::*};
+use bevy::{asset::{io::Reader, AssetLoader, LoadContext}, ecs::system::lifetimeless::SRes, prelude::*, utils::HashMap};
 use thiserror::Error;
 use serde::{Deserialize, Serialize};
 use tobj::LoadError;
 use wde_wgpu::{buffer::{BufferUsage, WBuffer}, instance::WRenderInstance, vertex::WVertex};
 
-use super::render_assets::{PrepareAssetError, RenderAsset};
+use crate::{components::{CastShadows, ReceiveShadows}, core::extract_macros::ExtractWorld};
+
+use super::render_assets::{CancelledHandles, PrepareAssetError, RenderAsset};
 
 /// The bounding box of the model.
 #[derive(Clone, Debug)]
@@ -24,9 +26,35 @@ impl Default for ModelBoundingBox {
         }
     }
 }
+impl ModelBoundingBox {
+    /// Whether the bounding box contains no point.
+    pub fn is_empty(&self) -> bool {
+        self.min.cmpgt(self.max).any()
+    }
+
+    /// The axis aligned bounding box enclosing this bounding box once transformed, e.g. to world space.
+    pub fn transformed(&self, transform: &Transform) -> ModelBoundingBox {
+        if self.is_empty() {
+            return self.clone();
+        }
+        let affine = transform.compute_affine();
+        let center = affine.transform_point3((self.min + self.max) / 2.0);
+        let extent = (self.max - self.min) / 2.0;
+        let extent = Vec3::from(affine.matrix3.x_axis.abs() * extent.x
+            + affine.matrix3.y_axis.abs() * extent.y
+            + affine.matrix3.z_axis.abs() * extent.z);
+        ModelBoundingBox { min: center - extent, max: center + extent }
+    }
+
+    /// The bounding box enclosing both bounding boxes.
+    pub fn union(&self, other: &ModelBoundingBox) -> ModelBoundingBox {
+        ModelBoundingBox { min: self.min.min(other.min), max: self.max.max(other.max) }
+    }
+}
 
 #[derive(Component, Default, Reflect)]
 #[reflect(Component)]
+#[require(CastShadows, ReceiveShadows)]
 pub struct Mesh(pub Handle<MeshAsset>);
 #[derive(Asset, TypePath, Clone)]
 pub struct MeshAsset {
@@ -39,6 +67,45 @@ pub struct MeshAsset {
     /// The bounding box of the model
     pub bounding_box: ModelBoundingBox,
 }
+impl MeshAsset {
+    /// Generate the tangents of the vertices from their positions, normals and UVs using the MikkTSpace algorithm.
+    /// The mesh must be made of indexed triangles. The tangents are left to zero if they cannot be generated.
+    pub fn generate_tangents(&mut self) {
+        let vertex_count = self.vertices.len() as u32;
+        if self.indices.iter().any(|&index| index >= vertex_count) {
+            warn!(self.label, "Cannot generate the tangents of a mesh with indices out of range.");
+            return;
+        }
+        if !bevy_mikktspace::generate_tangents(self) {
+            warn!(self.label, "Failed to generate the tangents of the mesh.");
+        }
+    }
+}
+impl bevy_mikktspace::Geometry for MeshAsset {
+    fn num_faces(&self) -> usize {
+        self.indices.len() / 3
+    }
+
+    fn num_vertices_of_face(&self, _face: usize) -> usize {
+        3
+    }
+
+    fn position(&self, face: usize, vert: usize) -> [f32; 3] {
+        self.vertices[self.indices[face * 3 + vert] as usize].position
+    }
+
+    fn normal(&self, face: usize, vert: usize) -> [f32; 3] {
+        self.vertices[self.indices[face * 3 + vert] as usize].normal
+    }
+
+    fn tex_coord(&self, face: usize, vert: usize) -> [f32; 2] {
+        self.vertices[self.indices[face * 3 + vert] as usize].uv
+    }
+
+    fn set_tangent_encoded(&mut self, tangent: [f32; 4], face: usize, vert: usize) {
+        self.vertices[self.indices[face * 3 + vert] as usize].tangent = tangent;
+    }
+}
 
 #[derive(Default)]
 pub struct MeshLoader;
@@ -47,11 +114,13 @@ pub struct MeshLoader;
 pub struct MeshLoaderSettings {
     /// The label of the mesh.
     pub label: String,
+    /// Whether the tangents of the vertices are generated, to use normal maps.
+    pub generate_tangents: bool,
 }
 
 impl Default for MeshLoaderSettings {
     fn default() -> Self {
-        Self { label: "".to_string() }
+        Self { label: "".to_string(), generate_tangents: true }
     }
 }
 
@@ -149,10 +218,13 @@ impl AssetLoader for MeshLoader {
                 }
 
                 // Vertex
+                // OBJ files only store a single UV set, which is also used as the lightmap UV set
                 vertices.push(WVertex {
                     position: [x, y, z],
                     normal: [nx, ny, nz],
                     uv: [u, v],
+                    uv1: [u, v],
+                    tangent: [0.0; 4],
                 });
 
                 // Update bounding box
@@ -169,10 +241,14 @@ impl AssetLoader for MeshLoader {
         }
 
         // Return the mesh
-        Ok(MeshAsset {
+        let mut mesh = MeshAsset {
             label,
             vertices, indices, bounding_box
-        })
+        };
+        if settings.generate_tangents {
+            mesh.generate_tangents();
+        }
+        Ok(mesh)
     }
 
     fn extensions(&self) -> &[&str] {
@@ -198,6 +274,8 @@ impl RenderAsset for GpuMesh {
     type SourceAsset = MeshAsset;
     type Param = SRes<WRenderInstance<'static>>;
 
+    const UNLOAD_CANCELLED: bool = true;
+
     fn prepare_asset(
             asset: Self::SourceAsset,
             render_instance: &mut bevy::ecs::system::SystemParamItem<Self::Param>,
@@ -234,3 +312,156 @@ impl RenderAsset for GpuMesh {
         &self.label
     }
 }
+
+
+
+/// Keeps track of the meshes used by the main world entities, in order to cancel the upload of the meshes
+/// that are no longer referenced by any entity once their entities are despawned.
+/// The cancelled GPU meshes are freed by the prepare system, and their CPU side kept in the render world
+/// since the mesh asset is moved out of the main world when extracted. They are uploaded again once an
+/// entity references them, or dropped once their asset is unused (`AssetEvent::Unused`).
+#[derive(Resource, Default)]
+pub(crate) struct MeshEntities {
+    /// The mesh used by each entity.
+    entities: HashMap<Entity, AssetId<MeshAsset>>,
+    /// The number of entities using each mesh.
+    references: HashMap<AssetId<MeshAsset>, usize>,
+    /// The meshes no longer referenced by any entity this frame.
+    removed: Vec<AssetId<MeshAsset>>,
+    /// The meshes referenced by an entity for the first time this frame.
+    referenced: Vec<AssetId<MeshAsset>>
+}
+impl MeshEntities {
+    /// Register the mesh of a new or modified entity.
+    fn insert(&mut self, entity: Entity, mesh: AssetId<MeshAsset>) {
+        if let Some(old_mesh) = self.entities.insert(entity, mesh) {
+            if old_mesh == mesh {
+                return;
+            }
+            self.release(old_mesh);
+        }
+        let count = self.references.entry(mesh).or_default();
+        if *count == 0 {
+            self.referenced.push(mesh);
+        }
+        *count += 1;
+    }
+
+    /// Unregister the mesh of a despawned entity, or of an entity whose mesh component was removed.
+    fn remove(&mut self, entity: Entity) {
+        if let Some(mesh) = self.entities.remove(&entity) {
+            self.release(mesh);
+        }
+    }
+
+    /// Release a reference to a mesh and mark it as removed if it is not used anymore.
+    fn release(&mut self, mesh: AssetId<MeshAsset>) {
+        if let Some(count) = self.references.get_mut(&mesh) {
+            *count -= 1;
+            if *count == 0 {
+                self.references.remove(&mesh);
+                self.removed.push(mesh);
+            }
+        }
+    }
+
+    /// Extract the meshes of the entities and the removed mesh components from the main world.
+    pub(crate) fn extract(
+        meshes: ExtractWorld<Query<(Entity, &Mesh), Changed<Mesh>>>,
+        mut removed_meshes: ExtractWorld<RemovedComponents<Mesh>>,
+        mut mesh_entities: ResMut<MeshEntities>
+    ) {
+        // Register the new meshes
+        for (entity, mesh) in meshes.iter() {
+            mesh_entities.insert(entity, mesh.0.id());
+        }
+
+        // Release the meshes of the despawned entities
+        for entity in removed_meshes.read() {
+            mesh_entities.remove(entity);
+        }
+    }
+
+    /// Cancel the upload of the meshes that are not used by any entity anymore, freeing them if they are loaded.
+    pub(crate) fn cancel(mesh_entities: Res<MeshEntities>, mut cancelled_handles: ResMut<CancelledHandles>) {
+        for mesh in mesh_entities.referenced.iter() {
+            cancelled_handles.restore(*mesh);
+        }
+        for mesh in mesh_entities.removed.iter() {
+            cancelled_handles.cancel(*mesh);
+        }
+    }
+
+    /// Clear the meshes referenced and released this frame.
+    pub(crate) fn cleanup(mut mesh_entities: ResMut<MeshEntities>) {
+        mesh_entities.referenced.clear();
+        mesh_entities.removed.clear();
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use bevy::{asset::AssetIndex, ecs::system::RunSystemOnce, prelude::*};
+
+    use crate::core::MainWorld;
+
+    use super::{CancelledHandles, Mesh, MeshAsset, MeshEntities};
+
+    #[test]
+    fn despawned_entities_release_their_mesh() {
+        let mesh = AssetId::<MeshAsset>::Index { index: AssetIndex::from_bits(0), marker: default() };
+        let mut mesh_entities = MeshEntities::default();
+
+        // Spawn and despawn 1000 entities per frame
+        for frame in 0..100 {
+            let entities: Vec<Entity> = (0..1000).map(|i| Entity::from_raw(frame * 1000 + i)).collect();
+            for &entity in entities.iter() {
+                mesh_entities.insert(entity, mesh);
+            }
+            assert_eq!(mesh_entities.references.get(&mesh), Some(&1000));
+            assert_eq!(mesh_entities.referenced, vec![mesh]);
+
+            for &entity in entities.iter() {
+                mesh_entities.remove(entity);
+            }
+            assert!(mesh_entities.entities.is_empty());
+            assert!(mesh_entities.references.is_empty());
+            assert_eq!(mesh_entities.removed, vec![mesh]);
+
+            // Cleanup
+            mesh_entities.referenced.clear();
+            mesh_entities.removed.clear();
+        }
+    }
+
+    #[test]
+    fn despawned_entities_cancel_their_gpu_mesh() {
+        let mesh = AssetId::<MeshAsset>::Index { index: AssetIndex::from_bits(0), marker: default() };
+        let mut world = World::new();
+        let mut main_world = World::new();
+        let entity = main_world.spawn(Mesh(Handle::Weak(mesh))).id();
+        world.insert_resource(MainWorld(main_world));
+        world.init_resource::<MeshEntities>();
+        world.init_resource::<CancelledHandles>();
+        let update = |world: &mut World| {
+            world.run_system_once(MeshEntities::extract).unwrap();
+            world.run_system_once(MeshEntities::cancel).unwrap();
+            world.run_system_once(MeshEntities::cleanup).unwrap();
+        };
+
+        // The mesh is kept while its entity exists
+        update(&mut world);
+        assert!(!world.resource::<CancelledHandles>().contains(mesh));
+
+        // The GPU mesh is unloaded once its entity is despawned
+        world.resource_mut::<MainWorld>().despawn(entity);
+        update(&mut world);
+        assert!(world.resource::<CancelledHandles>().contains(mesh));
+
+        // And uploaded again once an entity references it
+        world.resource_mut::<MainWorld>().spawn(Mesh(Handle::Weak(mesh)));
+        update(&mut world);
+        assert!(!world.resource::<CancelledHandles>().contains(mesh));
+    }
+}
@@ -0,0 +1,123 @@
+use bevy::{asset::LoadState, prelude::*};
+
+use crate::components::Camera;
+
+use super::{Mesh, MeshAsset};
+
+/// The fraction of the distance of a level the camera must move past before a less detailed level is loaded.
+/// This avoids loading the levels back and forth when the camera stays around the distance of a level.
+const LOD_HYSTERESIS: f32 = 0.1;
+
+/// A level of detail of a streamed mesh.
+#[derive(Clone, Debug, Reflect)]
+pub struct MeshLod {
+    /// The path of the mesh file of the level.
+    pub path: String,
+    /// The maximum distance to the camera at which the level is displayed.
+    pub max_distance: f32,
+}
+
+/// Stream the levels of detail of the mesh of the entity depending on its distance to the camera.
+/// Only the level matching the distance is kept in memory: when the entity gets closer or farther,
+/// the new level is loaded from disk in the background and replaces the `Mesh` handle once loaded,
+/// which unloads the previous level if it is not used elsewhere.
+#[derive(Component, Clone, Debug, Default, Reflect)]
+#[reflect(Component)]
+#[require(Mesh)]
+pub struct MeshLods {
+    /// The levels of detail, from the most to the least detailed, with increasing distances.
+    /// The last level is also used beyond its distance.
+    pub levels: Vec<MeshLod>,
+    /// The index of the displayed level.
+    #[reflect(ignore)]
+    current: Option<usize>,
+    /// The level being loaded, displayed once loaded.
+    #[reflect(ignore)]
+    pending: Option<(usize, Handle<MeshAsset>)>,
+    /// The last level that failed to load, not loaded again until another level is targeted.
+    #[reflect(ignore)]
+    failed: Option<usize>,
+}
+impl MeshLods {
+    /// Create the levels of detail of a mesh, from the most to the least detailed.
+    pub fn new(levels: Vec<MeshLod>) -> Self {
+        Self { levels, current: None, pending: None, failed: None }
+    }
+
+    /// The index of the displayed level, if any level was loaded.
+    pub fn current(&self) -> Option<usize> {
+        self.current
+    }
+
+    /// The level to display at the given distance to the camera.
+    fn target_level(&self, distance: f32) -> usize {
+        self.levels.iter().enumerate()
+            .position(|(index, level)| {
+                // Keep the displayed level a bit longer when moving away
+                let max_distance = if Some(index) == self.current {
+                    level.max_distance * (1.0 + LOD_HYSTERESIS)
+                } else {
+                    level.max_distance
+                };
+                distance <= max_distance
+            })
+            .unwrap_or(self.levels.len() - 1)
+    }
+
+    /// Start the loads of the levels matching the camera distance, and display the loaded ones.
+    fn update(
+        cameras: Query<&Transform, With<Camera>>,
+        mut meshes: Query<(Entity, &Transform, &mut Mesh, &mut MeshLods)>,
+        asset_server: Res<AssetServer>
+    ) {
+        let camera_position = match cameras.iter().next() {
+            Some(transform) => transform.translation,
+            None => return
+        };
+
+        for (entity, transform, mut mesh, mut lods) in meshes.iter_mut() {
+            if lods.levels.is_empty() {
+                continue;
+            }
+
+            // Start loading the level matching the distance
+            let target = lods.target_level(transform.translation.distance(camera_position));
+            if lods.current == Some(target) {
+                lods.pending = None;
+                lods.failed = None;
+                continue;
+            }
+            if lods.failed == Some(target) {
+                continue;
+            }
+            lods.failed = None;
+            if lods.pending.as_ref().is_none_or(|(level, _)| *level != target) {
+                debug!("Streaming the level of detail {} of the mesh of {:?}.", target, entity);
+                let handle = asset_server.load(lods.levels[target].path.clone());
+                lods.pending = Some((target, handle));
+            }
+
+            // Display the level once loaded
+            let (level, handle) = lods.pending.as_ref().unwrap();
+            if asset_server.is_loaded_with_dependencies(handle) {
+                mesh.0 = handle.clone();
+                lods.current = Some(*level);
+                lods.pending = None;
+            } else if let Some(LoadState::Failed(e)) = asset_server.get_load_state(handle) {
+                // Keep the displayed level, and do not load the failed level again while it is targeted
+                error!("Failed to stream the level of detail {} of the mesh of {:?}: {}.", level, entity, e);
+                lods.failed = Some(*level);
+                lods.pending = None;
+            }
+        }
+    }
+}
+
+pub(crate) struct MeshStreamingPlugin;
+impl Plugin for MeshStreamingPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_systems(Update, MeshLods::update)
+            .register_type::<MeshLods>();
+    }
+}
@@ -100,6 +100,8 @@ impl CubeMesh {
                 position: [x, y, z],
                 normal: [nx, ny, nz],
                 uv: [u, v],
+                uv1: [u, v],
+                tangent: [0.0; 4],
             });
         }
 
@@ -119,11 +121,13 @@ impl CubeMesh {
             max: Vec3::new( half_length,  half_length,  half_length),
         };
 
-        MeshAsset {
+        let mut mesh = MeshAsset {
             label: label.to_string(),
             vertices,
             indices,
             bounding_box,
-        }
+        };
+        mesh.generate_tangents();
+        mesh
     }
 }
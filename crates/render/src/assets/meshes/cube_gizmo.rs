@@ -44,6 +44,8 @@ impl CubeGizmoMesh {
                 position: [x, y, z],
                 normal: [0.0, 0.0, 0.0], // Normals are not used for gizmo
                 uv: [0.0, 0.0], // UVs are not used for gizmo
+                uv1: [0.0, 0.0],
+                tangent: [0.0; 4],
             });
         }
 
@@ -47,6 +47,8 @@ impl PlaneMesh {
                 position: [x, y, z],
                 normal: [nx, ny, nz],
                 uv: [u, v],
+                uv1: [u, v],
+                tangent: [0.0; 4],
             });
         }
 
@@ -59,12 +61,14 @@ impl PlaneMesh {
             max: Vec3::new( half_size[0], 0.0,  half_size[1]),
         };
 
-        MeshAsset {
+        let mut mesh = MeshAsset {
             label: label.to_string(),
             vertices,
             indices,
             bounding_box,
-        }
+        };
+        mesh.generate_tangents();
+        mesh
     }
 }
 
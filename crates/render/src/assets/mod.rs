@@ -4,6 +4,8 @@ mod buffer;
 mod shader;
 mod material;
 mod render_assets;
+mod preload;
+mod mesh_streaming;
 pub mod meshes;
 pub mod materials;
 
@@ -16,8 +18,10 @@ pub use buffer::*;
 pub use shader::*;
 pub use material::*;
 pub use render_assets::*;
+pub use preload::*;
+pub use mesh_streaming::*;
 
-use crate::core::RenderApp;
+use crate::core::{Extract, Render, RenderApp, RenderSet};
 
 pub struct SceneResourcesPlugin;
 impl Plugin for SceneResourcesPlugin {
@@ -26,6 +30,8 @@ impl Plugin for SceneResourcesPlugin {
         app
             .add_plugins(MaterialsPluginRaw)
             .add_plugins(MaterialsPlugin)
+            .add_plugins(AssetPreloadPlugin)
+            .add_plugins(MeshStreamingPlugin)
             .init_asset_loader::<TextureLoader>()
             .init_asset::<Texture>()
             .init_asset_loader::<MeshLoader>()
@@ -44,6 +50,13 @@ impl Plugin for SceneResourcesPlugin {
         app.get_sub_app_mut(RenderApp).unwrap()
             .init_resource::<MaterialsBuilderCache>();
 
+        // Free the meshes of the despawned entities and cancel their pending uploads
+        app.get_sub_app_mut(RenderApp).unwrap()
+            .init_resource::<MeshEntities>()
+            .add_systems(Extract, MeshEntities::extract)
+            .add_systems(Render, MeshEntities::cancel.in_set(RenderSet::ExtractCommands))
+            .add_systems(Render, MeshEntities::cleanup.in_set(RenderSet::Cleanup));
+
         // Register the components to the reflect system
         app
             .register_type::<Mesh>();
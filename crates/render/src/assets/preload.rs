@@ -0,0 +1,145 @@
+use std::{cmp::Ordering, collections::BinaryHeap};
+
+use bevy::{asset::{AssetPath, LoadedUntypedAsset, RecursiveDependencyLoadState}, prelude::*, utils::HashMap};
+
+/// The maximum number of asset loads started per frame by the preload queue.
+const MAX_LOADS_PER_FRAME: usize = 8;
+
+/// A request of the preload queue.
+struct PrioritizedLoadRequest {
+    /// The path of the asset to load.
+    path: AssetPath<'static>,
+    /// The assets that must be loaded before the asset is loaded.
+    deps: Vec<AssetPath<'static>>,
+    /// The priority of the request. Higher priorities are loaded first.
+    priority: u32,
+    /// The insertion order of the request, to load requests of the same priority in order.
+    order: u64,
+}
+impl PartialEq for PrioritizedLoadRequest {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+impl Eq for PrioritizedLoadRequest {}
+impl PartialOrd for PrioritizedLoadRequest {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl Ord for PrioritizedLoadRequest {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.priority.cmp(&other.priority)
+            .then_with(|| other.order.cmp(&self.order))
+    }
+}
+
+/// Queue of the assets to load, started in priority order.
+/// An asset pushed with dependencies is only loaded once all of its dependencies are loaded,
+/// so that for instance a mesh is never displayed before its textures.
+///
+/// Once an asset is loaded, its handle can be retrieved with `AssetServer::get_handle`.
+#[derive(Resource, Default)]
+pub struct AssetPreloadQueue {
+    queue: BinaryHeap<PrioritizedLoadRequest>,
+    /// The requests waiting for their dependencies.
+    waiting: Vec<PrioritizedLoadRequest>,
+    /// The handles of the started loads, kept alive by the queue.
+    loading: HashMap<AssetPath<'static>, Handle<LoadedUntypedAsset>>,
+    next_order: u64,
+}
+impl AssetPreloadQueue {
+    /// Add an asset to the queue.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the asset.
+    /// * `priority` - The priority of the asset. Higher priorities are loaded first.
+    pub fn load<'a>(&mut self, path: impl Into<AssetPath<'a>>, priority: u32) {
+        self.load_with_deps(path, priority, &[]);
+    }
+
+    /// Add an asset to the queue, loaded only once all of its dependencies are loaded.
+    /// The dependencies that are not already in the queue are added with the priority of the asset.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the asset.
+    /// * `priority` - The priority of the asset. Higher priorities are loaded first.
+    /// * `deps` - The paths of the assets that must be loaded first.
+    pub fn load_with_deps<'a>(&mut self, path: impl Into<AssetPath<'a>>, priority: u32, deps: &[AssetPath]) {
+        let deps: Vec<AssetPath<'static>> = deps.iter().map(|dep| dep.clone_owned()).collect();
+        for dep in deps.iter() {
+            if !self.contains(dep) {
+                self.push(dep.clone(), priority, Vec::new());
+            }
+        }
+        self.push(path.into().into_owned(), priority, deps);
+    }
+
+    /// Returns true if the asset was pushed to the queue.
+    pub fn contains(&self, path: &AssetPath) -> bool {
+        self.loading.contains_key(path)
+            || self.queue.iter().chain(self.waiting.iter()).any(|request| &request.path == path)
+    }
+
+    /// Returns true if the asset and its dependencies are loaded.
+    pub fn is_loaded(&self, path: &AssetPath, asset_server: &AssetServer) -> bool {
+        self.loading.get(path).is_some_and(|handle| asset_server.is_loaded_with_dependencies(handle))
+    }
+
+    /// Returns true if all the assets of the queue are loaded.
+    pub fn is_done(&self, asset_server: &AssetServer) -> bool {
+        self.queue.is_empty() && self.waiting.is_empty()
+            && self.loading.values().all(|handle| asset_server.is_loaded_with_dependencies(handle))
+    }
+
+    /// Returns true if the asset is loaded or failed to load.
+    fn is_resolved(&self, path: &AssetPath, asset_server: &AssetServer) -> bool {
+        self.loading.get(path).is_some_and(|handle| matches!(
+            asset_server.recursive_dependency_load_state(handle),
+            RecursiveDependencyLoadState::Loaded | RecursiveDependencyLoadState::Failed(_)
+        ))
+    }
+
+    fn push(&mut self, path: AssetPath<'static>, priority: u32, deps: Vec<AssetPath<'static>>) {
+        self.queue.push(PrioritizedLoadRequest { path, deps, priority, order: self.next_order });
+        self.next_order += 1;
+    }
+
+    /// Start the loads of the highest priority requests whose dependencies are loaded.
+    fn update(mut queue: ResMut<AssetPreloadQueue>, asset_server: Res<AssetServer>) {
+        // Move back the waiting requests to the queue
+        let waiting = std::mem::take(&mut queue.waiting);
+        queue.queue.extend(waiting);
+
+        let mut started = 0;
+        while started < MAX_LOADS_PER_FRAME {
+            let request = match queue.queue.pop() {
+                Some(request) => request,
+                None => break
+            };
+
+            // Wait for the dependencies, the failed ones are skipped
+            if !request.deps.iter().all(|dep| queue.is_resolved(dep, &asset_server)) {
+                queue.waiting.push(request);
+                continue;
+            }
+
+            // Start the load
+            debug!("Preloading asset {} with priority {}.", request.path, request.priority);
+            let handle = asset_server.load_untyped(request.path.clone());
+            queue.loading.insert(request.path, handle);
+            started += 1;
+        }
+    }
+}
+
+pub(crate) struct AssetPreloadPlugin;
+impl Plugin for AssetPreloadPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<AssetPreloadQueue>()
+            .add_systems(PreUpdate, AssetPreloadQueue::update);
+    }
+}
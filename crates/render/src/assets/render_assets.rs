@@ -1,6 +1,6 @@
 //! Extract the resources from the scene and load them to the GPU in the renderer.
 
-use bevy::{app::{App, Plugin}, ecs::{schedule::SystemConfigs, system::{StaticSystemParam, SystemParam, SystemParamItem, SystemState}, world}, prelude::*, utils::{HashMap, HashSet}};
+use bevy::{app::{App, Plugin}, asset::UntypedAssetId, ecs::{schedule::SystemConfigs, system::{StaticSystemParam, SystemParam, SystemParamItem, SystemState}, world}, prelude::*, utils::{HashMap, HashSet}};
 use thiserror::Error;
 
 use crate::core::{Extract, MainWorld, Render, RenderApp, RenderSet};
@@ -23,6 +23,10 @@ pub trait RenderAsset: Send + Sync + 'static + Sized {
     type SourceAsset: Asset + Clone;
     type Param: SystemParam;
 
+    /// Whether the asset is unloaded from the GPU while its upload is cancelled (default: false).
+    /// The CPU side of the loaded assets is then kept in the render world, to upload them again once restored.
+    const UNLOAD_CANCELLED: bool = false;
+
     /// Load the asset to the GPU from the CPU.
     fn prepare_asset(
         asset: Self::SourceAsset,
@@ -104,6 +108,34 @@ impl<A: RenderAsset> Default for PrepareNextFrameAssets<A> {
 }
 
 
+#[derive(Resource)]
+/// List of assets whose upload was cancelled, kept until their upload is restored or they are removed.
+struct CancelledAssets<A: RenderAsset> {
+    assets: Vec<(AssetId<A::SourceAsset>, A::SourceAsset)>
+}
+impl<A: RenderAsset> Default for CancelledAssets<A> {
+    fn default() -> Self {
+        Self {
+            assets: Default::default()
+        }
+    }
+}
+
+
+#[derive(Resource)]
+/// The CPU side of the loaded assets, kept to upload them again once unloaded if `UNLOAD_CANCELLED` is true.
+struct LoadedSources<A: RenderAsset> {
+    assets: HashMap<AssetId<A::SourceAsset>, A::SourceAsset>
+}
+impl<A: RenderAsset> Default for LoadedSources<A> {
+    fn default() -> Self {
+        Self {
+            assets: Default::default()
+        }
+    }
+}
+
+
 /// Stores all GPU representations of the assets.
 #[derive(Resource)]
 pub struct RenderAssets<A: RenderAsset>(HashMap<AssetId<A::SourceAsset>, A>);
@@ -143,6 +175,32 @@ impl<A: RenderAsset> RenderAssets<A> {
 
 
 
+/// List of assets whose upload to the GPU was cancelled before they were prepared.
+/// This is used to avoid uploading assets whose owners were despawned before the asset finished loading.
+/// The CPU side of a cancelled asset is kept by the prepare system, and uploaded once the asset is restored.
+/// The assets already loaded are unloaded from the GPU if their `RenderAsset::UNLOAD_CANCELLED` is true.
+/// An asset stays cancelled until it is removed or restored.
+#[derive(Resource, Default)]
+pub struct CancelledHandles(HashSet<UntypedAssetId>);
+impl CancelledHandles {
+    /// Cancel the upload of the asset.
+    pub fn cancel(&mut self, id: impl Into<UntypedAssetId>) {
+        self.0.insert(id.into());
+    }
+
+    /// Restore the upload of a previously cancelled asset.
+    pub fn restore(&mut self, id: impl Into<UntypedAssetId>) {
+        self.0.remove(&id.into());
+    }
+
+    /// Check if the upload of the asset is cancelled.
+    pub fn contains(&self, id: impl Into<UntypedAssetId>) -> bool {
+        self.0.contains(&id.into())
+    }
+}
+
+
+
 /// Plugin that adds the render assets system to the renderer app.
 pub struct RenderAssetsPlugin<A: RenderAsset, AFTER: RenderAssetDependency + 'static = ()> {
     phantom: std::marker::PhantomData<fn() -> (A, AFTER)>
@@ -156,8 +214,11 @@ impl<A: RenderAsset, AFTER: RenderAssetDependency + 'static> Plugin for RenderAs
         let renderer_app = app.get_sub_app_mut(RenderApp).unwrap();
         renderer_app
             .init_resource::<PrepareNextFrameAssets<A>>()
+            .init_resource::<CancelledAssets<A>>()
+            .init_resource::<LoadedSources<A>>()
             .init_resource::<ExtractedAssets<A>>()
             .init_resource::<RenderAssets<A>>()
+            .init_resource::<CancelledHandles>()
             .add_systems(Extract, extract_render_assets::<A>);
 
         // Add the prepare system to the renderer app
@@ -228,10 +289,35 @@ fn prepare_assets<A: RenderAsset>(
     mut extracted_assets: ResMut<ExtractedAssets<A>>,
     mut render_assets: ResMut<RenderAssets<A>>,
     mut prepare_next_frame: ResMut<PrepareNextFrameAssets<A>>,
+    mut cancelled_assets: ResMut<CancelledAssets<A>>,
+    mut loaded_sources: ResMut<LoadedSources<A>>,
+    mut cancelled_handles: ResMut<CancelledHandles>,
     param: StaticSystemParam<<A as RenderAsset>::Param>
 ) {
     let mut param = param.into_inner();
-    let queued_assets = std::mem::take(&mut prepare_next_frame.assets);
+    let mut queued_assets = std::mem::take(&mut prepare_next_frame.assets);
+
+    // Unload the loaded assets whose upload was cancelled, keeping their CPU side until they are restored
+    if A::UNLOAD_CANCELLED {
+        let unloaded: Vec<_> = loaded_sources.assets.keys().filter(|id| cancelled_handles.contains(**id)).copied().collect();
+        for id in unloaded {
+            debug!("Unloading cancelled asset of type {} with id {}.", std::any::type_name::<A::SourceAsset>(), id);
+            render_assets.remove(id);
+            cancelled_assets.assets.push((id, loaded_sources.assets.remove(&id).unwrap()));
+        }
+    }
+
+    // Queue the cancelled assets that were restored, dropping the ones removed or updated
+    for (id, extracted_asset) in std::mem::take(&mut cancelled_assets.assets) {
+        if extracted_assets.removed.contains(&id) || extracted_assets.added.contains(&id) {
+            continue;
+        }
+        if cancelled_handles.contains(id) {
+            cancelled_assets.assets.push((id, extracted_asset));
+        } else {
+            queued_assets.push((id, extracted_asset));
+        }
+    }
 
     // Initialize the render assets from the previous frame that have not been finalized yet
     for (id, extracted_asset) in queued_assets {
@@ -240,11 +326,22 @@ fn prepare_assets<A: RenderAsset>(
             continue;
         }
 
+        // Keep the assets whose upload was cancelled until they are restored
+        if cancelled_handles.contains(id) {
+            debug!("Deferring cancelled asset of type {} with id {}.", std::any::type_name::<A::SourceAsset>(), id);
+            cancelled_assets.assets.push((id, extracted_asset));
+            continue;
+        }
+
         // Load the asset to the GPU from the CPU
+        let source = A::UNLOAD_CANCELLED.then(|| extracted_asset.clone());
         match A::prepare_asset(extracted_asset, &mut param) {
             Ok(prepared_asset) => {
                 // Add the asset to the render world
                 render_assets.insert(id, prepared_asset);
+                if let Some(source) = source {
+                    loaded_sources.assets.insert(id, source);
+                }
             }
             Err(PrepareAssetError::RetryNextUpdate(extracted_asset)) => {
                 // Try again next frame
@@ -266,17 +363,31 @@ fn prepare_assets<A: RenderAsset>(
         };
         debug!("Removing asset of type {} labeled {}.", std::any::type_name::<A::SourceAsset>(), label);
         render_assets.remove(removed);
+        loaded_sources.assets.remove(&removed);
+        cancelled_handles.restore(removed);
     }
 
     // Update changed assets
     for (id, extracted_asset) in extracted_assets.extracted.drain(..) {
         render_assets.remove(id);
+        loaded_sources.assets.remove(&id);
+
+        // Keep the assets whose upload was cancelled until they are restored
+        if cancelled_handles.contains(id) {
+            debug!("Deferring cancelled asset of type {} with id {}.", std::any::type_name::<A::SourceAsset>(), id);
+            cancelled_assets.assets.push((id, extracted_asset));
+            continue;
+        }
 
         // Load the asset to the GPU from the CPU
+        let source = A::UNLOAD_CANCELLED.then(|| extracted_asset.clone());
         match A::prepare_asset(extracted_asset, &mut param) {
             Ok(prepared_asset) => {
                 // Add the asset to the render world
                 render_assets.insert(id, prepared_asset);
+                if let Some(source) = source {
+                    loaded_sources.assets.insert(id, source);
+                }
             }
             Err(PrepareAssetError::RetryNextUpdate(extracted_asset)) => {
                 // Try again next frame
@@ -289,3 +400,126 @@ fn prepare_assets<A: RenderAsset>(
         }
     }
 }
+
+
+#[cfg(test)]
+mod tests {
+    use bevy::{asset::AssetIndex, ecs::system::RunSystemOnce, prelude::*, utils::HashSet};
+
+    use super::*;
+
+    #[derive(Asset, TypePath, Clone)]
+    struct TestAsset;
+    struct GpuTestAsset;
+    impl RenderAsset for GpuTestAsset {
+        type SourceAsset = TestAsset;
+        type Param = ();
+
+        const UNLOAD_CANCELLED: bool = true;
+
+        fn prepare_asset(_asset: TestAsset, _param: &mut ()) -> Result<Self, PrepareAssetError<TestAsset>> {
+            Ok(GpuTestAsset)
+        }
+
+        fn label(&self) -> &str {
+            "test"
+        }
+    }
+
+    fn asset_id(index: u64) -> AssetId<TestAsset> {
+        AssetId::Index { index: AssetIndex::from_bits(index), marker: default() }
+    }
+
+    fn render_world() -> World {
+        let mut world = World::new();
+        world.init_resource::<ExtractedAssets<GpuTestAsset>>();
+        world.init_resource::<RenderAssets<GpuTestAsset>>();
+        world.init_resource::<PrepareNextFrameAssets<GpuTestAsset>>();
+        world.init_resource::<CancelledAssets<GpuTestAsset>>();
+        world.init_resource::<LoadedSources<GpuTestAsset>>();
+        world.init_resource::<CancelledHandles>();
+        world
+    }
+
+    /// Run the prepare system on the assets added and removed this frame.
+    fn prepare(world: &mut World, added: &[AssetId<TestAsset>], removed: &[AssetId<TestAsset>]) {
+        world.insert_resource(ExtractedAssets::<GpuTestAsset> {
+            added: added.iter().copied().collect(),
+            removed: removed.iter().copied().collect::<HashSet<_>>(),
+            extracted: added.iter().map(|id| (*id, TestAsset)).collect()
+        });
+        world.run_system_once(prepare_assets::<GpuTestAsset>).unwrap();
+    }
+
+    fn loaded(world: &World) -> usize {
+        world.resource::<RenderAssets<GpuTestAsset>>().iter().count()
+    }
+
+    #[test]
+    fn unused_assets_are_freed_and_uploaded_again() {
+        let mut world = render_world();
+        let ids: Vec<_> = (0..1000).map(asset_id).collect();
+
+        // Load and free the same assets every frame
+        for _ in 0..100 {
+            prepare(&mut world, &ids, &[]);
+            assert_eq!(loaded(&world), ids.len());
+            prepare(&mut world, &[], &ids);
+            assert_eq!(loaded(&world), 0);
+        }
+    }
+
+    #[test]
+    fn cancelled_assets_are_uploaded_once_restored() {
+        let mut world = render_world();
+        let id = asset_id(0);
+
+        // The cancelled asset is kept without being uploaded
+        world.resource_mut::<CancelledHandles>().cancel(id);
+        prepare(&mut world, &[id], &[]);
+        prepare(&mut world, &[], &[]);
+        assert_eq!(loaded(&world), 0);
+
+        // It is uploaded once restored
+        world.resource_mut::<CancelledHandles>().restore(id);
+        prepare(&mut world, &[], &[]);
+        assert_eq!(loaded(&world), 1);
+    }
+
+    #[test]
+    fn cancelled_assets_are_dropped_once_removed() {
+        let mut world = render_world();
+        let id = asset_id(0);
+
+        world.resource_mut::<CancelledHandles>().cancel(id);
+        prepare(&mut world, &[id], &[]);
+        prepare(&mut world, &[], &[id]);
+        assert!(!world.resource::<CancelledHandles>().contains(id));
+        assert!(world.resource::<CancelledAssets<GpuTestAsset>>().assets.is_empty());
+        assert_eq!(loaded(&world), 0);
+    }
+
+    #[test]
+    fn cancelled_assets_are_unloaded_until_restored() {
+        let mut world = render_world();
+        let id = asset_id(0);
+        prepare(&mut world, &[id], &[]);
+        assert_eq!(loaded(&world), 1);
+
+        // The loaded asset is unloaded once cancelled
+        world.resource_mut::<CancelledHandles>().cancel(id);
+        prepare(&mut world, &[], &[]);
+        assert_eq!(loaded(&world), 0);
+        assert_eq!(world.resource::<CancelledAssets<GpuTestAsset>>().assets.len(), 1);
+
+        // It is uploaded again once restored
+        world.resource_mut::<CancelledHandles>().restore(id);
+        prepare(&mut world, &[], &[]);
+        assert_eq!(loaded(&world), 1);
+
+        // The kept CPU side is dropped once removed
+        prepare(&mut world, &[], &[id]);
+        assert!(world.resource::<LoadedSources<GpuTestAsset>>().assets.is_empty());
+        assert_eq!(loaded(&world), 0);
+    }
+}
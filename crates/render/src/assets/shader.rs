@@ -54,3 +54,44 @@ impl AssetLoader for ShaderLoader {
     }
 }
 
+
+
+/// Vertex stage of the error shader, covering the screen without reading any vertex attribute.
+const ERROR_SHADER_VERT: &str = "
+@vertex
+fn main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+";
+
+/// Fragment stage of the error shader, rendering a bright pink color.
+const ERROR_SHADER_FRAG: &str = "
+@fragment
+fn main() -> @location(0) vec4<f32> {
+    return vec4<f32>(1.0, 0.0, 1.0, 1.0);
+}
+";
+
+/// Compute stage of the error shader, doing nothing.
+const ERROR_SHADER_COMP: &str = "
+@compute @workgroup_size(1)
+fn main() {}
+";
+
+/// Minimal valid shaders used by the pipelines in place of the shaders that failed to load.
+#[derive(Resource, Clone)]
+pub struct ErrorShader {
+    pub vert: Shader,
+    pub frag: Shader,
+    pub comp: Shader
+}
+impl Default for ErrorShader {
+    fn default() -> Self {
+        Self {
+            vert: Shader { content: ERROR_SHADER_VERT.to_string() },
+            frag: Shader { content: ERROR_SHADER_FRAG.to_string() },
+            comp: Shader { content: ERROR_SHADER_COMP.to_string() }
+        }
+    }
+}
@@ -1,8 +1,10 @@
+use std::path::Path;
+
 use bevy::{asset::{io::Reader, AssetLoader, LoadContext}, ecs::system::lifetimeless::SRes, prelude::*, utils::tracing::error};
 use image::GenericImageView;
 use thiserror::Error;
 use serde::{Deserialize, Serialize};
-use wde_wgpu::{instance::WRenderInstance, texture::{WTextureFormat, WTextureUsages}};
+use wde_wgpu::{instance::WRenderInstance, texture::{WSamplerDescriptor, WTexture, WTextureFormat, WTextureUsages}};
 
 use super::render_assets::{PrepareAssetError, RenderAsset};
 
@@ -13,7 +15,14 @@ pub struct Texture {
     pub size: (u32, u32),
     pub format: WTextureFormat,
     pub usages: WTextureUsages,
-    pub data: Vec<u8>
+    pub data: Vec<u8>,
+    /// Whether to generate the mip levels of the texture from its data, see `WTexture::mipmap_usages` for the supported formats.
+    pub generate_mipmaps: bool,
+    /// The sampler of the texture, used by the bind groups sampling it.
+    pub sampler: WSamplerDescriptor,
+    /// The number of samples per texel, above 1 for the multisampled render targets (by default 1).
+    /// The multisampled textures have no data nor mip levels.
+    pub sample_count: u32
 }
 impl Default for Texture {
     fn default() -> Self {
@@ -22,7 +31,10 @@ impl Default for Texture {
             size: (1, 1),
             format: WTextureFormat::Rgba8Unorm,
             usages: WTextureUsages::TEXTURE_BINDING,
-            data: Vec::new()
+            data: Vec::new(),
+            generate_mipmaps: false,
+            sampler: WSamplerDescriptor::default(),
+            sample_count: 1
         }
     }
 }
@@ -37,7 +49,11 @@ pub struct TextureLoaderSettings {
     /// The format of the texture (by default RGBA8Unorm).
     pub format: WTextureFormat,
     /// The usages of the texture (by default TEXTURE_BINDING).
-    pub usages: WTextureUsages
+    pub usages: WTextureUsages,
+    /// Whether to generate the mip levels of the texture (by default false).
+    pub generate_mipmaps: bool,
+    /// The sampler of the texture (by default linear and clamped to the edges).
+    pub sampler: WSamplerDescriptor
 }
 
 impl Default for TextureLoaderSettings {
@@ -45,7 +61,9 @@ impl Default for TextureLoaderSettings {
         Self {
             label: "texture".to_string(),
             format: WTextureFormat::Rgba8Unorm,
-            usages: WTextureUsages::TEXTURE_BINDING
+            usages: WTextureUsages::TEXTURE_BINDING,
+            generate_mipmaps: false,
+            sampler: WSamplerDescriptor::default()
         }
     }
 }
@@ -56,6 +74,14 @@ pub enum TextureLoaderError {
     Io(#[from] std::io::Error),
 }
 
+#[derive(Debug, Error)]
+pub enum TextureSaveError {
+    #[error("Cannot save a texture of format {0:?} to a png file")]
+    UnsupportedFormat(WTextureFormat),
+    #[error("Could not save texture: {0}")]
+    Image(#[from] image::ImageError),
+}
+
 impl AssetLoader for TextureLoader {
     type Asset = Texture;
     type Settings = TextureLoaderSettings;
@@ -97,7 +123,10 @@ impl AssetLoader for TextureLoader {
             format: settings.format,
             usages: settings.usages,
             size,
-            data
+            data,
+            generate_mipmaps: settings.generate_mipmaps,
+            sampler: settings.sampler,
+            sample_count: 1
         })
     }
 
@@ -112,6 +141,52 @@ pub struct GpuTexture {
     pub label: String,
     pub texture: wde_wgpu::texture::WTexture,
 }
+impl GpuTexture {
+    /// Read back the texture and save it to a png file, blocking until the texture is read.
+    /// The 8 bits formats are saved as is, the float formats are clamped to [0, 1].
+    /// Note that the texture must have the COPY_SRC usage.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `render_instance` - The render instance.
+    /// * `path` - The path of the png file.
+    /// 
+    /// # Errors
+    /// 
+    /// * `TextureSaveError::UnsupportedFormat` - The format of the texture has no natural png encoding.
+    /// * `TextureSaveError::Image` - The png file could not be written.
+    pub fn save_to_png(&self, render_instance: &WRenderInstance, path: &Path) -> Result<(), TextureSaveError> {
+        let format = self.texture.format;
+        if !matches!(format,
+            WTextureFormat::R8Unorm | WTextureFormat::Rgba8Unorm | WTextureFormat::Rgba8UnormSrgb
+            | WTextureFormat::Bgra8Unorm | WTextureFormat::Bgra8UnormSrgb
+            | WTextureFormat::Rgba16Float | WTextureFormat::Rgba32Float) {
+            return Err(TextureSaveError::UnsupportedFormat(format));
+        }
+        debug!(self.label, "Saving texture to {}.", path.display());
+
+        let pixels = {
+            let render_instance = render_instance.data.read().unwrap();
+            self.texture.read_pixels(&render_instance).ok_or(TextureSaveError::UnsupportedFormat(format))?
+        };
+
+        // Convert the pixels to 8 bits rgba
+        let to_u8 = |value: f32| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+        let rgba: Vec<u8> = match format {
+            WTextureFormat::R8Unorm => pixels.iter().flat_map(|&r| [r, r, r, 255]).collect(),
+            WTextureFormat::Bgra8Unorm | WTextureFormat::Bgra8UnormSrgb => pixels.chunks_exact(4)
+                .flat_map(|bgra| [bgra[2], bgra[1], bgra[0], bgra[3]]).collect(),
+            WTextureFormat::Rgba16Float => pixels.chunks_exact(2)
+                .map(|value| to_u8(f16_to_f32(u16::from_le_bytes([value[0], value[1]])))).collect(),
+            WTextureFormat::Rgba32Float => pixels.chunks_exact(4)
+                .map(|value| to_u8(f32::from_le_bytes([value[0], value[1], value[2], value[3]]))).collect(),
+            _ => pixels
+        };
+
+        image::save_buffer(path, &rgba, self.texture.size.0, self.texture.size.1, image::ExtendedColorType::Rgba8)?;
+        Ok(())
+    }
+}
 impl RenderAsset for GpuTexture {
     type SourceAsset = Texture;
     type Param = SRes<WRenderInstance<'static>>;
@@ -124,16 +199,48 @@ impl RenderAsset for GpuTexture {
 
         let render_instance = render_instance.data.as_ref().read().unwrap();
 
+        // Create the multisampled render targets
+        if asset.sample_count > 1 {
+            let texture = WTexture::new_multisampled(
+                &render_instance, &asset.label, (asset.size.0, asset.size.1),
+                asset.format, asset.usages, asset.sample_count);
+            return Ok(GpuTexture { label: asset.label, texture });
+        }
+
+        // Add the usages required to generate the mip levels
+        let mut usages = asset.usages;
+        let mipmaps = asset.generate_mipmaps && !asset.data.is_empty();
+        let mipmaps = mipmaps && match WTexture::mipmap_usages(asset.format) {
+            Some(mipmap_usages) => {
+                usages |= mipmap_usages;
+                true
+            },
+            None => {
+                warn!(asset.label, "Cannot generate the mip levels of a texture of format {:?}.", asset.format);
+                false
+            }
+        };
+
         // Create the texture
-        let texture = wde_wgpu::texture::WTexture::new(
+        let mut texture = WTexture::new(
             &render_instance, &asset.label, (asset.size.0, asset.size.1),
-            asset.format, asset.usages);
+            asset.format, usages, if mipmaps { None } else { Some(1) });
+        if asset.sampler != WSamplerDescriptor::default() {
+            texture.set_sampler(&render_instance, &asset.sampler);
+        }
 
         // Copy the texture data
         if !asset.data.is_empty() {
             texture.copy_from_buffer(&render_instance, asset.format, &asset.data);
         }
 
+        // Generate the mip levels from the data
+        if mipmaps {
+            if let Err(e) = texture.generate_mipmaps(&render_instance) {
+                error!(asset.label, "Failed to generate the mip levels of the texture: {:?}.", e);
+            }
+        }
+
         Ok(GpuTexture { label: asset.label, texture })
     }
 
@@ -199,3 +306,15 @@ fn from_channels<T: Clone + Copy + bytemuck::NoUninit + bytemuck::Pod>(data: &[T
     }
     buffer
 }
+
+/// Convert a half precision float to a single precision float.
+fn f16_to_f32(value: u16) -> f32 {
+    let sign = if value & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exponent = ((value >> 10) & 0x1f) as i32;
+    let mantissa = (value & 0x3ff) as f32;
+    match exponent {
+        0 => sign * mantissa * 2f32.powi(-24),
+        0x1f => if mantissa == 0.0 { sign * f32::INFINITY } else { f32::NAN },
+        _ => sign * (1.0 + mantissa / 1024.0) * 2f32.powi(exponent - 15)
+    }
+}
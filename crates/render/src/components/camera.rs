@@ -24,6 +24,41 @@ impl Default for CameraView {
         }
     }
 }
+impl CameraView {
+    /// Get the six frustum planes of the camera in world space, in the order left, right, bottom, top, near, far.
+    /// Each plane is stored as `(normal, distance)` with a normalized normal facing towards the inside of the frustum,
+    /// so a point `p` is inside the frustum if `plane.xyz.dot(p) + plane.w >= 0` for all six planes.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `transform` - The transform of the camera.
+    /// * `aspect_ratio` - The aspect ratio of the screen.
+    pub fn frustum_planes(&self, transform: &Transform, aspect_ratio: f32) -> [Vec4; 6] {
+        Self::frustum_planes_of(&CameraUniform::get_world_to_ndc(transform, self, aspect_ratio))
+    }
+
+    /// Get the six frustum planes of a world to ndc matrix, e.g. of a light, in the same order and convention as `frustum_planes`.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `world_to_ndc` - The world to ndc matrix, with a depth range of [0, 1].
+    pub fn frustum_planes_of(world_to_ndc: &Mat4) -> [Vec4; 6] {
+        let (row_x, row_y, row_z, row_w) = (
+            world_to_ndc.row(0), world_to_ndc.row(1),
+            world_to_ndc.row(2), world_to_ndc.row(3)
+        );
+
+        // Extract the planes from the rows of the matrix (the depth range of wgpu is [0, 1])
+        [
+            row_w + row_x, // Left
+            row_w - row_x, // Right
+            row_w + row_y, // Bottom
+            row_w - row_y, // Top
+            row_z,         // Near
+            row_w - row_z  // Far
+        ].map(|plane| plane / plane.truncate().length())
+    }
+}
 
 /// Camera is defined by a position and a view.
 #[derive(Component, Default, Clone, Debug, Reflect)]
@@ -106,3 +141,60 @@ impl CameraUniform {
         Self::get_world_to_ndc(transform, camera_view, aspect_ratio).inverse()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_inside(planes: &[Vec4; 6], point: Vec3) -> bool {
+        planes.iter().all(|plane| plane.truncate().dot(point) + plane.w >= 0.0)
+    }
+
+    fn view() -> CameraView {
+        CameraView { fov: 90.0, znear: 0.1, zfar: 100.0 }
+    }
+
+    #[test]
+    fn frustum_planes_are_normalized() {
+        let transform = Transform::from_xyz(1.0, 2.0, 3.0).looking_at(Vec3::new(-4.0, 0.5, 7.0), Vec3::Y);
+        for plane in view().frustum_planes(&transform, 16.0 / 9.0) {
+            assert!((plane.truncate().length() - 1.0).abs() < 1e-5, "plane {:?} is not normalized", plane);
+        }
+    }
+
+    #[test]
+    fn frustum_planes_classify_points() {
+        // Looking towards -z, with a 90 degrees field of view the frustum is 10 units wide on each side at z = -10
+        let planes = view().frustum_planes(&Transform::IDENTITY, 1.0);
+        for point in [Vec3::new(0.0, 0.0, -10.0), Vec3::new(9.0, -9.0, -10.0), Vec3::new(0.0, 0.0, -0.2), Vec3::new(0.0, 0.0, -99.0)] {
+            assert!(is_inside(&planes, point), "{} should be inside the frustum", point);
+        }
+        for point in [
+            Vec3::new(11.0, 0.0, -10.0), Vec3::new(-11.0, 0.0, -10.0), // Right and left
+            Vec3::new(0.0, 11.0, -10.0), Vec3::new(0.0, -11.0, -10.0), // Top and bottom
+            Vec3::new(0.0, 0.0, -0.05), Vec3::new(0.0, 0.0, -101.0),   // Near and far
+            Vec3::new(0.0, 0.0, 10.0)                                  // Behind
+        ] {
+            assert!(!is_inside(&planes, point), "{} should be outside the frustum", point);
+        }
+    }
+
+    #[test]
+    fn frustum_planes_follow_the_camera() {
+        let transform = Transform::from_xyz(50.0, 0.0, 0.0).looking_at(Vec3::new(50.0, 0.0, 10.0), Vec3::Y);
+        let planes = view().frustum_planes(&transform, 1.0);
+        assert!(is_inside(&planes, Vec3::new(50.0, 0.0, 10.0)));
+        assert!(!is_inside(&planes, Vec3::new(50.0, 0.0, -10.0)));
+        assert!(!is_inside(&planes, Vec3::new(0.0, 0.0, -10.0)));
+    }
+
+    #[test]
+    fn frustum_planes_of_orthographic_projection() {
+        let planes = CameraView::frustum_planes_of(&Mat4::orthographic_rh(-1.0, 1.0, -2.0, 2.0, 0.0, 10.0));
+        assert!(is_inside(&planes, Vec3::new(0.9, 1.9, -5.0)));
+        assert!(!is_inside(&planes, Vec3::new(1.1, 0.0, -5.0)));
+        assert!(!is_inside(&planes, Vec3::new(0.0, 2.1, -5.0)));
+        assert!(!is_inside(&planes, Vec3::new(0.0, 0.0, 1.0)));
+        assert!(!is_inside(&planes, Vec3::new(0.0, 0.0, -11.0)));
+    }
+}
@@ -1,9 +1,11 @@
 //! Camera controller based on bevy's first person camera controller.
 //! @see https://github.com/bevyengine/bevy/blob/8de15ae71a23ce2eb272a7036b4ae82649f09634/examples/helpers/camera_controller.rs
 
-use bevy::{input::mouse::{MouseMotion, MouseScrollUnit, MouseWheel}, prelude::*, window::CursorGrabMode};
+use bevy::{input::mouse::{MouseMotion, MouseScrollUnit, MouseWheel}, prelude::*};
 use std::f32::consts::*;
 
+use crate::core::window::CursorGrab;
+
 use super::CameraView;
 
 /// Based on Valorant's default sensitivity, not entirely sure why it is exactly 1.0 / 180.0,
@@ -71,7 +73,7 @@ impl Default for CameraController {
 // Update the camera controller
 fn update(
     mut camera_query: Query<(&mut Transform, &mut CameraController), With<CameraView>>,
-    time: Res<Time>, mut windows: Query<&mut Window>,
+    time: Res<Time>, mut cursor_grab_state: ResMut<CursorGrab>,
     (keyboard_input, mouse_button_input): (Res<ButtonInput<KeyCode>>, Res<ButtonInput<MouseButton>>),
     (mut mouse_events, mut mouse_scroll_events): (EventReader<MouseMotion>, EventReader<MouseWheel>),
     (mut toggle_cursor_grab, mut mouse_cursor_grab): (Local<bool>, Local<bool>),
@@ -123,6 +125,12 @@ fn update(
             axis_input.y -= 1.0;
         }
 
+        // Forget the grab if the cursor was released, e.g. when the window lost the focus
+        if !cursor_grab_state.0 {
+            *toggle_cursor_grab = false;
+            *mouse_cursor_grab = false;
+        }
+
         let mut cursor_grab_change = false;
         if keyboard_input.just_pressed(controller.keyboard_key_toggle_cursor_grab) {
             *toggle_cursor_grab = !*toggle_cursor_grab;
@@ -161,24 +169,10 @@ fn update(
 
         // Handle cursor grab
         if cursor_grab_change {
-            if cursor_grab {
-                for mut window in &mut windows {
-                    if !window.focused {
-                        continue;
-                    }
-
-                    window.cursor_options.grab_mode = CursorGrabMode::Locked;
-                    window.cursor_options.visible = false;
-                }
-            } else {
-                for mut window in &mut windows {
-                    window.cursor_options.grab_mode = CursorGrabMode::None;
-                    window.cursor_options.visible = true;
-                }
-            }
+            cursor_grab_state.set_if_neq(CursorGrab(cursor_grab));
         }
 
-        // Handle mouse input
+        // Handle mouse input, using the raw deltas of the device which are not clamped at the window edges
         let mut mouse_delta = Vec2::ZERO;
         if cursor_grab {
             for mouse_event in mouse_events.read() {
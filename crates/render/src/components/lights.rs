@@ -2,6 +2,18 @@ use bevy::prelude::*;
 
 use crate::utils::Color;
 
+/// Get the distance at which the attenuation `1 / (constant + linear * d + quadratic * d^2)` falls below 1/256.
+fn attenuation_range(constant: f32, linear: f32, quadratic: f32) -> f32 {
+    let c = constant - 256.0;
+    if quadratic > 0.0 {
+        (-linear + (linear * linear - 4.0 * quadratic * c).sqrt()) / (2.0 * quadratic)
+    } else if linear > 0.0 {
+        -c / linear
+    } else {
+        f32::INFINITY
+    }
+}
+
 /// Default color values for the lights.
 const AMBIENT_DEFAULT:  f32 = 0.05;
 const DIFFUSE_DEFAULT:  f32 = 0.8;
@@ -19,7 +31,13 @@ pub struct DirectionalLight {
     /// Diffuse color of the light.
     pub diffuse:  Color,
     /// Specular color of the light.
-    pub specular: Color
+    pub specular: Color,
+
+    /// Whether the light casts shadows with cascaded shadow maps.
+    /// Only the first directional light casting shadows is rendered into the shadow maps.
+    pub cast_shadows: bool,
+    /// The number of shadow cascades splitting the view of the camera, from 1 to `MAX_SHADOW_CASCADES`.
+    pub shadow_cascades: u32
 }
 impl Default for DirectionalLight {
     fn default() -> Self {
@@ -28,7 +46,10 @@ impl Default for DirectionalLight {
 
             ambient:  Color::from_srgba(AMBIENT_DEFAULT,  AMBIENT_DEFAULT,  AMBIENT_DEFAULT,  1.0),
             diffuse:  Color::from_srgba(DIFFUSE_DEFAULT,  DIFFUSE_DEFAULT,  DIFFUSE_DEFAULT,  1.0),
-            specular: Color::from_srgba(SPECULAR_DEFAULT, SPECULAR_DEFAULT, SPECULAR_DEFAULT, 1.0)
+            specular: Color::from_srgba(SPECULAR_DEFAULT, SPECULAR_DEFAULT, SPECULAR_DEFAULT, 1.0),
+
+            cast_shadows: true,
+            shadow_cascades: 4
         }
     }
 }
@@ -54,9 +75,19 @@ pub struct PointLight {
     /// Linear attenuation factor of the light.
     pub linear:    f32,
     /// Quadratic attenuation factor of the light.
-    pub quadratic: f32
+    pub quadratic: f32,
+
+    /// Whether the light casts shadows with a cubemap shadow map.
+    /// The scene is rendered six times per shadow casting point light, so this is disabled by default.
+    pub cast_shadows: bool
 }
 impl PointLight {
+    /// The distance at which the light is attenuated below 1/256 of its intensity, i.e. at which it stops contributing.
+    pub fn range(&self) -> f32 {
+        attenuation_range(self.constant, self.linear, self.quadratic)
+    }
+
+
     /// Sets the attenuation factors of the light.
     /// If the range is not a value in {7, 13, 20, 32, 50, 65, 100, 160, 200, 325, 600, 3250},
     /// the method will return None. Note that most of the light will be already attenuated at 20%.
@@ -138,7 +169,9 @@ impl Default for PointLight {
 
             constant:  0.0,
             linear:    0.0,
-            quadratic: 0.0
+            quadratic: 0.0,
+
+            cast_shadows: false
         }.with_range(100.0).unwrap()
     }
 }
@@ -168,12 +201,21 @@ pub struct SpotLight {
     /// Quadratic attenuation factor of the light.
     pub quadratic: f32,
 
-    /// Inner cut-off angle of the light (in degrees): the angle at which the light starts to decay.
+    /// Inner cut-off angle of the light (in radians): the angle to the direction at which the light starts to decay.
     pub inner_cutoff:  f32,
-    /// Outer cut-off angle of the light (in degrees): the angle at which the light is completely attenuated.
-    pub outer_cutoff: f32
+    /// Outer cut-off angle of the light (in radians): the angle to the direction at which the light is completely attenuated.
+    /// The light decays smoothly between the inner and outer angles.
+    pub outer_cutoff: f32,
+
+    /// Whether the light casts shadows with a shadow map.
+    pub cast_shadows: bool
 }
 impl SpotLight {
+    /// The distance at which the light is attenuated below 1/256 of its intensity, i.e. at which it stops contributing.
+    pub fn range(&self) -> f32 {
+        attenuation_range(self.constant, self.linear, self.quadratic)
+    }
+
     /// Sets the attenuation factors of the light.
     /// If the range is not a value in {7, 13, 20, 32, 50, 65, 100, 160, 200, 325, 600, 3250},
     /// the method will return None.
@@ -259,7 +301,9 @@ impl Default for SpotLight {
             quadratic: 0.0,
 
             inner_cutoff: 0.0,
-            outer_cutoff: std::f32::consts::PI / 4.0
+            outer_cutoff: std::f32::consts::PI / 4.0,
+
+            cast_shadows: false
         }.with_range(50.0).unwrap()
     }
 }
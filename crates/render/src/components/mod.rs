@@ -4,11 +4,15 @@ mod transform;
 mod camera;
 mod camera_controller;
 mod lights;
+mod shadows;
+mod transparency;
 
 pub use transform::*;
 pub use camera::*;
 pub use camera_controller::*;
 pub use lights::*;
+pub use shadows::*;
+pub use transparency::*;
 
 pub struct RenderComponentsPlugin;
 impl Plugin for RenderComponentsPlugin {
@@ -24,7 +28,10 @@ impl Plugin for RenderComponentsPlugin {
             .register_type::<Camera>()
             .register_type::<DirectionalLight>()
             .register_type::<PointLight>()
-            .register_type::<SpotLight>();
+            .register_type::<SpotLight>()
+            .register_type::<CastShadows>()
+            .register_type::<ReceiveShadows>()
+            .register_type::<Transparent>();
     }
 }
 
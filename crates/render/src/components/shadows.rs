@@ -0,0 +1,23 @@
+use bevy::prelude::*;
+
+/// Whether the entity is rendered into the shadow maps.
+/// By default, every entity with a `Mesh` casts shadows.
+#[derive(Component, Clone, Copy, Debug, Reflect)]
+#[reflect(Component)]
+pub struct CastShadows(pub bool);
+impl Default for CastShadows {
+    fn default() -> Self {
+        Self(true)
+    }
+}
+
+/// Whether the lighting pass samples the shadow maps for the fragments of the entity.
+/// By default, every entity with a `Mesh` receives shadows.
+#[derive(Component, Clone, Copy, Debug, Reflect)]
+#[reflect(Component)]
+pub struct ReceiveShadows(pub bool);
+impl Default for ReceiveShadows {
+    fn default() -> Self {
+        Self(true)
+    }
+}
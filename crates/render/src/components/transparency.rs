@@ -0,0 +1,7 @@
+use bevy::prelude::*;
+
+/// Renders the entity with the order-independent transparency pass instead of the gbuffer,
+/// whatever the alpha mode of its material. The alpha of the albedo is used as the opacity.
+#[derive(Component, Clone, Copy, Debug, Default, Reflect)]
+#[reflect(Component)]
+pub struct Transparent;
@@ -2,11 +2,13 @@
 //! 
 //! Provides the [`ExtractWorld`] system parameter, which allows accessing data from the main world in the render world.
 //! Also provides the [`ExtractState`] system parameter state, which is used to manage the [`ExtractWorld`] system parameter.
+//! The [`ExtractWorldChanged`] system parameter and the [`ExtractChangedResourcePlugin`] allow copying heavy resources only when they changed.
+//! The [`RenderEventPlugin`] forwards the events sent in the render world to the main world.
 
 use bevy::{ecs::{component::Tick, system::{ReadOnlySystemParam, SystemMeta, SystemParam, SystemParamItem, SystemState}, world::unsafe_world_cell::UnsafeWorldCell}, prelude::*};
-use std::ops::{Deref, DerefMut};
+use std::{marker::PhantomData, ops::{Deref, DerefMut}};
 
-use super::MainWorld;
+use super::{Extract, MainWorld, RenderApp};
 
 /// Code by `Bevy`: https://github.com/bevyengine/bevy/blob/main/crates/bevy_render/src/extract_param.rs.
 /// 
@@ -31,8 +33,8 @@ use super::MainWorld;
 /// ## Examples
 ///
 /// ```
-/// use bevy_ecs::prelude::*;
-/// use bevy_render::ExtractWorld;
+/// use bevy::prelude::*;
+/// use wde_render::core::extract_macros::ExtractWorld;
 /// # #[derive(Component)]
 /// # struct Cloud;
 /// fn extract_clouds(mut commands: Commands, clouds: ExtractWorld<Query<Entity, With<Cloud>>>) {
@@ -132,3 +134,130 @@ where
         (&self.item).into_iter()
     }
 }
+
+
+
+/// A helper for accessing a [`MainWorld`] resource only on the frames it changed.
+///
+/// Unlike `ExtractWorld<Res<R>>`, this does not give access to the resource on frames where it was not
+/// added or changed since the last run of the system, so that the render world can keep its previous copy.
+///
+/// ## Examples
+///
+/// ```
+/// use bevy::prelude::*;
+/// use wde_render::core::extract_macros::ExtractWorldChanged;
+/// # #[derive(Resource, Clone)]
+/// # struct Settings;
+/// fn extract_settings(mut commands: Commands, settings: ExtractWorldChanged<Settings>) {
+///     if let Some(settings) = settings.get() {
+///         commands.insert_resource(settings.clone());
+///     }
+/// }
+/// ```
+#[derive(SystemParam)]
+pub struct ExtractWorldChanged<'w, 's, R: Resource> {
+    resource: ExtractWorld<'w, 's, Option<Res<'static, R>>>,
+}
+impl<R: Resource> ExtractWorldChanged<'_, '_, R> {
+    /// Returns the resource if it was added or changed since the last extraction, and `None` otherwise.
+    pub fn get(&self) -> Option<&R> {
+        self.resource.as_ref()
+            .filter(|resource| resource.is_changed())
+            .map(|resource| resource.as_ref())
+    }
+}
+
+/// Counts the number of frames a resource was copied to the render world or skipped because it did not change.
+/// Inserted in the render world by the [`ExtractChangedResourcePlugin`].
+#[derive(Resource)]
+pub struct TrackedResource<R: Resource> {
+    /// The number of frames the resource was copied to the render world.
+    pub extracted: u64,
+    /// The number of frames the resource was not copied because it did not change.
+    pub skipped: u64,
+    phantom: PhantomData<fn() -> R>
+}
+impl<R: Resource> Default for TrackedResource<R> {
+    fn default() -> Self {
+        Self { extracted: 0, skipped: 0, phantom: PhantomData }
+    }
+}
+
+/// Plugin that copies a resource from the main world to the render world only when it changed.
+/// On unchanged frames, the render world keeps its previous copy of the resource.
+pub struct ExtractChangedResourcePlugin<R: Resource + Clone> {
+    phantom: PhantomData<fn() -> R>
+}
+impl<R: Resource + Clone> Default for ExtractChangedResourcePlugin<R> {
+    fn default() -> Self {
+        Self { phantom: PhantomData }
+    }
+}
+impl<R: Resource + Clone> Plugin for ExtractChangedResourcePlugin<R> {
+    fn build(&self, app: &mut App) {
+        app.get_sub_app_mut(RenderApp).unwrap()
+            .init_resource::<TrackedResource<R>>()
+            .add_systems(Extract, extract_changed_resource::<R>);
+    }
+}
+
+/// Copy the resource to the render world if it changed in the main world.
+fn extract_changed_resource<R: Resource + Clone>(
+    mut commands: Commands, resource: ExtractWorldChanged<R>, mut tracked: ResMut<TrackedResource<R>>
+) {
+    match resource.get() {
+        Some(resource) => {
+            trace!("Extracting the changed resource {}.", std::any::type_name::<R>());
+            commands.insert_resource(resource.clone());
+            tracked.extracted += 1;
+        },
+        None => tracked.skipped += 1
+    }
+}
+
+
+/// Events sent by the render world systems, forwarded to the main world during the next extraction.
+/// Inserted in the render world by the [`RenderEventPlugin`]. The events are read in the main world with an `EventReader`.
+#[derive(Resource)]
+pub struct RenderEvents<E: Event> {
+    events: Vec<E>
+}
+impl<E: Event> Default for RenderEvents<E> {
+    fn default() -> Self {
+        Self { events: Vec::new() }
+    }
+}
+impl<E: Event> RenderEvents<E> {
+    /// Send an event to the main world.
+    pub fn send(&mut self, event: E) {
+        self.events.push(event);
+    }
+}
+
+/// Plugin that forwards the events sent in the render world with [`RenderEvents`] to the main world.
+pub struct RenderEventPlugin<E: Event> {
+    phantom: PhantomData<fn() -> E>
+}
+impl<E: Event> Default for RenderEventPlugin<E> {
+    fn default() -> Self {
+        Self { phantom: PhantomData }
+    }
+}
+impl<E: Event> Plugin for RenderEventPlugin<E> {
+    fn build(&self, app: &mut App) {
+        app.add_event::<E>();
+        app.get_sub_app_mut(RenderApp).unwrap()
+            .init_resource::<RenderEvents<E>>()
+            .add_systems(Extract, forward_render_events::<E>);
+    }
+}
+
+/// Send the events of the render world to the main world.
+fn forward_render_events<E: Event>(mut events: ResMut<RenderEvents<E>>, mut main_world: ResMut<MainWorld>) {
+    if events.events.is_empty() {
+        return;
+    }
+    trace!("Forwarding {} render events {} to the main world.", events.events.len(), std::any::type_name::<E>());
+    main_world.send_event_batch(events.events.drain(..));
+}
@@ -6,21 +6,27 @@ pub mod extract;
 pub mod render_manager;
 pub mod extract_macros;
 pub mod render_multithread;
+pub mod msaa;
+pub mod pipeline_statistics;
 
-use bevy::{app::AppLabel, ecs::schedule::{ScheduleBuildSettings, ScheduleLabel}, prelude::*, tasks::futures_lite};
+use bevy::{app::AppLabel, ecs::schedule::{ScheduleBuildSettings, ScheduleLabel}, prelude::*, tasks::futures_lite, window::{ExitCondition, WindowPlugin}};
 use extract::{apply_extract_commands, main_extract};
-use render_manager::{init_main_world, init_surface, prepare, present};
+use render_manager::{init_headless_target, init_main_world, init_surface, prepare, prepare_headless, present, present_headless};
+use msaa::MsaaPlugin;
+use pipeline_statistics::PipelineStatisticsPlugin;
 use render_multithread::PipelinedRenderingPlugin;
-use wde_wgpu::instance::{create_instance, WLimits, WRenderTexture};
-use window::{extract_surface_size, send_surface_resized, SurfaceResized, WindowPlugins};
+use wde_wgpu::instance::{create_instance, WGrantedFeatures, WLimits, WRenderTexture};
+use window::{extract_surface_size, init_window_icon, send_surface_resized, send_window_state, should_render, toggle_fullscreen_on_key, update_cursor_grab, winit_settings, CursorGrab, SurfaceResized, WindowConfig, WindowPlugins, WindowState, WindowStateEvent};
 use std::ops::{Deref, DerefMut};
 
-use crate::{components:: RenderComponentsPlugin, features::RenderFeaturesPlugin, passes::{render_graph::RenderGraph, RendererPlugin}, pipelines::PipelineManagerPlugin};
+use extract_macros::ExtractChangedResourcePlugin;
+
+use crate::{components:: RenderComponentsPlugin, features::RenderFeaturesPlugin, passes::{render_graph::RenderGraph, RendererPlugin}, pipelines::{PipelineManagerPlugin, ShaderCompilationProgress}};
 
 
 /// Stores the main world for rendering as a resource.
 #[derive(Resource, Default)]
-pub(crate) struct MainWorld(World);
+pub(crate) struct MainWorld(pub(crate) World);
 
 impl Deref for MainWorld {
     type Target = World;
@@ -95,6 +101,28 @@ impl Render {
 pub struct DeviceLimits(pub WLimits);
 
 
+/// The output the renderer draws to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RenderMode {
+    /// Render to the surface of the primary window.
+    #[default]
+    Windowed,
+    /// Render without a window to an offscreen texture of the given size.
+    /// Each frame is copied to the buffer of the `HeadlessRenderTarget` resource of the render world.
+    Headless { width: u32, height: u32 },
+}
+
+/// The configuration of the renderer.
+/// It must be inserted before the `RenderPlugin` is added, as the window is only created in windowed mode.
+#[derive(Resource, Debug, Clone, Default)]
+pub struct RenderConfig {
+    pub mode: RenderMode,
+    /// The configuration of the window, unused in headless mode.
+    pub window: WindowConfig,
+    /// Whether the depth texture has a stencil aspect, see `DepthTexture::with_stencil`.
+    pub depth_stencil: bool,
+}
+
 #[derive(Resource, Default)]
 pub struct SwapchainFrame {
     pub data: Option<WRenderTexture>,
@@ -112,11 +140,32 @@ pub struct RenderCorePlugin;
 impl Plugin for RenderCorePlugin {
     fn build(&self, app: &mut App) {
         // === MAIN APP ===
+        let render_config = app.world().get_resource::<RenderConfig>().cloned().unwrap_or_default();
+        app.insert_resource(render_config.clone());
+
         // Add window
+        match render_config.mode {
+            RenderMode::Windowed => {
+                app
+                    .add_plugins(WindowPlugins)
+                    .insert_resource(winit_settings())
+                    .insert_resource(render_config.window.clone())
+                    .add_systems(Update, (init_window_icon, toggle_fullscreen_on_key, update_cursor_grab.after(send_window_state)));
+            },
+            RenderMode::Headless { .. } => {
+                app.add_plugins(WindowPlugin {
+                    primary_window: None,
+                    exit_condition: ExitCondition::DontExit,
+                    ..Default::default()
+                });
+            }
+        }
         app
-            .add_plugins(WindowPlugins)
             .add_event::<SurfaceResized>()
-            .add_systems(Update, send_surface_resized);
+            .add_event::<WindowStateEvent>()
+            .init_resource::<WindowState>()
+            .init_resource::<CursorGrab>()
+            .add_systems(Update, (send_surface_resized, send_window_state));
 
         // Add empty world component
         app.add_systems(Startup, init_main_world);
@@ -126,11 +175,13 @@ impl Plugin for RenderCorePlugin {
         // === RENDER APP ===
         let mut render_app = SubApp::new();
         let mut gpu_limits = None;
+        let mut granted_features = None;
         {
             // Create the wgpu instance
             render_app.insert_resource(futures_lite::future::block_on(async {
                 let instance = create_instance("wde_renderer", app).await;
                 gpu_limits = Some(instance.data.read().unwrap().device.limits());
+                granted_features = Some(WGrantedFeatures(instance.data.read().unwrap().device.features()));
                 instance
             }));
 
@@ -161,13 +212,27 @@ impl Plugin for RenderCorePlugin {
                 .init_resource::<RenderGraph>()
                 .add_systems(Render, RenderGraph::render.in_set(RenderSet::Render));
 
-            // Init wgpu instance
-            render_app.add_systems(Extract, (init_surface.run_if(run_once), extract_surface_size).chain());
-
-            // Add present system
+            // Init wgpu instance and add present system
+            render_app.insert_resource(render_config.clone());
+            match render_config.mode {
+                RenderMode::Windowed => {
+                    render_app
+                        .add_systems(Extract, (init_surface.run_if(run_once), extract_surface_size).chain())
+                        .add_systems(Render, prepare.in_set(RenderSet::Prepare).run_if(should_render))
+                        .add_systems(Render, present.in_set(RenderSet::Submit));
+                },
+                RenderMode::Headless { .. } => {
+                    render_app
+                        .add_systems(Extract, init_headless_target.run_if(run_once))
+                        .add_systems(Render, prepare_headless.in_set(RenderSet::Prepare).run_if(should_render))
+                        .add_systems(Render, present_headless.in_set(RenderSet::Submit));
+                }
+            }
+
+            // Skip the rendering while the window is minimized or unfocused
             render_app
-                .add_systems(Render, prepare.in_set(RenderSet::Prepare))
-                .add_systems(Render, present.in_set(RenderSet::Submit));
+                .init_resource::<WindowState>()
+                .configure_sets(Render, (RenderSet::Render, RenderSet::Submit).run_if(should_render));
 
             // Add render plugins
             render_app
@@ -176,11 +241,26 @@ impl Plugin for RenderCorePlugin {
 
         // Register the render app
         app.insert_sub_app(RenderApp, render_app);
+        app.add_plugins(ExtractChangedResourcePlugin::<WindowState>::default());
 
         // Add the GPU limits
         app.insert_resource(DeviceLimits(gpu_limits.as_ref().unwrap().clone()));
         app.get_sub_app_mut(RenderApp).unwrap().insert_resource(DeviceLimits(gpu_limits.unwrap()));
 
+        // Add the features enabled on the device
+        let granted_features = granted_features.unwrap();
+        app.insert_resource(granted_features);
+        app.get_sub_app_mut(RenderApp).unwrap().insert_resource(granted_features);
+
+        // Add the shader compilation progress
+        app.init_resource::<ShaderCompilationProgress>();
+
+        // Add the multi-sample anti-aliasing targets
+        app.add_plugins(MsaaPlugin);
+
+        // Add the pipeline statistics of the render passes
+        app.add_plugins(PipelineStatisticsPlugin);
+
         // Add the render pipeline plugins
         app
             .add_plugins(RendererPlugin)
@@ -0,0 +1,124 @@
+//! Multi-sample anti-aliasing of the passes rendering to the swapchain.
+//!
+//! The renderer only allocates the multisampled targets of the [`MsaaTextures`] resource, its own passes do not use them.
+//! A pass using the MSAA creates its pipelines with the sample count of the [`MsaaTextures`], renders to their color
+//! and depth targets, and resolves the color target into the swapchain (see the custom forward render example).
+
+use bevy::prelude::*;
+use wde_wgpu::texture::{WTexture, WTextureUsages};
+
+use crate::assets::Texture;
+
+use super::{extract_macros::ExtractWorld, window::SurfaceResized, Extract, RenderApp, RenderConfig, RenderMode};
+
+/// The global multi-sample anti-aliasing configuration.
+/// The multisampled targets are recreated when the sample count changes.
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MsaaSettings {
+    /// The number of samples per pixel: 1 (disabled), 2, 4 or 8.
+    /// Only 1 and 4 are supported by all the devices.
+    pub sample_count: u32,
+}
+impl Default for MsaaSettings {
+    fn default() -> Self {
+        Self { sample_count: 1 }
+    }
+}
+impl MsaaSettings {
+    /// Whether the MSAA is enabled.
+    pub fn is_enabled(&self) -> bool {
+        self.sample_count > 1
+    }
+}
+
+/// The multisampled color and depth targets of the size of the surface, when the MSAA is enabled.
+#[derive(Resource, Debug, Clone)]
+pub struct MsaaTextures {
+    /// The multisampled color target, in the swapchain format, to resolve into the swapchain.
+    pub color: Option<Handle<Texture>>,
+    /// The multisampled depth target.
+    pub depth: Option<Handle<Texture>>,
+    /// The sample count of the targets, 1 if the MSAA is disabled.
+    pub sample_count: u32,
+}
+impl Default for MsaaTextures {
+    fn default() -> Self {
+        Self { color: None, depth: None, sample_count: 1 }
+    }
+}
+impl MsaaTextures {
+    /// Create the targets when the settings change, and recreate them when the surface is resized.
+    fn update(
+        settings: Res<MsaaSettings>, config: Res<RenderConfig>, mut resized_events: EventReader<SurfaceResized>,
+        window: Query<&Window>, server: Res<AssetServer>, mut textures: ResMut<MsaaTextures>
+    ) {
+        let resized = resized_events.read().last().map(|event| (event.width, event.height));
+        if !settings.is_changed() && resized.is_none() {
+            return;
+        }
+
+        // Get the size of the surface
+        let size = resized.or_else(|| match config.mode {
+            RenderMode::Windowed => window.get_single().ok()
+                .map(|window| (window.resolution.physical_width(), window.resolution.physical_height())),
+            RenderMode::Headless { width, height } => Some((width, height))
+        });
+
+        // Check the sample count
+        let sample_count = match settings.sample_count {
+            1 | 2 | 4 | 8 => settings.sample_count,
+            sample_count => {
+                warn!("Unsupported MSAA sample count {}, the MSAA will be disabled.", sample_count);
+                1
+            }
+        };
+        let size = match size {
+            Some(size) if sample_count > 1 && size.0 > 0 && size.1 > 0 => size,
+            _ => {
+                *textures = MsaaTextures::default();
+                return;
+            }
+        };
+
+        // Create the targets
+        debug!("Creating the {}x{} MSAA targets with {} samples.", size.0, size.1, sample_count);
+        let color = server.add(Texture {
+            label: "msaa-color".to_string(),
+            size,
+            format: WTexture::SWAPCHAIN_FORMAT,
+            usages: WTextureUsages::RENDER_ATTACHMENT,
+            sample_count,
+            ..Default::default()
+        });
+        let depth = server.add(Texture {
+            label: "msaa-depth".to_string(),
+            size,
+            format: if config.depth_stencil { WTexture::DEPTH_STENCIL_FORMAT } else { WTexture::DEPTH_FORMAT },
+            usages: WTextureUsages::RENDER_ATTACHMENT,
+            sample_count,
+            ..Default::default()
+        });
+        *textures = MsaaTextures { color: Some(color), depth: Some(depth), sample_count };
+    }
+
+    /// Copy the targets to the render world.
+    fn extract(mut commands: Commands, textures: ExtractWorld<Res<MsaaTextures>>, settings: ExtractWorld<Res<MsaaSettings>>) {
+        commands.insert_resource(textures.clone());
+        commands.insert_resource(**settings);
+    }
+}
+
+pub(crate) struct MsaaPlugin;
+impl Plugin for MsaaPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<MsaaSettings>()
+            .init_resource::<MsaaTextures>()
+            .add_systems(Update, MsaaTextures::update);
+
+        app.get_sub_app_mut(RenderApp).unwrap()
+            .init_resource::<MsaaSettings>()
+            .init_resource::<MsaaTextures>()
+            .add_systems(Extract, MsaaTextures::extract);
+    }
+}
@@ -0,0 +1,164 @@
+//! Pipeline statistics of the render passes, counting the vertices and fragments they process.
+//!
+//! The render passes wrap their draws with [`PipelineStatisticsQueries::begin`] and [`PipelineStatisticsQueries::end`].
+//! The results are read back without blocking and exposed in the [`PipelineStatisticsReport`] resource
+//! of the main world, a few frames late. Nothing is counted if the device does not support pipeline statistics queries.
+
+use std::sync::Mutex;
+
+use bevy::prelude::*;
+use wde_wgpu::{buffer::{BufferUsage, WBuffer, WBufferMapStatus, WPendingBufferRead}, command_buffer::WCommandBuffer, instance::{WFeatures, WGrantedFeatures, WRenderInstance}, query_set::{WPipelineStatistics, WPipelineStatisticsQuerySet}, render_pass::WRenderPass};
+
+use super::{Extract, MainWorld, Render, RenderApp, RenderSet};
+
+/// The maximum number of render passes queried in a frame.
+pub const MAX_PIPELINE_STATISTICS_QUERIES: u32 = 64;
+
+/// The pipeline statistics of the render passes of the last read frame, by pass label.
+/// This resource is available in both the main world and the render world.
+#[derive(Resource, Debug, Clone, Default)]
+pub struct PipelineStatisticsReport {
+    /// The label and the statistics of each queried render pass, in render order.
+    pub passes: Vec<(String, WPipelineStatistics)>,
+}
+impl PipelineStatisticsReport {
+    /// Get the statistics of a render pass.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `label` - The label of the render pass.
+    pub fn get(&self, label: &str) -> Option<&WPipelineStatistics> {
+        self.passes.iter().find(|(pass, _)| pass == label).map(|(_, statistics)| statistics)
+    }
+}
+
+/// The pipeline statistics queries of the render passes of the frame.
+/// Only available in the render world, if the device supports pipeline statistics queries.
+#[derive(Resource)]
+pub struct PipelineStatisticsQueries {
+    pub query_set: WPipelineStatisticsQuerySet,
+    /// The buffer the results are copied to, to be read on the cpu.
+    readback_buffer: WBuffer,
+    /// The labels of the render passes queried this frame, in query order.
+    labels: Mutex<Vec<String>>,
+    /// The read of the results of the last queried frame, with the labels of its passes.
+    pending: Option<(WPendingBufferRead, Vec<String>)>,
+}
+impl PipelineStatisticsQueries {
+    /// Start counting the statistics of the next draws of a render pass.
+    /// Nothing is counted while the results of the last queried frame are not read.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `render_pass` - The render pass.
+    /// * `label` - The label of the render pass in the report.
+    /// 
+    /// # Returns
+    /// 
+    /// Whether the query was started, to give to `end`.
+    pub fn begin(&self, render_pass: &mut WRenderPass, label: &str) -> bool {
+        if self.pending.is_some() {
+            return false;
+        }
+        let mut labels = self.labels.lock().unwrap();
+        if labels.len() as u32 >= MAX_PIPELINE_STATISTICS_QUERIES {
+            return false;
+        }
+        render_pass.begin_pipeline_statistics_query(&self.query_set, labels.len() as u32);
+        labels.push(label.to_string());
+        true
+    }
+
+    /// Stop counting the statistics of a render pass.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `render_pass` - The render pass.
+    /// * `started` - Whether the query was started by `begin`.
+    pub fn end(&self, render_pass: &mut WRenderPass, started: bool) {
+        if started {
+            render_pass.end_pipeline_statistics_query();
+        }
+    }
+
+    /// Create the query set once the render instance is ready, if the device supports it.
+    fn create(mut commands: Commands, render_instance: Res<WRenderInstance<'static>>, features: Res<WGrantedFeatures>) {
+        if !features.has_feature(WFeatures::PIPELINE_STATISTICS_QUERY) {
+            return;
+        }
+        let render_instance = render_instance.data.read().unwrap();
+
+        let query_set = WPipelineStatisticsQuerySet::new(&render_instance, "render-passes", MAX_PIPELINE_STATISTICS_QUERIES);
+        let readback_buffer = WBuffer::new(&render_instance, "render-passes-pipeline-statistics",
+            WPipelineStatisticsQuerySet::results_size(MAX_PIPELINE_STATISTICS_QUERIES) as usize,
+            BufferUsage::MAP_READ | BufferUsage::COPY_DST, None);
+        commands.insert_resource(PipelineStatisticsQueries {
+            query_set,
+            readback_buffer,
+            labels: Mutex::new(Vec::new()),
+            pending: None,
+        });
+    }
+
+    /// Read the results of the last queried frame into the report once available,
+    /// then copy the results of this frame to be read in the next frames.
+    fn read_results(
+        queries: Option<ResMut<PipelineStatisticsQueries>>, mut report: ResMut<PipelineStatisticsReport>,
+        render_instance: Res<WRenderInstance<'static>>
+    ) {
+        let mut queries = match queries {
+            Some(queries) => queries,
+            None => return
+        };
+        let queries = &mut *queries;
+        let render_instance = render_instance.data.read().unwrap();
+
+        // Read the results of the last queried frame
+        if let Some((read, labels)) = queries.pending.as_ref() {
+            let status = queries.readback_buffer.poll_read_async(&render_instance, read, |view| {
+                report.passes = labels.iter().cloned()
+                    .zip(WPipelineStatistics::from_results(&view))
+                    .collect();
+            });
+            if status == WBufferMapStatus::Pending {
+                return;
+            }
+            queries.pending = None;
+        }
+
+        // Copy the results of this frame
+        let labels = std::mem::take(queries.labels.get_mut().unwrap());
+        if labels.is_empty() {
+            return;
+        }
+        let mut command_buffer = WCommandBuffer::new(&render_instance, "pipeline-statistics");
+        command_buffer.resolve_pipeline_statistics(&queries.query_set, 0..labels.len() as u32, &queries.readback_buffer);
+        command_buffer.submit(&render_instance);
+        let read = queries.readback_buffer.map_read_async(
+            WPipelineStatisticsQuerySet::results_size(labels.len() as u32) as usize);
+        queries.pending = Some((read, labels));
+    }
+
+    /// Copy the report to the main world.
+    fn extract_report(report: Res<PipelineStatisticsReport>, mut main_world: ResMut<MainWorld>) {
+        if let Some(mut main_report) = main_world.get_resource_mut::<PipelineStatisticsReport>() {
+            main_report.clone_from(&report);
+        }
+    }
+}
+
+pub(crate) struct PipelineStatisticsPlugin;
+impl Plugin for PipelineStatisticsPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<PipelineStatisticsReport>();
+
+        app.get_sub_app_mut(RenderApp).unwrap()
+            .init_resource::<PipelineStatisticsReport>()
+            .add_systems(Extract, PipelineStatisticsQueries::extract_report)
+            .add_systems(Render, (
+                PipelineStatisticsQueries::create.run_if(run_once).in_set(RenderSet::Prepare),
+                PipelineStatisticsQueries::read_results.in_set(RenderSet::Cleanup)
+            ));
+    }
+}
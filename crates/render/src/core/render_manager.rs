@@ -1,10 +1,13 @@
 //! Rendering system for the WDE renderer. Handle the initialization and presentation of the wgpu renderer.
 
 use bevy::window::{PrimaryWindow, RawHandleWrapperHolder};
-use bevy::prelude::*;
-use wde_wgpu::instance::{self, setup_surface, WRenderEvent, WRenderInstance};
+use bevy::{log::Level, prelude::*, utils::tracing::span};
+use wde_wgpu::buffer::{BufferUsage, WBuffer};
+use wde_wgpu::command_buffer::WCommandBuffer;
+use wde_wgpu::instance::{self, setup_headless, setup_surface, WRenderEvent, WRenderInstance, WRenderTexture, WSurfaceSource};
+use wde_wgpu::texture::{WTexture, WTextureFormat, WTextureUsages};
 
-use super::SwapchainFrame;
+use super::{RenderConfig, RenderMode, SwapchainFrame};
 
 use super::{extract_macros::ExtractWorld, EmptyWorld};
 
@@ -14,6 +17,46 @@ pub(crate) fn init_main_world(mut commands: Commands) {
     commands.init_resource::<EmptyWorld>();
 }
 
+/// The format of the offscreen texture rendered to in headless mode.
+/// The render pipelines target the swapchain format, so the offscreen texture uses it as well.
+/// Its pixels are 4 bytes wide, with the red and blue channels swapped compared to `Rgba8Unorm`.
+pub const HEADLESS_FORMAT: WTextureFormat = WTexture::SWAPCHAIN_FORMAT;
+
+/// The offscreen render target used instead of the window surface in headless mode.
+/// After each frame, the texture is copied to the `MAP_READ` buffer, which can be read back with `WBuffer::map_read`.
+#[derive(Resource)]
+pub struct HeadlessRenderTarget {
+    /// The texture the frame is rendered to.
+    pub texture: WTexture,
+    /// The buffer the frame is copied to. Each row of pixels is `padded_bytes_per_row` bytes long.
+    pub buffer: WBuffer,
+    /// The length of a row of pixels in the buffer, aligned to 256 bytes.
+    pub padded_bytes_per_row: u32,
+}
+
+/// Initialize the offscreen render target in headless mode.
+pub(crate) fn init_headless_target(mut commands: Commands, render_instance: Res<WRenderInstance<'static>>, render_config: Res<RenderConfig>) {
+    let (width, height) = match render_config.mode {
+        RenderMode::Headless { width, height } => (width.max(1), height.max(1)),
+        RenderMode::Windowed => return
+    };
+    trace!("Initializing {}x{} headless render target", width, height);
+
+    let mut render_instance = render_instance.data.write().unwrap();
+    let texture = WTexture::new(&render_instance, "headless-target", (width, height), HEADLESS_FORMAT,
+        WTextureUsages::RENDER_ATTACHMENT | WTextureUsages::COPY_SRC, Some(1));
+    let padded_bytes_per_row = WBuffer::padded_bytes_per_row(width, 4);
+    let buffer = WBuffer::new(&render_instance, "headless-target", (padded_bytes_per_row * height) as usize,
+        BufferUsage::MAP_READ | BufferUsage::COPY_DST, None);
+
+    // Describe the target as the surface configuration, as the render passes size their attachments with it.
+    // It is never used to configure a surface.
+    render_instance.surface_config = Some(setup_headless((width, height), HEADLESS_FORMAT));
+
+    commands.insert_resource(HeadlessRenderTarget { texture, buffer, padded_bytes_per_row });
+    commands.init_resource::<SwapchainFrame>();
+}
+
 /// Initialize the wgpu surface.
 pub(crate) fn init_surface(mut commands: Commands, mut render_instance: ResMut<WRenderInstance<'static>>, primary_window: ExtractWorld<Query<&RawHandleWrapperHolder, With<PrimaryWindow>>>, windows: ExtractWorld<Query<&Window>>) {
     trace!("Initializing wgpu surface");
@@ -25,10 +68,8 @@ pub(crate) fn init_surface(mut commands: Commands, mut render_instance: ResMut<W
             "Couldn't get the window handle in time for surface initialization.",
         );
         if let Some(wrapper) = window_handle.as_ref() {
-            let handle = unsafe { wrapper.get_handle() };
             Some(
-                render_instance.as_ref().data.read().unwrap().instance
-                    .create_surface(handle)
+                instance::create_surface(&render_instance.as_ref().data.read().unwrap().instance, WSurfaceSource::Window(wrapper.clone()))
                     .expect("Failed to create wgpu surface"),
             )
         } else {
@@ -53,6 +94,8 @@ pub(crate) fn init_surface(mut commands: Commands, mut render_instance: ResMut<W
 
 /// Prepare the rendering frame.
 pub(crate) fn prepare(mut swapchain_frame: ResMut<SwapchainFrame>, render_instance: Res<WRenderInstance<'static>>) {
+    let _prepare_span = span!(Level::INFO, "prepare").entered();
+
     // Wait for the surface to be initialized
     if render_instance.data.read().unwrap().surface.is_none() {
         debug!("Waiting for surface to be initialized.");
@@ -90,11 +133,48 @@ pub(crate) fn prepare(mut swapchain_frame: ResMut<SwapchainFrame>, render_instan
 
 /// Present the rendered frame to the screen.
 pub(crate) fn present(mut swapchain_frame: ResMut<SwapchainFrame>) {
-    let _ = instance::present(match swapchain_frame.data.take() {
-        Some(render_texture) => render_texture.texture,
+    let _present_span = span!(Level::INFO, "present").entered();
+
+    let _ = instance::present(match swapchain_frame.data.take().and_then(|render_texture| render_texture.texture) {
+        Some(surface_texture) => surface_texture,
         None => {
             error!("Failed to present frame: no render texture found.");
             return
         },
     });
 }
+
+/// Prepare the rendering frame in headless mode, rendering to the offscreen texture.
+pub(crate) fn prepare_headless(mut swapchain_frame: ResMut<SwapchainFrame>, target: Option<Res<HeadlessRenderTarget>>) {
+    let _prepare_span = span!(Level::INFO, "prepare_headless").entered();
+
+    // Wait for the target to be initialized
+    let Some(target) = target else {
+        debug!("Waiting for headless render target to be initialized.");
+        return
+    };
+
+    swapchain_frame.data = Some(WRenderTexture {
+        texture: None,
+        view: target.texture.view_mip_range(0, 1),
+    });
+}
+
+/// Copy the rendered frame to the readback buffer of the headless render target.
+pub(crate) fn present_headless(mut swapchain_frame: ResMut<SwapchainFrame>, target: Option<Res<HeadlessRenderTarget>>, render_instance: Res<WRenderInstance<'static>>) {
+    let _present_span = span!(Level::INFO, "present_headless").entered();
+
+    if swapchain_frame.data.take().is_none() {
+        error!("Failed to present frame: no render texture found.");
+        return
+    }
+    let Some(target) = target else {
+        return
+    };
+
+    // Copy the texture to the buffer
+    let render_instance = render_instance.data.read().unwrap();
+    let mut command_buffer = WCommandBuffer::new(&render_instance, "headless-readback");
+    command_buffer.copy_texture_to_padded_buffer(&target.texture.texture, &target.buffer, target.padded_bytes_per_row);
+    command_buffer.submit(&render_instance);
+}
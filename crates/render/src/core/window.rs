@@ -3,7 +3,10 @@
 //! This module contains the window plugin and related components.
 //! It is responsible for creating and managing the window.
 
-use bevy::{a11y::AccessibilityPlugin, app::{PluginGroup, PluginGroupBuilder}, prelude::{Event, EventReader, EventWriter, Query, ResMut}, utils::default, window::{PresentMode, Window, WindowPlugin, WindowResized, WindowTheme}, winit::WinitPlugin};
+use std::{path::{Path, PathBuf}, time::Duration};
+
+use bevy::{a11y::AccessibilityPlugin, app::{PluginGroup, PluginGroupBuilder}, input::{keyboard::KeyCode, ButtonInput}, log::{debug, warn, Level}, prelude::{DetectChanges, DetectChangesMut, Entity, Event, EventReader, EventWriter, Local, NonSend, Query, Res, ResMut, Resource, With}, utils::{default, tracing::span}, window::{CursorGrabMode, MonitorSelection, PresentMode, PrimaryWindow, Window, WindowFocused, WindowMode, WindowOccluded, WindowPlugin, WindowResized, WindowScaleFactorChanged, WindowTheme}, winit::{UpdateMode, WinitPlugin, WinitSettings, WinitWindows}};
+use thiserror::Error;
 use wde_wgpu::instance::WRenderInstance;
 
 use super::extract_macros::ExtractWorld;
@@ -15,6 +18,249 @@ pub struct SurfaceResized {
     pub height: u32,
 }
 
+/// An event that is sent when the focus or the visibility of the window changes.
+#[derive(Debug, Event, Clone, Copy, PartialEq, Eq)]
+pub enum WindowStateEvent {
+    /// The window gained (true) or lost (false) the focus.
+    Focus(bool),
+    /// The window was minimized or is fully occluded.
+    Minimized,
+    /// The window entered (true) or left (false) the fullscreen mode.
+    /// A `SurfaceResized` event follows once the window has its new size.
+    Fullscreen(bool),
+    /// The scale factor of the window changed, e.g. when it was moved to a monitor with another DPI.
+    /// The new factor is given by `Window::scale_factor`, and a `SurfaceResized` event is sent with the new physical size.
+    ScaleFactorChanged,
+}
+
+/// The state of the primary window.
+/// The frames are not rendered while the window is minimized or unfocused.
+#[derive(Resource, Debug, Clone, PartialEq, Eq)]
+pub struct WindowState {
+    /// Whether the window has the focus.
+    pub focused: bool,
+    /// Whether the window is minimized or fully occluded.
+    pub minimized: bool,
+}
+impl Default for WindowState {
+    fn default() -> Self {
+        Self { focused: true, minimized: false }
+    }
+}
+impl WindowState {
+    /// Whether the frames should be rendered and presented to the window.
+    pub fn should_render(&self) -> bool {
+        self.focused && !self.minimized
+    }
+}
+
+/// The css selector of the canvas the engine renders to on the web.
+#[cfg(target_arch = "wasm32")]
+pub const CANVAS_SELECTOR: &str = "#waterdropengine";
+
+/// The update interval of the application while the window is unfocused (10 Hz).
+const UNFOCUSED_UPDATE_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The winit update settings, throttling the updates while the window is unfocused.
+pub(crate) fn winit_settings() -> WinitSettings {
+    WinitSettings {
+        focused_mode: UpdateMode::Continuous,
+        unfocused_mode: UpdateMode::reactive_low_power(UNFOCUSED_UPDATE_INTERVAL),
+    }
+}
+
+/// The configuration of the primary window.
+#[derive(Resource, Debug, Clone)]
+pub struct WindowConfig {
+    /// The path of the png file used as the window icon, relative to the working directory.
+    pub icon_path: Option<PathBuf>,
+    /// The key toggling the borderless fullscreen mode, F11 by default. None to disable the toggle.
+    pub fullscreen_key: Option<KeyCode>,
+}
+impl Default for WindowConfig {
+    fn default() -> Self {
+        Self {
+            icon_path: None,
+            fullscreen_key: Some(KeyCode::F11),
+        }
+    }
+}
+
+/// The display mode of a window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FullscreenMode {
+    /// A window with decorations.
+    #[default]
+    Windowed,
+    /// A borderless window covering the current monitor, keeping the video mode of the monitor.
+    Borderless,
+    /// An exclusive fullscreen window on the current monitor, using the video mode with the highest resolution and refresh rate.
+    Exclusive,
+}
+
+/// Set the display mode of a window.
+/// The surface is resized once the window has its new size, see `SurfaceResized`.
+///
+/// # Arguments
+///
+/// * `window` - The window.
+/// * `mode` - The display mode.
+pub fn set_fullscreen(window: &mut Window, mode: FullscreenMode) {
+    window.mode = match mode {
+        FullscreenMode::Windowed => WindowMode::Windowed,
+        FullscreenMode::Borderless => WindowMode::BorderlessFullscreen(MonitorSelection::Current),
+        FullscreenMode::Exclusive => WindowMode::Fullscreen(MonitorSelection::Current),
+    };
+}
+
+/// Switch a window between the windowed and the borderless fullscreen modes.
+///
+/// # Arguments
+///
+/// * `window` - The window.
+pub fn toggle_fullscreen(window: &mut Window) {
+    let mode = if window.mode == WindowMode::Windowed {
+        FullscreenMode::Borderless
+    } else {
+        FullscreenMode::Windowed
+    };
+    set_fullscreen(window, mode);
+}
+
+/// Whether the cursor is captured by the primary window, locked and hidden, e.g. to control a first person camera.
+/// The cursor is released automatically when the window loses the focus.
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CursorGrab(pub bool);
+
+/// Lock the cursor in a window or release it.
+///
+/// # Arguments
+///
+/// * `window` - The window.
+/// * `grab` - Whether the cursor is locked.
+pub fn set_cursor_grab(window: &mut Window, grab: bool) {
+    window.cursor_options.grab_mode = if grab { CursorGrabMode::Locked } else { CursorGrabMode::None };
+}
+
+/// Show or hide the cursor over a window.
+///
+/// # Arguments
+///
+/// * `window` - The window.
+/// * `visible` - Whether the cursor is visible.
+pub fn set_cursor_visible(window: &mut Window, visible: bool) {
+    window.cursor_options.visible = visible;
+}
+
+/// Release the cursor when the window loses the focus, and apply the cursor grab to the primary window.
+pub(crate) fn update_cursor_grab(
+    mut cursor_grab: ResMut<CursorGrab>, mut events_reader: EventReader<WindowStateEvent>,
+    mut primary_window: Query<&mut Window, With<PrimaryWindow>>
+) {
+    for event in events_reader.read() {
+        if *event == WindowStateEvent::Focus(false) {
+            cursor_grab.set_if_neq(CursorGrab(false));
+        }
+    }
+
+    if !cursor_grab.is_changed() {
+        return;
+    }
+    if let Ok(mut window) = primary_window.get_single_mut() {
+        // The cursor can only be locked in a focused window
+        let grab = cursor_grab.0 && window.focused;
+        set_cursor_grab(&mut window, grab);
+        set_cursor_visible(&mut window, !grab);
+    }
+}
+
+/// Toggle the fullscreen mode of the primary window when the fullscreen key is pressed.
+pub(crate) fn toggle_fullscreen_on_key(
+    config: Res<WindowConfig>, keyboard_input: Option<Res<ButtonInput<KeyCode>>>,
+    mut primary_window: Query<&mut Window, With<PrimaryWindow>>
+) {
+    let (key, keyboard_input) = match (config.fullscreen_key, keyboard_input) {
+        (Some(key), Some(keyboard_input)) => (key, keyboard_input),
+        _ => return
+    };
+    if keyboard_input.just_pressed(key) {
+        if let Ok(mut window) = primary_window.get_single_mut() {
+            toggle_fullscreen(&mut window);
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum IconError {
+    #[error("Could not load the icon file: {0}")]
+    Image(#[from] image::ImageError),
+    #[error("Invalid icon: {0}")]
+    BadIcon(#[from] winit::window::BadIcon),
+}
+
+/// Set the icon of a window from a png file.
+///
+/// # Arguments
+///
+/// * `window` - The winit window.
+/// * `path` - The path of the png file.
+pub fn set_window_icon(window: &winit::window::Window, path: &Path) -> Result<(), IconError> {
+    let image = image::open(path)?.into_rgba8();
+    let (width, height) = image.dimensions();
+    set_window_icon_rgba(window, image.into_raw(), width, height)
+}
+
+/// Set the icon of a window from raw pixels.
+///
+/// # Arguments
+///
+/// * `window` - The winit window.
+/// * `rgba` - The pixels of the icon, row by row, with 8 bits per channel.
+/// * `width` - The width of the icon.
+/// * `height` - The height of the icon.
+///
+/// # Errors
+///
+/// Returns `IconError::BadIcon` if the size of the pixels does not match the dimensions.
+pub fn set_window_icon_rgba(window: &winit::window::Window, rgba: Vec<u8>, width: u32, height: u32) -> Result<(), IconError> {
+    let icon = winit::window::Icon::from_rgba(rgba, width, height)?;
+    window.set_window_icon(Some(icon));
+    Ok(())
+}
+
+/// Set the icon of the primary window once it is created.
+pub(crate) fn init_window_icon(
+    config: Res<WindowConfig>, winit_windows: NonSend<WinitWindows>,
+    primary_window: Query<Entity, With<PrimaryWindow>>, mut done: Local<bool>
+) {
+    if *done {
+        return;
+    }
+    let path = match config.icon_path.as_ref() {
+        Some(path) => path,
+        None => {
+            *done = true;
+            return;
+        }
+    };
+
+    // Wait for the window to be created
+    let window = match primary_window.get_single().ok().and_then(|entity| winit_windows.get_window(entity)) {
+        Some(window) => window,
+        None => return
+    };
+    *done = true;
+
+    // Continue without an icon if the file is missing
+    if !path.exists() {
+        warn!("The window icon {} does not exist, using the default icon.", path.display());
+        return;
+    }
+    if let Err(e) = set_window_icon(window, path) {
+        warn!("Failed to set the window icon {}: {}.", path.display(), e);
+    }
+}
+
 pub(crate) struct WindowPlugins;
 
 impl PluginGroup for WindowPlugins {
@@ -29,6 +275,8 @@ impl PluginGroup for WindowPlugins {
                     name: Some("waterdropengine".into()),
                     resolution: (600.0, 500.0).into(),
                     present_mode: PresentMode::AutoVsync,
+                    #[cfg(target_arch = "wasm32")]
+                    canvas: Some(CANVAS_SELECTOR.into()),
                     fit_canvas_to_parent: true,
                     prevent_default_event_handling: false,
                     window_theme: Some(WindowTheme::Dark),
@@ -52,8 +300,20 @@ impl PluginGroup for WindowPlugins {
 /// Send surface resized events with the physical window size.
 pub(crate) fn send_surface_resized(
     mut events_writer: EventWriter<SurfaceResized>, 
-    mut events_reader: EventReader<WindowResized>, window: Query<&Window>
+    mut events_reader: EventReader<WindowResized>, mut scale_factor_reader: EventReader<WindowScaleFactorChanged>,
+    window: Query<&Window>
 ) {
+    // The physical size also changes with the scale factor, even if the logical size stays the same
+    let scale_factor_changed = scale_factor_reader.read().count() > 0;
+    if scale_factor_changed && events_reader.is_empty() {
+        if let Ok(window) = window.get_single() {
+            events_writer.send(SurfaceResized {
+                width: window.resolution.physical_width().max(1),
+                height: window.resolution.physical_height().max(1),
+            });
+        }
+    }
+
     for _ in events_reader.read() {
         if let Ok(window) = window.get_single() {
             let (width, height) = (
@@ -73,8 +333,62 @@ pub(crate) fn send_surface_resized(
 }
 
 
+/// Update the window state and send window state events when the window focus or visibility changes.
+pub(crate) fn send_window_state(
+    mut events_writer: EventWriter<WindowStateEvent>, mut window_state: ResMut<WindowState>,
+    (mut focused_reader, mut occluded_reader, mut resized_reader, mut scale_factor_reader): (
+        EventReader<WindowFocused>, EventReader<WindowOccluded>, EventReader<WindowResized>, EventReader<WindowScaleFactorChanged>
+    ),
+    primary_window: Query<&Window, With<PrimaryWindow>>, mut fullscreen: Local<bool>
+) {
+    let mut state = window_state.clone();
+    for event in focused_reader.read() {
+        state.focused = event.focused;
+        events_writer.send(WindowStateEvent::Focus(event.focused));
+    }
+    for event in occluded_reader.read() {
+        state.minimized = event.occluded;
+        if event.occluded {
+            events_writer.send(WindowStateEvent::Minimized);
+        }
+    }
+    for event in resized_reader.read() {
+        let minimized = event.width == 0.0 || event.height == 0.0;
+        if minimized && !state.minimized {
+            events_writer.send(WindowStateEvent::Minimized);
+        }
+        state.minimized = minimized;
+    }
+
+    for event in scale_factor_reader.read() {
+        debug!("The scale factor of the window changed to {}.", event.scale_factor);
+        events_writer.send(WindowStateEvent::ScaleFactorChanged);
+    }
+
+    // Send the fullscreen transitions
+    if let Ok(window) = primary_window.get_single() {
+        let is_fullscreen = window.mode != WindowMode::Windowed;
+        if is_fullscreen != *fullscreen {
+            debug!("The window {} the fullscreen mode.", if is_fullscreen { "entered" } else { "left" });
+            *fullscreen = is_fullscreen;
+            events_writer.send(WindowStateEvent::Fullscreen(is_fullscreen));
+        }
+    }
+
+    // Only trigger the change detection if the state changed
+    window_state.set_if_neq(state);
+}
+
+/// Run condition skipping the rendering of the frames while the window is minimized or unfocused.
+pub(crate) fn should_render(window_state: Res<WindowState>) -> bool {
+    window_state.should_render()
+}
+
+
 /// Extract the window size from the primary window and update the surface configuration.
 pub(crate) fn extract_surface_size(render_instance: ResMut<WRenderInstance<'static>>, windows: ExtractWorld<Query<&Window>>) {
+    let _extract_span = span!(Level::INFO, "extract_surface_size").entered();
+
     // Check if there is a window
     if windows.iter().count() == 0 {
         return
@@ -32,6 +32,36 @@ pub struct CameraFeatureBuffer {
     pub buffer: Handle<Buffer>,
 }
 
+/// The frustum planes of the active camera in the render world, updated each frame.
+/// If there is no active camera, the frustum is `None` and nothing should be culled.
+#[derive(Resource, Default)]
+pub struct CameraFrustum {
+    /// The six inward facing planes, see `CameraView::frustum_planes`.
+    pub planes: Option<[Vec4; 6]>,
+}
+impl CameraFrustum {
+    /// Whether an axis aligned bounding box is at least partially inside the frustum.
+    /// This is conservative: some boxes outside of the frustum near its corners are considered visible.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `min` - The world space minimum corner of the box.
+    /// * `max` - The world space maximum corner of the box.
+    pub fn intersects_aabb(&self, min: Vec3, max: Vec3) -> bool {
+        let planes = match &self.planes {
+            Some(planes) => planes,
+            None => return true
+        };
+
+        // The box is outside if its corner furthest along the normal of a plane is behind the plane
+        planes.iter().all(|plane| {
+            let normal = plane.truncate();
+            let positive = Vec3::select(normal.cmpge(Vec3::ZERO), max, min);
+            normal.dot(positive) + plane.w >= 0.0
+        })
+    }
+}
+
 pub struct CameraFeature;
 impl Plugin for CameraFeature {
     fn build(&self, app: &mut App) {
@@ -40,7 +70,8 @@ impl Plugin for CameraFeature {
             .add_systems(Render, build_bind_group.in_set(RenderSet::BindGroups))
             .add_systems(Render, update_buffer.in_set(RenderSet::Prepare))
             .init_resource::<CameraFeatureRender>()
-            .init_resource::<CameraUniform>();
+            .init_resource::<CameraUniform>()
+            .init_resource::<CameraFrustum>();
     }
 
     fn finish(&self, app: &mut App) {
@@ -80,16 +111,19 @@ fn build_bind_group(
 
 // Extract the texture handle every frame
 fn extract(
-    (cameras, mut camera_uniform): (
-        ExtractWorld<Query<(&Transform, &CameraView), With<ActiveCamera>>>, ResMut<CameraUniform>
+    (cameras, mut camera_uniform, mut camera_frustum): (
+        ExtractWorld<Query<(&Transform, &CameraView), With<ActiveCamera>>>, ResMut<CameraUniform>, ResMut<CameraFrustum>
     ), window: ExtractWorld<Query<&Window>>)
 {
     if let (
         Ok((transform, view)), Ok(window)
     ) = (cameras.get_single(), window.get_single()) {
-        // Update the camera uniform
+        // Update the camera uniform and frustum
         let aspect_ratio = window.width() / window.height();
         *camera_uniform = CameraUniform::new(transform, view, aspect_ratio);
+        camera_frustum.planes = Some(view.frustum_planes(transform, aspect_ratio));
+    } else {
+        camera_frustum.planes = None;
     }
 }
 
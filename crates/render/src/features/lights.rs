@@ -1,10 +1,46 @@
 use bevy::prelude::*;
 use wde_wgpu::{bind_group::{BindGroup, BindGroupLayout, WgpuBindGroup}, buffer::{BufferBindingType, BufferUsage}, instance::WRenderInstance, render_pipeline::WShaderStages};
 
-use crate::{assets::{Buffer, GpuBuffer, RenderAssets}, components::{DirectionalLight, LightsStorageElement, PointLight, SpotLight}, core::{extract_macros::ExtractWorld, Extract, Render, RenderApp, RenderSet}};
+use crate::{assets::{Buffer, GpuBuffer, RenderAssets}, components::{DirectionalLight, LightsStorageElement, PointLight, SpotLight}, core::{extract_macros::ExtractWorld, DeviceLimits, Extract, Render, RenderApp, RenderSet}};
+
+/// Maximum number of lights of each type that can be sent to the lighting pass.
+/// The lights storage buffer is sized from these values when the renderer starts, so this resource
+/// must be inserted before the `RenderPlugin` is added to be taken into account.
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct MaxLightCount {
+    /// Maximum number of directional lights.
+    pub directional_lights: u32,
+    /// Maximum number of point lights.
+    pub point_lights: u32,
+    /// Maximum number of spot lights.
+    pub spot_lights: u32
+}
+impl Default for MaxLightCount {
+    fn default() -> Self {
+        Self {
+            directional_lights: 4,
+            point_lights: 30,
+            spot_lights: 30
+        }
+    }
+}
+impl MaxLightCount {
+    /// Total number of lights that can be stored in the lights buffer.
+    pub fn total(&self) -> u32 {
+        self.directional_lights + self.point_lights + self.spot_lights
+    }
+}
 
-/// Maximum number of lights.
-pub const MAX_LIGHTS: usize = 64;
+/// Whether the lights changed since the lights buffer was last uploaded.
+/// The lights buffer is only rebuilt and uploaded when it is true, and it is reset once the upload is done.
+#[derive(Resource)]
+pub struct LightsDirty(pub bool);
+impl Default for LightsDirty {
+    fn default() -> Self {
+        // Upload the lights on the first frame, even if there are none
+        Self(true)
+    }
+}
 
 /// Struct to hold the light uniform layout description.
 #[derive(Resource)]
@@ -51,27 +87,41 @@ impl LightsFeatureBuffer {
 pub struct LightsFeature;
 impl Plugin for LightsFeature {
     fn build(&self, app: &mut App) {
+        // Keep the user defined light counts if any
+        app.init_resource::<MaxLightCount>();
+
         app.get_sub_app_mut(RenderApp).unwrap()
-            .add_systems(Extract, extract)
+            .init_resource::<LightsDirty>()
+            .add_systems(Extract, (mark_lights_dirty, extract).chain())
             .add_systems(Render, LightsFeatureBuffer::build_bind_group.in_set(RenderSet::BindGroups));
     }
 
     fn finish(&self, app: &mut App) {
+        // Compute the size of the lights buffer
+        let max_light_count = *app.world().resource::<MaxLightCount>();
+        let buffer_size = std::mem::size_of::<LightsStorageElement>() * max_light_count.total().max(1) as usize;
+        let max_buffer_size = app.world().resource::<DeviceLimits>().0.max_storage_buffer_binding_size as usize;
+        if buffer_size > max_buffer_size {
+            warn!("The lights buffer size of {} bytes for {:?} exceeds the device maximum storage buffer binding size of {} bytes.",
+                buffer_size, max_light_count, max_buffer_size);
+        }
+
         let buffer_cpu: Handle<Buffer> = app.world_mut().add_asset(Buffer {
             label: "lights".to_string(),
-            size:  std::mem::size_of::<LightsStorageElement>() * MAX_LIGHTS,
+            size:  buffer_size,
             usage: BufferUsage::COPY_SRC | BufferUsage::MAP_WRITE,
             content: None,
         });
         let buffer_gpu: Handle<Buffer> = app.world_mut().add_asset(Buffer {
             label: "lights".to_string(),
-            size:  std::mem::size_of::<LightsStorageElement>() * MAX_LIGHTS,
+            size:  buffer_size,
             usage: BufferUsage::STORAGE | BufferUsage::COPY_DST,
             content: None,
         });
         
         // Add resources
         app.get_sub_app_mut(RenderApp).unwrap()
+            .insert_resource(max_light_count)
             .insert_resource(LightsFeatureBuffer {
                 buffer_cpu,
                 buffer_gpu,
@@ -81,15 +131,53 @@ impl Plugin for LightsFeature {
     }
 }
 
+/// Mark the lights as dirty if a light was added, changed or removed in the main world.
+fn mark_lights_dirty(
+    (directional_changed, point_changed, spot_changed): (
+        ExtractWorld<Query<(), Changed<DirectionalLight>>>, ExtractWorld<Query<(), Changed<PointLight>>>, ExtractWorld<Query<(), Changed<SpotLight>>>
+    ),
+    (mut directional_removed, mut point_removed, mut spot_removed): (
+        ExtractWorld<RemovedComponents<DirectionalLight>>, ExtractWorld<RemovedComponents<PointLight>>, ExtractWorld<RemovedComponents<SpotLight>>
+    ),
+    mut dirty: ResMut<LightsDirty>
+) {
+    // The added lights are also changed
+    let changed = !directional_changed.is_empty() || !point_changed.is_empty() || !spot_changed.is_empty();
+    let removed = directional_removed.read().count() > 0 || point_removed.read().count() > 0 || spot_removed.read().count() > 0;
+    if changed || removed {
+        dirty.0 = true;
+    }
+}
+
 fn extract(
     (lights_directional, lights_point, lights_spot): (
         ExtractWorld<Query<&DirectionalLight>>, ExtractWorld<Query<&PointLight>>, ExtractWorld<Query<&SpotLight>>
     ), 
-    (lights_buffer, buffers): (
-        Res<LightsFeatureBuffer>, Res<RenderAssets<GpuBuffer>>
+    (lights_buffer, buffers, max_light_count): (
+        Res<LightsFeatureBuffer>, Res<RenderAssets<GpuBuffer>>, Res<MaxLightCount>
     ),
-    render_instance: Res<WRenderInstance<'static>>
+    render_instance: Res<WRenderInstance<'static>>, mut dirty: ResMut<LightsDirty>,
+    mut last_ignored: Local<[usize; 3]>
 ) {
+    // Skip the upload if the lights did not change
+    if !dirty.0 {
+        return;
+    }
+
+    // Warn when the number of lights exceeding the maximum changes
+    let ignored = [
+        lights_directional.iter().count().saturating_sub(max_light_count.directional_lights as usize),
+        lights_point.iter().count().saturating_sub(max_light_count.point_lights as usize),
+        lights_spot.iter().count().saturating_sub(max_light_count.spot_lights as usize)
+    ];
+    if ignored != *last_ignored {
+        if ignored.iter().any(|&count| count > 0) {
+            warn!("The number of lights exceeds the maximum of {:?}. {:?} directional, point and spot lights are ignored.",
+                *max_light_count, ignored);
+        }
+        *last_ignored = ignored;
+    }
+
     // Get the lights buffer
     let lights_buffer_cpu = match buffers.get(&lights_buffer.buffer_cpu) {
         Some(lights_buffer) => lights_buffer,
@@ -103,7 +191,7 @@ fn extract(
         let mut first_element = None;
 
         // Extract directional lights
-        for light in lights_directional.iter() {
+        for light in lights_directional.iter().take(max_light_count.directional_lights as usize) {
             let element = LightsStorageElement::from_directional(light);
             if first_element.is_none() { first_element = Some(element); }
             unsafe { *data.add(offset) = element; }
@@ -111,7 +199,7 @@ fn extract(
         }
 
         // Extract point lights
-        for light in lights_point.iter() {
+        for light in lights_point.iter().take(max_light_count.point_lights as usize) {
             let element = LightsStorageElement::from_point(light);
             if first_element.is_none() { first_element = Some(element); }
             unsafe { *data.add(offset) = element; }
@@ -119,7 +207,7 @@ fn extract(
         }
 
         // Extract spot lights
-        for light in lights_spot.iter() {
+        for light in lights_spot.iter().take(max_light_count.spot_lights as usize) {
             let element = LightsStorageElement::from_spot(light);
             if first_element.is_none() { first_element = Some(element); }
             unsafe { *data.add(offset) = element; }
@@ -135,11 +223,6 @@ fn extract(
             ];
             unsafe { *data.add(0) = first_element; }
         }
-
-        // Warn if the number of lights exceeds the maximum
-        if lights_number > MAX_LIGHTS as f32 {
-            warn!("The number of lights exceeds the maximum of {}.", MAX_LIGHTS);
-        }
     });
 
     // Update the buffer
@@ -148,5 +231,6 @@ fn extract(
         None => return
     };
     lights_buffer_gpu.buffer.copy_from_buffer(&render_instance, &lights_buffer_cpu.buffer);
+    dirty.0 = false;
 }
 
@@ -2,15 +2,18 @@ use bevy::app::{App, Plugin};
 
 mod camera;
 mod lights;
+mod shadow_casters;
 
 pub use camera::*;
 pub use lights::*;
+pub use shadow_casters::*;
 
 pub struct RenderFeaturesPlugin;
 impl Plugin for RenderFeaturesPlugin {
     fn build(&self, app: &mut App) {
         app
             .add_plugins(CameraFeature)
-            .add_plugins(LightsFeature);
+            .add_plugins(LightsFeature)
+            .add_plugins(ShadowCastersFeature);
     }
 }
@@ -0,0 +1,141 @@
+use bevy::prelude::*;
+
+use crate::{components::{Camera, PointLight, SpotLight}, core::{extract_macros::{ExtractChangedResourcePlugin, ExtractWorld}, Extract, Render, RenderApp, RenderSet}};
+
+/// Limits the number of point and spot lights rendering shadows each frame.
+/// Lights outside of the budget still contribute to the direct lighting, but without shadows.
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct ShadowCasterBudget {
+    /// Maximum number of lights rendering shadows.
+    pub max_casters: u32,
+    /// Maximum distance between the camera and a light rendering shadows.
+    pub max_shadow_distance: f32
+}
+impl Default for ShadowCasterBudget {
+    fn default() -> Self {
+        Self {
+            max_casters: 4,
+            max_shadow_distance: 50.0
+        }
+    }
+}
+
+/// A light rendering shadows.
+#[derive(Clone, Copy, Debug)]
+pub struct ShadowCaster {
+    /// The light entity in the main world.
+    pub entity: Entity,
+    /// The world space position of the light.
+    pub position: Vec3,
+    /// The distance between the light and the camera.
+    pub distance: f32
+}
+
+/// The lights rendering shadows this frame, sorted from the nearest to the farthest from the camera.
+/// Only contains the point and spot lights with `cast_shadows` selected by the `ShadowCasterBudget`.
+#[derive(Resource, Default)]
+pub struct ShadowCasters {
+    pub lights: Vec<ShadowCaster>
+}
+
+/// The shadow casting lights extracted from the main world, before the budget selection.
+#[derive(Resource, Default)]
+struct ShadowCasterCandidates {
+    lights: Vec<ShadowCaster>
+}
+
+pub struct ShadowCastersFeature;
+impl Plugin for ShadowCastersFeature {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<ShadowCasterBudget>()
+            .add_plugins(ExtractChangedResourcePlugin::<ShadowCasterBudget>::default());
+
+        app.get_sub_app_mut(RenderApp).unwrap()
+            .init_resource::<ShadowCasterBudget>()
+            .init_resource::<ShadowCasterCandidates>()
+            .init_resource::<ShadowCasters>()
+            .add_systems(Extract, extract)
+            .add_systems(Render, select.in_set(RenderSet::Prepare));
+    }
+}
+
+fn extract(
+    (lights_point, lights_spot, cameras): (
+        ExtractWorld<Query<(Entity, &PointLight)>>, ExtractWorld<Query<(Entity, &SpotLight)>>,
+        ExtractWorld<Query<&Transform, With<Camera>>>
+    ),
+    mut candidates: ResMut<ShadowCasterCandidates>
+) {
+    candidates.lights.clear();
+
+    // Get the active camera
+    let camera_position = match cameras.iter().next() {
+        Some(transform) => transform.translation,
+        None => return
+    };
+
+    // Extract the shadow casting lights
+    let point_lights = lights_point.iter()
+        .filter(|(_, light)| light.cast_shadows)
+        .map(|(entity, light)| (entity, light.position));
+    let spot_lights = lights_spot.iter()
+        .filter(|(_, light)| light.cast_shadows)
+        .map(|(entity, light)| (entity, light.position));
+    for (entity, position) in point_lights.chain(spot_lights) {
+        candidates.lights.push(ShadowCaster {
+            entity,
+            position,
+            distance: position.distance(camera_position)
+        });
+    }
+}
+
+/// Select the nearest lights to the camera within the shadow casters budget.
+fn select(budget: Res<ShadowCasterBudget>, mut candidates: ResMut<ShadowCasterCandidates>, mut casters: ResMut<ShadowCasters>) {
+    casters.lights.clear();
+    candidates.lights.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+    casters.lights.extend(candidates.lights.iter()
+        .filter(|light| light.distance <= budget.max_shadow_distance)
+        .take(budget.max_casters as usize));
+
+    debug!("Rendering shadows for {} of the {} shadow casting lights.", casters.lights.len(), candidates.lights.len());
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use crate::core::MainWorld;
+
+    use super::*;
+
+    /// Select the shadow casters of a main world with a camera at the origin.
+    fn casters(mut main_world: World) -> Vec<Entity> {
+        main_world.spawn((Camera, Transform::IDENTITY));
+        let mut world = World::new();
+        world.insert_resource(MainWorld(main_world));
+        world.init_resource::<ShadowCasterBudget>();
+        world.init_resource::<ShadowCasterCandidates>();
+        world.init_resource::<ShadowCasters>();
+        world.run_system_once(extract).unwrap();
+        world.run_system_once(select).unwrap();
+        world.resource::<ShadowCasters>().lights.iter().map(|light| light.entity).collect()
+    }
+
+    #[test]
+    fn point_lights_cast_shadows_from_their_field() {
+        let mut main_world = World::new();
+        let caster = main_world.spawn(PointLight { cast_shadows: true, ..Default::default() }).id();
+        main_world.spawn(PointLight { cast_shadows: false, ..Default::default() });
+        assert_eq!(casters(main_world), [caster]);
+    }
+
+    #[test]
+    fn spot_lights_cast_shadows_from_their_field() {
+        let mut main_world = World::new();
+        let caster = main_world.spawn(SpotLight { cast_shadows: true, ..Default::default() }).id();
+        main_world.spawn(SpotLight { cast_shadows: false, ..Default::default() });
+        assert_eq!(casters(main_world), [caster]);
+    }
+}
@@ -0,0 +1,77 @@
+use bevy::{ecs::system::lifetimeless::{SRes, SResMut}, prelude::*};
+use wde_wgpu::render_pipeline::{WBlendComponent, WBlendFactor, WBlendOperation, WBlendState, WDepthStencilDescriptor};
+use crate::{assets::{PrepareAssetError, RenderAsset}, passes::hdr::HDR_FORMAT, pipelines::{CachedPipelineIndex, ComputePipelineDescriptor, PipelineManager, RenderPipelineDescriptor}};
+
+use super::BloomTextures;
+
+#[derive(Default, Asset, Clone, TypePath)]
+pub struct BloomPipelineAsset;
+#[derive(Component)]
+pub struct BloomPipeline(pub Handle<BloomPipelineAsset>);
+pub struct GpuBloomPipeline {
+    /// The compute pipeline thresholding and downsampling a level into the next one.
+    pub cached_downsample_index: CachedPipelineIndex,
+    /// The compute pipeline blurring a level into the larger one.
+    pub cached_upsample_index: CachedPipelineIndex,
+    /// The render pipeline adding the bloom to the hdr color.
+    pub cached_composite_index: CachedPipelineIndex
+}
+impl RenderAsset for GpuBloomPipeline {
+    type SourceAsset = BloomPipelineAsset;
+    type Param = (SRes<AssetServer>, SResMut<PipelineManager>, SRes<BloomTextures>);
+
+    fn prepare_asset(
+            _asset: Self::SourceAsset,
+            (assets_server, pipeline_manager, bloom_textures): &mut bevy::ecs::system::SystemParamItem<Self::Param>
+        ) -> Result<Self, PrepareAssetError<Self::SourceAsset>> {
+        // Create the compute pipelines
+        let cached_downsample_index = pipeline_manager.create_compute_pipeline(ComputePipelineDescriptor {
+            label: "bloom-downsample",
+            comp: Some(assets_server.load("bloom/downsample_comp.wgsl")),
+            bind_group_layouts: vec![bloom_textures.downsample_layout.clone()],
+            ..Default::default()
+        });
+        let cached_upsample_index = pipeline_manager.create_compute_pipeline(ComputePipelineDescriptor {
+            label: "bloom-upsample",
+            comp: Some(assets_server.load("bloom/upsample_comp.wgsl")),
+            bind_group_layouts: vec![bloom_textures.upsample_layout.clone()],
+            ..Default::default()
+        });
+
+        // Create the composite pipeline, drawing a full screen triangle without vertex buffer
+        // and adding the bloom to the hdr color
+        let additive = WBlendComponent {
+            src_factor: WBlendFactor::One,
+            dst_factor: WBlendFactor::One,
+            operation: WBlendOperation::Add
+        };
+        let cached_composite_index = pipeline_manager.create_render_pipeline(RenderPipelineDescriptor {
+            label: "bloom-composite",
+            vert: Some(assets_server.load("bloom/composite_vert.wgsl")),
+            frag: Some(assets_server.load("bloom/composite_frag.wgsl")),
+            bind_group_layouts: vec![bloom_textures.composite_layout.clone()],
+            depth: WDepthStencilDescriptor {
+                enabled: false,
+                ..Default::default()
+            },
+            render_targets: Some(vec![HDR_FORMAT]),
+            blend: WBlendState {
+                color: additive,
+                alpha: additive
+            },
+            cull_mode: None,
+            vertex_input: false,
+            ..Default::default()
+        });
+
+        Ok(GpuBloomPipeline {
+            cached_downsample_index,
+            cached_upsample_index,
+            cached_composite_index
+        })
+    }
+
+    fn label(&self) -> &str {
+        "bloom"
+    }
+}
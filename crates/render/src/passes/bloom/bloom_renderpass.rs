@@ -0,0 +1,119 @@
+use bevy::prelude::*;
+use crate::{core::pipeline_statistics::PipelineStatisticsQueries, assets::{GpuTexture, RenderAssets}, passes::{hdr::HdrTexture, render_graph::RenderPass}, pipelines::{CachedPipelineStatus, PipelineManager}};
+use wde_wgpu::{command_buffer::{RenderPassBuilder, RenderPassColorAttachment, WCommandBuffer, WLoadOp}, instance::WRenderInstance};
+
+use super::{BloomSettings, BloomTextures, GpuBloomPipeline};
+
+/// Adds the bloom to the hdr color of the lighting pass.
+/// The bright pixels are downsampled through the levels of `BloomTextures`, then blurred back up
+/// by accumulating the levels, and the result is blended additively into the hdr color.
+/// When the bloom is disabled, the pass is skipped.
+#[derive(Default)]
+pub struct BloomRenderPass;
+impl RenderPass for BloomRenderPass {
+    fn extract(&self, main_world: &mut World, render_world: &mut World) {
+        // Extract the settings
+        let settings = *main_world.get_resource::<BloomSettings>().unwrap();
+        render_world.insert_resource(settings);
+    }
+
+    fn render(&self, render_world: &mut World) {
+        // Check if the bloom is enabled
+        let settings = render_world.get_resource::<BloomSettings>().unwrap();
+        if !settings.enabled {
+            return;
+        }
+
+        // Get the render instance and the hdr texture
+        let render_instance = render_world.get_resource::<WRenderInstance>().unwrap();
+        let render_instance = render_instance.data.read().unwrap();
+        let hdr = match render_world.get_resource::<HdrTexture>()
+            .and_then(|hdr_texture| render_world.get_resource::<RenderAssets<GpuTexture>>().unwrap().get(&hdr_texture.texture)) {
+            Some(hdr) => hdr,
+            None => return
+        };
+
+        // Check if the composite is ready
+        let bloom_textures = render_world.get_resource::<BloomTextures>().unwrap();
+        let pipeline_manager = render_world.get_resource::<PipelineManager>().unwrap();
+        let bloom_pipeline = match render_world.get_resource::<RenderAssets<GpuBloomPipeline>>().unwrap().iter().next() {
+            Some((_, pipeline)) => pipeline,
+            None => return
+        };
+        let (composite_pipeline, composite_bind_group) = match (
+            pipeline_manager.get_pipeline(bloom_pipeline.cached_composite_index),
+            &bloom_textures.composite_bind_group
+        ) {
+            (CachedPipelineStatus::OkRender(pipeline), Some(bind_group)) => (pipeline, bind_group),
+            _ => return
+        };
+
+        let mut command_buffer = WCommandBuffer::new(&render_instance, "bloom");
+        let timestamp = command_buffer.begin_timestamp("bloom");
+
+        // Downsample and upsample the levels
+        if let (
+            CachedPipelineStatus::OkCompute(downsample_pipeline),
+            CachedPipelineStatus::OkCompute(upsample_pipeline),
+            Some(downsampled)
+        ) = (
+            pipeline_manager.get_pipeline(bloom_pipeline.cached_downsample_index),
+            pipeline_manager.get_pipeline(bloom_pipeline.cached_upsample_index),
+            &bloom_textures.downsampled
+        ) {
+            let level_size = |level: u32| ((downsampled.size.0 >> level).max(1), (downsampled.size.1 >> level).max(1));
+            let mut compute_pass = command_buffer.create_compute_pass("bloom");
+            if compute_pass.set_pipeline(downsample_pipeline).is_ok() {
+                for (level, bind_group) in bloom_textures.downsample_bind_groups.iter().enumerate() {
+                    let (width, height) = level_size(level as u32);
+                    compute_pass.set_bind_group(0, bind_group);
+                    if let Err(e) = compute_pass.dispatch(width.div_ceil(8), height.div_ceil(8), 1) {
+                        error!("Failed to dispatch: {:?}.", e);
+                    }
+                }
+            } else {
+                error!("Failed to set pipeline.");
+            }
+            if compute_pass.set_pipeline(upsample_pipeline).is_ok() {
+                for (level, bind_group) in bloom_textures.upsample_bind_groups.iter().enumerate().rev() {
+                    let (width, height) = level_size(level as u32);
+                    compute_pass.set_bind_group(0, bind_group);
+                    if let Err(e) = compute_pass.dispatch(width.div_ceil(8), height.div_ceil(8), 1) {
+                        error!("Failed to dispatch: {:?}.", e);
+                    }
+                }
+            } else {
+                error!("Failed to set pipeline.");
+            }
+        }
+
+        // Add the bloom to the hdr color
+        {
+            let mut render_pass = command_buffer.create_render_pass("bloom-composite", |builder: &mut RenderPassBuilder| {
+                builder.add_color_attachment(RenderPassColorAttachment {
+                    texture: Some(&hdr.texture.view),
+                    load: WLoadOp::Load,
+                    ..Default::default()
+                });
+            });
+            let statistics = render_world.get_resource::<PipelineStatisticsQueries>();
+            let statistics_query = statistics.is_some_and(|statistics| statistics.begin(&mut render_pass, "bloom-composite"));
+            if render_pass.set_pipeline(composite_pipeline).is_ok() {
+                render_pass.set_bind_group(0, composite_bind_group);
+                if let Err(e) = render_pass.draw(0..3, 0..1) {
+                    error!("Failed to draw: {:?}.", e);
+                }
+            } else {
+                error!("Failed to set pipeline.");
+            }
+            if let Some(statistics) = statistics {
+                statistics.end(&mut render_pass, statistics_query);
+            }
+        }
+        command_buffer.end_timestamp(timestamp);
+
+        // Submit the command buffer
+        command_buffer.submit(&render_instance);
+        command_buffer.trace_timestamps(&render_instance);
+    }
+}
@@ -0,0 +1,206 @@
+use bevy::prelude::*;
+use wde_wgpu::{bind_group::{BindGroup, BindGroupLayout, BindGroupLayoutBuilder, WStorageTextureAccess, WgpuBindGroup}, buffer::{BufferBindingType, BufferUsage, WBuffer}, instance::WRenderInstance, render_pipeline::WShaderStages, texture::{WSampler, WSamplerDescriptor, WTexture, WTextureUsages}};
+
+use crate::{assets::{GpuTexture, RenderAssets, Texture}, passes::hdr::{HdrTexture, HDR_FORMAT}};
+
+/// The maximum number of downsampled levels of the bloom.
+pub const MAX_BLOOM_MIP_LEVELS: u32 = 8;
+
+/// The settings of the bloom, spreading the light of the bright pixels around them.
+#[derive(Resource, Debug, Clone, Copy, PartialEq)]
+pub struct BloomSettings {
+    /// Whether the bloom is computed (default: true). When disabled, the hdr color is left as is.
+    pub enabled: bool,
+    /// The factor of the bloom added to the hdr color (default: 0.15).
+    pub intensity: f32,
+    /// The brightness above which the pixels bloom (default: 1.0).
+    pub threshold: f32,
+    /// The width of the soft transition around the threshold, 0 for a hard threshold (default: 0.5).
+    pub knee: f32,
+    /// The number of downsampled levels, up to `MAX_BLOOM_MIP_LEVELS` (default: 6).
+    /// The more levels, the wider the halo.
+    pub mip_levels: u32,
+}
+impl Default for BloomSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            intensity: 0.15,
+            threshold: 1.0,
+            knee: 0.5,
+            mip_levels: 6
+        }
+    }
+}
+
+/// The bloom uniform, read by the first downsampling and by the composite.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct BloomUniform {
+    /// x: the intensity, y: the threshold, z: the knee, w: padding.
+    pub params: [f32; 4]
+}
+
+/// The textures and bind groups of the bloom, recreated with the hdr texture or when the number of levels changes.
+///
+/// The hdr color is thresholded and downsampled into the levels of `downsampled`, the first being half its size.
+/// The levels are then blurred and accumulated back up into `upsampled`, from the smallest to the largest,
+/// and the first level of `upsampled` is added to the hdr color.
+#[derive(Resource)]
+pub struct BloomTextures {
+    /// The bilinear sampler of the levels.
+    pub sampler: WSampler,
+    /// The buffer of the bloom uniform.
+    pub uniform_buffer: WBuffer,
+    /// A bloom uniform without threshold, so that the levels after the first one are downsampled as is.
+    pub passthrough_buffer: WBuffer,
+
+    /// The layout of a downsampling: the source level and its sampler, the destination level and the uniform.
+    pub downsample_layout: BindGroupLayout,
+    /// The layout of an upsampling: the smaller level and its sampler, the destination level and the downsampled level of its size.
+    pub upsample_layout: BindGroupLayout,
+    /// The layout of the composite: the bloom and its sampler, and the uniform.
+    pub composite_layout: BindGroupLayout,
+
+    /// The downsampled levels of the hdr color.
+    pub downsampled: Option<WTexture>,
+    /// The accumulated levels, one less than the downsampled levels.
+    pub upsampled: Option<WTexture>,
+    /// The bind group of the downsampling into each level.
+    pub downsample_bind_groups: Vec<WgpuBindGroup>,
+    /// The bind group of the upsampling into each level of `upsampled`.
+    pub upsample_bind_groups: Vec<WgpuBindGroup>,
+    pub composite_bind_group: Option<WgpuBindGroup>,
+
+    /// The hdr texture and the number of levels the textures were created for.
+    source: Option<(AssetId<Texture>, u32)>,
+}
+impl FromWorld for BloomTextures {
+    fn from_world(world: &mut World) -> Self {
+        let render_instance = world.get_resource::<WRenderInstance<'static>>().unwrap();
+        let render_instance = render_instance.data.read().unwrap();
+
+        let sampler = WTexture::create_sampler(&render_instance, "bloom", &WSamplerDescriptor::LINEAR_CLAMP);
+        let uniform_buffer = WBuffer::new(&render_instance, "bloom",
+            std::mem::size_of::<BloomUniform>(), BufferUsage::UNIFORM | BufferUsage::COPY_DST, None);
+        let passthrough_buffer = WBuffer::new(&render_instance, "bloom-passthrough",
+            std::mem::size_of::<BloomUniform>(), BufferUsage::UNIFORM, Some(bytemuck::cast_slice(&[BloomUniform::default()])));
+
+        let downsample_layout = BindGroupLayout::new("bloom-downsample", |builder: &mut BindGroupLayoutBuilder| {
+            builder.add_texture_view(0, WShaderStages::COMPUTE);
+            builder.add_sampler(1, WShaderStages::COMPUTE, &WSamplerDescriptor::LINEAR_CLAMP);
+            builder.add_storage_texture(2, WShaderStages::COMPUTE, WStorageTextureAccess::WriteOnly, HDR_FORMAT);
+            builder.add_buffer(3, WShaderStages::COMPUTE, BufferBindingType::Uniform);
+        });
+        let upsample_layout = BindGroupLayout::new("bloom-upsample", |builder: &mut BindGroupLayoutBuilder| {
+            builder.add_texture_view(0, WShaderStages::COMPUTE);
+            builder.add_sampler(1, WShaderStages::COMPUTE, &WSamplerDescriptor::LINEAR_CLAMP);
+            builder.add_storage_texture(2, WShaderStages::COMPUTE, WStorageTextureAccess::WriteOnly, HDR_FORMAT);
+            builder.add_texture_view(3, WShaderStages::COMPUTE);
+        });
+        let composite_layout = BindGroupLayout::new("bloom-composite", |builder: &mut BindGroupLayoutBuilder| {
+            builder.add_texture_view(0, WShaderStages::FRAGMENT);
+            builder.add_sampler(1, WShaderStages::FRAGMENT, &WSamplerDescriptor::LINEAR_CLAMP);
+            builder.add_buffer(2, WShaderStages::FRAGMENT, BufferBindingType::Uniform);
+        });
+
+        BloomTextures {
+            sampler,
+            uniform_buffer,
+            passthrough_buffer,
+            downsample_layout,
+            upsample_layout,
+            composite_layout,
+            downsampled: None,
+            upsampled: None,
+            downsample_bind_groups: Vec::new(),
+            upsample_bind_groups: Vec::new(),
+            composite_bind_group: None,
+            source: None
+        }
+    }
+}
+impl BloomTextures {
+    /// The number of levels of the bloom.
+    pub fn mip_levels(&self) -> u32 {
+        self.downsample_bind_groups.len() as u32
+    }
+
+    /// Write the uniform, and recreate the textures and the bind groups when the hdr texture or the number of levels change.
+    pub fn prepare(
+        mut bloom_textures: ResMut<BloomTextures>, settings: Res<BloomSettings>,
+        hdr_texture: Option<Res<HdrTexture>>, textures: Res<RenderAssets<GpuTexture>>,
+        render_instance: Res<WRenderInstance<'static>>
+    ) {
+        let bloom_textures = &mut *bloom_textures;
+        let render_instance = render_instance.data.read().unwrap();
+
+        // Update the uniform
+        bloom_textures.uniform_buffer.write(&render_instance, bytemuck::cast_slice(&[BloomUniform {
+            params: [settings.intensity, settings.threshold.max(0.0), settings.knee.max(0.0), 0.0]
+        }]), 0);
+
+        // Check if the textures are up to date
+        let hdr_texture = match hdr_texture {
+            Some(hdr_texture) => hdr_texture,
+            None => return
+        };
+        let hdr = match textures.get(&hdr_texture.texture) {
+            Some(hdr) => hdr,
+            None => return
+        };
+        let size = ((hdr.texture.size.0 / 2).max(1), (hdr.texture.size.1 / 2).max(1));
+        let levels = settings.mip_levels.clamp(1, MAX_BLOOM_MIP_LEVELS).min(WTexture::mip_level_count(size));
+        if bloom_textures.source == Some((hdr_texture.texture.id(), levels)) {
+            return;
+        }
+        debug!("Creating the {} levels of the bloom of size {}x{}.", levels, size.0, size.1);
+
+        // Create the textures
+        let usages = WTextureUsages::TEXTURE_BINDING | WTextureUsages::STORAGE_BINDING;
+        let downsampled = WTexture::new(&render_instance, "bloom-downsampled", size, HDR_FORMAT, usages, Some(levels));
+        let upsampled = WTexture::new(&render_instance, "bloom-upsampled", size, HDR_FORMAT, usages, Some((levels - 1).max(1)));
+        let downsampled_views: Vec<_> = (0..levels).map(|level| downsampled.view_mip_range(level, 1)).collect();
+        let upsampled_views: Vec<_> = (0..levels - 1).map(|level| upsampled.view_mip_range(level, 1)).collect();
+
+        // Downsample the hdr color into the first level, then each level into the next one
+        let downsample_layout = bloom_textures.downsample_layout.build(&render_instance);
+        bloom_textures.downsample_bind_groups = (0..levels as usize).map(|level| {
+            let (source, uniform) = match level {
+                0 => (&hdr.texture.view, &bloom_textures.uniform_buffer),
+                _ => (&downsampled_views[level - 1], &bloom_textures.passthrough_buffer)
+            };
+            BindGroup::build(format!("bloom-downsample-{}", level).as_str(), &render_instance, &downsample_layout, &vec![
+                BindGroup::view(0, source),
+                BindGroup::sampler(1, &bloom_textures.sampler),
+                BindGroup::storage_texture(2, &downsampled_views[level]),
+                BindGroup::buffer(3, uniform)
+            ])
+        }).collect();
+
+        // Upsample the smallest level, then each accumulated level, into the larger one
+        let upsample_layout = bloom_textures.upsample_layout.build(&render_instance);
+        bloom_textures.upsample_bind_groups = (0..levels as usize - 1).map(|level| {
+            let source = if level + 2 == levels as usize { &downsampled_views[level + 1] } else { &upsampled_views[level + 1] };
+            BindGroup::build(format!("bloom-upsample-{}", level).as_str(), &render_instance, &upsample_layout, &vec![
+                BindGroup::view(0, source),
+                BindGroup::sampler(1, &bloom_textures.sampler),
+                BindGroup::storage_texture(2, &upsampled_views[level]),
+                BindGroup::view(3, &downsampled_views[level])
+            ])
+        }).collect();
+
+        // Composite the largest level, accumulated if there are several levels
+        let composite_layout = bloom_textures.composite_layout.build(&render_instance);
+        let bloom = upsampled_views.first().unwrap_or(&downsampled_views[0]);
+        bloom_textures.composite_bind_group = Some(BindGroup::build("bloom-composite", &render_instance, &composite_layout, &vec![
+            BindGroup::view(0, bloom),
+            BindGroup::sampler(1, &bloom_textures.sampler),
+            BindGroup::buffer(2, &bloom_textures.uniform_buffer)
+        ]));
+
+        bloom_textures.downsampled = Some(downsampled);
+        bloom_textures.upsampled = Some(upsampled);
+        bloom_textures.source = Some((hdr_texture.texture.id(), levels));
+    }
+}
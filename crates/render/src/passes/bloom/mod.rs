@@ -0,0 +1,45 @@
+use bevy::prelude::*;
+
+mod bloom_textures;
+mod bloom_pipeline;
+mod bloom_renderpass;
+
+pub use bloom_textures::*;
+pub use bloom_pipeline::*;
+pub use bloom_renderpass::*;
+
+use crate::{assets::RenderAssetsPlugin, core::{Render, RenderApp, RenderSet}};
+
+use super::render_graph::RenderGraph;
+
+/// Adds the bloom to the hdr color of the lighting pass.
+/// The bloom can be configured or disabled with `BloomSettings`.
+pub(crate) struct BloomPlugin;
+impl Plugin for BloomPlugin {
+    fn build(&self, app: &mut App) {
+        // Add the bloom settings
+        app
+            .init_resource::<BloomSettings>();
+
+        // Add the bloom pipelines
+        app
+            .init_asset::<BloomPipelineAsset>()
+            .add_plugins(RenderAssetsPlugin::<GpuBloomPipeline>::default());
+
+        // Add the bloom render pass
+        let render_app = app.get_sub_app_mut(RenderApp).unwrap();
+        render_app
+            .init_resource::<BloomSettings>()
+            .init_resource::<BloomTextures>()
+            .add_systems(Render, BloomTextures::prepare.in_set(RenderSet::BindGroups));
+        render_app.world_mut().get_resource_mut::<RenderGraph>().unwrap()
+            .add_pass::<BloomRenderPass>(12);
+    }
+
+    fn finish(&self, app: &mut App) {
+        // Create the bloom pipelines
+        let pipeline: Handle<BloomPipelineAsset> = app.world_mut()
+            .get_resource::<AssetServer>().unwrap().add(BloomPipelineAsset);
+        app.get_sub_app_mut(RenderApp).unwrap().world_mut().spawn(BloomPipeline(pipeline));
+    }
+}
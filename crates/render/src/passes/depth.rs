@@ -1,7 +1,7 @@
 use bevy::prelude::*;
-use wde_wgpu::{bind_group::{BindGroup, BindGroupLayout, BindGroupLayoutBuilder, WgpuBindGroup}, instance::WRenderInstance, render_pipeline::WShaderStages, texture::{WTexture, WTextureUsages}};
+use wde_wgpu::{bind_group::{BindGroup, BindGroupLayout, BindGroupLayoutBuilder, WgpuBindGroup}, instance::WRenderInstance, render_pipeline::WShaderStages, texture::{WTexture, WTextureFormat, WTextureUsages}};
 
-use crate::{assets::{GpuTexture, RenderAssets, Texture}, core::{extract_macros::ExtractWorld, window::SurfaceResized}};
+use crate::{assets::{GpuTexture, RenderAssets, Texture}, core::{extract_macros::ExtractWorld, window::SurfaceResized, RenderConfig}};
 
 #[derive(Resource, Default)]
 pub struct DepthTextureLayoutRegenerate(pub bool);
@@ -37,9 +37,10 @@ impl DepthTextureLayout {
         let render_instance = render_instance.data.read().unwrap();
         let layout_built = BindGroupLayout::build(&layout, &render_instance);
 
-        // Create the bind group
+        // Create the bind group, sampling only the depth aspect of a depth/stencil texture
+        let depth_view = depth_texture.texture.view_depth();
         let bind_group = BindGroup::build("depth-texture", &render_instance, &layout_built, &vec![
-            BindGroup::texture_view(   0, &depth_texture.texture),
+            BindGroup::view(           0, &depth_view),
             BindGroup::texture_sampler(1, &depth_texture.texture)
         ]);
 
@@ -53,19 +54,36 @@ impl DepthTextureLayout {
 #[derive(Resource)]
 pub struct DepthTexture {
     pub texture: Handle<Texture>,
-    pub resized: bool
+    pub resized: bool,
+    /// Whether the texture has a stencil aspect, set from `RenderConfig::depth_stencil`.
+    /// The depth of the render pipelines then uses the `Depth24PlusStencil8` format instead of `Depth32Float`.
+    pub with_stencil: bool
 }
 impl DepthTexture {
-    pub fn create_texture(mut commands: Commands, server: Res<AssetServer>, window: Query<&Window>) {
+    /// The format of the depth texture.
+    pub fn format(&self) -> WTextureFormat {
+        Self::format_of(self.with_stencil)
+    }
+
+    fn format_of(with_stencil: bool) -> WTextureFormat {
+        if with_stencil {
+            WTexture::DEPTH_STENCIL_FORMAT
+        } else {
+            WTexture::DEPTH_FORMAT
+        }
+    }
+
+    pub fn create_texture(mut commands: Commands, server: Res<AssetServer>, window: Query<&Window>, config: Res<RenderConfig>) {
         let resolution = &window.single().resolution;
+        let with_stencil = config.depth_stencil;
         let texture = server.add(Texture {
             label: "depth".to_string(),
             size: (resolution.physical_width(), resolution.physical_height()),
-            format: WTexture::DEPTH_FORMAT,
+            format: Self::format_of(with_stencil),
             usages: WTextureUsages::RENDER_ATTACHMENT | WTextureUsages::TEXTURE_BINDING,
             ..Default::default()
         });
-        commands.insert_resource(DepthTexture { texture, resized: false });
+        commands.insert_resource(DepthTexture { texture, resized: false, with_stencil });
     }
 
     pub fn resize_texture(
@@ -78,7 +96,7 @@ impl DepthTexture {
             let texture = server.add(Texture {
                 label: "depth".to_string(),
                 size: (event.width, event.height),
-                format: WTexture::DEPTH_FORMAT,
+                format: textures.format(),
                 usages: WTextureUsages::RENDER_ATTACHMENT | WTextureUsages::TEXTURE_BINDING,
                 ..Default::default()
             });
@@ -97,7 +115,8 @@ impl DepthTexture {
 
         commands.insert_resource(DepthTexture {
             texture: depth_texture.texture.clone(),
-            resized: false
+            resized: false,
+            with_stencil: depth_texture.with_stencil
         });
     }
 }
@@ -0,0 +1,60 @@
+use bevy::{ecs::system::lifetimeless::{SRes, SResMut}, prelude::*};
+use wde_wgpu::render_pipeline::{WBlendState, WDepthStencilDescriptor};
+use crate::{assets::{PrepareAssetError, RenderAsset}, passes::hdr::HDR_FORMAT, pipelines::{CachedPipelineIndex, ComputePipelineDescriptor, PipelineManager, RenderPipelineDescriptor}};
+
+use super::FogTextures;
+
+#[derive(Default, Asset, Clone, TypePath)]
+pub struct VolumetricFogPipelineAsset;
+#[derive(Component)]
+pub struct VolumetricFogPipeline(pub Handle<VolumetricFogPipelineAsset>);
+pub struct GpuVolumetricFogPipeline {
+    /// The compute pipeline marching the ray of each pixel through the fog.
+    pub cached_march_index: CachedPipelineIndex,
+    /// The render pipeline blending the fog over the hdr color.
+    pub cached_composite_index: CachedPipelineIndex
+}
+impl RenderAsset for GpuVolumetricFogPipeline {
+    type SourceAsset = VolumetricFogPipelineAsset;
+    type Param = (SRes<AssetServer>, SResMut<PipelineManager>, SRes<FogTextures>);
+
+    fn prepare_asset(
+            _asset: Self::SourceAsset,
+            (assets_server, pipeline_manager, fog_textures): &mut bevy::ecs::system::SystemParamItem<Self::Param>
+        ) -> Result<Self, PrepareAssetError<Self::SourceAsset>> {
+        // Create the ray marching pipeline
+        let cached_march_index = pipeline_manager.create_compute_pipeline(ComputePipelineDescriptor {
+            label: "fog-march",
+            comp: Some(assets_server.load("fog/march_comp.wgsl")),
+            bind_group_layouts: vec![fog_textures.march_layout.clone()],
+            ..Default::default()
+        });
+
+        // Create the composite pipeline, drawing a full screen triangle without vertex buffer
+        // The in-scattered light is premultiplied by the opacity of the fog
+        let cached_composite_index = pipeline_manager.create_render_pipeline(RenderPipelineDescriptor {
+            label: "fog-composite",
+            vert: Some(assets_server.load("fog/composite_vert.wgsl")),
+            frag: Some(assets_server.load("fog/composite_frag.wgsl")),
+            bind_group_layouts: vec![fog_textures.composite_layout.clone()],
+            depth: WDepthStencilDescriptor {
+                enabled: false,
+                ..Default::default()
+            },
+            render_targets: Some(vec![HDR_FORMAT]),
+            blend: WBlendState::PREMULTIPLIED_ALPHA_BLENDING,
+            cull_mode: None,
+            vertex_input: false,
+            ..Default::default()
+        });
+
+        Ok(GpuVolumetricFogPipeline {
+            cached_march_index,
+            cached_composite_index
+        })
+    }
+
+    fn label(&self) -> &str {
+        "fog"
+    }
+}
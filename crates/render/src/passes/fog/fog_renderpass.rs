@@ -0,0 +1,102 @@
+use bevy::prelude::*;
+use crate::{core::pipeline_statistics::PipelineStatisticsQueries, assets::{GpuTexture, RenderAssets}, passes::{hdr::HdrTexture, render_graph::RenderPass}, pipelines::{CachedPipelineStatus, PipelineManager}};
+use wde_wgpu::{command_buffer::{RenderPassBuilder, RenderPassColorAttachment, WCommandBuffer, WLoadOp}, instance::WRenderInstance};
+
+use super::{FogEnabled, FogSettings, FogTextures, GpuVolumetricFogPipeline};
+
+/// Marches the ray of each pixel through the fog up to the depth of the scene, accumulating the light of the
+/// lights scattered toward the camera, then blends the fog over the hdr color of the lighting pass.
+/// The lights are not shadowed in the fog. When the fog is disabled with `FogEnabled`, the pass is skipped.
+#[derive(Default)]
+pub struct VolumetricFogRenderPass;
+impl RenderPass for VolumetricFogRenderPass {
+    fn extract(&self, main_world: &mut World, render_world: &mut World) {
+        // Extract the settings
+        let settings = *main_world.get_resource::<FogSettings>().unwrap();
+        let enabled = *main_world.get_resource::<FogEnabled>().unwrap();
+        render_world.insert_resource(settings);
+        render_world.insert_resource(enabled);
+    }
+
+    fn render(&self, render_world: &mut World) {
+        // Check if the fog is enabled
+        if !render_world.get_resource::<FogEnabled>().unwrap().0 {
+            return;
+        }
+
+        // Get the render instance and the hdr texture
+        let render_instance = render_world.get_resource::<WRenderInstance>().unwrap();
+        let render_instance = render_instance.data.read().unwrap();
+        let hdr = match render_world.get_resource::<HdrTexture>()
+            .and_then(|hdr_texture| render_world.get_resource::<RenderAssets<GpuTexture>>().unwrap().get(&hdr_texture.texture)) {
+            Some(hdr) => hdr,
+            None => return
+        };
+
+        // Check if the pipelines and the bind groups are ready
+        let fog_textures = render_world.get_resource::<FogTextures>().unwrap();
+        let pipeline_manager = render_world.get_resource::<PipelineManager>().unwrap();
+        let fog_pipeline = match render_world.get_resource::<RenderAssets<GpuVolumetricFogPipeline>>().unwrap().iter().next() {
+            Some((_, pipeline)) => pipeline,
+            None => return
+        };
+        let (march_pipeline, composite_pipeline, fog, march_bind_group, composite_bind_group) = match (
+            pipeline_manager.get_pipeline(fog_pipeline.cached_march_index),
+            pipeline_manager.get_pipeline(fog_pipeline.cached_composite_index),
+            &fog_textures.fog,
+            &fog_textures.march_bind_group,
+            &fog_textures.composite_bind_group
+        ) {
+            (
+                CachedPipelineStatus::OkCompute(march_pipeline), CachedPipelineStatus::OkRender(composite_pipeline),
+                Some(fog), Some(march_bind_group), Some(composite_bind_group)
+            ) => (march_pipeline, composite_pipeline, fog, march_bind_group, composite_bind_group),
+            _ => return
+        };
+
+        let mut command_buffer = WCommandBuffer::new(&render_instance, "fog");
+        let timestamp = command_buffer.begin_timestamp("fog");
+
+        // March the rays through the fog
+        {
+            let mut compute_pass = command_buffer.create_compute_pass("fog-march");
+            if compute_pass.set_pipeline(march_pipeline).is_ok() {
+                compute_pass.set_bind_group(0, march_bind_group);
+                if let Err(e) = compute_pass.dispatch(fog.size.0.div_ceil(8), fog.size.1.div_ceil(8), 1) {
+                    error!("Failed to dispatch: {:?}.", e);
+                }
+            } else {
+                error!("Failed to set pipeline.");
+            }
+        }
+
+        // Blend the fog over the hdr color
+        {
+            let mut render_pass = command_buffer.create_render_pass("fog-composite", |builder: &mut RenderPassBuilder| {
+                builder.add_color_attachment(RenderPassColorAttachment {
+                    texture: Some(&hdr.texture.view),
+                    load: WLoadOp::Load,
+                    ..Default::default()
+                });
+            });
+            let statistics = render_world.get_resource::<PipelineStatisticsQueries>();
+            let statistics_query = statistics.is_some_and(|statistics| statistics.begin(&mut render_pass, "fog-composite"));
+            if render_pass.set_pipeline(composite_pipeline).is_ok() {
+                render_pass.set_bind_group(0, composite_bind_group);
+                if let Err(e) = render_pass.draw(0..3, 0..1) {
+                    error!("Failed to draw: {:?}.", e);
+                }
+            } else {
+                error!("Failed to set pipeline.");
+            }
+            if let Some(statistics) = statistics {
+                statistics.end(&mut render_pass, statistics_query);
+            }
+        }
+        command_buffer.end_timestamp(timestamp);
+
+        // Submit the command buffer
+        command_buffer.submit(&render_instance);
+        command_buffer.trace_timestamps(&render_instance);
+    }
+}
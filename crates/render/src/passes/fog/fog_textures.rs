@@ -0,0 +1,147 @@
+use bevy::prelude::*;
+use wde_wgpu::{bind_group::{BindGroup, BindGroupLayout, BindGroupLayoutBuilder, WStorageTextureAccess, WgpuBindGroup}, buffer::{BufferBindingType, BufferUsage, WBuffer}, instance::WRenderInstance, render_pipeline::WShaderStages, texture::{WTexture, WTextureFormat, WTextureUsages}};
+
+use crate::{assets::{GpuBuffer, GpuTexture, RenderAssets, Texture}, features::{CameraFeatureBuffer, LightsFeatureBuffer}, passes::depth::DepthTexture};
+
+/// The format of the fog texture: the in-scattered light (rgb) and the opacity of the fog (a).
+pub const FOG_FORMAT: WTextureFormat = WTextureFormat::Rgba16Float;
+/// The number of samples along the ray of each pixel.
+pub const FOG_STEPS: u32 = 32;
+/// The distance up to which the fog is marched for the pixels without geometry.
+pub const FOG_MAX_DISTANCE: f32 = 100.0;
+
+/// Whether the volumetric fog is rendered (default: false).
+#[derive(Resource, Debug, Clone, Copy, Default, PartialEq)]
+pub struct FogEnabled(pub bool);
+
+/// The settings of the volumetric fog, a homogeneous medium filling the scene.
+#[derive(Resource, Debug, Clone, Copy, PartialEq)]
+pub struct FogSettings {
+    /// The density of the medium per world unit (default: 0.02).
+    pub density: f32,
+    /// The fraction of the density scattering the light toward the camera (default: 0.8).
+    pub scattering: f32,
+    /// The fraction of the density absorbing the light (default: 0.2).
+    pub absorption: f32,
+    /// The linear color of the fog, tinting the scattered light (default: white).
+    pub color: [f32; 3],
+}
+impl Default for FogSettings {
+    fn default() -> Self {
+        Self {
+            density: 0.02,
+            scattering: 0.8,
+            absorption: 0.2,
+            color: [1.0, 1.0, 1.0]
+        }
+    }
+}
+
+/// The fog uniform, read by the ray marching.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct FogUniform {
+    /// rgb: the color, a: the density.
+    pub color_density: [f32; 4],
+    /// x: the scattering, y: the absorption, z: the maximum distance, w: the number of steps.
+    pub params: [f32; 4]
+}
+
+/// The fog texture and the bind groups of the fog, recreated with the depth texture.
+#[derive(Resource)]
+pub struct FogTextures {
+    /// The buffer of the fog uniform.
+    pub uniform_buffer: WBuffer,
+    /// The layout of the ray marching: the depth, the camera, the lights, the uniform and the fog texture.
+    pub march_layout: BindGroupLayout,
+    /// The layout of the composite: the fog texture.
+    pub composite_layout: BindGroupLayout,
+
+    /// The in-scattered light and the opacity of the fog of each pixel.
+    pub fog: Option<WTexture>,
+    pub march_bind_group: Option<WgpuBindGroup>,
+    pub composite_bind_group: Option<WgpuBindGroup>,
+
+    /// The depth texture the fog texture was created for.
+    source: Option<AssetId<Texture>>,
+}
+impl FromWorld for FogTextures {
+    fn from_world(world: &mut World) -> Self {
+        let render_instance = world.get_resource::<WRenderInstance<'static>>().unwrap();
+        let render_instance = render_instance.data.read().unwrap();
+
+        let uniform_buffer = WBuffer::new(&render_instance, "fog",
+            std::mem::size_of::<FogUniform>(), BufferUsage::UNIFORM | BufferUsage::COPY_DST, None);
+        let march_layout = BindGroupLayout::new("fog-march", |builder: &mut BindGroupLayoutBuilder| {
+            builder.add_depth_texture_view(0, WShaderStages::COMPUTE);
+            builder.add_buffer(1, WShaderStages::COMPUTE, BufferBindingType::Uniform);
+            builder.add_buffer(2, WShaderStages::COMPUTE, BufferBindingType::Storage { read_only: true });
+            builder.add_buffer(3, WShaderStages::COMPUTE, BufferBindingType::Uniform);
+            builder.add_storage_texture(4, WShaderStages::COMPUTE, WStorageTextureAccess::WriteOnly, FOG_FORMAT);
+        });
+        let composite_layout = BindGroupLayout::new("fog-composite", |builder: &mut BindGroupLayoutBuilder| {
+            builder.add_texture_view(0, WShaderStages::FRAGMENT);
+        });
+
+        FogTextures {
+            uniform_buffer,
+            march_layout,
+            composite_layout,
+            fog: None,
+            march_bind_group: None,
+            composite_bind_group: None,
+            source: None
+        }
+    }
+}
+impl FogTextures {
+    /// Write the uniform, and recreate the fog texture and the bind groups when the depth texture changes.
+    pub fn prepare(
+        mut fog_textures: ResMut<FogTextures>, settings: Res<FogSettings>, depth_texture: Res<DepthTexture>,
+        (camera_buffer, lights_buffer): (Res<CameraFeatureBuffer>, Res<LightsFeatureBuffer>),
+        (textures, buffers): (Res<RenderAssets<GpuTexture>>, Res<RenderAssets<GpuBuffer>>),
+        render_instance: Res<WRenderInstance<'static>>
+    ) {
+        let fog_textures = &mut *fog_textures;
+        let render_instance = render_instance.data.read().unwrap();
+
+        // Update the uniform
+        fog_textures.uniform_buffer.write(&render_instance, bytemuck::cast_slice(&[FogUniform {
+            color_density: [settings.color[0], settings.color[1], settings.color[2], settings.density.max(0.0)],
+            params: [settings.scattering.max(0.0), settings.absorption.max(0.0), FOG_MAX_DISTANCE, FOG_STEPS as f32]
+        }]), 0);
+
+        // Check if the bind groups are up to date
+        if fog_textures.source == Some(depth_texture.texture.id()) {
+            return;
+        }
+        let (depth, camera, lights) = match (
+            textures.get(&depth_texture.texture), buffers.get(&camera_buffer.buffer), buffers.get(&lights_buffer.buffer_gpu)
+        ) {
+            (Some(depth), Some(camera), Some(lights)) => (depth, camera, lights),
+            _ => return
+        };
+
+        // Create the fog texture
+        let fog = WTexture::new(&render_instance, "fog", depth.texture.size, FOG_FORMAT,
+            WTextureUsages::TEXTURE_BINDING | WTextureUsages::STORAGE_BINDING, Some(1));
+
+        // Create the bind groups
+        let depth_view = depth.texture.view_depth();
+        let march_layout = fog_textures.march_layout.build(&render_instance);
+        fog_textures.march_bind_group = Some(BindGroup::build("fog-march", &render_instance, &march_layout, &vec![
+            BindGroup::view(0, &depth_view),
+            BindGroup::buffer(1, &camera.buffer),
+            BindGroup::buffer(2, &lights.buffer),
+            BindGroup::buffer(3, &fog_textures.uniform_buffer),
+            BindGroup::storage_texture(4, &fog.view)
+        ]));
+        let composite_layout = fog_textures.composite_layout.build(&render_instance);
+        fog_textures.composite_bind_group = Some(BindGroup::build("fog-composite", &render_instance, &composite_layout, &vec![
+            BindGroup::view(0, &fog.view)
+        ]));
+
+        fog_textures.fog = Some(fog);
+        fog_textures.source = Some(depth_texture.texture.id());
+    }
+}
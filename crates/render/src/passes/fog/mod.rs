@@ -0,0 +1,47 @@
+use bevy::prelude::*;
+
+mod fog_textures;
+mod fog_pipeline;
+mod fog_renderpass;
+
+pub use fog_textures::*;
+pub use fog_pipeline::*;
+pub use fog_renderpass::*;
+
+use crate::{assets::RenderAssetsPlugin, core::{Render, RenderApp, RenderSet}};
+
+use super::render_graph::RenderGraph;
+
+/// Adds a volumetric fog in front of the lit frame, scattering the light of the scene.
+/// The fog can be configured with `FogSettings` and enabled with `FogEnabled`.
+pub(crate) struct VolumetricFogPlugin;
+impl Plugin for VolumetricFogPlugin {
+    fn build(&self, app: &mut App) {
+        // Add the fog settings
+        app
+            .init_resource::<FogSettings>()
+            .init_resource::<FogEnabled>();
+
+        // Add the fog pipelines
+        app
+            .init_asset::<VolumetricFogPipelineAsset>()
+            .add_plugins(RenderAssetsPlugin::<GpuVolumetricFogPipeline>::default());
+
+        // Add the fog render pass
+        let render_app = app.get_sub_app_mut(RenderApp).unwrap();
+        render_app
+            .init_resource::<FogSettings>()
+            .init_resource::<FogEnabled>()
+            .init_resource::<FogTextures>()
+            .add_systems(Render, FogTextures::prepare.in_set(RenderSet::BindGroups));
+        render_app.world_mut().get_resource_mut::<RenderGraph>().unwrap()
+            .add_pass::<VolumetricFogRenderPass>(10);
+    }
+
+    fn finish(&self, app: &mut App) {
+        // Create the fog pipelines
+        let pipeline: Handle<VolumetricFogPipelineAsset> = app.world_mut()
+            .get_resource::<AssetServer>().unwrap().add(VolumetricFogPipelineAsset);
+        app.get_sub_app_mut(RenderApp).unwrap().world_mut().spawn(VolumetricFogPipeline(pipeline));
+    }
+}
@@ -0,0 +1,321 @@
+use bevy::prelude::*;
+use bytemuck::{Pod, Zeroable};
+use wde_wgpu::{bind_group::{BindGroup, BindGroupLayout, WgpuBindGroup}, buffer::{BufferBindingType, BufferUsage}, instance::WRenderInstance, render_pipeline::WShaderStages};
+
+use crate::{assets::{Buffer, GpuBuffer, RenderAssets}, core::{extract_macros::ExtractWorld, Extract, Render, RenderApp, RenderSet}};
+
+/// The maximum number of gizmo lines drawn per frame.
+const MAX_GIZMO_LINES: usize = 100_000;
+/// The maximum number of gizmo triangles drawn per frame.
+const MAX_GIZMO_TRIANGLES: usize = 50_000;
+/// The maximum number of gizmo wireframe triangles drawn per frame.
+const MAX_GIZMO_WIREFRAME_TRIANGLES: usize = 50_000;
+
+/// A gizmo vertex in world space.
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable, Default, Debug)]
+pub struct GizmoVertex {
+    /// The world space position of the vertex (w is unused).
+    pub position: [f32; 4],
+    /// The color of the vertex.
+    pub color: [f32; 4],
+}
+
+/// Accumulates the gizmo line segments of the current frame.
+/// All the lines are drawn with a single draw call, and the buffer is cleared at the start of each frame.
+#[derive(Resource, Default)]
+pub struct GizmoLineBuffer {
+    vertices: Vec<GizmoVertex>,
+}
+impl GizmoLineBuffer {
+    /// Add a line segment to the buffer.
+    ///
+    /// # Arguments
+    ///
+    /// * `start` - The world space start of the line.
+    /// * `end` - The world space end of the line.
+    /// * `color` - The color of the line.
+    pub fn line(&mut self, start: Vec3, end: Vec3, color: [f32; 4]) {
+        self.vertices.push(GizmoVertex { position: start.extend(1.0).to_array(), color });
+        self.vertices.push(GizmoVertex { position: end.extend(1.0).to_array(), color });
+    }
+
+    /// Get the number of lines in the buffer.
+    pub fn line_count(&self) -> usize {
+        self.vertices.len() / 2
+    }
+
+    /// Remove all the lines from the buffer.
+    pub fn clear(&mut self) {
+        self.vertices.clear();
+    }
+}
+
+/// Accumulates the solid gizmo triangles of the current frame.
+/// All the triangles are drawn with a single draw call, and the buffer is cleared at the start of each frame.
+#[derive(Resource, Default)]
+pub struct GizmoTriangleBuffer {
+    vertices: Vec<GizmoVertex>,
+}
+impl GizmoTriangleBuffer {
+    /// Add a filled triangle to the buffer.
+    ///
+    /// # Arguments
+    ///
+    /// * `a`, `b`, `c` - The world space corners of the triangle.
+    /// * `color` - The color of the triangle.
+    pub fn triangle(&mut self, a: Vec3, b: Vec3, c: Vec3, color: [f32; 4]) {
+        for corner in [a, b, c] {
+            self.vertices.push(GizmoVertex { position: corner.extend(1.0).to_array(), color });
+        }
+    }
+
+    /// Get the number of triangles in the buffer.
+    pub fn triangle_count(&self) -> usize {
+        self.vertices.len() / 3
+    }
+
+    /// Remove all the triangles from the buffer.
+    pub fn clear(&mut self) {
+        self.vertices.clear();
+    }
+}
+
+/// Accumulates the gizmo wireframe triangles of the current frame, drawn with the line polygon mode.
+/// All the triangles are drawn with a single draw call, and the buffer is cleared at the start of each frame.
+/// If the device does not support the line polygon mode, the edges of the triangles are drawn as gizmo lines.
+#[derive(Resource, Default)]
+pub struct GizmoWireframeBuffer {
+    vertices: Vec<GizmoVertex>,
+}
+impl GizmoWireframeBuffer {
+    /// Add a wireframe triangle to the buffer.
+    ///
+    /// # Arguments
+    ///
+    /// * `a`, `b`, `c` - The world space corners of the triangle.
+    /// * `color` - The color of the triangle edges.
+    pub fn triangle(&mut self, a: Vec3, b: Vec3, c: Vec3, color: [f32; 4]) {
+        for corner in [a, b, c] {
+            self.vertices.push(GizmoVertex { position: corner.extend(1.0).to_array(), color });
+        }
+    }
+
+    /// Add the outline of an axis aligned bounding box to the buffer, as two triangles per face.
+    ///
+    /// # Arguments
+    ///
+    /// * `min` - The world space minimum corner of the box.
+    /// * `max` - The world space maximum corner of the box.
+    /// * `color` - The color of the box edges.
+    pub fn aabb(&mut self, min: Vec3, max: Vec3, color: [f32; 4]) {
+        let corner = |x: bool, y: bool, z: bool| Vec3::new(
+            if x { max.x } else { min.x }, if y { max.y } else { min.y }, if z { max.z } else { min.z });
+        let faces = [
+            [corner(false, false, false), corner(true, false, false), corner(true, true, false), corner(false, true, false)],
+            [corner(false, false, true), corner(true, false, true), corner(true, true, true), corner(false, true, true)],
+            [corner(false, false, false), corner(false, true, false), corner(false, true, true), corner(false, false, true)],
+            [corner(true, false, false), corner(true, true, false), corner(true, true, true), corner(true, false, true)],
+            [corner(false, false, false), corner(true, false, false), corner(true, false, true), corner(false, false, true)],
+            [corner(false, true, false), corner(true, true, false), corner(true, true, true), corner(false, true, true)],
+        ];
+        for [a, b, c, d] in faces {
+            self.triangle(a, b, c, color);
+            self.triangle(a, c, d, color);
+        }
+    }
+
+    /// Get the number of triangles in the buffer.
+    pub fn triangle_count(&self) -> usize {
+        self.vertices.len() / 3
+    }
+
+    /// Remove all the triangles from the buffer.
+    pub fn clear(&mut self) {
+        self.vertices.clear();
+    }
+}
+
+/// The gpu buffers holding the gizmo lines and triangles of the frame.
+#[derive(Resource)]
+pub struct GizmoBuffersGpu {
+    pub lines: Handle<Buffer>,
+    pub line_count: u32,
+    pub triangles: Handle<Buffer>,
+    pub triangle_count: u32,
+    pub wireframe: Handle<Buffer>,
+    pub wireframe_triangle_count: u32,
+    pub bind_group_layout: BindGroupLayout,
+    pub lines_bind_group: Option<WgpuBindGroup>,
+    pub triangles_bind_group: Option<WgpuBindGroup>,
+    pub wireframe_bind_group: Option<WgpuBindGroup>,
+}
+impl GizmoBuffersGpu {
+    /// Flush the lines and triangles of the main world into the gpu buffers.
+    fn extract(
+        (lines, triangles, wireframe): (
+            ExtractWorld<Res<GizmoLineBuffer>>, ExtractWorld<Res<GizmoTriangleBuffer>>, ExtractWorld<Res<GizmoWireframeBuffer>>
+        ),
+        mut gizmo_buffers: ResMut<GizmoBuffersGpu>, mut buffers: ResMut<RenderAssets<GpuBuffer>>,
+        render_instance: Res<WRenderInstance<'static>>
+    ) {
+        let render_instance = render_instance.data.read().unwrap();
+
+        // Draw the edges of the wireframe triangles as lines if the device cannot draw wireframes
+        let wireframe_supported = render_instance.supports_polygon_mode_line();
+        let mut line_vertices = std::borrow::Cow::Borrowed(&lines.vertices);
+        if !wireframe_supported && wireframe.triangle_count() > 0 {
+            let line_vertices = line_vertices.to_mut();
+            for triangle in wireframe.vertices.chunks_exact(3) {
+                for (start, end) in [(0, 1), (1, 2), (2, 0)] {
+                    line_vertices.push(triangle[start]);
+                    line_vertices.push(triangle[end]);
+                }
+            }
+        }
+
+        // Write the lines
+        gizmo_buffers.line_count = 0;
+        if let Some(buffer) = buffers.get_mut(&gizmo_buffers.lines) {
+            let line_count = line_vertices.len() / 2;
+            if line_count > MAX_GIZMO_LINES {
+                warn!("Too many gizmo lines this frame ({}), only the first {} will be drawn.", line_count, MAX_GIZMO_LINES);
+            }
+            let count = line_count.min(MAX_GIZMO_LINES);
+            if count > 0 {
+                buffer.buffer.write(&render_instance, bytemuck::cast_slice(&line_vertices[..count * 2]), 0);
+            }
+            gizmo_buffers.line_count = count as u32;
+        }
+
+        // Write the triangles
+        gizmo_buffers.triangle_count = 0;
+        if let Some(buffer) = buffers.get_mut(&gizmo_buffers.triangles) {
+            if triangles.triangle_count() > MAX_GIZMO_TRIANGLES {
+                warn!("Too many gizmo triangles this frame ({}), only the first {} will be drawn.", triangles.triangle_count(), MAX_GIZMO_TRIANGLES);
+            }
+            let count = triangles.triangle_count().min(MAX_GIZMO_TRIANGLES);
+            if count > 0 {
+                buffer.buffer.write(&render_instance, bytemuck::cast_slice(&triangles.vertices[..count * 3]), 0);
+            }
+            gizmo_buffers.triangle_count = count as u32;
+        }
+
+        // Write the wireframe triangles
+        gizmo_buffers.wireframe_triangle_count = 0;
+        if !wireframe_supported {
+            return;
+        }
+        if let Some(buffer) = buffers.get_mut(&gizmo_buffers.wireframe) {
+            if wireframe.triangle_count() > MAX_GIZMO_WIREFRAME_TRIANGLES {
+                warn!("Too many gizmo wireframe triangles this frame ({}), only the first {} will be drawn.",
+                    wireframe.triangle_count(), MAX_GIZMO_WIREFRAME_TRIANGLES);
+            }
+            let count = wireframe.triangle_count().min(MAX_GIZMO_WIREFRAME_TRIANGLES);
+            if count > 0 {
+                buffer.buffer.write(&render_instance, bytemuck::cast_slice(&wireframe.vertices[..count * 3]), 0);
+            }
+            gizmo_buffers.wireframe_triangle_count = count as u32;
+        }
+    }
+
+    /// Create the bind groups of the lines and triangles buffers.
+    fn build_bind_groups(
+        buffers: Res<RenderAssets<GpuBuffer>>, mut gizmo_buffers: ResMut<GizmoBuffersGpu>,
+        render_instance: Res<WRenderInstance<'static>>
+    ) {
+        // Check if the bind groups are already created
+        if gizmo_buffers.lines_bind_group.is_some() && gizmo_buffers.triangles_bind_group.is_some()
+            && gizmo_buffers.wireframe_bind_group.is_some() {
+            return;
+        }
+
+        // Get the buffers
+        let (lines, triangles, wireframe) = match (
+            buffers.get(&gizmo_buffers.lines), buffers.get(&gizmo_buffers.triangles), buffers.get(&gizmo_buffers.wireframe)
+        ) {
+            (Some(lines), Some(triangles), Some(wireframe)) => (lines, triangles, wireframe),
+            _ => return
+        };
+
+        // Create the bind groups
+        let render_instance = render_instance.data.read().unwrap();
+        let layout = gizmo_buffers.bind_group_layout.build(&render_instance);
+        let lines_bind_group = BindGroup::build("gizmo-lines", &render_instance, &layout, &vec![
+            BindGroup::buffer(0, &lines.buffer)
+        ]);
+        let triangles_bind_group = BindGroup::build("gizmo-triangles", &render_instance, &layout, &vec![
+            BindGroup::buffer(0, &triangles.buffer)
+        ]);
+        let wireframe_bind_group = BindGroup::build("gizmo-wireframe", &render_instance, &layout, &vec![
+            BindGroup::buffer(0, &wireframe.buffer)
+        ]);
+        gizmo_buffers.lines_bind_group = Some(lines_bind_group);
+        gizmo_buffers.triangles_bind_group = Some(triangles_bind_group);
+        gizmo_buffers.wireframe_bind_group = Some(wireframe_bind_group);
+    }
+}
+
+/// Remove the gizmo lines and triangles of the last frame.
+fn clear_gizmo_buffers(
+    mut lines: ResMut<GizmoLineBuffer>, mut triangles: ResMut<GizmoTriangleBuffer>, mut wireframe: ResMut<GizmoWireframeBuffer>
+) {
+    lines.clear();
+    triangles.clear();
+    wireframe.clear();
+}
+
+pub struct GizmoBuffersPlugin;
+impl Plugin for GizmoBuffersPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<GizmoLineBuffer>()
+            .init_resource::<GizmoTriangleBuffer>()
+            .init_resource::<GizmoWireframeBuffer>()
+            .add_systems(First, clear_gizmo_buffers);
+
+        app.get_sub_app_mut(RenderApp).unwrap()
+            .add_systems(Extract, GizmoBuffersGpu::extract)
+            .add_systems(Render, GizmoBuffersGpu::build_bind_groups.in_set(RenderSet::BindGroups));
+    }
+
+    fn finish(&self, app: &mut App) {
+        let lines: Handle<Buffer> = app.world_mut().add_asset(Buffer {
+            label: "gizmo-lines".to_string(),
+            size: std::mem::size_of::<GizmoVertex>() * 2 * MAX_GIZMO_LINES,
+            usage: BufferUsage::STORAGE | BufferUsage::COPY_DST,
+            content: None,
+        });
+        let triangles: Handle<Buffer> = app.world_mut().add_asset(Buffer {
+            label: "gizmo-triangles".to_string(),
+            size: std::mem::size_of::<GizmoVertex>() * 3 * MAX_GIZMO_TRIANGLES,
+            usage: BufferUsage::STORAGE | BufferUsage::COPY_DST,
+            content: None,
+        });
+        let wireframe: Handle<Buffer> = app.world_mut().add_asset(Buffer {
+            label: "gizmo-wireframe".to_string(),
+            size: std::mem::size_of::<GizmoVertex>() * 3 * MAX_GIZMO_WIREFRAME_TRIANGLES,
+            usage: BufferUsage::STORAGE | BufferUsage::COPY_DST,
+            content: None,
+        });
+
+        let bind_group_layout = BindGroupLayout::new("gizmo-buffers", |builder| {
+            builder.add_buffer(0,
+                WShaderStages::VERTEX,
+                BufferBindingType::Storage { read_only: true });
+        });
+        app.get_sub_app_mut(RenderApp).unwrap()
+            .world_mut().insert_resource(GizmoBuffersGpu {
+                lines,
+                line_count: 0,
+                triangles,
+                triangle_count: 0,
+                wireframe,
+                wireframe_triangle_count: 0,
+                bind_group_layout,
+                lines_bind_group: None,
+                triangles_bind_group: None,
+                wireframe_bind_group: None
+            });
+    }
+}
@@ -1,8 +1,8 @@
 use bevy::{ecs::system::lifetimeless::{SRes, SResMut}, prelude::*};
-use wde_wgpu::render_pipeline::WDepthStencilDescriptor;
+use wde_wgpu::{instance::WRenderInstance, render_pipeline::{WDepthStencilDescriptor, WPolygonMode}};
 use crate::{assets::{materials::GizmoMaterialAsset, GpuMaterial, PrepareAssetError, RenderAsset, RenderAssets}, features::CameraFeatureRender, pipelines::{CachedPipelineIndex, PipelineManager, RenderPipelineDescriptor}};
 
-use super::GizmoSsbo;
+use super::{GizmoBuffersGpu, GizmoSsbo};
 
 
 #[derive(Default, Asset, Clone, TypePath)]
@@ -64,3 +64,78 @@ impl RenderAsset for GpuGizmoRenderPipeline {
         "gizmo"
     }
 }
+
+
+#[derive(Default, Asset, Clone, TypePath)]
+pub struct GizmoBuffersRenderPipelineAsset;
+#[derive(Component)]
+pub struct GizmoBuffersRenderPipeline(pub Handle<GizmoBuffersRenderPipelineAsset>);
+pub struct GpuGizmoBuffersRenderPipeline {
+    pub lines_cached_pipeline_index: CachedPipelineIndex,
+    pub triangles_cached_pipeline_index: CachedPipelineIndex,
+    /// The pipeline drawing the triangles as wireframes, if the device supports the line polygon mode.
+    pub wireframe_cached_pipeline_index: Option<CachedPipelineIndex>
+}
+impl RenderAsset for GpuGizmoBuffersRenderPipeline {
+    type SourceAsset = GizmoBuffersRenderPipelineAsset;
+    type Param = (
+        SRes<AssetServer>, SResMut<PipelineManager>, SRes<CameraFeatureRender>, SRes<GizmoBuffersGpu>,
+        SRes<WRenderInstance<'static>>
+    );
+
+    fn prepare_asset(
+            _asset: Self::SourceAsset,
+            (
+                assets_server, pipeline_manager,
+                camera_feature, gizmo_buffers, render_instance
+            ): &mut bevy::ecs::system::SystemParamItem<Self::Param>
+        ) -> Result<Self, PrepareAssetError<Self::SourceAsset>> {
+
+        // Create the lines pipeline
+        let lines_desc = RenderPipelineDescriptor {
+            label: "gizmo-lines",
+            vert: Some(assets_server.load("gizmo/buffers_vert.wgsl")),
+            frag: Some(assets_server.load("gizmo/buffers_frag.wgsl")),
+            bind_group_layouts: vec![camera_feature.layout.clone(), gizmo_buffers.bind_group_layout.clone()],
+            depth: WDepthStencilDescriptor {
+                enabled: true,
+                ..Default::default()
+            },
+            topology: wde_wgpu::render_pipeline::WTopology::LineList,
+            cull_mode: None,
+            blend: wde_wgpu::render_pipeline::WBlendState::ALPHA_BLENDING,
+            vertex_input: false,
+            ..Default::default()
+        };
+        let lines_cached_index = pipeline_manager.create_render_pipeline(lines_desc.clone());
+
+        // Create the triangles pipeline
+        let triangles_desc = RenderPipelineDescriptor {
+            label: "gizmo-triangles",
+            topology: wde_wgpu::render_pipeline::WTopology::TriangleList,
+            ..lines_desc
+        };
+        let triangles_cached_index = pipeline_manager.create_render_pipeline(triangles_desc.clone());
+
+        // Create the wireframe pipeline, the wireframe triangles being drawn as lines without support
+        let wireframe_cached_index = if render_instance.data.read().unwrap().supports_polygon_mode_line() {
+            Some(pipeline_manager.create_render_pipeline(RenderPipelineDescriptor {
+                label: "gizmo-wireframe",
+                polygon_mode: WPolygonMode::Line,
+                ..triangles_desc
+            }))
+        } else {
+            None
+        };
+
+        Ok(GpuGizmoBuffersRenderPipeline {
+            lines_cached_pipeline_index: lines_cached_index,
+            triangles_cached_pipeline_index: triangles_cached_index,
+            wireframe_cached_pipeline_index: wireframe_cached_index
+        })
+    }
+
+    fn label(&self) -> &str {
+        "gizmo-buffers"
+    }
+}
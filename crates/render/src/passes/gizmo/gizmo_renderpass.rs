@@ -2,7 +2,7 @@ use bevy::{prelude::*, utils::HashMap};
 use crate::{assets::{materials::{GizmoMaterial, GizmoMaterialAsset}, GpuBuffer, GpuMaterial, GpuMesh, GpuTexture, Mesh, MeshAsset, RenderAssets}, components::TransformUniform, core::SwapchainFrame, features::CameraFeatureRender, passes::{depth::DepthTexture, render_graph::RenderPass}, pipelines::{CachedPipelineStatus, PipelineManager}};
 use wde_wgpu::{command_buffer::{RenderPassBuilder, RenderPassColorAttachment, RenderPassDepth, WCommandBuffer, WLoadOp}, instance::WRenderInstance};
 
-use super::{GizmoSsbo, GpuGizmoRenderPipeline};
+use super::{GizmoBuffersGpu, GizmoSsbo, GpuGizmoBuffersRenderPipeline, GpuGizmoRenderPipeline};
 
 pub struct GizmoRenderBatch {
     mesh: Handle<MeshAsset>,
@@ -163,11 +163,14 @@ impl RenderPass for GizmoRenderPass {
             None => return
         };
 
-        // Check if pipeline is ready
-        let gizmo_pipeline = match render_world.get_resource::<RenderAssets<GpuGizmoRenderPipeline>>().unwrap().iter().next() {
-            Some((_, pipeline)) => pipeline,
-            None => return
-        };
+        // Get the pipelines
+        let gizmo_pipeline = render_world.get_resource::<RenderAssets<GpuGizmoRenderPipeline>>().unwrap()
+            .iter().next().map(|(_, pipeline)| pipeline);
+        let gizmo_buffers_pipeline = render_world.get_resource::<RenderAssets<GpuGizmoBuffersRenderPipeline>>().unwrap()
+            .iter().next().map(|(_, pipeline)| pipeline);
+        if gizmo_pipeline.is_none() && gizmo_buffers_pipeline.is_none() {
+            return;
+        }
 
         // Create the render pass
         let mut command_buffer = WCommandBuffer::new(&render_instance, "gizmo");
@@ -190,11 +193,11 @@ impl RenderPass for GizmoRenderPass {
             // Render the mesh
             let pipeline_manager = render_world.get_resource::<PipelineManager>().unwrap();
             if let (
-                CachedPipelineStatus::OkRender(pipeline),
+                Some(CachedPipelineStatus::OkRender(pipeline)),
                 Some(camera_bg),
                 Some(ssbo_bind_group)
             ) = (
-                pipeline_manager.get_pipeline(gizmo_pipeline.cached_pipeline_index),
+                gizmo_pipeline.map(|gizmo_pipeline| pipeline_manager.get_pipeline(gizmo_pipeline.cached_pipeline_index)),
                 &render_world.get_resource::<CameraFeatureRender>().unwrap().bind_group,
                 &render_world.get_resource::<GizmoSsbo>().unwrap().bind_group
             ) {
@@ -257,6 +260,45 @@ impl RenderPass for GizmoRenderPass {
                     error!("Failed to set pipeline.");
                 }
             }
+
+            // Render the lines and triangles buffers, with one draw call each
+            let gizmo_buffers = render_world.get_resource::<GizmoBuffersGpu>().unwrap();
+            if let (
+                Some(gizmo_buffers_pipeline),
+                Some(camera_bg)
+            ) = (
+                gizmo_buffers_pipeline,
+                &render_world.get_resource::<CameraFeatureRender>().unwrap().bind_group
+            ) {
+                let draws = [
+                    (Some(gizmo_buffers_pipeline.lines_cached_pipeline_index), &gizmo_buffers.lines_bind_group, gizmo_buffers.line_count * 2),
+                    (Some(gizmo_buffers_pipeline.triangles_cached_pipeline_index), &gizmo_buffers.triangles_bind_group, gizmo_buffers.triangle_count * 3),
+                    (gizmo_buffers_pipeline.wireframe_cached_pipeline_index, &gizmo_buffers.wireframe_bind_group, gizmo_buffers.wireframe_triangle_count * 3)
+                ];
+                for (cached_pipeline_index, bind_group, vertex_count) in draws {
+                    let cached_pipeline_index = match cached_pipeline_index {
+                        Some(index) if vertex_count > 0 => index,
+                        _ => continue
+                    };
+                    if let (
+                        CachedPipelineStatus::OkRender(pipeline),
+                        Some(bind_group)
+                    ) = (
+                        pipeline_manager.get_pipeline(cached_pipeline_index),
+                        bind_group
+                    ) {
+                        if render_pass.set_pipeline(pipeline).is_err() {
+                            error!("Failed to set pipeline.");
+                            continue;
+                        }
+                        render_pass.set_bind_group(0, camera_bg);
+                        render_pass.set_bind_group(1, bind_group);
+                        if let Err(e) = render_pass.draw(0..vertex_count, 0..1) {
+                            error!("Failed to draw: {:?}.", e);
+                        }
+                    }
+                }
+            }
         }
 
         // Submit the command buffer
@@ -1,9 +1,11 @@
 use bevy::prelude::*;
 
+mod gizmo_buffers;
 mod gizmo_pipeline;
 mod gizmo_renderpass;
 mod gizmo_ssbo;
 
+pub use gizmo_buffers::*;
 pub use gizmo_pipeline::*;
 pub use gizmo_renderpass::*;
 pub use gizmo_ssbo::*;
@@ -17,12 +19,15 @@ impl Plugin for GizmoFeaturesPlugin {
     fn build(&self, app: &mut App) {
         // Add the gizmo ssbo
         app
-            .add_plugins(GizmoSsboPlugin);
+            .add_plugins(GizmoSsboPlugin)
+            .add_plugins(GizmoBuffersPlugin);
 
         // Add the pbr pipelines
         app
             .init_asset::<GizmoRenderPipelineAsset>()
-            .add_plugins(RenderAssetsPlugin::<GpuGizmoRenderPipeline>::default());
+            .add_plugins(RenderAssetsPlugin::<GpuGizmoRenderPipeline>::default())
+            .init_asset::<GizmoBuffersRenderPipelineAsset>()
+            .add_plugins(RenderAssetsPlugin::<GpuGizmoBuffersRenderPipeline>::default());
 
         // Add the gizmo render pass
         let mut render_graph = app.get_sub_app_mut(RenderApp).unwrap()
@@ -42,6 +47,11 @@ impl Plugin for GizmoFeaturesPlugin {
         let pipeline: Handle<GizmoRenderPipelineAsset> = app.world_mut()
             .get_resource::<AssetServer>().unwrap().add(GizmoRenderPipelineAsset);
         app.get_sub_app_mut(RenderApp).unwrap().world_mut().spawn(GizmoRenderPipeline(pipeline));
+
+        // Create the gizmo lines and triangles pipelines
+        let pipeline: Handle<GizmoBuffersRenderPipelineAsset> = app.world_mut()
+            .get_resource::<AssetServer>().unwrap().add(GizmoBuffersRenderPipelineAsset);
+        app.get_sub_app_mut(RenderApp).unwrap().world_mut().spawn(GizmoBuffersRenderPipeline(pipeline));
     }
 }
 
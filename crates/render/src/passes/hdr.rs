@@ -0,0 +1,52 @@
+use bevy::prelude::*;
+use wde_wgpu::texture::{WSamplerDescriptor, WTextureFormat, WTextureUsages};
+
+use crate::{assets::Texture, core::{extract_macros::ExtractWorld, window::SurfaceResized}};
+
+/// The format of the high dynamic range color texture.
+pub const HDR_FORMAT: WTextureFormat = WTextureFormat::Rgba16Float;
+
+/// The high dynamic range color texture the lighting pass renders into, keeping the values above 1.0.
+/// The bloom is added to it, then it is tone mapped onto the swapchain.
+#[derive(Resource)]
+pub struct HdrTexture {
+    pub texture: Handle<Texture>,
+    pub resized: bool
+}
+impl HdrTexture {
+    /// Create an hdr color texture.
+    fn texture(size: (u32, u32)) -> Texture {
+        Texture {
+            label: "hdr-color".to_string(),
+            size,
+            format: HDR_FORMAT,
+            usages: WTextureUsages::RENDER_ATTACHMENT | WTextureUsages::TEXTURE_BINDING,
+            sampler: WSamplerDescriptor::LINEAR_CLAMP,
+            ..Default::default()
+        }
+    }
+
+    pub fn create_texture(mut commands: Commands, server: Res<AssetServer>, window: Query<&Window>) {
+        let resolution = &window.single().resolution;
+        let texture = server.add(Self::texture((resolution.physical_width(), resolution.physical_height())));
+        commands.insert_resource(HdrTexture { texture, resized: false });
+    }
+
+    pub fn resize_texture(
+        mut window_resized_events: EventReader<SurfaceResized>,
+        server: Res<AssetServer>, mut hdr_texture: ResMut<HdrTexture>
+    ) {
+        hdr_texture.resized = false;
+        for event in window_resized_events.read() {
+            hdr_texture.texture = server.add(Self::texture((event.width, event.height)));
+            hdr_texture.resized = true;
+        }
+    }
+
+    pub fn extract_texture(mut commands: Commands, hdr_texture: ExtractWorld<Res<HdrTexture>>) {
+        commands.insert_resource(HdrTexture {
+            texture: hdr_texture.texture.clone(),
+            resized: hdr_texture.resized
+        });
+    }
+}
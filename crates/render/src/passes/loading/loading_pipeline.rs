@@ -0,0 +1,47 @@
+use bevy::{ecs::system::lifetimeless::{SRes, SResMut}, prelude::*};
+use wde_wgpu::render_pipeline::{WDepthStencilDescriptor, WShaderStages};
+use crate::{assets::{PrepareAssetError, RenderAsset}, pipelines::{CachedPipelineIndex, PipelineManager, PushConstantDescriptor, RenderPipelineDescriptor}};
+
+
+#[derive(Default, Asset, Clone, TypePath)]
+pub struct LoadingScreenRenderPipelineAsset;
+#[derive(Component)]
+pub struct LoadingScreenRenderPipeline(pub Handle<LoadingScreenRenderPipelineAsset>);
+pub struct GpuLoadingScreenRenderPipeline {
+    pub cached_pipeline_index: CachedPipelineIndex
+}
+impl RenderAsset for GpuLoadingScreenRenderPipeline {
+    type SourceAsset = LoadingScreenRenderPipelineAsset;
+    type Param = (SRes<AssetServer>, SResMut<PipelineManager>);
+
+    fn prepare_asset(
+            _asset: Self::SourceAsset,
+            (assets_server, pipeline_manager): &mut bevy::ecs::system::SystemParamItem<Self::Param>
+        ) -> Result<Self, PrepareAssetError<Self::SourceAsset>> {
+        // Create the pipeline
+        let pipeline_desc = RenderPipelineDescriptor {
+            label: "loading-screen",
+            vert: Some(assets_server.load("loading/loading_screen_vert.wgsl")),
+            frag: Some(assets_server.load("loading/loading_screen_frag.wgsl")),
+            depth: WDepthStencilDescriptor {
+                enabled: false,
+                ..Default::default()
+            },
+            push_constants: vec![PushConstantDescriptor {
+                stages: WShaderStages::FRAGMENT,
+                offset: 0,
+                size: std::mem::size_of::<[f32; 4]>() as u32
+            }],
+            ..Default::default()
+        };
+        let cached_index = pipeline_manager.create_render_pipeline(pipeline_desc);
+
+        Ok(GpuLoadingScreenRenderPipeline {
+            cached_pipeline_index: cached_index
+        })
+    }
+
+    fn label(&self) -> &str {
+        "loading-screen"
+    }
+}
@@ -0,0 +1,126 @@
+use bevy::prelude::*;
+use crate::{assets::{GpuMesh, MeshAsset, ModelBoundingBox, RenderAssets}, core::SwapchainFrame, passes::render_graph::RenderPass, pipelines::{CachedPipelineStatus, PipelineManager, ShaderCompilationProgress}};
+use wde_wgpu::{command_buffer::{RenderPassBuilder, RenderPassColorAttachment, WCommandBuffer, WLoadOp}, instance::WRenderInstance, render_pipeline::WShaderStages, vertex::WVertex};
+
+use super::{GpuLoadingScreenRenderPipeline, LoadingScreen};
+
+#[derive(Resource, Default)]
+pub struct LoadingScreenRenderPassMesh {
+    pub mesh: Option<Handle<MeshAsset>>
+}
+impl LoadingScreenRenderPassMesh {
+    // Creates the rendering mesh.
+    pub fn init(assets_server: Res<AssetServer>, mut render_pass: ResMut<LoadingScreenRenderPassMesh>) {
+        // Create the 2d quad mesh
+        let mesh: Handle<MeshAsset> = assets_server.add(MeshAsset {
+            label: "loading-screen".to_string(),
+            vertices: vec![
+                WVertex { position: [-1.0, 1.0, 0.0], uv: [0.0, 1.0], normal: [0.0, 0.0, 0.0], uv1: [0.0, 1.0], tangent: [0.0; 4] },
+                WVertex { position: [-1.0, -1.0, 0.0], uv: [0.0, 0.0], normal: [0.0, 0.0, 0.0], uv1: [0.0, 0.0], tangent: [0.0; 4] },
+                WVertex { position: [1.0, -1.0, 0.0], uv: [1.0, 0.0], normal: [0.0, 0.0, 0.0], uv1: [1.0, 0.0], tangent: [0.0; 4] },
+                WVertex { position: [1.0, 1.0, 0.0], uv: [1.0, 1.0], normal: [0.0, 0.0, 0.0], uv1: [1.0, 1.0], tangent: [0.0; 4] },
+            ],
+            indices: vec![0, 1, 2, 0, 2, 3],
+            bounding_box: ModelBoundingBox {
+                min: Vec3::new(-1.0, -1.0, 0.0),
+                max: Vec3::new(1.0, 1.0, 0.0),
+            },
+        });
+        render_pass.mesh = Some(mesh);
+    }
+}
+
+/// The state of the loading screen in the render world.
+#[derive(Resource, Default)]
+pub struct LoadingScreenState {
+    /// The progress of the loading between 0 and 1. None if the loading screen is not displayed.
+    pub progress: Option<f32>
+}
+
+#[derive(Default)]
+pub struct LoadingScreenRenderPass;
+impl RenderPass for LoadingScreenRenderPass {
+    fn extract(&self, main_world: &mut World, render_world: &mut World) {
+        // Extract the mesh
+        let mesh_cpu = main_world.get_resource::<LoadingScreenRenderPassMesh>().unwrap();
+        let mut render_pass = render_world.get_resource_mut::<LoadingScreenRenderPassMesh>().unwrap();
+        render_pass.mesh = mesh_cpu.mesh.as_ref().map(|mesh| mesh.clone_weak());
+
+        // Extract the progress
+        let progress = match main_world.get_resource::<LoadingScreen>() {
+            Some(_) => {
+                let progress = main_world.get_resource::<ShaderCompilationProgress>().unwrap();
+                Some(if progress.total == 0 { 0.0 } else { progress.finished() as f32 / progress.total as f32 })
+            },
+            None => None
+        };
+        render_world.get_resource_mut::<LoadingScreenState>().unwrap().progress = progress;
+    }
+
+    fn render(&self, world: &mut World) {
+        // Check if the loading screen is displayed
+        let progress = match world.get_resource::<LoadingScreenState>().unwrap().progress {
+            Some(progress) => progress,
+            None => return
+        };
+
+        // Get the render instance and swapchain frame
+        let render_instance = world.get_resource::<WRenderInstance>().unwrap();
+        let render_instance = render_instance.data.read().unwrap();
+        let swapchain_frame = world.get_resource::<SwapchainFrame>().unwrap().data.as_ref().unwrap();
+
+        // Check if mesh is ready
+        let meshes = world.get_resource::<RenderAssets<GpuMesh>>().unwrap();
+        let mesh = match &world.get_resource::<LoadingScreenRenderPassMesh>().unwrap().mesh {
+            Some(mesh) => match meshes.get(mesh) {
+                Some(mesh) => mesh,
+                None => return
+            },
+            None => return
+        };
+
+        // Check if pipeline is ready
+        let pipeline_manager = world.get_resource::<PipelineManager>().unwrap();
+        let loading_pipeline = match world.get_resource::<RenderAssets<GpuLoadingScreenRenderPipeline>>().unwrap().iter().next() {
+            Some((_, pipeline)) => pipeline,
+            None => return
+        };
+
+        // Create the render pass
+        let mut command_buffer = WCommandBuffer::new(&render_instance, "loading-screen");
+        {
+            let mut render_pass = command_buffer.create_render_pass("loading-screen", |builder: &mut RenderPassBuilder| {
+                builder.add_color_attachment(RenderPassColorAttachment {
+                    texture: Some(&swapchain_frame.view),
+                    load: WLoadOp::Load,
+                    ..Default::default()
+                });
+            });
+
+            // Render the progress bar
+            if let CachedPipelineStatus::OkRender(pipeline) = pipeline_manager.get_pipeline(loading_pipeline.cached_pipeline_index) {
+                if render_pass.set_pipeline(pipeline).is_ok() {
+                    // Set the mesh
+                    render_pass.set_vertex_buffer(0, &mesh.vertex_buffer);
+                    render_pass.set_index_buffer(&mesh.index_buffer);
+
+                    // Set the progress
+                    render_pass.set_push_constants(WShaderStages::FRAGMENT, bytemuck::cast_slice(&[progress, 0.0, 0.0, 0.0]));
+
+                    // Draw the mesh
+                    match render_pass.draw_indexed(0..mesh.index_count, 0..1) {
+                        Ok(_) => {},
+                        Err(e) => {
+                            error!("Failed to draw: {:?}.", e);
+                        }
+                    };
+                } else {
+                    error!("Failed to set pipeline.");
+                }
+            }
+        }
+
+        // Submit the command buffer
+        command_buffer.submit(&render_instance);
+    }
+}
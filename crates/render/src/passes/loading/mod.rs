@@ -0,0 +1,89 @@
+use bevy::prelude::*;
+
+mod loading_pipeline;
+mod loading_renderpass;
+
+pub use loading_pipeline::*;
+pub use loading_renderpass::*;
+
+use crate::{assets::RenderAssetsPlugin, core::RenderApp, pipelines::ShaderCompilationProgress};
+
+use super::render_graph::RenderGraph;
+
+/// Displays a progress bar on top of the screen while the pipelines are compiling.
+/// Once every registered pipeline is compiled or failed to be created, the `LoadingScreen` resource is removed
+/// and the game content is rendered.
+#[derive(Resource, Default)]
+pub struct LoadingScreen;
+impl LoadingScreen {
+    /// Remove the loading screen once all the pipelines are compiled or failed.
+    pub fn update(mut commands: Commands, progress: Res<ShaderCompilationProgress>) {
+        if progress.total > 0 && progress.is_done() {
+            if progress.failed > 0 {
+                warn!("{} of the {} pipelines failed to be created, removing the loading screen.", progress.failed, progress.total);
+            } else {
+                info!("All the {} pipelines are compiled, removing the loading screen.", progress.total);
+            }
+            commands.remove_resource::<LoadingScreen>();
+        }
+    }
+}
+
+pub(crate) struct LoadingScreenPlugin;
+impl Plugin for LoadingScreenPlugin {
+    fn build(&self, app: &mut App) {
+        // Add the loading screen to the main world
+        app
+            .init_resource::<LoadingScreen>()
+            .init_resource::<LoadingScreenRenderPassMesh>()
+            .add_systems(Startup, LoadingScreenRenderPassMesh::init)
+            .add_systems(Update, LoadingScreen::update.run_if(resource_exists::<LoadingScreen>));
+
+        // Add the loading screen pipeline
+        app
+            .init_asset::<LoadingScreenRenderPipelineAsset>()
+            .add_plugins(RenderAssetsPlugin::<GpuLoadingScreenRenderPipeline>::default());
+
+        // Add the loading screen render pass
+        let render_app = app.get_sub_app_mut(RenderApp).unwrap();
+        render_app
+            .init_resource::<LoadingScreenRenderPassMesh>()
+            .init_resource::<LoadingScreenState>();
+        render_app.world_mut().get_resource_mut::<RenderGraph>().unwrap()
+            .add_pass::<LoadingScreenRenderPass>(10_000);
+    }
+
+    fn finish(&self, app: &mut App) {
+        // Create the loading screen pipeline
+        let pipeline: Handle<LoadingScreenRenderPipelineAsset> = app.world_mut()
+            .get_resource::<AssetServer>().unwrap().add(LoadingScreenRenderPipelineAsset);
+        app.get_sub_app_mut(RenderApp).unwrap().world_mut().spawn(LoadingScreenRenderPipeline(pipeline));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn loading_screen_counts_the_failed_pipelines_as_finished() {
+        let mut world = World::new();
+        world.init_resource::<LoadingScreen>();
+        world.insert_resource(ShaderCompilationProgress { total: 3, compiled: 1, failed: 1 });
+        let mut system = IntoSystem::into_system(LoadingScreen::update);
+        system.initialize(&mut world);
+        let mut update = |world: &mut World| {
+            system.run((), world);
+            system.apply_deferred(world);
+        };
+
+        // A pipeline is still compiling
+        update(&mut world);
+        assert!(world.contains_resource::<LoadingScreen>());
+
+        // The last pipeline fails to be created
+        world.insert_resource(ShaderCompilationProgress { total: 3, compiled: 1, failed: 2 });
+        update(&mut world);
+        assert!(!world.contains_resource::<LoadingScreen>());
+    }
+}
@@ -1,13 +1,27 @@
 use bevy::prelude::*;
+use bloom::BloomPlugin;
 use depth::{DepthTexture, DepthTextureLayout};
+use fog::VolumetricFogPlugin;
 use gizmo::GizmoFeaturesPlugin;
+use hdr::HdrTexture;
+use loading::LoadingScreenPlugin;
 use pbr::PbrFeaturesPlugin;
+use skybox::SkyboxPlugin;
+use tonemapping::ToneMappingPlugin;
+use voxel::VoxelizationPlugin;
 
 use crate::core::{Extract, Render, RenderApp, RenderSet};
 
 pub mod pbr;
 pub mod depth;
+pub mod hdr;
+pub mod skybox;
+pub mod fog;
+pub mod bloom;
+pub mod tonemapping;
 pub mod gizmo;
+pub mod loading;
+pub mod voxel;
 pub mod render_graph;
 
 pub(crate) struct RendererPlugin;
@@ -22,9 +36,22 @@ impl Plugin for RendererPlugin {
             .add_systems(Extract, DepthTexture::extract_texture)
             .add_systems(Render, DepthTextureLayout::build_bind_group.in_set(RenderSet::BindGroups));
 
+        // Add the hdr color texture to the app
+        app
+            .add_systems(Startup, HdrTexture::create_texture)
+            .add_systems(Update, HdrTexture::resize_texture);
+        app.get_sub_app_mut(RenderApp).unwrap()
+            .add_systems(Extract, HdrTexture::extract_texture);
+
         // Add the different render passes to the app
         app
+            .add_plugins(SkyboxPlugin)
             .add_plugins(PbrFeaturesPlugin)
-            .add_plugins(GizmoFeaturesPlugin);
+            .add_plugins(GizmoFeaturesPlugin)
+            .add_plugins(VolumetricFogPlugin)
+            .add_plugins(VoxelizationPlugin)
+            .add_plugins(BloomPlugin)
+            .add_plugins(ToneMappingPlugin)
+            .add_plugins(LoadingScreenPlugin);
     }
 }
@@ -8,6 +8,25 @@ mod pbr_pipeline_lighting;
 mod pbr_renderpass_lighting;
 mod pbr_ssbo;
 mod pbr_textures;
+mod pbr_brdf_lut;
+mod pbr_occlusion;
+mod pbr_shadow_maps;
+mod pbr_pipeline_shadow;
+mod pbr_renderpass_shadow;
+mod pbr_point_shadow_maps;
+mod pbr_renderpass_point_shadow;
+mod pbr_spot_shadow_maps;
+mod pbr_renderpass_spot_shadow;
+mod pbr_ssao;
+mod pbr_pipeline_ssao;
+mod pbr_renderpass_ssao;
+mod pbr_ssr;
+mod pbr_pipeline_ssr;
+mod pbr_renderpass_ssr;
+mod pbr_indirect;
+mod pbr_wboit;
+mod pbr_pipeline_wboit;
+mod pbr_renderpass_wboit;
 
 pub use pbr_pipeline_gbuffer::*;
 pub use pbr_renderpass_gbuffer::*;
@@ -15,6 +34,25 @@ pub use pbr_pipeline_lighting::*;
 pub use pbr_renderpass_lighting::*;
 pub use pbr_ssbo::*;
 pub use pbr_textures::*;
+pub use pbr_brdf_lut::*;
+pub use pbr_occlusion::*;
+pub use pbr_shadow_maps::*;
+pub use pbr_pipeline_shadow::*;
+pub use pbr_renderpass_shadow::*;
+pub use pbr_point_shadow_maps::*;
+pub use pbr_renderpass_point_shadow::*;
+pub use pbr_spot_shadow_maps::*;
+pub use pbr_renderpass_spot_shadow::*;
+pub use pbr_ssao::*;
+pub use pbr_pipeline_ssao::*;
+pub use pbr_renderpass_ssao::*;
+pub use pbr_ssr::*;
+pub use pbr_pipeline_ssr::*;
+pub use pbr_renderpass_ssr::*;
+pub use pbr_indirect::*;
+pub use pbr_wboit::*;
+pub use pbr_pipeline_wboit::*;
+pub use pbr_renderpass_wboit::*;
 
 use crate::{assets::RenderAssetsPlugin, core::{Extract, Render, RenderApp, RenderSet}};
 
@@ -29,6 +67,7 @@ impl Plugin for PbrFeaturesPlugin {
 
         // Add the pbr defered textures
         app
+            .init_resource::<GBufferQuality>()
             .add_systems(Startup, PbrDeferredTextures::create_textures)
             .add_systems(Update, PbrDeferredTextures::resize_textures);
         app.get_sub_app_mut(RenderApp).unwrap()
@@ -36,12 +75,87 @@ impl Plugin for PbrFeaturesPlugin {
             .add_systems(Extract, PbrDeferredTextures::extract_textures)
             .add_systems(Render, PbrDeferredTexturesLayout::build_bind_group.in_set(RenderSet::BindGroups));
 
+        // Add the pre-integrated BRDF lookup table
+        app
+            .add_systems(Startup, PbrBrdfLut::create_texture);
+        app.get_sub_app_mut(RenderApp).unwrap()
+            .init_resource::<PbrBrdfLutGenerated>()
+            .add_systems(Extract, PbrBrdfLut::extract_texture)
+            .add_systems(Render, PbrBrdfLut::generate.in_set(RenderSet::Process));
+
+        // Add the occlusion culling of the gbuffer batches
+        app.get_sub_app_mut(RenderApp).unwrap()
+            .add_systems(Render, (
+                PbrOcclusionQueries::create.in_set(RenderSet::Prepare),
+                PbrOcclusionQueries::read_results.in_set(RenderSet::Process)
+            ));
+
+        // Add the cascaded shadow map of the directional light
+        app.get_sub_app_mut(RenderApp).unwrap()
+            .init_resource::<PbrShadowMaps>()
+            .add_systems(Extract, PbrShadowMaps::extract_cascades);
+
+        // Add the cubemap shadow maps of the point lights, created once their maximum number is known
+        app
+            .init_resource::<MaxShadowCastingPointLights>();
+        app.get_sub_app_mut(RenderApp).unwrap()
+            .add_systems(Extract, PbrPointShadowMaps::extract_candidates)
+            .add_systems(Render, PbrPointShadowMaps::update_faces.in_set(RenderSet::Process));
+
+        // Add the shadow maps of the spot lights, created once their maximum number is known
+        app
+            .init_resource::<MaxShadowCastingSpotLights>();
+        app.get_sub_app_mut(RenderApp).unwrap()
+            .add_systems(Extract, PbrSpotShadowMaps::extract_candidates)
+            .add_systems(Render, PbrSpotShadowMaps::update_layers.in_set(RenderSet::Process));
+
+        // Add the screen-space ambient occlusion
+        app
+            .init_resource::<SsaoSettings>()
+            .add_systems(Startup, PbrSsaoTextures::create_textures)
+            .add_systems(Update, PbrSsaoTextures::resize_textures);
+        app.get_sub_app_mut(RenderApp).unwrap()
+            .init_resource::<SsaoSettings>()
+            .init_resource::<PbrSsaoLayout>()
+            .add_systems(Extract, PbrSsaoTextures::extract_textures)
+            .add_systems(Render, (
+                PbrSsaoLayout::update_uniform.in_set(RenderSet::Prepare),
+                PbrSsaoLayout::build_bind_groups.in_set(RenderSet::BindGroups)
+            ));
+
+        // Add the screen-space reflections
+        app
+            .init_resource::<SsrSettings>();
+        app.get_sub_app_mut(RenderApp).unwrap()
+            .init_resource::<SsrSettings>()
+            .init_resource::<PbrSsrLayout>()
+            .add_systems(Render, PbrSsrLayout::prepare.in_set(RenderSet::BindGroups));
+
+        // Add the weighted blended order-independent transparency
+        app
+            .add_systems(Startup, PbrWboitTextures::create_textures)
+            .add_systems(Update, PbrWboitTextures::resize_textures);
+        app.get_sub_app_mut(RenderApp).unwrap()
+            .init_resource::<PbrWboitLayout>()
+            .add_systems(Extract, PbrWboitTextures::extract_textures)
+            .add_systems(Render, PbrWboitLayout::build_bind_group.in_set(RenderSet::BindGroups));
+
         // Add the pbr pipelines
         app
             .init_asset::<PbrGBufferRenderPipelineAsset>()
             .add_plugins(RenderAssetsPlugin::<GpuPbrGBufferRenderPipeline>::default())
             .init_asset::<PbrLightingRenderPipelineAsset>()
-            .add_plugins(RenderAssetsPlugin::<GpuPbrLightingRenderPipeline>::default());
+            .add_plugins(RenderAssetsPlugin::<GpuPbrLightingRenderPipeline>::default())
+            .init_asset::<PbrBrdfLutRenderPipelineAsset>()
+            .add_plugins(RenderAssetsPlugin::<GpuPbrBrdfLutRenderPipeline>::default())
+            .init_asset::<PbrShadowMapRenderPipelineAsset>()
+            .add_plugins(RenderAssetsPlugin::<GpuPbrShadowMapRenderPipeline>::default())
+            .init_asset::<PbrSsaoRenderPipelineAsset>()
+            .add_plugins(RenderAssetsPlugin::<GpuPbrSsaoRenderPipeline>::default())
+            .init_asset::<PbrSsrRenderPipelineAsset>()
+            .add_plugins(RenderAssetsPlugin::<GpuPbrSsrRenderPipeline>::default())
+            .init_asset::<PbrWboitRenderPipelineAsset>()
+            .add_plugins(RenderAssetsPlugin::<GpuPbrWboitRenderPipeline>::default());
 
         // Init the render graph
         app
@@ -53,16 +167,32 @@ impl Plugin for PbrFeaturesPlugin {
         // Add the pbr render passes
         let mut render_graph = app.get_sub_app_mut(RenderApp).unwrap()
             .world_mut().get_resource_mut::<RenderGraph>().unwrap();
-        render_graph.add_pass::<PbrGBufferRenderPass>(0);
-        render_graph.add_pass::<PbrLightingRenderPass>(1);
+        render_graph.add_pass::<PbrShadowMapRenderPass>(1);
+        render_graph.add_pass::<PbrPointShadowRenderPass>(2);
+        render_graph.add_pass::<PbrSpotShadowRenderPass>(3);
+        render_graph.add_pass::<PbrGBufferRenderPass>(4);
+        render_graph.add_pass::<PbrSsaoRenderPass>(5);
+        render_graph.add_pass::<PbrLightingRenderPass>(6);
+        render_graph.add_pass::<PbrSsrRenderPass>(7);
+        render_graph.add_pass::<PbrWboitRenderPass>(9);
     }
 
     fn finish(&self, app: &mut App) {
+        // Create the shadow maps of the point and spot lights
+        let max_point_shadows = *app.world().resource::<MaxShadowCastingPointLights>();
+        let max_spot_shadows = *app.world().resource::<MaxShadowCastingSpotLights>();
+        app.get_sub_app_mut(RenderApp).unwrap()
+            .insert_resource(max_point_shadows)
+            .insert_resource(max_spot_shadows)
+            .init_resource::<PbrPointShadowMaps>()
+            .init_resource::<PbrSpotShadowMaps>();
+
         // Create the render pass
         app.get_sub_app_mut(RenderApp).unwrap()
             .insert_resource(PbrGBufferRenderPass {
                 batches_order: HashMap::new(),
-                batches: Vec::new()
+                opaque_batches: Vec::new(),
+                transparent_batches: Vec::new()
             });
 
         // Create the gbuffer pipeline
@@ -74,6 +204,31 @@ impl Plugin for PbrFeaturesPlugin {
         let pipeline = app.world_mut()
             .get_resource::<AssetServer>().unwrap().add(PbrLightingRenderPipelineAsset);
         app.get_sub_app_mut(RenderApp).unwrap().world_mut().spawn(PbrLightingRenderPipeline(pipeline));
+
+        // Create the BRDF lookup table pipeline
+        let pipeline = app.world_mut()
+            .get_resource::<AssetServer>().unwrap().add(PbrBrdfLutRenderPipelineAsset);
+        app.get_sub_app_mut(RenderApp).unwrap().world_mut().spawn(PbrBrdfLutRenderPipeline(pipeline));
+
+        // Create the shadow map pipeline
+        let pipeline = app.world_mut()
+            .get_resource::<AssetServer>().unwrap().add(PbrShadowMapRenderPipelineAsset);
+        app.get_sub_app_mut(RenderApp).unwrap().world_mut().spawn(PbrShadowMapRenderPipeline(pipeline));
+
+        // Create the ambient occlusion pipelines
+        let pipeline = app.world_mut()
+            .get_resource::<AssetServer>().unwrap().add(PbrSsaoRenderPipelineAsset);
+        app.get_sub_app_mut(RenderApp).unwrap().world_mut().spawn(PbrSsaoRenderPipeline(pipeline));
+
+        // Create the screen-space reflections pipelines
+        let pipeline = app.world_mut()
+            .get_resource::<AssetServer>().unwrap().add(PbrSsrRenderPipelineAsset);
+        app.get_sub_app_mut(RenderApp).unwrap().world_mut().spawn(PbrSsrRenderPipeline(pipeline));
+
+        // Create the order-independent transparency pipelines
+        let pipeline = app.world_mut()
+            .get_resource::<AssetServer>().unwrap().add(PbrWboitRenderPipelineAsset);
+        app.get_sub_app_mut(RenderApp).unwrap().world_mut().spawn(PbrWboitRenderPipeline(pipeline));
     }
 }
 
@@ -0,0 +1,239 @@
+use bevy::{ecs::system::lifetimeless::{SRes, SResMut}, prelude::*};
+use wde_wgpu::{command_buffer::{RenderPassBuilder, RenderPassColorAttachment, WCommandBuffer}, instance::WRenderInstance, render_pipeline::WDepthStencilDescriptor, texture::{WTextureFormat, WTextureUsages}};
+use crate::{assets::{GpuTexture, PrepareAssetError, RenderAsset, RenderAssets, Texture}, core::extract_macros::ExtractWorld, pipelines::{CachedPipelineIndex, CachedPipelineStatus, PipelineManager, RenderPipelineDescriptor}};
+
+/// The width and height of the BRDF lookup table.
+pub const BRDF_LUT_SIZE: u32 = 512;
+/// The format of the BRDF lookup table.
+pub const BRDF_LUT_FORMAT: WTextureFormat = WTextureFormat::Rg16Float;
+
+/// The pre-integrated GGX BRDF lookup table used by the lighting pass for the split-sum approximation.
+/// It is bound with the deferred textures.
+/// The u axis is `dot(N, V)` and the v axis is the perceptual roughness, both in [0, 1].
+/// The red channel is the scale applied to F0 and the green channel is the bias, so that the specular
+/// reflectance is `F0 * lut.r + lut.g`. The (1, 0) corner is white, as a smooth surface seen from the front
+/// reflects all the light.
+#[derive(Resource)]
+pub struct PbrBrdfLut {
+    pub texture: Handle<Texture>,
+}
+impl PbrBrdfLut {
+    /// Create the lookup table texture. It is filled on the gpu by the `generate` system.
+    pub fn create_texture(mut commands: Commands, assets_server: Res<AssetServer>) {
+        let texture = assets_server.add(Texture {
+            label: "pbr-brdf-lut".to_string(),
+            size: (BRDF_LUT_SIZE, BRDF_LUT_SIZE),
+            format: BRDF_LUT_FORMAT,
+            usages: WTextureUsages::RENDER_ATTACHMENT | WTextureUsages::TEXTURE_BINDING,
+            ..Default::default()
+        });
+        commands.insert_resource(PbrBrdfLut { texture });
+    }
+
+    /// Extract the lookup table texture.
+    pub fn extract_texture(mut commands: Commands, lut: ExtractWorld<Option<Res<PbrBrdfLut>>>) {
+        if let Some(lut) = lut.as_ref() {
+            commands.insert_resource(PbrBrdfLut { texture: lut.texture.clone() });
+        }
+    }
+
+    /// Integrate the BRDF into the lookup table once its texture and pipeline are ready.
+    pub fn generate(
+        lut: Option<Res<PbrBrdfLut>>, mut generated: ResMut<PbrBrdfLutGenerated>,
+        textures: Res<RenderAssets<GpuTexture>>, pipeline_manager: Res<PipelineManager>,
+        pipelines: Res<RenderAssets<GpuPbrBrdfLutRenderPipeline>>, render_instance: Res<WRenderInstance<'static>>
+    ) {
+        // Check if the lookup table is already generated
+        if generated.0 {
+            return;
+        }
+
+        // Get the texture and the pipeline
+        let texture = match lut.and_then(|lut| textures.get(&lut.texture)) {
+            Some(texture) => texture,
+            None => return
+        };
+        let pipeline = match pipelines.iter().next()
+            .map(|(_, pipeline)| pipeline_manager.get_pipeline(pipeline.cached_pipeline_index)) {
+            Some(CachedPipelineStatus::OkRender(pipeline)) => pipeline,
+            _ => return
+        };
+
+        // Render the lookup table
+        let render_instance = render_instance.data.read().unwrap();
+        let mut command_buffer = WCommandBuffer::new(&render_instance, "pbr-brdf-lut");
+        {
+            let mut render_pass = command_buffer.create_render_pass("pbr-brdf-lut", |builder: &mut RenderPassBuilder| {
+                builder.add_color_attachment(RenderPassColorAttachment {
+                    texture: Some(&texture.texture.view),
+                    ..Default::default()
+                });
+            });
+            if render_pass.set_pipeline(pipeline).is_err() {
+                error!("Failed to set the BRDF lookup table pipeline.");
+                return;
+            }
+            if let Err(e) = render_pass.draw(0..3, 0..1) {
+                error!("Failed to draw the BRDF lookup table: {:?}.", e);
+                return;
+            }
+        }
+        command_buffer.submit(&render_instance);
+
+        debug!("Generated the {}x{} BRDF lookup table.", BRDF_LUT_SIZE, BRDF_LUT_SIZE);
+        generated.0 = true;
+    }
+}
+
+/// Whether the BRDF lookup table was integrated on the gpu.
+#[derive(Resource, Default)]
+pub struct PbrBrdfLutGenerated(pub bool);
+
+#[derive(Default, Asset, Clone, TypePath)]
+pub struct PbrBrdfLutRenderPipelineAsset;
+#[derive(Component)]
+pub struct PbrBrdfLutRenderPipeline(pub Handle<PbrBrdfLutRenderPipelineAsset>);
+pub struct GpuPbrBrdfLutRenderPipeline {
+    pub cached_pipeline_index: CachedPipelineIndex
+}
+impl RenderAsset for GpuPbrBrdfLutRenderPipeline {
+    type SourceAsset = PbrBrdfLutRenderPipelineAsset;
+    type Param = (SRes<AssetServer>, SResMut<PipelineManager>);
+
+    fn prepare_asset(
+            _asset: Self::SourceAsset,
+            (assets_server, pipeline_manager): &mut bevy::ecs::system::SystemParamItem<Self::Param>
+        ) -> Result<Self, PrepareAssetError<Self::SourceAsset>> {
+        // Create the pipeline, drawing a full screen triangle without vertex buffer
+        let pipeline_desc = RenderPipelineDescriptor {
+            label: "pbr-brdf-lut",
+            vert: Some(assets_server.load("pbr/brdf_lut_vert.wgsl")),
+            frag: Some(assets_server.load("pbr/brdf_lut_frag.wgsl")),
+            depth: WDepthStencilDescriptor {
+                enabled: false,
+                ..Default::default()
+            },
+            render_targets: Some(vec![BRDF_LUT_FORMAT]),
+            cull_mode: None,
+            vertex_input: false,
+            ..Default::default()
+        };
+        let cached_index = pipeline_manager.create_render_pipeline(pipeline_desc);
+
+        Ok(GpuPbrBrdfLutRenderPipeline {
+            cached_pipeline_index: cached_index
+        })
+    }
+
+    fn label(&self) -> &str {
+        "pbr-brdf-lut"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use wde_wgpu::{instance::create_headless_instance, render_pipeline::{WRenderPipeline, WShaderStages}, texture::WTexture};
+
+    use super::*;
+
+    /// Integrate the BRDF on the cpu, as the lookup table shader does.
+    fn integrate_brdf(n_dot_v: f32, roughness: f32) -> (f32, f32) {
+        const SAMPLE_COUNT: u32 = 1024;
+        let geometry = |n_dot_x: f32| {
+            let k = roughness * roughness / 2.0;
+            n_dot_x / (n_dot_x * (1.0 - k) + k)
+        };
+
+        let v = Vec3::new((1.0 - n_dot_v * n_dot_v).sqrt(), 0.0, n_dot_v);
+        let (mut scale, mut bias) = (0.0, 0.0);
+        for i in 0..SAMPLE_COUNT {
+            // Sample a halfway vector following the GGX distribution
+            let xi = Vec2::new(i as f32 / SAMPLE_COUNT as f32, i.reverse_bits() as f32 * 2.328_306_4e-10);
+            let a = roughness * roughness;
+            let phi = 2.0 * std::f32::consts::PI * xi.x;
+            let cos_theta = ((1.0 - xi.y) / (1.0 + (a * a - 1.0) * xi.y)).sqrt();
+            let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+            let h = Vec3::new(phi.cos() * sin_theta, phi.sin() * sin_theta, cos_theta);
+            let l = (2.0 * v.dot(h) * h - v).normalize();
+
+            let n_dot_l = l.z.max(0.0);
+            let n_dot_h = h.z.max(0.0);
+            let v_dot_h = v.dot(h).max(0.0);
+            if n_dot_l > 0.0 {
+                let g_vis = geometry(n_dot_v) * geometry(n_dot_l) * v_dot_h / (n_dot_h * n_dot_v);
+                let fresnel = (1.0 - v_dot_h).powi(5);
+                scale += (1.0 - fresnel) * g_vis;
+                bias += fresnel * g_vis;
+            }
+        }
+        (scale / SAMPLE_COUNT as f32, bias / SAMPLE_COUNT as f32)
+    }
+
+    /// Convert a half float to a float.
+    fn f16_to_f32(value: u16) -> f32 {
+        let sign = if value >> 15 == 1 { -1.0 } else { 1.0 };
+        let exponent = ((value >> 10) & 0x1f) as i32;
+        let mantissa = (value & 0x3ff) as f32 / 1024.0;
+        match exponent {
+            0 => sign * mantissa * 2f32.powi(-14),
+            _ => sign * (1.0 + mantissa) * 2f32.powi(exponent - 15),
+        }
+    }
+
+    #[test]
+    fn lut_matches_the_integrated_brdf() {
+        let Some(instance) = bevy::tasks::block_on(create_headless_instance("brdf-lut-test")) else {
+            eprintln!("No gpu adapter available, skipping the BRDF lookup table test.");
+            return;
+        };
+        let instance = instance.data.read().unwrap();
+
+        // Render the lookup table shader, only at the tested texels
+        let format = BRDF_LUT_FORMAT;
+        let texture = WTexture::new(&instance, "brdf-lut-test", (BRDF_LUT_SIZE, BRDF_LUT_SIZE), format,
+            WTextureUsages::RENDER_ATTACHMENT | WTextureUsages::COPY_SRC, Some(1));
+        let mut pipeline = WRenderPipeline::new("brdf-lut-test");
+        pipeline
+            .set_shader(include_str!("../../../../../res/pbr/brdf_lut_vert.wgsl"), WShaderStages::VERTEX)
+            .set_shader(include_str!("../../../../../res/pbr/brdf_lut_frag.wgsl"), WShaderStages::FRAGMENT)
+            .set_render_targets(vec![format])
+            .set_cull_mode(None)
+            .set_vertex_input(false);
+        pipeline.init(&instance).unwrap();
+
+        // Texels of (dot(N, V), roughness) at their center
+        let texels = [(511, 0), (255, 255), (127, 383), (383, 127), (40, 500), (500, 500)];
+        let mut command_buffer = WCommandBuffer::new(&instance, "brdf-lut-test");
+        {
+            let mut render_pass = command_buffer.create_render_pass("brdf-lut-test", |builder: &mut RenderPassBuilder| {
+                builder.add_color_attachment(RenderPassColorAttachment {
+                    texture: Some(&texture.view),
+                    ..Default::default()
+                });
+            });
+            render_pass.set_pipeline(&pipeline).unwrap();
+            for &(x, y) in texels.iter() {
+                render_pass.set_scissor_rect(x, y, 1, 1);
+                render_pass.draw(0..3, 0..1).unwrap();
+            }
+        }
+        command_buffer.submit(&instance);
+        let pixels: Vec<f32> = texture.read_pixels(&instance).unwrap().chunks_exact(2)
+            .map(|value| f16_to_f32(u16::from_le_bytes([value[0], value[1]]))).collect();
+
+        for &(x, y) in texels.iter() {
+            let index = (y * BRDF_LUT_SIZE + x) as usize * 2;
+            let lut = (pixels[index], pixels[index + 1]);
+            let n_dot_v = (x as f32 + 0.5) / BRDF_LUT_SIZE as f32;
+            let roughness = (y as f32 + 0.5) / BRDF_LUT_SIZE as f32;
+            let expected = integrate_brdf(n_dot_v, roughness);
+            assert!((lut.0 - expected.0).abs() < 0.01 && (lut.1 - expected.1).abs() < 0.01,
+                "LUT at ({}, {}) is {:?}, expected {:?}", n_dot_v, roughness, lut, expected);
+            assert!(lut.0 + lut.1 <= 1.01, "LUT at ({}, {}) reflects more than the incoming light", n_dot_v, roughness);
+        }
+
+        // A smooth surface seen from the front reflects F0
+        let front = (pixels[511 * 2], pixels[511 * 2 + 1]);
+        assert!((front.0 - 1.0).abs() < 0.02 && front.1.abs() < 0.02, "LUT of a smooth surface seen from the front is {:?}", front);
+    }
+}
@@ -0,0 +1,76 @@
+use bevy::prelude::*;
+use wde_wgpu::{buffer::{BufferUsage, WBuffer}, instance::WRenderInstance};
+
+use crate::{core::{Render, RenderApp, RenderSet}, features::CameraFrustum};
+
+use super::{PbrGBufferRenderPass, PbrOcclusionQueries};
+
+/// The arguments of an indexed indirect draw, as read by the GPU.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct PbrDrawIndexedIndirect {
+    pub index_count: u32,
+    pub instance_count: u32,
+    pub first_index: u32,
+    pub base_vertex: i32,
+    pub first_instance: u32
+}
+
+/// The indirect draw commands of the opaque gbuffer batches, one per batch in the order of `PbrGBufferRenderPass::opaque_batches`.
+/// The culled batches are written with no instance, so that the consecutive batches of a mesh can be drawn
+/// with a single `multi_draw_indexed_indirect`. The instances read their transform from the pbr ssbo,
+/// in which the entities of a batch are contiguous, using their instance index.
+#[derive(Resource, Default)]
+pub struct PbrIndirectDraws {
+    /// The buffer of the draw commands, recreated when the batches do not fit.
+    pub buffer: Option<WBuffer>,
+    /// The number of draw commands the buffer can hold.
+    capacity: usize
+}
+impl PbrIndirectDraws {
+    /// Write the draw commands of the opaque batches, once the frustum and the occlusion culling are known.
+    pub fn prepare(
+        mut indirect_draws: ResMut<PbrIndirectDraws>, gbuffer_pass: Option<Res<PbrGBufferRenderPass>>,
+        frustum: Res<CameraFrustum>, occlusion: Option<Res<PbrOcclusionQueries>>,
+        render_instance: Res<WRenderInstance<'static>>
+    ) {
+        let gbuffer_pass = match gbuffer_pass {
+            Some(gbuffer_pass) if !gbuffer_pass.opaque_batches.is_empty() => gbuffer_pass,
+            _ => return
+        };
+
+        // Create the draw commands, without instances for the culled batches
+        let commands: Vec<PbrDrawIndexedIndirect> = gbuffer_pass.opaque_batches.iter().map(|batch| {
+            let visible = frustum.intersects_aabb(batch.bounding_box.min, batch.bounding_box.max)
+                && !occlusion.as_ref().is_some_and(|occlusion| occlusion.is_culled(&(batch.mesh.id(), batch.material.id())));
+            PbrDrawIndexedIndirect {
+                index_count: batch.index_count as u32,
+                instance_count: if visible { batch.count as u32 } else { 0 },
+                first_index: 0,
+                base_vertex: 0,
+                first_instance: batch.first as u32
+            }
+        }).collect();
+
+        // Grow the buffer if needed
+        let render_instance = render_instance.data.read().unwrap();
+        if indirect_draws.buffer.is_none() || indirect_draws.capacity < commands.len() {
+            let capacity = commands.len().next_power_of_two();
+            indirect_draws.buffer = Some(WBuffer::new(&render_instance, "pbr-indirect-draws",
+                capacity * std::mem::size_of::<PbrDrawIndexedIndirect>(), BufferUsage::INDIRECT | BufferUsage::COPY_DST, None));
+            indirect_draws.capacity = capacity;
+        }
+        indirect_draws.buffer.as_mut().unwrap().write(&render_instance, bytemuck::cast_slice(&commands), 0);
+    }
+}
+
+/// Draws the opaque gbuffer batches from indirect draw commands written each frame, merging the consecutive
+/// batches sharing a mesh into a single draw call. Without this plugin, each batch is drawn with its own draw call.
+pub struct GpuDrivenPlugin;
+impl Plugin for GpuDrivenPlugin {
+    fn build(&self, app: &mut App) {
+        app.get_sub_app_mut(RenderApp).unwrap()
+            .init_resource::<PbrIndirectDraws>()
+            .add_systems(Render, PbrIndirectDraws::prepare.in_set(RenderSet::Process).after(PbrOcclusionQueries::read_results));
+    }
+}
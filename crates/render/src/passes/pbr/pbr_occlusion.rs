@@ -0,0 +1,128 @@
+use std::{collections::HashSet, sync::Mutex};
+
+use bevy::prelude::*;
+use wde_wgpu::{buffer::{BufferUsage, WBuffer, WBufferMapStatus, WPendingBufferRead}, command_buffer::WCommandBuffer, instance::WRenderInstance, query_set::WOcclusionQuerySet};
+
+use crate::assets::{materials::PbrMaterialAsset, MeshAsset};
+
+/// The maximum number of opaque batches tested for occlusion in a frame. The remaining batches are always drawn.
+pub const MAX_OCCLUSION_QUERIES: u32 = 4096;
+/// The number of query cycles between two tests of the batches found occluded, a cycle lasting from the queries
+/// of a frame to the read of their results. An occluded batch becoming visible can thus appear up to this number
+/// of cycles late.
+pub const OCCLUSION_RETEST_INTERVAL: u32 = 4;
+
+/// Identifies an opaque batch of the gbuffer pass across frames.
+pub type PbrBatchKey = (AssetId<MeshAsset>, AssetId<PbrMaterialAsset>);
+
+/// The hardware occlusion culling of the opaque batches of the gbuffer pass.
+/// The draw of each batch is wrapped in an occlusion query, whose result is read back without blocking one or
+/// more frames later. The batches with no sample passing the depth test are then skipped, and drawn again every
+/// `OCCLUSION_RETEST_INTERVAL` query cycles to test whether they became visible.
+#[derive(Resource)]
+pub struct PbrOcclusionQueries {
+    /// The occlusion queries of the gbuffer pass, one per drawn batch.
+    pub query_set: WOcclusionQuerySet,
+    /// The buffer the queries are resolved into.
+    resolve_buffer: WBuffer,
+    /// The buffer the results are copied to, to be read on the cpu.
+    readback_buffer: WBuffer,
+    /// The read of the results of the last queried frame, with the batches in query order.
+    pending: Mutex<Option<(WPendingBufferRead, Vec<PbrBatchKey>)>>,
+    /// The batches found occluded by their last query.
+    occluded: HashSet<PbrBatchKey>,
+    /// The number of query cycles whose results were read, to retest the occluded batches.
+    cycle: u32,
+}
+impl PbrOcclusionQueries {
+    /// Create the query set and the buffers once the render instance is ready.
+    pub fn create(mut commands: Commands, queries: Option<Res<PbrOcclusionQueries>>, render_instance: Res<WRenderInstance<'static>>) {
+        if queries.is_some() {
+            return;
+        }
+
+        let render_instance = render_instance.data.read().unwrap();
+        let query_set = WOcclusionQuerySet::builder("gbuffer-pbr")
+            .count(MAX_OCCLUSION_QUERIES)
+            .build(&render_instance);
+        let resolve_buffer = WBuffer::new(&render_instance, "gbuffer-pbr-occlusion-resolve",
+            query_set.resolve_size() as usize, BufferUsage::QUERY_RESOLVE | BufferUsage::COPY_SRC, None);
+        let readback_buffer = WBuffer::new(&render_instance, "gbuffer-pbr-occlusion-readback",
+            query_set.resolve_size() as usize, BufferUsage::MAP_READ | BufferUsage::COPY_DST, None);
+
+        commands.insert_resource(PbrOcclusionQueries {
+            query_set,
+            resolve_buffer,
+            readback_buffer,
+            pending: Mutex::new(None),
+            occluded: HashSet::new(),
+            cycle: 0,
+        });
+    }
+
+    /// Poll the read of the results of the last queried frame, and update the occluded batches once read.
+    pub fn read_results(queries: Option<ResMut<PbrOcclusionQueries>>, render_instance: Res<WRenderInstance<'static>>) {
+        let mut queries = match queries {
+            Some(queries) => queries,
+            None => return
+        };
+        let queries = &mut *queries;
+
+        let pending = queries.pending.get_mut().unwrap();
+        let (read, keys) = match pending.as_ref() {
+            Some(pending) => pending,
+            None => return
+        };
+        let occluded = &mut queries.occluded;
+        let status = queries.readback_buffer.poll_read_async(&render_instance.data.read().unwrap(), read, |view| {
+            let samples: &[u64] = bytemuck::cast_slice(&view);
+            for (key, samples) in keys.iter().zip(samples) {
+                if *samples == 0 {
+                    occluded.insert(*key);
+                } else {
+                    occluded.remove(key);
+                }
+            }
+        });
+        if status != WBufferMapStatus::Pending {
+            *pending = None;
+            queries.cycle = queries.cycle.wrapping_add(1);
+        }
+    }
+
+    /// Whether the batches can be queried this frame, i.e. the results of the last queried frame were read.
+    pub fn can_query(&self) -> bool {
+        self.pending.lock().unwrap().is_none()
+    }
+
+    /// Whether the batch was found occluded and is not retested this frame.
+    /// The occluded batches are drawn and queried again every `OCCLUSION_RETEST_INTERVAL` query cycles, on the first frame
+    /// the batches can be queried.
+    pub fn is_culled(&self, key: &PbrBatchKey) -> bool {
+        let retest = self.cycle.is_multiple_of(OCCLUSION_RETEST_INTERVAL) && self.can_query();
+        !retest && self.occluded.contains(key)
+    }
+
+    /// Resolve the queries of the drawn batches and copy them to the readback buffer.
+    /// The command buffer must be submitted before calling `start_read`.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `command_buffer` - The command buffer of the gbuffer pass, once the render pass is ended.
+    /// * `count` - The number of queried batches.
+    pub fn resolve(&self, command_buffer: &mut WCommandBuffer, count: u32) {
+        command_buffer.resolve_occlusion_query_range(&self.query_set, 0..count, &self.resolve_buffer);
+        command_buffer.copy_buffer_range(&self.resolve_buffer, 0, &self.readback_buffer, 0,
+            count as u64 * std::mem::size_of::<u64>() as u64);
+    }
+
+    /// Start reading the results of the queried batches without blocking.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `keys` - The queried batches, in query order.
+    pub fn start_read(&self, keys: Vec<PbrBatchKey>) {
+        let read = self.readback_buffer.map_read_async(keys.len() * std::mem::size_of::<u64>());
+        *self.pending.lock().unwrap() = Some((read, keys));
+    }
+}
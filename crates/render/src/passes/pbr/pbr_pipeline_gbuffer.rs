@@ -5,12 +5,43 @@ use crate::{assets::{materials::PbrMaterialAsset, GpuMaterial, GpuTexture, Prepa
 use super::{PbrDeferredTextures, PbrSsbo};
 
 
+/// The variants of the gbuffer pipeline, selected per material.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PbrGBufferPipelineVariant {
+    /// The back faces are culled.
+    SingleSidedPipeline,
+    /// Both faces are rendered, the normal being flipped on the back faces.
+    DoubleSidedPipeline
+}
+impl PbrGBufferPipelineVariant {
+    /// Get the variant to use for a material.
+    pub fn from_double_sided(double_sided: bool) -> Self {
+        if double_sided {
+            PbrGBufferPipelineVariant::DoubleSidedPipeline
+        } else {
+            PbrGBufferPipelineVariant::SingleSidedPipeline
+        }
+    }
+}
+
 #[derive(Default, Asset, Clone, TypePath)]
 pub struct PbrGBufferRenderPipelineAsset;
 #[derive(Component)]
 pub struct PbrGBufferRenderPipeline(pub Handle<PbrGBufferRenderPipelineAsset>);
 pub struct GpuPbrGBufferRenderPipeline {
-    pub cached_pipeline_index: CachedPipelineIndex
+    /// The single sided pipeline, culling the back faces.
+    pub cached_pipeline_index: CachedPipelineIndex,
+    /// The double sided pipeline, without culling.
+    pub double_sided_cached_pipeline_index: CachedPipelineIndex
+}
+impl GpuPbrGBufferRenderPipeline {
+    /// Get the cached pipeline index of a pipeline variant.
+    pub fn variant(&self, variant: PbrGBufferPipelineVariant) -> CachedPipelineIndex {
+        match variant {
+            PbrGBufferPipelineVariant::SingleSidedPipeline => self.cached_pipeline_index,
+            PbrGBufferPipelineVariant::DoubleSidedPipeline => self.double_sided_cached_pipeline_index
+        }
+    }
 }
 impl RenderAsset for GpuPbrGBufferRenderPipeline {
     type SourceAsset = PbrGBufferRenderPipelineAsset;
@@ -29,12 +60,13 @@ impl RenderAsset for GpuPbrGBufferRenderPipeline {
             ): &mut bevy::ecs::system::SystemParamItem<Self::Param>
         ) -> Result<Self, PrepareAssetError<Self::SourceAsset>> {
         // Get the defered textures
-        let (albedo, normal, material_tex) =
+        let (albedo, normal, material_tex, emissive) =
             match (textures.get(&defered_textures.albedo),
-                   textures.get(&defered_textures.normal), textures.get(&defered_textures.material)
+                   textures.get(&defered_textures.normal), textures.get(&defered_textures.material),
+                   textures.get(&defered_textures.emissive)
             ) {
-                (Some(albedo), Some(normal), Some(material_tex))
-                    => (albedo, normal, material_tex),
+                (Some(albedo), Some(normal), Some(material_tex), Some(emissive))
+                    => (albedo, normal, material_tex, emissive),
                 _ => return Err(PrepareAssetError::RetryNextUpdate(asset))
             };
 
@@ -61,14 +93,23 @@ impl RenderAsset for GpuPbrGBufferRenderPipeline {
                 ..Default::default()
             },
             render_targets: Some(vec![
-                albedo.texture.format, normal.texture.format, material_tex.texture.format
+                albedo.texture.format, normal.texture.format, material_tex.texture.format, emissive.texture.format
             ]),
             ..Default::default()
         };
-        let cached_index = pipeline_manager.create_render_pipeline(pipeline_desc);
+        let cached_index = pipeline_manager.create_render_pipeline(pipeline_desc.clone());
+
+        // Create the double sided pipeline
+        let double_sided_desc = RenderPipelineDescriptor {
+            label: "gbuffer-pbr-double-sided",
+            cull_mode: None,
+            ..pipeline_desc
+        };
+        let double_sided_cached_index = pipeline_manager.create_render_pipeline(double_sided_desc);
 
         Ok(GpuPbrGBufferRenderPipeline {
-            cached_pipeline_index: cached_index
+            cached_pipeline_index: cached_index,
+            double_sided_cached_pipeline_index: double_sided_cached_index
         })
     }
 
@@ -1,6 +1,6 @@
 use bevy::{ecs::system::lifetimeless::{SRes, SResMut}, prelude::*};
 use wde_wgpu::render_pipeline::WDepthStencilDescriptor;
-use crate::{assets::{PrepareAssetError, RenderAsset}, features::{CameraFeatureRender, LightsFeatureBuffer}, pipelines::{CachedPipelineIndex, PipelineManager, RenderPipelineDescriptor}, passes::depth::DepthTextureLayout};
+use crate::{assets::{PrepareAssetError, RenderAsset}, features::{CameraFeatureRender, LightsFeatureBuffer}, pipelines::{CachedPipelineIndex, PipelineManager, RenderPipelineDescriptor}, passes::{depth::DepthTextureLayout, hdr::HDR_FORMAT}};
 
 use super::PbrDeferredTexturesLayout;
 
@@ -55,7 +55,7 @@ impl RenderAsset for GpuPbrLightingRenderPipeline {
                 enabled: false,
                 ..Default::default()
             },
-            render_targets: None,
+            render_targets: Some(vec![HDR_FORMAT]),
             ..Default::default()
         };
         let cached_index = pipeline_manager.create_render_pipeline(pipeline_desc);
@@ -0,0 +1,60 @@
+use bevy::{ecs::system::lifetimeless::{SRes, SResMut}, prelude::*};
+use wde_wgpu::render_pipeline::{WDepthBiasState, WDepthStencilDescriptor};
+use crate::{assets::{PrepareAssetError, RenderAsset}, pipelines::{CachedPipelineIndex, PipelineManager, RenderPipelineDescriptor}};
+
+use super::{PbrShadowMaps, PbrSsbo, SHADOW_MAP_FORMAT};
+
+
+#[derive(Default, Asset, Clone, TypePath)]
+pub struct PbrShadowMapRenderPipelineAsset;
+#[derive(Component)]
+pub struct PbrShadowMapRenderPipeline(pub Handle<PbrShadowMapRenderPipelineAsset>);
+pub struct GpuPbrShadowMapRenderPipeline {
+    pub cached_pipeline_index: CachedPipelineIndex
+}
+impl RenderAsset for GpuPbrShadowMapRenderPipeline {
+    type SourceAsset = PbrShadowMapRenderPipelineAsset;
+    type Param = (SRes<AssetServer>, SResMut<PipelineManager>, SRes<PbrShadowMaps>, SRes<PbrSsbo>);
+
+    fn prepare_asset(
+            asset: Self::SourceAsset,
+            (assets_server, pipeline_manager, shadow_maps, ssbo): &mut bevy::ecs::system::SystemParamItem<Self::Param>
+        ) -> Result<Self, PrepareAssetError<Self::SourceAsset>> {
+        // Get the ssbo layout
+        let ssbo_layout = match &ssbo.bind_group_layout {
+            Some(layout) => layout,
+            None => return Err(PrepareAssetError::RetryNextUpdate(asset))
+        };
+
+        // Create the pipeline, rendering both faces so that the single sided meshes cast shadows from their back
+        let pipeline_desc = RenderPipelineDescriptor {
+            label: "pbr-shadow-map",
+            vert: Some(assets_server.load("pbr/shadow_map_vert.wgsl")),
+            frag: Some(assets_server.load("pbr/shadow_map_frag.wgsl")),
+            bind_group_layouts: vec![shadow_maps.cascade_layout.clone(), ssbo_layout.clone()],
+            depth: WDepthStencilDescriptor {
+                enabled: true,
+                format: SHADOW_MAP_FORMAT,
+                bias: WDepthBiasState {
+                    constant: 2,
+                    slope_scale: 1.5,
+                    clamp: 0.0
+                },
+                ..Default::default()
+            },
+            use_depth_texture_format: false,
+            render_targets: Some(vec![]),
+            cull_mode: None,
+            ..Default::default()
+        };
+        let cached_index = pipeline_manager.create_render_pipeline(pipeline_desc);
+
+        Ok(GpuPbrShadowMapRenderPipeline {
+            cached_pipeline_index: cached_index
+        })
+    }
+
+    fn label(&self) -> &str {
+        "pbr-shadow-map"
+    }
+}
@@ -0,0 +1,78 @@
+use bevy::{ecs::system::lifetimeless::{SRes, SResMut}, prelude::*};
+use wde_wgpu::render_pipeline::WDepthStencilDescriptor;
+use crate::{assets::{PrepareAssetError, RenderAsset}, features::CameraFeatureRender, pipelines::{CachedPipelineIndex, PipelineManager, RenderPipelineDescriptor}, passes::depth::DepthTextureLayout};
+
+use super::{PbrSsaoLayout, SSAO_FORMAT};
+
+#[derive(Default, Asset, Clone, TypePath)]
+pub struct PbrSsaoRenderPipelineAsset;
+#[derive(Component)]
+pub struct PbrSsaoRenderPipeline(pub Handle<PbrSsaoRenderPipelineAsset>);
+pub struct GpuPbrSsaoRenderPipeline {
+    /// The pipeline computing the occlusion of each pixel.
+    pub cached_pipeline_index: CachedPipelineIndex,
+    /// The pipeline blurring the occlusion with a bilateral filter.
+    pub cached_blur_pipeline_index: CachedPipelineIndex
+}
+impl RenderAsset for GpuPbrSsaoRenderPipeline {
+    type SourceAsset = PbrSsaoRenderPipelineAsset;
+    type Param = (
+        SRes<AssetServer>, SResMut<PipelineManager>, SRes<PbrSsaoLayout>,
+        SRes<DepthTextureLayout>, SRes<CameraFeatureRender>
+    );
+
+    fn prepare_asset(
+            asset: Self::SourceAsset,
+            (
+                assets_server, pipeline_manager,
+                ssao_layout, depth_texture_layout, camera_feature
+            ): &mut bevy::ecs::system::SystemParamItem<Self::Param>
+        ) -> Result<Self, PrepareAssetError<Self::SourceAsset>> {
+        // Get the depth layout
+        let depth_layout = match &depth_texture_layout.layout {
+            Some(layout) => layout,
+            None => return Err(PrepareAssetError::RetryNextUpdate(asset))
+        };
+
+        // Create the pipelines, drawing a full screen triangle without vertex buffer
+        let pipeline_desc = RenderPipelineDescriptor {
+            label: "pbr-ssao",
+            vert: Some(assets_server.load("pbr/ssao_vert.wgsl")),
+            frag: Some(assets_server.load("pbr/ssao_frag.wgsl")),
+            bind_group_layouts: vec![camera_feature.layout.clone(), depth_layout.clone(), ssao_layout.ssao_layout.clone()],
+            depth: WDepthStencilDescriptor {
+                enabled: false,
+                ..Default::default()
+            },
+            render_targets: Some(vec![SSAO_FORMAT]),
+            cull_mode: None,
+            vertex_input: false,
+            ..Default::default()
+        };
+        let cached_index = pipeline_manager.create_render_pipeline(pipeline_desc);
+        let blur_pipeline_desc = RenderPipelineDescriptor {
+            label: "pbr-ssao-blur",
+            vert: Some(assets_server.load("pbr/ssao_vert.wgsl")),
+            frag: Some(assets_server.load("pbr/ssao_blur_frag.wgsl")),
+            bind_group_layouts: vec![camera_feature.layout.clone(), depth_layout.clone(), ssao_layout.blur_layout.clone()],
+            depth: WDepthStencilDescriptor {
+                enabled: false,
+                ..Default::default()
+            },
+            render_targets: Some(vec![SSAO_FORMAT]),
+            cull_mode: None,
+            vertex_input: false,
+            ..Default::default()
+        };
+        let cached_blur_index = pipeline_manager.create_render_pipeline(blur_pipeline_desc);
+
+        Ok(GpuPbrSsaoRenderPipeline {
+            cached_pipeline_index: cached_index,
+            cached_blur_pipeline_index: cached_blur_index
+        })
+    }
+
+    fn label(&self) -> &str {
+        "pbr-ssao"
+    }
+}
@@ -0,0 +1,68 @@
+use bevy::{ecs::system::lifetimeless::{SRes, SResMut}, prelude::*};
+use wde_wgpu::render_pipeline::{WBlendComponent, WBlendFactor, WBlendOperation, WBlendState, WDepthStencilDescriptor};
+use crate::{assets::{PrepareAssetError, RenderAsset}, passes::hdr::HDR_FORMAT, pipelines::{CachedPipelineIndex, ComputePipelineDescriptor, PipelineManager, RenderPipelineDescriptor}};
+
+use super::PbrSsrLayout;
+
+#[derive(Default, Asset, Clone, TypePath)]
+pub struct PbrSsrRenderPipelineAsset;
+#[derive(Component)]
+pub struct PbrSsrRenderPipeline(pub Handle<PbrSsrRenderPipelineAsset>);
+pub struct GpuPbrSsrRenderPipeline {
+    /// The compute pipeline tracing the reflected ray of each pixel.
+    pub cached_trace_index: CachedPipelineIndex,
+    /// The render pipeline adding the reflections to the hdr color.
+    pub cached_composite_index: CachedPipelineIndex
+}
+impl RenderAsset for GpuPbrSsrRenderPipeline {
+    type SourceAsset = PbrSsrRenderPipelineAsset;
+    type Param = (SRes<AssetServer>, SResMut<PipelineManager>, SRes<PbrSsrLayout>);
+
+    fn prepare_asset(
+            _asset: Self::SourceAsset,
+            (assets_server, pipeline_manager, ssr_layout): &mut bevy::ecs::system::SystemParamItem<Self::Param>
+        ) -> Result<Self, PrepareAssetError<Self::SourceAsset>> {
+        // Create the ray tracing pipeline
+        let cached_trace_index = pipeline_manager.create_compute_pipeline(ComputePipelineDescriptor {
+            label: "pbr-ssr-trace",
+            comp: Some(assets_server.load("pbr/ssr_comp.wgsl")),
+            bind_group_layouts: vec![ssr_layout.trace_layout.clone()],
+            ..Default::default()
+        });
+
+        // Create the composite pipeline, drawing a full screen triangle without vertex buffer
+        // The reflections are already weighted by the fresnel, so they are added to the specular light
+        let additive = WBlendComponent {
+            src_factor: WBlendFactor::One,
+            dst_factor: WBlendFactor::One,
+            operation: WBlendOperation::Add
+        };
+        let cached_composite_index = pipeline_manager.create_render_pipeline(RenderPipelineDescriptor {
+            label: "pbr-ssr-composite",
+            vert: Some(assets_server.load("pbr/ssr_composite_vert.wgsl")),
+            frag: Some(assets_server.load("pbr/ssr_composite_frag.wgsl")),
+            bind_group_layouts: vec![ssr_layout.composite_layout.clone()],
+            depth: WDepthStencilDescriptor {
+                enabled: false,
+                ..Default::default()
+            },
+            render_targets: Some(vec![HDR_FORMAT]),
+            blend: WBlendState {
+                color: additive,
+                alpha: additive
+            },
+            cull_mode: None,
+            vertex_input: false,
+            ..Default::default()
+        });
+
+        Ok(GpuPbrSsrRenderPipeline {
+            cached_trace_index,
+            cached_composite_index
+        })
+    }
+
+    fn label(&self) -> &str {
+        "pbr-ssr"
+    }
+}
@@ -0,0 +1,117 @@
+use bevy::{ecs::system::lifetimeless::{SRes, SResMut}, prelude::*};
+use wde_wgpu::render_pipeline::{WBlendComponent, WBlendFactor, WBlendOperation, WBlendState, WDepthStencilDescriptor};
+use crate::{assets::{materials::PbrMaterialAsset, GpuMaterial, PrepareAssetError, RenderAsset, RenderAssets}, features::{CameraFeatureRender, LightsFeatureBuffer}, pipelines::{CachedPipelineIndex, PipelineManager, RenderPipelineDescriptor}, passes::hdr::HDR_FORMAT};
+
+use super::{PbrGBufferPipelineVariant, PbrSsbo, PbrWboitLayout, WBOIT_ACCUM_FORMAT, WBOIT_REVEALAGE_FORMAT};
+
+#[derive(Default, Asset, Clone, TypePath)]
+pub struct PbrWboitRenderPipelineAsset;
+#[derive(Component)]
+pub struct PbrWboitRenderPipeline(pub Handle<PbrWboitRenderPipelineAsset>);
+pub struct GpuPbrWboitRenderPipeline {
+    /// The pipeline accumulating the single sided transparent meshes, culling the back faces.
+    pub cached_accum_pipeline_index: CachedPipelineIndex,
+    /// The pipeline accumulating the double sided transparent meshes, without culling.
+    pub double_sided_cached_accum_pipeline_index: CachedPipelineIndex,
+    /// The pipeline resolving the accumulated colors over the hdr color.
+    pub cached_resolve_pipeline_index: CachedPipelineIndex
+}
+impl GpuPbrWboitRenderPipeline {
+    /// Get the cached accumulation pipeline index of a pipeline variant.
+    pub fn accum_variant(&self, variant: PbrGBufferPipelineVariant) -> CachedPipelineIndex {
+        match variant {
+            PbrGBufferPipelineVariant::SingleSidedPipeline => self.cached_accum_pipeline_index,
+            PbrGBufferPipelineVariant::DoubleSidedPipeline => self.double_sided_cached_accum_pipeline_index
+        }
+    }
+}
+impl RenderAsset for GpuPbrWboitRenderPipeline {
+    type SourceAsset = PbrWboitRenderPipelineAsset;
+    type Param = (
+        SRes<AssetServer>, SResMut<PipelineManager>,
+        SRes<CameraFeatureRender>, SRes<RenderAssets<GpuMaterial<PbrMaterialAsset>>>, SRes<PbrSsbo>,
+        SRes<LightsFeatureBuffer>, SRes<PbrWboitLayout>
+    );
+
+    fn prepare_asset(
+            asset: Self::SourceAsset,
+            (
+                assets_server, pipeline_manager,
+                camera_feature, materials, ssbo,
+                lights_buffer, wboit_layout
+            ): &mut bevy::ecs::system::SystemParamItem<Self::Param>
+        ) -> Result<Self, PrepareAssetError<Self::SourceAsset>> {
+        // Get the ssbo, material and lights layouts
+        let (ssbo_layout, material, lights_layout) = match (
+            &ssbo.bind_group_layout, materials.iter().next(), &lights_buffer.bind_group_layout
+        ) {
+            (Some(ssbo_layout), Some((_, material)), Some(lights_layout)) => (ssbo_layout, material, lights_layout),
+            _ => return Err(PrepareAssetError::RetryNextUpdate(asset))
+        };
+
+        // Create the accumulation pipelines, testing the depth of the opaque meshes without writing it
+        // The colors are summed in the accumulation texture, and the transparencies multiplied in the revealage
+        let additive = WBlendComponent {
+            src_factor: WBlendFactor::One,
+            dst_factor: WBlendFactor::One,
+            operation: WBlendOperation::Add
+        };
+        let revealage = WBlendComponent {
+            src_factor: WBlendFactor::Zero,
+            dst_factor: WBlendFactor::OneMinusSrc,
+            operation: WBlendOperation::Add
+        };
+        let accum_desc = RenderPipelineDescriptor {
+            label: "pbr-wboit-accum",
+            vert: Some(assets_server.load("pbr/wboit_vert.wgsl")),
+            frag: Some(assets_server.load("pbr/wboit_frag.wgsl")),
+            bind_group_layouts: vec![
+                camera_feature.layout.clone(), ssbo_layout.clone(), material.bind_group_layout.clone(), lights_layout.clone()
+            ],
+            depth: WDepthStencilDescriptor {
+                enabled: true,
+                write: false,
+                ..Default::default()
+            },
+            render_targets: Some(vec![WBOIT_ACCUM_FORMAT, WBOIT_REVEALAGE_FORMAT]),
+            target_blends: vec![
+                WBlendState { color: additive, alpha: additive },
+                WBlendState { color: revealage, alpha: revealage }
+            ],
+            ..Default::default()
+        };
+        let cached_accum_index = pipeline_manager.create_render_pipeline(accum_desc.clone());
+        let double_sided_cached_accum_index = pipeline_manager.create_render_pipeline(RenderPipelineDescriptor {
+            label: "pbr-wboit-accum-double-sided",
+            cull_mode: None,
+            ..accum_desc
+        });
+
+        // Create the resolve pipeline, drawing a full screen triangle without vertex buffer
+        let cached_resolve_index = pipeline_manager.create_render_pipeline(RenderPipelineDescriptor {
+            label: "pbr-wboit-resolve",
+            vert: Some(assets_server.load("pbr/wboit_resolve_vert.wgsl")),
+            frag: Some(assets_server.load("pbr/wboit_resolve_frag.wgsl")),
+            bind_group_layouts: vec![wboit_layout.resolve_layout.clone()],
+            depth: WDepthStencilDescriptor {
+                enabled: false,
+                ..Default::default()
+            },
+            render_targets: Some(vec![HDR_FORMAT]),
+            blend: WBlendState::ALPHA_BLENDING,
+            cull_mode: None,
+            vertex_input: false,
+            ..Default::default()
+        });
+
+        Ok(GpuPbrWboitRenderPipeline {
+            cached_accum_pipeline_index: cached_accum_index,
+            double_sided_cached_accum_pipeline_index: double_sided_cached_accum_index,
+            cached_resolve_pipeline_index: cached_resolve_index
+        })
+    }
+
+    fn label(&self) -> &str {
+        "pbr-wboit"
+    }
+}
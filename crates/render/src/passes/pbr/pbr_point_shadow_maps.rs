@@ -0,0 +1,198 @@
+use bevy::prelude::*;
+use wde_wgpu::{bind_group::{BindGroup, BindGroupLayout, WgpuBindGroup}, buffer::{BufferBindingType, BufferUsage, WBuffer}, instance::WRenderInstance, render_pipeline::WShaderStages, texture::{WTexture, WTextureUsages, WTextureView}};
+
+use crate::{components::{CameraView, DirectionalLight, PointLight}, core::extract_macros::ExtractWorld, features::{CameraFrustum, MaxLightCount, ShadowCasters}};
+
+use super::SHADOW_MAP_FORMAT;
+
+/// The width and height of each face of the cubemap shadow maps.
+pub const POINT_SHADOW_MAP_SIZE: u32 = 1024;
+/// The near plane of the projections of the faces of the cubemap shadow maps.
+pub const POINT_SHADOW_NEAR: f32 = 0.1;
+
+/// The maximum number of point lights rendering a cubemap shadow map each frame (by default 4).
+/// It is read when the renderer starts to allocate the cubemaps, so later changes are ignored.
+/// The point lights are selected among the `ShadowCasters`, from the nearest to the camera.
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct MaxShadowCastingPointLights(pub u32);
+impl Default for MaxShadowCastingPointLights {
+    fn default() -> Self {
+        Self(4)
+    }
+}
+
+/// Describes the cubemap shadow map of a point light in the point shadows buffer, aligned to 16 bytes for the GPU.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct PbrPointShadowElement {
+    /// World space position of the light for xyz. The w component is the far plane of the projections of the faces.
+    pub position_far: [f32; 4],
+    /// x: the near plane of the projections of the faces, y: the index of the light in the lights buffer (-1 if the cubemap is unused), zw: padding.
+    pub params: [f32; 4]
+}
+impl Default for PbrPointShadowElement {
+    fn default() -> Self {
+        Self {
+            position_far: [0.0; 4],
+            params: [0.0, -1.0, 0.0, 0.0]
+        }
+    }
+}
+
+/// A point light that can render a cubemap shadow map, extracted from the main world.
+struct PbrPointShadowCandidate {
+    entity: Entity,
+    /// The index of the light in the lights buffer.
+    light_index: u32,
+    position: Vec3,
+    /// The far plane of the projections of the faces, at the range of the light.
+    far: f32
+}
+
+/// The cubemap shadow maps of the nearest point lights casting shadows, up to `MaxShadowCastingPointLights`.
+/// Each light renders the scene into the six faces of a cubemap of a depth cubemap array, looking along the axes.
+/// The point shadows buffer and the cubemap array are bound with the deferred textures.
+#[derive(Resource)]
+pub struct PbrPointShadowMaps {
+    /// The maximum number of cubemaps.
+    pub max_lights: u32,
+    /// The depth cubemap array, the face `face` of the cubemap `i` being the layer `6 * i + face`.
+    pub texture: WTexture,
+    /// The views of each face of each cubemap, rendered by the point shadow pass.
+    pub face_views: Vec<WTextureView>,
+
+    /// The world to ndc matrix of each face of each cubemap.
+    pub face_buffers: Vec<WBuffer>,
+    /// The bind group of each face of each cubemap, with the layout of the shadow map cascades.
+    pub face_bind_groups: Vec<WgpuBindGroup>,
+    /// The frustum of each face of the cubemaps rendered this frame, to cull the objects not casting shadows into the face.
+    pub face_frustums: Vec<CameraFrustum>,
+
+    /// The description of each cubemap, read by the lighting pass.
+    pub elements_buffer: WBuffer,
+    /// The point lights that can render a cubemap shadow map this frame.
+    candidates: Vec<PbrPointShadowCandidate>,
+}
+impl FromWorld for PbrPointShadowMaps {
+    fn from_world(world: &mut World) -> Self {
+        let max_lights = world.get_resource::<MaxShadowCastingPointLights>().copied().unwrap_or_default().0;
+        let render_instance = world.get_resource::<WRenderInstance<'static>>().unwrap();
+        let render_instance = render_instance.data.read().unwrap();
+
+        // Create the cubemap array, with at least one cubemap to be bound
+        let cubemap_count = max_lights.max(1);
+        let texture = WTexture::new_cube_array(&render_instance, "pbr-point-shadow-map", POINT_SHADOW_MAP_SIZE,
+            cubemap_count, SHADOW_MAP_FORMAT, WTextureUsages::RENDER_ATTACHMENT | WTextureUsages::TEXTURE_BINDING);
+        let face_views = (0..6 * max_lights).map(|layer| texture.view_layer(layer)).collect();
+
+        // Create the faces buffers
+        let face_layout = BindGroupLayout::new("pbr-point-shadow-map-face", |builder| {
+            builder.add_buffer(0, WShaderStages::VERTEX, BufferBindingType::Uniform);
+        });
+        let face_layout_built = face_layout.build(&render_instance);
+        let face_buffers: Vec<WBuffer> = (0..6 * max_lights).map(|face| {
+            WBuffer::new(&render_instance, format!("pbr-point-shadow-map-face-{}", face).as_str(),
+                std::mem::size_of::<[[f32; 4]; 4]>(), BufferUsage::UNIFORM | BufferUsage::COPY_DST, None)
+        }).collect();
+        let face_bind_groups = face_buffers.iter().enumerate().map(|(face, buffer)| {
+            BindGroup::build(format!("pbr-point-shadow-map-face-{}", face).as_str(), &render_instance, &face_layout_built, &vec![
+                BindGroup::buffer(0, buffer)
+            ])
+        }).collect();
+
+        // Create the point shadows buffer
+        let elements_buffer = WBuffer::new(&render_instance, "pbr-point-shadow-maps",
+            std::mem::size_of::<PbrPointShadowElement>() * cubemap_count as usize, BufferUsage::STORAGE | BufferUsage::COPY_DST,
+            Some(bytemuck::cast_slice(&vec![PbrPointShadowElement::default(); cubemap_count as usize])));
+
+        PbrPointShadowMaps {
+            max_lights,
+            texture,
+            face_views,
+            face_buffers,
+            face_bind_groups,
+            face_frustums: Vec::new(),
+            elements_buffer,
+            candidates: Vec::new()
+        }
+    }
+}
+impl PbrPointShadowMaps {
+    /// The direction and the up vector of the view of each face of a cubemap, in the order of `WCubeFace`.
+    /// The up vectors match the orientation of the faces when sampling a cubemap.
+    pub const FACES: [(Vec3, Vec3); 6] = [
+        (Vec3::X,     Vec3::Y),
+        (Vec3::NEG_X, Vec3::Y),
+        (Vec3::Y,     Vec3::NEG_Z),
+        (Vec3::NEG_Y, Vec3::Z),
+        (Vec3::Z,     Vec3::Y),
+        (Vec3::NEG_Z, Vec3::Y)
+    ];
+
+    /// Get the world to ndc matrix of a face of the cubemap of a point light.
+    /// The x axis is mirrored as the faces of a cubemap are seen from its inside.
+    ///
+    /// # Arguments
+    ///
+    /// * `position` - The world space position of the light.
+    /// * `face` - The index of the face, in the order of `WCubeFace`.
+    /// * `far` - The far plane of the projection.
+    pub fn face_world_to_ndc(position: Vec3, face: usize, far: f32) -> Mat4 {
+        let (direction, up) = Self::FACES[face];
+        let view = Mat4::look_at_rh(position, position + direction, up);
+        let proj = Mat4::from_scale(Vec3::new(-1.0, 1.0, 1.0))
+            * Mat4::perspective_rh(std::f32::consts::FRAC_PI_2, 1.0, POINT_SHADOW_NEAR, far);
+        proj * view
+    }
+
+    /// Extract the point lights with their index in the lights buffer.
+    /// The lights casting shadows are selected by the `ShadowCasters`, from their `cast_shadows` field.
+    pub fn extract_candidates(
+        (lights_directional, lights_point): (ExtractWorld<Query<&DirectionalLight>>, ExtractWorld<Query<(Entity, &PointLight)>>),
+        mut point_shadow_maps: ResMut<PbrPointShadowMaps>, max_light_count: Res<MaxLightCount>
+    ) {
+        // The point lights follow the directional lights in the lights buffer
+        let first_index = lights_directional.iter().take(max_light_count.directional_lights as usize).count() as u32;
+        point_shadow_maps.candidates = lights_point.iter().take(max_light_count.point_lights as usize)
+            .zip(first_index..)
+            .map(|((entity, light), light_index)| PbrPointShadowCandidate {
+                entity,
+                light_index,
+                position: light.position,
+                far: light.range().clamp(POINT_SHADOW_NEAR * 2.0, 1000.0)
+            })
+            .collect();
+    }
+
+    /// Assign the cubemaps to the nearest point lights selected by the shadow casters budget, and update their faces.
+    pub fn update_faces(
+        mut point_shadow_maps: ResMut<PbrPointShadowMaps>, casters: Res<ShadowCasters>,
+        render_instance: Res<WRenderInstance<'static>>
+    ) {
+        let point_shadow_maps = &mut *point_shadow_maps;
+        point_shadow_maps.face_frustums.clear();
+
+        // The casters are sorted from the nearest to the farthest from the camera
+        let lights = casters.lights.iter()
+            .filter_map(|caster| point_shadow_maps.candidates.iter().find(|candidate| candidate.entity == caster.entity))
+            .take(point_shadow_maps.max_lights as usize);
+
+        let render_instance = render_instance.data.read().unwrap();
+        let mut elements = vec![PbrPointShadowElement::default(); point_shadow_maps.max_lights.max(1) as usize];
+        for (cubemap, light) in lights.enumerate() {
+            for face in 0..6 {
+                let world_to_ndc = Self::face_world_to_ndc(light.position, face, light.far);
+                point_shadow_maps.face_buffers[6 * cubemap + face].write(&render_instance,
+                    bytemuck::cast_slice(&[world_to_ndc.to_cols_array_2d()]), 0);
+                point_shadow_maps.face_frustums.push(CameraFrustum {
+                    planes: Some(CameraView::frustum_planes_of(&world_to_ndc))
+                });
+            }
+            elements[cubemap] = PbrPointShadowElement {
+                position_far: [light.position.x, light.position.y, light.position.z, light.far],
+                params: [POINT_SHADOW_NEAR, light.light_index as f32, 0.0, 0.0]
+            };
+        }
+        point_shadow_maps.elements_buffer.write(&render_instance, bytemuck::cast_slice(&elements), 0);
+    }
+}
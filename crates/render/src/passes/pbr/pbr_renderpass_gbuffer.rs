@@ -1,24 +1,33 @@
-use std::collections::HashMap;
+use std::{collections::{HashMap, HashSet}, sync::Mutex};
 
-use bevy::prelude::*;
-use crate::{assets::{materials::{PbrMaterial, PbrMaterialAsset}, GpuBuffer, GpuMaterial, GpuMesh, GpuTexture, Mesh, MeshAsset, RenderAssets}, components::TransformUniform, features::CameraFeatureRender, passes::{depth::DepthTexture, render_graph::RenderPass}, pipelines::{CachedPipelineStatus, PipelineManager}};
+use bevy::{log::Level, prelude::*, utils::tracing::span};
+use crate::{assets::{materials::{PbrMaterial, PbrMaterialAsset, PbrMaterialUniform}, GpuBuffer, GpuMaterial, GpuMesh, GpuTexture, Mesh, MeshAsset, ModelBoundingBox, RenderAssets}, components::{Camera, CastShadows, ReceiveShadows, Transparent}, core::pipeline_statistics::PipelineStatisticsQueries, features::{CameraFeatureRender, CameraFrustum}, passes::{depth::DepthTexture, render_graph::RenderPass}, pipelines::{CachedPipelineStatus, PipelineManager}};
 use wde_wgpu::{command_buffer::{RenderPassBuilder, RenderPassColorAttachment, RenderPassDepth, WCommandBuffer}, instance::WRenderInstance};
 
-use super::{GpuPbrGBufferRenderPipeline, PbrDeferredTextures, PbrSsbo};
+use super::{GpuPbrGBufferRenderPipeline, PbrDeferredTextures, PbrGBufferPipelineVariant, PbrDrawIndexedIndirect, PbrIndirectDraws, PbrOcclusionQueries, PbrSsbo, PbrSsboElement, MAX_MATERIAL_COUNT, MAX_OCCLUSION_QUERIES};
 
 pub struct PbrGBufferRenderBatch {
-    mesh: Handle<MeshAsset>,
-    material: Handle<PbrMaterialAsset>,
-    first: usize,
-    count: usize,
-    index_count: usize,
+    pub mesh: Handle<MeshAsset>,
+    pub material: Handle<PbrMaterialAsset>,
+    pub first: usize,
+    pub count: usize,
+    pub index_count: usize,
+    /// Whether the material of the batch is double sided.
+    pub double_sided: bool,
+    /// Whether the material of the batch samples textures, and thus needs its bind group to be set.
+    pub textured: bool,
+    /// The world space bounding box of the entities of the batch, used to cull the batch outside of the camera frustum.
+    pub bounding_box: ModelBoundingBox,
 }
 #[derive(Resource, Default)]
 pub struct PbrGBufferRenderPass {
-    /// The order of the batches: (mesh, material) -> [batch index].
+    /// The order of the opaque batches: (mesh, material) -> [batch index].
     pub batches_order: HashMap<(AssetId<MeshAsset>, AssetId<PbrMaterialAsset>), Vec<usize>>,
-    /// The render batches.
-    pub batches: Vec<PbrGBufferRenderBatch>,
+    /// The opaque render batches, rendered in the gbuffer.
+    pub opaque_batches: Vec<PbrGBufferRenderBatch>,
+    /// The transparent render batches, one per entity sorted back to front, for the entities with a
+    /// transparent material or a `Transparent` component. They are rendered by the `PbrWboitRenderPass`.
+    pub transparent_batches: Vec<PbrGBufferRenderBatch>,
 }
 impl RenderPass for PbrGBufferRenderPass {
     fn extract(&self, main_world: &mut World, render_world: &mut World) {
@@ -35,108 +44,138 @@ impl RenderPass for PbrGBufferRenderPass {
         };
         
         // If no entities, return
-        let mut entities = main_world.query::<(&Transform, &Mesh, &PbrMaterial)>();
+        let mut entities = main_world.query::<(Entity, &Transform, &Mesh, &PbrMaterial, &CastShadows, &ReceiveShadows, Has<Transparent>)>();
         if entities.iter(main_world).count() == 0 {
             return
         }
 
+        // Get the camera position to sort the transparent entities
+        let camera_position = main_world.query_filtered::<&Transform, With<Camera>>()
+            .iter(main_world).next().map(|transform| transform.translation).unwrap_or_default();
+
+        // Gather the parameters of the materials used this frame, read by the shaders from the materials buffer
+        let mut material_indices: HashMap<AssetId<PbrMaterialAsset>, u32> = HashMap::new();
+        let mut material_uniforms: Vec<PbrMaterialUniform> = Vec::new();
+        let mut textured_materials: HashSet<AssetId<PbrMaterialAsset>> = HashSet::new();
+        {
+            let material_assets = main_world.get_resource::<Assets<PbrMaterialAsset>>().unwrap();
+            for (_, _, _, material, ..) in entities.iter(main_world) {
+                if material_indices.contains_key(&material.0.id()) {
+                    continue;
+                }
+                if material_uniforms.len() >= MAX_MATERIAL_COUNT {
+                    warn!("Too many pbr materials ({}), the remaining materials will use the first material parameters.", MAX_MATERIAL_COUNT);
+                    break;
+                }
+                if let Some(asset) = material_assets.get(&material.0) {
+                    material_indices.insert(material.0.id(), material_uniforms.len() as u32);
+                    material_uniforms.push(PbrMaterialUniform::new(asset));
+                    if asset.has_textures() {
+                        textured_materials.insert(material.0.id());
+                    }
+                }
+            }
+        }
+        let material_index = |material: &PbrMaterial| material_indices.get(&material.0.id()).copied().unwrap_or(0);
+
         // Create the batches
         let mut passes = PbrGBufferRenderPass {
             batches_order: HashMap::new(),
-            batches: Vec::new()
+            opaque_batches: Vec::new(),
+            transparent_batches: Vec::new()
         };
         {
-            let render_instance = render_world.get_resource::<WRenderInstance>().unwrap();
-            let render_instance = render_instance.data.read().unwrap();
-            ssbo_bf.buffer.map_write(&render_instance, |mut view| {
-                let mut first = 0;
-                let mut count = 1;
-                let mut last_mesh: Option<Handle<MeshAsset>> = None;
-                let mut last_material: Option<Handle<PbrMaterialAsset>> = None;
-                let data = view.as_mut_ptr() as *mut TransformUniform;
+            let _create_batches_span = span!(Level::INFO, "create_batches").entered();
+            let meshes = render_world.get_resource::<RenderAssets<GpuMesh>>().unwrap();
+            let materials = render_world.get_resource::<RenderAssets<GpuMaterial<PbrMaterialAsset>>>().unwrap();
 
-                let meshes = render_world.get_resource::<RenderAssets<GpuMesh>>().unwrap();
-                let materials = render_world.get_resource::<RenderAssets<GpuMaterial<PbrMaterialAsset>>>().unwrap();
-                for (transform, mesh, material) in entities.iter(main_world) {
-                    // Check if new element in same batch
-                    let last_mesh_ref = last_mesh.as_ref();
-                    let last_material_ref = last_material.as_ref();
-                    if last_mesh_ref.is_some() && last_material_ref.is_some() {
-                        if mesh.0.id() == last_mesh_ref.unwrap().id() && material.0.id() == last_material_ref.unwrap().id() {
-                            // Update the ssbo
-                            let transform = TransformUniform::new(transform);
-                            unsafe {
-                                *data.add(first + count) = transform;
-                            }
-
-                            // Increment the count
-                            count += 1;
-
-                            continue;
-                        } else {
-                            // Push the batch
-                            passes.batches.push(PbrGBufferRenderBatch {
-                                mesh: last_mesh_ref.unwrap().clone_weak(),
-                                material: last_material_ref.unwrap().clone_weak(),
-                                first,
-                                count,
-                                index_count: match meshes.get(last_mesh_ref.unwrap()) {
-                                    Some(mesh) => mesh.index_count as usize,
-                                    None => 0
-                                }
-                            });
+            // Gather the entities whose mesh and material are loaded in parallel
+            let opaque_entities = Mutex::new(Vec::new());
+            let transparent_entities = Mutex::new(Vec::new());
+            entities.par_iter(main_world).for_each(|(entity, transform, mesh, material, cast_shadows, receive_shadows, tagged_transparent)| {
+                let (bounding_box, transparent) = match (meshes.get(&mesh.0), materials.get(&material.0)) {
+                    (Some(gpu_mesh), Some(gpu_material)) => (gpu_mesh.bounding_box.transformed(transform), gpu_material.transparent || tagged_transparent),
+                    _ => return
+                };
+                let element = PbrSsboElement::new(transform, cast_shadows, receive_shadows, material_index(material));
+                if transparent {
+                    let distance = transform.translation.distance_squared(camera_position);
+                    transparent_entities.lock().unwrap().push((distance, entity, mesh, material, element, bounding_box));
+                } else {
+                    opaque_entities.lock().unwrap().push((entity, mesh, material, element, bounding_box));
+                }
+            });
 
-                            let batch_index = passes.batches.len() - 1;
-                            passes.batches_order.entry(
-                                (last_mesh_ref.unwrap().id(), last_material_ref.unwrap().id())
-                            ).or_default().push(batch_index);
+            // Sort the opaque entities by mesh and material to batch them, and the transparent entities
+            // from back to front. The entity is used as tiebreaker so that the order does not depend on the threads.
+            let mut opaque_entities = opaque_entities.into_inner().unwrap();
+            opaque_entities.sort_unstable_by_key(|(entity, mesh, material, ..)| (mesh.0.id(), material.0.id(), *entity));
+            let mut transparent_entities = transparent_entities.into_inner().unwrap();
+            transparent_entities.sort_unstable_by(|(a_distance, a_entity, ..), (b_distance, b_entity, ..)| {
+                b_distance.total_cmp(a_distance).then_with(|| a_entity.cmp(b_entity))
+            });
 
+            let render_instance = render_world.get_resource::<WRenderInstance>().unwrap();
+            let render_instance = render_instance.data.read().unwrap();
+            ssbo_bf.buffer.map_write(&render_instance, |mut view| {
+                let data = view.as_mut_ptr() as *mut PbrSsboElement;
 
-                            // Reset the batch
-                            first += count;
-                            count = 1;
-                            last_mesh = None;
-                            last_material = None;
-                        }
+                // Create one batch per mesh and material
+                let mut first = 0;
+                let mut bounding_box = ModelBoundingBox::default();
+                for (index, (_, mesh, material, element, entity_bounding_box)) in opaque_entities.iter().enumerate() {
+                    // Update the ssbo
+                    unsafe {
+                        *data.add(index) = *element;
                     }
+                    bounding_box = bounding_box.union(entity_bounding_box);
 
-                    // Update the last mesh and ssbo if loaded
-                    let mut updated_mesh = false;
-                    let mut updated_material = false;
-                    if meshes.get(&mesh.0).is_some() {
-                        last_mesh = Some(mesh.0.clone_weak());
-                        updated_mesh = true;
+                    // Push the batch after its last entity
+                    let batch_end = opaque_entities.get(index + 1)
+                        .is_none_or(|(_, next_mesh, next_material, ..)| next_mesh.0.id() != mesh.0.id() || next_material.0.id() != material.0.id());
+                    if !batch_end {
+                        continue;
                     }
-                    if materials.get(&material.0).is_some() {
-                        last_material = Some(material.0.clone_weak());
-                        updated_material = true;
-                    }
-                    if updated_mesh && updated_material {
-                        // Update the ssbo
-                        let transform = TransformUniform::new(transform);
-                        unsafe {
-                            *data.add(first) = transform;
-                        }
-                    }
-                }
-
-                // Push the last batch
-                if let (Some(last_mesh), Some(last_material)) = (last_mesh, last_material) {
-                    passes.batches.push(PbrGBufferRenderBatch {
-                        mesh: last_mesh.clone_weak(),
-                        material: last_material.clone_weak(),
+                    passes.opaque_batches.push(PbrGBufferRenderBatch {
+                        mesh: mesh.0.clone_weak(),
+                        material: material.0.clone_weak(),
                         first,
-                        count,
-                        index_count: match meshes.get(&last_mesh) {
+                        count: index + 1 - first,
+                        index_count: match meshes.get(&mesh.0) {
                             Some(mesh) => mesh.index_count as usize,
                             None => 0
-                        }
+                        },
+                        double_sided: materials.get(&material.0)
+                            .is_some_and(|material| material.double_sided),
+                        textured: textured_materials.contains(&material.0.id()),
+                        bounding_box: std::mem::take(&mut bounding_box)
                     });
 
-                    let batch_index = passes.batches.len() - 1;
+                    let batch_index = passes.opaque_batches.len() - 1;
                     passes.batches_order.entry(
-                        (last_mesh.id(), last_material.id())
+                        (mesh.0.id(), material.0.id())
                     ).or_default().push(batch_index);
+                    first = index + 1;
+                }
+
+                // Create one batch per transparent entity
+                for (next, (_, _, mesh, material, element, bounding_box)) in (first..).zip(transparent_entities) {
+                    // Update the ssbo
+                    unsafe {
+                        *data.add(next) = element;
+                    }
+
+                    passes.transparent_batches.push(PbrGBufferRenderBatch {
+                        mesh: mesh.0.clone_weak(),
+                        material: material.0.clone_weak(),
+                        first: next,
+                        count: 1,
+                        index_count: meshes.get(&mesh.0).map(|mesh| mesh.index_count as usize).unwrap_or(0),
+                        double_sided: materials.get(&material.0)
+                            .is_some_and(|material| material.double_sided),
+                        textured: textured_materials.contains(&material.0.id()),
+                        bounding_box
+                    });
                 }
             });
         }
@@ -151,6 +190,16 @@ impl RenderPass for PbrGBufferRenderPass {
             ssbo_gpu.buffer.copy_from_buffer(&render_instance, &ssbo_bf.buffer);
         }
 
+        // Update the materials buffer
+        if !material_uniforms.is_empty() {
+            let render_instance = render_world.get_resource::<WRenderInstance>().unwrap().data.clone();
+            let materials_buffer = render_world.get_resource::<PbrSsbo>().unwrap().materials_buffer.clone();
+            let mut buffers = render_world.get_resource_mut::<RenderAssets<GpuBuffer>>().unwrap();
+            if let Some(buffer) = buffers.get_mut(&materials_buffer) {
+                buffer.buffer.write(&render_instance.read().unwrap(), bytemuck::cast_slice(&material_uniforms), 0);
+            }
+        }
+
         // Insert the passes
         render_world.insert_resource(passes);
     }
@@ -186,19 +235,29 @@ impl RenderPass for PbrGBufferRenderPass {
             Some(textures) => textures,
             None => return
         };
-        let (albedo, normal, material_tex) = match (
+        let (albedo, normal, material_tex, emissive) = match (
             textures.get(&deferred_textures.albedo),
-            textures.get(&deferred_textures.normal), textures.get(&deferred_textures.material)
+            textures.get(&deferred_textures.normal), textures.get(&deferred_textures.material),
+            textures.get(&deferred_textures.emissive)
         ) {
-            (Some(albedo), Some(normal), Some(material_tex))
-                => (albedo, normal, material_tex),
+            (Some(albedo), Some(normal), Some(material_tex), Some(emissive))
+                => (albedo, normal, material_tex, emissive),
             _ => return
         };
 
+        // Query the occlusion of the batches if the results of the last queried frame were read
+        let occlusion = render_world.get_resource::<PbrOcclusionQueries>();
+        let occlusion_queries = occlusion.filter(|occlusion| occlusion.can_query());
+        let mut queried_batches = Vec::new();
+
         // Create the render pass
         let mut command_buffer = WCommandBuffer::new(&render_instance, "gbuffer-pbr");
+        let timestamp = command_buffer.begin_timestamp("gbuffer-pbr");
         {
             let mut render_pass = command_buffer.create_render_pass("gbuffer-pbr", |builder: &mut RenderPassBuilder| {
+                if let Some(occlusion) = occlusion_queries {
+                    builder.set_occlusion_query_set(&occlusion.query_set);
+                }
                 builder.set_depth_texture(RenderPassDepth {
                     texture: Some(&depth_texture.texture.view),
                     ..Default::default()
@@ -215,83 +274,164 @@ impl RenderPass for PbrGBufferRenderPass {
                     texture: Some(&material_tex.texture.view),
                     ..Default::default()
                 });
+                builder.add_color_attachment(RenderPassColorAttachment {
+                    texture: Some(&emissive.texture.view),
+                    ..Default::default()
+                });
             });
+            let statistics = render_world.get_resource::<PipelineStatisticsQueries>();
+            let statistics_query = statistics.is_some_and(|statistics| statistics.begin(&mut render_pass, "gbuffer-pbr"));
 
             // Render the mesh
             let render_mesh_pass = render_world.get_resource::<PbrGBufferRenderPass>().unwrap();
             let pipeline_manager = render_world.get_resource::<PipelineManager>().unwrap();
             let camera_layout = render_world.get_resource::<CameraFeatureRender>().unwrap();
             let ssbo = render_world.get_resource::<PbrSsbo>().unwrap();
+            let frustum = render_world.get_resource::<CameraFrustum>().unwrap();
+            let indirect_buffer = render_world.get_resource::<PbrIndirectDraws>().and_then(|indirect_draws| indirect_draws.buffer.as_ref());
             if let (
-                CachedPipelineStatus::OkRender(pipeline),
                 Some(camera_bg),
                 Some(ssbo_bind_group)
             ) = (
-                pipeline_manager.get_pipeline(gbuffer_pipeline.cached_pipeline_index),
                 &camera_layout.bind_group,
                 &ssbo.bind_group
             ) {
-                // Set the camera bind group
-                render_pass.set_bind_group(0, camera_bg);
+                let materials = render_world.get_resource::<RenderAssets<GpuMaterial<PbrMaterialAsset>>>().unwrap();
+                let meshes = render_world.get_resource::<RenderAssets<GpuMesh>>().unwrap();
 
-                // Set the pipeline
-                if render_pass.set_pipeline(pipeline).is_ok() {
-                    // Set the ssbo
+                // Render the batches of each pipeline variant
+                for variant in [PbrGBufferPipelineVariant::SingleSidedPipeline, PbrGBufferPipelineVariant::DoubleSidedPipeline] {
+                    // Skip the variant if it has no batch
+                    if !render_mesh_pass.opaque_batches.iter()
+                        .any(|batch| PbrGBufferPipelineVariant::from_double_sided(batch.double_sided) == variant) {
+                        continue;
+                    }
+
+                    // Set the pipeline
+                    let pipeline = match pipeline_manager.get_pipeline(gbuffer_pipeline.variant(variant)) {
+                        CachedPipelineStatus::OkRender(pipeline) => pipeline,
+                        _ => continue
+                    };
+                    if render_pass.set_pipeline(pipeline).is_err() {
+                        error!("Failed to set pipeline.");
+                        continue;
+                    }
+
+                    // Set the camera bind group and the ssbo
+                    render_pass.set_bind_group(0, camera_bg);
                     render_pass.set_bind_group(1, ssbo_bind_group);
 
+                    // Gather the draws of the variant: (first batch index, number of batches)
+                    let is_visible = |batch: &PbrGBufferRenderBatch| {
+                        // Skip the batches outside of the camera frustum, or found occluded in a previous frame
+                        frustum.intersects_aabb(batch.bounding_box.min, batch.bounding_box.max)
+                            && !occlusion.is_some_and(|occlusion| occlusion.is_culled(&(batch.mesh.id(), batch.material.id())))
+                    };
+                    let mut draws = Vec::new();
+                    match indirect_buffer {
+                        // Merge the consecutive batches of a mesh with no textured material, the culled batches being drawn
+                        // without instances. The batches are not merged while their occlusion is queried.
+                        Some(_) => {
+                            let batches = &render_mesh_pass.opaque_batches;
+                            let mut index = 0;
+                            while index < batches.len() {
+                                let batch = &batches[index];
+                                if PbrGBufferPipelineVariant::from_double_sided(batch.double_sided) != variant || !is_visible(batch) {
+                                    index += 1;
+                                    continue;
+                                }
+                                let mut count = 1;
+                                while batches.get(index + count).is_some_and(|next| occlusion_queries.is_none()
+                                    && !batch.textured && !next.textured && next.mesh.id() == batch.mesh.id()
+                                    && PbrGBufferPipelineVariant::from_double_sided(next.double_sided) == variant) {
+                                    count += 1;
+                                }
+                                draws.push((index, count));
+                                index += count;
+                            }
+                        },
+                        // One draw call per set of mesh and material
+                        None => for (_, batch_index) in render_mesh_pass.batches_order.iter() {
+                            for &batch_index in batch_index.iter() {
+                                let batch = render_mesh_pass.opaque_batches.get(batch_index).unwrap();
+                                if PbrGBufferPipelineVariant::from_double_sided(batch.double_sided) == variant && is_visible(batch) {
+                                    draws.push((batch_index, 1));
+                                }
+                            }
+                        }
+                    }
+
                     let mut old_mesh_id = None;
                     let mut old_material_id = None;
+                    for (batch_index, count) in draws {
+                        let batch = render_mesh_pass.opaque_batches.get(batch_index).unwrap();
+                        let batch_key = (batch.mesh.id(), batch.material.id());
 
-                    // For each set of mesh and material
-                    let materials = render_world.get_resource::<RenderAssets<GpuMaterial<PbrMaterialAsset>>>().unwrap();
-                    let meshes = render_world.get_resource::<RenderAssets<GpuMesh>>().unwrap();
-                    for (_, batch_index) in render_mesh_pass.batches_order.iter() {
-                        // For each batch of the set
-                        for &batch_index in batch_index.iter() {
-                            let batch = render_mesh_pass.batches.get(batch_index).unwrap();
-                        
-                            // Set the material
-                            if old_material_id != Some(batch.material.id()) {
-                                let material = match materials.get(&batch.material) {
-                                    Some(material) => material,
-                                    None => continue // Should not happen
-                                };
-
-                                // Set the material bind group
-                                render_pass.set_bind_group(2, &material.bind_group);
-                                old_material_id = Some(batch.material.id());
-                            }
+                        // Set the material, only if it samples textures as the parameters are read from the materials buffer
+                        if old_material_id.is_none() || (batch.textured && old_material_id != Some(batch.material.id())) {
+                            let material = match materials.get(&batch.material) {
+                                Some(material) => material,
+                                None => continue // Should not happen
+                            };
 
-                            // Set the mesh
-                            if old_mesh_id != Some(batch.mesh.id()) {
-                                let mesh = match meshes.get(&batch.mesh) {
-                                    Some(mesh) => mesh,
-                                    None => continue // Should not happen
-                                };
-
-                                // Set the mesh buffers
-                                render_pass.set_vertex_buffer(0, &mesh.vertex_buffer);
-                                render_pass.set_index_buffer(&mesh.index_buffer);
-                                old_mesh_id = Some(batch.mesh.id());
-                            }
+                            // Set the material bind group
+                            render_pass.set_bind_group(2, &material.bind_group);
+                            old_material_id = Some(batch.material.id());
+                        }
 
-                            // Draw the mesh
-                            let instance_indices = batch.first as u32..((batch.first + batch.count) as u32);
-                            match render_pass.draw_indexed(0..batch.index_count as u32, instance_indices) {
-                                Ok(_) => {},
-                                Err(e) => {
-                                    error!("Failed to draw: {:?}.", e);
-                                }
+                        // Set the mesh
+                        if old_mesh_id != Some(batch.mesh.id()) {
+                            let mesh = match meshes.get(&batch.mesh) {
+                                Some(mesh) => mesh,
+                                None => continue // Should not happen
                             };
+
+                            // Set the mesh buffers
+                            render_pass.set_vertex_buffer(0, &mesh.vertex_buffer);
+                            render_pass.set_index_buffer(&mesh.index_buffer);
+                            old_mesh_id = Some(batch.mesh.id());
+                        }
+
+                        // Draw the mesh, counting its visible samples
+                        let query = occlusion_queries.is_some() && (queried_batches.len() as u32) < MAX_OCCLUSION_QUERIES;
+                        if query {
+                            render_pass.begin_occlusion_query(queried_batches.len() as u32);
+                            queried_batches.push(batch_key);
+                        }
+                        let result = match indirect_buffer {
+                            Some(indirect_buffer) => render_pass.multi_draw_indexed_indirect(indirect_buffer,
+                                (batch_index * std::mem::size_of::<PbrDrawIndexedIndirect>()) as u64, count as u32),
+                            None => {
+                                let instance_indices = batch.first as u32..((batch.first + batch.count) as u32);
+                                render_pass.draw_indexed(0..batch.index_count as u32, instance_indices)
+                            }
+                        };
+                        if let Err(e) = result {
+                            error!("Failed to draw: {:?}.", e);
+                        }
+                        if query {
+                            render_pass.end_occlusion_query();
                         }
                     }
-                } else {
-                    error!("Failed to set pipeline.");
                 }
             }
+            if let Some(statistics) = statistics {
+                statistics.end(&mut render_pass, statistics_query);
+            }
+        }
+        command_buffer.end_timestamp(timestamp);
+
+        // Copy the occlusion results to be read in the next frames
+        let occlusion_queries = occlusion_queries.filter(|_| !queried_batches.is_empty());
+        if let Some(occlusion) = occlusion_queries {
+            occlusion.resolve(&mut command_buffer, queried_batches.len() as u32);
         }
 
         // Submit the command buffer
         command_buffer.submit(&render_instance);
+        command_buffer.trace_timestamps(&render_instance);
+        if let Some(occlusion) = occlusion_queries {
+            occlusion.start_read(queried_batches);
+        }
     }
 }
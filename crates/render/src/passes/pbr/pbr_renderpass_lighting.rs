@@ -1,8 +1,8 @@
 use bevy::prelude::*;
-use crate::{assets::{GpuMesh, MeshAsset, ModelBoundingBox, RenderAssets}, core::SwapchainFrame, features::{CameraFeatureRender, LightsFeatureBuffer}, passes::{depth::DepthTextureLayout, render_graph::RenderPass}, pipelines::{CachedPipelineStatus, PipelineManager}};
-use wde_wgpu::{command_buffer::{RenderPassBuilder, RenderPassColorAttachment, WCommandBuffer}, instance::WRenderInstance, vertex::WVertex};
+use crate::{assets::{GpuMesh, GpuTexture, MeshAsset, ModelBoundingBox, RenderAssets}, core::pipeline_statistics::PipelineStatisticsQueries, features::{CameraFeatureRender, LightsFeatureBuffer}, passes::{depth::DepthTextureLayout, hdr::HdrTexture, render_graph::RenderPass}, pipelines::{CachedPipelineStatus, PipelineManager}};
+use wde_wgpu::{command_buffer::{RenderPassBuilder, RenderPassColorAttachment, WCommandBuffer, WLoadOp}, instance::WRenderInstance, vertex::WVertex};
 
-use super::{GpuPbrLightingRenderPipeline, PbrDeferredTexturesLayout};
+use super::{GpuPbrLightingRenderPipeline, PbrBrdfLutGenerated, PbrDeferredTexturesLayout};
 
 #[derive(Resource, Default)]
 pub struct PbrLightingRenderPassMesh {
@@ -15,10 +15,10 @@ impl PbrLightingRenderPassMesh {
         let deferred_mesh: Handle<MeshAsset> = assets_server.add(MeshAsset {
             label: "deferred-lighting-pass".to_string(),
             vertices: vec![
-                WVertex { position: [-1.0, 1.0, 0.0], uv: [0.0, 1.0], normal: [0.0, 0.0, 0.0] },
-                WVertex { position: [-1.0, -1.0, 0.0], uv: [0.0, 0.0], normal: [0.0, 0.0, 0.0] },
-                WVertex { position: [1.0, -1.0, 0.0], uv: [1.0, 0.0], normal: [0.0, 0.0, 0.0] },
-                WVertex { position: [1.0, 1.0, 0.0], uv: [1.0, 1.0], normal: [0.0, 0.0, 0.0] },
+                WVertex { position: [-1.0, 1.0, 0.0], uv: [0.0, 1.0], normal: [0.0, 0.0, 0.0], uv1: [0.0, 1.0], tangent: [0.0; 4] },
+                WVertex { position: [-1.0, -1.0, 0.0], uv: [0.0, 0.0], normal: [0.0, 0.0, 0.0], uv1: [0.0, 0.0], tangent: [0.0; 4] },
+                WVertex { position: [1.0, -1.0, 0.0], uv: [1.0, 0.0], normal: [0.0, 0.0, 0.0], uv1: [1.0, 0.0], tangent: [0.0; 4] },
+                WVertex { position: [1.0, 1.0, 0.0], uv: [1.0, 1.0], normal: [0.0, 0.0, 0.0], uv1: [1.0, 1.0], tangent: [0.0; 4] },
             ],
             indices: vec![0, 1, 2, 0, 2, 3],
             bounding_box: ModelBoundingBox {
@@ -43,10 +43,14 @@ impl RenderPass for PbrLightingRenderPass {
     }
 
     fn render(&self, world: &mut World) {
-        // Get the render instance and swapchain frame
+        // Get the render instance and the hdr color texture
         let render_instance = world.get_resource::<WRenderInstance>().unwrap();
         let render_instance = render_instance.data.read().unwrap();
-        let swapchain_frame = world.get_resource::<SwapchainFrame>().unwrap().data.as_ref().unwrap();
+        let hdr_texture = match world.get_resource::<HdrTexture>()
+            .and_then(|hdr_texture| world.get_resource::<RenderAssets<GpuTexture>>().unwrap().get(&hdr_texture.texture)) {
+            Some(texture) => texture,
+            None => return
+        };
 
         // Check if mesh is ready
         let meshes = world.get_resource::<RenderAssets<GpuMesh>>().unwrap();
@@ -67,13 +71,17 @@ impl RenderPass for PbrLightingRenderPass {
 
         // Create the render pass
         let mut command_buffer = WCommandBuffer::new(&render_instance, "lighting-pbr");
+        let timestamp = command_buffer.begin_timestamp("lighting-pbr");
         {
             let mut render_pass = command_buffer.create_render_pass("lighting-pbr", |builder: &mut RenderPassBuilder| {
                 builder.add_color_attachment(RenderPassColorAttachment {
-                    texture: Some(&swapchain_frame.view),
+                    texture: Some(&hdr_texture.texture.view),
+                    load: WLoadOp::Load,
                     ..Default::default()
                 });
             });
+            let statistics = world.get_resource::<PipelineStatisticsQueries>();
+            let statistics_query = statistics.is_some_and(|statistics| statistics.begin(&mut render_pass, "lighting-pbr"));
 
             // Render the mesh
             if let (
@@ -81,13 +89,17 @@ impl RenderPass for PbrLightingRenderPass {
                 Some(camera_bind_group),
                 Some(depth_bind_group),
                 Some(deferred_bind_group),
-                Some(lights_bind_group)
+                Some(deferred_layout),
+                Some(lights_bind_group),
+                PbrBrdfLutGenerated(true)
             ) = (
                 pipeline_manager.get_pipeline(lighting_pipeline.cached_pipeline_index),
                 &world.get_resource::<CameraFeatureRender>().unwrap().bind_group,
                 &world.get_resource::<DepthTextureLayout>().unwrap().bind_group,
                 &world.get_resource::<PbrDeferredTexturesLayout>().unwrap().deferred_bind_group,
-                &world.get_resource::<LightsFeatureBuffer>().unwrap().bind_group
+                &world.get_resource::<PbrDeferredTexturesLayout>().unwrap().deferred_layout,
+                &world.get_resource::<LightsFeatureBuffer>().unwrap().bind_group,
+                world.get_resource::<PbrBrdfLutGenerated>().unwrap()
             ) {
                 // Set the pipeline
                 if render_pass.set_pipeline(pipeline).is_ok() {
@@ -98,7 +110,7 @@ impl RenderPass for PbrLightingRenderPass {
                     // Set bind groups
                     render_pass.set_bind_group(0, camera_bind_group);
                     render_pass.set_bind_group(1, depth_bind_group);
-                    render_pass.set_bind_group(2, deferred_bind_group);
+                    render_pass.set_bind_group_with_layout(2, deferred_bind_group, deferred_layout);
                     render_pass.set_bind_group(3, lights_bind_group);
                     
                     // Draw the mesh
@@ -112,9 +124,14 @@ impl RenderPass for PbrLightingRenderPass {
                     error!("Failed to set pipeline.");
                 }
             }
+            if let Some(statistics) = statistics {
+                statistics.end(&mut render_pass, statistics_query);
+            }
         }
+        command_buffer.end_timestamp(timestamp);
 
         // Submit the command buffer
         command_buffer.submit(&render_instance);
+        command_buffer.trace_timestamps(&render_instance);
     }
 }
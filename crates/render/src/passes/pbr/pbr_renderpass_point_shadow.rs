@@ -0,0 +1,79 @@
+use bevy::prelude::*;
+use crate::{assets::{GpuMesh, RenderAssets}, core::pipeline_statistics::PipelineStatisticsQueries, passes::render_graph::RenderPass, pipelines::{CachedPipelineStatus, PipelineManager}};
+use wde_wgpu::{command_buffer::{RenderPassBuilder, RenderPassDepth, WCommandBuffer}, instance::WRenderInstance};
+
+use super::{draw_shadow_casters, GpuPbrShadowMapRenderPipeline, PbrGBufferRenderPass, PbrPointShadowMaps, PbrSsbo};
+
+/// Renders the opaque batches of the gbuffer pass into the six faces of the cubemap of each shadow casting point light.
+/// The lights and their faces are selected by `PbrPointShadowMaps::update_faces`.
+#[derive(Resource, Default)]
+pub struct PbrPointShadowRenderPass;
+impl RenderPass for PbrPointShadowRenderPass {
+    fn extract(&self, _main_world: &mut World, _render_world: &mut World) {}
+
+    fn render(&self, render_world: &mut World) {
+        // Get the render instance
+        let render_instance = render_world.get_resource::<WRenderInstance>().unwrap();
+        let render_instance = render_instance.data.read().unwrap();
+
+        // Check if a point light casts shadows
+        let point_shadow_maps = render_world.get_resource::<PbrPointShadowMaps>().unwrap();
+        if point_shadow_maps.face_frustums.is_empty() {
+            return;
+        }
+
+        // Check if pipeline is ready
+        let pipeline_manager = render_world.get_resource::<PipelineManager>().unwrap();
+        let pipeline = match render_world.get_resource::<RenderAssets<GpuPbrShadowMapRenderPipeline>>()
+            .and_then(|pipelines| pipelines.iter().next())
+            .map(|(_, pipeline)| pipeline_manager.get_pipeline(pipeline.cached_pipeline_index)) {
+            Some(CachedPipelineStatus::OkRender(pipeline)) => pipeline,
+            _ => return
+        };
+
+        // Check if the batches are ready
+        let ssbo_bind_group = match &render_world.get_resource::<PbrSsbo>().unwrap().bind_group {
+            Some(bind_group) => bind_group,
+            None => return
+        };
+        let batches = match render_world.get_resource::<PbrGBufferRenderPass>() {
+            Some(pass) => &pass.opaque_batches,
+            None => return
+        };
+        let meshes = render_world.get_resource::<RenderAssets<GpuMesh>>().unwrap();
+
+        // Render each face of each cubemap
+        let mut command_buffer = WCommandBuffer::new(&render_instance, "pbr-point-shadow-map");
+        let timestamp = command_buffer.begin_timestamp("pbr-point-shadow-map");
+        for (face, frustum) in point_shadow_maps.face_frustums.iter().enumerate() {
+            let mut render_pass = command_buffer.create_render_pass("pbr-point-shadow-map", |builder: &mut RenderPassBuilder| {
+                builder.set_depth_texture(RenderPassDepth {
+                    texture: Some(&point_shadow_maps.face_views[face]),
+                    ..Default::default()
+                });
+            });
+            let statistics = render_world.get_resource::<PipelineStatisticsQueries>();
+            let statistics_query = statistics.is_some_and(|statistics|
+                statistics.begin(&mut render_pass, format!("pbr-point-shadow-map-{}-{}", face / 6, face % 6).as_str()));
+
+            // Set the pipeline
+            if render_pass.set_pipeline(pipeline).is_err() {
+                error!("Failed to set pipeline.");
+                continue;
+            }
+            render_pass.set_bind_group(0, &point_shadow_maps.face_bind_groups[face]);
+            render_pass.set_bind_group(1, ssbo_bind_group);
+
+            // Render the batches inside of the frustum of the face
+            draw_shadow_casters(&mut render_pass, batches, meshes, frustum);
+            if let Some(statistics) = statistics {
+                statistics.end(&mut render_pass, statistics_query);
+            }
+        }
+        command_buffer.end_timestamp(timestamp);
+
+        // Submit the command buffer
+        command_buffer.submit(&render_instance);
+        command_buffer.trace_timestamps(&render_instance);
+    }
+}
@@ -0,0 +1,113 @@
+use bevy::prelude::*;
+use crate::{assets::{GpuMesh, RenderAssets}, core::pipeline_statistics::PipelineStatisticsQueries, features::CameraFrustum, passes::render_graph::RenderPass, pipelines::{CachedPipelineStatus, PipelineManager}};
+use wde_wgpu::{command_buffer::{RenderPassBuilder, RenderPassDepth, WCommandBuffer}, instance::WRenderInstance, render_pass::WRenderPass};
+
+use super::{GpuPbrShadowMapRenderPipeline, PbrGBufferRenderBatch, PbrGBufferRenderPass, PbrShadowMaps, PbrSsbo};
+
+/// Draw the opaque batches inside of a frustum into a shadow map, once the pipeline and the bind groups are set.
+/// 
+/// # Arguments
+/// 
+/// * `render_pass` - The render pass of the shadow map.
+/// * `batches` - The opaque batches of the gbuffer pass.
+/// * `meshes` - The meshes of the batches.
+/// * `frustum` - The frustum of the light.
+pub(crate) fn draw_shadow_casters<'pass>(render_pass: &mut WRenderPass<'pass>, batches: &[PbrGBufferRenderBatch], meshes: &'pass RenderAssets<GpuMesh>, frustum: &CameraFrustum) {
+    let mut old_mesh_id = None;
+    for batch in batches.iter() {
+        if !frustum.intersects_aabb(batch.bounding_box.min, batch.bounding_box.max) {
+            continue;
+        }
+
+        // Set the mesh
+        if old_mesh_id != Some(batch.mesh.id()) {
+            let mesh = match meshes.get(&batch.mesh) {
+                Some(mesh) => mesh,
+                None => continue // Should not happen
+            };
+            render_pass.set_vertex_buffer(0, &mesh.vertex_buffer);
+            render_pass.set_index_buffer(&mesh.index_buffer);
+            old_mesh_id = Some(batch.mesh.id());
+        }
+
+        // Draw the mesh
+        let instance_indices = batch.first as u32..((batch.first + batch.count) as u32);
+        if let Err(e) = render_pass.draw_indexed(0..batch.index_count as u32, instance_indices) {
+            error!("Failed to draw: {:?}.", e);
+        }
+    }
+}
+
+/// Renders the opaque batches of the gbuffer pass into each cascade of the shadow map.
+/// The cascades are computed by `PbrShadowMaps::extract_cascades`.
+#[derive(Resource, Default)]
+pub struct PbrShadowMapRenderPass;
+impl RenderPass for PbrShadowMapRenderPass {
+    fn extract(&self, _main_world: &mut World, _render_world: &mut World) {}
+
+    fn render(&self, render_world: &mut World) {
+        // Get the render instance
+        let render_instance = render_world.get_resource::<WRenderInstance>().unwrap();
+        let render_instance = render_instance.data.read().unwrap();
+
+        // Check if a light casts shadows
+        let shadow_maps = render_world.get_resource::<PbrShadowMaps>().unwrap();
+        if shadow_maps.cascade_frustums.is_empty() {
+            return;
+        }
+
+        // Check if pipeline is ready
+        let pipeline_manager = render_world.get_resource::<PipelineManager>().unwrap();
+        let pipeline = match render_world.get_resource::<RenderAssets<GpuPbrShadowMapRenderPipeline>>()
+            .and_then(|pipelines| pipelines.iter().next())
+            .map(|(_, pipeline)| pipeline_manager.get_pipeline(pipeline.cached_pipeline_index)) {
+            Some(CachedPipelineStatus::OkRender(pipeline)) => pipeline,
+            _ => return
+        };
+
+        // Check if the batches are ready
+        let ssbo_bind_group = match &render_world.get_resource::<PbrSsbo>().unwrap().bind_group {
+            Some(bind_group) => bind_group,
+            None => return
+        };
+        let batches = match render_world.get_resource::<PbrGBufferRenderPass>() {
+            Some(pass) => &pass.opaque_batches,
+            None => return
+        };
+        let meshes = render_world.get_resource::<RenderAssets<GpuMesh>>().unwrap();
+
+        // Render each cascade
+        let mut command_buffer = WCommandBuffer::new(&render_instance, "pbr-shadow-map");
+        let timestamp = command_buffer.begin_timestamp("pbr-shadow-map");
+        for (cascade, frustum) in shadow_maps.cascade_frustums.iter().enumerate() {
+            let mut render_pass = command_buffer.create_render_pass("pbr-shadow-map", |builder: &mut RenderPassBuilder| {
+                builder.set_depth_texture(RenderPassDepth {
+                    texture: Some(&shadow_maps.layer_views[cascade]),
+                    ..Default::default()
+                });
+            });
+            let statistics = render_world.get_resource::<PipelineStatisticsQueries>();
+            let statistics_query = statistics.is_some_and(|statistics|
+                statistics.begin(&mut render_pass, format!("pbr-shadow-map-{}", cascade).as_str()));
+
+            // Set the pipeline
+            if render_pass.set_pipeline(pipeline).is_err() {
+                error!("Failed to set pipeline.");
+                continue;
+            }
+            render_pass.set_bind_group(0, &shadow_maps.cascade_bind_groups[cascade]);
+            render_pass.set_bind_group(1, ssbo_bind_group);
+
+            // Render the batches inside of the frustum of the cascade
+            draw_shadow_casters(&mut render_pass, batches, meshes, frustum);
+            if let Some(statistics) = statistics {
+                statistics.end(&mut render_pass, statistics_query);
+            }
+        }
+        command_buffer.end_timestamp(timestamp);
+
+        // Submit the command buffer
+        command_buffer.submit(&render_instance);
+        command_buffer.trace_timestamps(&render_instance);
+    }
+}
@@ -0,0 +1,122 @@
+use bevy::prelude::*;
+use crate::{assets::{GpuTexture, RenderAssets}, core::pipeline_statistics::PipelineStatisticsQueries, features::CameraFeatureRender, passes::{depth::DepthTextureLayout, render_graph::RenderPass}, pipelines::{CachedPipelineStatus, PipelineManager}};
+use wde_wgpu::{command_buffer::{RenderPassBuilder, RenderPassColorAttachment, WColor, WCommandBuffer, WLoadOp}, instance::WRenderInstance};
+
+use super::{GpuPbrSsaoRenderPipeline, PbrSsaoLayout, PbrSsaoTextures};
+
+/// Computes the screen-space ambient occlusion from the depth and the normals of the gbuffer, then blurs it
+/// with a bilateral filter preserving the edges. The blurred occlusion is read by the lighting pass.
+/// While the pipelines are not ready, the blurred occlusion is cleared to 1 so that nothing is occluded.
+#[derive(Resource, Default)]
+pub struct PbrSsaoRenderPass;
+impl RenderPass for PbrSsaoRenderPass {
+    fn extract(&self, _main_world: &mut World, _render_world: &mut World) {}
+
+    fn render(&self, render_world: &mut World) {
+        // Get the render instance
+        let render_instance = render_world.get_resource::<WRenderInstance>().unwrap();
+        let render_instance = render_instance.data.read().unwrap();
+
+        // Check if the textures are ready
+        let textures = render_world.get_resource::<RenderAssets<GpuTexture>>().unwrap();
+        let (occlusion, blurred) = match render_world.get_resource::<PbrSsaoTextures>()
+            .map(|ssao_textures| (textures.get(&ssao_textures.occlusion), textures.get(&ssao_textures.blurred))) {
+            Some((Some(occlusion), Some(blurred))) => (occlusion, blurred),
+            _ => return
+        };
+
+        // Check if the pipelines and the bind groups are ready
+        let pipeline_manager = render_world.get_resource::<PipelineManager>().unwrap();
+        let pipelines = render_world.get_resource::<RenderAssets<GpuPbrSsaoRenderPipeline>>()
+            .and_then(|pipelines| pipelines.iter().next())
+            .map(|(_, pipeline)| (
+                pipeline_manager.get_pipeline(pipeline.cached_pipeline_index),
+                pipeline_manager.get_pipeline(pipeline.cached_blur_pipeline_index)
+            ));
+        let ssao_layout = render_world.get_resource::<PbrSsaoLayout>().unwrap();
+        let ready = match (
+            pipelines,
+            &render_world.get_resource::<CameraFeatureRender>().unwrap().bind_group,
+            &render_world.get_resource::<DepthTextureLayout>().unwrap().bind_group,
+            &ssao_layout.ssao_bind_group,
+            &ssao_layout.blur_bind_group
+        ) {
+            (
+                Some((CachedPipelineStatus::OkRender(pipeline), CachedPipelineStatus::OkRender(blur_pipeline))),
+                Some(camera_bind_group), Some(depth_bind_group), Some(ssao_bind_group), Some(blur_bind_group)
+            ) => Some((pipeline, blur_pipeline, camera_bind_group, depth_bind_group, ssao_bind_group, blur_bind_group)),
+            _ => None
+        };
+
+        let mut command_buffer = WCommandBuffer::new(&render_instance, "pbr-ssao");
+        let timestamp = command_buffer.begin_timestamp("pbr-ssao");
+        match ready {
+            Some((pipeline, blur_pipeline, camera_bind_group, depth_bind_group, ssao_bind_group, blur_bind_group)) => {
+                let statistics = render_world.get_resource::<PipelineStatisticsQueries>();
+
+                // Compute the occlusion
+                {
+                    let mut render_pass = command_buffer.create_render_pass("pbr-ssao", |builder: &mut RenderPassBuilder| {
+                        builder.add_color_attachment(RenderPassColorAttachment {
+                            texture: Some(&occlusion.texture.view),
+                            ..Default::default()
+                        });
+                    });
+                    let statistics_query = statistics.is_some_and(|statistics| statistics.begin(&mut render_pass, "pbr-ssao"));
+                    if render_pass.set_pipeline(pipeline).is_ok() {
+                        render_pass.set_bind_group(0, camera_bind_group);
+                        render_pass.set_bind_group(1, depth_bind_group);
+                        render_pass.set_bind_group(2, ssao_bind_group);
+                        if let Err(e) = render_pass.draw(0..3, 0..1) {
+                            error!("Failed to draw: {:?}.", e);
+                        }
+                    } else {
+                        error!("Failed to set pipeline.");
+                    }
+                    if let Some(statistics) = statistics {
+                        statistics.end(&mut render_pass, statistics_query);
+                    }
+                }
+
+                // Blur the occlusion
+                {
+                    let mut render_pass = command_buffer.create_render_pass("pbr-ssao-blur", |builder: &mut RenderPassBuilder| {
+                        builder.add_color_attachment(RenderPassColorAttachment {
+                            texture: Some(&blurred.texture.view),
+                            ..Default::default()
+                        });
+                    });
+                    let statistics_query = statistics.is_some_and(|statistics| statistics.begin(&mut render_pass, "pbr-ssao-blur"));
+                    if render_pass.set_pipeline(blur_pipeline).is_ok() {
+                        render_pass.set_bind_group(0, camera_bind_group);
+                        render_pass.set_bind_group(1, depth_bind_group);
+                        render_pass.set_bind_group(2, blur_bind_group);
+                        if let Err(e) = render_pass.draw(0..3, 0..1) {
+                            error!("Failed to draw: {:?}.", e);
+                        }
+                    } else {
+                        error!("Failed to set pipeline.");
+                    }
+                    if let Some(statistics) = statistics {
+                        statistics.end(&mut render_pass, statistics_query);
+                    }
+                }
+            },
+            None => {
+                // Clear the blurred occlusion so that nothing is occluded
+                command_buffer.create_render_pass("pbr-ssao-blur", |builder: &mut RenderPassBuilder| {
+                    builder.add_color_attachment(RenderPassColorAttachment {
+                        texture: Some(&blurred.texture.view),
+                        load: WLoadOp::Clear(WColor::WHITE),
+                        ..Default::default()
+                    });
+                });
+            }
+        }
+        command_buffer.end_timestamp(timestamp);
+
+        // Submit the command buffer
+        command_buffer.submit(&render_instance);
+        command_buffer.trace_timestamps(&render_instance);
+    }
+}
@@ -0,0 +1,101 @@
+use bevy::prelude::*;
+use crate::{assets::{GpuTexture, RenderAssets}, core::pipeline_statistics::PipelineStatisticsQueries, passes::{hdr::HdrTexture, render_graph::RenderPass}, pipelines::{CachedPipelineStatus, PipelineManager}};
+use wde_wgpu::{command_buffer::{RenderPassBuilder, RenderPassColorAttachment, WCommandBuffer, WLoadOp}, instance::WRenderInstance};
+
+use super::{GpuPbrSsrRenderPipeline, PbrSsrLayout, SsrSettings};
+
+/// Traces the reflected ray of each pixel in screen space against the depth buffer, refines the intersection
+/// with a binary search and reads the lit color at the hit. The reflections are weighted by the fresnel of the
+/// gbuffer material and added to the hdr color of the lighting pass. Rays leaving the screen reflect nothing.
+/// When `SsrSettings::max_iterations` is 0, the pass is skipped.
+#[derive(Default)]
+pub struct PbrSsrRenderPass;
+impl RenderPass for PbrSsrRenderPass {
+    fn extract(&self, main_world: &mut World, render_world: &mut World) {
+        // Extract the settings
+        let settings = *main_world.get_resource::<SsrSettings>().unwrap();
+        render_world.insert_resource(settings);
+    }
+
+    fn render(&self, render_world: &mut World) {
+        // Check if the reflections are enabled
+        if render_world.get_resource::<SsrSettings>().unwrap().max_iterations == 0 {
+            return;
+        }
+
+        // Get the render instance and the hdr texture
+        let render_instance = render_world.get_resource::<WRenderInstance>().unwrap();
+        let render_instance = render_instance.data.read().unwrap();
+        let hdr = match render_world.get_resource::<HdrTexture>()
+            .and_then(|hdr_texture| render_world.get_resource::<RenderAssets<GpuTexture>>().unwrap().get(&hdr_texture.texture)) {
+            Some(hdr) => hdr,
+            None => return
+        };
+
+        // Check if the pipelines and the bind groups are ready
+        let ssr_layout = render_world.get_resource::<PbrSsrLayout>().unwrap();
+        let pipeline_manager = render_world.get_resource::<PipelineManager>().unwrap();
+        let ssr_pipeline = match render_world.get_resource::<RenderAssets<GpuPbrSsrRenderPipeline>>().unwrap().iter().next() {
+            Some((_, pipeline)) => pipeline,
+            None => return
+        };
+        let (trace_pipeline, composite_pipeline, reflections, trace_bind_group, composite_bind_group) = match (
+            pipeline_manager.get_pipeline(ssr_pipeline.cached_trace_index),
+            pipeline_manager.get_pipeline(ssr_pipeline.cached_composite_index),
+            &ssr_layout.reflections,
+            &ssr_layout.trace_bind_group,
+            &ssr_layout.composite_bind_group
+        ) {
+            (
+                CachedPipelineStatus::OkCompute(trace_pipeline), CachedPipelineStatus::OkRender(composite_pipeline),
+                Some(reflections), Some(trace_bind_group), Some(composite_bind_group)
+            ) => (trace_pipeline, composite_pipeline, reflections, trace_bind_group, composite_bind_group),
+            _ => return
+        };
+
+        let mut command_buffer = WCommandBuffer::new(&render_instance, "pbr-ssr");
+        let timestamp = command_buffer.begin_timestamp("pbr-ssr");
+
+        // Trace the reflected rays
+        {
+            let mut compute_pass = command_buffer.create_compute_pass("pbr-ssr-trace");
+            if compute_pass.set_pipeline(trace_pipeline).is_ok() {
+                compute_pass.set_bind_group(0, trace_bind_group);
+                if let Err(e) = compute_pass.dispatch(reflections.size.0.div_ceil(8), reflections.size.1.div_ceil(8), 1) {
+                    error!("Failed to dispatch: {:?}.", e);
+                }
+            } else {
+                error!("Failed to set pipeline.");
+            }
+        }
+
+        // Add the reflections to the hdr color
+        {
+            let mut render_pass = command_buffer.create_render_pass("pbr-ssr-composite", |builder: &mut RenderPassBuilder| {
+                builder.add_color_attachment(RenderPassColorAttachment {
+                    texture: Some(&hdr.texture.view),
+                    load: WLoadOp::Load,
+                    ..Default::default()
+                });
+            });
+            let statistics = render_world.get_resource::<PipelineStatisticsQueries>();
+            let statistics_query = statistics.is_some_and(|statistics| statistics.begin(&mut render_pass, "pbr-ssr-composite"));
+            if render_pass.set_pipeline(composite_pipeline).is_ok() {
+                render_pass.set_bind_group(0, composite_bind_group);
+                if let Err(e) = render_pass.draw(0..3, 0..1) {
+                    error!("Failed to draw: {:?}.", e);
+                }
+            } else {
+                error!("Failed to set pipeline.");
+            }
+            if let Some(statistics) = statistics {
+                statistics.end(&mut render_pass, statistics_query);
+            }
+        }
+        command_buffer.end_timestamp(timestamp);
+
+        // Submit the command buffer
+        command_buffer.submit(&render_instance);
+        command_buffer.trace_timestamps(&render_instance);
+    }
+}
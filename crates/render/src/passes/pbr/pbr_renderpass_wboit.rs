@@ -0,0 +1,177 @@
+use bevy::prelude::*;
+use crate::{assets::{materials::PbrMaterialAsset, GpuMaterial, GpuMesh, GpuTexture, RenderAssets}, core::pipeline_statistics::PipelineStatisticsQueries, features::{CameraFeatureRender, CameraFrustum, LightsFeatureBuffer}, passes::{depth::DepthTexture, hdr::HdrTexture, render_graph::RenderPass}, pipelines::{CachedPipelineStatus, PipelineManager}};
+use wde_wgpu::{command_buffer::{RenderPassBuilder, RenderPassColorAttachment, RenderPassDepth, WColor, WCommandBuffer, WLoadOp}, instance::WRenderInstance};
+
+use super::{GpuPbrWboitRenderPipeline, PbrGBufferPipelineVariant, PbrGBufferRenderPass, PbrSsbo, PbrWboitLayout, PbrWboitTextures};
+
+/// Renders the transparent batches of the `PbrGBufferRenderPass` with weighted blended order-independent transparency.
+/// The lit transparent fragments are accumulated in any order into the `PbrWboitTextures`, weighted by their depth,
+/// then resolved over the hdr color of the lighting pass.
+/// The transparent meshes are lit by the lights without their shadows, and do not write the depth.
+#[derive(Resource, Default)]
+pub struct PbrWboitRenderPass;
+impl RenderPass for PbrWboitRenderPass {
+    fn render(&self, render_world: &mut World) {
+        // Check if there are transparent batches
+        let batches = match render_world.get_resource::<PbrGBufferRenderPass>() {
+            Some(pass) if !pass.transparent_batches.is_empty() => &pass.transparent_batches,
+            _ => return
+        };
+
+        // Get the render instance
+        let render_instance = render_world.get_resource::<WRenderInstance>().unwrap();
+        let render_instance = render_instance.data.read().unwrap();
+
+        // Check if the textures are ready
+        let textures = render_world.get_resource::<RenderAssets<GpuTexture>>().unwrap();
+        let wboit_textures = match render_world.get_resource::<PbrWboitTextures>() {
+            Some(wboit_textures) => wboit_textures,
+            None => return
+        };
+        let (depth, accum, revealage, hdr) = match (
+            textures.get(&render_world.get_resource::<DepthTexture>().unwrap().texture),
+            textures.get(&wboit_textures.accum), textures.get(&wboit_textures.revealage),
+            render_world.get_resource::<HdrTexture>().and_then(|hdr_texture| textures.get(&hdr_texture.texture))
+        ) {
+            (Some(depth), Some(accum), Some(revealage), Some(hdr)) => (depth, accum, revealage, hdr),
+            _ => return
+        };
+
+        // Check if the pipelines and the bind groups are ready
+        let pipeline_manager = render_world.get_resource::<PipelineManager>().unwrap();
+        let wboit_pipeline = match render_world.get_resource::<RenderAssets<GpuPbrWboitRenderPipeline>>()
+            .and_then(|pipelines| pipelines.iter().next()) {
+            Some((_, pipeline)) => pipeline,
+            None => return
+        };
+        let (resolve_pipeline, camera_bind_group, ssbo_bind_group, lights_bind_group, resolve_bind_group) = match (
+            pipeline_manager.get_pipeline(wboit_pipeline.cached_resolve_pipeline_index),
+            &render_world.get_resource::<CameraFeatureRender>().unwrap().bind_group,
+            &render_world.get_resource::<PbrSsbo>().unwrap().bind_group,
+            &render_world.get_resource::<LightsFeatureBuffer>().unwrap().bind_group,
+            &render_world.get_resource::<PbrWboitLayout>().unwrap().resolve_bind_group
+        ) {
+            (
+                CachedPipelineStatus::OkRender(resolve_pipeline),
+                Some(camera_bind_group), Some(ssbo_bind_group), Some(lights_bind_group), Some(resolve_bind_group)
+            ) => (resolve_pipeline, camera_bind_group, ssbo_bind_group, lights_bind_group, resolve_bind_group),
+            _ => return
+        };
+
+        let mut command_buffer = WCommandBuffer::new(&render_instance, "pbr-wboit");
+        let timestamp = command_buffer.begin_timestamp("pbr-wboit");
+        let statistics = render_world.get_resource::<PipelineStatisticsQueries>();
+
+        // Accumulate the transparent fragments in front of the opaque meshes
+        {
+            let mut render_pass = command_buffer.create_render_pass("pbr-wboit-accum", |builder: &mut RenderPassBuilder| {
+                builder.set_depth_texture(RenderPassDepth {
+                    texture: Some(&depth.texture.view),
+                    load_operation: WLoadOp::Load,
+                    ..Default::default()
+                });
+                builder.add_color_attachment(RenderPassColorAttachment {
+                    texture: Some(&accum.texture.view),
+                    load: WLoadOp::Clear(WColor::TRANSPARENT),
+                    ..Default::default()
+                });
+                builder.add_color_attachment(RenderPassColorAttachment {
+                    texture: Some(&revealage.texture.view),
+                    load: WLoadOp::Clear(WColor::WHITE),
+                    ..Default::default()
+                });
+            });
+            let statistics_query = statistics.is_some_and(|statistics| statistics.begin(&mut render_pass, "pbr-wboit-accum"));
+
+            let frustum = render_world.get_resource::<CameraFrustum>().unwrap();
+            let materials = render_world.get_resource::<RenderAssets<GpuMaterial<PbrMaterialAsset>>>().unwrap();
+            let meshes = render_world.get_resource::<RenderAssets<GpuMesh>>().unwrap();
+            let mut old_variant = None;
+            let mut old_material_id = None;
+            let mut old_mesh_id = None;
+            for batch in batches.iter() {
+                // Skip the batches outside of the camera frustum
+                if !frustum.intersects_aabb(batch.bounding_box.min, batch.bounding_box.max) {
+                    continue;
+                }
+
+                // Set the pipeline of the variant and the bind groups
+                let variant = PbrGBufferPipelineVariant::from_double_sided(batch.double_sided);
+                if old_variant != Some(variant) {
+                    let pipeline = match pipeline_manager.get_pipeline(wboit_pipeline.accum_variant(variant)) {
+                        CachedPipelineStatus::OkRender(pipeline) => pipeline,
+                        _ => continue
+                    };
+                    if render_pass.set_pipeline(pipeline).is_err() {
+                        error!("Failed to set pipeline.");
+                        continue;
+                    }
+                    render_pass.set_bind_group(0, camera_bind_group);
+                    render_pass.set_bind_group(1, ssbo_bind_group);
+                    render_pass.set_bind_group(3, lights_bind_group);
+                    old_variant = Some(variant);
+                    old_material_id = None;
+                    old_mesh_id = None;
+                }
+
+                // Set the material, only if it samples textures as the parameters are read from the materials buffer
+                if old_material_id.is_none() || (batch.textured && old_material_id != Some(batch.material.id())) {
+                    let material = match materials.get(&batch.material) {
+                        Some(material) => material,
+                        None => continue
+                    };
+                    render_pass.set_bind_group(2, &material.bind_group);
+                    old_material_id = Some(batch.material.id());
+                }
+
+                // Set the mesh
+                if old_mesh_id != Some(batch.mesh.id()) {
+                    let mesh = match meshes.get(&batch.mesh) {
+                        Some(mesh) => mesh,
+                        None => continue
+                    };
+                    render_pass.set_vertex_buffer(0, &mesh.vertex_buffer);
+                    render_pass.set_index_buffer(&mesh.index_buffer);
+                    old_mesh_id = Some(batch.mesh.id());
+                }
+
+                // Draw the mesh
+                let instance_indices = batch.first as u32..((batch.first + batch.count) as u32);
+                if let Err(e) = render_pass.draw_indexed(0..batch.index_count as u32, instance_indices) {
+                    error!("Failed to draw: {:?}.", e);
+                }
+            }
+            if let Some(statistics) = statistics {
+                statistics.end(&mut render_pass, statistics_query);
+            }
+        }
+
+        // Resolve the transparent fragments over the hdr color
+        {
+            let mut render_pass = command_buffer.create_render_pass("pbr-wboit-resolve", |builder: &mut RenderPassBuilder| {
+                builder.add_color_attachment(RenderPassColorAttachment {
+                    texture: Some(&hdr.texture.view),
+                    load: WLoadOp::Load,
+                    ..Default::default()
+                });
+            });
+            let statistics_query = statistics.is_some_and(|statistics| statistics.begin(&mut render_pass, "pbr-wboit-resolve"));
+            if render_pass.set_pipeline(resolve_pipeline).is_ok() {
+                render_pass.set_bind_group(0, resolve_bind_group);
+                if let Err(e) = render_pass.draw(0..3, 0..1) {
+                    error!("Failed to draw: {:?}.", e);
+                }
+            } else {
+                error!("Failed to set pipeline.");
+            }
+            if let Some(statistics) = statistics {
+                statistics.end(&mut render_pass, statistics_query);
+            }
+        }
+        command_buffer.end_timestamp(timestamp);
+
+        // Submit the command buffer
+        command_buffer.submit(&render_instance);
+        command_buffer.trace_timestamps(&render_instance);
+    }
+}
@@ -0,0 +1,185 @@
+use bevy::prelude::*;
+use wde_wgpu::{bind_group::{BindGroup, BindGroupLayout, WgpuBindGroup}, buffer::{BufferBindingType, BufferUsage, WBuffer}, instance::WRenderInstance, render_pipeline::WShaderStages, texture::{WCompareFunction, WSampler, WSamplerDescriptor, WTexture, WTextureFormat, WTextureUsages, WTextureView}};
+
+use crate::{components::{ActiveCamera, CameraView, DirectionalLight}, core::extract_macros::ExtractWorld, features::{CameraFrustum, MaxLightCount}};
+
+/// The width and height of each cascade of the shadow map.
+pub const SHADOW_MAP_SIZE: u32 = 2048;
+/// The format of the shadow map.
+pub const SHADOW_MAP_FORMAT: WTextureFormat = WTexture::DEPTH_FORMAT;
+/// The maximum number of cascades of the shadow map.
+pub const MAX_SHADOW_CASCADES: usize = 4;
+/// The maximum distance to the camera covered by the cascades. The fragments further away are not shadowed.
+pub const SHADOW_DISTANCE: f32 = 150.0;
+/// The distance behind each cascade, towards the light, in which the objects still cast shadows into the cascade.
+pub const SHADOW_CASTER_EXTENT: f32 = 200.0;
+/// The blend between the logarithmic (1) and uniform (0) split distances of the cascades.
+/// The logarithmic splits give more resolution close to the camera.
+pub const SHADOW_SPLIT_LAMBDA: f32 = 0.8;
+
+/// The cascades of the shadow map, read by the lighting pass.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct PbrShadowCascadesUniform {
+    /// From world space to the ndc of the light, for each cascade.
+    pub world_to_light: [[[f32; 4]; 4]; MAX_SHADOW_CASCADES],
+    /// The view space depth at which each cascade ends.
+    pub splits: [f32; 4],
+    /// The world space size of a texel of each cascade.
+    pub texel_sizes: [f32; 4],
+    /// x: the number of cascades (0 if no directional light casts shadows), y: the index of the light in the lights buffer, zw: padding.
+    pub params: [f32; 4]
+}
+
+/// The cascaded shadow map of the first directional light casting shadows.
+/// The view of the camera is split into up to `MAX_SHADOW_CASCADES` cascades, each rendered into a layer of a depth texture array
+/// from the point of view of the light. The lighting pass selects the cascade of each fragment from its view space depth.
+/// The cascades uniform, the texture and its sampler are bound with the deferred textures.
+#[derive(Resource)]
+pub struct PbrShadowMaps {
+    /// The depth texture array, one layer per cascade.
+    pub texture: WTexture,
+    /// The views of each layer of the texture, rendered by the shadow map pass.
+    pub layer_views: Vec<WTextureView>,
+    /// The comparison sampler of the texture, used for the percentage closer filtering.
+    pub sampler: WSampler,
+
+    /// The layout of the world to light matrix of a cascade, used by the shadow map pipeline.
+    pub cascade_layout: BindGroupLayout,
+    /// The world to light matrix of each cascade.
+    pub cascade_buffers: Vec<WBuffer>,
+    /// The bind group of each cascade.
+    pub cascade_bind_groups: Vec<WgpuBindGroup>,
+    /// The frustum of the light of each cascade, to cull the objects not casting shadows into the cascade.
+    pub cascade_frustums: Vec<CameraFrustum>,
+
+    /// The cascades uniform, read by the lighting pass.
+    pub cascades: PbrShadowCascadesUniform,
+    /// The buffer of the cascades uniform.
+    pub cascades_buffer: WBuffer,
+}
+impl FromWorld for PbrShadowMaps {
+    fn from_world(world: &mut World) -> Self {
+        let render_instance = world.get_resource::<WRenderInstance<'static>>().unwrap();
+        let render_instance = render_instance.data.read().unwrap();
+
+        // Create the texture array
+        let texture = WTexture::new_array(&render_instance, "pbr-shadow-map", (SHADOW_MAP_SIZE, SHADOW_MAP_SIZE),
+            MAX_SHADOW_CASCADES as u32, SHADOW_MAP_FORMAT, WTextureUsages::RENDER_ATTACHMENT | WTextureUsages::TEXTURE_BINDING);
+        let layer_views = (0..MAX_SHADOW_CASCADES as u32).map(|layer| texture.view_layer(layer)).collect();
+        let sampler = WTexture::create_sampler(&render_instance, "pbr-shadow-map", &Self::SAMPLER);
+
+        // Create the cascades buffers
+        let cascade_layout = BindGroupLayout::new("pbr-shadow-map-cascade", |builder| {
+            builder.add_buffer(0, WShaderStages::VERTEX, BufferBindingType::Uniform);
+        });
+        let cascade_layout_built = cascade_layout.build(&render_instance);
+        let cascade_buffers: Vec<WBuffer> = (0..MAX_SHADOW_CASCADES).map(|cascade| {
+            WBuffer::new(&render_instance, format!("pbr-shadow-map-cascade-{}", cascade).as_str(),
+                std::mem::size_of::<[[f32; 4]; 4]>(), BufferUsage::UNIFORM | BufferUsage::COPY_DST, None)
+        }).collect();
+        let cascade_bind_groups = cascade_buffers.iter().enumerate().map(|(cascade, buffer)| {
+            BindGroup::build(format!("pbr-shadow-map-cascade-{}", cascade).as_str(), &render_instance, &cascade_layout_built, &vec![
+                BindGroup::buffer(0, buffer)
+            ])
+        }).collect();
+        let cascades_buffer = WBuffer::new(&render_instance, "pbr-shadow-map-cascades",
+            std::mem::size_of::<PbrShadowCascadesUniform>(), BufferUsage::UNIFORM | BufferUsage::COPY_DST, None);
+
+        PbrShadowMaps {
+            texture,
+            layer_views,
+            sampler,
+            cascade_layout,
+            cascade_buffers,
+            cascade_bind_groups,
+            cascade_frustums: Vec::new(),
+            cascades: PbrShadowCascadesUniform::default(),
+            cascades_buffer
+        }
+    }
+}
+impl PbrShadowMaps {
+    /// The comparison sampler of the shadow maps, filtering the results of the depth comparisons.
+    pub const SAMPLER: WSamplerDescriptor = WSamplerDescriptor {
+        compare: Some(WCompareFunction::LessEqual),
+        ..WSamplerDescriptor::LINEAR_CLAMP
+    };
+
+    /// Fit the cascades to the view of the camera and the direction of the first directional light casting shadows.
+    #[allow(clippy::type_complexity)]
+    pub fn extract_cascades(
+        (cameras, window, lights): (
+            ExtractWorld<Query<(&Transform, &CameraView), With<ActiveCamera>>>, ExtractWorld<Query<&Window>>,
+            ExtractWorld<Query<&DirectionalLight>>
+        ),
+        mut shadow_maps: ResMut<PbrShadowMaps>, max_light_count: Res<MaxLightCount>, render_instance: Res<WRenderInstance<'static>>
+    ) {
+        let shadow_maps = &mut *shadow_maps;
+        shadow_maps.cascades = PbrShadowCascadesUniform::default();
+        shadow_maps.cascade_frustums.clear();
+
+        // Get the shadow casting light, with its index in the lights buffer
+        let light = lights.iter().take(max_light_count.directional_lights as usize)
+            .enumerate()
+            .find(|(_, light)| light.cast_shadows && light.direction.length_squared() > 0.0);
+        let render_instance = render_instance.data.read().unwrap();
+        if let (
+            Ok((transform, view)), Ok(window), Some((light_index, light))
+        ) = (cameras.get_single(), window.get_single(), light) {
+            let count = light.shadow_cascades.clamp(1, MAX_SHADOW_CASCADES as u32) as usize;
+            let aspect_ratio = window.width() / window.height();
+            let near = view.znear;
+            let far = view.zfar.min(SHADOW_DISTANCE).max(near);
+            let light_direction = light.direction.normalize();
+            let camera_to_world = transform.compute_matrix();
+            let tan_half_fov = (view.fov.to_radians() * 0.5).tan();
+
+            // Split the view between the practical split distances
+            let mut split_near = near;
+            for cascade in 0..count {
+                let ratio = (cascade + 1) as f32 / count as f32;
+                let split_log = near * (far / near).powf(ratio);
+                let split_uniform = near + (far - near) * ratio;
+                let split_far = SHADOW_SPLIT_LAMBDA * split_log + (1.0 - SHADOW_SPLIT_LAMBDA) * split_uniform;
+
+                // Bounding sphere of the slice of the view, so that the cascade does not change with the camera rotation
+                let corners = [split_near, split_far].into_iter().flat_map(|depth| {
+                    let (x, y) = (depth * tan_half_fov * aspect_ratio, depth * tan_half_fov);
+                    [(-x, -y), (x, -y), (-x, y), (x, y)].map(|(x, y)| camera_to_world.transform_point3(Vec3::new(x, y, -depth)))
+                }).collect::<Vec<_>>();
+                let center = corners.iter().sum::<Vec3>() / corners.len() as f32;
+                let radius = corners.iter().map(|corner| corner.distance(center)).fold(0.0, f32::max);
+                let radius = (radius * 16.0).ceil() / 16.0;
+
+                // Orthographic projection from the light, behind the sphere to include the casters in front of it
+                let up = if light_direction.y.abs() > 0.99 { Vec3::Z } else { Vec3::Y };
+                let eye = center - light_direction * (radius + SHADOW_CASTER_EXTENT);
+                let light_view = Mat4::look_at_rh(eye, center, up);
+                let mut light_proj = Mat4::orthographic_rh(-radius, radius, -radius, radius, 0.0, 2.0 * radius + SHADOW_CASTER_EXTENT);
+
+                // Snap the projection to the texels to avoid shimmering edges when the camera moves
+                let half_size = SHADOW_MAP_SIZE as f32 * 0.5;
+                let origin = (light_proj * light_view).transform_point3(Vec3::ZERO).truncate() * half_size;
+                let offset = (origin.round() - origin) / half_size;
+                light_proj.w_axis.x += offset.x;
+                light_proj.w_axis.y += offset.y;
+                let world_to_light = light_proj * light_view;
+
+                shadow_maps.cascades.world_to_light[cascade] = world_to_light.to_cols_array_2d();
+                shadow_maps.cascades.splits[cascade] = split_far;
+                shadow_maps.cascades.texel_sizes[cascade] = 2.0 * radius / SHADOW_MAP_SIZE as f32;
+                shadow_maps.cascade_frustums.push(CameraFrustum {
+                    planes: Some(CameraView::frustum_planes_of(&world_to_light))
+                });
+                shadow_maps.cascade_buffers[cascade].write(&render_instance, bytemuck::cast_slice(&[world_to_light.to_cols_array_2d()]), 0);
+                split_near = split_far;
+            }
+            shadow_maps.cascades.params = [count as f32, light_index as f32, 0.0, 0.0];
+        }
+
+        // Update the cascades buffer
+        let cascades = shadow_maps.cascades;
+        shadow_maps.cascades_buffer.write(&render_instance, bytemuck::cast_slice(&[cascades]), 0);
+    }
+}
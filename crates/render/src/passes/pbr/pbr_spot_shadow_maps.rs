@@ -0,0 +1,189 @@
+use bevy::prelude::*;
+use wde_wgpu::{bind_group::{BindGroup, BindGroupLayout, WgpuBindGroup}, buffer::{BufferBindingType, BufferUsage, WBuffer}, instance::WRenderInstance, render_pipeline::WShaderStages, texture::{WTexture, WTextureUsages, WTextureView}};
+
+use crate::{components::{CameraView, DirectionalLight, PointLight, SpotLight}, core::extract_macros::ExtractWorld, features::{CameraFrustum, MaxLightCount, ShadowCasters}};
+
+use super::SHADOW_MAP_FORMAT;
+
+/// The width and height of the shadow map of each spot light.
+pub const SPOT_SHADOW_MAP_SIZE: u32 = 1024;
+/// The near plane of the projections of the spot lights.
+pub const SPOT_SHADOW_NEAR: f32 = 0.1;
+
+/// The maximum number of spot lights rendering a shadow map each frame (by default 4).
+/// It is read when the renderer starts to allocate the shadow maps, so later changes are ignored.
+/// The spot lights are selected among the `ShadowCasters`, from the nearest to the camera.
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct MaxShadowCastingSpotLights(pub u32);
+impl Default for MaxShadowCastingSpotLights {
+    fn default() -> Self {
+        Self(4)
+    }
+}
+
+/// Describes the shadow map of a spot light in the spot shadows buffer, aligned to 16 bytes for the GPU.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct PbrSpotShadowElement {
+    /// From world space to the ndc of the light.
+    pub world_to_light: [[f32; 4]; 4],
+    /// x: the index of the light in the lights buffer (-1 if the shadow map is unused), yzw: padding.
+    pub params: [f32; 4]
+}
+impl Default for PbrSpotShadowElement {
+    fn default() -> Self {
+        Self {
+            world_to_light: [[0.0; 4]; 4],
+            params: [-1.0, 0.0, 0.0, 0.0]
+        }
+    }
+}
+
+/// A spot light that can render a shadow map, extracted from the main world.
+struct PbrSpotShadowCandidate {
+    entity: Entity,
+    /// The index of the light in the lights buffer.
+    light_index: u32,
+    /// From world space to the ndc of the light.
+    world_to_light: Mat4
+}
+
+/// The shadow maps of the nearest spot lights casting shadows, up to `MaxShadowCastingSpotLights`.
+/// Each light renders the scene into a layer of a depth texture array, with a perspective projection covering its cone.
+/// The spot shadows buffer and the texture array are bound with the deferred textures.
+#[derive(Resource)]
+pub struct PbrSpotShadowMaps {
+    /// The maximum number of shadow maps.
+    pub max_lights: u32,
+    /// The depth texture array, one layer per light.
+    pub texture: WTexture,
+    /// The view of the texture as an array, bound in the lighting pass.
+    pub array_view: WTextureView,
+    /// The views of each layer of the texture, rendered by the spot shadow pass.
+    pub layer_views: Vec<WTextureView>,
+
+    /// The world to light matrix of each layer.
+    pub layer_buffers: Vec<WBuffer>,
+    /// The bind group of each layer, with the layout of the shadow map cascades.
+    pub layer_bind_groups: Vec<WgpuBindGroup>,
+    /// The frustum of each layer rendered this frame, to cull the objects not casting shadows into the layer.
+    pub layer_frustums: Vec<CameraFrustum>,
+
+    /// The description of each shadow map, read by the lighting pass.
+    pub elements_buffer: WBuffer,
+    /// The spot lights that can render a shadow map this frame.
+    candidates: Vec<PbrSpotShadowCandidate>,
+}
+impl FromWorld for PbrSpotShadowMaps {
+    fn from_world(world: &mut World) -> Self {
+        let max_lights = world.get_resource::<MaxShadowCastingSpotLights>().copied().unwrap_or_default().0;
+        let render_instance = world.get_resource::<WRenderInstance<'static>>().unwrap();
+        let render_instance = render_instance.data.read().unwrap();
+
+        // Create the texture array, with at least one layer to be bound
+        let layer_count = max_lights.max(1);
+        let texture = WTexture::new_array(&render_instance, "pbr-spot-shadow-map", (SPOT_SHADOW_MAP_SIZE, SPOT_SHADOW_MAP_SIZE),
+            layer_count, SHADOW_MAP_FORMAT, WTextureUsages::RENDER_ATTACHMENT | WTextureUsages::TEXTURE_BINDING);
+        let array_view = texture.view_array();
+        let layer_views = (0..max_lights).map(|layer| texture.view_layer(layer)).collect();
+
+        // Create the layers buffers
+        let layer_layout = BindGroupLayout::new("pbr-spot-shadow-map-layer", |builder| {
+            builder.add_buffer(0, WShaderStages::VERTEX, BufferBindingType::Uniform);
+        });
+        let layer_layout_built = layer_layout.build(&render_instance);
+        let layer_buffers: Vec<WBuffer> = (0..max_lights).map(|layer| {
+            WBuffer::new(&render_instance, format!("pbr-spot-shadow-map-layer-{}", layer).as_str(),
+                std::mem::size_of::<[[f32; 4]; 4]>(), BufferUsage::UNIFORM | BufferUsage::COPY_DST, None)
+        }).collect();
+        let layer_bind_groups = layer_buffers.iter().enumerate().map(|(layer, buffer)| {
+            BindGroup::build(format!("pbr-spot-shadow-map-layer-{}", layer).as_str(), &render_instance, &layer_layout_built, &vec![
+                BindGroup::buffer(0, buffer)
+            ])
+        }).collect();
+
+        // Create the spot shadows buffer
+        let elements_buffer = WBuffer::new(&render_instance, "pbr-spot-shadow-maps",
+            std::mem::size_of::<PbrSpotShadowElement>() * layer_count as usize, BufferUsage::STORAGE | BufferUsage::COPY_DST,
+            Some(bytemuck::cast_slice(&vec![PbrSpotShadowElement::default(); layer_count as usize])));
+
+        PbrSpotShadowMaps {
+            max_lights,
+            texture,
+            array_view,
+            layer_views,
+            layer_buffers,
+            layer_bind_groups,
+            layer_frustums: Vec::new(),
+            elements_buffer,
+            candidates: Vec::new()
+        }
+    }
+}
+impl PbrSpotShadowMaps {
+    /// Get the world to ndc matrix of the shadow map of a spot light, whose projection covers the outer cone of the light.
+    ///
+    /// # Arguments
+    ///
+    /// * `light` - The spot light.
+    pub fn world_to_light(light: &SpotLight) -> Mat4 {
+        let direction = light.direction.normalize();
+        let up = if direction.y.abs() > 0.99 { Vec3::Z } else { Vec3::Y };
+        let view = Mat4::look_at_rh(light.position, light.position + direction, up);
+        let fov = (2.0 * light.outer_cutoff).clamp(0.01, 170.0_f32.to_radians());
+        let far = light.range().clamp(SPOT_SHADOW_NEAR * 2.0, 1000.0);
+        Mat4::perspective_rh(fov, 1.0, SPOT_SHADOW_NEAR, far) * view
+    }
+
+    /// Extract the spot lights with a direction, with their index in the lights buffer.
+    /// The lights casting shadows are selected by the `ShadowCasters`, from their `cast_shadows` field.
+    #[allow(clippy::type_complexity)]
+    pub fn extract_candidates(
+        (lights_directional, lights_point, lights_spot): (
+            ExtractWorld<Query<&DirectionalLight>>, ExtractWorld<Query<&PointLight>>, ExtractWorld<Query<(Entity, &SpotLight)>>
+        ),
+        mut spot_shadow_maps: ResMut<PbrSpotShadowMaps>, max_light_count: Res<MaxLightCount>
+    ) {
+        // The spot lights follow the directional and point lights in the lights buffer
+        let first_index = lights_directional.iter().take(max_light_count.directional_lights as usize).count()
+            + lights_point.iter().take(max_light_count.point_lights as usize).count();
+        spot_shadow_maps.candidates = lights_spot.iter().take(max_light_count.spot_lights as usize)
+            .zip(first_index as u32..)
+            .filter(|((_, light), _)| light.direction.length_squared() > 0.0)
+            .map(|((entity, light), light_index)| PbrSpotShadowCandidate {
+                entity,
+                light_index,
+                world_to_light: Self::world_to_light(light)
+            })
+            .collect();
+    }
+
+    /// Assign the shadow maps to the nearest spot lights selected by the shadow casters budget, and update their layers.
+    pub fn update_layers(
+        mut spot_shadow_maps: ResMut<PbrSpotShadowMaps>, casters: Res<ShadowCasters>,
+        render_instance: Res<WRenderInstance<'static>>
+    ) {
+        let spot_shadow_maps = &mut *spot_shadow_maps;
+        spot_shadow_maps.layer_frustums.clear();
+
+        // The casters are sorted from the nearest to the farthest from the camera
+        let lights = casters.lights.iter()
+            .filter_map(|caster| spot_shadow_maps.candidates.iter().find(|candidate| candidate.entity == caster.entity))
+            .take(spot_shadow_maps.max_lights as usize);
+
+        let render_instance = render_instance.data.read().unwrap();
+        let mut elements = vec![PbrSpotShadowElement::default(); spot_shadow_maps.max_lights.max(1) as usize];
+        for (layer, light) in lights.enumerate() {
+            let world_to_light = light.world_to_light.to_cols_array_2d();
+            spot_shadow_maps.layer_buffers[layer].write(&render_instance, bytemuck::cast_slice(&[world_to_light]), 0);
+            spot_shadow_maps.layer_frustums.push(CameraFrustum {
+                planes: Some(CameraView::frustum_planes_of(&light.world_to_light))
+            });
+            elements[layer] = PbrSpotShadowElement {
+                world_to_light,
+                params: [light.light_index as f32, 0.0, 0.0, 0.0]
+            };
+        }
+        spot_shadow_maps.elements_buffer.write(&render_instance, bytemuck::cast_slice(&elements), 0);
+    }
+}
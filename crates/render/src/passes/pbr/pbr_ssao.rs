@@ -0,0 +1,261 @@
+use bevy::prelude::*;
+use wde_wgpu::{bind_group::{BindGroup, BindGroupLayout, BindGroupLayoutBuilder, WgpuBindGroup}, buffer::{BufferBindingType, BufferUsage, WBuffer}, instance::WRenderInstance, render_pipeline::WShaderStages, texture::{WSamplerDescriptor, WTextureFormat, WTextureUsages}};
+
+use crate::{assets::{GpuTexture, RenderAssets, Texture}, core::{extract_macros::ExtractWorld, window::SurfaceResized}};
+
+use super::{PbrDeferredTextures, PbrDeferredTexturesLayout};
+
+/// The maximum number of samples of the hemisphere kernel of the ambient occlusion.
+pub const MAX_SSAO_KERNEL_SIZE: usize = 64;
+/// The width and height of the noise texture rotating the kernel, tiled over the screen.
+pub const SSAO_NOISE_SIZE: u32 = 4;
+/// The format of the ambient occlusion textures.
+pub const SSAO_FORMAT: WTextureFormat = WTextureFormat::R8Unorm;
+
+/// The settings of the screen-space ambient occlusion, applied to the diffuse and ambient light of the lighting pass.
+#[derive(Resource, Debug, Clone, Copy, PartialEq)]
+pub struct SsaoSettings {
+    /// The world space radius of the hemisphere around each fragment in which the occluders are searched (default: 0.5).
+    pub radius: f32,
+    /// The depth difference under which an occluder is ignored, to avoid the self-occlusion of flat surfaces (default: 0.025).
+    pub bias: f32,
+    /// The number of samples of the hemisphere, up to `MAX_SSAO_KERNEL_SIZE` (default: 32).
+    pub kernel_size: u32,
+}
+impl Default for SsaoSettings {
+    fn default() -> Self {
+        Self {
+            radius: 0.5,
+            bias: 0.025,
+            kernel_size: 32
+        }
+    }
+}
+
+/// The ambient occlusion uniform, read by the ambient occlusion pass.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct PbrSsaoUniform {
+    /// The tangent space offsets of the samples of the hemisphere, of length up to 1 and oriented along +z.
+    pub kernel: [[f32; 4]; MAX_SSAO_KERNEL_SIZE],
+    /// x: the radius, y: the bias, z: the number of samples, w: padding.
+    pub params: [f32; 4]
+}
+
+/// Get a pseudo-random number in [0, 1] from a xorshift state.
+fn next_random(state: &mut u32) -> f32 {
+    *state ^= *state << 13;
+    *state ^= *state >> 17;
+    *state ^= *state << 5;
+    *state as f32 / u32::MAX as f32
+}
+
+/// The textures of the screen-space ambient occlusion.
+/// The raw occlusion is rendered into `occlusion`, then blurred into `blurred` which is bound with the deferred textures.
+#[derive(Resource)]
+pub struct PbrSsaoTextures {
+    /// The raw occlusion of each pixel, 1 when the pixel is not occluded.
+    pub occlusion: Handle<Texture>,
+    /// The occlusion blurred by the bilateral filter.
+    pub blurred: Handle<Texture>,
+    /// The random rotations of the kernel around the normals, tiled over the screen.
+    pub noise: Handle<Texture>,
+    pub resized: bool
+}
+impl PbrSsaoTextures {
+    /// Create an occlusion texture.
+    fn occlusion_texture(label: &str, size: (u32, u32)) -> Texture {
+        Texture {
+            label: label.to_string(),
+            size,
+            format: SSAO_FORMAT,
+            usages: WTextureUsages::RENDER_ATTACHMENT | WTextureUsages::TEXTURE_BINDING,
+            sampler: WSamplerDescriptor::NEAREST_CLAMP,
+            ..Default::default()
+        }
+    }
+
+    /// Create the occlusion textures and the noise texture.
+    pub fn create_textures(mut commands: Commands, assets_server: Res<AssetServer>, window: Query<&Window>) {
+        let resolution = &window.single().resolution;
+        let size = (resolution.physical_width(), resolution.physical_height());
+
+        // Create the noise texture, with random unit vectors on the tangent plane
+        let mut state = 0x9e37_79b9;
+        let data = (0..SSAO_NOISE_SIZE * SSAO_NOISE_SIZE).flat_map(|_| {
+            let rotation = Vec2::new(next_random(&mut state) * 2.0 - 1.0, next_random(&mut state) * 2.0 - 1.0)
+                .try_normalize().unwrap_or(Vec2::X);
+            [rotation.x, rotation.y, 0.0, 1.0].map(|v| ((v * 0.5 + 0.5) * 255.0).round() as u8)
+        }).collect();
+        let noise = assets_server.add(Texture {
+            label: "pbr-ssao-noise".to_string(),
+            size: (SSAO_NOISE_SIZE, SSAO_NOISE_SIZE),
+            format: WTextureFormat::Rgba8Unorm,
+            usages: WTextureUsages::TEXTURE_BINDING | WTextureUsages::COPY_DST,
+            data,
+            sampler: WSamplerDescriptor::NEAREST_CLAMP,
+            ..Default::default()
+        });
+
+        commands.insert_resource(PbrSsaoTextures {
+            occlusion: assets_server.add(Self::occlusion_texture("pbr-ssao", size)),
+            blurred: assets_server.add(Self::occlusion_texture("pbr-ssao-blurred", size)),
+            noise,
+            resized: false
+        });
+    }
+
+    /// Recreate the occlusion textures when the surface is resized.
+    pub fn resize_textures(
+        mut window_resized_events: EventReader<SurfaceResized>,
+        server: Res<AssetServer>, mut textures: ResMut<PbrSsaoTextures>
+    ) {
+        textures.resized = false;
+        for event in window_resized_events.read() {
+            let size = (event.width, event.height);
+            textures.occlusion = server.add(Self::occlusion_texture("pbr-ssao", size));
+            textures.blurred = server.add(Self::occlusion_texture("pbr-ssao-blurred", size));
+            textures.resized = true;
+        }
+    }
+
+    /// Extract the textures and the settings.
+    pub fn extract_textures(
+        mut commands: Commands, (textures, settings): (ExtractWorld<Res<PbrSsaoTextures>>, ExtractWorld<Res<SsaoSettings>>),
+        mut ssao_layout: ResMut<PbrSsaoLayout>, mut deferred_layout: ResMut<PbrDeferredTexturesLayout>
+    ) {
+        if textures.resized {
+            ssao_layout.ssao_bind_group = None;
+            ssao_layout.blur_bind_group = None;
+            deferred_layout.deferred_layout = None;
+            deferred_layout.deferred_bind_group = None;
+        }
+
+        commands.insert_resource(PbrSsaoTextures {
+            occlusion: textures.occlusion.clone(),
+            blurred: textures.blurred.clone(),
+            noise: textures.noise.clone(),
+            resized: false
+        });
+        commands.insert_resource(**settings);
+    }
+}
+
+/// The bind groups of the ambient occlusion pass and of its blur.
+#[derive(Resource)]
+pub struct PbrSsaoLayout {
+    /// The layout of the inputs of the ambient occlusion: the normals, the noise and the uniform.
+    pub ssao_layout: BindGroupLayout,
+    pub ssao_bind_group: Option<WgpuBindGroup>,
+    /// The layout of the inputs of the blur: the raw occlusion and the normals.
+    pub blur_layout: BindGroupLayout,
+    pub blur_bind_group: Option<WgpuBindGroup>,
+
+    /// The buffer of the ambient occlusion uniform.
+    pub uniform_buffer: WBuffer,
+    /// The settings the uniform was last written with.
+    settings: Option<SsaoSettings>
+}
+impl FromWorld for PbrSsaoLayout {
+    fn from_world(world: &mut World) -> Self {
+        let render_instance = world.get_resource::<WRenderInstance<'static>>().unwrap();
+        let render_instance = render_instance.data.read().unwrap();
+
+        let ssao_layout = BindGroupLayout::new("pbr-ssao", |builder: &mut BindGroupLayoutBuilder| {
+            builder.add_texture_view(   0, WShaderStages::FRAGMENT);
+            builder.add_texture_sampler(1, WShaderStages::FRAGMENT);
+            builder.add_texture_view(   2, WShaderStages::FRAGMENT);
+            builder.add_buffer(3, WShaderStages::FRAGMENT, BufferBindingType::Uniform);
+        });
+        let blur_layout = BindGroupLayout::new("pbr-ssao-blur", |builder: &mut BindGroupLayoutBuilder| {
+            builder.add_texture_view(   0, WShaderStages::FRAGMENT);
+            builder.add_texture_sampler(1, WShaderStages::FRAGMENT);
+            builder.add_texture_view(   2, WShaderStages::FRAGMENT);
+            builder.add_texture_sampler(3, WShaderStages::FRAGMENT);
+        });
+        let uniform_buffer = WBuffer::new(&render_instance, "pbr-ssao",
+            std::mem::size_of::<PbrSsaoUniform>(), BufferUsage::UNIFORM | BufferUsage::COPY_DST, None);
+
+        PbrSsaoLayout {
+            ssao_layout,
+            ssao_bind_group: None,
+            blur_layout,
+            blur_bind_group: None,
+            uniform_buffer,
+            settings: None
+        }
+    }
+}
+impl PbrSsaoLayout {
+    /// Write the uniform when the settings change, regenerating the hemisphere kernel for the number of samples.
+    pub fn update_uniform(
+        mut ssao_layout: ResMut<PbrSsaoLayout>, settings: Res<SsaoSettings>, render_instance: Res<WRenderInstance<'static>>
+    ) {
+        if ssao_layout.settings == Some(*settings) {
+            return;
+        }
+        let kernel_size = settings.kernel_size.clamp(1, MAX_SSAO_KERNEL_SIZE as u32);
+
+        // Random samples in the hemisphere, closer to the fragment for the first samples
+        let mut state = 0x2545_f491;
+        let mut uniform = PbrSsaoUniform {
+            kernel: [[0.0; 4]; MAX_SSAO_KERNEL_SIZE],
+            params: [settings.radius, settings.bias, kernel_size as f32, 0.0]
+        };
+        for (i, sample) in uniform.kernel.iter_mut().take(kernel_size as usize).enumerate() {
+            let direction = Vec3::new(
+                next_random(&mut state) * 2.0 - 1.0,
+                next_random(&mut state) * 2.0 - 1.0,
+                next_random(&mut state)
+            ).try_normalize().unwrap_or(Vec3::Z);
+            let ratio = i as f32 / kernel_size as f32;
+            let scale = 0.1 + 0.9 * ratio * ratio;
+            *sample = (direction * next_random(&mut state) * scale).extend(0.0).to_array();
+        }
+
+        let render_instance = render_instance.data.read().unwrap();
+        ssao_layout.uniform_buffer.write(&render_instance, bytemuck::cast_slice(&[uniform]), 0);
+        ssao_layout.settings = Some(*settings);
+    }
+
+    /// Build the bind groups of the ambient occlusion pass once the textures are ready.
+    pub fn build_bind_groups(
+        mut ssao_layout: ResMut<PbrSsaoLayout>, ssao_textures: Option<Res<PbrSsaoTextures>>,
+        deferred_textures: Res<PbrDeferredTextures>, textures: Res<RenderAssets<GpuTexture>>,
+        render_instance: Res<WRenderInstance<'static>>
+    ) {
+        // Check if the bind groups are already created
+        if ssao_layout.ssao_bind_group.is_some() && ssao_layout.blur_bind_group.is_some() {
+            return;
+        }
+
+        // Get the textures
+        let (normal, noise, occlusion) = match ssao_textures.map(|ssao_textures| (
+            textures.get(&deferred_textures.normal), textures.get(&ssao_textures.noise), textures.get(&ssao_textures.occlusion)
+        )) {
+            Some((Some(normal), Some(noise), Some(occlusion))) => (normal, noise, occlusion),
+            _ => return
+        };
+
+        // Create the bind groups
+        let render_instance = render_instance.data.read().unwrap();
+        let ssao_layout_built = ssao_layout.ssao_layout.build(&render_instance);
+        let ssao_bind_group = BindGroup::build("pbr-ssao", &render_instance, &ssao_layout_built, &vec![
+            BindGroup::texture_view(   0, &normal.texture),
+            BindGroup::texture_sampler(1, &normal.texture),
+            BindGroup::texture_view(   2, &noise.texture),
+            BindGroup::buffer(3, &ssao_layout.uniform_buffer)
+        ]);
+        let blur_layout_built = ssao_layout.blur_layout.build(&render_instance);
+        let blur_bind_group = BindGroup::build("pbr-ssao-blur", &render_instance, &blur_layout_built, &vec![
+            BindGroup::texture_view(   0, &occlusion.texture),
+            BindGroup::texture_sampler(1, &occlusion.texture),
+            BindGroup::texture_view(   2, &normal.texture),
+            BindGroup::texture_sampler(3, &normal.texture)
+        ]);
+
+        // Insert the resources
+        ssao_layout.ssao_bind_group = Some(ssao_bind_group);
+        ssao_layout.blur_bind_group = Some(blur_bind_group);
+    }
+}
@@ -1,15 +1,57 @@
 use bevy::prelude::*;
 use wde_wgpu::{bind_group::{BindGroup, BindGroupLayout, WgpuBindGroup}, buffer::{BufferBindingType, BufferUsage}, instance::WRenderInstance, render_pipeline::WShaderStages};
 
-use crate::{assets::{Buffer, GpuBuffer, RenderAssets}, components::TransformUniform, core::{Render, RenderApp, RenderSet}};
+use crate::{assets::{materials::PbrMaterialUniform, Buffer, GpuBuffer, RenderAssets}, components::{CastShadows, ReceiveShadows, TransformUniform}, core::{Render, RenderApp, RenderSet}};
 
 /// The maximum number of entities in the ssbo.
 pub const MAX_ENTITY_COUNT: usize = 100_000;
+/// The maximum number of materials in the materials buffer.
+pub const MAX_MATERIAL_COUNT: usize = 4096;
+
+/// The entity casts shadows.
+pub const PBR_INSTANCE_FLAG_CAST_SHADOWS: u32 = 1 << 0;
+/// The entity receives shadows.
+pub const PBR_INSTANCE_FLAG_RECEIVE_SHADOWS: u32 = 1 << 1;
+
+/// Describes an entity instance in the pbr ssbo, aligned to 16 bytes for the GPU.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct PbrSsboElement {
+    /// From object to world space.
+    pub object_to_world: [[f32; 4]; 4],
+    /// Flags of the instance (x: see `PBR_INSTANCE_FLAG_*`, y: index of the material in the materials buffer, zw: padding).
+    pub flags: [u32; 4]
+}
+impl PbrSsboElement {
+    /// Create a new pbr ssbo element.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `transform` - The transform component.
+    /// * `cast_shadows` - The cast shadows component.
+    /// * `receive_shadows` - The receive shadows component.
+    /// * `material_index` - The index of the material in the materials buffer.
+    pub fn new(transform: &Transform, cast_shadows: &CastShadows, receive_shadows: &ReceiveShadows, material_index: u32) -> Self {
+        let mut flags = 0;
+        if cast_shadows.0 {
+            flags |= PBR_INSTANCE_FLAG_CAST_SHADOWS;
+        }
+        if receive_shadows.0 {
+            flags |= PBR_INSTANCE_FLAG_RECEIVE_SHADOWS;
+        }
+        Self {
+            object_to_world: TransformUniform::transform_obj_to_world(transform).to_cols_array_2d(),
+            flags: [flags, material_index, 0, 0]
+        }
+    }
+}
 
 #[derive(Resource)]
 pub struct PbrSsbo {
     pub buffer: Handle<Buffer>,
     pub buffer_gpu: Handle<Buffer>,
+    /// The parameters of the materials used this frame, indexed by the material index of the instances.
+    pub materials_buffer: Handle<Buffer>,
     pub bind_group_layout: Option<BindGroupLayout>,
     pub bind_group: Option<WgpuBindGroup>
 }
@@ -20,10 +62,10 @@ impl PbrSsbo {
             return;
         }
 
-        // Get the ssbo buffer
-        let buffer = match buffers.get(&ssbo.buffer_gpu) {
-            Some(buffer) => buffer,
-            None => return
+        // Get the ssbo buffers
+        let (buffer, materials_buffer) = match (buffers.get(&ssbo.buffer_gpu), buffers.get(&ssbo.materials_buffer)) {
+            (Some(buffer), Some(materials_buffer)) => (buffer, materials_buffer),
+            _ => return
         };
 
         // Create the ssbo layout
@@ -31,13 +73,17 @@ impl PbrSsbo {
             builder.add_buffer(0,
                 WShaderStages::VERTEX,
                 BufferBindingType::Storage { read_only: true });
+            builder.add_buffer(1,
+                WShaderStages::FRAGMENT,
+                BufferBindingType::Storage { read_only: true });
         });
         let ssbo_layout_built = ssbo_layout.build(&render_instance.data.read().unwrap());
 
         // Create the bind group
         let render_instance = render_instance.data.read().unwrap();
         let bind_group = BindGroup::build("pbr-ssbo", &render_instance, &ssbo_layout_built, &vec![
-            BindGroup::buffer(0, &buffer.buffer)
+            BindGroup::buffer(0, &buffer.buffer),
+            BindGroup::buffer(1, &materials_buffer.buffer)
         ]);
         ssbo.bind_group_layout = Some(ssbo_layout);
         ssbo.bind_group = Some(bind_group);
@@ -54,13 +100,19 @@ impl Plugin for PbrSsboPlugin {
     fn finish(&self, app: &mut bevy::app::App) {
         let buffer: Handle<Buffer> = app.world_mut().add_asset(Buffer {
             label: "pbr-ssbo-cpu".to_string(),
-            size: std::mem::size_of::<TransformUniform>() * MAX_ENTITY_COUNT,
+            size: std::mem::size_of::<PbrSsboElement>() * MAX_ENTITY_COUNT,
             usage: BufferUsage::COPY_SRC | BufferUsage::MAP_WRITE,
             content: None,
         });
         let buffer_gpu: Handle<Buffer> = app.world_mut().add_asset(Buffer {
             label: "pbr-ssbo-gpu".to_string(),
-            size: std::mem::size_of::<TransformUniform>() * MAX_ENTITY_COUNT,
+            size: std::mem::size_of::<PbrSsboElement>() * MAX_ENTITY_COUNT,
+            usage: BufferUsage::STORAGE | BufferUsage::COPY_DST,
+            content: None,
+        });
+        let materials_buffer: Handle<Buffer> = app.world_mut().add_asset(Buffer {
+            label: "pbr-ssbo-materials".to_string(),
+            size: std::mem::size_of::<PbrMaterialUniform>() * MAX_MATERIAL_COUNT,
             usage: BufferUsage::STORAGE | BufferUsage::COPY_DST,
             content: None,
         });
@@ -69,6 +121,7 @@ impl Plugin for PbrSsboPlugin {
             .world_mut().insert_resource(PbrSsbo {
                 buffer,
                 buffer_gpu,
+                materials_buffer,
                 bind_group_layout: None,
                 bind_group: None
             });
@@ -0,0 +1,164 @@
+use bevy::prelude::*;
+use wde_wgpu::{bind_group::{BindGroup, BindGroupLayout, BindGroupLayoutBuilder, WStorageTextureAccess, WgpuBindGroup}, buffer::{BufferBindingType, BufferUsage, WBuffer}, instance::WRenderInstance, render_pipeline::WShaderStages, texture::{WTexture, WTextureFormat, WTextureUsages}};
+
+use crate::{assets::{GpuBuffer, GpuTexture, RenderAssets, Texture}, features::CameraFeatureBuffer, passes::{depth::DepthTexture, hdr::HdrTexture}};
+
+use super::{PbrBrdfLut, PbrDeferredTextures};
+
+/// The format of the reflections texture: the reflected light, weighted by the fresnel of the surface.
+pub const SSR_FORMAT: WTextureFormat = WTextureFormat::Rgba16Float;
+/// The number of steps of the binary search refining the intersection of a ray.
+pub const SSR_BINARY_SEARCH_STEPS: u32 = 8;
+
+/// The settings of the screen-space reflections, added to the specular light of the lighting pass.
+#[derive(Resource, Debug, Clone, Copy, PartialEq)]
+pub struct SsrSettings {
+    /// The maximum number of steps along each reflected ray, 0 to disable the reflections (default: 64).
+    pub max_iterations: u32,
+    /// The world space length of a step along the reflected rays (default: 0.25).
+    /// It is also the thickness given to the surfaces of the depth buffer.
+    pub ray_stride: f32,
+    /// The world space distance over which the reflections fade out along the rays (default: 20.0).
+    pub fade_distance: f32,
+}
+impl Default for SsrSettings {
+    fn default() -> Self {
+        Self {
+            max_iterations: 64,
+            ray_stride: 0.25,
+            fade_distance: 20.0
+        }
+    }
+}
+
+/// The reflections uniform, read by the ray tracing.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct PbrSsrUniform {
+    /// x: the maximum number of steps, y: the stride, z: the fade distance, w: the number of binary search steps.
+    pub params: [f32; 4]
+}
+
+/// The reflections texture and the bind groups of the reflections, recreated with the screen textures.
+#[derive(Resource)]
+pub struct PbrSsrLayout {
+    /// The buffer of the reflections uniform.
+    pub uniform_buffer: WBuffer,
+    /// The layout of the ray tracing: the depth, the hdr color, the gbuffer, the BRDF lookup table,
+    /// the camera, the uniform and the reflections texture.
+    pub trace_layout: BindGroupLayout,
+    /// The layout of the composite: the reflections texture.
+    pub composite_layout: BindGroupLayout,
+
+    /// The reflected light of each pixel.
+    pub reflections: Option<WTexture>,
+    pub trace_bind_group: Option<WgpuBindGroup>,
+    pub composite_bind_group: Option<WgpuBindGroup>,
+
+    /// The depth, hdr and normal textures the bind groups were created for.
+    source: Option<[AssetId<Texture>; 3]>,
+}
+impl FromWorld for PbrSsrLayout {
+    fn from_world(world: &mut World) -> Self {
+        let render_instance = world.get_resource::<WRenderInstance<'static>>().unwrap();
+        let render_instance = render_instance.data.read().unwrap();
+
+        let uniform_buffer = WBuffer::new(&render_instance, "pbr-ssr",
+            std::mem::size_of::<PbrSsrUniform>(), BufferUsage::UNIFORM | BufferUsage::COPY_DST, None);
+        let trace_layout = BindGroupLayout::new("pbr-ssr-trace", |builder: &mut BindGroupLayoutBuilder| {
+            builder.add_depth_texture_view(0, WShaderStages::COMPUTE);
+            builder.add_texture_view(   1, WShaderStages::COMPUTE);
+            builder.add_texture_view(   2, WShaderStages::COMPUTE);
+            builder.add_texture_view(   3, WShaderStages::COMPUTE);
+            builder.add_texture_view(   4, WShaderStages::COMPUTE);
+            builder.add_texture_view(   5, WShaderStages::COMPUTE);
+            builder.add_texture_sampler(6, WShaderStages::COMPUTE);
+            builder.add_buffer(7, WShaderStages::COMPUTE, BufferBindingType::Uniform);
+            builder.add_buffer(8, WShaderStages::COMPUTE, BufferBindingType::Uniform);
+            builder.add_storage_texture(9, WShaderStages::COMPUTE, WStorageTextureAccess::WriteOnly, SSR_FORMAT);
+        });
+        let composite_layout = BindGroupLayout::new("pbr-ssr-composite", |builder: &mut BindGroupLayoutBuilder| {
+            builder.add_texture_view(0, WShaderStages::FRAGMENT);
+        });
+
+        PbrSsrLayout {
+            uniform_buffer,
+            trace_layout,
+            composite_layout,
+            reflections: None,
+            trace_bind_group: None,
+            composite_bind_group: None,
+            source: None
+        }
+    }
+}
+impl PbrSsrLayout {
+    /// Write the uniform, and recreate the reflections texture and the bind groups when the screen textures change.
+    pub fn prepare(
+        mut ssr_layout: ResMut<PbrSsrLayout>, settings: Res<SsrSettings>,
+        (depth_texture, hdr_texture, deferred_textures, brdf_lut): (
+            Res<DepthTexture>, Option<Res<HdrTexture>>, Res<PbrDeferredTextures>, Option<Res<PbrBrdfLut>>
+        ),
+        camera_buffer: Res<CameraFeatureBuffer>,
+        (textures, buffers): (Res<RenderAssets<GpuTexture>>, Res<RenderAssets<GpuBuffer>>),
+        render_instance: Res<WRenderInstance<'static>>
+    ) {
+        let ssr_layout = &mut *ssr_layout;
+        let render_instance = render_instance.data.read().unwrap();
+
+        // Update the uniform
+        ssr_layout.uniform_buffer.write(&render_instance, bytemuck::cast_slice(&[PbrSsrUniform {
+            params: [
+                settings.max_iterations as f32, settings.ray_stride.max(0.001),
+                settings.fade_distance.max(0.001), SSR_BINARY_SEARCH_STEPS as f32
+            ]
+        }]), 0);
+
+        // Check if the bind groups are up to date
+        let (hdr_texture, brdf_lut) = match (hdr_texture, brdf_lut) {
+            (Some(hdr_texture), Some(brdf_lut)) => (hdr_texture, brdf_lut),
+            _ => return
+        };
+        let source = [depth_texture.texture.id(), hdr_texture.texture.id(), deferred_textures.normal.id()];
+        if ssr_layout.source == Some(source) {
+            return;
+        }
+        let (depth, hdr, albedo, normal, emissive, brdf_lut, camera) = match (
+            textures.get(&depth_texture.texture), textures.get(&hdr_texture.texture),
+            textures.get(&deferred_textures.albedo), textures.get(&deferred_textures.normal),
+            textures.get(&deferred_textures.emissive), textures.get(&brdf_lut.texture),
+            buffers.get(&camera_buffer.buffer)
+        ) {
+            (Some(depth), Some(hdr), Some(albedo), Some(normal), Some(emissive), Some(brdf_lut), Some(camera))
+                => (depth, hdr, albedo, normal, emissive, brdf_lut, camera),
+            _ => return
+        };
+
+        // Create the reflections texture
+        let reflections = WTexture::new(&render_instance, "pbr-ssr", depth.texture.size, SSR_FORMAT,
+            WTextureUsages::TEXTURE_BINDING | WTextureUsages::STORAGE_BINDING, Some(1));
+
+        // Create the bind groups
+        let depth_view = depth.texture.view_depth();
+        let trace_layout = ssr_layout.trace_layout.build(&render_instance);
+        ssr_layout.trace_bind_group = Some(BindGroup::build("pbr-ssr-trace", &render_instance, &trace_layout, &vec![
+            BindGroup::view(0, &depth_view),
+            BindGroup::texture_view(   1, &hdr.texture),
+            BindGroup::texture_view(   2, &albedo.texture),
+            BindGroup::texture_view(   3, &normal.texture),
+            BindGroup::texture_view(   4, &emissive.texture),
+            BindGroup::texture_view(   5, &brdf_lut.texture),
+            BindGroup::texture_sampler(6, &brdf_lut.texture),
+            BindGroup::buffer(7, &camera.buffer),
+            BindGroup::buffer(8, &ssr_layout.uniform_buffer),
+            BindGroup::storage_texture(9, &reflections.view)
+        ]));
+        let composite_layout = ssr_layout.composite_layout.build(&render_instance);
+        ssr_layout.composite_bind_group = Some(BindGroup::build("pbr-ssr-composite", &render_instance, &composite_layout, &vec![
+            BindGroup::view(0, &reflections.view)
+        ]));
+
+        ssr_layout.reflections = Some(reflections);
+        ssr_layout.source = Some(source);
+    }
+}
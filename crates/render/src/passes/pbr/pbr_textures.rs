@@ -1,6 +1,7 @@
 use bevy::prelude::*;
+use super::{PbrBrdfLut, PbrPointShadowMaps, PbrShadowMaps, PbrSpotShadowMaps, PbrSsaoTextures};
 use crate::{assets::{GpuTexture, RenderAssets, Texture}, core::{extract_macros::ExtractWorld, window::SurfaceResized}};
-use wde_wgpu::{bind_group::{BindGroup, BindGroupLayout, BindGroupLayoutBuilder, WgpuBindGroup}, instance::WRenderInstance, render_pipeline::WShaderStages, texture::{WTextureFormat, WTextureUsages}};
+use wde_wgpu::{bind_group::{BindGroup, BindGroupLayout, BindGroupLayoutBuilder, WgpuBindGroup}, buffer::BufferBindingType, instance::WRenderInstance, render_pipeline::WShaderStages, texture::{WSamplerDescriptor, WTextureFormat, WTextureUsages}};
 
 #[derive(Resource, Default)]
 pub struct PbrDeferredTexturesLayoutRegenerate(pub bool);
@@ -14,7 +15,11 @@ impl PbrDeferredTexturesLayout {
     /// Build the bind group for the deferred renderer.
     pub fn build_bind_group(
         textures: Res<RenderAssets<GpuTexture>>, render_instance: Res<WRenderInstance<'static>>,
-        mut textures_layout: ResMut<PbrDeferredTexturesLayout>, deferred_textures: Res<PbrDeferredTextures>
+        mut textures_layout: ResMut<PbrDeferredTexturesLayout>, deferred_textures: Res<PbrDeferredTextures>,
+        brdf_lut: Option<Res<PbrBrdfLut>>, ssao_textures: Option<Res<PbrSsaoTextures>>,
+        (shadow_maps, point_shadow_maps, spot_shadow_maps): (
+            Res<PbrShadowMaps>, Option<Res<PbrPointShadowMaps>>, Option<Res<PbrSpotShadowMaps>>
+        )
     ) {
         // Check if the bind group is already created
         if textures_layout.deferred_bind_group.is_some() & textures_layout.deferred_layout.is_some() {
@@ -22,12 +27,18 @@ impl PbrDeferredTexturesLayout {
         }
 
         // Get the textures
-        let (albedo, normal, material) = match (
+        let (albedo, normal, material, emissive, brdf_lut, ssao, point_shadow_maps, spot_shadow_maps) = match (
             textures.get(&deferred_textures.albedo),
-            textures.get(&deferred_textures.normal), textures.get(&deferred_textures.material)
+            textures.get(&deferred_textures.normal), textures.get(&deferred_textures.material),
+            textures.get(&deferred_textures.emissive),
+            brdf_lut.and_then(|lut| textures.get(&lut.texture)),
+            ssao_textures.and_then(|ssao_textures| textures.get(&ssao_textures.blurred)),
+            point_shadow_maps, spot_shadow_maps
         ) {
-            (Some(albedo), Some(normal), Some(material)) =>
-                (albedo, normal, material),
+            (
+                Some(albedo), Some(normal), Some(material), Some(emissive), Some(brdf_lut), Some(ssao),
+                Some(point_shadow_maps), Some(spot_shadow_maps)
+            ) => (albedo, normal, material, emissive, brdf_lut, ssao, point_shadow_maps, spot_shadow_maps),
             _ => return
         };
 
@@ -39,6 +50,19 @@ impl PbrDeferredTexturesLayout {
             builder.add_texture_sampler(3, WShaderStages::FRAGMENT);
             builder.add_texture_view(   4, WShaderStages::FRAGMENT);
             builder.add_texture_sampler(5, WShaderStages::FRAGMENT);
+            builder.add_texture_view(   6, WShaderStages::FRAGMENT);
+            builder.add_texture_sampler(7, WShaderStages::FRAGMENT);
+            builder.add_texture_view(   8, WShaderStages::FRAGMENT);
+            builder.add_texture_sampler(9, WShaderStages::FRAGMENT);
+            builder.add_buffer(10, WShaderStages::FRAGMENT, BufferBindingType::Uniform);
+            builder.add_depth_texture_array_view(11, WShaderStages::FRAGMENT);
+            builder.add_sampler(12, WShaderStages::FRAGMENT, &PbrShadowMaps::SAMPLER);
+            builder.add_buffer(13, WShaderStages::FRAGMENT, BufferBindingType::Storage { read_only: true });
+            builder.add_depth_texture_cube_array_view(14, WShaderStages::FRAGMENT);
+            builder.add_buffer(15, WShaderStages::FRAGMENT, BufferBindingType::Storage { read_only: true });
+            builder.add_depth_texture_array_view(16, WShaderStages::FRAGMENT);
+            builder.add_texture_view(   17, WShaderStages::FRAGMENT);
+            builder.add_texture_sampler(18, WShaderStages::FRAGMENT);
         });
 
         // Build the layout
@@ -52,7 +76,20 @@ impl PbrDeferredTexturesLayout {
             BindGroup::texture_view(   2, &normal.texture),
             BindGroup::texture_sampler(3, &normal.texture),
             BindGroup::texture_view(   4, &material.texture),
-            BindGroup::texture_sampler(5, &material.texture)
+            BindGroup::texture_sampler(5, &material.texture),
+            BindGroup::texture_view(   6, &brdf_lut.texture),
+            BindGroup::texture_sampler(7, &brdf_lut.texture),
+            BindGroup::texture_view(   8, &emissive.texture),
+            BindGroup::texture_sampler(9, &emissive.texture),
+            BindGroup::buffer(10, &shadow_maps.cascades_buffer),
+            BindGroup::view(11, &shadow_maps.texture.view),
+            BindGroup::sampler(12, &shadow_maps.sampler),
+            BindGroup::buffer(13, &point_shadow_maps.elements_buffer),
+            BindGroup::view(14, &point_shadow_maps.texture.view),
+            BindGroup::buffer(15, &spot_shadow_maps.elements_buffer),
+            BindGroup::view(16, &spot_shadow_maps.array_view),
+            BindGroup::texture_view(   17, &ssao.texture),
+            BindGroup::texture_sampler(18, &ssao.texture)
         ]);
 
         // Insert the resources
@@ -61,16 +98,34 @@ impl PbrDeferredTexturesLayout {
     }
 }
 
+/// Quality settings of the gbuffer textures.
+/// Changes are applied the next time the textures are recreated (on resize).
+#[derive(Resource, Clone)]
+pub struct GBufferQuality {
+    /// The format of the normals texture (default: `Rgba16Float`).
+    /// The normals are stored unencoded in [-1, 1], so the format must store signed values.
+    pub normals_format: WTextureFormat,
+}
+impl Default for GBufferQuality {
+    fn default() -> Self {
+        Self {
+            normals_format: WTextureFormat::Rgba16Float,
+        }
+    }
+}
+
 #[derive(Resource)]
 pub struct PbrDeferredTextures {
     pub albedo: Handle<Texture>,
     pub normal: Handle<Texture>,
     pub material: Handle<Texture>,
+    /// The emitted color, stored in a floating point texture to keep the values above 1.0 for the bloom.
+    pub emissive: Handle<Texture>,
     pub resized: bool
 }
 impl PbrDeferredTextures {
     /// Create the textures for the deferred renderer.
-    pub fn create_textures(mut commands: Commands, assets_server: Res<AssetServer>, window: Query<&Window>) {
+    pub fn create_textures(mut commands: Commands, assets_server: Res<AssetServer>, window: Query<&Window>, quality: Res<GBufferQuality>) {
         let resolution = &window.single().resolution;
 
         // Create the albedo texture
@@ -79,6 +134,7 @@ impl PbrDeferredTextures {
             size: (resolution.physical_width(), resolution.physical_height()),
             format: WTextureFormat::Rgba8UnormSrgb,
             usages: WTextureUsages::RENDER_ATTACHMENT | WTextureUsages::TEXTURE_BINDING,
+            sampler: WSamplerDescriptor::NEAREST_CLAMP,
             ..Default::default()
         });
 
@@ -86,8 +142,9 @@ impl PbrDeferredTextures {
         let normal = assets_server.add(Texture {
             label: "pbr-normal".to_string(),
             size: (resolution.physical_width(), resolution.physical_height()),
-            format: WTextureFormat::Rgba16Float,
+            format: quality.normals_format,
             usages: WTextureUsages::RENDER_ATTACHMENT | WTextureUsages::TEXTURE_BINDING,
+            sampler: WSamplerDescriptor::NEAREST_CLAMP,
             ..Default::default()
         });
 
@@ -97,19 +154,30 @@ impl PbrDeferredTextures {
             size: (resolution.physical_width(), resolution.physical_height()),
             format: WTextureFormat::Rgba8Unorm,
             usages: WTextureUsages::RENDER_ATTACHMENT | WTextureUsages::TEXTURE_BINDING,
+            sampler: WSamplerDescriptor::NEAREST_CLAMP,
+            ..Default::default()
+        });
+
+        // Create the emissive texture
+        let emissive = assets_server.add(Texture {
+            label: "pbr-emissive".to_string(),
+            size: (resolution.physical_width(), resolution.physical_height()),
+            format: WTextureFormat::Rgba16Float,
+            usages: WTextureUsages::RENDER_ATTACHMENT | WTextureUsages::TEXTURE_BINDING,
+            sampler: WSamplerDescriptor::NEAREST_CLAMP,
             ..Default::default()
         });
 
         // Insert the resources
         commands.insert_resource(PbrDeferredTextures {
-            albedo, normal, material, resized: false
+            albedo, normal, material, emissive, resized: false
         });
     }
 
     /// Resize the textures for the deferred renderer.
     pub fn resize_textures(
         mut window_resized_events: EventReader<SurfaceResized>,
-        server: Res<AssetServer>, mut deferred_textures: ResMut<PbrDeferredTextures>
+        server: Res<AssetServer>, mut deferred_textures: ResMut<PbrDeferredTextures>, quality: Res<GBufferQuality>
     ) {
         deferred_textures.resized = false;
         for event in window_resized_events.read() {
@@ -119,6 +187,7 @@ impl PbrDeferredTextures {
                 size: (event.width, event.height),
                 format: WTextureFormat::Rgba8UnormSrgb,
                 usages: WTextureUsages::RENDER_ATTACHMENT | WTextureUsages::TEXTURE_BINDING,
+                sampler: WSamplerDescriptor::NEAREST_CLAMP,
                 ..Default::default()
             });
 
@@ -126,8 +195,9 @@ impl PbrDeferredTextures {
             let normal = server.add(Texture {
                 label: "pbr-normal".to_string(),
                 size: (event.width, event.height),
-                format: WTextureFormat::Rgba16Float,
+                format: quality.normals_format,
                 usages: WTextureUsages::RENDER_ATTACHMENT | WTextureUsages::TEXTURE_BINDING,
+                sampler: WSamplerDescriptor::NEAREST_CLAMP,
                 ..Default::default()
             });
 
@@ -137,6 +207,17 @@ impl PbrDeferredTextures {
                 size: (event.width, event.height),
                 format: WTextureFormat::Rgba8Unorm,
                 usages: WTextureUsages::RENDER_ATTACHMENT | WTextureUsages::TEXTURE_BINDING,
+                sampler: WSamplerDescriptor::NEAREST_CLAMP,
+                ..Default::default()
+            });
+
+            // Recreate the emissive texture
+            let emissive = server.add(Texture {
+                label: "pbr-emissive".to_string(),
+                size: (event.width, event.height),
+                format: WTextureFormat::Rgba16Float,
+                usages: WTextureUsages::RENDER_ATTACHMENT | WTextureUsages::TEXTURE_BINDING,
+                sampler: WSamplerDescriptor::NEAREST_CLAMP,
                 ..Default::default()
             });
 
@@ -144,6 +225,7 @@ impl PbrDeferredTextures {
             deferred_textures.albedo = albedo;
             deferred_textures.normal = normal;
             deferred_textures.material = material;
+            deferred_textures.emissive = emissive;
             deferred_textures.resized = true;
         }
     }
@@ -159,6 +241,7 @@ impl PbrDeferredTextures {
             albedo: textures.albedo.clone(),
             normal: textures.normal.clone(),
             material: textures.material.clone(),
+            emissive: textures.emissive.clone(),
             resized: false
         });
     }
@@ -0,0 +1,121 @@
+use bevy::prelude::*;
+use wde_wgpu::{bind_group::{BindGroup, BindGroupLayout, BindGroupLayoutBuilder, WgpuBindGroup}, instance::WRenderInstance, render_pipeline::WShaderStages, texture::{WSamplerDescriptor, WTextureFormat, WTextureUsages}};
+
+use crate::{assets::{GpuTexture, RenderAssets, Texture}, core::{extract_macros::ExtractWorld, window::SurfaceResized}};
+
+/// The format of the accumulated premultiplied colors of the transparent fragments, weighted by their depth.
+pub const WBOIT_ACCUM_FORMAT: WTextureFormat = WTextureFormat::Rgba16Float;
+/// The format of the revealage, the product of the transparency of the fragments.
+pub const WBOIT_REVEALAGE_FORMAT: WTextureFormat = WTextureFormat::R8Unorm;
+
+/// The textures of the weighted blended order-independent transparency.
+/// The transparent fragments are accumulated into `accum` and `revealage` in any order,
+/// then resolved over the hdr color.
+#[derive(Resource)]
+pub struct PbrWboitTextures {
+    /// The sum of the weighted premultiplied colors (rgb) and of the weighted opacities (a).
+    pub accum: Handle<Texture>,
+    /// The product of the transparencies of the fragments, 1 where there is no transparent fragment.
+    pub revealage: Handle<Texture>,
+    pub resized: bool
+}
+impl PbrWboitTextures {
+    /// Create an accumulation texture.
+    fn texture(label: &str, size: (u32, u32), format: WTextureFormat) -> Texture {
+        Texture {
+            label: label.to_string(),
+            size,
+            format,
+            usages: WTextureUsages::RENDER_ATTACHMENT | WTextureUsages::TEXTURE_BINDING,
+            sampler: WSamplerDescriptor::NEAREST_CLAMP,
+            ..Default::default()
+        }
+    }
+
+    pub fn create_textures(mut commands: Commands, assets_server: Res<AssetServer>, window: Query<&Window>) {
+        let resolution = &window.single().resolution;
+        let size = (resolution.physical_width(), resolution.physical_height());
+        commands.insert_resource(PbrWboitTextures {
+            accum: assets_server.add(Self::texture("pbr-wboit-accum", size, WBOIT_ACCUM_FORMAT)),
+            revealage: assets_server.add(Self::texture("pbr-wboit-revealage", size, WBOIT_REVEALAGE_FORMAT)),
+            resized: false
+        });
+    }
+
+    /// Recreate the accumulation textures when the surface is resized.
+    pub fn resize_textures(
+        mut window_resized_events: EventReader<SurfaceResized>,
+        server: Res<AssetServer>, mut textures: ResMut<PbrWboitTextures>
+    ) {
+        textures.resized = false;
+        for event in window_resized_events.read() {
+            let size = (event.width, event.height);
+            textures.accum = server.add(Self::texture("pbr-wboit-accum", size, WBOIT_ACCUM_FORMAT));
+            textures.revealage = server.add(Self::texture("pbr-wboit-revealage", size, WBOIT_REVEALAGE_FORMAT));
+            textures.resized = true;
+        }
+    }
+
+    pub fn extract_textures(
+        mut commands: Commands, textures: ExtractWorld<Res<PbrWboitTextures>>, mut wboit_layout: ResMut<PbrWboitLayout>
+    ) {
+        if textures.resized {
+            wboit_layout.resolve_bind_group = None;
+        }
+
+        commands.insert_resource(PbrWboitTextures {
+            accum: textures.accum.clone(),
+            revealage: textures.revealage.clone(),
+            resized: false
+        });
+    }
+}
+
+/// The bind group of the resolve of the order-independent transparency.
+#[derive(Resource)]
+pub struct PbrWboitLayout {
+    /// The layout of the inputs of the resolve: the accumulated colors and the revealage.
+    pub resolve_layout: BindGroupLayout,
+    pub resolve_bind_group: Option<WgpuBindGroup>
+}
+impl Default for PbrWboitLayout {
+    fn default() -> Self {
+        let resolve_layout = BindGroupLayout::new("pbr-wboit-resolve", |builder: &mut BindGroupLayoutBuilder| {
+            builder.add_texture_view(0, WShaderStages::FRAGMENT);
+            builder.add_texture_view(1, WShaderStages::FRAGMENT);
+        });
+
+        PbrWboitLayout {
+            resolve_layout,
+            resolve_bind_group: None
+        }
+    }
+}
+impl PbrWboitLayout {
+    /// Build the bind group of the resolve once the textures are ready.
+    pub fn build_bind_group(
+        mut wboit_layout: ResMut<PbrWboitLayout>, wboit_textures: Option<Res<PbrWboitTextures>>,
+        textures: Res<RenderAssets<GpuTexture>>, render_instance: Res<WRenderInstance<'static>>
+    ) {
+        // Check if the bind group is already created
+        if wboit_layout.resolve_bind_group.is_some() {
+            return;
+        }
+
+        // Get the textures
+        let (accum, revealage) = match wboit_textures.map(|wboit_textures| (
+            textures.get(&wboit_textures.accum), textures.get(&wboit_textures.revealage)
+        )) {
+            Some((Some(accum), Some(revealage))) => (accum, revealage),
+            _ => return
+        };
+
+        // Create the bind group
+        let render_instance = render_instance.data.read().unwrap();
+        let resolve_layout = wboit_layout.resolve_layout.build(&render_instance);
+        wboit_layout.resolve_bind_group = Some(BindGroup::build("pbr-wboit-resolve", &render_instance, &resolve_layout, &vec![
+            BindGroup::texture_view(0, &accum.texture),
+            BindGroup::texture_view(1, &revealage.texture)
+        ]));
+    }
+}
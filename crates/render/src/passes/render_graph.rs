@@ -1,4 +1,6 @@
-use bevy::{prelude::*, utils::HashMap};
+use std::any::TypeId;
+
+use bevy::{log::Level, prelude::*, utils::{tracing::span, HashMap, HashSet}};
 
 /** Defines a render pass. */
 pub trait RenderPass: Send + Sync {
@@ -21,6 +23,12 @@ pub type PassIndex = u32;
 pub struct RenderGraph {
     passes: HashMap<PassIndex, Box<dyn RenderPass>>,
     sorted_passes: Vec<PassIndex>,
+    /// The index of the pass of each type.
+    pass_types: HashMap<TypeId, PassIndex>,
+    /// The type name of each pass, used to name the tracing spans.
+    pass_names: HashMap<PassIndex, &'static str>,
+    /// The passes whose rendering is skipped.
+    disabled_passes: HashSet<PassIndex>,
 }
 impl RenderGraph {
     /** 
@@ -39,20 +47,78 @@ impl RenderGraph {
 
         // Add the pass
         self.passes.insert(id, Box::new(P::default()));
+        self.pass_types.insert(TypeId::of::<P>(), id);
+        self.pass_names.insert(id, std::any::type_name::<P>());
 
         // Sort the passes
         self.sorted_passes = self.passes.keys().copied().collect();
         self.sorted_passes.sort();
     }
 
+    /**
+     * Removes a render pass from the render graph.
+     * 
+     * # Returns
+     * - `bool`: True if the pass was found and removed.
+     */
+    pub fn remove_pass<P: RenderPass + 'static>(&mut self) -> bool {
+        // Find the pass
+        let id = match self.pass_types.remove(&TypeId::of::<P>()) {
+            Some(id) => id,
+            None => {
+                warn!("The pass {} is not in the render graph.", std::any::type_name::<P>());
+                return false;
+            }
+        };
+        info!("Removing the render pass with id {} from the render graph.", id);
+
+        // Remove the pass
+        self.passes.remove(&id);
+        self.pass_names.remove(&id);
+        self.disabled_passes.remove(&id);
+        self.sorted_passes.retain(|pass_id| *pass_id != id);
+        true
+    }
+
+    /**
+     * Enables or disables a render pass without removing it from the render graph.
+     * A disabled pass is still extracted, so that the passes depending on its resources keep working, but it is not rendered.
+     * 
+     * # Parameters
+     * - `enabled: bool`: Whether the pass is rendered.
+     */
+    pub fn set_pass_enabled<P: RenderPass + 'static>(&mut self, enabled: bool) {
+        let id = match self.pass_types.get(&TypeId::of::<P>()) {
+            Some(id) => *id,
+            None => {
+                warn!("The pass {} is not in the render graph.", std::any::type_name::<P>());
+                return;
+            }
+        };
+        debug!("Setting the render pass with id {} enabled: {}.", id, enabled);
+
+        if enabled {
+            self.disabled_passes.remove(&id);
+        } else {
+            self.disabled_passes.insert(id);
+        }
+    }
+
+    /** Returns true if the render pass is in the render graph and enabled. */
+    pub fn is_pass_enabled<P: RenderPass + 'static>(&self) -> bool {
+        self.pass_types.get(&TypeId::of::<P>())
+            .is_some_and(|id| !self.disabled_passes.contains(id))
+    }
+
     /**
      * Extracts the render passes from the main world to the render world.
      * This method is automatically called by the render system.
      */
     pub(crate) fn extract(&mut self, main_world: &mut World, render_world: &mut World) {
         // Extract the passes
-        for pass in self.sorted_passes.iter().map(|id| self.passes.get(id).unwrap()) {
-            pass.extract(main_world, render_world);
+        for id in self.sorted_passes.iter() {
+            let _pass_span = span!(Level::INFO, "extract_pass", name = self.pass_names[id], id).entered();
+            self.passes.get(id).unwrap().extract(main_world, render_world);
         }
     }
 
@@ -65,8 +131,9 @@ impl RenderGraph {
 
         // Run the update methods for each pass
         render_world.resource_scope(|render_world, graph: Mut<RenderGraph>| {
-            for pass in graph.sorted_passes.iter().map(|id| graph.passes.get(id).unwrap()) {
-                pass.render(render_world);
+            for id in graph.sorted_passes.iter().filter(|id| !graph.disabled_passes.contains(*id)) {
+                let _pass_span = span!(Level::INFO, "render_pass", name = graph.pass_names[id], id).entered();
+                graph.passes.get(id).unwrap().render(render_world);
             }
         });
     }
@@ -0,0 +1,40 @@
+use bevy::prelude::*;
+
+mod skybox_settings;
+mod skybox_pipeline;
+mod skybox_renderpass;
+
+pub use skybox_settings::*;
+pub use skybox_pipeline::*;
+pub use skybox_renderpass::*;
+
+use crate::{assets::RenderAssetsPlugin, core::{Render, RenderApp, RenderSet}};
+
+use super::render_graph::RenderGraph;
+
+/// Renders the background of the hdr color before the other passes, from the `Skybox` resource.
+/// Without `Skybox`, the background is cleared to a dark gray.
+pub(crate) struct SkyboxPlugin;
+impl Plugin for SkyboxPlugin {
+    fn build(&self, app: &mut App) {
+        // Add the skybox pipeline
+        app
+            .init_asset::<SkyboxPipelineAsset>()
+            .add_plugins(RenderAssetsPlugin::<GpuSkyboxPipeline>::default());
+
+        // Add the skybox render pass
+        let render_app = app.get_sub_app_mut(RenderApp).unwrap();
+        render_app
+            .init_resource::<SkyboxLayout>()
+            .add_systems(Render, SkyboxLayout::prepare.in_set(RenderSet::BindGroups));
+        render_app.world_mut().get_resource_mut::<RenderGraph>().unwrap()
+            .add_pass::<SkyboxRenderPass>(0);
+    }
+
+    fn finish(&self, app: &mut App) {
+        // Create the skybox pipeline
+        let pipeline: Handle<SkyboxPipelineAsset> = app.world_mut()
+            .get_resource::<AssetServer>().unwrap().add(SkyboxPipelineAsset);
+        app.get_sub_app_mut(RenderApp).unwrap().world_mut().spawn(SkyboxPipeline(pipeline));
+    }
+}
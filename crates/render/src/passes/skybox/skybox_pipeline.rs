@@ -0,0 +1,47 @@
+use bevy::{ecs::system::lifetimeless::{SRes, SResMut}, prelude::*};
+use wde_wgpu::render_pipeline::WDepthStencilDescriptor;
+use crate::{assets::{PrepareAssetError, RenderAsset}, features::CameraFeatureRender, passes::hdr::HDR_FORMAT, pipelines::{CachedPipelineIndex, PipelineManager, RenderPipelineDescriptor}};
+
+use super::SkyboxLayout;
+
+#[derive(Default, Asset, Clone, TypePath)]
+pub struct SkyboxPipelineAsset;
+#[derive(Component)]
+pub struct SkyboxPipeline(pub Handle<SkyboxPipelineAsset>);
+pub struct GpuSkyboxPipeline {
+    /// The render pipeline drawing the sky into the hdr color.
+    pub cached_pipeline_index: CachedPipelineIndex
+}
+impl RenderAsset for GpuSkyboxPipeline {
+    type SourceAsset = SkyboxPipelineAsset;
+    type Param = (SRes<AssetServer>, SResMut<PipelineManager>, SRes<CameraFeatureRender>, SRes<SkyboxLayout>);
+
+    fn prepare_asset(
+            _asset: Self::SourceAsset,
+            (assets_server, pipeline_manager, camera_feature, skybox_layout): &mut bevy::ecs::system::SystemParamItem<Self::Param>
+        ) -> Result<Self, PrepareAssetError<Self::SourceAsset>> {
+        // Create the pipeline, drawing a full screen triangle at the far plane without vertex buffer
+        let cached_pipeline_index = pipeline_manager.create_render_pipeline(RenderPipelineDescriptor {
+            label: "skybox",
+            vert: Some(assets_server.load("skybox/skybox_vert.wgsl")),
+            frag: Some(assets_server.load("skybox/skybox_frag.wgsl")),
+            bind_group_layouts: vec![camera_feature.layout.clone(), skybox_layout.layout.clone()],
+            depth: WDepthStencilDescriptor {
+                enabled: false,
+                ..Default::default()
+            },
+            render_targets: Some(vec![HDR_FORMAT]),
+            cull_mode: None,
+            vertex_input: false,
+            ..Default::default()
+        });
+
+        Ok(GpuSkyboxPipeline {
+            cached_pipeline_index
+        })
+    }
+
+    fn label(&self) -> &str {
+        "skybox"
+    }
+}
@@ -0,0 +1,82 @@
+use bevy::prelude::*;
+use crate::{assets::{GpuTexture, RenderAssets}, core::pipeline_statistics::PipelineStatisticsQueries, features::CameraFeatureRender, passes::{hdr::HdrTexture, render_graph::RenderPass}, pipelines::{CachedPipelineStatus, PipelineManager}};
+use wde_wgpu::{command_buffer::{RenderPassBuilder, RenderPassColorAttachment, WCommandBuffer}, instance::WRenderInstance};
+
+use super::{GpuSkyboxPipeline, Skybox, SkyboxLayout};
+
+/// Clears the hdr color and draws the `Skybox` into it, before the other passes.
+/// The view direction of each pixel is reconstructed from the inverse view projection of the camera.
+/// The lighting pass then overwrites the sky where there is geometry.
+#[derive(Default)]
+pub struct SkyboxRenderPass;
+impl RenderPass for SkyboxRenderPass {
+    fn extract(&self, main_world: &mut World, render_world: &mut World) {
+        // Extract the skybox
+        match main_world.get_resource::<Skybox>() {
+            Some(skybox) => render_world.insert_resource(skybox.clone()),
+            None => { render_world.remove_resource::<Skybox>(); }
+        }
+    }
+
+    fn render(&self, render_world: &mut World) {
+        // Get the render instance and the hdr texture
+        let render_instance = render_world.get_resource::<WRenderInstance>().unwrap();
+        let render_instance = render_instance.data.read().unwrap();
+        let hdr = match render_world.get_resource::<HdrTexture>()
+            .and_then(|hdr_texture| render_world.get_resource::<RenderAssets<GpuTexture>>().unwrap().get(&hdr_texture.texture)) {
+            Some(hdr) => hdr,
+            None => return
+        };
+
+        // Check if the skybox can be drawn
+        let pipeline_manager = render_world.get_resource::<PipelineManager>().unwrap();
+        let pipeline = render_world.get_resource::<RenderAssets<GpuSkyboxPipeline>>()
+            .and_then(|pipelines| pipelines.iter().next())
+            .map(|(_, pipeline)| pipeline_manager.get_pipeline(pipeline.cached_pipeline_index));
+        let ready = match (
+            render_world.contains_resource::<Skybox>(),
+            pipeline,
+            &render_world.get_resource::<CameraFeatureRender>().unwrap().bind_group,
+            &render_world.get_resource::<SkyboxLayout>().unwrap().bind_group
+        ) {
+            (true, Some(CachedPipelineStatus::OkRender(pipeline)), Some(camera_bind_group), Some(skybox_bind_group))
+                => Some((pipeline, camera_bind_group, skybox_bind_group)),
+            _ => None
+        };
+
+        let mut command_buffer = WCommandBuffer::new(&render_instance, "skybox");
+        let timestamp = command_buffer.begin_timestamp("skybox");
+        {
+            // Clear the hdr color
+            let mut render_pass = command_buffer.create_render_pass("skybox", |builder: &mut RenderPassBuilder| {
+                builder.add_color_attachment(RenderPassColorAttachment {
+                    texture: Some(&hdr.texture.view),
+                    ..Default::default()
+                });
+            });
+
+            // Draw the sky
+            if let Some((pipeline, camera_bind_group, skybox_bind_group)) = ready {
+                let statistics = render_world.get_resource::<PipelineStatisticsQueries>();
+                let statistics_query = statistics.is_some_and(|statistics| statistics.begin(&mut render_pass, "skybox"));
+                if render_pass.set_pipeline(pipeline).is_ok() {
+                    render_pass.set_bind_group(0, camera_bind_group);
+                    render_pass.set_bind_group(1, skybox_bind_group);
+                    if let Err(e) = render_pass.draw(0..3, 0..1) {
+                        error!("Failed to draw: {:?}.", e);
+                    }
+                } else {
+                    error!("Failed to set pipeline.");
+                }
+                if let Some(statistics) = statistics {
+                    statistics.end(&mut render_pass, statistics_query);
+                }
+            }
+        }
+        command_buffer.end_timestamp(timestamp);
+
+        // Submit the command buffer
+        command_buffer.submit(&render_instance);
+        command_buffer.trace_timestamps(&render_instance);
+    }
+}
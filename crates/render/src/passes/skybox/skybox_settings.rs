@@ -0,0 +1,129 @@
+use bevy::prelude::*;
+use wde_wgpu::{bind_group::{BindGroup, BindGroupLayout, BindGroupLayoutBuilder, WgpuBindGroup}, buffer::{BufferBindingType, BufferUsage, WBuffer}, instance::WRenderInstance, render_pipeline::WShaderStages, texture::{WTexture, WTextureFormat, WTextureUsages}};
+
+use crate::assets::{GpuTexture, RenderAssets, Texture};
+
+/// A procedural sky, computed from the position of the sun with the Preetham analytic model.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AtmosphericScattering {
+    /// The direction from the ground toward the sun. The sky darkens when the sun goes below the horizon.
+    pub sun_direction: Vec3,
+    /// The haziness of the atmosphere, from 2 (clear sky) to 10 (hazy sky).
+    pub turbidity: f32
+}
+impl Default for AtmosphericScattering {
+    fn default() -> Self {
+        Self {
+            sun_direction: Vec3::new(0.3, 0.6, -0.4).normalize(),
+            turbidity: 2.5
+        }
+    }
+}
+
+/// The background of the scene, rendered where no geometry is drawn.
+/// Insert it as a resource to replace the dark gray background.
+#[derive(Resource, Debug, Clone)]
+pub enum Skybox {
+    /// An environment map in the equirectangular projection, twice as wide as high, sampled in the direction of each pixel.
+    /// The engine textures being 2D, the six faces of a cubemap are given as this single panorama.
+    Texture(Handle<Texture>),
+    /// A procedural sky.
+    Atmosphere(AtmosphericScattering)
+}
+
+/// The skybox uniform.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct SkyboxUniform {
+    /// xyz: the normalized sun direction, w: the turbidity.
+    pub sun_direction_turbidity: [f32; 4],
+    /// x: 0 for the texture, 1 for the procedural sky.
+    pub params: [f32; 4]
+}
+
+/// The layout and the bind group of the skybox, recreated when the texture of the skybox changes.
+#[derive(Resource)]
+pub struct SkyboxLayout {
+    /// The buffer of the skybox uniform.
+    pub uniform_buffer: WBuffer,
+    /// The layout of the skybox: the texture and its sampler, and the uniform.
+    pub layout: BindGroupLayout,
+    pub bind_group: Option<WgpuBindGroup>,
+
+    /// A texture bound in place of the environment map by the procedural sky.
+    placeholder: WTexture,
+    /// The texture the bind group was created for, `None` for the placeholder.
+    source: Option<Option<AssetId<Texture>>>
+}
+impl FromWorld for SkyboxLayout {
+    fn from_world(world: &mut World) -> Self {
+        let render_instance = world.get_resource::<WRenderInstance<'static>>().unwrap();
+        let render_instance = render_instance.data.read().unwrap();
+
+        let uniform_buffer = WBuffer::new(&render_instance, "skybox",
+            std::mem::size_of::<SkyboxUniform>(), BufferUsage::UNIFORM | BufferUsage::COPY_DST, None);
+        let layout = BindGroupLayout::new("skybox", |builder: &mut BindGroupLayoutBuilder| {
+            builder.add_texture_view(   0, WShaderStages::FRAGMENT);
+            builder.add_texture_sampler(1, WShaderStages::FRAGMENT);
+            builder.add_buffer(2, WShaderStages::FRAGMENT, BufferBindingType::Uniform);
+        });
+        let placeholder = WTexture::new(&render_instance, "skybox-placeholder", (1, 1),
+            WTextureFormat::Rgba8Unorm, WTextureUsages::TEXTURE_BINDING, Some(1));
+
+        SkyboxLayout {
+            uniform_buffer,
+            layout,
+            bind_group: None,
+            placeholder,
+            source: None
+        }
+    }
+}
+impl SkyboxLayout {
+    /// Write the uniform, and recreate the bind group when the texture of the skybox changes.
+    pub fn prepare(
+        mut skybox_layout: ResMut<SkyboxLayout>, skybox: Option<Res<Skybox>>,
+        textures: Res<RenderAssets<GpuTexture>>, render_instance: Res<WRenderInstance<'static>>
+    ) {
+        let skybox_layout = &mut *skybox_layout;
+        let skybox = match skybox {
+            Some(skybox) => skybox,
+            None => return
+        };
+        let render_instance = render_instance.data.read().unwrap();
+
+        // Update the uniform
+        let (texture, uniform) = match &*skybox {
+            Skybox::Texture(texture) => (Some(texture), SkyboxUniform::default()),
+            Skybox::Atmosphere(atmosphere) => (None, SkyboxUniform {
+                sun_direction_turbidity: atmosphere.sun_direction.normalize_or(Vec3::Y).extend(atmosphere.turbidity.clamp(2.0, 10.0)).to_array(),
+                params: [1.0, 0.0, 0.0, 0.0]
+            })
+        };
+        skybox_layout.uniform_buffer.write(&render_instance, bytemuck::cast_slice(&[uniform]), 0);
+
+        // Check if the bind group is up to date
+        let source = texture.map(|texture| texture.id());
+        if skybox_layout.source == Some(source) {
+            return;
+        }
+        let texture = match texture.map(|texture| textures.get(texture)) {
+            Some(Some(texture)) => &texture.texture,
+            Some(None) => {
+                // Wait for the texture to be loaded
+                skybox_layout.bind_group = None;
+                return;
+            },
+            None => &skybox_layout.placeholder
+        };
+
+        // Create the bind group
+        let layout = skybox_layout.layout.build(&render_instance);
+        skybox_layout.bind_group = Some(BindGroup::build("skybox", &render_instance, &layout, &vec![
+            BindGroup::texture_view(   0, texture),
+            BindGroup::texture_sampler(1, texture),
+            BindGroup::buffer(2, &skybox_layout.uniform_buffer)
+        ]));
+        skybox_layout.source = Some(source);
+    }
+}
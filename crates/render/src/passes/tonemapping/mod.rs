@@ -0,0 +1,45 @@
+use bevy::prelude::*;
+
+mod tonemapping_settings;
+mod tonemapping_pipeline;
+mod tonemapping_renderpass;
+
+pub use tonemapping_settings::*;
+pub use tonemapping_pipeline::*;
+pub use tonemapping_renderpass::*;
+
+use crate::{assets::RenderAssetsPlugin, core::{Render, RenderApp, RenderSet}};
+
+use super::render_graph::RenderGraph;
+
+/// Maps the hdr color to the displayable range and writes it to the swapchain, as the last post-processing step.
+/// The operator and the exposure can be configured with `ToneMappingSettings`.
+pub(crate) struct ToneMappingPlugin;
+impl Plugin for ToneMappingPlugin {
+    fn build(&self, app: &mut App) {
+        // Add the tone mapping settings
+        app
+            .init_resource::<ToneMappingSettings>();
+
+        // Add the tone mapping pipeline
+        app
+            .init_asset::<ToneMappingPipelineAsset>()
+            .add_plugins(RenderAssetsPlugin::<GpuToneMappingPipeline>::default());
+
+        // Add the tone mapping render pass
+        let render_app = app.get_sub_app_mut(RenderApp).unwrap();
+        render_app
+            .init_resource::<ToneMappingSettings>()
+            .init_resource::<ToneMappingLayout>()
+            .add_systems(Render, ToneMappingLayout::prepare.in_set(RenderSet::BindGroups));
+        render_app.world_mut().get_resource_mut::<RenderGraph>().unwrap()
+            .add_pass::<ToneMappingRenderPass>(13);
+    }
+
+    fn finish(&self, app: &mut App) {
+        // Create the tone mapping pipeline
+        let pipeline: Handle<ToneMappingPipelineAsset> = app.world_mut()
+            .get_resource::<AssetServer>().unwrap().add(ToneMappingPipelineAsset);
+        app.get_sub_app_mut(RenderApp).unwrap().world_mut().spawn(ToneMappingPipeline(pipeline));
+    }
+}
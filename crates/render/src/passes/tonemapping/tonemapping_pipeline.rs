@@ -0,0 +1,47 @@
+use bevy::{ecs::system::lifetimeless::{SRes, SResMut}, prelude::*};
+use wde_wgpu::render_pipeline::WDepthStencilDescriptor;
+use crate::{assets::{PrepareAssetError, RenderAsset}, pipelines::{CachedPipelineIndex, PipelineManager, RenderPipelineDescriptor}};
+
+use super::ToneMappingLayout;
+
+#[derive(Default, Asset, Clone, TypePath)]
+pub struct ToneMappingPipelineAsset;
+#[derive(Component)]
+pub struct ToneMappingPipeline(pub Handle<ToneMappingPipelineAsset>);
+pub struct GpuToneMappingPipeline {
+    /// The render pipeline tone mapping the hdr color into the swapchain.
+    pub cached_pipeline_index: CachedPipelineIndex
+}
+impl RenderAsset for GpuToneMappingPipeline {
+    type SourceAsset = ToneMappingPipelineAsset;
+    type Param = (SRes<AssetServer>, SResMut<PipelineManager>, SRes<ToneMappingLayout>);
+
+    fn prepare_asset(
+            _asset: Self::SourceAsset,
+            (assets_server, pipeline_manager, tonemapping_layout): &mut bevy::ecs::system::SystemParamItem<Self::Param>
+        ) -> Result<Self, PrepareAssetError<Self::SourceAsset>> {
+        // Create the pipeline, drawing a full screen triangle without vertex buffer
+        let cached_pipeline_index = pipeline_manager.create_render_pipeline(RenderPipelineDescriptor {
+            label: "tonemapping",
+            vert: Some(assets_server.load("tonemapping/tonemapping_vert.wgsl")),
+            frag: Some(assets_server.load("tonemapping/tonemapping_frag.wgsl")),
+            bind_group_layouts: vec![tonemapping_layout.layout.clone()],
+            depth: WDepthStencilDescriptor {
+                enabled: false,
+                ..Default::default()
+            },
+            render_targets: None,
+            cull_mode: None,
+            vertex_input: false,
+            ..Default::default()
+        });
+
+        Ok(GpuToneMappingPipeline {
+            cached_pipeline_index
+        })
+    }
+
+    fn label(&self) -> &str {
+        "tonemapping"
+    }
+}
@@ -0,0 +1,70 @@
+use bevy::prelude::*;
+use crate::{core::{pipeline_statistics::PipelineStatisticsQueries, SwapchainFrame}, assets::RenderAssets, passes::render_graph::RenderPass, pipelines::{CachedPipelineStatus, PipelineManager}};
+use wde_wgpu::{command_buffer::{RenderPassBuilder, RenderPassColorAttachment, WCommandBuffer}, instance::WRenderInstance};
+
+use super::{GpuToneMappingPipeline, ToneMappingLayout, ToneMappingSettings};
+
+/// Maps the hdr color to the displayable range with the operator of `ToneMappingSettings`, and writes it
+/// to the swapchain. The color is gamma corrected in the shader when the swapchain format is not sRGB.
+#[derive(Default)]
+pub struct ToneMappingRenderPass;
+impl RenderPass for ToneMappingRenderPass {
+    fn extract(&self, main_world: &mut World, render_world: &mut World) {
+        // Extract the settings
+        let settings = *main_world.get_resource::<ToneMappingSettings>().unwrap();
+        render_world.insert_resource(settings);
+    }
+
+    fn render(&self, render_world: &mut World) {
+        // Get the render instance and swapchain frame
+        let render_instance = render_world.get_resource::<WRenderInstance>().unwrap();
+        let render_instance = render_instance.data.read().unwrap();
+        let swapchain_frame = render_world.get_resource::<SwapchainFrame>().unwrap().data.as_ref().unwrap();
+
+        // Check if the pipeline and the bind group are ready
+        let tonemapping_layout = render_world.get_resource::<ToneMappingLayout>().unwrap();
+        let pipeline_manager = render_world.get_resource::<PipelineManager>().unwrap();
+        let tonemapping_pipeline = match render_world.get_resource::<RenderAssets<GpuToneMappingPipeline>>().unwrap().iter().next() {
+            Some((_, pipeline)) => pipeline,
+            None => return
+        };
+        let (pipeline, bind_group) = match (
+            pipeline_manager.get_pipeline(tonemapping_pipeline.cached_pipeline_index),
+            &tonemapping_layout.bind_group
+        ) {
+            (CachedPipelineStatus::OkRender(pipeline), Some(bind_group)) => (pipeline, bind_group),
+            _ => return
+        };
+
+        let mut command_buffer = WCommandBuffer::new(&render_instance, "tonemapping");
+        let timestamp = command_buffer.begin_timestamp("tonemapping");
+
+        // Tone map the hdr color into the swapchain
+        {
+            let mut render_pass = command_buffer.create_render_pass("tonemapping", |builder: &mut RenderPassBuilder| {
+                builder.add_color_attachment(RenderPassColorAttachment {
+                    texture: Some(&swapchain_frame.view),
+                    ..Default::default()
+                });
+            });
+            let statistics = render_world.get_resource::<PipelineStatisticsQueries>();
+            let statistics_query = statistics.is_some_and(|statistics| statistics.begin(&mut render_pass, "tonemapping"));
+            if render_pass.set_pipeline(pipeline).is_ok() {
+                render_pass.set_bind_group(0, bind_group);
+                if let Err(e) = render_pass.draw(0..3, 0..1) {
+                    error!("Failed to draw: {:?}.", e);
+                }
+            } else {
+                error!("Failed to set pipeline.");
+            }
+            if let Some(statistics) = statistics {
+                statistics.end(&mut render_pass, statistics_query);
+            }
+        }
+        command_buffer.end_timestamp(timestamp);
+
+        // Submit the command buffer
+        command_buffer.submit(&render_instance);
+        command_buffer.trace_timestamps(&render_instance);
+    }
+}
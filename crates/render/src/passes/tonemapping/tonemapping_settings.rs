@@ -0,0 +1,134 @@
+use bevy::prelude::*;
+use wde_wgpu::{bind_group::{BindGroup, BindGroupLayout, BindGroupLayoutBuilder, WgpuBindGroup}, buffer::{BufferBindingType, BufferUsage, WBuffer}, instance::WRenderInstance, render_pipeline::WShaderStages, texture::WTexture};
+
+use crate::{assets::{GpuTexture, RenderAssets, Texture}, passes::hdr::HdrTexture};
+
+/// The operator mapping the hdr color to the [0, 1] range of the swapchain.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ToneMappingOperator {
+    /// Clamp the color, saturating the values above 1.0.
+    Linear,
+    /// Map each channel `c` to `c / (1 + c)`.
+    Reinhard,
+    /// Reinhard operator reaching 1.0 for the `max_white` brightness instead of the infinity.
+    ReinhardExtended {
+        max_white: f32
+    },
+    /// Fit of the ACES filmic curve by Krzysztof Narkowicz.
+    Aces,
+    /// Filmic curve of John Hable used in Uncharted 2.
+    Uncharted2
+}
+impl ToneMappingOperator {
+    /// The index of the operator in the shader.
+    fn index(&self) -> u32 {
+        match self {
+            ToneMappingOperator::Linear => 0,
+            ToneMappingOperator::Reinhard => 1,
+            ToneMappingOperator::ReinhardExtended { .. } => 2,
+            ToneMappingOperator::Aces => 3,
+            ToneMappingOperator::Uncharted2 => 4
+        }
+    }
+}
+
+/// The settings of the tone mapping, converting the hdr color to the colors of the swapchain.
+#[derive(Resource, Debug, Clone, Copy, PartialEq)]
+pub struct ToneMappingSettings {
+    /// The operator of the tone mapping (default: Linear, which keeps the colors as before the hdr target).
+    pub operator: ToneMappingOperator,
+    /// The factor applied to the hdr color before the operator (default: 1.0).
+    pub exposure: f32
+}
+impl Default for ToneMappingSettings {
+    fn default() -> Self {
+        Self {
+            operator: ToneMappingOperator::Linear,
+            exposure: 1.0
+        }
+    }
+}
+
+/// The tone mapping uniform.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ToneMappingUniform {
+    /// x: the index of the operator, y: the exposure, z: the maximum white brightness,
+    /// w: 1 if the color must be gamma corrected as the swapchain is not sRGB.
+    pub params: [f32; 4]
+}
+
+/// The layout and the bind group of the tone mapping, recreated with the hdr texture.
+#[derive(Resource)]
+pub struct ToneMappingLayout {
+    /// The buffer of the tone mapping uniform.
+    pub uniform_buffer: WBuffer,
+    /// The layout of the tone mapping: the hdr color and the uniform.
+    pub layout: BindGroupLayout,
+    pub bind_group: Option<WgpuBindGroup>,
+
+    /// The hdr texture the bind group was created for.
+    source: Option<AssetId<Texture>>
+}
+impl FromWorld for ToneMappingLayout {
+    fn from_world(world: &mut World) -> Self {
+        let render_instance = world.get_resource::<WRenderInstance<'static>>().unwrap();
+        let render_instance = render_instance.data.read().unwrap();
+
+        let uniform_buffer = WBuffer::new(&render_instance, "tonemapping",
+            std::mem::size_of::<ToneMappingUniform>(), BufferUsage::UNIFORM | BufferUsage::COPY_DST, None);
+        let layout = BindGroupLayout::new("tonemapping", |builder: &mut BindGroupLayoutBuilder| {
+            builder.add_texture_view(0, WShaderStages::FRAGMENT);
+            builder.add_buffer(1, WShaderStages::FRAGMENT, BufferBindingType::Uniform);
+        });
+
+        ToneMappingLayout {
+            uniform_buffer,
+            layout,
+            bind_group: None,
+            source: None
+        }
+    }
+}
+impl ToneMappingLayout {
+    /// Write the uniform, and recreate the bind group when the hdr texture changes.
+    pub fn prepare(
+        mut tonemapping_layout: ResMut<ToneMappingLayout>, settings: Res<ToneMappingSettings>,
+        hdr_texture: Option<Res<HdrTexture>>, textures: Res<RenderAssets<GpuTexture>>,
+        render_instance: Res<WRenderInstance<'static>>
+    ) {
+        let tonemapping_layout = &mut *tonemapping_layout;
+        let render_instance = render_instance.data.read().unwrap();
+
+        // Update the uniform
+        let max_white = match settings.operator {
+            ToneMappingOperator::ReinhardExtended { max_white } => max_white.max(1e-3),
+            _ => 1.0
+        };
+        let srgb_encode = if WTexture::SWAPCHAIN_FORMAT.is_srgb() { 0.0 } else { 1.0 };
+        tonemapping_layout.uniform_buffer.write(&render_instance, bytemuck::cast_slice(&[ToneMappingUniform {
+            params: [settings.operator.index() as f32, settings.exposure.max(0.0), max_white, srgb_encode]
+        }]), 0);
+
+        // Check if the bind group is up to date
+        let hdr_texture = match hdr_texture {
+            Some(hdr_texture) => hdr_texture,
+            None => return
+        };
+        if tonemapping_layout.source == Some(hdr_texture.texture.id()) {
+            return;
+        }
+        let hdr = match textures.get(&hdr_texture.texture) {
+            Some(hdr) => hdr,
+            None => return
+        };
+
+        // Create the bind group
+        let layout = tonemapping_layout.layout.build(&render_instance);
+        tonemapping_layout.bind_group = Some(BindGroup::build("tonemapping", &render_instance, &layout, &vec![
+            BindGroup::view(0, &hdr.texture.view),
+            BindGroup::buffer(1, &tonemapping_layout.uniform_buffer)
+        ]));
+        tonemapping_layout.source = Some(hdr_texture.texture.id());
+    }
+}
@@ -0,0 +1,48 @@
+use bevy::prelude::*;
+
+mod voxel_grid;
+mod voxel_pipeline;
+mod voxel_renderpass;
+
+pub use voxel_grid::*;
+pub use voxel_pipeline::*;
+pub use voxel_renderpass::*;
+
+use crate::{assets::RenderAssetsPlugin, core::{Render, RenderApp, RenderSet}};
+
+use super::render_graph::RenderGraph;
+
+/// Rasterizes the pbr entities into a voxel grid using conservative rasterization.
+/// This pass is disabled by default, see `VoxelizationSettings`.
+pub(crate) struct VoxelizationPlugin;
+impl Plugin for VoxelizationPlugin {
+    fn build(&self, app: &mut App) {
+        // Add the voxelization settings
+        app
+            .init_resource::<VoxelizationSettings>();
+
+        // Add the voxelization pipeline
+        app
+            .init_asset::<VoxelizationRenderPipelineAsset>()
+            .add_plugins(RenderAssetsPlugin::<GpuVoxelizationRenderPipeline>::default());
+
+        // Add the voxelization render pass
+        let render_app = app.get_sub_app_mut(RenderApp).unwrap();
+        render_app
+            .init_resource::<VoxelizationSettings>()
+            .add_systems(Render, VoxelGrid::build_bind_group.in_set(RenderSet::BindGroups));
+        render_app.world_mut().get_resource_mut::<RenderGraph>().unwrap()
+            .add_pass::<VoxelizationRenderPass>(11);
+    }
+
+    fn finish(&self, app: &mut App) {
+        // Create the voxel grid
+        let grid = VoxelGrid::new(app.world_mut());
+        app.get_sub_app_mut(RenderApp).unwrap().insert_resource(grid);
+
+        // Create the voxelization pipeline
+        let pipeline: Handle<VoxelizationRenderPipelineAsset> = app.world_mut()
+            .get_resource::<AssetServer>().unwrap().add(VoxelizationRenderPipelineAsset);
+        app.get_sub_app_mut(RenderApp).unwrap().world_mut().spawn(VoxelizationRenderPipeline(pipeline));
+    }
+}
@@ -0,0 +1,93 @@
+use bevy::prelude::*;
+use wde_wgpu::{bind_group::{BindGroup, BindGroupLayout, WgpuBindGroup}, buffer::{BufferBindingType, BufferUsage}, instance::WRenderInstance, render_pipeline::WShaderStages, texture::{WTextureFormat, WTextureUsages}};
+
+use crate::assets::{Buffer, GpuBuffer, RenderAssets, Texture};
+
+/// The number of voxels along each axis of the voxel grid.
+pub const VOXEL_GRID_SIZE: u32 = 128;
+
+/// The settings of the voxelization pass.
+#[derive(Resource, Clone, Debug)]
+pub struct VoxelizationSettings {
+    /// Whether the voxelization pass runs (default: false).
+    pub enabled: bool,
+    /// The world space center of the voxel grid.
+    pub center: Vec3,
+    /// The world space half size of the voxel grid.
+    pub half_extent: f32,
+}
+impl Default for VoxelizationSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            center: Vec3::ZERO,
+            half_extent: 64.0,
+        }
+    }
+}
+
+/// The voxel grid filled by the voxelization pass.
+/// Each voxel is stored as a single bit, set if a triangle touches the voxel.
+/// The voxel (x, y, z) is the bit `i % 32` of the element `i / 32` with `i = x + y * size + z * size * size`.
+#[derive(Resource)]
+pub struct VoxelGrid {
+    /// The voxels buffer.
+    pub buffer: Handle<Buffer>,
+    /// The color target of the voxelization pass, only used to define the rasterization resolution.
+    pub target: Handle<Texture>,
+    pub bind_group_layout: Option<BindGroupLayout>,
+    pub bind_group: Option<WgpuBindGroup>
+}
+impl VoxelGrid {
+    /// Create the voxel grid buffer and target texture.
+    pub(crate) fn new(world: &mut World) -> Self {
+        let buffer = world.add_asset(Buffer {
+            label: "voxel-grid".to_string(),
+            size: (VOXEL_GRID_SIZE * VOXEL_GRID_SIZE * VOXEL_GRID_SIZE / 32) as usize * std::mem::size_of::<u32>(),
+            usage: BufferUsage::STORAGE | BufferUsage::COPY_DST,
+            content: None,
+        });
+        let target = world.add_asset(Texture {
+            label: "voxel-grid-target".to_string(),
+            size: (VOXEL_GRID_SIZE, VOXEL_GRID_SIZE),
+            format: WTextureFormat::R8Unorm,
+            usages: WTextureUsages::RENDER_ATTACHMENT,
+            ..Default::default()
+        });
+
+        Self {
+            buffer,
+            target,
+            bind_group_layout: None,
+            bind_group: None
+        }
+    }
+
+    pub fn build_bind_group(buffers: Res<RenderAssets<GpuBuffer>>, mut grid: ResMut<VoxelGrid>, render_instance: Res<WRenderInstance<'static>>) {
+        // Check if the grid bind group is already created
+        if grid.bind_group.is_some() {
+            return;
+        }
+
+        // Get the grid buffer
+        let buffer = match buffers.get(&grid.buffer) {
+            Some(buffer) => buffer,
+            None => return
+        };
+
+        // Create the grid layout
+        let grid_layout = BindGroupLayout::new("voxel-grid", |builder| {
+            builder.add_buffer(0,
+                WShaderStages::FRAGMENT,
+                BufferBindingType::Storage { read_only: false });
+        });
+
+        // Create the bind group
+        let render_instance = render_instance.data.read().unwrap();
+        let bind_group = BindGroup::build("voxel-grid", &render_instance, &grid_layout.build(&render_instance), &vec![
+            BindGroup::buffer(0, &buffer.buffer)
+        ]);
+        grid.bind_group_layout = Some(grid_layout);
+        grid.bind_group = Some(bind_group);
+    }
+}
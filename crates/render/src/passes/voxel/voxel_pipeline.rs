@@ -0,0 +1,61 @@
+use bevy::{ecs::system::lifetimeless::{SRes, SResMut}, prelude::*};
+use wde_wgpu::{render_pipeline::WShaderStages, texture::WTextureFormat};
+use crate::{assets::{PrepareAssetError, RenderAsset}, passes::pbr::PbrSsbo, pipelines::{CachedPipelineIndex, PipelineManager, PushConstantDescriptor, RenderPipelineDescriptor}};
+
+use super::VoxelGrid;
+
+
+#[derive(Default, Asset, Clone, TypePath)]
+pub struct VoxelizationRenderPipelineAsset;
+#[derive(Component)]
+pub struct VoxelizationRenderPipeline(pub Handle<VoxelizationRenderPipelineAsset>);
+pub struct GpuVoxelizationRenderPipeline {
+    pub cached_pipeline_index: CachedPipelineIndex
+}
+impl RenderAsset for GpuVoxelizationRenderPipeline {
+    type SourceAsset = VoxelizationRenderPipelineAsset;
+    type Param = (
+        SRes<AssetServer>, SResMut<PipelineManager>,
+        SRes<PbrSsbo>, SRes<VoxelGrid>
+    );
+
+    fn prepare_asset(
+            asset: Self::SourceAsset,
+            (
+                assets_server, pipeline_manager,
+                ssbo, grid
+            ): &mut bevy::ecs::system::SystemParamItem<Self::Param>
+        ) -> Result<Self, PrepareAssetError<Self::SourceAsset>> {
+        // Get the layouts
+        let (ssbo_layout, grid_layout) = match (&ssbo.bind_group_layout, &grid.bind_group_layout) {
+            (Some(ssbo_layout), Some(grid_layout)) => (ssbo_layout, grid_layout),
+            _ => return Err(PrepareAssetError::RetryNextUpdate(asset))
+        };
+
+        // Create the pipeline
+        let pipeline_desc = RenderPipelineDescriptor {
+            label: "voxelization",
+            vert: Some(assets_server.load("voxel/voxelize_vert.wgsl")),
+            frag: Some(assets_server.load("voxel/voxelize_frag.wgsl")),
+            bind_group_layouts: vec![ssbo_layout.clone(), grid_layout.clone()],
+            render_targets: Some(vec![WTextureFormat::R8Unorm]),
+            push_constants: vec![PushConstantDescriptor {
+                stages: WShaderStages::VERTEX | WShaderStages::FRAGMENT,
+                offset: 0,
+                size: std::mem::size_of::<[f32; 4]>() as u32 + std::mem::size_of::<[u32; 4]>() as u32
+            }],
+            cull_mode: None,
+            conservative_rasterization: true,
+            ..Default::default()
+        };
+        let cached_index = pipeline_manager.create_render_pipeline(pipeline_desc);
+
+        Ok(GpuVoxelizationRenderPipeline {
+            cached_pipeline_index: cached_index
+        })
+    }
+
+    fn label(&self) -> &str {
+        "voxelization"
+    }
+}
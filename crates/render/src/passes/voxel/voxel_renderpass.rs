@@ -0,0 +1,115 @@
+use bevy::prelude::*;
+use crate::{assets::{GpuBuffer, GpuMesh, GpuTexture, RenderAssets}, passes::{pbr::{PbrGBufferRenderPass, PbrSsbo}, render_graph::RenderPass}, pipelines::{CachedPipelineStatus, PipelineManager}};
+use wde_wgpu::{command_buffer::{RenderPassBuilder, RenderPassColorAttachment, WCommandBuffer}, instance::WRenderInstance, render_pipeline::WShaderStages};
+
+use super::{GpuVoxelizationRenderPipeline, VoxelGrid, VoxelizationSettings};
+
+/// Rasterizes the batches of the pbr gbuffer pass into the voxel grid.
+/// The batches are drawn once per axis of the grid, and each triangle is only kept along its dominant axis
+/// so that it covers as many voxels as possible. Conservative rasterization ensures that every voxel touched
+/// by a triangle is filled.
+#[derive(Default)]
+pub struct VoxelizationRenderPass;
+impl RenderPass for VoxelizationRenderPass {
+    fn extract(&self, main_world: &mut World, render_world: &mut World) {
+        // Extract the settings
+        let settings = main_world.get_resource::<VoxelizationSettings>().unwrap().clone();
+        render_world.insert_resource(settings);
+    }
+
+    fn render(&self, render_world: &mut World) {
+        // Check if the voxelization is enabled
+        let settings = render_world.get_resource::<VoxelizationSettings>().unwrap();
+        if !settings.enabled {
+            return;
+        }
+
+        // Get the render instance
+        let render_instance = render_world.get_resource::<WRenderInstance>().unwrap();
+        let render_instance = render_instance.data.read().unwrap();
+
+        // Check if the voxel grid is ready
+        let grid = match render_world.get_resource::<VoxelGrid>() {
+            Some(grid) => grid,
+            None => return
+        };
+        let (grid_buffer, grid_target, grid_bind_group) = match (
+            render_world.get_resource::<RenderAssets<GpuBuffer>>().unwrap().get(&grid.buffer),
+            render_world.get_resource::<RenderAssets<GpuTexture>>().unwrap().get(&grid.target),
+            &grid.bind_group
+        ) {
+            (Some(buffer), Some(target), Some(bind_group)) => (buffer, target, bind_group),
+            _ => return
+        };
+
+        // Check if pipeline is ready
+        let voxelization_pipeline = match render_world.get_resource::<RenderAssets<GpuVoxelizationRenderPipeline>>().unwrap().iter().next() {
+            Some((_, pipeline)) => pipeline,
+            None => return
+        };
+
+        // Create the render pass
+        let mut command_buffer = WCommandBuffer::new(&render_instance, "voxelization");
+        command_buffer.encoder().clear_buffer(&grid_buffer.buffer.buffer, 0, None);
+        {
+            let mut render_pass = command_buffer.create_render_pass("voxelization", |builder: &mut RenderPassBuilder| {
+                builder.add_color_attachment(RenderPassColorAttachment {
+                    texture: Some(&grid_target.texture.view),
+                    ..Default::default()
+                });
+            });
+
+            // Render the batches
+            let batches = render_world.get_resource::<PbrGBufferRenderPass>().unwrap();
+            let pipeline_manager = render_world.get_resource::<PipelineManager>().unwrap();
+            let ssbo = render_world.get_resource::<PbrSsbo>().unwrap();
+            if let (
+                CachedPipelineStatus::OkRender(pipeline),
+                Some(ssbo_bind_group)
+            ) = (
+                pipeline_manager.get_pipeline(voxelization_pipeline.cached_pipeline_index),
+                &ssbo.bind_group
+            ) {
+                if render_pass.set_pipeline(pipeline).is_ok() {
+                    // Set the ssbo and the grid
+                    render_pass.set_bind_group(0, ssbo_bind_group);
+                    render_pass.set_bind_group(1, grid_bind_group);
+
+                    // Draw the batches along each axis of the grid
+                    let meshes = render_world.get_resource::<RenderAssets<GpuMesh>>().unwrap();
+                    for axis in 0..3u32 {
+                        render_pass.set_push_constants(WShaderStages::VERTEX | WShaderStages::FRAGMENT, bytemuck::cast_slice(&[
+                            settings.center.x.to_bits(), settings.center.y.to_bits(), settings.center.z.to_bits(),
+                            settings.half_extent.to_bits(), axis, 0, 0, 0
+                        ]));
+
+                        let mut old_mesh_id = None;
+                        for batch in batches.opaque_batches.iter() {
+                            // Set the mesh
+                            if old_mesh_id != Some(batch.mesh.id()) {
+                                let mesh = match meshes.get(&batch.mesh) {
+                                    Some(mesh) => mesh,
+                                    None => continue
+                                };
+                                render_pass.set_vertex_buffer(0, &mesh.vertex_buffer);
+                                render_pass.set_index_buffer(&mesh.index_buffer);
+                                old_mesh_id = Some(batch.mesh.id());
+                            }
+
+                            // Draw the mesh
+                            let instance_indices = batch.first as u32..((batch.first + batch.count) as u32);
+                            if let Err(e) = render_pass.draw_indexed(0..batch.index_count as u32, instance_indices) {
+                                error!("Failed to draw: {:?}.", e);
+                            }
+                        }
+                    }
+                } else {
+                    error!("Failed to set pipeline.");
+                }
+            }
+        }
+
+        // Submit the command buffer
+        command_buffer.submit(&render_instance);
+    }
+}
@@ -1,5 +1,7 @@
 mod pipeline_types;
 mod pipeline_manager;
+mod pipeline_layout_cache;
 
 pub use pipeline_types::*;
 pub use pipeline_manager::*;
+pub use pipeline_layout_cache::*;
@@ -0,0 +1,57 @@
+use std::{collections::HashMap, sync::Arc};
+
+use bevy::{ecs::prelude::*, log::trace};
+use wde_wgpu::{bind_group::{BindGroupLayout, WBindGroupLayoutEntry}, instance::WRenderInstanceData, render_pipeline::{create_pipeline_layout, WPipelineLayout, WPushConstantRange}};
+
+/// The entries of each bind group layout and the push constants of a pipeline layout.
+type PipelineLayoutKey = (Vec<Vec<WBindGroupLayoutEntry>>, Vec<WPushConstantRange>);
+
+#[derive(Resource, Default)]
+/// Caches the pipeline layouts of the render pipelines.
+/// Pipelines using identical bind group layouts and push constants share the same pipeline layout,
+/// so that the bind groups set in a render pass stay valid when switching between these pipelines.
+pub struct PipelineLayoutCache {
+    layouts: HashMap<PipelineLayoutKey, Arc<WPipelineLayout>>,
+}
+
+impl PipelineLayoutCache {
+    /// Get the pipeline layout matching the bind group layouts and push constants, or create it if it does not exist.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `instance` - Render instance.
+    /// * `label` - Label of the pipeline layout if it is created.
+    /// * `bind_group_layouts` - The bind group layouts, in the order of the bindings in the shaders.
+    /// * `push_constants` - The push constant ranges.
+    pub fn get_or_create(
+        &mut self, instance: &WRenderInstanceData<'_>, label: &str,
+        bind_group_layouts: &[BindGroupLayout], push_constants: Vec<WPushConstantRange>
+    ) -> Arc<WPipelineLayout> {
+        let key = (
+            bind_group_layouts.iter().map(|layout| layout.builder.entries().to_vec()).collect(),
+            push_constants
+        );
+        if let Some(layout) = self.layouts.get(&key) {
+            trace!("Reusing the cached pipeline layout for pipeline {}.", label);
+            return layout.clone();
+        }
+
+        // Create the layout
+        let bind_groups = bind_group_layouts.iter()
+            .map(|layout| layout.build(instance))
+            .collect::<Vec<_>>();
+        let layout = Arc::new(create_pipeline_layout(instance, label, &bind_groups, &key.1));
+        self.layouts.insert(key, layout.clone());
+        layout
+    }
+
+    /// The number of distinct pipeline layouts.
+    pub fn len(&self) -> usize {
+        self.layouts.len()
+    }
+
+    /// Whether the cache is empty.
+    pub fn is_empty(&self) -> bool {
+        self.layouts.is_empty()
+    }
+}
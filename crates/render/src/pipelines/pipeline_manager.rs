@@ -1,11 +1,11 @@
 use std::collections::HashMap;
 
-use bevy::{app::{App, Plugin}, asset::{AssetEvent, AssetId, Assets}, ecs::prelude::*, log::{debug, error}};
-use wde_wgpu::{compute_pipeline::WComputePipeline, instance::WRenderInstance, render_pipeline::{WRenderPipeline, WShaderStages}};
+use bevy::{app::{App, Plugin}, asset::{AssetEvent, AssetId, AssetLoadFailedEvent, Assets, Handle}, ecs::prelude::*, log::{debug, error, warn}};
+use wde_wgpu::{compute_pipeline::WComputePipeline, instance::WRenderInstance, render_pipeline::{validate_push_constants, WPushConstantRange, WRenderPipeline, WShaderStages}};
 
-use crate::{core::{extract_macros::ExtractWorld, Extract, Render, RenderSet}, assets::Shader};
+use crate::{core::{extract_macros::ExtractWorld, Extract, MainWorld, Render, RenderSet}, assets::{ErrorShader, Shader}, passes::depth::DepthTexture};
 
-use super::{RenderPipelineDescriptor, ComputePipelineDescriptor};
+use super::{ComputePipelineDescriptor, PipelineLayoutCache, RenderPipelineDescriptor};
 
 /// The index of a cached pipeline.
 pub type CachedPipelineIndex = usize;
@@ -19,13 +19,43 @@ pub enum CachedPipelineStatus<'a> {
 }
 
 
+/// The progress of the pipelines compilation.
+/// This resource is available in both the main world and the render world.
+#[derive(Resource, Default, Clone, Copy, Debug)]
+pub struct ShaderCompilationProgress {
+    /// The number of pipelines registered in the pipeline manager.
+    pub total: usize,
+    /// The number of pipelines compiled.
+    pub compiled: usize,
+    /// The number of pipelines that failed to be created.
+    pub failed: usize
+}
+impl ShaderCompilationProgress {
+    /// The number of pipelines compiled or failed.
+    pub fn finished(&self) -> usize {
+        self.compiled + self.failed
+    }
+
+    /// Whether all the registered pipelines are compiled or failed.
+    pub fn is_done(&self) -> bool {
+        self.finished() >= self.total
+    }
+}
+
+
 pub struct PipelineManagerPlugin;
 impl Plugin for PipelineManagerPlugin {
     fn build(&self, app: &mut App) {
         app
             .init_resource::<PipelineManager>()
-            .add_systems(Extract, extract_shaders)
-            .add_systems(Render, (load_render_pipelines, load_compute_pipelines).in_set(RenderSet::Prepare));
+            .init_resource::<PipelineLayoutCache>()
+            .init_resource::<ShaderCompilationProgress>()
+            .init_resource::<ErrorShader>()
+            .add_systems(Extract, (extract_shaders, extract_compilation_progress))
+            .add_systems(Render, (
+                (load_render_pipelines, load_compute_pipelines),
+                update_compilation_progress
+            ).chain().in_set(RenderSet::Prepare));
     }
 }
 
@@ -38,13 +68,19 @@ pub struct PipelineManager {
     pub processing_render_pipelines: HashMap<CachedPipelineIndex, RenderPipelineDescriptor>,
     pub loaded_render_pipelines: HashMap<CachedPipelineIndex, WRenderPipeline>,
     pub loaded_render_pipelines_desc: HashMap<CachedPipelineIndex, RenderPipelineDescriptor>,
+    /// The render pipelines that failed to be created, created again once one of their shaders is modified.
+    pub failed_render_pipelines: HashMap<CachedPipelineIndex, RenderPipelineDescriptor>,
 
     pub processing_compute_pipelines: HashMap<CachedPipelineIndex, ComputePipelineDescriptor>,
     pub loaded_compute_pipelines: HashMap<CachedPipelineIndex, WComputePipeline>,
     pub loaded_compute_pipelines_desc: HashMap<CachedPipelineIndex, ComputePipelineDescriptor>,
+    /// The compute pipelines that failed to be created, created again once their shader is modified.
+    pub failed_compute_pipelines: HashMap<CachedPipelineIndex, ComputePipelineDescriptor>,
 
     pub shader_cache: HashMap<AssetId<Shader>, Shader>,
     pub shader_to_pipelines: HashMap<AssetId<Shader>, Vec<CachedPipelineIndex>>,
+    /// The shaders that failed to load with their path, replaced by the error shader.
+    pub failed_shaders: HashMap<AssetId<Shader>, String>,
 }
 
 impl PipelineManager {
@@ -81,6 +117,8 @@ impl PipelineManager {
             CachedPipelineStatus::OkRender(pipeline)
         } else if let Some(pipeline) = self.loaded_compute_pipelines.get(&id) {
             CachedPipelineStatus::OkCompute(pipeline)
+        } else if self.failed_render_pipelines.contains_key(&id) || self.failed_compute_pipelines.contains_key(&id) {
+            CachedPipelineStatus::Error
         } else {
             error!("Pipeline with id {} not found", id);
             CachedPipelineStatus::Error
@@ -88,11 +126,37 @@ impl PipelineManager {
     }
 }
 
+/// Get a shader of a pipeline from the shader cache.
+/// If the shader failed to load, the error shader is returned instead.
+/// 
+/// # Returns
+/// The shader, or `None` if the shader is not loaded yet.
+fn get_shader<'a>(
+    pipeline_manager: &'a PipelineManager, shader: &Handle<Shader>, error_shader: &'a Shader, label: &str
+) -> Option<&'a Shader> {
+    if let Some(shader) = pipeline_manager.shader_cache.get(&shader.id()) {
+        return Some(shader);
+    }
+    if let Some(path) = pipeline_manager.failed_shaders.get(&shader.id()) {
+        warn!("Pipeline {} uses the error shader in place of the shader {} that failed to load.", label, path);
+        return Some(error_shader);
+    }
+    None
+}
+
 /// Extract the shaders from the asset server and store them in the pipeline manager.
 fn extract_shaders(
     mut pipeline_manager: ResMut<PipelineManager>, shaders: ExtractWorld<Res<Assets<Shader>>>,
-    mut shader_events: ExtractWorld<EventReader<AssetEvent<Shader>>>
+    mut shader_events: ExtractWorld<EventReader<AssetEvent<Shader>>>,
+    mut shader_failed_events: ExtractWorld<EventReader<AssetLoadFailedEvent<Shader>>>
 ) {
+    // Register the shaders that failed to load
+    for event in shader_failed_events.read() {
+        warn!("Failed to load shader {}: {}. The error shader will be used instead.", event.path, event.error);
+        pipeline_manager.failed_shaders.insert(event.id, event.path.to_string());
+    }
+
+    let pipeline_manager = &mut *pipeline_manager;
     let cache = &mut pipeline_manager.shader_cache;
     let mut updated_ids = Vec::new();
     for event in shader_events.read() {
@@ -100,6 +164,7 @@ fn extract_shaders(
             AssetEvent::Added { id } => {
                 if let Some(shader) = shaders.get(*id) {
                     cache.insert(*id, shader.clone());
+                    pipeline_manager.failed_shaders.remove(id);
                 }
             }
             AssetEvent::Modified { id } => {
@@ -123,7 +188,13 @@ fn extract_shaders(
             None => continue
         };
         for p_id in p_ids.iter() {
-            // Only update the pipeline if it is loaded
+            // Only update the pipeline if it is loaded or failed
+            if let Some(desc) = pipeline_manager.failed_render_pipelines.remove(p_id) {
+                pipeline_manager.processing_render_pipelines.insert(*p_id, desc);
+            }
+            if let Some(desc) = pipeline_manager.failed_compute_pipelines.remove(p_id) {
+                pipeline_manager.processing_compute_pipelines.insert(*p_id, desc);
+            }
             if pipeline_manager.loaded_render_pipelines.contains_key(p_id) {
                 let desc = pipeline_manager.loaded_render_pipelines_desc.remove(p_id).unwrap();
                 pipeline_manager.processing_render_pipelines.insert(*p_id, desc.clone());
@@ -138,12 +209,34 @@ fn extract_shaders(
     }
 }
 
+/// Update the number of compiled and failed pipelines.
+fn update_compilation_progress(pipeline_manager: Res<PipelineManager>, mut progress: ResMut<ShaderCompilationProgress>) {
+    let compiled = pipeline_manager.loaded_render_pipelines.len() + pipeline_manager.loaded_compute_pipelines.len();
+    let failed = pipeline_manager.failed_render_pipelines.len() + pipeline_manager.failed_compute_pipelines.len();
+    if progress.total != pipeline_manager.pipeline_iter || progress.compiled != compiled || progress.failed != failed {
+        debug!("Compiled {} and failed {} out of {} pipelines.", compiled, failed, pipeline_manager.pipeline_iter);
+        progress.total = pipeline_manager.pipeline_iter;
+        progress.compiled = compiled;
+        progress.failed = failed;
+    }
+}
+
+/// Copy the compilation progress of the pipelines to the main world.
+fn extract_compilation_progress(progress: Res<ShaderCompilationProgress>, mut main_world: ResMut<MainWorld>) {
+    if let Some(mut main_progress) = main_world.get_resource_mut::<ShaderCompilationProgress>() {
+        *main_progress = *progress;
+    }
+}
+
 /// Load the pipelines that are queued in the pipeline manager.
 fn load_render_pipelines(
     mut pipeline_manager: ResMut<PipelineManager>,
-    render_instance: Res<WRenderInstance<'static>>
+    mut layout_cache: ResMut<PipelineLayoutCache>,
+    render_instance: Res<WRenderInstance<'static>>, error_shader: Res<ErrorShader>,
+    depth_texture: Option<Res<DepthTexture>>
 ) {
     let mut pipelines_loaded_indices: Vec<(usize, WRenderPipeline)> = Vec::new();
+    let mut pipelines_failed_indices: Vec<CachedPipelineIndex> = Vec::new();
     let mut pipelines_loaded_desc: HashMap<CachedPipelineIndex, RenderPipelineDescriptor> = HashMap::new();
     let mut shaders_to_pipelines: HashMap<AssetId<Shader>, Vec<CachedPipelineIndex>> = pipeline_manager.shader_to_pipelines.clone();
     for (id, descriptor) in pipeline_manager.processing_render_pipelines.iter() {
@@ -152,7 +245,7 @@ fn load_render_pipelines(
         // Check if vertex shader is loaded
         let vert_shader = match &descriptor.vert {
             Some(shader) => {
-                match pipeline_manager.shader_cache.get(&shader.id()) {
+                match get_shader(&pipeline_manager, shader, &error_shader.vert, descriptor.label) {
                     Some(shader) => Some(shader),
                     None => {
                         // Shader is not loaded yet
@@ -167,7 +260,7 @@ fn load_render_pipelines(
         // Check if fragment shader is loaded
         let frag_shader = match &descriptor.frag {
             Some(shader) => {
-                match pipeline_manager.shader_cache.get(&shader.id()) {
+                match get_shader(&pipeline_manager, shader, &error_shader.frag, descriptor.label) {
                     Some(shader) => Some(shader),
                     None => {
                         // Shader is not loaded yet
@@ -179,8 +272,8 @@ fn load_render_pipelines(
             None => None
         };
 
-        // Skip if shaders are not loaded
-        if !can_load {
+        // Skip if shaders are not loaded, or if the format of the depth texture is not known yet
+        if !can_load || (descriptor.depth.enabled && descriptor.use_depth_texture_format && depth_texture.is_none()) {
             continue;
         }
         pipelines_loaded_desc.insert(*id, descriptor.clone());
@@ -189,11 +282,18 @@ fn load_render_pipelines(
 
         debug!("Loading pipeline with id {}", id);
 
-        // Build the layouts
-        let mut bind_group_layouts = Vec::new();
-        for layout in descriptor.bind_group_layouts.iter() {
-            bind_group_layouts.push(layout.build(&render_instance.data.read().unwrap()));
+        // Get the layout, shared with the pipelines using the same bind group layouts and push constants
+        let push_constants = descriptor.push_constants.iter().map(|push_constant| WPushConstantRange {
+            stages: push_constant.stages,
+            range: push_constant.offset..push_constant.offset + push_constant.size,
+        }).collect::<Vec<_>>();
+        if let Err(e) = validate_push_constants(descriptor.label, &push_constants) {
+            error!("Failed to load pipeline: {:?}", e);
+            pipelines_failed_indices.push(*id);
+            continue;
         }
+        let layout = layout_cache.get_or_create(
+            &render_instance.data.read().unwrap(), descriptor.label, &descriptor.bind_group_layouts, push_constants);
 
         // Load the pipeline
         let mut pipeline = WRenderPipeline::new(descriptor.label);
@@ -205,18 +305,34 @@ fn load_render_pipelines(
         }
         pipeline.set_topology(descriptor.topology);
         pipeline.set_cull_mode(descriptor.cull_mode);
-        pipeline.set_depth(descriptor.depth.clone());
+        pipeline.set_front_face(descriptor.front_face);
+        pipeline.set_polygon_mode(descriptor.polygon_mode);
+        pipeline.set_msaa(descriptor.sample_count);
+        pipeline.set_blend_state(descriptor.blend.color, descriptor.blend.alpha);
+        pipeline.set_target_blend_states(descriptor.target_blends.clone());
+        pipeline.set_conservative_rasterization(descriptor.conservative_rasterization);
+        pipeline.set_depth_clamp(descriptor.depth_clamp);
+        pipeline.set_vertex_input(descriptor.vertex_input);
+        let mut depth = descriptor.depth.clone();
+        if let (true, true, Some(depth_texture)) = (depth.enabled, descriptor.use_depth_texture_format, depth_texture.as_ref()) {
+            // The pipelines render to the depth texture
+            depth.format = depth_texture.format();
+        }
+        pipeline.set_depth(depth);
+        pipeline.set_bind_group_entries(descriptor.bind_group_layouts.iter()
+            .map(|layout| layout.builder.entries().to_vec()).collect());
         if let Some(ref render_targets) = descriptor.render_targets {
             pipeline.set_render_targets(render_targets.clone());
         }
         for push_constant in descriptor.push_constants.iter() {
             pipeline.add_push_constant(push_constant.stages, push_constant.offset, push_constant.size);
         }
-        pipeline.set_bind_groups(bind_group_layouts);
+        pipeline.set_layout(layout);
         match pipeline.init(&render_instance.data.read().unwrap()) {
             Ok(_) => (),
             Err(e) => {
                 error!("Failed to load pipeline: {:?}", e);
+                pipelines_failed_indices.push(*id);
                 continue;
             }
         }
@@ -232,6 +348,12 @@ fn load_render_pipelines(
         pipeline_manager.loaded_render_pipelines_desc.insert(id, pipelines_loaded_desc.remove(&id).unwrap());
     }
 
+    // Keep the failed pipelines until one of their shaders is modified
+    for id in pipelines_failed_indices {
+        let desc = pipeline_manager.processing_render_pipelines.remove(&id).unwrap();
+        pipeline_manager.failed_render_pipelines.insert(id, desc);
+    }
+
     // Update the shader to pipelines map
     pipeline_manager.shader_to_pipelines = shaders_to_pipelines;
 }
@@ -239,9 +361,10 @@ fn load_render_pipelines(
 /// Load the pipelines that are queued in the pipeline manager.
 fn load_compute_pipelines(
     mut pipeline_manager: ResMut<PipelineManager>,
-    render_instance: Res<WRenderInstance<'static>>
+    render_instance: Res<WRenderInstance<'static>>, error_shader: Res<ErrorShader>
 ) {
     let mut pipelines_loaded_indices: Vec<(usize, WComputePipeline)> = Vec::new();
+    let mut pipelines_failed_indices: Vec<CachedPipelineIndex> = Vec::new();
     let mut pipelines_loaded_desc: HashMap<CachedPipelineIndex, ComputePipelineDescriptor> = HashMap::new();
     let mut shaders_to_pipelines: HashMap<AssetId<Shader>, Vec<CachedPipelineIndex>> = pipeline_manager.shader_to_pipelines.clone();
     for (id, descriptor) in pipeline_manager.processing_compute_pipelines.iter() {
@@ -250,7 +373,7 @@ fn load_compute_pipelines(
         // Check if compute shader is loaded
         let compute_shader = match &descriptor.comp {
             Some(shader) => {
-                match pipeline_manager.shader_cache.get(&shader.id()) {
+                match get_shader(&pipeline_manager, shader, &error_shader.comp, descriptor.label) {
                     Some(shader) => Some(shader),
                     None => {
                         // Shader is not loaded yet
@@ -287,6 +410,7 @@ fn load_compute_pipelines(
             Ok(_) => (),
             Err(e) => {
                 error!("Failed to load pipeline: {:?}", e);
+                pipelines_failed_indices.push(*id);
                 continue;
             }
         }
@@ -302,6 +426,47 @@ fn load_compute_pipelines(
         pipeline_manager.loaded_compute_pipelines_desc.insert(id, pipelines_loaded_desc.remove(&id).unwrap());
     }
 
+    // Keep the failed pipelines until their shader is modified
+    for id in pipelines_failed_indices {
+        let desc = pipeline_manager.processing_compute_pipelines.remove(&id).unwrap();
+        pipeline_manager.failed_compute_pipelines.insert(id, desc);
+    }
+
     // Update the shader to pipelines map
     pipeline_manager.shader_to_pipelines = shaders_to_pipelines;
 }
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+
+    #[test]
+    fn failed_pipelines_are_counted_as_finished() {
+        let mut world = World::new();
+        let mut pipeline_manager = PipelineManager::default();
+        let failed = pipeline_manager.create_render_pipeline(RenderPipelineDescriptor::default());
+        let loading = pipeline_manager.create_render_pipeline(RenderPipelineDescriptor::default());
+        let desc = pipeline_manager.processing_render_pipelines.remove(&failed).unwrap();
+        pipeline_manager.failed_render_pipelines.insert(failed, desc);
+        world.insert_resource(pipeline_manager);
+        world.init_resource::<ShaderCompilationProgress>();
+
+        // The failed pipeline is finished, the other one is still loading
+        world.run_system_once(update_compilation_progress).unwrap();
+        let progress = *world.resource::<ShaderCompilationProgress>();
+        assert_eq!((progress.total, progress.compiled, progress.failed), (2, 0, 1));
+        assert!(!progress.is_done());
+        let pipeline_manager = world.resource::<PipelineManager>();
+        assert!(matches!(pipeline_manager.get_pipeline(failed), CachedPipelineStatus::Error));
+        assert!(matches!(pipeline_manager.get_pipeline(loading), CachedPipelineStatus::Loading));
+
+        // The loading is done once the other pipeline fails too
+        let mut pipeline_manager = world.resource_mut::<PipelineManager>();
+        let desc = pipeline_manager.processing_render_pipelines.remove(&loading).unwrap();
+        pipeline_manager.failed_render_pipelines.insert(loading, desc);
+        world.run_system_once(update_compilation_progress).unwrap();
+        assert!(world.resource::<ShaderCompilationProgress>().is_done());
+    }
+}
@@ -1,5 +1,5 @@
 use bevy::{asset::Handle, ecs::prelude::*};
-use wde_wgpu::{bind_group::BindGroupLayout, render_pipeline::{WDepthStencilDescriptor, WFace, WShaderStages, WTopology}, texture::WTextureFormat};
+use wde_wgpu::{bind_group::BindGroupLayout, render_pipeline::{WBlendState, WDepthStencilDescriptor, WFace, WFrontFace, WPolygonMode, WShaderStages, WTopology}, texture::WTextureFormat};
 
 use crate::assets::Shader;
 
@@ -36,6 +36,26 @@ pub struct RenderPipelineDescriptor {
     pub topology: WTopology,
     /// The culling mode that the pipeline will use (default: Back). None will disable culling.
     pub cull_mode: Option<WFace>,
+    /// The winding order of the front faces (default: Ccw).
+    pub front_face: WFrontFace,
+    /// How the polygons are rasterized (default: Fill). Line and Point fall back to Fill if the device does not support them.
+    pub polygon_mode: WPolygonMode,
+    /// The number of samples per pixel of the render targets (default: 1), see `MsaaSettings`.
+    pub sample_count: u32,
+    /// The blending of the colors written to the render targets (default: REPLACE).
+    pub blend: WBlendState,
+    /// The blending of each of the first render targets, overriding `blend` (default: empty, all the targets use `blend`).
+    pub target_blends: Vec<WBlendState>,
+    /// Whether the pipeline uses conservative rasterization (default: false). Ignored if the device does not support it.
+    pub conservative_rasterization: bool,
+    /// Whether the depth of the primitives is clamped instead of clipped by the near and far planes (default: false).
+    /// Ignored if the device does not support it.
+    pub depth_clamp: bool,
+    /// Whether the pipeline reads its vertices from a `WVertex` vertex buffer (default: true).
+    pub vertex_input: bool,
+    /// Whether the depth format of the pipeline is replaced by the format of the `DepthTexture` (default: true).
+    /// Disable it for the pipelines rendering to their own depth target, in the format of `depth.format`.
+    pub use_depth_texture_format: bool,
 }
 impl Default for RenderPipelineDescriptor {
     fn default() -> Self {
@@ -49,6 +69,15 @@ impl Default for RenderPipelineDescriptor {
             push_constants: vec![],
             topology: WTopology::TriangleList,
             cull_mode: Some(WFace::Back),
+            front_face: WFrontFace::Ccw,
+            polygon_mode: WPolygonMode::Fill,
+            sample_count: 1,
+            blend: WBlendState::REPLACE,
+            target_blends: vec![],
+            conservative_rasterization: false,
+            depth_clamp: false,
+            vertex_input: true,
+            use_depth_texture_format: true,
         }
     }
 }
@@ -0,0 +1,161 @@
+//! Selection of the GPU adapter used by the renderer, on machines with multiple GPUs.
+
+use bevy::log::{debug, warn};
+use serde::Deserialize;
+use wgpu::Surface;
+
+/// Information about a GPU adapter.
+pub type WAdapterInfo = wgpu::AdapterInfo;
+
+/// The file from which the adapter preference is read.
+pub const ADAPTER_CONFIG_FILE: &str = "config.ron";
+
+/// The environment variable overriding the preferred vendor id, in decimal or hexadecimal (e.g. `0x10DE`).
+pub const ADAPTER_VENDOR_VAR: &str = "WDE_GPU_VENDOR";
+
+/// The preferred GPU adapter, read at startup from the `config.ron` file and the `WDE_GPU_VENDOR` environment variable.
+///
+/// # Example
+/// ```ron
+/// // config.ron
+/// (
+///     adapter: (
+///         discrete: true,
+///         vendor_id: Some(0x10DE),
+///     ),
+/// )
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct WAdapterPreference {
+    /// Whether a discrete GPU is preferred over an integrated one.
+    pub discrete: bool,
+    /// The PCI vendor id of the preferred adapter (e.g. `0x10DE` for NVIDIA, `0x1002` for AMD, `0x8086` for Intel).
+    pub vendor_id: Option<u32>,
+}
+impl Default for WAdapterPreference {
+    fn default() -> Self {
+        Self { discrete: true, vendor_id: None }
+    }
+}
+
+/// The content of the `config.ron` file read by the render instance.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct WInstanceConfig {
+    adapter: WAdapterPreference,
+}
+
+impl WAdapterPreference {
+    /// Read the adapter preference from the `config.ron` file, if any, then from the `WDE_GPU_VENDOR` environment variable.
+    /// Invalid values are ignored with a warning.
+    pub fn load() -> Self {
+        let mut preference = match std::fs::read_to_string(ADAPTER_CONFIG_FILE) {
+            Ok(content) => match ron::from_str::<WInstanceConfig>(&content) {
+                Ok(config) => config.adapter,
+                Err(e) => {
+                    warn!("Failed to parse the adapter preference of {}: {}.", ADAPTER_CONFIG_FILE, e);
+                    Self::default()
+                }
+            },
+            Err(_) => Self::default()
+        };
+
+        if let Ok(vendor) = std::env::var(ADAPTER_VENDOR_VAR) {
+            let vendor = vendor.trim();
+            let parsed = match vendor.strip_prefix("0x").or_else(|| vendor.strip_prefix("0X")) {
+                Some(hex) => u32::from_str_radix(hex, 16),
+                None => vendor.parse::<u32>()
+            };
+            match parsed {
+                Ok(vendor_id) => preference.vendor_id = Some(vendor_id),
+                Err(_) => warn!("Ignoring the invalid {} vendor id '{}'.", ADAPTER_VENDOR_VAR, vendor)
+            }
+        }
+
+        debug!("Using the adapter preference {:?}.", preference);
+        preference
+    }
+
+    /// The power preference used to request the default adapter.
+    pub fn power_preference(&self) -> wgpu::PowerPreference {
+        if self.discrete {
+            wgpu::PowerPreference::HighPerformance
+        } else {
+            wgpu::PowerPreference::LowPower
+        }
+    }
+
+    /// Select the adapter of the preferred vendor among the adapters compatible with the surface.
+    /// The discrete or integrated adapters of the vendor are then preferred.
+    ///
+    /// # Arguments
+    ///
+    /// * `instance` - The wgpu instance.
+    /// * `surface` - The surface the adapter must be able to render to, if any.
+    ///
+    /// # Returns
+    ///
+    /// The preferred adapter, or none if no vendor is preferred or no adapter matches it, in which case the default
+    /// adapter should be requested with the `power_preference`.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn select_adapter(&self, instance: &wgpu::Instance, surface: Option<&Surface>) -> Option<wgpu::Adapter> {
+        let vendor_id = self.vendor_id?;
+        let mut adapters: Vec<wgpu::Adapter> = instance.enumerate_adapters(wgpu::Backends::all()).into_iter()
+            .filter(|adapter| surface.is_none_or(|surface| adapter.is_surface_supported(surface)))
+            .filter(|adapter| adapter.get_info().vendor == vendor_id)
+            .collect();
+        if adapters.is_empty() {
+            warn!("No adapter of vendor {:#06x} was found, using the default adapter.", vendor_id);
+            return None;
+        }
+
+        let preferred_type = if self.discrete {
+            wgpu::DeviceType::DiscreteGpu
+        } else {
+            wgpu::DeviceType::IntegratedGpu
+        };
+        let index = adapters.iter()
+            .position(|adapter| adapter.get_info().device_type == preferred_type)
+            .unwrap_or(0);
+        Some(adapters.swap_remove(index))
+    }
+
+    /// Select the preferred adapter. Adapters cannot be enumerated on the web, so the default adapter is always used.
+    #[cfg(target_arch = "wasm32")]
+    pub fn select_adapter(&self, _instance: &wgpu::Instance, _surface: Option<&Surface>) -> Option<wgpu::Adapter> {
+        None
+    }
+}
+
+/// The size in bytes of the device local memory of the adapter, i.e. its video memory.
+/// The memory is only queried with the Vulkan backend, none is returned for the other backends.
+pub fn adapter_vram(adapter: &wgpu::Adapter) -> Option<u64> {
+    #[cfg(any(windows, all(unix, not(target_os = "emscripten"), not(target_os = "ios"), not(target_os = "macos"))))]
+    {
+        /// The `VK_MEMORY_HEAP_DEVICE_LOCAL_BIT` flag of the memory heaps.
+        const DEVICE_LOCAL_HEAP: u32 = 0x1;
+
+        // The adapter must be a Vulkan one to be accessed as a Vulkan adapter
+        if adapter.get_info().backend != wgpu::Backend::Vulkan {
+            return None;
+        }
+
+        // Safety: the raw instance and physical device are only used to read the memory properties
+        unsafe {
+            adapter.as_hal::<wgpu::hal::api::Vulkan, _, _>(|adapter| adapter.map(|adapter| {
+                let properties = adapter.shared_instance().raw_instance()
+                    .get_physical_device_memory_properties(adapter.raw_physical_device());
+                properties.memory_heaps_as_slice().iter()
+                    .filter(|heap| heap.flags.as_raw() & DEVICE_LOCAL_HEAP != 0)
+                    .map(|heap| heap.size)
+                    .sum()
+            }))
+        }
+    }
+    #[cfg(not(any(windows, all(unix, not(target_os = "emscripten"), not(target_os = "ios"), not(target_os = "macos")))))]
+    {
+        let _ = adapter;
+        None
+    }
+}
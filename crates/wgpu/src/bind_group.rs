@@ -2,7 +2,7 @@
 
 use bevy::{log::Level, utils::tracing::event};
 
-use crate::{buffer::WBuffer, instance::WRenderInstanceData, render_pipeline::WShaderStages, texture::WTexture};
+use crate::{buffer::{TypedPaddedBuffer, WBuffer}, instance::WRenderInstanceData, render_pipeline::WShaderStages, texture::{WSampler, WSamplerDescriptor, WTexture, WTextureFormat, WTextureView}};
 
 /// The wgpu bind group layout builder.
 pub type WgpuBindGroup = wgpu::BindGroup;
@@ -10,6 +10,12 @@ pub type WgpuBindGroup = wgpu::BindGroup;
 /// The buffer binding type.
 pub type WBufferBindingType = wgpu::BufferBindingType;
 
+/// The access mode of a storage texture.
+pub type WStorageTextureAccess = wgpu::StorageTextureAccess;
+
+/// An entry of a bind group layout.
+pub type WBindGroupLayoutEntry = wgpu::BindGroupLayoutEntry;
+
 /// Builder for a bind group layout.
 #[derive(Debug, Clone)]
 pub struct BindGroupLayoutBuilder {
@@ -17,6 +23,11 @@ pub struct BindGroupLayoutBuilder {
 }
 
 impl BindGroupLayoutBuilder {
+    /// Get the entries of the bind group layout.
+    pub fn entries(&self) -> &[WBindGroupLayoutEntry] {
+        &self.layout_entries
+    }
+
     /// Add a buffer to the bind group.
     /// 
     /// # Arguments
@@ -40,6 +51,51 @@ impl BindGroupLayoutBuilder {
         self
     }
 
+    /// Add a buffer with a dynamic offset to the bind group.
+    /// The offset is given when setting the bind group, see `TypedPaddedBuffer::dynamic_offset`.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `binding` - The binding index of the buffer.
+    /// * `visibility` - The shader stages that can access the buffer.
+    /// * `binding_type` - The type of the buffer binding.
+    pub fn add_dynamic_buffer(&mut self, binding: u32, visibility: WShaderStages, binding_type: WBufferBindingType) -> &mut Self {
+        // Create bind group layout
+        self.layout_entries.push(wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility,
+            ty: wgpu::BindingType::Buffer {
+                has_dynamic_offset: true,
+                min_binding_size: None,
+                ty: binding_type,
+            },
+            count: None,
+        });
+
+        self
+    }
+
+    /// Add an array of depth cubemaps to the bind group, e.g. the shadow maps of point lights.
+    ///
+    /// # Arguments
+    /// 
+    /// * `binding` - The binding index of the texture.
+    /// * `visibility` - The shader stages that can access the texture.
+    pub fn add_depth_texture_cube_array_view(&mut self, binding: u32, visibility: WShaderStages) -> &mut Self {
+        self.layout_entries.push(wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility,
+            ty: wgpu::BindingType::Texture {
+                multisampled: false,
+                view_dimension: wgpu::TextureViewDimension::CubeArray,
+                sample_type: wgpu::TextureSampleType::Depth,
+            },
+            count: None
+        });
+
+        self
+    }
+
     /// Add a texture to the bind group.
     /// 
     /// # Arguments
@@ -62,6 +118,170 @@ impl BindGroupLayoutBuilder {
         self
     }
 
+    /// Add a 2D texture array to the bind group.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `binding` - The binding index of the texture.
+    /// * `visibility` - The shader stages that can access the texture.
+    pub fn add_texture_array_view(&mut self, binding: u32, visibility: WShaderStages) -> &mut Self {
+        // Create bind group layout
+        self.layout_entries.push(wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility,
+            ty: wgpu::BindingType::Texture {
+                multisampled: false,
+                view_dimension: wgpu::TextureViewDimension::D2Array,
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+            },
+            count: None
+        });
+
+        self
+    }
+
+    /// Add a 3D texture to the bind group.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `binding` - The binding index of the texture.
+    /// * `visibility` - The shader stages that can access the texture.
+    pub fn add_texture_3d_view(&mut self, binding: u32, visibility: WShaderStages) -> &mut Self {
+        // Create bind group layout
+        self.layout_entries.push(wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility,
+            ty: wgpu::BindingType::Texture {
+                multisampled: false,
+                view_dimension: wgpu::TextureViewDimension::D3,
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+            },
+            count: None
+        });
+
+        self
+    }
+
+    /// Add an unsigned integer texture to the bind group.
+    /// The texture cannot be sampled and must be read with `textureLoad`.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `binding` - The binding index of the texture.
+    /// * `visibility` - The shader stages that can access the texture.
+    pub fn add_uint_texture_view(&mut self, binding: u32, visibility: WShaderStages) -> &mut Self {
+        // Create bind group layout
+        self.layout_entries.push(wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility,
+            ty: wgpu::BindingType::Texture {
+                multisampled: false,
+                view_dimension: wgpu::TextureViewDimension::D2,
+                sample_type: wgpu::TextureSampleType::Uint,
+            },
+            count: None
+        });
+
+        self
+    }
+
+    /// Add a storage texture to the bind group, read or written by the shaders with `textureLoad` and `textureStore`.
+    /// The texture must have the `STORAGE_BINDING` usage. Note that only the `r32float`, `r32sint` and `r32uint` formats
+    /// support the `ReadWrite` access without adapter specific features.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `binding` - The binding index of the texture.
+    /// * `visibility` - The shader stages that can access the texture.
+    /// * `access` - The access mode of the texture.
+    /// * `format` - The format of the texture, which must match the format declared in the shader.
+    pub fn add_storage_texture(&mut self, binding: u32, visibility: WShaderStages, access: WStorageTextureAccess, format: WTextureFormat) -> &mut Self {
+        // Create bind group layout
+        self.layout_entries.push(wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility,
+            ty: wgpu::BindingType::StorageTexture {
+                access,
+                format,
+                view_dimension: wgpu::TextureViewDimension::D2,
+            },
+            count: None
+        });
+
+        self
+    }
+
+    /// Add a storage texture to the bind group as `add_storage_texture`, checking that the texture can be bound to it.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `binding` - The binding index of the texture.
+    /// * `texture` - The texture bound to the binding.
+    /// * `access` - The access mode of the texture.
+    /// * `format` - The format of the texture, which must match the format declared in the shader.
+    /// * `visibility` - The shader stages that can access the texture.
+    /// 
+    /// # Panics
+    /// 
+    /// In debug builds, if the texture was not created with the `STORAGE_BINDING` usage or has another format.
+    pub fn add_storage_texture_for(&mut self, binding: u32, texture: &WTexture, access: WStorageTextureAccess, format: WTextureFormat, visibility: WShaderStages) -> &mut Self {
+        debug_assert!(texture.texture.usage().contains(wgpu::TextureUsages::STORAGE_BINDING),
+            "The texture {} is bound as a storage texture at binding {}, but was not created with the STORAGE_BINDING usage.",
+            texture.label, binding);
+        debug_assert!(texture.format == format,
+            "The texture {} of format {:?} is bound as a storage texture of format {:?} at binding {}.",
+            texture.label, texture.format, format, binding);
+
+        self.add_storage_texture(binding, visibility, access, format)
+    }
+
+    /// Add a cubemap texture to the bind group, sampled with a direction.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `binding` - The binding index of the texture.
+    /// * `visibility` - The shader stages that can access the texture.
+    pub fn add_texture_cube_view(&mut self, binding: u32, visibility: WShaderStages) -> &mut Self {
+        // Create bind group layout
+        self.layout_entries.push(wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility,
+            ty: wgpu::BindingType::Texture {
+                multisampled: false,
+                view_dimension: wgpu::TextureViewDimension::Cube,
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+            },
+            count: None
+        });
+
+        self
+    }
+
+    /// Add a 2D storage texture array to the bind group, see `add_storage_texture`.
+    /// This is also used to write to the faces of a cubemap, which cannot be bound as a storage texture.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `binding` - The binding index of the texture.
+    /// * `visibility` - The shader stages that can access the texture.
+    /// * `access` - Whether the shaders read, write or read and write the texture.
+    /// * `format` - The format of the texture.
+    pub fn add_storage_texture_array(&mut self, binding: u32, visibility: WShaderStages, access: WStorageTextureAccess, format: WTextureFormat) -> &mut Self {
+        // Create bind group layout
+        self.layout_entries.push(wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility,
+            ty: wgpu::BindingType::StorageTexture {
+                access,
+                format,
+                view_dimension: wgpu::TextureViewDimension::D2Array,
+            },
+            count: None
+        });
+
+        self
+    }
+
     /// Add a depth texture to the bind group.
     ///
     /// # Arguments
@@ -84,6 +304,27 @@ impl BindGroupLayoutBuilder {
         self
     }
 
+    /// Add a 2D depth texture array to the bind group, e.g. the cascades of a shadow map.
+    ///
+    /// # Arguments
+    /// 
+    /// * `binding` - The binding index of the texture.
+    /// * `visibility` - The shader stages that can access the texture.
+    pub fn add_depth_texture_array_view(&mut self, binding: u32, visibility: WShaderStages) -> &mut Self {
+        self.layout_entries.push(wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility,
+            ty: wgpu::BindingType::Texture {
+                multisampled: false,
+                view_dimension: wgpu::TextureViewDimension::D2Array,
+                sample_type: wgpu::TextureSampleType::Depth,
+            },
+            count: None
+        });
+
+        self
+    }
+
     /// Add a texture to the bind group.
     /// 
     /// # Arguments
@@ -101,6 +342,32 @@ impl BindGroupLayoutBuilder {
         self
     }
 
+    /// Add a sampler to the bind group, bound separately from the textures it samples.
+    /// The type of the binding is deduced from the description of the sampler.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `binding` - The binding index of the sampler.
+    /// * `visibility` - The shader stages that can access the sampler.
+    /// * `sampler` - The description of the sampler.
+    pub fn add_sampler(&mut self, binding: u32, visibility: WShaderStages, sampler: &WSamplerDescriptor) -> &mut Self {
+        let sampler_type = if sampler.compare.is_some() {
+            wgpu::SamplerBindingType::Comparison
+        } else if sampler.is_filtering() {
+            wgpu::SamplerBindingType::Filtering
+        } else {
+            wgpu::SamplerBindingType::NonFiltering
+        };
+        self.layout_entries.push(wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility,
+            ty: wgpu::BindingType::Sampler(sampler_type),
+            count: None,
+        });
+
+        self
+    }
+
     /// Add a depth texture sampler to the bind group.
     /// 
     /// # Arguments
@@ -158,11 +425,88 @@ impl BindGroupLayout {
 }
 
 
+/// Check that the layout of a bound bind group matches the layout expected by a pipeline.
+/// Only compiled in debug builds, as wgpu also validates the layouts when drawing.
+/// 
+/// # Arguments
+/// 
+/// * `pipeline` - The label of the pipeline.
+/// * `group` - The index of the bind group in the pipeline.
+/// * `expected` - The entries of the bind group layout of the pipeline.
+/// * `actual` - The entries of the layout of the bound bind group.
+/// 
+/// # Panics
+/// 
+/// If a binding is missing from one of the layouts or if the binding types differ.
+#[cfg(debug_assertions)]
+pub fn assert_layout_compatible(pipeline: &str, group: u32, expected: &[WBindGroupLayoutEntry], actual: &[WBindGroupLayoutEntry]) {
+    for expected_entry in expected {
+        match actual.iter().find(|entry| entry.binding == expected_entry.binding) {
+            Some(actual_entry) => if actual_entry.ty != expected_entry.ty {
+                panic!("The bind group {} of the pipeline {} does not match its layout at binding {}: expected {:?}, got {:?}.",
+                    group, pipeline, expected_entry.binding, expected_entry.ty, actual_entry.ty);
+            },
+            None => panic!("The bind group {} of the pipeline {} is missing the binding {}: expected {:?}.",
+                group, pipeline, expected_entry.binding, expected_entry.ty)
+        }
+    }
+    if let Some(extra_entry) = actual.iter().find(|entry| !expected.iter().any(|e| e.binding == entry.binding)) {
+        panic!("The bind group {} of the pipeline {} has the binding {} that is not in its layout: got {:?}.",
+            group, pipeline, extra_entry.binding, extra_entry.ty);
+    }
+}
+
 pub type WBindGroupEntry<'a> = wgpu::BindGroupEntry<'a>;
 
 /// Structure for a bind group.
-pub struct BindGroup;
-impl BindGroup {
+/// The bind group created with `BindGroup::new` keeps its layout and entries, so that a single binding can be
+/// replaced without describing the whole bind group again. Use `BindGroup::build` to only create the wgpu bind group.
+pub struct BindGroup<'a> {
+    pub label: String,
+    pub bind_group: wgpu::BindGroup,
+    layout: &'a wgpu::BindGroupLayout,
+    entries: Vec<wgpu::BindGroupEntry<'a>>,
+}
+impl<'a> BindGroup<'a> {
+    /// Create a bind group keeping its layout and entries.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `label` - The label of the bind group.
+    /// * `instance` - Game instance.
+    /// * `layout` - The built layout of the bind group.
+    /// * `entries` - The entries of the bind group.
+    pub fn new(label: &str, instance: &WRenderInstanceData, layout: &'a wgpu::BindGroupLayout, entries: Vec<wgpu::BindGroupEntry<'a>>) -> Self {
+        let bind_group = Self::build(label, instance, layout, &entries);
+
+        BindGroup {
+            label: label.to_string(),
+            bind_group,
+            layout,
+            entries,
+        }
+    }
+
+    /// Replace the buffer of a binding and recreate the bind group with the other entries unchanged.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `instance` - Game instance.
+    /// * `binding` - The binding index of the buffer.
+    /// * `buffer` - The new buffer of the binding.
+    /// 
+    /// # Panics
+    /// 
+    /// If the bind group has no entry at the binding index.
+    pub fn update_buffer(&mut self, instance: &WRenderInstanceData, binding: u32, buffer: &'a WBuffer) {
+        let entry = self.entries.iter_mut()
+            .find(|entry| entry.binding == binding)
+            .unwrap_or_else(|| panic!("The bind group {} has no entry at binding {}.", self.label, binding));
+        *entry = Self::buffer(binding, buffer);
+
+        self.bind_group = Self::build(&self.label, instance, self.layout, &self.entries);
+    }
+
     pub fn build(label: &str, instance: &WRenderInstanceData, layout: &wgpu::BindGroupLayout, entries: &Vec<wgpu::BindGroupEntry>) -> wgpu::BindGroup {
         event!(Level::TRACE, "Creating bind group: {}.", label);
 
@@ -189,6 +533,24 @@ impl BindGroup {
         }
     }
 
+    /// Add a single element of a padded buffer to the bind group.
+    /// The element is selected by the dynamic offset given when setting the bind group.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `binding` - The binding index of the buffer.
+    /// * `buffer` - The padded buffer to add to the bind group.
+    pub fn padded_buffer<T: bytemuck::Pod>(binding: u32, buffer: &TypedPaddedBuffer<T>) -> wgpu::BindGroupEntry<'_> {
+        wgpu::BindGroupEntry {
+            binding,
+            resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                buffer: &buffer.buffer.buffer,
+                offset: 0,
+                size: wgpu::BufferSize::new(std::mem::size_of::<T>() as u64),
+            }),
+        }
+    }
+
     /// Add a texture view to the bind group.
     /// 
     /// # Arguments
@@ -201,6 +563,54 @@ impl BindGroup {
             resource: wgpu::BindingResource::TextureView(&texture.view),
         }
     }
+
+    /// Add a view of a texture to the bind group, such as a single layer or mip level of the texture.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `binding` - The binding index of the texture.
+    /// * `view` - The view of the texture to add to the bind group, see `WTexture::view_layer` and `WTexture::view_mip_range`.
+    pub fn view(binding: u32, view: &WTextureView) -> wgpu::BindGroupEntry<'_> {
+        wgpu::BindGroupEntry {
+            binding,
+            resource: wgpu::BindingResource::TextureView(view),
+        }
+    }
+
+    /// Add a storage texture view to the bind group.
+    /// Use `WTexture::view_mip_range` to bind a single mip level of the texture.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `binding` - The binding index of the texture.
+    /// * `view` - The view of the texture to add to the bind group.
+    pub fn storage_texture(binding: u32, view: &WTextureView) -> wgpu::BindGroupEntry<'_> {
+        wgpu::BindGroupEntry {
+            binding,
+            resource: wgpu::BindingResource::TextureView(view),
+        }
+    }
+
+    /// Add the view of a storage texture to the bind group, to be read and written by the shaders.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `binding` - The binding index of the texture.
+    /// * `texture` - The texture to add to the bind group.
+    /// 
+    /// # Panics
+    /// 
+    /// In debug builds, if the texture was not created with the `STORAGE_BINDING` usage.
+    pub fn storage_texture_view(binding: u32, texture: &WTexture) -> wgpu::BindGroupEntry<'_> {
+        debug_assert!(texture.texture.usage().contains(wgpu::TextureUsages::STORAGE_BINDING),
+            "The texture {} is bound as a storage texture at binding {}, but was not created with the STORAGE_BINDING usage.",
+            texture.label, binding);
+
+        wgpu::BindGroupEntry {
+            binding,
+            resource: wgpu::BindingResource::TextureView(&texture.view),
+        }
+    }
     
     /// Add a texture sampler to the bind group.
     /// 
@@ -214,4 +624,17 @@ impl BindGroup {
             resource: wgpu::BindingResource::Sampler(&texture.sampler),
         }
     }
+
+    /// Add a sampler to the bind group, created with `WTexture::create_sampler`.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `binding` - The binding index of the sampler.
+    /// * `sampler` - The sampler to add to the bind group.
+    pub fn sampler(binding: u32, sampler: &WSampler) -> wgpu::BindGroupEntry<'_> {
+        wgpu::BindGroupEntry {
+            binding,
+            resource: wgpu::BindingResource::Sampler(sampler),
+        }
+    }
 }
@@ -1,6 +1,6 @@
 //! Contains the buffer struct and its implementations.
 
-use std::fmt::Formatter;
+use std::{fmt::Formatter, marker::PhantomData, sync::{Arc, Mutex}};
 use bevy::{log::Level, utils::tracing::event};
 use wgpu::{util::DeviceExt, BufferView};
 
@@ -26,12 +26,18 @@ pub type BufferViewMut<'a> = wgpu::BufferViewMut<'a>;
 /// // Copy data to the buffer
 /// buffer.copy_from_buffer(&instance, &buffer);
 /// 
+/// // Copy the first 256 bytes of another buffer to the buffer starting at 64 bytes
+/// buffer.copy_range(&instance, &other, 0, 64, 256);
+/// 
 /// // Copy data to the buffer from a texture
 /// buffer.copy_from_texture(&instance, &texture);
 /// 
 /// // Write data to the buffer starting at 16 bytes
 /// buffer.write(&instance, bytemuck::cast_slice(&[data]), 16);
 /// 
+/// // Grow the buffer to 2048 bytes, keeping its content
+/// buffer.resize(&instance, 2048, true);
+/// 
 /// // Map the buffer and read the data
 /// buffer.map_read(&instance, |data| {
 ///   let data = bytemuck::cast_slice(data);
@@ -43,6 +49,10 @@ pub type BufferViewMut<'a> = wgpu::BufferViewMut<'a>;
 ///   let data = bytemuck::cast_slice_mut(data);
 ///   // ...
 /// });
+/// 
+/// // Write the data without blocking, then poll the write in the next frames
+/// let pending = buffer.map_write_async(data);
+/// while buffer.poll_write_async(&instance, &pending) == WBufferMapStatus::Pending {}
 /// ```
 pub struct WBuffer {
     pub label: String,
@@ -130,6 +140,38 @@ impl WBuffer {
         command_buffer.submit(instance);
     }
 
+    /// Copy a range of another buffer to the buffer.
+    /// Note that the buffer must have the COPY_DST usage, and the source buffer the COPY_SRC usage.
+    /// The offsets and the size must be multiples of 4 bytes.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `instance` - The render instance.
+    /// * `src` - The buffer to copy from.
+    /// * `src_offset_bytes` - The offset of the range in the source buffer.
+    /// * `dst_offset_bytes` - The offset at which the range is copied in the buffer.
+    /// * `size_bytes` - The size of the range.
+    pub fn copy_range(&self, instance: &WRenderInstanceData<'_>, src: &WBuffer, src_offset_bytes: u64, dst_offset_bytes: u64, size_bytes: u64) {
+        event!(Level::TRACE, "Copying {} bytes from buffer {} to buffer {}.", size_bytes, src.label, self.label);
+        debug_assert!(src_offset_bytes + size_bytes <= src.buffer.size(),
+            "The range {}..{} exceeds the size {} of the source buffer {}.",
+            src_offset_bytes, src_offset_bytes + size_bytes, src.buffer.size(), src.label);
+        debug_assert!(dst_offset_bytes + size_bytes <= self.buffer.size(),
+            "The range {}..{} exceeds the size {} of the destination buffer {}.",
+            dst_offset_bytes, dst_offset_bytes + size_bytes, self.buffer.size(), self.label);
+
+        // Create command encoder
+        let mut command_buffer = WCommandBuffer::new(
+            instance,
+            &format!("copy-range-from-{}-to-{}", src.label, self.label));
+
+        // Copy the range
+        command_buffer.copy_buffer_range(src, src_offset_bytes, self, dst_offset_bytes, size_bytes);
+
+        // Submit commands
+        command_buffer.submit(instance);
+    }
+
     /// Copy data to the buffer from a texture.
     /// Note that the buffer must have the COPY_DST usage.
     /// 
@@ -169,6 +211,96 @@ impl WBuffer {
             content);
     }
 
+    /// Resize the buffer by allocating a new buffer of the given size with the same usage.
+    /// The bind groups created with the previous buffer keep referencing it, and must be created again.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `instance` - The render instance.
+    /// * `new_size` - The new size of the buffer in bytes.
+    /// * `keep_content` - Whether to copy the start of the previous content to the new buffer, up to the smallest of the two sizes.
+    ///   This requires the COPY_SRC and COPY_DST usages, the content is discarded otherwise.
+    /// 
+    /// # Returns
+    /// 
+    /// Whether the buffer was reallocated, false if it already has the given size.
+    pub fn resize(&mut self, instance: &WRenderInstanceData, new_size: usize, keep_content: bool) -> bool {
+        let old_size = self.buffer.size();
+        if new_size as u64 == old_size {
+            return false;
+        }
+        event!(Level::TRACE, "Resizing buffer {} from {} to {} bytes.", self.label, old_size, new_size);
+
+        // Create the new buffer
+        let usage = self.buffer.usage();
+        let buffer = WBuffer::new(instance, &self.label, new_size, usage, None);
+
+        // Copy the content, the size of a copy must be a multiple of 4 bytes
+        if keep_content {
+            if usage.contains(BufferUsage::COPY_SRC | BufferUsage::COPY_DST) {
+                let copy_size = old_size.min(new_size as u64) / wgpu::COPY_BUFFER_ALIGNMENT * wgpu::COPY_BUFFER_ALIGNMENT;
+                if copy_size > 0 {
+                    let mut command_buffer = WCommandBuffer::new(instance, &format!("resize-{}", self.label));
+                    command_buffer.copy_buffer_range(self, 0, &buffer, 0, copy_size);
+                    command_buffer.submit(instance);
+                }
+            } else {
+                event!(Level::WARN, "Cannot keep the content of buffer {} when resizing it without the COPY_SRC and COPY_DST usages.", self.label);
+            }
+        }
+
+        // Replace the buffer, the previous one is destroyed once the copy is done
+        self.buffer = buffer.buffer;
+        true
+    }
+
+    /// Compute the stride of the elements of type `T` in a buffer used with dynamic uniform offsets.
+    /// This is the size of `T` aligned up to the `min_uniform_buffer_offset_alignment` of the device (typically 256 bytes).
+    /// 
+    /// # Arguments
+    /// 
+    /// * `instance` - The render instance.
+    pub fn padded_stride<T>(instance: &WRenderInstanceData) -> usize {
+        let alignment = instance.device.limits().min_uniform_buffer_offset_alignment as usize;
+        std::mem::size_of::<T>().div_ceil(alignment) * alignment
+    }
+
+    /// Compute the length of a row of pixels in a buffer receiving a texture copy.
+    /// This is the size of a row aligned up to `wgpu::COPY_BYTES_PER_ROW_ALIGNMENT` (256 bytes).
+    /// 
+    /// # Arguments
+    /// 
+    /// * `width` - The width of the texture in pixels.
+    /// * `bytes_per_pixel` - The size of a pixel of the texture in bytes.
+    pub fn padded_bytes_per_row(width: u32, bytes_per_pixel: u32) -> u32 {
+        (width * bytes_per_pixel).next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT)
+    }
+
+    /// Write elements to the buffer, each element being aligned to the uniform buffer offset alignment.
+    /// The element `i` of `data` is written at `(element_index + i) * padded_stride` and the padding is filled with zeros.
+    /// Note that the buffer must have the COPY_DST usage.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `instance` - The render instance.
+    /// * `data` - The elements to write to the buffer.
+    /// * `element_index` - The index of the first element to write.
+    pub fn write_padded<T: bytemuck::Pod>(&mut self, instance: &WRenderInstanceData, data: &[T], element_index: usize) {
+        event!(Level::TRACE, "Writing padded data to buffer {}.", self.label);
+
+        // Pad the elements
+        let stride = Self::padded_stride::<T>(instance);
+        let mut content = vec![0u8; stride * data.len()];
+        for (i, element) in data.iter().enumerate() {
+            content[i * stride..i * stride + std::mem::size_of::<T>()].copy_from_slice(bytemuck::bytes_of(element));
+        }
+
+        instance.queue.write_buffer(
+            &self.buffer,
+            (element_index * stride) as u64,
+            &content);
+    }
+
     /// Map the buffer.
     /// The access to the buffer is read-only.
     /// This will wait for the buffer to be mapped.
@@ -228,4 +360,309 @@ impl WBuffer {
         // Unmap buffer
         self.buffer.unmap();
     }
+
+    /// Start mapping the buffer for writing without waiting for the mapping.
+    /// The data is copied into the buffer by `poll_write_async` once the buffer is mapped.
+    /// Note that the buffer must have the MAP_WRITE usage, and must not be used until the write is done.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `data` - The data to write at the start of the buffer.
+    /// 
+    /// # Returns
+    /// 
+    /// The pending write, to give to `poll_write_async`.
+    pub fn map_write_async(&self, data: Vec<u8>) -> WPendingBufferWrite {
+        event!(Level::TRACE, "Mapping buffer {} for writing asynchronously.", self.label);
+
+        let state = self.map_async_state(wgpu::MapMode::Write, data.len() as u64);
+        WPendingBufferWrite { state, data }
+    }
+
+    /// Poll a pending write started by `map_write_async` without blocking.
+    /// If the buffer is mapped, the data is copied into the buffer and the buffer is unmapped.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `instance` - The render instance.
+    /// * `pending` - The pending write.
+    /// 
+    /// # Returns
+    /// 
+    /// The status of the write.
+    pub fn poll_write_async(&self, instance: &WRenderInstanceData, pending: &WPendingBufferWrite) -> WBufferMapStatus {
+        let status = self.poll_map_state(instance, &pending.state);
+        if status == WBufferMapStatus::Done {
+            // Copy the data and unmap the buffer
+            self.buffer.slice(..pending.data.len() as u64).get_mapped_range_mut()
+                .copy_from_slice(&pending.data);
+            self.buffer.unmap();
+        }
+        status
+    }
+
+    /// Start mapping the first `size` bytes of the buffer for reading without waiting for the mapping.
+    /// The data is read by `poll_read_async` once the buffer is mapped.
+    /// Note that the buffer must have the MAP_READ usage, and must not be used until the read is done.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `size` - The number of bytes to read, must be a multiple of 4.
+    /// 
+    /// # Returns
+    /// 
+    /// The pending read, to give to `poll_read_async`.
+    pub fn map_read_async(&self, size: usize) -> WPendingBufferRead {
+        event!(Level::TRACE, "Mapping buffer {} for reading asynchronously.", self.label);
+
+        let state = self.map_async_state(wgpu::MapMode::Read, size as u64);
+        WPendingBufferRead { state, size: size as u64 }
+    }
+
+    /// Poll a pending read started by `map_read_async` without blocking.
+    /// If the buffer is mapped, the callback is called with the mapped data and the buffer is unmapped.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `instance` - The render instance.
+    /// * `pending` - The pending read.
+    /// * `callback` - A closure that takes a reference to the mapped data.
+    /// 
+    /// # Returns
+    /// 
+    /// The status of the read.
+    pub fn poll_read_async(&self, instance: &WRenderInstanceData, pending: &WPendingBufferRead, callback: impl FnOnce(BufferView)) -> WBufferMapStatus {
+        let status = self.poll_map_state(instance, &pending.state);
+        if status == WBufferMapStatus::Done {
+            // Read the data and unmap the buffer
+            callback(self.buffer.slice(..pending.size).get_mapped_range());
+            self.buffer.unmap();
+        }
+        status
+    }
+
+    /// Start mapping the first `size` bytes of the buffer, and return the state updated once the buffer is mapped.
+    fn map_async_state(&self, mode: wgpu::MapMode, size: u64) -> WMapState {
+        let state = WMapState::default();
+        let callback_state = state.clone();
+        self.buffer.slice(..size).map_async(mode,
+            move |r| *callback_state.lock().unwrap() = Some(r));
+        state
+    }
+
+    /// Poll the device and get the status of a mapping started by `map_async_state`.
+    fn poll_map_state(&self, instance: &WRenderInstanceData, state: &WMapState) -> WBufferMapStatus {
+        // Process the mapping callbacks
+        instance.device.poll(wgpu::Maintain::Poll);
+
+        match state.lock().unwrap().as_ref() {
+            Some(Ok(())) => WBufferMapStatus::Done,
+            Some(Err(e)) => {
+                event!(Level::ERROR, "Failed to map buffer {}: {:?}.", self.label, e);
+                WBufferMapStatus::Failed
+            },
+            None => WBufferMapStatus::Pending
+        }
+    }
+}
+
+/// The result of an asynchronous mapping, set by the mapping callback.
+type WMapState = Arc<Mutex<Option<Result<(), wgpu::BufferAsyncError>>>>;
+
+/// A write started by `WBuffer::map_write_async`, waiting for the buffer to be mapped.
+pub struct WPendingBufferWrite {
+    state: WMapState,
+    data: Vec<u8>,
+}
+
+/// A read started by `WBuffer::map_read_async`, waiting for the buffer to be mapped.
+pub struct WPendingBufferRead {
+    state: WMapState,
+    size: u64,
+}
+
+/// The status of an asynchronous mapping started by `WBuffer::map_write_async` or `WBuffer::map_read_async`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WBufferMapStatus {
+    /// The buffer is not mapped yet.
+    Pending,
+    /// The data was written or read and the buffer is unmapped.
+    Done,
+    /// The buffer could not be mapped.
+    Failed,
+}
+
+
+
+/// A buffer of elements of type `T` used with dynamic uniform offsets.
+/// Each element is aligned to the `min_uniform_buffer_offset_alignment` of the device.
+/// 
+/// # Example
+/// 
+/// ```
+/// # use wde_wgpu::{bind_group::WgpuBindGroup, buffer::{BufferUsage, TypedPaddedBuffer}, instance::WRenderInstanceData, render_pass::WRenderPass};
+/// # #[repr(C)]
+/// # #[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+/// # struct Data([f32; 4]);
+/// # fn example<'a>(instance: &WRenderInstanceData, render_pass: &mut WRenderPass<'a>, bind_group: &'a WgpuBindGroup, data: Data) {
+/// // Create a buffer of 16 elements
+/// let mut buffer = TypedPaddedBuffer::<Data>::new(&instance, "Buffer label", 16, BufferUsage::UNIFORM | BufferUsage::COPY_DST);
+/// 
+/// // Write the element 3
+/// buffer.write(&instance, &[data], 3);
+/// 
+/// // Bind the element 3
+/// render_pass.set_bind_group_with_offsets(0, &bind_group, &[buffer.dynamic_offset(3)]);
+/// # }
+/// ```
+pub struct TypedPaddedBuffer<T: bytemuck::Pod> {
+    pub buffer: WBuffer,
+    /// The padded stride of the elements in bytes.
+    pub stride: usize,
+    /// The maximum number of elements of the buffer.
+    pub capacity: usize,
+    phantom: PhantomData<T>,
+}
+
+impl<T: bytemuck::Pod> TypedPaddedBuffer<T> {
+    /// Create a new buffer that can hold `capacity` elements.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `instance` - The render instance.
+    /// * `label` - The label of the buffer.
+    /// * `capacity` - The maximum number of elements of the buffer.
+    /// * `usage` - The usage of the buffer (uniform, storage).
+    pub fn new(instance: &WRenderInstanceData, label: &str, capacity: usize, usage: BufferUsage) -> Self {
+        let stride = WBuffer::padded_stride::<T>(instance);
+        Self {
+            buffer: WBuffer::new(instance, label, stride * capacity, usage, None),
+            stride,
+            capacity,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Write elements to the buffer starting at the element `element_index`.
+    /// Note that the buffer must have the COPY_DST usage.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `instance` - The render instance.
+    /// * `data` - The elements to write to the buffer.
+    /// * `element_index` - The index of the first element to write.
+    pub fn write(&mut self, instance: &WRenderInstanceData, data: &[T], element_index: usize) {
+        if element_index + data.len() > self.capacity {
+            event!(Level::ERROR, "Writing {} elements at index {} overflows the buffer {} of capacity {}.",
+                data.len(), element_index, self.buffer.label, self.capacity);
+            return;
+        }
+        self.buffer.write_padded(instance, data, element_index);
+    }
+
+    /// Get the dynamic offset of the element `element_index` in bytes.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `element_index` - The index of the element.
+    pub fn dynamic_offset(&self, element_index: usize) -> u32 {
+        (element_index * self.stride) as u32
+    }
+}
+
+
+
+/// A buffer of elements of type `T`, tightly packed.
+/// The data is written and read as elements, without casting it to bytes at each call.
+/// 
+/// # Example
+/// 
+/// ```
+/// # use wde_wgpu::{buffer::{BufferUsage, TypedBuffer}, instance::WRenderInstanceData};
+/// # #[repr(C)]
+/// # #[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+/// # struct Data([f32; 4]);
+/// # fn example(instance: &WRenderInstanceData, data_3: Data, data_4: Data) {
+/// // Create a buffer of 16 elements
+/// let mut buffer = TypedBuffer::<Data>::new(&instance, "Buffer label", 16, BufferUsage::STORAGE | BufferUsage::COPY_DST);
+/// 
+/// // Write two elements starting at the element 3
+/// buffer.write_slice(&instance, &[data_3, data_4], 3);
+/// 
+/// // Map the buffer and read the elements
+/// buffer.map_read_typed(&instance, |elements: &[Data]| {
+///   // ...
+/// });
+/// # }
+/// ```
+pub struct TypedBuffer<T: bytemuck::Pod> {
+    pub buffer: WBuffer,
+    /// The maximum number of elements of the buffer.
+    capacity: usize,
+    phantom: PhantomData<T>,
+}
+
+impl<T: bytemuck::Pod> TypedBuffer<T> {
+    /// Create a new buffer that can hold `capacity` elements.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `instance` - The render instance.
+    /// * `label` - The label of the buffer.
+    /// * `capacity` - The maximum number of elements of the buffer.
+    /// * `usage` - The usage of the buffer (vertex, index, uniform, storage).
+    pub fn new(instance: &WRenderInstanceData, label: &str, capacity: usize, usage: BufferUsage) -> Self {
+        Self {
+            buffer: WBuffer::new(instance, label, std::mem::size_of::<T>() * capacity, usage, None),
+            capacity,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Get the maximum number of elements of the buffer.
+    pub fn len(&self) -> usize {
+        self.capacity
+    }
+
+    /// Whether the buffer cannot hold any element.
+    pub fn is_empty(&self) -> bool {
+        self.capacity == 0
+    }
+
+    /// Write elements to the buffer starting at the element `offset_elements`.
+    /// Note that the buffer must have the COPY_DST usage.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `instance` - The render instance.
+    /// * `data` - The elements to write to the buffer.
+    /// * `offset_elements` - The index of the first element to write.
+    pub fn write_slice(&mut self, instance: &WRenderInstanceData, data: &[T], offset_elements: usize) {
+        if offset_elements + data.len() > self.capacity {
+            event!(Level::ERROR, "Writing {} elements at index {} overflows the buffer {} of capacity {}.",
+                data.len(), offset_elements, self.buffer.label, self.capacity);
+            return;
+        }
+        self.buffer.write(instance, bytemuck::cast_slice(data), offset_elements * std::mem::size_of::<T>());
+    }
+
+    /// Map the buffer and read its elements.
+    /// This will wait for the buffer to be mapped.
+    /// Note that the buffer must have the MAP_READ usage.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `instance` - The render instance.
+    /// * `callback` - A closure that takes the elements of the buffer.
+    pub fn map_read_typed(&self, instance: &WRenderInstanceData, callback: impl FnOnce(&[T])) {
+        self.buffer.map_read(instance, |view| {
+            let data = &view[..self.capacity * std::mem::size_of::<T>()];
+
+            // The mapped data may not be aligned for `T`, in which case it is copied
+            match bytemuck::try_cast_slice::<u8, T>(data) {
+                Ok(elements) => callback(elements),
+                Err(_) => callback(&bytemuck::pod_collect_to_vec::<u8, T>(data))
+            }
+        });
+    }
 }
@@ -2,7 +2,7 @@
 use bevy::{log::Level, utils::tracing::event};
 use wgpu::Texture;
 
-use crate::{buffer::WBuffer, compute_pass::WComputePass, instance::WRenderInstanceData, texture::WTextureView};
+use crate::{buffer::WBuffer, compute_pass::WComputePass, instance::WRenderInstanceData, query_set::{WOcclusionQuerySet, WPipelineStatisticsQuerySet}, texture::WTextureView};
 
 use super::render_pass::WRenderPass;
 
@@ -30,6 +30,8 @@ pub struct RenderPassDepth<'pass> {
     pub load_operation: WLoadOp<f32>,
     /// The depth operation when storing the texture. By default, store the texture.
     pub store_operation: WStoreOp,
+    /// The stencil operations of a depth/stencil texture. By default, the stencil is read only.
+    pub stencil_operations: Option<Operations<u32>>,
 }
 impl Default for RenderPassDepth<'_> {
     fn default() -> Self {
@@ -37,6 +39,7 @@ impl Default for RenderPassDepth<'_> {
             texture: None,
             load_operation: wgpu::LoadOp::Clear(1.0),
             store_operation: wgpu::StoreOp::Store,
+            stencil_operations: None,
         }
     }
 }
@@ -49,6 +52,9 @@ pub struct RenderPassColorAttachment<'pass> {
     pub load: WLoadOp<WColor>,
     /// The color store operation. By default, store the texture.
     pub store: WStoreOp,
+    /// The single sampled texture into which a multisampled color texture is resolved at the end of the pass.
+    /// By default, the color texture is not multisampled and not resolved.
+    pub msaa_resolve_target: Option<&'pass WTextureView>,
 }
 impl Default for RenderPassColorAttachment<'_> {
     fn default() -> Self {
@@ -57,6 +63,7 @@ impl Default for RenderPassColorAttachment<'_> {
             texture: None,
             load: wgpu::LoadOp::Clear(WColor { r: color_srgb, g: color_srgb, b: color_srgb, a: 1.0 }),
             store: wgpu::StoreOp::Store,
+            msaa_resolve_target: None,
         }
     }
 }
@@ -68,6 +75,8 @@ pub struct RenderPassBuilder<'pass> {
     depth: RenderPassDepth<'pass>,
     /// The color attachments of the render pass. By default, no color attachments.
     color_attachments: Vec<RenderPassColorAttachment<'pass>>,
+    /// The occlusion queries of the render pass. By default, no occlusion queries.
+    occlusion_query_set: Option<&'pass WOcclusionQuerySet>,
 }
 impl<'pass> RenderPassBuilder<'pass> {
     /// Set the depth texture of the render pass.
@@ -80,6 +89,9 @@ impl<'pass> RenderPassBuilder<'pass> {
     }
 
     /// Add a color attachment to the render pass.
+    /// Multiple attachments can be added to write to multiple render targets in a single pass: they are written in order
+    /// by the `@location(0)`, `@location(1)`, ... outputs of the fragment shader, and the render targets of the pipeline
+    /// must list their formats in the same order.
     /// 
     /// # Arguments
     /// 
@@ -87,6 +99,15 @@ impl<'pass> RenderPassBuilder<'pass> {
     pub fn add_color_attachment(&mut self, attachment: RenderPassColorAttachment<'pass>) {
         self.color_attachments.push(attachment);
     }
+
+    /// Set the occlusion query set written by `begin_occlusion_query` and `end_occlusion_query` in the render pass.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `query_set` - The occlusion query set.
+    pub fn set_occlusion_query_set(&mut self, query_set: &'pass WOcclusionQuerySet) {
+        self.occlusion_query_set = Some(query_set);
+    }
 }
 
 
@@ -116,7 +137,27 @@ impl<'pass> RenderPassBuilder<'pass> {
 /// ```
 pub struct WCommandBuffer {
     pub label: String,
-    encoder: wgpu::CommandEncoder,
+    encoder: Option<wgpu::CommandEncoder>,
+    #[cfg(feature = "gpu-profiling")]
+    timestamps: Option<WTimestampQueries>,
+}
+
+/// The maximum number of timestamp ranges recorded by a command buffer.
+pub const MAX_TIMESTAMPS: u32 = 32;
+
+/// A range of the gpu timeline started by `WCommandBuffer::begin_timestamp`.
+/// Without the `gpu-profiling` feature, the handle is a no-op.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WTimestampHandle(u32);
+
+/// The timestamp queries of a command buffer and the buffers into which they are resolved.
+#[cfg(feature = "gpu-profiling")]
+struct WTimestampQueries {
+    query_set: wgpu::QuerySet,
+    resolve_buffer: wgpu::Buffer,
+    readback_buffer: wgpu::Buffer,
+    labels: Vec<String>,
+    resolved: bool,
 }
 
 impl std::fmt::Debug for WCommandBuffer {
@@ -142,9 +183,47 @@ impl WCommandBuffer {
             label: Some(format!("{}-command-encoder", label).as_str()),
         });
 
+        // Create the timestamp queries if supported by the device
+        #[cfg(feature = "gpu-profiling")]
+        let timestamps = instance.has_feature(wgpu::Features::TIMESTAMP_QUERY | wgpu::Features::TIMESTAMP_QUERY_INSIDE_ENCODERS)
+            .then(|| {
+                let size = 2 * MAX_TIMESTAMPS as u64 * std::mem::size_of::<u64>() as u64;
+                WTimestampQueries {
+                    query_set: instance.device.create_query_set(&wgpu::QuerySetDescriptor {
+                        label: Some(format!("{}-timestamps", label).as_str()),
+                        ty: wgpu::QueryType::Timestamp,
+                        count: 2 * MAX_TIMESTAMPS,
+                    }),
+                    resolve_buffer: instance.device.create_buffer(&wgpu::BufferDescriptor {
+                        label: Some(format!("{}-timestamps-resolve", label).as_str()),
+                        size,
+                        usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+                        mapped_at_creation: false,
+                    }),
+                    readback_buffer: instance.device.create_buffer(&wgpu::BufferDescriptor {
+                        label: Some(format!("{}-timestamps-readback", label).as_str()),
+                        size,
+                        usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+                        mapped_at_creation: false,
+                    }),
+                    labels: Vec::new(),
+                    resolved: false,
+                }
+            });
+
         Self {
             label: label.to_string(),
-            encoder: command_encoder,
+            encoder: Some(command_encoder),
+            #[cfg(feature = "gpu-profiling")]
+            timestamps,
+        }
+    }
+
+    /// Get the encoder of the command buffer, which is only taken when submitting.
+    fn encoder_mut(&mut self) -> &mut wgpu::CommandEncoder {
+        match self.encoder.as_mut() {
+            Some(encoder) => encoder,
+            None => panic!("The command buffer {} was already submitted.", self.label)
         }
     }
 
@@ -171,14 +250,17 @@ impl WCommandBuffer {
                     load: builder.depth.load_operation,
                     store: builder.depth.store_operation
                 }),
-                stencil_ops: None,
+                stencil_ops: builder.depth.stencil_operations.as_ref().map(|operations| wgpu::Operations {
+                    load: operations.load,
+                    store: operations.store
+                }),
             });
         }
 
         let color_attachments = builder.color_attachments.iter().map(|attachment| {
             attachment.texture.map(|texture| wgpu::RenderPassColorAttachment {
                 view: texture,
-                resolve_target: None,
+                resolve_target: attachment.msaa_resolve_target,
                 ops: wgpu::Operations {
                     load: attachment.load,
                     store: attachment.store
@@ -186,12 +268,12 @@ impl WCommandBuffer {
             })
         }).collect::<Vec<_>>();
 
-        let render_pass = self.encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        let render_pass = self.encoder_mut().begin_render_pass(&wgpu::RenderPassDescriptor {
             label: Some(format!("{}-render-pass", label).as_str()),
             color_attachments: &color_attachments,
             depth_stencil_attachment: depth_attachment,
             timestamp_writes: None,
-            occlusion_query_set: None,
+            occlusion_query_set: builder.occlusion_query_set.map(|query_set| &query_set.query_set),
         });
 
         WRenderPass::new(label, render_pass)
@@ -204,7 +286,7 @@ impl WCommandBuffer {
     /// * `label` - The label of the compute pass.
     pub fn create_compute_pass<'pass>(&'pass mut self, label: &str) -> WComputePass<'pass> {
         event!(Level::TRACE, "Creating a compute pass {}.", label);
-        let compute_pass = self.encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+        let compute_pass = self.encoder_mut().begin_compute_pass(&wgpu::ComputePassDescriptor {
             label: Some(format!("{}-compute-pass", label).as_str()),
             timestamp_writes: None
         });
@@ -212,14 +294,152 @@ impl WCommandBuffer {
         WComputePass::new(label, compute_pass)
     }
 
+    /// Start measuring the gpu duration of the commands recorded until `end_timestamp` is called.
+    /// The durations are read with `read_timestamps` once the command buffer is submitted.
+    /// Without the `gpu-profiling` feature or the timestamp query features, nothing is recorded.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `label` - The label of the measured range.
+    /// 
+    /// # Returns
+    /// 
+    /// The handle to pass to `end_timestamp`.
+    #[allow(unused_variables)]
+    pub fn begin_timestamp(&mut self, label: &str) -> WTimestampHandle {
+        #[cfg(feature = "gpu-profiling")]
+        if let (Some(timestamps), Some(encoder)) = (self.timestamps.as_mut(), self.encoder.as_mut()) {
+            let index = timestamps.labels.len() as u32;
+            if index >= MAX_TIMESTAMPS {
+                bevy::log::warn!("Command buffer {} has more than {} timestamps, {} is not measured.", self.label, MAX_TIMESTAMPS, label);
+                return WTimestampHandle(u32::MAX);
+            }
+            encoder.write_timestamp(&timestamps.query_set, 2 * index);
+            timestamps.labels.push(label.to_string());
+            return WTimestampHandle(index);
+        }
+        WTimestampHandle(u32::MAX)
+    }
+
+    /// Stop measuring the gpu duration of a range started by `begin_timestamp`.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `handle` - The handle returned by `begin_timestamp`.
+    #[allow(unused_variables)]
+    pub fn end_timestamp(&mut self, handle: WTimestampHandle) {
+        #[cfg(feature = "gpu-profiling")]
+        if let (Some(timestamps), Some(encoder)) = (self.timestamps.as_ref(), self.encoder.as_mut()) {
+            if handle.0 < timestamps.labels.len() as u32 {
+                encoder.write_timestamp(&timestamps.query_set, 2 * handle.0 + 1);
+            }
+        }
+    }
+
+    /// Read the gpu durations of the timestamp ranges once the command buffer is submitted.
+    /// This blocks until the gpu has executed the command buffer, so it is only available with the `gpu-profiling` feature.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `instance` - The render instance.
+    /// 
+    /// # Returns
+    /// 
+    /// The label and the duration of each range, in the order they were started.
+    /// Empty if the command buffer is not submitted or if the device does not support timestamp queries.
+    #[cfg(feature = "gpu-profiling")]
+    pub fn read_timestamps(&self, instance: &WRenderInstanceData) -> Vec<(String, std::time::Duration)> {
+        let timestamps = match self.timestamps.as_ref() {
+            Some(timestamps) if timestamps.resolved => timestamps,
+            _ => return Vec::new()
+        };
+
+        // Map the readback buffer
+        let size = 2 * timestamps.labels.len() as u64 * std::mem::size_of::<u64>() as u64;
+        let slice = timestamps.readback_buffer.slice(0..size);
+        slice.map_async(wgpu::MapMode::Read, |_| {});
+        instance.device.poll(wgpu::Maintain::Wait);
+
+        // Convert the ticks to durations
+        let period = instance.queue.get_timestamp_period() as f64;
+        let durations = {
+            let data = slice.get_mapped_range();
+            let ticks: &[u64] = bytemuck::cast_slice(&data);
+            timestamps.labels.iter().enumerate().map(|(index, label)| {
+                let elapsed = ticks[2 * index + 1].saturating_sub(ticks[2 * index]);
+                (label.clone(), std::time::Duration::from_nanos((elapsed as f64 * period) as u64))
+            }).collect()
+        };
+        timestamps.readback_buffer.unmap();
+        durations
+    }
+
+    /// Read the gpu durations of the timestamp ranges and log them at the trace level.
+    /// Without the `gpu-profiling` feature, nothing is logged.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `instance` - The render instance.
+    #[allow(unused_variables)]
+    pub fn trace_timestamps(&self, instance: &WRenderInstanceData) {
+        #[cfg(feature = "gpu-profiling")]
+        for (label, duration) in self.read_timestamps(instance) {
+            event!(Level::TRACE, "Gpu duration of {} in command buffer {}: {:?}.", label, self.label, duration);
+        }
+    }
+
+    /// Resolve the recorded timestamps into the readback buffer before submitting.
+    #[cfg(feature = "gpu-profiling")]
+    fn resolve_timestamps(&mut self) {
+        if let (Some(timestamps), Some(encoder)) = (self.timestamps.as_mut(), self.encoder.as_mut()) {
+            let count = 2 * timestamps.labels.len() as u32;
+            if count > 0 {
+                encoder.resolve_query_set(&timestamps.query_set, 0..count, &timestamps.resolve_buffer, 0);
+                encoder.copy_buffer_to_buffer(&timestamps.resolve_buffer, 0, &timestamps.readback_buffer, 0,
+                    count as u64 * std::mem::size_of::<u64>() as u64);
+                timestamps.resolved = true;
+            }
+        }
+    }
+
     /// Finish and submit a command buffer.
+    /// The command buffer cannot record commands once submitted, but its timestamps can still be read.
     /// 
     /// # Arguments
     /// 
     /// * `instance` - The render instance.
-    pub fn submit(self, instance: &WRenderInstanceData) {
+    /// 
+    /// # Panics
+    /// 
+    /// * If the command buffer was already submitted.
+    pub fn submit(&mut self, instance: &WRenderInstanceData) {
         event!(Level::TRACE, "Submitted command buffer {}.", self.label);
-        instance.queue.submit(std::iter::once(self.encoder.finish()));
+        #[cfg(feature = "gpu-profiling")]
+        self.resolve_timestamps();
+        instance.queue.submit(std::iter::once(self.finish()));
+    }
+
+    /// Take the encoder of the command buffer and finish it.
+    fn finish(&mut self) -> wgpu::CommandBuffer {
+        match self.encoder.take() {
+            Some(encoder) => encoder.finish(),
+            None => panic!("The command buffer {} was already submitted.", self.label)
+        }
+    }
+
+    /// Submit the command buffer to the queue and block until the gpu has executed it.
+    /// This stalls the cpu, so it is only available with the `blocking` feature, for tests and readbacks.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `instance` - The render instance.
+    #[cfg(feature = "blocking")]
+    pub fn submit_and_wait(&mut self, instance: &WRenderInstanceData) {
+        event!(Level::TRACE, "Submitted command buffer {} and waiting for its completion.", self.label);
+        #[cfg(feature = "gpu-profiling")]
+        self.resolve_timestamps();
+        let index = instance.queue.submit(std::iter::once(self.finish()));
+        instance.device.poll(wgpu::Maintain::WaitForSubmissionIndex(index));
     }
 
 
@@ -233,12 +453,31 @@ impl WCommandBuffer {
     pub fn copy_buffer_to_buffer(&mut self, source: &WBuffer, destination: &WBuffer) {
         event!(Level::TRACE, "Copying buffer {} to buffer {}.", source.label, destination.label);
 
-        self.encoder.copy_buffer_to_buffer(
+        self.encoder_mut().copy_buffer_to_buffer(
             &source.buffer, 0,
             &destination.buffer, 0,
             source.buffer.size());
     }
 
+    /// Copy a range of a buffer to another buffer.
+    /// The offsets and the size must be multiples of 4 bytes.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `source` - The source buffer.
+    /// * `source_offset` - The offset of the range in the source buffer in bytes.
+    /// * `destination` - The destination buffer.
+    /// * `destination_offset` - The offset of the range in the destination buffer in bytes.
+    /// * `size` - The size of the range in bytes.
+    pub fn copy_buffer_range(&mut self, source: &WBuffer, source_offset: u64, destination: &WBuffer, destination_offset: u64, size: u64) {
+        event!(Level::TRACE, "Copying {} bytes of buffer {} to buffer {}.", size, source.label, destination.label);
+
+        self.encoder_mut().copy_buffer_to_buffer(
+            &source.buffer, source_offset,
+            &destination.buffer, destination_offset,
+            size);
+    }
+
     /// Copy a texture to a buffer.
     /// Please use the `copy_from_texture` method of the buffer to copy data.
     /// 
@@ -264,18 +503,89 @@ impl WCommandBuffer {
         };
 
         // Copy texture to buffer
-        self.encoder.copy_texture_to_buffer(
+        self.encoder_mut().copy_texture_to_buffer(
             texture_copy,
             buffer_copy,
             size);
     }
 
+    /// Copy a texture to a buffer with rows of `bytes_per_row` bytes.
+    /// Use `WBuffer::padded_bytes_per_row` to compute the aligned length of the rows.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `source` - The source texture.
+    /// * `destination` - The destination buffer.
+    /// * `bytes_per_row` - The length of a row of pixels in the buffer.
+    pub fn copy_texture_to_padded_buffer(&mut self, source: &Texture, destination: &WBuffer, bytes_per_row: u32) {
+        event!(Level::TRACE, "Copying texture to padded buffer {}.", destination.label);
+
+        self.encoder_mut().copy_texture_to_buffer(
+            source.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &destination.buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(bytes_per_row),
+                    rows_per_image: None,
+                }
+            },
+            source.size());
+    }
+
+    /// Resolve the results of all the occlusion queries of a set into a buffer, once the render passes using them are ended.
+    /// All the queries of the set must have been written. See `resolve_occlusion_query_range`.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `query_set` - The occlusion query set.
+    /// * `destination` - The destination buffer.
+    pub fn resolve_occlusion_queries(&mut self, query_set: &WOcclusionQuerySet, destination: &WBuffer) {
+        self.resolve_occlusion_query_range(query_set, 0..query_set.count, destination);
+    }
+
+    /// Resolve the results of a range of occlusion queries of a set at the start of a buffer.
+    /// Each result is a `u64` counting the samples that passed the depth test, zero if the draws were occluded.
+    /// The buffer must have the QUERY_RESOLVE usage and be large enough for the results.
+    /// To read the results on the cpu without stalling, copy them to a MAP_READ buffer and use `WBuffer::map_read_async`.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `query_set` - The occlusion query set.
+    /// * `queries` - The range of the written queries to resolve.
+    /// * `destination` - The destination buffer.
+    pub fn resolve_occlusion_query_range(&mut self, query_set: &WOcclusionQuerySet, queries: std::ops::Range<u32>, destination: &WBuffer) {
+        event!(Level::TRACE, "Resolving occlusion query set {} into buffer {}.", query_set.label, destination.label);
+        debug_assert!(destination.buffer.usage().contains(wgpu::BufferUsages::QUERY_RESOLVE),
+            "Buffer {} is used to resolve queries without the QUERY_RESOLVE usage.", destination.label);
+
+        self.encoder_mut().resolve_query_set(&query_set.query_set, queries, &destination.buffer, 0);
+    }
+
+    /// Resolve the results of a range of pipeline statistics queries, and copy them at the start of a buffer.
+    /// Use `WPipelineStatistics::from_results` to parse the results.
+    /// The buffer must have the COPY_DST usage, and the MAP_READ usage to be read on the cpu.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `query_set` - The pipeline statistics query set.
+    /// * `queries` - The range of the written queries to resolve.
+    /// * `destination` - The destination buffer, at least `WPipelineStatisticsQuerySet::results_size` bytes long.
+    pub fn resolve_pipeline_statistics(&mut self, query_set: &WPipelineStatisticsQuerySet, queries: std::ops::Range<u32>, destination: &WBuffer) {
+        event!(Level::TRACE, "Resolving pipeline statistics query set {} into buffer {}.", query_set.label, destination.label);
+
+        let size = WPipelineStatisticsQuerySet::results_size(queries.len() as u32);
+        let encoder = self.encoder_mut();
+        encoder.resolve_query_set(&query_set.query_set, queries, &query_set.resolve_buffer.buffer, 0);
+        encoder.copy_buffer_to_buffer(&query_set.resolve_buffer.buffer, 0, &destination.buffer, 0, size);
+    }
+
     /// Get the encoder of the command buffer.
     /// 
     /// # Returns
     /// 
     /// The encoder of the command buffer.
     pub fn encoder(&mut self) -> &mut wgpu::CommandEncoder {
-        &mut self.encoder
+        self.encoder_mut()
     }
 }
@@ -2,7 +2,10 @@
 
 use bevy::{log::Level, prelude::*, utils::tracing::event};
 
-use crate::{compute_pipeline::WComputePipeline, instance::WRenderError};
+use crate::{buffer::WBuffer, compute_pipeline::WComputePipeline, instance::WRenderError};
+
+// Alias struct for the dispatch indirect function.
+pub use wgpu::util::DispatchIndirectArgs;
 
 
 /// Create a compute pass instance.
@@ -97,6 +100,18 @@ impl<'a> WComputePass<'a> {
         self
     }
 
+    /// Set a bind group of the compute pass at a binding, with the offsets of its dynamic buffers.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `binding` - The binding of the bind group.
+    /// * `bind_group` - The bind group to set.
+    /// * `offsets` - The offsets of the dynamic buffers of the bind group, in the order of their bindings.
+    pub fn set_bind_group_with_offsets(&mut self, binding: u32, bind_group: &'a wgpu::BindGroup, offsets: &[u32]) -> &mut Self {
+        self.compute_pass.set_bind_group(binding, bind_group, offsets);
+        self
+    }
+
 
     /// Dispatch the compute pass.
     /// 
@@ -120,4 +135,30 @@ impl<'a> WComputePass<'a> {
         self.compute_pass.dispatch_workgroups(x, y, z);
         Ok(())
     }
+
+    /// Dispatch the compute pass with a workgroup count read from a buffer, e.g. written by a previous compute pass.
+    /// The buffer must have the `INDIRECT` usage and contain a `DispatchIndirectArgs` (three `u32`) at the offset.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `buffer` - The buffer to read the workgroup count from.
+    /// * `offset_bytes` - The offset of the workgroup count in the buffer, a multiple of 4.
+    /// 
+    /// # Errors
+    /// 
+    /// * `RenderError::PipelineNotSet` - The pipeline is not set.
+    pub fn dispatch_indirect(&mut self, buffer: &'a WBuffer, offset_bytes: u64) -> Result<(), WRenderError> {
+        if !self.pipeline_set {
+            error!(self.label, "Pipeline is not set.");
+            return Err(WRenderError::PipelineNotSet);
+        }
+        debug_assert!(buffer.buffer.usage().contains(wgpu::BufferUsages::INDIRECT),
+            "Buffer {} is used for an indirect dispatch without the INDIRECT usage.", buffer.label);
+        debug_assert!(offset_bytes.is_multiple_of(4), "The indirect dispatch offset {} is not a multiple of 4.", offset_bytes);
+
+        // Dispatch
+        event!(Level::TRACE, "Dispatching compute pipeline {} from indirect buffer {}.", self.label, buffer.label);
+        self.compute_pass.dispatch_workgroups_indirect(&buffer.buffer, offset_bytes);
+        Ok(())
+    }
 }
@@ -2,12 +2,78 @@
 
 use std::sync::{Arc, RwLock};
 
-use bevy::{ecs::system::SystemState, log::{debug, error, warn, Level}, prelude::*, utils::tracing::{event, span}, window::{PresentMode, PrimaryWindow, RawHandleWrapperHolder}};
+use bevy::{ecs::system::SystemState, log::{debug, error, warn, Level}, prelude::*, utils::tracing::{event, span}, window::{PresentMode, PrimaryWindow, RawHandleWrapper, RawHandleWrapperHolder}};
 use wgpu::{Device, Limits, Surface, SurfaceConfiguration, SurfaceTexture};
 
-use crate::texture::WTextureView;
+use crate::{adapter::{adapter_vram, WAdapterInfo, WAdapterPreference}, texture::WTextureView};
 
 pub type WLimits = Limits;
+pub type WFeatures = wgpu::Features;
+
+/// The device features requested by the renderer.
+/// It can be inserted as a resource before the render instance is created to request other features.
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WFeatureRequirements {
+    /// The features the renderer cannot run without. The instance creation fails if the adapter does not support them.
+    pub required: WFeatures,
+    /// The features enabled only if the adapter supports them. Use `WRenderInstanceData::has_feature` before relying on them.
+    pub optional: WFeatures,
+}
+impl Default for WFeatureRequirements {
+    fn default() -> Self {
+        let optional = WFeatures::CONSERVATIVE_RASTERIZATION
+            | WFeatures::DEPTH_CLIP_CONTROL
+            | WFeatures::TIMESTAMP_QUERY
+            | WFeatures::POLYGON_MODE_LINE
+            | WFeatures::POLYGON_MODE_POINT
+            | WFeatures::PIPELINE_STATISTICS_QUERY;
+
+        // The timestamps of the command buffers are written between the passes
+        #[cfg(feature = "gpu-profiling")]
+        let optional = optional | WFeatures::TIMESTAMP_QUERY_INSIDE_ENCODERS;
+
+        Self {
+            required: WFeatures::INDIRECT_FIRST_INSTANCE
+                | WFeatures::MULTI_DRAW_INDIRECT
+                | WFeatures::PUSH_CONSTANTS,
+            optional,
+        }
+    }
+}
+
+impl WFeatureRequirements {
+    /// Add features the renderer cannot run without.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `features` - The required features.
+    pub fn require_feature(mut self, features: WFeatures) -> Self {
+        self.required |= features;
+        self
+    }
+
+    /// Add features enabled only if the adapter supports them.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `features` - The optional features.
+    pub fn request_feature(mut self, features: WFeatures) -> Self {
+        self.optional |= features;
+        self
+    }
+}
+
+/// The features enabled on the device, required or optional.
+/// It is inserted in the main world and the render world once the render instance is created,
+/// so that the systems can check the optional features without locking the render instance.
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WGrantedFeatures(pub WFeatures);
+impl WGrantedFeatures {
+    /// Whether the device supports all the given features.
+    pub fn has_feature(&self, features: WFeatures) -> bool {
+        self.0.contains(features)
+    }
+}
 
 /// Error type of the renderer.
 #[derive(Debug)]
@@ -32,13 +98,19 @@ pub enum WRenderError {
     UnsupportedDepthFormat,
     /// Shader compilation error.
     ShaderCompilationError,
+    /// Two push constant ranges share bytes.
+    OverlappingPushConstants,
+    /// The push constant ranges leave bytes uncovered between 0 and their total size.
+    GapInPushConstants,
+    /// Texture format not supported as a storage texture.
+    UnsupportedStorageFormat,
 }
 
 /// Type of the render texture.
 #[derive(Debug)]
 pub struct WRenderTexture {
-    /// Texture of the render texture.
-    pub texture: wgpu::SurfaceTexture,
+    /// Texture of the render texture, none when rendering to an offscreen texture.
+    pub texture: Option<wgpu::SurfaceTexture>,
     /// View of the render texture.
     pub view: WTextureView,
 }
@@ -79,6 +151,32 @@ pub struct WRenderInstance<'a> {
     pub data: Arc<RwLock<WRenderInstanceData<'a>>>,
 }
 
+impl WRenderInstance<'_> {
+    /// Request optional device features before the render instance is created, i.e. before the render plugin is added.
+    /// The features are enabled only if the adapter supports them, check them with `WGrantedFeatures::has_feature`.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `app` - The application creating the render instance.
+    /// * `features` - The optional features.
+    pub fn request_feature(app: &mut App, features: WFeatures) {
+        let requirements = app.world().get_resource::<WFeatureRequirements>().copied().unwrap_or_default();
+        app.insert_resource(requirements.request_feature(features));
+    }
+
+    /// Require device features before the render instance is created, i.e. before the render plugin is added.
+    /// The instance creation fails if the adapter does not support them.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `app` - The application creating the render instance.
+    /// * `features` - The required features.
+    pub fn require_feature(app: &mut App, features: WFeatures) {
+        let requirements = app.world().get_resource::<WFeatureRequirements>().copied().unwrap_or_default();
+        app.insert_resource(requirements.require_feature(features));
+    }
+}
+
 /// Data of the render instance.
 pub struct WRenderInstanceData<'a> {
     /// Device of the instance.
@@ -93,6 +191,53 @@ pub struct WRenderInstanceData<'a> {
     pub instance: wgpu::Instance,
     /// Surface configuration of the instance.
     pub surface_config: Option<SurfaceConfiguration>,
+    /// The optional features supported by the adapter and enabled on the device.
+    pub enabled_optional: WFeatures,
+}
+
+impl WRenderInstanceData<'_> {
+    /// Get the information about the adapter of the instance, such as its name, vendor and type.
+    pub fn adapter_info(&self) -> WAdapterInfo {
+        self.adapter.get_info()
+    }
+
+    /// Whether the device supports all the given features, required or optional.
+    pub fn has_feature(&self, features: WFeatures) -> bool {
+        self.device.features().contains(features)
+    }
+
+    /// Whether the device can draw the triangles as wireframes, see `WRenderPipeline::set_polygon_mode`.
+    pub fn supports_polygon_mode_line(&self) -> bool {
+        self.has_feature(WFeatures::POLYGON_MODE_LINE)
+    }
+}
+
+/// The source from which the render surface is created.
+#[derive(Clone)]
+pub enum WSurfaceSource {
+    /// The raw handle of a window. On the web, this is the canvas created or selected by winit.
+    Window(RawHandleWrapper),
+    /// An existing html canvas element.
+    #[cfg(target_arch = "wasm32")]
+    Canvas(web_sys::HtmlCanvasElement),
+}
+
+/// Create a surface from a window or a canvas.
+/// 
+/// # Arguments
+/// 
+/// * `instance` - The wgpu instance.
+/// * `source` - The window or canvas to render to.
+pub fn create_surface(instance: &wgpu::Instance, source: WSurfaceSource) -> Result<Surface<'static>, wgpu::CreateSurfaceError> {
+    match source {
+        // SAFETY: the window handle is used on the thread the window was created on, and the
+        // surface is dropped with the render instance before the window is closed
+        WSurfaceSource::Window(wrapper) => unsafe {
+            instance.create_surface(wrapper.get_handle())
+        },
+        #[cfg(target_arch = "wasm32")]
+        WSurfaceSource::Canvas(canvas) => instance.create_surface(wgpu::SurfaceTarget::Canvas(canvas)),
+    }
 }
 
 /// Create a new instance of the GPU device.
@@ -126,48 +271,62 @@ pub async fn create_instance(label: &str, app: &mut App) -> WRenderInstance<'sta
     });
 
     // Retrieve surface
-    let surface = primary_window.and_then(|wrapper| unsafe {
+    let surface = primary_window.and_then(|wrapper| {
         let maybe_handle = wrapper.0.lock().expect(
             "Couldn't get the window handle in time for renderer initialization",
         );
-        if let Some(wrapper) = maybe_handle.as_ref() {
-            let handle = wrapper.get_handle();
-            Some(
-                instance
-                    .create_surface(handle)
-                    .expect("Failed to create wgpu surface"),
-            )
-        } else { None }
+        maybe_handle.as_ref().map(|wrapper| {
+            create_surface(&instance, WSurfaceSource::Window(wrapper.clone()))
+                .expect("Failed to create wgpu surface")
+        })
     });
 
-    // Retrieve adapter
-    let adapter = instance
-        .request_adapter(&wgpu::RequestAdapterOptions {
-            power_preference: wgpu::PowerPreference::HighPerformance,
-            compatible_surface: surface.as_ref(),
-            ..Default::default()
-        })
-        .await
-        .unwrap_or_else(|| panic!("Failed to create adapter for '{}'.", label));
+    // Retrieve the preferred adapter, or the default one
+    let preference = WAdapterPreference::load();
+    let adapter = match preference.select_adapter(&instance, surface.as_ref()) {
+        Some(adapter) => adapter,
+        None => instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: preference.power_preference(),
+                compatible_surface: surface.as_ref(),
+                ..Default::default()
+            })
+            .await
+            .unwrap_or_else(|| panic!("Failed to create adapter for '{}'.", label))
+    };
 
     // Check adaptater infos
     let adapter_info = adapter.get_info();
-    info!("Using adapter named {} of {} type.", adapter_info.name, match adapter_info.device_type {
+    let vram = match adapter_vram(&adapter) {
+        Some(vram) => format!("{} MiB", vram / (1024 * 1024)),
+        None => "an unknown amount".to_string()
+    };
+    info!("Using adapter named {} of {} type from vendor {:#06x} with the {:?} backend and {} of video memory.", adapter_info.name, match adapter_info.device_type {
         wgpu::DeviceType::DiscreteGpu => "Discrete GPU",
         wgpu::DeviceType::IntegratedGpu => "Integrated GPU",
         wgpu::DeviceType::Cpu => "CPU",
         wgpu::DeviceType::VirtualGpu => "Virtual GPU",
         wgpu::DeviceType::Other => "Other",
-    });
+    }, adapter_info.vendor, adapter_info.backend, vram);
     if adapter_info.device_type == wgpu::DeviceType::Cpu {
         warn!("The selected adapter is using a driver that only supports software rendering, this will be very slow.");
     }
 
-    // Set required features
-    let required_features = wgpu::Features::INDIRECT_FIRST_INSTANCE
-        | wgpu::Features::MULTI_DRAW_INDIRECT
-        | wgpu::Features::PUSH_CONSTANTS;
-        
+    // Check the required features
+    let features = app.world().get_resource::<WFeatureRequirements>().copied().unwrap_or_default();
+    let missing_features = features.required - adapter.features();
+    if !missing_features.is_empty() {
+        panic!("The adapter {} does not support the features {:?} required by '{}'.", adapter_info.name, missing_features, label);
+    }
+
+    // Set optional features if supported by the adapter
+    let enabled_optional = features.optional & adapter.features();
+    let disabled_optional = features.optional - enabled_optional;
+    if !disabled_optional.is_empty() {
+        debug!(label, "Disabling the optional features {:?} not supported by the adapter.", disabled_optional);
+    }
+    let required_features = features.required | enabled_optional;
+
     // Set limits
     let required_limits = Limits {
         max_push_constant_size: 128,
@@ -200,11 +359,61 @@ pub async fn create_instance(label: &str, app: &mut App) -> WRenderInstance<'sta
             surface,
             adapter,
             instance,
-            surface_config: None
+            surface_config: None,
+            enabled_optional
         }))
     }
 }
 
+/// Create a new instance of the GPU device without surface, e.g. for tests and offline rendering.
+/// The default features are enabled if the adapter supports them, check them with `WRenderInstanceData::has_feature`.
+/// 
+/// # Arguments
+/// 
+/// * `label` - Label of the instance.
+/// 
+/// # Returns
+/// 
+/// The render instance, or none if no adapter or device is available.
+pub async fn create_headless_instance(label: &str) -> Option<WRenderInstance<'static>> {
+    debug!(label, "Creating headless render instance.");
+    let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+        backends: wgpu::Backends::all(),
+        ..Default::default()
+    });
+
+    // Retrieve the default adapter
+    let adapter = instance.request_adapter(&wgpu::RequestAdapterOptions::default()).await?;
+    let features = WFeatureRequirements::default();
+    let enabled_optional = (features.required | features.optional) & adapter.features();
+
+    // Create device instance and queue
+    let (device, queue) = adapter
+        .request_device(
+            &wgpu::DeviceDescriptor {
+                label: Some(label),
+                required_features: enabled_optional,
+                required_limits: adapter.limits(),
+                memory_hints: wgpu::MemoryHints::Performance,
+            },
+            None,
+        )
+        .await
+        .ok()?;
+
+    Some(WRenderInstance {
+        data: Arc::new(RwLock::new(WRenderInstanceData {
+            device,
+            queue,
+            surface: None,
+            adapter,
+            instance,
+            surface_config: None,
+            enabled_optional
+        }))
+    })
+}
+
 /// Setup the surface of the instance.
 /// 
 /// # Arguments
@@ -252,6 +461,30 @@ pub fn setup_surface(label: &str, size: (u32, u32), device: &Device, surface: &S
     surface_config
 }
 
+/// Describe an offscreen render target as a surface configuration, for rendering without a window.
+/// The configuration is never used to configure a surface.
+/// 
+/// # Arguments
+/// 
+/// * `size` - Size of the render target.
+/// * `format` - Format of the render target.
+/// 
+/// # Returns
+/// 
+/// * `SurfaceConfiguration` - Surface configuration describing the render target.
+pub fn setup_headless(size: (u32, u32), format: wgpu::TextureFormat) -> SurfaceConfiguration {
+    wgpu::SurfaceConfiguration {
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        format,
+        width: size.0,
+        height: size.1,
+        present_mode: wgpu::PresentMode::Fifo,
+        alpha_mode: wgpu::CompositeAlphaMode::Opaque,
+        view_formats: vec![],
+        desired_maximum_frame_latency: 2
+    }
+}
+
 /// Get the render texture.
 /// 
 /// # Arguments
@@ -290,7 +523,7 @@ pub fn get_current_texture(surface: &Surface, surface_config: &SurfaceConfigurat
                 array_layer_count: None,
             });
             let cur_render = WRenderTexture {
-                texture: surface_texture,
+                texture: Some(surface_texture),
                 view: render_view
             };
             WRenderEvent::Redraw(cur_render)
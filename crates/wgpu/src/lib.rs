@@ -262,6 +262,7 @@
 //! [ComputePipeline]: compute_pipeline/struct.ComputePipeline.html
 //! [ComputePass]: compute_pass/struct.ComputePass.html
 pub mod instance;
+pub mod adapter;
 pub mod vertex;
 pub mod bind_group;
 pub mod render_pipeline;
@@ -271,3 +272,4 @@ pub mod render_pass;
 pub mod compute_pass;
 pub mod buffer;
 pub mod command_buffer;
+pub mod query_set;
@@ -0,0 +1,215 @@
+//! Contains the occlusion and pipeline statistics query sets.
+
+use bevy::{log::Level, utils::tracing::event};
+
+use crate::{buffer::{BufferUsage, WBuffer}, instance::WRenderInstanceData};
+
+/// The statistics counted by a pipeline statistics query.
+pub type WPipelineStatisticsTypes = wgpu::PipelineStatisticsTypes;
+
+/// A set of occlusion queries, counting the samples of the draws of a render pass that pass the depth test.
+/// The results are resolved into a buffer with `WCommandBuffer::resolve_occlusion_queries`, one `u64` per query.
+/// 
+/// # Example
+/// 
+/// ```
+/// # use wde_wgpu::{command_buffer::WCommandBuffer, instance::WRenderInstanceData, query_set::WOcclusionQuerySet};
+/// # fn example(instance: &WRenderInstanceData, command_buffer: &mut WCommandBuffer, index_count: u32) {
+/// // Create a set of 256 occlusion queries
+/// let query_set = WOcclusionQuerySet::builder("occlusion")
+///     .count(256)
+///     .build(&instance);
+/// 
+/// // Use it in a render pass
+/// let mut render_pass = command_buffer.create_render_pass("pass", |builder| {
+///     builder.set_occlusion_query_set(&query_set);
+/// });
+/// render_pass.begin_occlusion_query(0);
+/// render_pass.draw_indexed(0..index_count, 0..1);
+/// render_pass.end_occlusion_query();
+/// # }
+/// ```
+pub struct WOcclusionQuerySet {
+    pub label: String,
+    pub query_set: wgpu::QuerySet,
+    /// The number of queries of the set.
+    pub count: u32,
+}
+
+impl std::fmt::Debug for WOcclusionQuerySet {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OcclusionQuerySet")
+            .field("label", &self.label)
+            .field("count", &self.count)
+            .finish()
+    }
+}
+
+impl WOcclusionQuerySet {
+    /// Create a new occlusion query set.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `instance` - The render instance.
+    /// * `label` - The label of the query set.
+    /// * `count` - The number of queries of the set.
+    pub fn new(instance: &WRenderInstanceData, label: &str, count: u32) -> Self {
+        event!(Level::TRACE, "Creating occlusion query set {} of {} queries.", label, count);
+
+        let query_set = instance.device.create_query_set(&wgpu::QuerySetDescriptor {
+            label: Some(format!("{}-occlusion-query-set", label).as_str()),
+            ty: wgpu::QueryType::Occlusion,
+            count,
+        });
+
+        Self {
+            label: label.to_string(),
+            query_set,
+            count,
+        }
+    }
+
+    /// Start describing a new occlusion query set.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `label` - The label of the query set.
+    pub fn builder(label: &str) -> WOcclusionQuerySetBuilder {
+        WOcclusionQuerySetBuilder {
+            label: label.to_string(),
+            count: 1,
+        }
+    }
+
+    /// The size in bytes of the buffer into which all the queries of the set are resolved.
+    pub fn resolve_size(&self) -> u64 {
+        self.count as u64 * std::mem::size_of::<u64>() as u64
+    }
+}
+
+/// Builder of an occlusion query set.
+#[derive(Debug, Clone)]
+pub struct WOcclusionQuerySetBuilder {
+    label: String,
+    count: u32,
+}
+
+impl WOcclusionQuerySetBuilder {
+    /// Set the number of queries of the set. By default, the set has a single query.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `count` - The number of queries.
+    pub fn count(mut self, count: u32) -> Self {
+        self.count = count;
+        self
+    }
+
+    /// Create the occlusion query set.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `instance` - The render instance.
+    pub fn build(self, instance: &WRenderInstanceData) -> WOcclusionQuerySet {
+        WOcclusionQuerySet::new(instance, &self.label, self.count)
+    }
+}
+
+
+/// A set of pipeline statistics queries, counting the vertices and fragments processed by the draws of render passes.
+/// It requires the `PIPELINE_STATISTICS_QUERY` feature, check it with `WRenderInstanceData::has_feature` before creating the set.
+/// The results are copied to a buffer with `WCommandBuffer::resolve_pipeline_statistics`,
+/// and parsed with `WPipelineStatistics::from_results`.
+pub struct WPipelineStatisticsQuerySet {
+    pub label: String,
+    pub query_set: wgpu::QuerySet,
+    /// The number of queries of the set.
+    pub count: u32,
+    /// The buffer the queries are resolved into, before being copied to the destination buffer.
+    pub(crate) resolve_buffer: WBuffer,
+}
+
+impl std::fmt::Debug for WPipelineStatisticsQuerySet {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PipelineStatisticsQuerySet")
+            .field("label", &self.label)
+            .field("count", &self.count)
+            .finish()
+    }
+}
+
+impl WPipelineStatisticsQuerySet {
+    /// The statistics counted by each query, in the order of their results.
+    pub const STATISTICS: WPipelineStatisticsTypes = WPipelineStatisticsTypes::VERTEX_SHADER_INVOCATIONS
+        .union(WPipelineStatisticsTypes::CLIPPER_INVOCATIONS)
+        .union(WPipelineStatisticsTypes::FRAGMENT_SHADER_INVOCATIONS);
+
+    /// Create a new pipeline statistics query set.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `instance` - The render instance.
+    /// * `label` - The label of the query set.
+    /// * `count` - The number of queries of the set.
+    pub fn new(instance: &WRenderInstanceData, label: &str, count: u32) -> Self {
+        event!(Level::TRACE, "Creating pipeline statistics query set {} of {} queries.", label, count);
+
+        let query_set = instance.device.create_query_set(&wgpu::QuerySetDescriptor {
+            label: Some(format!("{}-pipeline-statistics-query-set", label).as_str()),
+            ty: wgpu::QueryType::PipelineStatistics(Self::STATISTICS),
+            count,
+        });
+        let resolve_buffer = WBuffer::new(instance, format!("{}-pipeline-statistics-resolve", label).as_str(),
+            (count as u64 * WPipelineStatistics::RESULT_SIZE) as usize, BufferUsage::QUERY_RESOLVE | BufferUsage::COPY_SRC, None);
+
+        Self {
+            label: label.to_string(),
+            query_set,
+            count,
+            resolve_buffer,
+        }
+    }
+
+    /// The size in bytes of the results of `count` queries.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `count` - The number of queries.
+    pub fn results_size(count: u32) -> u64 {
+        count as u64 * WPipelineStatistics::RESULT_SIZE
+    }
+}
+
+/// The statistics counted by a pipeline statistics query.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WPipelineStatistics {
+    /// The number of vertex shader invocations, i.e. the number of processed vertices.
+    pub vertex_shader_invocations: u64,
+    /// The number of primitives sent to the clipper.
+    pub clipper_invocations: u64,
+    /// The number of fragment shader invocations.
+    pub fragment_shader_invocations: u64,
+}
+
+impl WPipelineStatistics {
+    /// The size in bytes of the result of a query.
+    pub const RESULT_SIZE: u64 = 3 * std::mem::size_of::<u64>() as u64;
+
+    /// Parse the results of queries copied by `WCommandBuffer::resolve_pipeline_statistics`.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `results` - The content of the destination buffer.
+    /// 
+    /// # Returns
+    /// 
+    /// The statistics of each query, in query order.
+    pub fn from_results(results: &[u8]) -> Vec<Self> {
+        let values: &[u64] = bytemuck::cast_slice(results);
+        values.chunks_exact(3).map(|values| Self {
+            vertex_shader_invocations: values[0],
+            clipper_invocations: values[1],
+            fragment_shader_invocations: values[2],
+        }).collect()
+    }
+}
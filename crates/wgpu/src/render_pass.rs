@@ -8,8 +8,10 @@ use bevy::utils::tracing::event;
 use wgpu::BufferAddress;
 use wgpu::ShaderStages;
 
+use crate::bind_group::BindGroupLayout;
 use crate::buffer::WBuffer;
 use crate::instance::WRenderError;
+use crate::query_set::WPipelineStatisticsQuerySet;
 
 use super::render_pipeline::WRenderPipeline;
 
@@ -27,7 +29,8 @@ pub use wgpu::util::DrawIndexedIndirectArgs;
 /// 
 /// // Set render pass dependencies
 /// render_pass
-///     .set_scissor_rect(0, 0, 800, 600) // Set the scissor rect to (0, 0) with width 800 and height 600
+///     .set_viewport(0.0, 0.0, 400.0, 600.0, 0.0, 1.0) // Render to the left half of a 800x600 target
+///     .set_scissor_rect(0, 0, 400, 600) // Set the scissor rect to (0, 0) with width 400 and height 600
 ///     .set_vertex_buffer(vertex_buffer) // Set the vertex buffer of the current render pass
 ///     .set_index_buffer(index_buffer);  // Set the index buffer of the current render pass
 /// 
@@ -65,6 +68,11 @@ pub struct WRenderPass<'a> {
     pipeline_set: bool,
     vertex_buffer_set: bool,
     index_buffer_set: bool,
+    /// The layout entries of the bind groups set with their layout, checked against the pipeline in debug builds.
+    #[cfg(debug_assertions)]
+    bound_layouts: Vec<(u32, &'a BindGroupLayout)>,
+    #[cfg(debug_assertions)]
+    pipeline: Option<&'a WRenderPipeline>,
 }
 
 impl std::fmt::Debug for WRenderPass<'_> {
@@ -91,6 +99,10 @@ impl<'a> WRenderPass<'a> {
             pipeline_set: false,
             vertex_buffer_set: false,
             index_buffer_set: false,
+            #[cfg(debug_assertions)]
+            bound_layouts: Vec::new(),
+            #[cfg(debug_assertions)]
+            pipeline: None,
         }
     }
 
@@ -110,12 +122,36 @@ impl<'a> WRenderPass<'a> {
             return Err(WRenderError::PipelineNotInitialized);
         }
 
+        // Check the layouts of the bind groups already set
+        #[cfg(debug_assertions)]
+        {
+            for (group, layout) in self.bound_layouts.iter() {
+                Self::check_bind_group_layout(pipeline, *group, layout);
+            }
+            self.pipeline = Some(pipeline);
+        }
+
         // Set pipeline
         self.render_pass.set_pipeline(pipeline.get_pipeline().as_ref().unwrap());
         self.pipeline_set = true;
+
+        // Pipelines without vertex input do not need a vertex buffer
+        if !pipeline.has_vertex_input() {
+            self.vertex_buffer_set = true;
+        }
         Ok(self)
     }
 
+    /// Set the reference value of the stencil tests and operations of the pipelines.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `reference` - The stencil reference value.
+    pub fn set_stencil_reference(&mut self, reference: u32) -> &mut Self {
+        self.render_pass.set_stencil_reference(reference);
+        self
+    }
+
     /// Set a vertex buffer of the render pass.
     /// 
     /// # Arguments
@@ -152,6 +188,21 @@ impl<'a> WRenderPass<'a> {
         self
     }
 
+    /// Set the viewport of the render pass. The rasterized primitives are mapped to this region of the render targets.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `x` - X coordinate of the viewport.
+    /// * `y` - Y coordinate of the viewport.
+    /// * `width` - Width of the viewport.
+    /// * `height` - Height of the viewport.
+    /// * `min_depth` - Minimum depth of the viewport (usually 0.0).
+    /// * `max_depth` - Maximum depth of the viewport (usually 1.0).
+    pub fn set_viewport(&mut self, x: f32, y: f32, width: f32, height: f32, min_depth: f32, max_depth: f32) -> &mut Self {
+        self.render_pass.set_viewport(x, y, width, height, min_depth, max_depth);
+        self
+    }
+
 
 
     /// Set push constants of the render pass.
@@ -172,11 +223,97 @@ impl<'a> WRenderPass<'a> {
     /// * `binding` - The binding of the bind group.
     /// * `bind_group` - The bind group to set.
     pub fn set_bind_group(&mut self, binding: u32, bind_group: &'a wgpu::BindGroup) -> &mut Self {
+        #[cfg(debug_assertions)]
+        self.bound_layouts.retain(|(group, _)| *group != binding);
+
+        self.render_pass.set_bind_group(binding, bind_group, &[]);
+        self
+    }
+
+    /// Set a bind group of the render pass at a binding, with the layout it was created with.
+    /// In debug builds, the layout is checked against the layout expected by the current and next pipelines,
+    /// and a mismatch panics with the binding and the expected and actual binding types.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `binding` - The binding of the bind group.
+    /// * `bind_group` - The bind group to set.
+    /// * `layout` - The layout of the bind group.
+    #[cfg_attr(not(debug_assertions), allow(unused_variables))]
+    pub fn set_bind_group_with_layout(&mut self, binding: u32, bind_group: &'a wgpu::BindGroup, layout: &'a BindGroupLayout) -> &mut Self {
+        #[cfg(debug_assertions)]
+        {
+            if let Some(pipeline) = self.pipeline {
+                Self::check_bind_group_layout(pipeline, binding, layout);
+            }
+            self.bound_layouts.retain(|(group, _)| *group != binding);
+            self.bound_layouts.push((binding, layout));
+        }
+
         self.render_pass.set_bind_group(binding, bind_group, &[]);
         self
     }
 
+    /// Check the layout of a bind group against the layout expected by a pipeline, if the pipeline knows it.
+    #[cfg(debug_assertions)]
+    fn check_bind_group_layout(pipeline: &WRenderPipeline, group: u32, layout: &BindGroupLayout) {
+        if let Some(expected) = pipeline.get_bind_group_entries(group) {
+            crate::bind_group::assert_layout_compatible(&pipeline.label, group, expected, layout.builder.entries());
+        }
+    }
+
+    /// Set a bind group of the render pass at a binding, with the offsets of its dynamic buffers.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `binding` - The binding of the bind group.
+    /// * `bind_group` - The bind group to set.
+    /// * `offsets` - The offsets of the dynamic buffers of the bind group, in the order of their bindings.
+    pub fn set_bind_group_with_offsets(&mut self, binding: u32, bind_group: &'a wgpu::BindGroup, offsets: &[u32]) -> &mut Self {
+        #[cfg(debug_assertions)]
+        self.bound_layouts.retain(|(group, _)| *group != binding);
+
+        self.render_pass.set_bind_group(binding, bind_group, offsets);
+        self
+    }
+
+
 
+    /// Start counting the samples of the next draws that pass the depth test into a query of the occlusion query set of the render pass.
+    /// The render pass must be created with an occlusion query set, and each query can only be used once per render pass.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `index` - The index of the query in the set.
+    pub fn begin_occlusion_query(&mut self, index: u32) -> &mut Self {
+        event!(Level::TRACE, "Beginning occlusion query {} in render pass {}.", index, self.label);
+        self.render_pass.begin_occlusion_query(index);
+        self
+    }
+
+    /// Stop counting the samples into the occlusion query started by `begin_occlusion_query`.
+    pub fn end_occlusion_query(&mut self) -> &mut Self {
+        self.render_pass.end_occlusion_query();
+        self
+    }
+
+    /// Start counting the vertices and fragments processed by the next draws into a pipeline statistics query.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `query_set` - The pipeline statistics query set.
+    /// * `index` - The index of the query in the set.
+    pub fn begin_pipeline_statistics_query(&mut self, query_set: &WPipelineStatisticsQuerySet, index: u32) -> &mut Self {
+        event!(Level::TRACE, "Beginning pipeline statistics query {} in render pass {}.", index, self.label);
+        self.render_pass.begin_pipeline_statistics_query(&query_set.query_set, index);
+        self
+    }
+
+    /// Stop counting into the pipeline statistics query started by `begin_pipeline_statistics_query`.
+    pub fn end_pipeline_statistics_query(&mut self) -> &mut Self {
+        self.render_pass.end_pipeline_statistics_query();
+        self
+    }
 
     /// Draws primitives from the active vertex buffers.
     /// 
@@ -1,9 +1,11 @@
 //! Render pipeline module.
 
-use bevy::{log::{error, trace, Level}, utils::tracing::event};
+use std::sync::Arc;
+
+use bevy::{log::{error, trace, warn, Level}, utils::tracing::event};
 use wgpu::{naga, BindGroupLayout};
 
-use crate::{instance::{WRenderError, WRenderInstanceData}, texture::{WTexture, WTextureFormat}, vertex::WVertex};
+use crate::{bind_group::WBindGroupLayoutEntry, instance::{WFeatures, WRenderError, WRenderInstanceData}, texture::{WTexture, WTextureFormat}, vertex::WVertex};
 
 /// List of available shaders.
 pub type WShaderStages = wgpu::ShaderStages;
@@ -11,25 +13,104 @@ pub type WShaderStages = wgpu::ShaderStages;
 pub type WShaderModule = wgpu::ShaderModule;
 /// Export culling params.
 pub type WFace = wgpu::Face;
+/// Export front face winding order.
+pub type WFrontFace = wgpu::FrontFace;
 /// Export compare function.
 pub type WCompareFunction = wgpu::CompareFunction;
+/// The layout of the bind groups and push constants of a pipeline.
+pub type WPipelineLayout = wgpu::PipelineLayout;
+/// A push constant range of a pipeline layout.
+pub type WPushConstantRange = wgpu::PushConstantRange;
+/// The blending of the colors written to the render targets.
+pub type WBlendState = wgpu::BlendState;
+/// The blending of the color or alpha channels.
+pub type WBlendComponent = wgpu::BlendComponent;
+/// The factor of a color in the blending.
+pub type WBlendFactor = wgpu::BlendFactor;
+/// How the colors are combined in the blending.
+pub type WBlendOperation = wgpu::BlendOperation;
+/// How the polygons are rasterized: filled, as wireframes or as points.
+pub type WPolygonMode = wgpu::PolygonMode;
+/// The depth bias applied to the primitives, e.g. to render shadow maps.
+pub type WDepthBiasState = wgpu::DepthBiasState;
+/// The stencil operations of a face of the primitives.
+pub type WStencilFaceState = wgpu::StencilFaceState;
+/// The stencil test and operations of a pipeline.
+pub type WStencilState = wgpu::StencilState;
+
+/// Create a pipeline layout from the layouts of its bind groups and its push constants.
+/// 
+/// # Arguments
+/// 
+/// * `instance` - Render instance.
+/// * `label` - Label of the pipeline layout.
+/// * `bind_groups` - The layouts of the bind groups, in the order of the bindings in the shaders.
+/// * `push_constants` - The push constant ranges.
+pub fn create_pipeline_layout(instance: &WRenderInstanceData<'_>, label: &str, bind_groups: &[BindGroupLayout], push_constants: &[WPushConstantRange]) -> WPipelineLayout {
+    instance.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some(format!("{}-render-pip-layout", label).as_str()),
+        bind_group_layouts: &bind_groups.iter().collect::<Vec<&BindGroupLayout>>(),
+        push_constant_ranges: push_constants,
+    })
+}
+
+/// Check that the push constant ranges do not overlap and cover `[0, total_size)` without gaps.
+/// 
+/// # Arguments
+/// 
+/// * `label` - Label of the pipeline, used in the error messages.
+/// * `push_constants` - The push constant ranges.
+/// 
+/// # Errors
+/// 
+/// * `WRenderError::OverlappingPushConstants` - Two ranges share bytes.
+/// * `WRenderError::GapInPushConstants` - Some bytes between 0 and the total size are not covered by a range.
+pub fn validate_push_constants(label: &str, push_constants: &[WPushConstantRange]) -> Result<(), WRenderError> {
+    let mut ranges: Vec<&WPushConstantRange> = push_constants.iter().collect();
+    ranges.sort_by_key(|push_constant| push_constant.range.start);
+
+    let mut end = 0;
+    for push_constant in ranges {
+        if push_constant.range.start < end {
+            error!(label, "The push constant range {:?} of stages {:?} overlaps the previous range ending at {}.",
+                push_constant.range, push_constant.stages, end);
+            return Err(WRenderError::OverlappingPushConstants);
+        }
+        if push_constant.range.start > end {
+            error!(label, "The push constant range {:?} of stages {:?} leaves a gap after the previous range ending at {}.",
+                push_constant.range, push_constant.stages, end);
+            return Err(WRenderError::GapInPushConstants);
+        }
+        end = push_constant.range.end;
+    }
+    Ok(())
+}
 
 /// Describes the depth/stencil attachment of a render pipeline.
 #[derive(Clone)]
 pub struct WDepthStencilDescriptor {
     /// Whether the pipeline should have a depth/stencil attachment.
     pub enabled: bool,
-    /// Whether the stencil attachment should be read-only.
+    /// Whether the depth of the fragments is written to the depth attachment.
     pub write: bool,
     /// The comparison function that the depth attachment will use.
-    pub compare: WCompareFunction
+    pub compare: WCompareFunction,
+    /// The format of the depth/stencil attachment, `WTexture::DEPTH_FORMAT` or `WTexture::DEPTH_STENCIL_FORMAT`.
+    pub format: WTextureFormat,
+    /// The stencil test and operations. By default, the stencil is neither tested nor written.
+    pub stencil: WStencilState,
+    /// The depth bias added to the depth of the fragments. By default, no bias is applied.
+    pub bias: WDepthBiasState
 }
 impl Default for WDepthStencilDescriptor {
     fn default() -> Self {
         Self {
             enabled: false,
             write: true,
-            compare: WCompareFunction::Less
+            compare: WCompareFunction::Less,
+            format: WTexture::DEPTH_FORMAT,
+            stencil: WStencilState::default(),
+            bias: WDepthBiasState::default()
         }
     }
 }
@@ -51,9 +132,19 @@ struct WRenderPipelineConfig {
     primitive_topology: wgpu::PrimitiveTopology,
     push_constants: Vec<wgpu::PushConstantRange>,
     bind_groups: Vec<wgpu::BindGroupLayout>,
+    bind_group_entries: Vec<Vec<WBindGroupLayoutEntry>>,
     vertex_shader: String,
     fragment_shader: String,
     cull_mode: Option<WFace>,
+    front_face: WFrontFace,
+    polygon_mode: WPolygonMode,
+    sample_count: u32,
+    blend: WBlendState,
+    target_blends: Vec<WBlendState>,
+    conservative: bool,
+    depth_clamp: bool,
+    vertex_input: bool,
+    shared_layout: Option<Arc<WPipelineLayout>>,
 }
 
 
@@ -82,7 +173,7 @@ pub struct WRenderPipeline {
     pub label: String,
     is_initialized: bool,
     pipeline: Option<wgpu::RenderPipeline>,
-    layout: Option<wgpu::PipelineLayout>,
+    layout: Option<Arc<WPipelineLayout>>,
     config: WRenderPipelineConfig,
 }
 
@@ -99,7 +190,7 @@ impl WRenderPipeline {
     /// Create a new render pipeline.
     /// By default, the render pipeline does not have a depth or stencil.
     /// By default, the primitive topology is `Topology::TriangleList`.
-    /// By default, the cull mode is `Some(Face::Back)`.
+    /// By default, the cull mode is `Some(Face::Back)`, with counter-clockwise front faces.
     /// 
     /// # Arguments
     /// 
@@ -116,9 +207,19 @@ impl WRenderPipeline {
                 primitive_topology: wgpu::PrimitiveTopology::TriangleList,
                 push_constants: Vec::new(),
                 bind_groups: Vec::new(),
+                bind_group_entries: Vec::new(),
                 vertex_shader: String::new(),
                 fragment_shader: String::new(),
                 cull_mode: Some(WFace::Back),
+                front_face: WFrontFace::Ccw,
+                polygon_mode: WPolygonMode::Fill,
+                sample_count: 1,
+                blend: WBlendState::REPLACE,
+                target_blends: Vec::new(),
+                conservative: false,
+                depth_clamp: false,
+                vertex_input: true,
+                shared_layout: None,
             },
         }
     }
@@ -160,12 +261,171 @@ impl WRenderPipeline {
         self
     }
 
+    /// Set the stencil test and operations of the pipeline.
+    /// This enables the depth/stencil attachment, and switches its format to `WTexture::DEPTH_STENCIL_FORMAT`
+    /// if it has no stencil aspect. The reference value is set with `WRenderPass::set_stencil_reference`.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `front` - The stencil operations of the front faces.
+    /// * `back` - The stencil operations of the back faces.
+    /// * `read_mask` - The mask of the stencil bits read by the test.
+    /// * `write_mask` - The mask of the stencil bits written by the operations.
+    pub fn set_stencil(&mut self, front: WStencilFaceState, back: WStencilFaceState, read_mask: u32, write_mask: u32) -> &mut Self {
+        self.config.depth.enabled = true;
+        if !self.config.depth.format.has_stencil_aspect() {
+            self.config.depth.format = WTexture::DEPTH_STENCIL_FORMAT;
+        }
+        self.config.depth.stencil = WStencilState { front, back, read_mask, write_mask };
+        self
+    }
+
+    /// Set the depth bias added to the depth of the fragments, to avoid the shadow acne when rendering shadow maps.
+    /// The bias is `constant * r + slope_scale * max_slope`, with `r` the smallest representable depth difference
+    /// and `max_slope` the depth slope of the primitive, limited to `clamp` if it is not 0.
+    /// Typical values for shadow maps are `constant = 2`, `slope_scale = 1.5` and `clamp = 0.0`.
+    /// Only the triangle topologies support a depth bias.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `constant` - The constant depth bias.
+    /// * `slope_scale` - The depth bias scaled by the slope of the primitive.
+    /// * `clamp` - The maximum depth bias, 0 for no limit.
+    pub fn set_depth_bias(&mut self, constant: i32, slope_scale: f32, clamp: f32) -> &mut Self {
+        self.config.depth.bias = WDepthBiasState { constant, slope_scale, clamp };
+        self
+    }
+
+    /// Enable or disable the writes to the depth attachment, keeping the depth test unchanged.
+    /// The sky and transparent passes should not write their depth.
+    pub fn set_depth_write_enabled(&mut self, enabled: bool) -> &mut Self {
+        self.config.depth.write = enabled;
+        self
+    }
+
+    /// Enable the depth test with the `Less` comparison, without writing to the depth attachment.
+    /// This is used by the transparent forward passes, that are occluded by the opaque geometry but do not occlude each other.
+    pub fn set_depth_test_only(&mut self) -> &mut Self {
+        self.config.depth = WDepthStencilDescriptor {
+            enabled: true,
+            write: false,
+            compare: WCompareFunction::Less,
+            ..self.config.depth.clone()
+        };
+        self
+    }
+
     /// Set the cull mode. None means no culling.
     pub fn set_cull_mode(&mut self, cull_mode: Option<WFace>) -> &mut Self {
         self.config.cull_mode = cull_mode;
         self
     }
 
+    /// Set the winding order of the front faces. By default, the counter-clockwise triangles are the front faces.
+    pub fn set_front_face(&mut self, front_face: WFrontFace) -> &mut Self {
+        self.config.front_face = front_face;
+        self
+    }
+
+    /// Set how the polygons are rasterized, e.g. `WPolygonMode::Line` to draw wireframes when debugging meshes.
+    /// The `Line` and `Point` modes require the `POLYGON_MODE_LINE` and `POLYGON_MODE_POINT` features,
+    /// and fall back to `Fill` when the device does not support them (see `WRenderInstanceData::supports_polygon_mode_line`).
+    pub fn set_polygon_mode(&mut self, mode: WPolygonMode) -> &mut Self {
+        self.config.polygon_mode = mode;
+        self
+    }
+
+    /// Set the number of samples per pixel of the render targets and depth attachment, for multi-sample anti-aliasing.
+    /// The render targets must be textures created with `WTexture::new_multisampled` with the same sample count.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `sample_count` - The number of samples (1, 2, 4 or 8). 1 disables the MSAA.
+    pub fn set_msaa(&mut self, sample_count: u32) -> &mut Self {
+        self.config.sample_count = sample_count;
+        self
+    }
+
+    /// Set the blending of the colors written to all the render targets. By default, the colors replace the targets.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `color_blend` - The blending of the color channels.
+    /// * `alpha_blend` - The blending of the alpha channel.
+    pub fn set_blend_state(&mut self, color_blend: WBlendComponent, alpha_blend: WBlendComponent) -> &mut Self {
+        self.config.blend = WBlendState { color: color_blend, alpha: alpha_blend };
+        self
+    }
+
+    /// Blend the colors over the render targets, with colors premultiplied by their alpha.
+    pub fn set_blend_premultiplied_alpha(&mut self) -> &mut Self {
+        self.config.blend = WBlendState::PREMULTIPLIED_ALPHA_BLENDING;
+        self
+    }
+
+    /// Set the blending of each render target, overriding the blending of `set_blend_state` for the first targets.
+    /// The targets without a blending of their own use the blending of `set_blend_state`.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `blends` - The blending of the first render targets, in the order of the targets.
+    pub fn set_target_blend_states(&mut self, blends: Vec<WBlendState>) -> &mut Self {
+        self.config.target_blends = blends;
+        self
+    }
+
+    /// Add the colors to the render targets, e.g. for particles or light accumulation.
+    pub fn set_blend_additive(&mut self) -> &mut Self {
+        let additive = WBlendComponent {
+            src_factor: wgpu::BlendFactor::One,
+            dst_factor: wgpu::BlendFactor::One,
+            operation: wgpu::BlendOperation::Add,
+        };
+        self.set_blend_state(additive, additive)
+    }
+
+    /// Enable or disable conservative rasterization.
+    /// When enabled, a pixel is covered by a triangle if the triangle touches any part of the pixel.
+    /// This requires the `CONSERVATIVE_RASTERIZATION` device feature, and is ignored if the feature is not available.
+    pub fn set_conservative_rasterization(&mut self, conservative: bool) -> &mut Self {
+        self.config.conservative = conservative;
+        self
+    }
+
+    /// Enable or disable depth clamping.
+    /// When enabled, the primitives are not clipped by the near and far planes and their depth is clamped instead,
+    /// for instance so that the shadow casters behind the near plane of a light still write to its shadow map.
+    /// This requires the `DEPTH_CLIP_CONTROL` device feature, and is ignored if the feature is not available
+    /// (see `WRenderPipeline::supports_depth_clamp`).
+    pub fn set_depth_clamp(&mut self, depth_clamp: bool) -> &mut Self {
+        self.config.depth_clamp = depth_clamp;
+        self
+    }
+
+    /// Whether the device supports depth clamping. When it does not, the pipelines relying on it
+    /// should extend their near and far planes instead.
+    pub fn supports_depth_clamp(instance: &WRenderInstanceData) -> bool {
+        instance.has_feature(WFeatures::DEPTH_CLIP_CONTROL)
+    }
+
+    /// Set whether the pipeline reads its vertices from a `WVertex` vertex buffer (default: true).
+    /// When disabled, the vertex shader has no vertex input and must build its vertices from the vertex index.
+    pub fn set_vertex_input(&mut self, vertex_input: bool) -> &mut Self {
+        self.config.vertex_input = vertex_input;
+        self
+    }
+
+    /// Use an existing pipeline layout, for instance shared with other pipelines using the same bind group layouts.
+    /// When set, the bind groups and push constants of the pipeline are ignored and must match the layout.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `layout` - The pipeline layout.
+    pub fn set_layout(&mut self, layout: Arc<WPipelineLayout>) -> &mut Self {
+        self.config.shared_layout = Some(layout);
+        self
+    }
+
     /// Add a set of bind groups via its layout to the render pipeline.
     /// Note that the order of the bind groups will be the same as the order of the bindings in the shaders.
     /// 
@@ -180,6 +440,17 @@ impl WRenderPipeline {
         self
     }
 
+    /// Set the entries of the layouts of the bind groups of the render pipeline, in the order of the bindings in the shaders.
+    /// They are used to check the bind groups set with `WRenderPass::set_bind_group_with_layout` in debug builds.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `entries` - The entries of each bind group layout.
+    pub fn set_bind_group_entries(&mut self, entries: Vec<Vec<WBindGroupLayoutEntry>>) -> &mut Self {
+        self.config.bind_group_entries = entries;
+        self
+    }
+
     /// Set the render targets of the render pipeline.
     ///
     /// # Arguments
@@ -222,6 +493,7 @@ impl WRenderPipeline {
             error!(self.label, "Pipeline does not have a vertex or fragment shader.");
             return Err(WRenderError::MissingShader);
         }
+        validate_push_constants(&self.label, &d.push_constants)?;
         
         // Load vertex shader
         trace!(self.label, "Loading shaders.");
@@ -284,15 +556,40 @@ impl WRenderPipeline {
             }
         };
 
+        // Check the conservative rasterization support
+        let conservative = d.conservative && instance.has_feature(WFeatures::CONSERVATIVE_RASTERIZATION);
+        if d.conservative && !conservative {
+            warn!(self.label, "Conservative rasterization is not supported by the device, it will be disabled.");
+        }
+
+        // Check the polygon mode support
+        let polygon_mode = match d.polygon_mode {
+            WPolygonMode::Line if !instance.has_feature(WFeatures::POLYGON_MODE_LINE) => {
+                warn!(self.label, "Line polygon mode is not supported by the device, the polygons will be filled.");
+                WPolygonMode::Fill
+            },
+            WPolygonMode::Point if !instance.has_feature(WFeatures::POLYGON_MODE_POINT) => {
+                warn!(self.label, "Point polygon mode is not supported by the device, the polygons will be filled.");
+                WPolygonMode::Fill
+            },
+            mode => mode
+        };
+
+        // Check the depth clamp support
+        let depth_clamp = d.depth_clamp && Self::supports_depth_clamp(instance);
+        if d.depth_clamp && !depth_clamp {
+            warn!(self.label, "Depth clamping is not supported by the device, it will be disabled.");
+        }
+
         // Create pipeline layout
         trace!(self.label, "Creating render pipeline instance.");
-        let layout = instance.device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            label: Some(format!("{}-render-pip-layout", self.label).as_str()),
-            bind_group_layouts: &d.bind_groups.iter().collect::<Vec<&wgpu::BindGroupLayout>>(),
-            push_constant_ranges: &d.push_constants,
-        });
+        let layout = match &d.shared_layout {
+            Some(layout) => layout.clone(),
+            None => Arc::new(create_pipeline_layout(instance, &self.label, &d.bind_groups, &d.push_constants))
+        };
 
         // Create pipeline
+        let vertex_buffers = if d.vertex_input { vec![WVertex::describe()] } else { vec![] };
         let mut res: Result<(), WRenderError> = Ok(());
         let pipeline = instance.device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
             label: Some(format!("{}-render-pip", self.label).as_str()),
@@ -301,15 +598,15 @@ impl WRenderPipeline {
             vertex: wgpu::VertexState {
                 module: &shader_module_vert,
                 entry_point: "main",
-                buffers: &[WVertex::describe()],
+                buffers: &vertex_buffers,
                 compilation_options: wgpu::PipelineCompilationOptions::default(),
             },
             fragment: Some(wgpu::FragmentState { // Always write to swapchain format
                 module: &shader_module_frag,
                 entry_point: "main",
-                targets: d.render_targets.iter().map(|format| Some(wgpu::ColorTargetState {
+                targets: d.render_targets.iter().enumerate().map(|(index, format)| Some(wgpu::ColorTargetState {
                     format: *format,
-                    blend: Some(wgpu::BlendState::REPLACE),
+                    blend: Some(*d.target_blends.get(index).unwrap_or(&d.blend)),
                     write_mask: wgpu::ColorWrites::ALL,
                 })).collect::<Vec<Option<wgpu::ColorTargetState>>>().as_slice(),
                 compilation_options: wgpu::PipelineCompilationOptions::default(),
@@ -317,15 +614,16 @@ impl WRenderPipeline {
             primitive: wgpu::PrimitiveState {
                 topology: d.primitive_topology,
                 strip_index_format: None,
-                front_face: wgpu::FrontFace::Ccw,
+                front_face: d.front_face,
                 cull_mode: d.cull_mode,
-                polygon_mode: wgpu::PolygonMode::Fill,
-                conservative: false,
-                unclipped_depth: false,
+                polygon_mode,
+                conservative,
+                unclipped_depth: depth_clamp,
             },
             depth_stencil: if d.depth.enabled { Some(wgpu::DepthStencilState {
-                format: match WTexture::DEPTH_FORMAT {
+                format: match d.depth.format {
                     WTextureFormat::Depth32Float => wgpu::TextureFormat::Depth32Float,
+                    WTextureFormat::Depth24PlusStencil8 => wgpu::TextureFormat::Depth24PlusStencil8,
                     _ => {
                         error!("Depth format is not supported for render pipeline '{}'.", self.label);
                         res = Err(WRenderError::UnsupportedDepthFormat);
@@ -334,10 +632,20 @@ impl WRenderPipeline {
                 },
                 depth_write_enabled: d.depth.write,
                 depth_compare: d.depth.compare,
-                stencil: wgpu::StencilState::default(),
-                bias: wgpu::DepthBiasState::default(),
+                stencil: if d.depth.format.has_stencil_aspect() {
+                    d.depth.stencil.clone()
+                } else {
+                    if d.depth.stencil.is_enabled() {
+                        error!("The stencil of render pipeline '{}' is ignored, as its depth format has no stencil.", self.label);
+                    }
+                    wgpu::StencilState::default()
+                },
+                bias: d.depth.bias,
             }) } else { None },
-            multisample: wgpu::MultisampleState::default(),
+            multisample: wgpu::MultisampleState {
+                count: d.sample_count,
+                ..Default::default()
+            },
             multiview: Default::default(),
         });
 
@@ -359,13 +667,31 @@ impl WRenderPipeline {
         self.pipeline.as_ref()
     }
 
+    /// Check if the render pipeline reads its vertices from a vertex buffer.
+    ///
+    /// # Returns
+    /// 
+    /// * `bool` - True if the pipeline has a vertex input, false otherwise.
+    pub fn has_vertex_input(&self) -> bool {
+        self.config.vertex_input
+    }
+
+    /// Get the entries of the layout of a bind group of the pipeline, if they were set.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `group` - The index of the bind group.
+    pub fn get_bind_group_entries(&self, group: u32) -> Option<&[WBindGroupLayoutEntry]> {
+        self.config.bind_group_entries.get(group as usize).map(|entries| entries.as_slice())
+    }
+
     /// Get the pipeline layout.
     /// 
     /// # Returns
     /// 
     /// * `Option<&PipelineLayout>` - The pipeline layout.
     pub fn get_layout(&self) -> Option<&wgpu::PipelineLayout> {
-        self.layout.as_ref()
+        self.layout.as_deref()
     }
 
     /// Check if the render pipeline is initialized.
@@ -0,0 +1,49 @@
+// Convert an equirectangular image to the six faces of a cubemap.
+// The format of the cubemap is set by replacing CUBE_FORMAT before compiling the shader.
+
+struct Params {
+    // Size of a face of the cubemap
+    face_size: u32,
+    // Size of the equirectangular image
+    width: u32,
+    height: u32,
+    padding: u32
+};
+
+var<push_constant> params: Params;
+
+@group(0) @binding(0) var<storage, read> equirect: array<vec4<f32>>;
+@group(0) @binding(1) var cube: texture_storage_2d_array<CUBE_FORMAT, write>;
+
+const PI: f32 = 3.14159265359;
+
+// Direction of a point of a face, with uv in [-1, 1] and v going down
+fn face_direction(face: u32, uv: vec2<f32>) -> vec3<f32> {
+    switch face {
+        case 0u: { return vec3<f32>(1.0, -uv.y, -uv.x); }  // +X
+        case 1u: { return vec3<f32>(-1.0, -uv.y, uv.x); }  // -X
+        case 2u: { return vec3<f32>(uv.x, 1.0, uv.y); }    // +Y
+        case 3u: { return vec3<f32>(uv.x, -1.0, -uv.y); }  // -Y
+        case 4u: { return vec3<f32>(uv.x, -uv.y, 1.0); }   // +Z
+        default: { return vec3<f32>(-uv.x, -uv.y, -1.0); } // -Z
+    }
+}
+
+@compute @workgroup_size(8, 8, 1)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    if id.x >= params.face_size || id.y >= params.face_size || id.z >= 6u {
+        return;
+    }
+
+    // Direction of the texel
+    let uv = (vec2<f32>(id.xy) + 0.5) / f32(params.face_size) * 2.0 - 1.0;
+    let direction = normalize(face_direction(id.z, uv));
+
+    // Spherical coordinates of the direction in the equirectangular image
+    let u = atan2(direction.z, direction.x) / (2.0 * PI) + 0.5;
+    let v = acos(clamp(direction.y, -1.0, 1.0)) / PI;
+    let x = min(u32(u * f32(params.width)), params.width - 1u);
+    let y = min(u32(v * f32(params.height)), params.height - 1u);
+
+    textureStore(cube, vec2<i32>(id.xy), i32(id.z), equirect[y * params.width + x]);
+}
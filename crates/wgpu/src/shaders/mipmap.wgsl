@@ -0,0 +1,43 @@
+// Downsample a mip level of a texture into the next one.
+// The format of the next level is set by replacing MIP_FORMAT, and SRGB_VALUE is replaced by whether
+// the texels are srgb encoded, in which case they are averaged in linear space.
+
+const SRGB: bool = SRGB_VALUE;
+
+@group(0) @binding(0) var source: texture_2d<f32>;
+@group(0) @binding(1) var destination: texture_storage_2d<MIP_FORMAT, write>;
+
+fn to_linear(color: vec4<f32>) -> vec4<f32> {
+    if !SRGB {
+        return color;
+    }
+    let rgb = select(pow((color.rgb + 0.055) / 1.055, vec3<f32>(2.4)), color.rgb / 12.92, color.rgb <= vec3<f32>(0.04045));
+    return vec4<f32>(rgb, color.a);
+}
+
+fn to_srgb(color: vec4<f32>) -> vec4<f32> {
+    if !SRGB {
+        return color;
+    }
+    let rgb = select(1.055 * pow(color.rgb, vec3<f32>(1.0 / 2.4)) - 0.055, color.rgb * 12.92, color.rgb <= vec3<f32>(0.0031308));
+    return vec4<f32>(rgb, color.a);
+}
+
+@compute @workgroup_size(8, 8, 1)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let size = textureDimensions(destination);
+    if id.x >= size.x || id.y >= size.y {
+        return;
+    }
+
+    // Average the four texels of the source level, as a bilinear sample at their center.
+    // The last row and column of odd sizes are clamped.
+    let max_texel = vec2<i32>(textureDimensions(source)) - 1;
+    let texel = vec2<i32>(id.xy) * 2;
+    let color = to_linear(textureLoad(source, min(texel, max_texel), 0))
+        + to_linear(textureLoad(source, min(texel + vec2<i32>(1, 0), max_texel), 0))
+        + to_linear(textureLoad(source, min(texel + vec2<i32>(0, 1), max_texel), 0))
+        + to_linear(textureLoad(source, min(texel + vec2<i32>(1, 1), max_texel), 0));
+
+    textureStore(destination, vec2<i32>(id.xy), to_srgb(color * 0.25));
+}
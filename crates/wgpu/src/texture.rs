@@ -1,9 +1,10 @@
 //! Contains the texture struct and its implementations.
 
 use bevy::{log::Level, utils::tracing::event};
+use serde::{Deserialize, Serialize};
 use wgpu::TextureFormat;
 
-use crate::instance::WRenderInstanceData;
+use crate::{bind_group::{BindGroup, BindGroupLayout, WStorageTextureAccess}, buffer::{BufferBindingType, BufferUsage, WBuffer}, command_buffer::WCommandBuffer, compute_pipeline::WComputePipeline, instance::{WRenderError, WRenderInstanceData}, render_pipeline::WShaderStages};
 
 /// Surface texture.
 pub type WSurfaceTexture = wgpu::SurfaceTexture;
@@ -17,6 +18,104 @@ pub type WTextureUsages = wgpu::TextureUsages;
 /// Texture format.
 pub type WTextureFormat = wgpu::TextureFormat;
 
+/// Texture view dimension.
+pub type WTextureViewDimension = wgpu::TextureViewDimension;
+
+/// Texture sampler.
+pub type WSampler = wgpu::Sampler;
+
+/// Sampler address mode.
+pub type WAddressMode = wgpu::AddressMode;
+
+/// Sampler filter mode.
+pub type WFilterMode = wgpu::FilterMode;
+
+/// Sampler comparison function.
+pub type WCompareFunction = wgpu::CompareFunction;
+
+/// The description of the sampler of a texture.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct WSamplerDescriptor {
+    /// How the texture coordinates outside of [0, 1] are handled, on all the axes.
+    pub address_mode: WAddressMode,
+    /// The filter used when the texture is magnified.
+    pub mag_filter: WFilterMode,
+    /// The filter used when the texture is minified.
+    pub min_filter: WFilterMode,
+    /// The filter used between the mip levels.
+    pub mipmap_filter: WFilterMode,
+    /// The maximum anisotropy, from 1 (disabled) to 16. All the filters must be linear when it is above 1.
+    pub anisotropy_clamp: u16,
+    /// The comparison function of a depth comparison sampler, if any.
+    pub compare: Option<WCompareFunction>,
+}
+impl WSamplerDescriptor {
+    /// Linear filtering, clamped to the edges.
+    pub const LINEAR_CLAMP: Self = Self {
+        address_mode: WAddressMode::ClampToEdge,
+        mag_filter: WFilterMode::Linear,
+        min_filter: WFilterMode::Linear,
+        mipmap_filter: WFilterMode::Linear,
+        anisotropy_clamp: 1,
+        compare: None,
+    };
+    /// Nearest filtering, clamped to the edges, e.g. to read the texels of render targets.
+    pub const NEAREST_CLAMP: Self = Self {
+        address_mode: WAddressMode::ClampToEdge,
+        mag_filter: WFilterMode::Nearest,
+        min_filter: WFilterMode::Nearest,
+        mipmap_filter: WFilterMode::Nearest,
+        anisotropy_clamp: 1,
+        compare: None,
+    };
+    /// Trilinear and 16x anisotropic filtering, repeated, for the textures of the materials.
+    pub const TRILINEAR_ANISOTROPIC: Self = Self {
+        address_mode: WAddressMode::Repeat,
+        mag_filter: WFilterMode::Linear,
+        min_filter: WFilterMode::Linear,
+        mipmap_filter: WFilterMode::Linear,
+        anisotropy_clamp: 16,
+        compare: None,
+    };
+
+    /// Whether the sampler interpolates between the texels or mip levels.
+    pub fn is_filtering(&self) -> bool {
+        self.mag_filter == WFilterMode::Linear || self.min_filter == WFilterMode::Linear || self.mipmap_filter == WFilterMode::Linear
+    }
+}
+impl Default for WSamplerDescriptor {
+    fn default() -> Self {
+        Self::LINEAR_CLAMP
+    }
+}
+
+/// A face of a cubemap, in the order of the layers of the cubemap texture.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WCubeFace {
+    PositiveX = 0,
+    NegativeX = 1,
+    PositiveY = 2,
+    NegativeY = 3,
+    PositiveZ = 4,
+    NegativeZ = 5,
+}
+
+/// The compute shader converting an equirectangular image to the faces of a cubemap.
+const EQUIRECT_TO_CUBE_SHADER: &str = include_str!("shaders/equirect_to_cube.wgsl");
+
+/// The compute shader downsampling a mip level of a texture into the next one.
+const MIPMAP_SHADER: &str = include_str!("shaders/mipmap.wgsl");
+
+/// Get the name in the shaders of a format that can be written as a storage texture.
+fn storage_format_name(format: WTextureFormat) -> Option<&'static str> {
+    match format {
+        WTextureFormat::Rgba8Unorm => Some("rgba8unorm"),
+        WTextureFormat::Rgba16Float => Some("rgba16float"),
+        WTextureFormat::Rgba32Float => Some("rgba32float"),
+        _ => None
+    }
+}
+
 /// Texture struct.
 /// 
 /// # Example
@@ -25,13 +124,28 @@ pub type WTextureFormat = wgpu::TextureFormat;
 /// // Create a new texture
 /// let texture = WTexture::new(&instance,
 ///     "Texture Label", (1024, 1024), TextureFormat::Rgba8Unorm,
-///     TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC);
+///     TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC, Some(1));
 /// 
 /// // Copy buffer to texture
 /// texture.copy_from_buffer(&instance, &buffer, false);
 /// 
 /// // Copy texture to texture
 /// texture.copy_from_texture(&instance, &texture, (1024, 1024));
+/// 
+/// // Create a texture array of 4 layers, and a view of its layer 2
+/// let array = WTexture::new_array(&instance,
+///     "Array Label", (1024, 1024), 4, TextureFormat::Rgba8Unorm,
+///     TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_SRC);
+/// let layer_view = array.view_layer(2);
+/// 
+/// // Copy the layer 2 of the array to the layer 0 of another array
+/// other_array.copy_from_texture_layers(&instance, &array.texture, (1024, 1024), 2, 0);
+/// 
+/// // Create a cubemap from an equirectangular image
+/// let cubemap = WTexture::new_cube(&instance,
+///     "Cubemap Label", 512, TextureFormat::Rgba16Float,
+///     TextureUsages::TEXTURE_BINDING | TextureUsages::STORAGE_BINDING);
+/// cubemap.load_from_equirectangular(&instance, &equirect_buffer)?;
 /// ```
 pub struct WTexture {
     pub label: String,
@@ -40,6 +154,10 @@ pub struct WTexture {
     pub view: WTextureView,
     pub sampler: wgpu::Sampler,
     pub size: (u32, u32),
+    /// The number of layers of the texture, or its depth for a 3D texture.
+    pub layers: u32,
+    /// The dimension of the default view of the texture.
+    pub dimension: WTextureViewDimension,
 }
 
 impl std::fmt::Debug for WTexture {
@@ -48,6 +166,8 @@ impl std::fmt::Debug for WTexture {
             .field("label", &self.label)
             .field("sampler", &self.sampler)
             .field("size", &self.size)
+            .field("layers", &self.layers)
+            .field("dimension", &self.dimension)
             .finish()
     }
 }
@@ -57,6 +177,8 @@ impl WTexture {
     pub const SWAPCHAIN_FORMAT: WTextureFormat = WTextureFormat::Bgra8UnormSrgb;
     /// The depth texture format.
     pub const DEPTH_FORMAT: WTextureFormat = WTextureFormat::Depth32Float;
+    /// The format of the depth textures with a stencil aspect.
+    pub const DEPTH_STENCIL_FORMAT: WTextureFormat = WTextureFormat::Depth24PlusStencil8;
 
     /// Create a new texture.
     /// 
@@ -67,22 +189,127 @@ impl WTexture {
     /// * `size` - Size of the texture.
     /// * `format` - Format of the texture.
     /// * `usage` - Usage of the texture.
-    pub fn new(instance: &WRenderInstanceData<'_>, label: &str, size: (u32, u32), format: WTextureFormat, usage: WTextureUsages) -> Self {
+    /// * `mip_level_count` - Number of mip levels of the texture, or none for the full mip chain (see `mip_level_count`).
+    pub fn new(instance: &WRenderInstanceData<'_>, label: &str, size: (u32, u32), format: WTextureFormat, usage: WTextureUsages, mip_level_count: Option<u32>) -> Self {
         event!(Level::DEBUG, "Creating wgpu texture {}.", label);
-        
+        let mip_level_count = mip_level_count.unwrap_or_else(|| Self::mip_level_count(size));
+        Self::create(instance, label, size, 1, WTextureViewDimension::D2, format, usage, mip_level_count, 1)
+    }
+
+    /// Create a new multisampled 2D texture, used as a MSAA render target resolved into a single sampled texture.
+    /// Only the sample counts 1 and 4 are supported by all the devices and formats.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `instance` - Game instance.
+    /// * `label` - Label of the texture.
+    /// * `size` - Size of the texture.
+    /// * `format` - Format of the texture.
+    /// * `usage` - Usage of the texture, usually `RENDER_ATTACHMENT`.
+    /// * `sample_count` - Number of samples per texel.
+    pub fn new_multisampled(instance: &WRenderInstanceData<'_>, label: &str, size: (u32, u32), format: WTextureFormat, usage: WTextureUsages, sample_count: u32) -> Self {
+        event!(Level::DEBUG, "Creating wgpu texture {} with {} samples.", label, sample_count);
+        Self::create(instance, label, size, 1, WTextureViewDimension::D2, format, usage, 1, sample_count)
+    }
+
+    /// The number of samples per texel of the texture, 1 if the texture is not multisampled.
+    pub fn sample_count(&self) -> u32 {
+        self.texture.sample_count()
+    }
+
+    /// Get the number of mip levels of the full mip chain of a texture, down to a 1x1 level.
+    /// This is `floor(log2(max(width, height))) + 1`.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `size` - Size of the texture.
+    pub fn mip_level_count(size: (u32, u32)) -> u32 {
+        u32::BITS - size.0.max(size.1).max(1).leading_zeros()
+    }
+
+    /// Create a new 2D texture array.
+    /// The texture view will be a `texture_2d_array` with `layers` layers.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `instance` - Game instance.
+    /// * `label` - Label of the texture.
+    /// * `size` - Size of each layer of the texture.
+    /// * `layers` - Number of layers of the texture.
+    /// * `format` - Format of the texture.
+    /// * `usage` - Usage of the texture.
+    pub fn new_array(instance: &WRenderInstanceData<'_>, label: &str, size: (u32, u32), layers: u32, format: WTextureFormat, usage: WTextureUsages) -> Self {
+        event!(Level::DEBUG, "Creating wgpu texture array {} with {} layers.", label, layers);
+        let dimension = if layers > 1 { WTextureViewDimension::D2Array } else { WTextureViewDimension::D2 };
+        Self::create(instance, label, size, layers, dimension, format, usage, 1, 1)
+    }
+
+    /// Create a new 3D texture, e.g. for volumetric data.
+    /// The texture view will be a `texture_3d`.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `instance` - Game instance.
+    /// * `label` - Label of the texture.
+    /// * `extent` - Width, height and depth of the texture.
+    /// * `format` - Format of the texture.
+    /// * `usage` - Usage of the texture.
+    pub fn new_3d(instance: &WRenderInstanceData<'_>, label: &str, extent: (u32, u32, u32), format: WTextureFormat, usage: WTextureUsages) -> Self {
+        event!(Level::DEBUG, "Creating wgpu 3D texture {} of depth {}.", label, extent.2);
+        Self::create(instance, label, (extent.0, extent.1), extent.2, WTextureViewDimension::D3, format, usage, 1, 1)
+    }
+
+    /// Create a new cubemap texture of 6 square layers, in the order of `WCubeFace`.
+    /// The texture view will be a `texture_cube`, see `view_face` to access a single face.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `instance` - Game instance.
+    /// * `label` - Label of the texture.
+    /// * `size` - Width and height of each face of the texture.
+    /// * `format` - Format of the texture.
+    /// * `usage` - Usage of the texture.
+    pub fn new_cube(instance: &WRenderInstanceData<'_>, label: &str, size: u32, format: WTextureFormat, usage: WTextureUsages) -> Self {
+        event!(Level::DEBUG, "Creating wgpu cubemap texture {}.", label);
+        Self::create(instance, label, (size, size), 6, WTextureViewDimension::Cube, format, usage, 1, 1)
+    }
+
+    /// Create a new array of cubemap textures, each of 6 square layers in the order of `WCubeFace`.
+    /// The texture view will be a `texture_cube_array`, see `view_layer` to access the face `face` of the cubemap `i` at the layer `6 * i + face`.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `instance` - Game instance.
+    /// * `label` - Label of the texture.
+    /// * `size` - Width and height of each face of the textures.
+    /// * `count` - Number of cubemaps of the array.
+    /// * `format` - Format of the texture.
+    /// * `usage` - Usage of the texture.
+    pub fn new_cube_array(instance: &WRenderInstanceData<'_>, label: &str, size: u32, count: u32, format: WTextureFormat, usage: WTextureUsages) -> Self {
+        event!(Level::DEBUG, "Creating wgpu cubemap texture array {} with {} cubemaps.", label, count);
+        Self::create(instance, label, (size, size), 6 * count, WTextureViewDimension::CubeArray, format, usage, 1, 1)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn create(instance: &WRenderInstanceData<'_>, label: &str, size: (u32, u32), layers: u32, dimension: WTextureViewDimension, format: WTextureFormat, usage: WTextureUsages, mip_level_count: u32, sample_count: u32) -> Self {
         // Create texture
         let texture = instance.device.create_texture(&wgpu::TextureDescriptor {
             label: Some(format!("{}-texture", label).as_str()),
             size: wgpu::Extent3d {
                 width: size.0,
                 height: size.1,
-                depth_or_array_layers: 1,
+                depth_or_array_layers: layers,
+            },
+            mip_level_count,
+            sample_count,
+            dimension: if dimension == WTextureViewDimension::D3 {
+                wgpu::TextureDimension::D3
+            } else {
+                wgpu::TextureDimension::D2
             },
-            mip_level_count: 1,
-            sample_count: 1,
-            dimension: wgpu::TextureDimension::D2,
             format,
-            usage: usage | wgpu::TextureUsages::COPY_DST,
+            // The multisampled textures cannot be copied to
+            usage: if sample_count > 1 { usage } else { usage | wgpu::TextureUsages::COPY_DST },
             view_formats: &[]
         });
 
@@ -94,11 +321,7 @@ impl WTexture {
             } else {
                 Some(format)
             },
-            dimension: if format == Self::DEPTH_FORMAT {
-                None
-            } else {
-                Some(wgpu::TextureViewDimension::D2)
-            },
+            dimension: Some(dimension),
             aspect: wgpu::TextureAspect::All,
             base_mip_level: 0,
             base_array_layer: 0,
@@ -107,20 +330,7 @@ impl WTexture {
         });
 
         // Create sampler
-        let sampler = instance.device.create_sampler(&wgpu::SamplerDescriptor {
-            label: Some(format!("{}-texture-sampler", label).as_str()),
-            address_mode_u: wgpu::AddressMode::ClampToEdge,
-            address_mode_v: wgpu::AddressMode::ClampToEdge,
-            address_mode_w: wgpu::AddressMode::ClampToEdge,
-            mag_filter: wgpu::FilterMode::Linear,
-            min_filter: wgpu::FilterMode::Linear,
-            mipmap_filter: wgpu::FilterMode::Nearest,
-            lod_min_clamp: 0.0,
-            lod_max_clamp: 100.0,
-            compare: None,
-            anisotropy_clamp: 1,
-            border_color: None,
-        });
+        let sampler = Self::create_sampler(instance, label, &WSamplerDescriptor::default());
 
         // Return texture
         Self {
@@ -130,9 +340,351 @@ impl WTexture {
             view,
             sampler,
             size,
+            layers,
+            dimension,
         }
     }
 
+    /// Create a sampler.
+    /// The anisotropy is disabled if a filter of the sampler is not linear.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `instance` - Game instance.
+    /// * `label` - Label of the sampler.
+    /// * `desc` - Description of the sampler.
+    pub fn create_sampler(instance: &WRenderInstanceData<'_>, label: &str, desc: &WSamplerDescriptor) -> WSampler {
+        let mut anisotropy_clamp = desc.anisotropy_clamp.clamp(1, 16);
+        if anisotropy_clamp > 1 && !(desc.mag_filter == WFilterMode::Linear && desc.min_filter == WFilterMode::Linear && desc.mipmap_filter == WFilterMode::Linear) {
+            event!(Level::WARN, "Disabling the anisotropy of sampler {} as its filters are not all linear.", label);
+            anisotropy_clamp = 1;
+        }
+
+        instance.device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some(format!("{}-texture-sampler", label).as_str()),
+            address_mode_u: desc.address_mode,
+            address_mode_v: desc.address_mode,
+            address_mode_w: desc.address_mode,
+            mag_filter: desc.mag_filter,
+            min_filter: desc.min_filter,
+            mipmap_filter: desc.mipmap_filter,
+            lod_min_clamp: 0.0,
+            lod_max_clamp: 100.0,
+            compare: desc.compare,
+            anisotropy_clamp,
+            border_color: None,
+        })
+    }
+
+    /// Replace the sampler of the texture.
+    /// The bind groups created with the previous sampler keep using it, and must be created again.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `instance` - Game instance.
+    /// * `desc` - Description of the sampler.
+    pub fn set_sampler(&mut self, instance: &WRenderInstanceData<'_>, desc: &WSamplerDescriptor) {
+        self.sampler = Self::create_sampler(instance, &self.label, desc);
+    }
+
+    /// Create a view of a range of mip levels of the texture.
+    /// Useful for the passes reading from a mip level and writing to another one.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `base_mip` - The first mip level of the view.
+    /// * `mip_count` - The number of mip levels of the view.
+    /// 
+    /// # Panics
+    /// 
+    /// * If the range exceeds the mip levels of the texture.
+    pub fn view_mip_range(&self, base_mip: u32, mip_count: u32) -> WTextureView {
+        assert!(base_mip + mip_count <= self.texture.mip_level_count(),
+            "Mip range {}..{} exceeds the {} mip levels of texture {}.",
+            base_mip, base_mip + mip_count, self.texture.mip_level_count(), self.label);
+
+        self.texture.create_view(&wgpu::TextureViewDescriptor {
+            label: Some(format!("{}-texture-view-mip-{}-{}", self.label, base_mip, base_mip + mip_count).as_str()),
+            format: if self.format == Self::DEPTH_FORMAT {
+                None
+            } else {
+                Some(self.format)
+            },
+            dimension: if self.format == Self::DEPTH_FORMAT {
+                None
+            } else {
+                Some(self.dimension)
+            },
+            aspect: wgpu::TextureAspect::All,
+            base_mip_level: base_mip,
+            base_array_layer: 0,
+            mip_level_count: Some(mip_count),
+            array_layer_count: None
+        })
+    }
+
+    /// Create a view of the depth aspect of a depth/stencil texture, to sample its depth in the shaders.
+    pub fn view_depth(&self) -> WTextureView {
+        self.texture.create_view(&wgpu::TextureViewDescriptor {
+            label: Some(format!("{}-texture-view-depth", self.label).as_str()),
+            aspect: wgpu::TextureAspect::DepthOnly,
+            ..Default::default()
+        })
+    }
+
+    /// Create a 2D view of a single layer of the texture.
+    /// Useful to render to or sample a layer of a texture array.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `layer_index` - The index of the layer of the view.
+    /// 
+    /// # Panics
+    /// 
+    /// * If the layer exceeds the layers of the texture.
+    /// * If the texture is a 3D texture.
+    pub fn view_layer(&self, layer_index: u32) -> WTextureView {
+        assert!(self.dimension != WTextureViewDimension::D3, "Texture {} is a 3D texture without layers.", self.label);
+        assert!(layer_index < self.layers,
+            "Layer {} exceeds the {} layers of texture {}.", layer_index, self.layers, self.label);
+
+        self.texture.create_view(&wgpu::TextureViewDescriptor {
+            label: Some(format!("{}-texture-view-layer-{}", self.label, layer_index).as_str()),
+            format: if self.format == Self::DEPTH_FORMAT {
+                None
+            } else {
+                Some(self.format)
+            },
+            dimension: Some(wgpu::TextureViewDimension::D2),
+            aspect: wgpu::TextureAspect::All,
+            base_mip_level: 0,
+            base_array_layer: layer_index,
+            mip_level_count: None,
+            array_layer_count: Some(1)
+        })
+    }
+
+    /// Create a 2D array view of all the layers of a texture.
+    /// Unlike the default view, the view is an array even if the texture has a single layer.
+    /// 
+    /// # Panics
+    /// 
+    /// * If the texture is a 3D texture.
+    pub fn view_array(&self) -> WTextureView {
+        assert!(self.dimension != WTextureViewDimension::D3, "Texture {} is a 3D texture without layers.", self.label);
+
+        self.texture.create_view(&wgpu::TextureViewDescriptor {
+            label: Some(format!("{}-texture-view-array", self.label).as_str()),
+            dimension: Some(wgpu::TextureViewDimension::D2Array),
+            ..Default::default()
+        })
+    }
+
+    /// Create a 2D view of a face of a cubemap texture.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `face` - The face of the view.
+    pub fn view_face(&self, face: WCubeFace) -> WTextureView {
+        self.view_layer(face as u32)
+    }
+
+    /// Fill the faces of a cubemap texture from an equirectangular image, using a compute shader.
+    /// The texture must have the STORAGE_BINDING usage and the `Rgba8Unorm`, `Rgba16Float` or `Rgba32Float` format.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `instance` - Game instance.
+    /// * `buffer` - Storage buffer of the equirectangular image, twice as wide as high, with a `vec4<f32>` per pixel.
+    /// 
+    /// # Errors
+    /// 
+    /// * `WRenderError::UnsupportedStorageFormat` - The format of the texture cannot be written by the shader.
+    /// * `WRenderError::ShaderCompilationError` - The conversion shader failed to compile.
+    /// 
+    /// # Panics
+    /// 
+    /// * If the texture is not a cubemap, or the buffer is not a 2:1 image.
+    pub fn load_from_equirectangular(&self, instance: &WRenderInstanceData<'_>, buffer: &WBuffer) -> Result<(), WRenderError> {
+        event!(Level::DEBUG, "Loading cubemap texture {} from the equirectangular image {}.", self.label, buffer.label);
+        assert!(self.dimension == WTextureViewDimension::Cube, "Texture {} is not a cubemap.", self.label);
+
+        // Get the format of the storage texture in the shader
+        let format = match storage_format_name(self.format) {
+            Some(format) => format,
+            None => {
+                event!(Level::ERROR, "Cannot write the cubemap {} of format {:?} from a compute shader.", self.label, self.format);
+                return Err(WRenderError::UnsupportedStorageFormat);
+            }
+        };
+
+        // Get the size of the equirectangular image
+        let pixel_count = buffer.buffer.size() / std::mem::size_of::<[f32; 4]>() as u64;
+        let height = ((pixel_count / 2) as f64).sqrt() as u32;
+        let width = 2 * height;
+        assert!(height > 0 && (width * height) as u64 == pixel_count,
+            "Buffer {} of {} pixels is not a 2:1 equirectangular image.", buffer.label, pixel_count);
+
+        // Create the pipeline
+        let layout = BindGroupLayout::new("equirect-to-cube", |builder| {
+            builder.add_buffer(0, WShaderStages::COMPUTE, BufferBindingType::Storage { read_only: true });
+            builder.add_storage_texture_array(1, WShaderStages::COMPUTE, WStorageTextureAccess::WriteOnly, self.format);
+        });
+        let mut pipeline = WComputePipeline::new("equirect-to-cube");
+        pipeline
+            .set_shader(&EQUIRECT_TO_CUBE_SHADER.replace("CUBE_FORMAT", format))
+            .set_bind_groups(vec![layout.build(instance)])
+            .add_push_constant(std::mem::size_of::<[u32; 4]>() as u32);
+        pipeline.init(instance)?;
+
+        // Bind the faces as a texture array, as a cubemap cannot be written
+        let faces_view = self.texture.create_view(&wgpu::TextureViewDescriptor {
+            label: Some(format!("{}-texture-view-faces", self.label).as_str()),
+            dimension: Some(WTextureViewDimension::D2Array),
+            ..Default::default()
+        });
+        let bind_group = BindGroup::build("equirect-to-cube", instance, &layout.build(instance), &vec![
+            BindGroup::buffer(0, buffer),
+            BindGroup::storage_texture(1, &faces_view)
+        ]);
+
+        // Convert the image, with a thread per texel of each face
+        let face_size = self.size.0;
+        let mut command_buffer = WCommandBuffer::new(instance, "equirect-to-cube");
+        {
+            let mut compute_pass = command_buffer.create_compute_pass("equirect-to-cube");
+            compute_pass
+                .set_pipeline(&pipeline)?
+                .set_push_constants(bytemuck::cast_slice(&[face_size, width, height, 0]))
+                .set_bind_group(0, &bind_group);
+            compute_pass.dispatch(face_size.div_ceil(8), face_size.div_ceil(8), 6)?;
+        }
+        command_buffer.submit(instance);
+        Ok(())
+    }
+
+    /// Get the usages a texture of the given format requires for `generate_mipmaps`, or none if its mipmaps cannot be generated.
+    /// Only the `Rgba8Unorm`, `Rgba8UnormSrgb` and `Rgba16Float` formats are supported.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `format` - Format of the texture.
+    pub fn mipmap_usages(format: WTextureFormat) -> Option<WTextureUsages> {
+        match format {
+            // The srgb formats cannot be written by the shaders, their mip levels are generated in a separate texture
+            WTextureFormat::Rgba8UnormSrgb => Some(WTextureUsages::TEXTURE_BINDING | WTextureUsages::COPY_SRC),
+            WTextureFormat::Rgba8Unorm | WTextureFormat::Rgba16Float => Some(WTextureUsages::TEXTURE_BINDING | WTextureUsages::STORAGE_BINDING),
+            _ => None
+        }
+    }
+
+    /// Generate the mip levels of the texture from its first level, using a compute shader.
+    /// Each level is the average of the four texels of the previous level, in linear space for the srgb formats.
+    /// The texture must be a 2D texture with the usages of `mipmap_usages`.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `instance` - Game instance.
+    /// 
+    /// # Errors
+    /// 
+    /// * `WRenderError::UnsupportedStorageFormat` - The mip levels of a texture of this format cannot be generated.
+    /// * `WRenderError::ShaderCompilationError` - The downsampling shader failed to compile.
+    pub fn generate_mipmaps(&self, instance: &WRenderInstanceData<'_>) -> Result<(), WRenderError> {
+        let mip_level_count = self.texture.mip_level_count();
+        if mip_level_count <= 1 {
+            return Ok(());
+        }
+        event!(Level::DEBUG, "Generating the {} mip levels of texture {}.", mip_level_count, self.label);
+        assert!(self.dimension == WTextureViewDimension::D2, "Cannot generate the mip levels of the non 2D texture {}.", self.label);
+
+        // Generate the levels of the srgb textures in a texture with the same bytes without the srgb encoding
+        let srgb = self.format == WTextureFormat::Rgba8UnormSrgb;
+        let storage_format = if srgb { WTextureFormat::Rgba8Unorm } else { self.format };
+        let format = match (Self::mipmap_usages(self.format), storage_format_name(storage_format)) {
+            (Some(_), Some(format)) => format,
+            _ => {
+                event!(Level::ERROR, "Cannot generate the mip levels of texture {} of format {:?}.", self.label, self.format);
+                return Err(WRenderError::UnsupportedStorageFormat);
+            }
+        };
+        let srgb_texture = srgb.then(|| {
+            let texture = Self::create(instance, &format!("{}-mipmaps", self.label), self.size, 1, WTextureViewDimension::D2,
+                storage_format, WTextureUsages::TEXTURE_BINDING | WTextureUsages::STORAGE_BINDING | WTextureUsages::COPY_SRC, mip_level_count, 1);
+            texture.copy_mip_levels_from(instance, self, 0..1);
+            texture
+        });
+        let target = srgb_texture.as_ref().unwrap_or(self);
+
+        // Create the pipeline
+        let layout = BindGroupLayout::new("mipmap", |builder| {
+            builder.add_texture_view(0, WShaderStages::COMPUTE);
+            builder.add_storage_texture(1, WShaderStages::COMPUTE, WStorageTextureAccess::WriteOnly, storage_format);
+        });
+        let mut pipeline = WComputePipeline::new("mipmap");
+        pipeline
+            .set_shader(&MIPMAP_SHADER.replace("MIP_FORMAT", format).replace("SRGB_VALUE", &srgb.to_string()))
+            .set_bind_groups(vec![layout.build(instance)]);
+        pipeline.init(instance)?;
+
+        // Create a bind group per level, reading the previous level
+        let layout = layout.build(instance);
+        let views: Vec<WTextureView> = (0..mip_level_count).map(|level| target.view_mip_range(level, 1)).collect();
+        let bind_groups: Vec<wgpu::BindGroup> = (1..mip_level_count as usize).map(|level| {
+            BindGroup::build("mipmap", instance, &layout, &vec![
+                BindGroup::view(0, &views[level - 1]),
+                BindGroup::storage_texture(1, &views[level])
+            ])
+        }).collect();
+
+        // Downsample each level, with a thread per texel of the level
+        let mut command_buffer = WCommandBuffer::new(instance, "mipmap");
+        {
+            let mut compute_pass = command_buffer.create_compute_pass("mipmap");
+            compute_pass.set_pipeline(&pipeline)?;
+            for (level, bind_group) in (1..mip_level_count).zip(bind_groups.iter()) {
+                let width = (self.size.0 >> level).max(1);
+                let height = (self.size.1 >> level).max(1);
+                compute_pass.set_bind_group(0, bind_group);
+                compute_pass.dispatch(width.div_ceil(8), height.div_ceil(8), 1)?;
+            }
+        }
+        command_buffer.submit(instance);
+
+        // Copy the generated levels back to the srgb texture
+        if let Some(srgb_texture) = srgb_texture.as_ref() {
+            self.copy_mip_levels_from(instance, srgb_texture, 1..mip_level_count);
+        }
+        Ok(())
+    }
+
+    /// Copy mip levels of a texture of the same size and compatible format to the texture.
+    fn copy_mip_levels_from(&self, instance: &WRenderInstanceData<'_>, source: &WTexture, levels: std::ops::Range<u32>) {
+        let mut command = WCommandBuffer::new(instance, "Copy Mip Levels");
+        for level in levels {
+            command.encoder().copy_texture_to_texture(
+                wgpu::ImageCopyTexture {
+                    texture: &source.texture,
+                    mip_level: level,
+                    origin: wgpu::Origin3d::ZERO,
+                    aspect: wgpu::TextureAspect::All,
+                },
+                wgpu::ImageCopyTexture {
+                    texture: &self.texture,
+                    mip_level: level,
+                    origin: wgpu::Origin3d::ZERO,
+                    aspect: wgpu::TextureAspect::All,
+                },
+                wgpu::Extent3d {
+                    width: (self.size.0 >> level).max(1),
+                    height: (self.size.1 >> level).max(1),
+                    depth_or_array_layers: 1,
+                },
+            );
+        }
+        command.submit(instance);
+    }
+
 
     /// Copy buffer to texture.
     /// It is assumed that the buffer is the same size as the texture.
@@ -175,6 +727,67 @@ impl WTexture {
         );
     } 
     
+    /// Copy buffer to a 3D texture.
+    /// The buffer contains the slices of the texture one after the other, with tightly packed rows.
+    /// It will be copied on the next queue submit.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `instance` - Game instance.
+    /// * `buffer` - Image buffer of `width * height * depth` pixels.
+    pub fn copy_from_buffer_3d(&self, instance: &WRenderInstanceData, buffer: &[u8]) {
+        event!(Level::TRACE, "Copying buffer to 3D texture {}.", self.label);
+        let bytes_per_pixel = self.format.block_copy_size(None)
+            .unwrap_or_else(|| panic!("Cannot copy a buffer to the texture {} of format {:?}.", self.label, self.format));
+
+        instance.queue.write_texture(
+            self.texture.as_image_copy(),
+            buffer,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(self.size.0 * bytes_per_pixel),
+                rows_per_image: Some(self.size.1),
+            },
+            self.texture.size(),
+        );
+    }
+
+    /// Copy a 3D texture to a buffer.
+    /// The buffer receives the slices of the texture one after the other, with rows aligned to 256 bytes,
+    /// and must be at least `padded_bytes_per_row * height * depth` bytes large.
+    /// Note that the texture must have the COPY_SRC usage and the buffer the COPY_DST usage.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `instance` - Game instance.
+    /// * `buffer` - Buffer to copy to.
+    /// 
+    /// # Returns
+    /// 
+    /// The padded length of a row in the buffer in bytes, see `WBuffer::padded_bytes_per_row`.
+    pub fn copy_to_buffer_3d(&self, instance: &WRenderInstanceData, buffer: &WBuffer) -> u32 {
+        event!(Level::TRACE, "Copying 3D texture {} to buffer {}.", self.label, buffer.label);
+        let bytes_per_pixel = self.format.block_copy_size(None)
+            .unwrap_or_else(|| panic!("Cannot copy the texture {} of format {:?} to a buffer.", self.label, self.format));
+        let padded_bytes_per_row = WBuffer::padded_bytes_per_row(self.size.0, bytes_per_pixel);
+
+        let mut command = crate::command_buffer::WCommandBuffer::new(instance, "Copy 3D Texture");
+        command.encoder().copy_texture_to_buffer(
+            self.texture.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer.buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_bytes_per_row),
+                    rows_per_image: Some(self.size.1),
+                }
+            },
+            self.texture.size());
+        command.submit(instance);
+
+        padded_bytes_per_row
+    }
+    
     /// Copy texture to texture.
     /// It is assumed that the texture is the same size as the source texture.
     /// Note that the input texture must have the COPY_SRC usage, and the output texture must have the COPY_DST usage.
@@ -185,7 +798,36 @@ impl WTexture {
     /// * `texture` - Texture to copy from.
     /// * `size` - Size of the texture.
     pub fn copy_from_texture(&self, instance: &WRenderInstanceData<'_>, texture: &wgpu::Texture, size: (u32, u32)) {
-        event!(Level::TRACE, "Copying texture to texture.");
+        self.copy_from_texture_layer(instance, texture, size, 0);
+    }
+
+    /// Copy texture to a layer of the texture.
+    /// It is assumed that the texture is the same size as the source texture.
+    /// Note that the input texture must have the COPY_SRC usage, and the output texture must have the COPY_DST usage.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `instance` - Game instance.
+    /// * `texture` - Texture to copy from.
+    /// * `size` - Size of the texture.
+    /// * `layer` - Index of the layer of the texture to copy to.
+    pub fn copy_from_texture_layer(&self, instance: &WRenderInstanceData<'_>, texture: &wgpu::Texture, size: (u32, u32), layer: u32) {
+        self.copy_from_texture_layers(instance, texture, size, 0, layer);
+    }
+
+    /// Copy a layer of a texture to a layer of the texture.
+    /// It is assumed that the texture is the same size as the source texture.
+    /// Note that the input texture must have the COPY_SRC usage, and the output texture must have the COPY_DST usage.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `instance` - Game instance.
+    /// * `texture` - Texture to copy from.
+    /// * `size` - Size of the texture.
+    /// * `source_layer` - Index of the layer of the source texture to copy from.
+    /// * `layer` - Index of the layer of the texture to copy to.
+    pub fn copy_from_texture_layers(&self, instance: &WRenderInstanceData<'_>, texture: &wgpu::Texture, size: (u32, u32), source_layer: u32, layer: u32) {
+        event!(Level::TRACE, "Copying texture layer {} to texture layer {}.", source_layer, layer);
 
         // Create command buffer
         let mut command = crate::command_buffer::WCommandBuffer::new(instance, "Copy Texture");
@@ -195,13 +837,13 @@ impl WTexture {
             wgpu::ImageCopyTexture {
                 texture,
                 mip_level: 0,
-                origin: wgpu::Origin3d::ZERO,
+                origin: wgpu::Origin3d { x: 0, y: 0, z: source_layer },
                 aspect: wgpu::TextureAspect::All,
             },
             wgpu::ImageCopyTexture {
                 texture: &self.texture,
                 mip_level: 0,
-                origin: wgpu::Origin3d::ZERO,
+                origin: wgpu::Origin3d { x: 0, y: 0, z: layer },
                 aspect: wgpu::TextureAspect::All,
             },
             wgpu::Extent3d {
@@ -214,4 +856,53 @@ impl WTexture {
         // Submit the commands
         command.submit(instance);
     }
+
+    /// Read back the pixels of the first layer of the texture, blocking until the copy is done.
+    /// The rows of the returned data are tightly packed, without padding.
+    /// Note that the texture must have the COPY_SRC usage.
+    /// 
+    /// # Arguments
+    /// 
+    /// * `instance` - Game instance.
+    /// 
+    /// # Returns
+    /// 
+    /// The raw bytes of the pixels in the texture format, or none if the format cannot be copied (e.g. depth formats).
+    pub fn read_pixels(&self, instance: &WRenderInstanceData<'_>) -> Option<Vec<u8>> {
+        event!(Level::TRACE, "Reading back the pixels of texture {}.", self.label);
+        let bytes_per_pixel = self.format.block_copy_size(None)?;
+        let (width, height) = self.size;
+
+        // Copy the texture to a buffer, with rows aligned to the copy alignment
+        let padded_bytes_per_row = WBuffer::padded_bytes_per_row(width, bytes_per_pixel);
+        let buffer = WBuffer::new(instance, &format!("{}-readback", self.label),
+            (padded_bytes_per_row * height) as usize, BufferUsage::MAP_READ | BufferUsage::COPY_DST, None);
+        let mut command = crate::command_buffer::WCommandBuffer::new(instance, "Read Texture");
+        command.encoder().copy_texture_to_buffer(
+            self.texture.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer.buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_bytes_per_row),
+                    rows_per_image: None,
+                }
+            },
+            wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            });
+        command.submit(instance);
+
+        // Remove the padding of the rows
+        let bytes_per_row = (width * bytes_per_pixel) as usize;
+        let mut pixels = Vec::with_capacity(bytes_per_row * height as usize);
+        buffer.map_read(instance, |data| {
+            for row in data.chunks_exact(padded_bytes_per_row as usize) {
+                pixels.extend_from_slice(&row[..bytes_per_row]);
+            }
+        });
+        Some(pixels)
+    }
 }
@@ -7,6 +7,8 @@
 /// * `position` - The position of the vertex (location 0).
 /// * `uv`       - The texture UV of the vertex (location 1).
 /// * `normal`   - The normal of the vertex (location 2).
+/// * `uv1`      - The second texture UV of the vertex, used for lightmaps (location 3).
+/// * `tangent`  - The tangent of the vertex, used for normal mapping (location 4).
 #[repr(C)]
 #[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable, Debug, Default)]
 pub struct WVertex {
@@ -16,6 +18,11 @@ pub struct WVertex {
     pub uv: [f32; 2],
     /// The normal of the vertex (must be normalized).
     pub normal: [f32; 3],
+    /// The second texture UV of the vertex, used for lightmaps (must be between 0.0 and 1.0).
+    pub uv1: [f32; 2],
+    /// The tangent of the vertex (xyz), with the sign of the bitangent (w).
+    /// Zero if the mesh has no tangents.
+    pub tangent: [f32; 4],
 }
 
 impl WVertex {
@@ -44,6 +51,16 @@ impl WVertex {
                     shader_location: 2,
                     format: wgpu::VertexFormat::Float32x3,
                 },
+                wgpu::VertexAttribute { // UV1
+                    offset: std::mem::size_of::<[f32; 8]>() as wgpu::BufferAddress,
+                    shader_location: 3,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+                wgpu::VertexAttribute { // Tangent
+                    offset: std::mem::size_of::<[f32; 10]>() as wgpu::BufferAddress,
+                    shader_location: 4,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
             ],
         }
     }
@@ -0,0 +1,33 @@
+use wde_wgpu::adapter::{adapter_vram, WAdapterPreference};
+
+#[test]
+fn no_vendor_uses_the_default_adapter() {
+    let instance = wgpu::Instance::default();
+    let preference = WAdapterPreference { discrete: true, vendor_id: None };
+    assert!(preference.select_adapter(&instance, None).is_none());
+}
+
+#[test]
+fn unknown_vendor_uses_the_default_adapter() {
+    let instance = wgpu::Instance::default();
+    let preference = WAdapterPreference { discrete: true, vendor_id: Some(0xFFFF) };
+    assert!(preference.select_adapter(&instance, None).is_none());
+}
+
+#[test]
+fn preferred_vendor_selects_its_adapter() {
+    let instance = wgpu::Instance::default();
+    let Some(adapter) = instance.enumerate_adapters(wgpu::Backends::all()).into_iter().next() else {
+        eprintln!("No gpu adapter available, skipping the test preferred_vendor_selects_its_adapter.");
+        return;
+    };
+    let vendor_id = adapter.get_info().vendor;
+    let preference = WAdapterPreference { discrete: true, vendor_id: Some(vendor_id) };
+    let selected = preference.select_adapter(&instance, None).unwrap();
+    assert_eq!(selected.get_info().vendor, vendor_id);
+
+    // The video memory is only known with the Vulkan backend
+    if selected.get_info().backend != wgpu::Backend::Vulkan {
+        assert!(adapter_vram(&selected).is_none());
+    }
+}
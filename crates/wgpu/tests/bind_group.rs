@@ -0,0 +1,62 @@
+mod common;
+
+use wde_wgpu::{bind_group::{BindGroup, BindGroupLayout, WStorageTextureAccess}, buffer::{BufferUsage, WBuffer}, command_buffer::WCommandBuffer, compute_pipeline::WComputePipeline, render_pipeline::WShaderStages, texture::{WTexture, WTextureFormat, WTextureUsages}};
+
+const CHECKERBOARD_SHADER: &str = "
+@group(0) @binding(0) var out_texture: texture_storage_2d<rgba8unorm, write>;
+
+@compute @workgroup_size(8, 8, 1)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let value = f32((id.x + id.y) % 2u);
+    textureStore(out_texture, vec2<i32>(id.xy), vec4<f32>(value, value, value, 1.0));
+}
+";
+
+#[test]
+fn storage_texture_is_written_by_a_compute_shader() {
+    let Some(instance) = common::instance("storage_texture_is_written_by_a_compute_shader") else { return };
+    let instance = instance.data.read().unwrap();
+
+    // Create the texture and the pipeline writing to it
+    let size = 16;
+    let format = WTextureFormat::Rgba8Unorm;
+    let texture = WTexture::new(&instance, "checkerboard", (size, size), format,
+        WTextureUsages::STORAGE_BINDING | WTextureUsages::COPY_SRC, Some(1));
+    let layout = BindGroupLayout::new("checkerboard", |builder| {
+        builder.add_storage_texture_for(0, &texture, WStorageTextureAccess::WriteOnly, format, WShaderStages::COMPUTE);
+    });
+    let mut pipeline = WComputePipeline::new("checkerboard");
+    pipeline
+        .set_shader(CHECKERBOARD_SHADER)
+        .set_bind_groups(vec![layout.build(&instance)]);
+    pipeline.init(&instance).unwrap();
+    let layout = layout.build(&instance);
+    let bind_group = BindGroup::build("checkerboard", &instance, &layout, &vec![
+        BindGroup::storage_texture_view(0, &texture)
+    ]);
+
+    // Write the checkerboard and copy the texture to a buffer
+    let bytes_per_row = WBuffer::padded_bytes_per_row(size, 4);
+    let buffer = WBuffer::new(&instance, "checkerboard", (bytes_per_row * size) as usize,
+        BufferUsage::MAP_READ | BufferUsage::COPY_DST, None);
+    let mut command_buffer = WCommandBuffer::new(&instance, "checkerboard");
+    {
+        let mut compute_pass = command_buffer.create_compute_pass("checkerboard");
+        compute_pass.set_pipeline(&pipeline).unwrap();
+        compute_pass.set_bind_group(0, &bind_group);
+        compute_pass.dispatch(size / 8, size / 8, 1).unwrap();
+    }
+    command_buffer.copy_texture_to_padded_buffer(&texture.texture, &buffer, bytes_per_row);
+    command_buffer.submit(&instance);
+
+    // Check the pixels
+    buffer.map_read(&instance, |data| {
+        for y in 0..size {
+            for x in 0..size {
+                let index = (y * bytes_per_row + x * 4) as usize;
+                let value = if (x + y) % 2 == 1 { 255 } else { 0 };
+                assert_eq!(&data[index..index + 4], &[value, value, value, 255], "pixel ({}, {})", x, y);
+            }
+        }
+    });
+}
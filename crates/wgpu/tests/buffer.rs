@@ -0,0 +1,70 @@
+mod common;
+
+use wde_wgpu::buffer::{BufferUsage, WBuffer};
+
+const USAGE: BufferUsage = BufferUsage::STORAGE.union(BufferUsage::COPY_SRC).union(BufferUsage::COPY_DST);
+
+fn bytes(count: usize) -> Vec<u8> {
+    (0..count).map(|i| (i % 251) as u8 + 1).collect()
+}
+
+#[test]
+fn resize_keeps_the_content_when_growing() {
+    let Some(instance) = common::instance("resize_keeps_the_content_when_growing") else { return };
+    let instance = instance.data.read().unwrap();
+
+    let content = bytes(64);
+    let mut buffer = WBuffer::new(&instance, "buffer", content.len(), USAGE, Some(&content));
+    assert!(buffer.resize(&instance, 256, true));
+
+    let data = common::read_buffer(&instance, &buffer);
+    assert_eq!(data.len(), 256);
+    assert_eq!(&data[..64], &content[..]);
+    assert!(data[64..].iter().all(|&byte| byte == 0));
+}
+
+#[test]
+fn resize_truncates_the_content_when_shrinking() {
+    let Some(instance) = common::instance("resize_truncates_the_content_when_shrinking") else { return };
+    let instance = instance.data.read().unwrap();
+
+    let content = bytes(256);
+    let mut buffer = WBuffer::new(&instance, "buffer", content.len(), USAGE, Some(&content));
+    assert!(buffer.resize(&instance, 64, true));
+
+    assert_eq!(common::read_buffer(&instance, &buffer), &content[..64]);
+}
+
+#[test]
+fn resize_discards_the_content() {
+    let Some(instance) = common::instance("resize_discards_the_content") else { return };
+    let instance = instance.data.read().unwrap();
+
+    let content = bytes(64);
+    let mut buffer = WBuffer::new(&instance, "buffer", content.len(), USAGE, Some(&content));
+    assert!(!buffer.resize(&instance, 64, true));
+    assert!(buffer.resize(&instance, 128, false));
+
+    assert!(common::read_buffer(&instance, &buffer).iter().all(|&byte| byte == 0));
+}
+
+#[test]
+fn copy_range_only_writes_the_destination_range() {
+    let Some(instance) = common::instance("copy_range_only_writes_the_destination_range") else { return };
+    let instance = instance.data.read().unwrap();
+
+    let src_content = bytes(128);
+    let dst_content = vec![0xAA; 128];
+    let src = WBuffer::new(&instance, "src", src_content.len(), USAGE, Some(&src_content));
+    let dst = WBuffer::new(&instance, "dst", dst_content.len(), USAGE, Some(&dst_content));
+
+    // Copy two ranges, the second one at the end of the buffer
+    dst.copy_range(&instance, &src, 16, 32, 24);
+    dst.copy_range(&instance, &src, 0, 120, 8);
+
+    let mut expected = dst_content.clone();
+    expected[32..56].copy_from_slice(&src_content[16..40]);
+    expected[120..128].copy_from_slice(&src_content[0..8]);
+    assert_eq!(common::read_buffer(&instance, &dst), expected);
+    assert_eq!(common::read_buffer(&instance, &src), src_content);
+}
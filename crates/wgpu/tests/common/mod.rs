@@ -0,0 +1,25 @@
+//! Helpers shared by the gpu tests.
+//! The tests return early when no gpu adapter is available.
+#![allow(dead_code)]
+
+use wde_wgpu::{buffer::{BufferUsage, WBuffer}, instance::{create_headless_instance, WRenderInstance, WRenderInstanceData}};
+
+/// Create a headless render instance, or none if no gpu adapter is available.
+pub fn instance(label: &str) -> Option<WRenderInstance<'static>> {
+    let instance = bevy::tasks::block_on(create_headless_instance(label));
+    if instance.is_none() {
+        eprintln!("No gpu adapter available, skipping the test {}.", label);
+    }
+    instance
+}
+
+/// Read back the whole content of a buffer with the COPY_SRC usage.
+pub fn read_buffer(instance: &WRenderInstanceData, buffer: &WBuffer) -> Vec<u8> {
+    let size = buffer.buffer.size() as usize;
+    let readback = WBuffer::new(instance, "readback", size, BufferUsage::MAP_READ | BufferUsage::COPY_DST, None);
+    readback.copy_from_buffer(instance, buffer);
+
+    let mut content = Vec::with_capacity(size);
+    readback.map_read(instance, |data| content.extend_from_slice(&data));
+    content
+}
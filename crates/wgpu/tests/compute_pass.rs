@@ -0,0 +1,69 @@
+mod common;
+
+use wde_wgpu::{bind_group::{BindGroup, BindGroupLayout, WBufferBindingType, WgpuBindGroup}, buffer::{BufferUsage, WBuffer}, command_buffer::WCommandBuffer, compute_pipeline::WComputePipeline, instance::WRenderInstanceData, render_pipeline::WShaderStages};
+
+// Write the workgroup count (4, 1, 1) after the first four values of the buffer
+const WRITE_ARGS_SHADER: &str = "
+@group(0) @binding(0) var<storage, read_write> args: array<u32>;
+
+@compute @workgroup_size(1, 1, 1)
+fn main() {
+    args[4] = 4u;
+    args[5] = 1u;
+    args[6] = 1u;
+}
+";
+// Mark the element of each workgroup
+const MARK_SHADER: &str = "
+@group(0) @binding(0) var<storage, read_write> marks: array<u32>;
+
+@compute @workgroup_size(1, 1, 1)
+fn main(@builtin(workgroup_id) id: vec3<u32>) {
+    marks[id.x] = id.x + 1u;
+}
+";
+
+/// Create a compute pipeline with a single storage buffer, and its bind group.
+fn pipeline(instance: &WRenderInstanceData, label: &str, shader: &str, buffer: &WBuffer) -> (WComputePipeline, WgpuBindGroup) {
+    let layout = BindGroupLayout::new(label, |builder| {
+        builder.add_buffer(0, WShaderStages::COMPUTE, WBufferBindingType::Storage { read_only: false });
+    });
+    let mut pipeline = WComputePipeline::new(label);
+    pipeline
+        .set_shader(shader)
+        .set_bind_groups(vec![layout.build(instance)]);
+    pipeline.init(instance).unwrap();
+    let bind_group = BindGroup::build(label, instance, &layout.build(instance), &vec![BindGroup::buffer(0, buffer)]);
+    (pipeline, bind_group)
+}
+
+#[test]
+fn dispatch_indirect_reads_the_workgroup_count_written_by_a_previous_pass() {
+    let Some(instance) = common::instance("dispatch_indirect_reads_the_workgroup_count_written_by_a_previous_pass") else { return };
+    let instance = instance.data.read().unwrap();
+
+    let args = WBuffer::new(&instance, "args", 8 * 4, BufferUsage::STORAGE | BufferUsage::INDIRECT, None);
+    let marks = WBuffer::new(&instance, "marks", 8 * 4, BufferUsage::STORAGE | BufferUsage::COPY_SRC, None);
+    let (write_args, write_args_bind_group) = pipeline(&instance, "write-args", WRITE_ARGS_SHADER, &args);
+    let (mark, mark_bind_group) = pipeline(&instance, "mark", MARK_SHADER, &marks);
+
+    // Write the arguments, then dispatch from them
+    let mut command_buffer = WCommandBuffer::new(&instance, "dispatch-indirect");
+    {
+        let mut compute_pass = command_buffer.create_compute_pass("write-args");
+        compute_pass.set_pipeline(&write_args).unwrap();
+        compute_pass.set_bind_group(0, &write_args_bind_group);
+        compute_pass.dispatch(1, 1, 1).unwrap();
+    }
+    {
+        let mut compute_pass = command_buffer.create_compute_pass("mark");
+        compute_pass.set_pipeline(&mark).unwrap();
+        compute_pass.set_bind_group(0, &mark_bind_group);
+        compute_pass.dispatch_indirect(&args, 4 * 4).unwrap();
+    }
+    command_buffer.submit(&instance);
+
+    // Only the four dispatched workgroups marked their element
+    let marks: Vec<u32> = bytemuck::cast_slice(&common::read_buffer(&instance, &marks)).to_vec();
+    assert_eq!(marks, [1, 2, 3, 4, 0, 0, 0, 0]);
+}
@@ -0,0 +1,75 @@
+mod common;
+
+use wde_wgpu::{command_buffer::{RenderPassBuilder, RenderPassColorAttachment, WColor, WCommandBuffer, WLoadOp}, instance::WRenderInstanceData, render_pipeline::{WFace, WFrontFace, WRenderPipeline, WShaderStages}, texture::{WTexture, WTextureFormat, WTextureUsages}};
+
+// A triangle covering the center of the target, counter-clockwise on screen,
+// or seen from the other side, i.e. clockwise, when drawn with the instance 1
+const TRIANGLE_VERT: &str = "
+@vertex
+fn main(@builtin(vertex_index) index: u32, @builtin(instance_index) instance: u32) -> @builtin(position) vec4<f32> {
+    var positions = array<vec2<f32>, 3>(vec2<f32>(-0.8, -0.8), vec2<f32>(0.8, -0.8), vec2<f32>(0.0, 0.8));
+    var position = positions[index];
+    if instance == 1u {
+        position.x = -position.x;
+    }
+    return vec4<f32>(position, 0.0, 1.0);
+}
+";
+const TRIANGLE_FRAG: &str = "
+@fragment
+fn main() -> @location(0) vec4<f32> {
+    return vec4<f32>(1.0);
+}
+";
+
+/// Draw the triangle from the front or from the back with the back faces culled, and count the covered pixels.
+fn covered_pixels(instance: &WRenderInstanceData, front_face: WFrontFace, from_back: bool) -> usize {
+    let format = WTextureFormat::Rgba8Unorm;
+    let texture = WTexture::new(instance, "front-face", (32, 32), format,
+        WTextureUsages::RENDER_ATTACHMENT | WTextureUsages::COPY_SRC, Some(1));
+    let mut pipeline = WRenderPipeline::new("front-face");
+    pipeline
+        .set_shader(TRIANGLE_VERT, WShaderStages::VERTEX)
+        .set_shader(TRIANGLE_FRAG, WShaderStages::FRAGMENT)
+        .set_render_targets(vec![format])
+        .set_cull_mode(Some(WFace::Back))
+        .set_front_face(front_face)
+        .set_vertex_input(false);
+    pipeline.init(instance).unwrap();
+
+    let mut command_buffer = WCommandBuffer::new(instance, "front-face");
+    {
+        let mut render_pass = command_buffer.create_render_pass("front-face", |builder: &mut RenderPassBuilder| {
+            builder.add_color_attachment(RenderPassColorAttachment {
+                texture: Some(&texture.view),
+                load: WLoadOp::Clear(WColor::BLACK),
+                ..Default::default()
+            });
+        });
+        render_pass.set_pipeline(&pipeline).unwrap();
+        let instance_index = from_back as u32;
+        render_pass.draw(0..3, instance_index..instance_index + 1).unwrap();
+    }
+    command_buffer.submit(instance);
+
+    texture.read_pixels(instance).unwrap().chunks_exact(4)
+        .filter(|pixel| pixel[0] > 0)
+        .count()
+}
+
+#[test]
+fn back_faces_are_culled_depending_on_the_front_face() {
+    let Some(instance) = common::instance("back_faces_are_culled_depending_on_the_front_face") else { return };
+    let instance = instance.data.read().unwrap();
+
+    // Counter-clockwise front faces, the default
+    assert!(covered_pixels(&instance, WFrontFace::Ccw, false) > 0);
+    assert_eq!(covered_pixels(&instance, WFrontFace::Ccw, true), 0);
+
+    // Clockwise front faces
+    assert_eq!(covered_pixels(&instance, WFrontFace::Cw, false), 0);
+    assert!(covered_pixels(&instance, WFrontFace::Cw, true) > 0);
+
+    // Both sides cover the same pixels
+    assert_eq!(covered_pixels(&instance, WFrontFace::Ccw, false), covered_pixels(&instance, WFrontFace::Cw, true));
+}
@@ -0,0 +1,19 @@
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>
+};
+
+@group(0) @binding(0) var in_bloom_texture: texture_2d<f32>;
+@group(0) @binding(1) var in_bloom_sampler: sampler;
+struct Bloom {
+    params: vec4<f32> // x: intensity, y: threshold, z: knee
+}
+@group(0) @binding(2) var<uniform> in_bloom: Bloom;
+
+// Output the bloom, blended additively into the hdr color
+@fragment
+fn main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let uv    = in.clip_position.xy / (2.0 * vec2<f32>(textureDimensions(in_bloom_texture)));
+    let bloom = textureSampleLevel(in_bloom_texture, in_bloom_sampler, uv, 0.0).rgb;
+
+    return vec4<f32>(bloom * in_bloom.params.x, 0.0);
+}
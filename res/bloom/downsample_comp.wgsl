@@ -0,0 +1,41 @@
+// Downsample a level of the bloom into the next one, keeping only the bright pixels.
+// The threshold and the knee are 0 after the first level, so that the levels are downsampled as is.
+
+@group(0) @binding(0) var in_source: texture_2d<f32>;
+@group(0) @binding(1) var in_sampler: sampler;
+@group(0) @binding(2) var out_destination: texture_storage_2d<rgba16float, write>;
+struct Bloom {
+    params: vec4<f32> // x: intensity, y: threshold, z: knee
+}
+@group(0) @binding(3) var<uniform> in_bloom: Bloom;
+
+// Keep the part of the color above the threshold, with a quadratic transition of the width of the knee
+fn soft_threshold(color: vec3<f32>) -> vec3<f32> {
+    let threshold  = in_bloom.params.y;
+    let knee       = in_bloom.params.z;
+    let brightness = max(color.r, max(color.g, color.b));
+    var soft = clamp(brightness - threshold + knee, 0.0, 2.0 * knee);
+    soft = soft * soft / (4.0 * knee + 1e-4);
+    let contribution = max(soft, brightness - threshold) / max(brightness, 1e-4);
+    return color * contribution;
+}
+
+@compute @workgroup_size(8, 8, 1)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let size = textureDimensions(out_destination);
+    if id.x >= size.x || id.y >= size.y {
+        return;
+    }
+
+    // Four bilinear samples at the corners of the destination texel, each averaging 2x2 source texels, and the center
+    let texel  = 1.0 / vec2<f32>(size);
+    let uv     = (vec2<f32>(id.xy) + 0.5) * texel;
+    let corner = texel * 0.5;
+    let color = (textureSampleLevel(in_source, in_sampler, uv + vec2<f32>(-corner.x, -corner.y), 0.0).rgb
+        + textureSampleLevel(in_source, in_sampler, uv + vec2<f32>( corner.x, -corner.y), 0.0).rgb
+        + textureSampleLevel(in_source, in_sampler, uv + vec2<f32>(-corner.x,  corner.y), 0.0).rgb
+        + textureSampleLevel(in_source, in_sampler, uv + vec2<f32>( corner.x,  corner.y), 0.0).rgb) * 0.125
+        + textureSampleLevel(in_source, in_sampler, uv, 0.0).rgb * 0.5;
+
+    textureStore(out_destination, vec2<i32>(id.xy), vec4<f32>(soft_threshold(color), 1.0));
+}
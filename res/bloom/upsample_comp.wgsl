@@ -0,0 +1,28 @@
+// Upsample a level of the bloom with a 3x3 tent filter, and accumulate it with the downsampled level of the destination.
+
+@group(0) @binding(0) var in_source: texture_2d<f32>;
+@group(0) @binding(1) var in_sampler: sampler;
+@group(0) @binding(2) var out_destination: texture_storage_2d<rgba16float, write>;
+@group(0) @binding(3) var in_downsampled: texture_2d<f32>;
+
+@compute @workgroup_size(8, 8, 1)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let size = textureDimensions(out_destination);
+    if id.x >= size.x || id.y >= size.y {
+        return;
+    }
+
+    // Tent filter with a radius of a texel of the source
+    let texel = 1.0 / vec2<f32>(textureDimensions(in_source));
+    let uv    = (vec2<f32>(id.xy) + 0.5) / vec2<f32>(size);
+    var color = vec3<f32>(0.0);
+    for (var x = -1; x <= 1; x = x + 1) {
+        for (var y = -1; y <= 1; y = y + 1) {
+            let weight = f32((2 - abs(x)) * (2 - abs(y)));
+            color += textureSampleLevel(in_source, in_sampler, uv + vec2<f32>(f32(x), f32(y)) * texel, 0.0).rgb * weight;
+        }
+    }
+    color = color / 16.0 + textureLoad(in_downsampled, vec2<i32>(id.xy), 0).rgb;
+
+    textureStore(out_destination, vec2<i32>(id.xy), vec4<f32>(color, 1.0));
+}
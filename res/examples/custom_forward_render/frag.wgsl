@@ -7,11 +7,16 @@ struct VertexOutput {
 // Material description
 struct CustomMaterial {
     base_color: vec3<f32>,
-    has_texture: f32,
+    layer_count: f32,
 };
 @group(2) @binding(0) var<uniform> in_material: CustomMaterial;
-@group(2) @binding(1) var in_material_texture: texture_2d<f32>;
-@group(2) @binding(2) var in_material_sampler: sampler;
+@group(2) @binding(1) var in_material_textures: texture_2d_array<f32>;
+
+// Blend weight of each texture layer
+struct LayerBlendWeights {
+    weights: vec4<f32>,
+};
+var<push_constant> in_blend: LayerBlendWeights;
 
 @fragment
 fn main(in: VertexOutput) -> @location(0) vec4<f32> {
@@ -21,12 +26,20 @@ fn main(in: VertexOutput) -> @location(0) vec4<f32> {
     let intensity = max(dot(in.normal, light_dir), 0.0) + ambiant;
     var color = vec4<f32>(in_material.base_color, 1.0);
 
-    if (in_material.has_texture == 1.0) {
+    if (in_material.layer_count > 0.0) {
+        // Blend the texture layers
+        let size = vec2<f32>(textureDimensions(in_material_textures));
+        let uv = vec2<i32>(clamp(in.tex_coord, vec2<f32>(0.0), vec2<f32>(1.0)) * (size - 1.0));
+        var texel = vec3<f32>(0.0);
+        for (var layer = 0; layer < i32(in_material.layer_count); layer++) {
+            texel += in_blend.weights[layer] * textureLoad(in_material_textures, uv, layer, 0).rgb;
+        }
+
         let min = 0.2;
         let max = 0.9;
-        color.r *= map(textureSample(in_material_texture, in_material_sampler, in.tex_coord).r, min, max, 0.0, 1.0);
-        color.g *= map(textureSample(in_material_texture, in_material_sampler, in.tex_coord).g, min, max, 0.0, 1.0);
-        color.b *= map(textureSample(in_material_texture, in_material_sampler, in.tex_coord).b, min, max, 0.0, 1.0);
+        color.r *= map(texel.r, min, max, 0.0, 1.0);
+        color.g *= map(texel.g, min, max, 0.0, 1.0);
+        color.b *= map(texel.b, min, max, 0.0, 1.0);
     }
 
     return color * intensity;
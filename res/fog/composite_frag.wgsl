@@ -0,0 +1,11 @@
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>
+};
+
+@group(0) @binding(0) var in_fog_texture: texture_2d<f32>;
+
+// Output the in-scattered light and the opacity of the fog, blended over the hdr color
+@fragment
+fn main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return textureLoad(in_fog_texture, vec2<i32>(in.clip_position.xy), 0);
+}
@@ -0,0 +1,110 @@
+struct Camera {
+    world_to_ndc: mat4x4<f32>,
+    ndc_to_world: mat4x4<f32>,
+    position: vec4<f32>
+}
+struct Light {
+    /// World space position of the directional light for xyz. If it is the first element, the w component is the number of lights.
+    position_number: vec4<f32>,
+    /// World space direction of the light. The w component is the type of the light: 0 for directional, 1 for point, 2 for spot.
+    direction_type:  vec4<f32>,
+    /// Ambient color of the light. The w component is the constant attenuation factor if the light is a point light.
+    ambient_const:   vec4<f32>,
+    /// Diffuse color of the light. The w component is the linear attenuation factor if the light is a point light.
+    diffuse_linea:   vec4<f32>,
+    /// Specular color of the light. The w component is the quadratic attenuation factor if the light is a point light.
+    specular_quadr:  vec4<f32>,
+    /// Cos of the inner and outer cut-off angles if the light is a spot light.
+    cut_off:         vec4<f32>
+};
+struct Fog {
+    color_density: vec4<f32>, // rgb: color, a: density
+    params: vec4<f32> // x: scattering, y: absorption, z: max distance, w: number of steps
+}
+
+@group(0) @binding(0) var in_depth_texture: texture_depth_2d;
+@group(0) @binding(1) var<uniform> in_camera: Camera;
+@group(0) @binding(2) var<storage> in_lights: array<Light>;
+@group(0) @binding(3) var<uniform> in_fog: Fog;
+@group(0) @binding(4) var out_fog: texture_storage_2d<rgba16float, write>;
+
+const PI: f32 = 3.14159265359;
+
+// World space position of a pixel at a depth
+fn world_from_screen_coord(uv: vec2<f32>, depth: f32) -> vec3<f32> {
+    let ndc_position   = vec4<f32>(uv.x * 2.0 - 1.0, (1 - uv.y) * 2.0 - 1.0, depth, 1.0);
+    let world_position = in_camera.ndc_to_world * ndc_position;
+    return world_position.xyz / world_position.w;
+}
+
+// Light of the lights reaching a position, scattered equally in all directions
+fn in_scattered(position: vec3<f32>) -> vec3<f32> {
+    let lights_count = i32(in_lights[0].position_number.w);
+    var light_sum = vec3<f32>(0.0);
+    for (var i = 0; i < lights_count; i = i + 1) {
+        let light = in_lights[i];
+        let light_type = i32(light.direction_type.w);
+        var radiance = light.diffuse_linea.rgb;
+
+        // Point light or spot light attenuation
+        if light_type == 1 || light_type == 2 {
+            let distance = length(light.position_number.xyz - position);
+            radiance /= light.ambient_const.w
+                + light.diffuse_linea.w * distance
+                + light.specular_quadr.w * distance * distance;
+        }
+
+        // Spot light cone
+        if light_type == 2 {
+            let light_dir = normalize(position - light.position_number.xyz);
+            let theta     = dot(light_dir, normalize(light.direction_type.xyz));
+            radiance *= smoothstep(light.cut_off.y, light.cut_off.x, theta);
+        }
+        light_sum += light.ambient_const.rgb + radiance / (4.0 * PI);
+    }
+    return light_sum;
+}
+
+// Interleaved gradient noise, offsetting the samples of neighbour pixels to hide the banding
+fn noise(pixel: vec2<f32>) -> f32 {
+    return fract(52.9829189 * fract(dot(pixel, vec2<f32>(0.06711056, 0.00583715))));
+}
+
+@compute @workgroup_size(8, 8, 1)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let size = textureDimensions(out_fog);
+    if (id.x >= size.x || id.y >= size.y) {
+        return;
+    }
+
+    // Ray from the camera to the scene, up to the maximum distance for the background
+    let uv       = (vec2<f32>(id.xy) + 0.5) / vec2<f32>(size);
+    let depth    = textureLoad(in_depth_texture, vec2<i32>(id.xy), 0);
+    let start    = in_camera.position.xyz;
+    let end      = world_from_screen_coord(uv, depth);
+    let ray      = end - start;
+    let distance = min(length(ray), in_fog.params.z);
+    let ray_dir  = normalize(ray);
+
+    // Extinction of the medium
+    let density    = in_fog.color_density.a;
+    let scattering = density * in_fog.params.x;
+    let extinction = max(density * (in_fog.params.x + in_fog.params.y), 1e-5);
+
+    // March the ray, accumulating the in-scattered light attenuated by the fog in front of it
+    let steps = max(u32(in_fog.params.w), 1u);
+    let step_length = distance / f32(steps);
+    let step_transmittance = exp(-extinction * step_length);
+    var transmittance = 1.0;
+    var light = vec3<f32>(0.0);
+    for (var i = 0u; i < steps; i = i + 1u) {
+        let position = start + ray_dir * (f32(i) + noise(vec2<f32>(id.xy))) * step_length;
+        let scattered = in_scattered(position) * in_fog.color_density.rgb * scattering;
+
+        // Integrate the light scattered along the step
+        light += transmittance * scattered * (1.0 - step_transmittance) / extinction;
+        transmittance *= step_transmittance;
+    }
+
+    textureStore(out_fog, vec2<i32>(id.xy), vec4<f32>(light, 1.0 - transmittance));
+}
@@ -0,0 +1,29 @@
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec4<f32>
+};
+
+// From world space to normalized device coordinates
+struct Camera {
+    world_to_ndc: mat4x4<f32>
+}
+@group(0) @binding(0) var<uniform> in_camera: Camera;
+
+// World space gizmo vertices
+struct GizmoVertex {
+    position: vec4<f32>,
+    color:    vec4<f32>
+}
+@group(1) @binding(0) var<storage> in_vertices: array<GizmoVertex>;
+
+
+@vertex
+fn main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    var out: VertexOutput;
+
+    let vertex = in_vertices[index];
+    out.clip_position = in_camera.world_to_ndc * vec4<f32>(vertex.position.xyz, 1.0);
+    out.color = vertex.color;
+
+    return out;
+}
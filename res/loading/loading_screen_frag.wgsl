@@ -0,0 +1,29 @@
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coord: vec2<f32>
+};
+
+// x: progress between 0 and 1
+var<push_constant> in_progress: vec4<f32>;
+
+@fragment
+fn main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let background = pow(vec3<f32>(0.1), vec3<f32>(2.2));
+    let bar_empty  = pow(vec3<f32>(0.2), vec3<f32>(2.2));
+    let bar_filled = pow(vec3<f32>(0.8), vec3<f32>(2.2));
+
+    // Progress bar bounds
+    let bar_min = vec2<f32>(0.2, 0.48);
+    let bar_max = vec2<f32>(0.8, 0.52);
+    if in.tex_coord.x < bar_min.x || in.tex_coord.x > bar_max.x
+        || in.tex_coord.y < bar_min.y || in.tex_coord.y > bar_max.y {
+        return vec4<f32>(background, 1.0);
+    }
+
+    // Fill the bar up to the progress
+    let bar_position = (in.tex_coord.x - bar_min.x) / (bar_max.x - bar_min.x);
+    if bar_position <= in_progress.x {
+        return vec4<f32>(bar_filled, 1.0);
+    }
+    return vec4<f32>(bar_empty, 1.0);
+}
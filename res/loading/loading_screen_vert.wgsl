@@ -0,0 +1,19 @@
+struct ModelInput {
+    @location(0) position: vec3<f32>,
+    @location(1) tex_coord: vec2<f32>,
+    @location(2) normal: vec3<f32>,
+};
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coord: vec2<f32>
+};
+
+@vertex
+fn main(model: ModelInput) -> VertexOutput {
+    var out: VertexOutput;
+
+    out.clip_position = vec4<f32>(model.position, 1.0);
+    out.tex_coord = vec2<f32>(model.tex_coord.x, 1.0 - model.tex_coord.y); // Flip Y
+
+    return out;
+}
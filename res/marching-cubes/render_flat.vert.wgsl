@@ -0,0 +1,44 @@
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) position: vec3<f32>,
+    @location(1) @interpolate(flat) normal: vec3<f32>
+};
+
+// From world space to normalized device coordinates
+struct Camera {
+    world_to_ndc: mat4x4<f32>,
+    ndc_to_world: mat4x4<f32>,
+    position: vec4<f32>
+}
+@group(0) @binding(0) var<uniform> in_camera: Camera;
+
+// Raw triangles generated by the marching cubes compute shader
+struct Triangle {
+    vertices: array<vec4<f32>, 3> // Position of the vertices (x, y, z) - the w components store the normal of the triangle
+}
+@group(2) @binding(0) var<storage, read> in_triangles: array<Triangle>;
+
+@vertex
+fn main(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+    var out: VertexOutput;
+
+    // Unpack the vertex from the triangles
+    let triangle_index = vertex_index / 3u;
+    let position = in_triangles[triangle_index].vertices[vertex_index % 3u].xyz;
+
+    // Flat normal of the triangle, computed from its edges if it is not stored
+    let v0 = in_triangles[triangle_index].vertices[0];
+    let v1 = in_triangles[triangle_index].vertices[1];
+    let v2 = in_triangles[triangle_index].vertices[2];
+    var normal = vec3<f32>(v0.w, v1.w, v2.w);
+    if (dot(normal, normal) == 0.0) {
+        normal = cross(v1.xyz - v0.xyz, v2.xyz - v0.xyz);
+    }
+
+    out.clip_position = in_camera.world_to_ndc
+        * vec4<f32>(position, 1.0);
+    out.position = position;
+    out.normal = normalize(normal);
+
+    return out;
+}
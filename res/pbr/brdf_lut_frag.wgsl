@@ -0,0 +1,57 @@
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>
+};
+
+const PI: f32 = 3.14159265359;
+const SAMPLE_COUNT: u32 = 1024u;
+const LUT_SIZE: f32 = 512.0;
+
+// Low discrepancy sequence over the hemisphere
+fn hammersley(i: u32, n: u32) -> vec2<f32> {
+    return vec2<f32>(f32(i) / f32(n), f32(reverseBits(i)) * 2.3283064365386963e-10);
+}
+
+// Sample a halfway vector following the GGX distribution
+fn importance_sample_ggx(xi: vec2<f32>, roughness: f32) -> vec3<f32> {
+    let a = roughness * roughness;
+    let phi = 2.0 * PI * xi.x;
+    let cos_theta = sqrt((1.0 - xi.y) / (1.0 + (a * a - 1.0) * xi.y));
+    let sin_theta = sqrt(1.0 - cos_theta * cos_theta);
+    return vec3<f32>(cos(phi) * sin_theta, sin(phi) * sin_theta, cos_theta);
+}
+
+// Smith-Schlick geometry term, with the k remapping of image based lighting
+fn geometry_schlick_ggx(n_dot_x: f32, roughness: f32) -> f32 {
+    let k = (roughness * roughness) / 2.0;
+    return n_dot_x / (n_dot_x * (1.0 - k) + k);
+}
+
+@fragment
+fn main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // u: dot(N, V), v: roughness
+    let uv = in.clip_position.xy / LUT_SIZE;
+    let n_dot_v = max(uv.x, 1e-4);
+    let roughness = uv.y;
+
+    // Integrate the BRDF over the hemisphere, with the normal along z
+    let v = vec3<f32>(sqrt(1.0 - n_dot_v * n_dot_v), 0.0, n_dot_v);
+    var scale = 0.0;
+    var bias = 0.0;
+    for (var i = 0u; i < SAMPLE_COUNT; i = i + 1u) {
+        let h = importance_sample_ggx(hammersley(i, SAMPLE_COUNT), roughness);
+        let l = normalize(2.0 * dot(v, h) * h - v);
+
+        let n_dot_l = max(l.z, 0.0);
+        let n_dot_h = max(h.z, 0.0);
+        let v_dot_h = max(dot(v, h), 0.0);
+        if n_dot_l > 0.0 {
+            let g = geometry_schlick_ggx(n_dot_v, roughness) * geometry_schlick_ggx(n_dot_l, roughness);
+            let g_vis = (g * v_dot_h) / (n_dot_h * n_dot_v);
+            let fresnel = pow(1.0 - v_dot_h, 5.0);
+            scale += (1.0 - fresnel) * g_vis;
+            bias += fresnel * g_vis;
+        }
+    }
+
+    return vec4<f32>(scale / f32(SAMPLE_COUNT), bias / f32(SAMPLE_COUNT), 0.0, 1.0);
+}
@@ -0,0 +1,14 @@
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>
+};
+
+// Full screen triangle, generated from the vertex index
+@vertex
+fn main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    var out: VertexOutput;
+
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    out.clip_position = vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+
+    return out;
+}
@@ -1,44 +1,131 @@
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
     @location(0) tex_coord:    vec2<f32>,
-    @location(1) normal_world: vec3<f32>  // Normal in world space
+    @location(1) normal_world: vec3<f32>, // Normal in world space
+    @location(2) @interpolate(flat) flags: u32, // Instance flags
+    @location(3) tex_coord_lightmap: vec2<f32>, // UV1, used for the lightmap
+    @location(4) @interpolate(flat) material_index: u32, // Index in the materials buffer
+    @location(5) tangent_world: vec4<f32> // Tangent in world space, with the sign of the bitangent
 };
 
 struct FragOutput {
-    @location(0) albedo:   vec4<f32>,
+    @location(0) albedo:   vec4<f32>, // rgb: albedo, a: roughness
     @location(1) normal:   vec4<f32>,
-    @location(2) material: vec4<f32>
+    @location(2) material: vec4<f32>,
+    @location(3) emissive: vec4<f32> // rgb: emissive, a: metallic
 };
 
-// Material description
+// Material description, indexed by the material index of the instance
 struct PbrMaterial {
-    flags:    vec4<f32>, // x: has_albedo, y: has_specular
+    flags:    vec4<f32>, // x: has_albedo, y: has_specular, z: has_lightmap, w: has_normal
     albedo:   vec4<f32>,
-    specular: f32
+    uv_transform: vec4<f32>, // xy: uv scale, zw: uv offset
+    emissive: vec4<f32>, // rgb: emissive color, w: has_emissive
+    specular: f32,
+    lightmap_intensity: f32,
+    roughness: f32,
+    metallic: f32,
+    has_roughness_metallic: f32,
+    has_ao: f32,
+    ao_strength: f32,
+    alpha_cutoff: f32
 };
-@group(2) @binding(0) var<uniform> in_material: PbrMaterial;
+@group(1) @binding(1) var<storage> in_materials: array<PbrMaterial>;
 @group(2) @binding(1) var in_albedo_texture: texture_2d<f32>;
 @group(2) @binding(2) var in_albedo_sampler: sampler;
 @group(2) @binding(3) var in_specular_texture: texture_2d<f32>;
 @group(2) @binding(4) var in_specular_sampler: sampler;
+@group(2) @binding(5) var in_lightmap_texture: texture_2d<f32>;
+@group(2) @binding(6) var in_lightmap_sampler: sampler;
+@group(2) @binding(7) var in_normal_texture: texture_2d<f32>;
+@group(2) @binding(8) var in_normal_sampler: sampler;
+@group(2) @binding(9) var in_emissive_texture: texture_2d<f32>;
+@group(2) @binding(10) var in_emissive_sampler: sampler;
+@group(2) @binding(11) var in_roughness_metallic_texture: texture_2d<f32>;
+@group(2) @binding(12) var in_roughness_metallic_sampler: sampler;
+@group(2) @binding(13) var in_ao_texture: texture_2d<f32>;
+@group(2) @binding(14) var in_ao_sampler: sampler;
 
 @fragment
-fn main(in: VertexOutput) -> FragOutput {
+fn main(in: VertexOutput, @builtin(front_facing) front_facing: bool) -> FragOutput {
     var out: FragOutput;
-    
+    let in_material = in_materials[in.material_index];
+
+    // Tile the textures using the material uv transform
+    // The samplers clamp to the edges, so the coordinates are wrapped manually with the gradients of the unwrapped coordinates
+    let tex_coord = in.tex_coord * in_material.uv_transform.xy + in_material.uv_transform.zw;
+    let tex_coord_dx = dpdx(tex_coord);
+    let tex_coord_dy = dpdy(tex_coord);
+
+    // Sample the textures in uniform control flow, as the material flags are not uniform
+    let albedo_sample = textureSampleGrad(in_albedo_texture, in_albedo_sampler, fract(tex_coord), tex_coord_dx, tex_coord_dy);
+    let specular_sample = textureSampleGrad(in_specular_texture, in_specular_sampler, fract(tex_coord), tex_coord_dx, tex_coord_dy).r;
+    let lightmap_sample = textureSample(in_lightmap_texture, in_lightmap_sampler, in.tex_coord_lightmap).rgb;
+    let normal_sample = textureSampleGrad(in_normal_texture, in_normal_sampler, fract(tex_coord), tex_coord_dx, tex_coord_dy).xyz;
+    let emissive_sample = textureSampleGrad(in_emissive_texture, in_emissive_sampler, fract(tex_coord), tex_coord_dx, tex_coord_dy).rgb;
+    let roughness_metallic_sample = textureSampleGrad(in_roughness_metallic_texture, in_roughness_metallic_sampler, fract(tex_coord), tex_coord_dx, tex_coord_dy);
+    let ao_sample = textureSampleGrad(in_ao_texture, in_ao_sampler, fract(tex_coord), tex_coord_dx, tex_coord_dy).r;
+
+    // Perturb the normal with the tangent space normal map, if the mesh has tangents
+    var normal_world = normalize(in.normal_world);
+    let tangent_length = length(in.tangent_world.xyz);
+    if (in_material.flags.w == 1.0 && tangent_length > 0.0) {
+        // Orthogonalize the interpolated tangent
+        let tangent = normalize(in.tangent_world.xyz - normal_world * dot(normal_world, in.tangent_world.xyz));
+        let bitangent = cross(normal_world, tangent) * sign(in.tangent_world.w);
+        let normal_tangent = normal_sample * 2.0 - 1.0;
+        normal_world = normalize(mat3x3<f32>(tangent, bitangent, normal_world) * normal_tangent);
+    }
+
+    // Flip the normal of the back faces (only rendered by double sided materials)
+    if (!front_facing) {
+        normal_world = -normal_world;
+    }
+
     // Read textures using material flags
     if (in_material.flags.x == 1.0) {
-        out.albedo = textureSample(in_albedo_texture, in_albedo_sampler, in.tex_coord);
+        out.albedo = albedo_sample;
     } else {
         out.albedo = in_material.albedo;
     }
+    // Discard the cut out fragments
+    if (out.albedo.a < in_material.alpha_cutoff) {
+        discard;
+    }
+    // Store the roughness and metallic factors in the unused alpha channels, scaled by the texture as in glTF 2.0
+    var roughness = in_material.roughness;
+    var metallic = in_material.metallic;
+    if (in_material.has_roughness_metallic == 1.0) {
+        roughness *= roughness_metallic_sample.g;
+        metallic *= roughness_metallic_sample.b;
+    }
+    out.albedo.a = roughness;
     if (in_material.flags.y == 1.0) {
-        let specular_intensity = textureSample(in_specular_texture, in_specular_sampler, in.tex_coord).r;
-        out.normal = vec4<f32>(normalize(in.normal_world), specular_intensity);
+        out.normal = vec4<f32>(normal_world, specular_sample);
     } else {
-        out.normal = vec4<f32>(normalize(in.normal_world), in_material.specular);
+        out.normal = vec4<f32>(normal_world, in_material.specular);
+    }
+    // Store whether the fragment receives shadows
+    var receive_shadows = 0.0;
+    if ((in.flags & 2u) != 0u) {
+        receive_shadows = 1.0;
+    }
+    // Store the baked indirect lighting and ambient occlusion (clamped to 1 by the texture format)
+    var lightmap = vec3<f32>(1.0);
+    if (in_material.flags.z == 1.0) {
+        lightmap = lightmap_sample * in_material.lightmap_intensity;
+    }
+    // Darken the indirect lighting with the ambient occlusion
+    if (in_material.has_ao == 1.0) {
+        lightmap *= mix(1.0, ao_sample, in_material.ao_strength);
+    }
+    out.material = vec4<f32>(receive_shadows, lightmap);
+    // Store the emitted color, unclamped
+    var emissive = in_material.emissive.rgb;
+    if (in_material.emissive.w == 1.0) {
+        emissive *= emissive_sample;
     }
-    out.material = vec4<f32>(0.0, 0.0, 0.0, 1.0);
+    out.emissive = vec4<f32>(emissive, metallic);
 
     return out;
 }
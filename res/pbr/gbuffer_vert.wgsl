@@ -1,12 +1,18 @@
 struct ModelInput {
     @location(0) position:  vec3<f32>,
     @location(1) tex_coord: vec2<f32>,
-    @location(2) normal:    vec3<f32>
+    @location(2) normal:    vec3<f32>,
+    @location(3) tex_coord_lightmap: vec2<f32>,
+    @location(4) tangent:   vec4<f32> // xyz: tangent, w: sign of the bitangent
 };
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
     @location(0) tex_coord:    vec2<f32>,
     @location(1) normal_world: vec3<f32>, // Normal in world space
+    @location(2) @interpolate(flat) flags: u32, // Instance flags
+    @location(3) tex_coord_lightmap: vec2<f32>, // UV1, used for the lightmap
+    @location(4) @interpolate(flat) material_index: u32, // Index in the materials buffer
+    @location(5) tangent_world: vec4<f32> // Tangent in world space, with the sign of the bitangent
 };
 
 // From world space to normalized device coordinates
@@ -17,7 +23,8 @@ struct Camera {
 
 // Object to world space transformation ssbo
 struct ObjectToWorld {
-    obj_to_world:  mat4x4<f32>
+    obj_to_world:  mat4x4<f32>,
+    flags:         vec4<u32> // x: 1 cast shadows, 2 receive shadows, y: material index
 }
 @group(1) @binding(0) var<storage> in_model: array<ObjectToWorld>;
 
@@ -31,10 +38,14 @@ fn main(@builtin(instance_index) instance: u32, model: ModelInput) -> VertexOutp
         * obj_to_world
         * vec4<f32>(model.position, 1.0);
     out.tex_coord = model.tex_coord;
+    out.tex_coord_lightmap = model.tex_coord_lightmap;
+    out.flags = in_model[instance].flags.x;
+    out.material_index = in_model[instance].flags.y;
 
     // Only works for uniform scaling
     let normal_matrix = mat3x3<f32>(obj_to_world[0].xyz, obj_to_world[1].xyz, obj_to_world[2].xyz);
     out.normal_world = normal_matrix * model.normal;
+    out.tangent_world = vec4<f32>(normal_matrix * model.tangent.xyz, model.tangent.w);
 
     return out;
 }
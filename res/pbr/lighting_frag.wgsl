@@ -19,6 +19,11 @@ struct Camera {
 @group(2) @binding(3) var in_normal_sampler:   sampler;
 @group(2) @binding(4) var in_material_texture: texture_2d<f32>;
 @group(2) @binding(5) var in_material_sampler: sampler;
+// Pre-integrated GGX BRDF. u: dot(N, V), v: roughness. r: scale of F0, g: bias
+@group(2) @binding(6) var in_brdf_lut_texture: texture_2d<f32>;
+@group(2) @binding(7) var in_brdf_lut_sampler: sampler;
+@group(2) @binding(8) var in_emissive_texture: texture_2d<f32>;
+@group(2) @binding(9) var in_emissive_sampler: sampler;
 
 struct Light {
     /// World space position of the directional light for xyz. If it is the first element, the w component is the number of lights.
@@ -36,6 +41,37 @@ struct Light {
 };
 @group(3) @binding(0) var<storage> in_lights: array<Light>;
 
+// Cascaded shadow map of the first directional light casting shadows
+struct ShadowCascades {
+    world_to_light: array<mat4x4<f32>, 4>, // From world space to the ndc of the light for each cascade
+    splits:         vec4<f32>, // View space depth at which each cascade ends
+    texel_sizes:    vec4<f32>, // World space size of a texel of each cascade
+    params:         vec4<f32>  // x: number of cascades, y: index of the shadowed light
+}
+@group(2) @binding(10) var<uniform> in_shadow_cascades: ShadowCascades;
+@group(2) @binding(11) var in_shadow_map: texture_depth_2d_array;
+@group(2) @binding(12) var in_shadow_sampler: sampler_comparison;
+
+// Cubemap shadow maps of the point lights casting shadows
+struct PointShadow {
+    position_far: vec4<f32>, // xyz: world space position of the light, w: far plane of the faces
+    params:       vec4<f32>  // x: near plane of the faces, y: index of the light (-1 if unused)
+}
+@group(2) @binding(13) var<storage> in_point_shadows: array<PointShadow>;
+@group(2) @binding(14) var in_point_shadow_maps: texture_depth_cube_array;
+
+// Shadow maps of the spot lights casting shadows
+struct SpotShadow {
+    world_to_light: mat4x4<f32>, // From world space to the ndc of the light
+    params:         vec4<f32>    // x: index of the light (-1 if unused)
+}
+@group(2) @binding(15) var<storage> in_spot_shadows: array<SpotShadow>;
+@group(2) @binding(16) var in_spot_shadow_maps: texture_depth_2d_array;
+
+// Screen-space ambient occlusion, 1 when the pixel is not occluded
+@group(2) @binding(17) var in_ssao_texture: texture_2d<f32>;
+@group(2) @binding(18) var in_ssao_sampler: sampler;
+
 
 
 fn world_from_screen_coord(uv: vec2<f32>, depth: f32) -> vec3<f32> {
@@ -45,6 +81,126 @@ fn world_from_screen_coord(uv: vec2<f32>, depth: f32) -> vec3<f32> {
     return world_position;
 }
 
+// Get the fraction of the light reaching a world space position from the cascaded shadow map, filtered with a 3x3 PCF.
+fn directional_shadow(position: vec3<f32>, normal: vec3<f32>, light_dir: vec3<f32>) -> f32 {
+    // Select the cascade from the view space depth
+    let view_depth = (in_camera.world_to_ndc * vec4<f32>(position, 1.0)).w;
+    let count = i32(in_shadow_cascades.params.x);
+    var cascade = 0;
+    while cascade < count && view_depth > in_shadow_cascades.splits[cascade] {
+        cascade = cascade + 1;
+    }
+    if cascade >= count {
+        return 1.0;
+    }
+
+    // Offset the position along the normal to avoid the shadow acne on the surfaces facing away from the light
+    let texel_size = in_shadow_cascades.texel_sizes[cascade];
+    let n_dot_l = clamp(dot(normal, light_dir), 0.0, 1.0);
+    let offset_position = position + normal * texel_size * 1.5 * (1.0 - n_dot_l);
+    let light_position = in_shadow_cascades.world_to_light[cascade] * vec4<f32>(offset_position, 1.0);
+    let light_ndc = light_position.xyz / light_position.w;
+    let uv = vec2<f32>(light_ndc.x * 0.5 + 0.5, 0.5 - light_ndc.y * 0.5);
+    if light_ndc.z > 1.0 {
+        return 1.0;
+    }
+
+    // Percentage closer filtering
+    let texel = 1.0 / vec2<f32>(textureDimensions(in_shadow_map));
+    var lit = 0.0;
+    for (var x = -1; x <= 1; x = x + 1) {
+        for (var y = -1; y <= 1; y = y + 1) {
+            lit += textureSampleCompareLevel(in_shadow_map, in_shadow_sampler,
+                uv + vec2<f32>(f32(x), f32(y)) * texel, cascade, light_ndc.z);
+        }
+    }
+    return lit / 9.0;
+}
+
+// Get the fraction of the light of a point light reaching a world space position from its cubemap shadow map, filtered with a PCF.
+// Returns 1 if the light has no cubemap.
+fn point_shadow(light_index: i32, position: vec3<f32>, normal: vec3<f32>) -> f32 {
+    // Find the cubemap of the light
+    var cubemap = -1;
+    for (var i = 0; i < i32(arrayLength(&in_point_shadows)); i = i + 1) {
+        if i32(in_point_shadows[i].params.y) == light_index {
+            cubemap = i;
+            break;
+        }
+    }
+    if cubemap < 0 {
+        return 1.0;
+    }
+    let shadow = in_point_shadows[cubemap];
+    let near = shadow.params.x;
+    let far = shadow.position_far.w;
+
+    // Depth of the position in the face looking along the major axis of the light direction, offset along the normal
+    let to_position = position + normal * 0.02 - shadow.position_far.xyz;
+    let abs_direction = abs(to_position);
+    let distance = max(abs_direction.x, max(abs_direction.y, abs_direction.z));
+    if distance >= far {
+        return 1.0;
+    }
+    let depth = far * (distance - near) / ((far - near) * distance);
+
+    // Percentage closer filtering, with offsets orthogonal to the direction
+    let direction = normalize(to_position);
+    var tangent = cross(direction, vec3<f32>(0.0, 1.0, 0.0));
+    if dot(tangent, tangent) < 0.01 {
+        tangent = cross(direction, vec3<f32>(1.0, 0.0, 0.0));
+    }
+    tangent = normalize(tangent);
+    let bitangent = cross(direction, tangent);
+    let radius = 1.5 / f32(textureDimensions(in_point_shadow_maps).x);
+    var lit = 0.0;
+    for (var x = -1; x <= 1; x = x + 1) {
+        for (var y = -1; y <= 1; y = y + 1) {
+            let offset = (tangent * f32(x) + bitangent * f32(y)) * radius;
+            lit += textureSampleCompareLevel(in_point_shadow_maps, in_shadow_sampler, direction + offset, cubemap, depth);
+        }
+    }
+    return lit / 9.0;
+}
+
+// Get the fraction of the light of a spot light reaching a world space position from its shadow map, filtered with a 3x3 PCF.
+// Returns 1 if the light has no shadow map.
+fn spot_shadow(light_index: i32, position: vec3<f32>, normal: vec3<f32>) -> f32 {
+    // Find the shadow map of the light
+    var layer = -1;
+    for (var i = 0; i < i32(arrayLength(&in_spot_shadows)); i = i + 1) {
+        if i32(in_spot_shadows[i].params.x) == light_index {
+            layer = i;
+            break;
+        }
+    }
+    if layer < 0 {
+        return 1.0;
+    }
+
+    // Project the position, offset along the normal, into the shadow map
+    let light_position = in_spot_shadows[layer].world_to_light * vec4<f32>(position + normal * 0.02, 1.0);
+    if light_position.w <= 0.0 {
+        return 1.0;
+    }
+    let light_ndc = light_position.xyz / light_position.w;
+    let uv = vec2<f32>(light_ndc.x * 0.5 + 0.5, 0.5 - light_ndc.y * 0.5);
+    if light_ndc.z > 1.0 || any(uv < vec2<f32>(0.0)) || any(uv > vec2<f32>(1.0)) {
+        return 1.0;
+    }
+
+    // Percentage closer filtering
+    let texel = 1.0 / vec2<f32>(textureDimensions(in_spot_shadow_maps));
+    var lit = 0.0;
+    for (var x = -1; x <= 1; x = x + 1) {
+        for (var y = -1; y <= 1; y = y + 1) {
+            lit += textureSampleCompareLevel(in_spot_shadow_maps, in_shadow_sampler,
+                uv + vec2<f32>(f32(x), f32(y)) * texel, layer, light_ndc.z);
+        }
+    }
+    return lit / 9.0;
+}
+
 @fragment
 fn main(in: VertexOutput) -> @location(0) vec4<f32> {
     // Read position of the object in world space
@@ -55,16 +211,32 @@ fn main(in: VertexOutput) -> @location(0) vec4<f32> {
     let position = world_from_screen_coord(in.tex_coord, depth);
 
     // Read G-Buffer
-    let g_albedo   = textureSample(in_albedo_texture, in_albedo_sampler, in.tex_coord).xyz;
+    let g_albedo_raw = textureSample(in_albedo_texture, in_albedo_sampler, in.tex_coord);
+    let g_albedo   = g_albedo_raw.xyz;
+    let g_roughness = g_albedo_raw.w;
     let g_norm_raw = textureSample(in_normal_texture, in_normal_sampler, in.tex_coord);
     let g_normal   = normalize(g_norm_raw.xyz);
     let g_specular = g_norm_raw.w;
-    let g_material = textureSample(in_material_texture, in_material_sampler, in.tex_coord);
+    let g_material = textureSample(in_material_texture, in_material_sampler, in.tex_coord); // x: receive shadows, yzw: lightmap and ambient occlusion
+    let g_emissive_raw = textureSample(in_emissive_texture, in_emissive_sampler, in.tex_coord);
+    let g_emissive = g_emissive_raw.rgb;
+    let g_metallic = g_emissive_raw.a;
+    let ssao = textureSample(in_ssao_texture, in_ssao_sampler, in.tex_coord).r;
 
     // General parameters
-    let shininess = 32.0;
+    // The Blinn-Phong shininess is matched to the roughness
+    let roughness = clamp(g_roughness, 0.05, 1.0);
+    let shininess = 2.0 / pow(roughness, 4.0) - 2.0;
     let view_dir  = normalize(in_camera.position.xyz - position);
 
+    // Specular reflectance using the split-sum approximation, F0 * lut.r + lut.g
+    // F0 is the specular intensity for the dielectrics, tinted by the albedo for the metals
+    let n_dot_v    = clamp(dot(g_normal, view_dir), 0.0, 1.0);
+    let env_brdf   = textureSample(in_brdf_lut_texture, in_brdf_lut_sampler, vec2<f32>(n_dot_v, roughness)).rg;
+    let f0         = mix(vec3<f32>(g_specular), g_albedo * g_specular, g_metallic);
+    let spec_color = f0 * env_brdf.x + env_brdf.y;
+    let diffuse_color = g_albedo * (1.0 - g_metallic);
+
     // Compute lighting
     let lights_count = i32(in_lights[0].position_number.w);
     var transmitted = pow(vec3<f32>(0.1), vec3<f32>(2.2));
@@ -93,9 +265,16 @@ fn main(in: VertexOutput) -> @location(0) vec4<f32> {
         let spec_value  = pow(max(dot(g_normal, halfway_dir), 0.0), shininess);
 
         // Combine results
-        let ambient  =  g_albedo                 * light.ambient_const.rgb;
-        var diffused = (g_albedo * light_angle)  * light.diffuse_linea.rgb;
-        var specular = (g_specular * spec_value) * light.specular_quadr.rgb;
+        let ambient  =  g_albedo * g_material.yzw * light.ambient_const.rgb * ssao;
+        var diffused = (diffuse_color * light_angle)  * light.diffuse_linea.rgb * ssao;
+        var specular = (spec_color * spec_value) * light.specular_quadr.rgb;
+
+        // Directional light shadow
+        if light_type == 0 && i == i32(in_shadow_cascades.params.y) && in_shadow_cascades.params.x > 0.0 && g_material.x > 0.5 {
+            let shadow = directional_shadow(position, g_normal, light_dir);
+            diffused *= shadow;
+            specular *= shadow;
+        }
 
         // Point light or spot light
         if light_type == 1 || light_type == 2 {
@@ -109,18 +288,34 @@ fn main(in: VertexOutput) -> @location(0) vec4<f32> {
             specular *= attenuation;
         }
 
-        // Spot light intensity
+        // Point light shadow
+        if light_type == 1 && g_material.x > 0.5 {
+            let shadow = point_shadow(i, position, g_normal);
+            diffused *= shadow;
+            specular *= shadow;
+        }
+
+        // Spot light cone, decaying smoothly from the inner to the outer angle
         if light_type == 2 {
-            let theta     = dot(normalize(light.direction_type.xyz), light.direction_type.xyz);
-            let epsilon   = light.cut_off.x - light.cut_off.y;
-            let intensity = clamp((theta - light.diffuse_linea.w) / epsilon, 0.0, 1.0);
+            let theta     = dot(-light_dir, normalize(light.direction_type.xyz));
+            let intensity = smoothstep(light.cut_off.y, light.cut_off.x, theta);
 
             diffused *= intensity;
             specular *= intensity;
+
+            // Spot light shadow
+            if g_material.x > 0.5 {
+                let shadow = spot_shadow(i, position, g_normal);
+                diffused *= shadow;
+                specular *= shadow;
+            }
         }
         transmitted += ambient + diffused + specular;
     }
 
+    // Add the emitted light
+    transmitted += g_emissive;
+
     // Return the final color
     return vec4<f32>(transmitted, 1.0);
 }
@@ -0,0 +1,3 @@
+// Only the depth is written to the shadow map
+@fragment
+fn main() {}
@@ -0,0 +1,33 @@
+struct ModelInput {
+    @location(0) position:  vec3<f32>,
+    @location(1) tex_coord: vec2<f32>,
+    @location(2) normal:    vec3<f32>,
+    @location(3) tex_coord_lightmap: vec2<f32>,
+    @location(4) tangent:   vec4<f32>
+};
+
+// From world space to the normalized device coordinates of the cascade
+struct Cascade {
+    world_to_light: mat4x4<f32>
+}
+@group(0) @binding(0) var<uniform> in_cascade: Cascade;
+
+// Object to world space transformation ssbo
+struct ObjectToWorld {
+    obj_to_world:  mat4x4<f32>,
+    flags:         vec4<u32> // x: 1 cast shadows, 2 receive shadows, y: material index
+}
+@group(1) @binding(0) var<storage> in_model: array<ObjectToWorld>;
+
+
+@vertex
+fn main(@builtin(instance_index) instance: u32, model: ModelInput) -> @builtin(position) vec4<f32> {
+    // Move the instances not casting shadows outside of the clip space
+    if (in_model[instance].flags.x & 1u) == 0u {
+        return vec4<f32>(0.0, 0.0, -2.0, 1.0);
+    }
+
+    return in_cascade.world_to_light
+        * in_model[instance].obj_to_world
+        * vec4<f32>(model.position, 1.0);
+}
@@ -0,0 +1,72 @@
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>
+};
+
+struct Camera {
+    world_to_ndc: mat4x4<f32>,
+    ndc_to_world: mat4x4<f32>,
+    position: vec4<f32>
+}
+@group(0) @binding(0) var<uniform> in_camera: Camera;
+
+@group(1) @binding(0) var in_depth_texture: texture_depth_2d;
+@group(1) @binding(1) var in_depth_sampler: sampler;
+
+@group(2) @binding(0) var in_occlusion_texture: texture_2d<f32>;
+@group(2) @binding(1) var in_occlusion_sampler: sampler;
+@group(2) @binding(2) var in_normal_texture:    texture_2d<f32>;
+@group(2) @binding(3) var in_normal_sampler:    sampler;
+
+// Relative difference of distance to the camera at which a neighbour stops contributing
+const DEPTH_SIGMA: f32 = 0.05;
+// Sharpness of the falloff of the neighbours with a different normal
+const NORMAL_POWER: f32 = 16.0;
+
+
+
+fn world_from_screen_coord(uv: vec2<f32>, depth: f32) -> vec3<f32> {
+    let ndc_position   = vec4<f32>(uv.x * 2.0 - 1.0, (1 - uv.y) * 2.0 - 1.0, depth, 1.0);
+    let view_position  = in_camera.ndc_to_world * ndc_position;
+    let world_position = view_position.xyz / view_position.w;
+    return world_position;
+}
+
+// Blur the occlusion over a 5x5 neighbourhood, weighting the neighbours by their similarity in distance
+// to the camera and in normal so that the occlusion does not bleed over the edges.
+@fragment
+fn main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let size  = vec2<f32>(textureDimensions(in_occlusion_texture));
+    let uv    = in.clip_position.xy / size;
+    let depth = textureSampleLevel(in_depth_texture, in_depth_sampler, uv, 0.0);
+    if depth == 1.0 {
+        return vec4<f32>(1.0);
+    }
+    let center_distance = distance(in_camera.position.xyz, world_from_screen_coord(uv, depth));
+    let center_normal   = normalize(textureSampleLevel(in_normal_texture, in_normal_sampler, uv, 0.0).xyz);
+
+    var occlusion    = 0.0;
+    var total_weight = 0.0;
+    for (var x = -2; x <= 2; x = x + 1) {
+        for (var y = -2; y <= 2; y = y + 1) {
+            let sample_uv = uv + vec2<f32>(f32(x), f32(y)) / size;
+            let sample_depth = textureSampleLevel(in_depth_texture, in_depth_sampler, sample_uv, 0.0);
+            if sample_depth == 1.0 {
+                continue;
+            }
+            let sample_distance = distance(in_camera.position.xyz, world_from_screen_coord(sample_uv, sample_depth));
+            let sample_normal   = normalize(textureSampleLevel(in_normal_texture, in_normal_sampler, sample_uv, 0.0).xyz);
+
+            let depth_weight  = exp(-abs(sample_distance - center_distance) / (DEPTH_SIGMA * center_distance));
+            let normal_weight = pow(max(dot(sample_normal, center_normal), 0.0), NORMAL_POWER);
+            let weight = depth_weight * normal_weight;
+            occlusion    += textureSampleLevel(in_occlusion_texture, in_occlusion_sampler, sample_uv, 0.0).r * weight;
+            total_weight += weight;
+        }
+    }
+
+    // The center always contributes, unless its normal is degenerate
+    if total_weight <= 0.0 {
+        return vec4<f32>(textureSampleLevel(in_occlusion_texture, in_occlusion_sampler, uv, 0.0).r, 0.0, 0.0, 1.0);
+    }
+    return vec4<f32>(occlusion / total_weight, 0.0, 0.0, 1.0);
+}
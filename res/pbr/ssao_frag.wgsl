@@ -0,0 +1,88 @@
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>
+};
+
+struct Camera {
+    world_to_ndc: mat4x4<f32>,
+    ndc_to_world: mat4x4<f32>,
+    position: vec4<f32>
+}
+@group(0) @binding(0) var<uniform> in_camera: Camera;
+
+@group(1) @binding(0) var in_depth_texture: texture_depth_2d;
+@group(1) @binding(1) var in_depth_sampler: sampler;
+
+@group(2) @binding(0) var in_normal_texture: texture_2d<f32>;
+@group(2) @binding(1) var in_normal_sampler: sampler;
+// Random rotations of the kernel around the normals, tiled over the screen. xy: rotation in [0, 1]
+@group(2) @binding(2) var in_noise_texture:  texture_2d<f32>;
+struct Ssao {
+    kernel: array<vec4<f32>, 64>, // Tangent space offsets of the samples of the hemisphere
+    params: vec4<f32>             // x: radius, y: bias, z: number of samples
+}
+@group(2) @binding(3) var<uniform> in_ssao: Ssao;
+
+
+
+fn world_from_screen_coord(uv: vec2<f32>, depth: f32) -> vec3<f32> {
+    let ndc_position   = vec4<f32>(uv.x * 2.0 - 1.0, (1 - uv.y) * 2.0 - 1.0, depth, 1.0);
+    let view_position  = in_camera.ndc_to_world * ndc_position;
+    let world_position = view_position.xyz / view_position.w;
+    return world_position;
+}
+
+// Compute the fraction of the hemisphere around the normal of each pixel that is not occluded by the scene.
+@fragment
+fn main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // Read position of the object in world space
+    let pixel = vec2<i32>(in.clip_position.xy);
+    let uv    = in.clip_position.xy / vec2<f32>(textureDimensions(in_depth_texture));
+    let depth = textureSampleLevel(in_depth_texture, in_depth_sampler, uv, 0.0);
+    if depth == 1.0 { // Background is not occluded
+        return vec4<f32>(1.0);
+    }
+    let position = world_from_screen_coord(uv, depth);
+    let normal   = normalize(textureSampleLevel(in_normal_texture, in_normal_sampler, uv, 0.0).xyz);
+
+    // Rotate the kernel around the normal with the noise (Gram-Schmidt)
+    let noise     = vec3<f32>(textureLoad(in_noise_texture, pixel % vec2<i32>(textureDimensions(in_noise_texture)), 0).xy * 2.0 - 1.0, 0.0);
+    var tangent   = noise - normal * dot(noise, normal);
+    if dot(tangent, tangent) < 1e-6 {
+        tangent = select(vec3<f32>(1.0, 0.0, 0.0), vec3<f32>(0.0, 1.0, 0.0), abs(normal.x) > 0.9);
+        tangent = tangent - normal * dot(tangent, normal);
+    }
+    tangent = normalize(tangent);
+    let tbn = mat3x3<f32>(tangent, cross(normal, tangent), normal);
+
+    // Compare the distance to the camera of each sample with the one of the scene at the sample
+    let radius = in_ssao.params.x;
+    let bias   = in_ssao.params.y;
+    let sample_count = i32(in_ssao.params.z);
+    let distance_to_camera = distance(in_camera.position.xyz, position);
+    var occlusion = 0.0;
+    for (var i = 0; i < sample_count; i = i + 1) {
+        let sample_position = position + tbn * in_ssao.kernel[i].xyz * radius;
+
+        // Project the sample on the screen
+        let sample_clip = in_camera.world_to_ndc * vec4<f32>(sample_position, 1.0);
+        if sample_clip.w <= 0.0 {
+            continue;
+        }
+        let sample_ndc = sample_clip.xyz / sample_clip.w;
+        let sample_uv  = vec2<f32>(sample_ndc.x * 0.5 + 0.5, 0.5 - sample_ndc.y * 0.5);
+        if any(sample_uv < vec2<f32>(0.0)) || any(sample_uv > vec2<f32>(1.0)) {
+            continue;
+        }
+
+        // The sample is occluded if the scene is closer to the camera, ignoring the occluders out of the radius
+        let scene_depth    = textureSampleLevel(in_depth_texture, in_depth_sampler, sample_uv, 0.0);
+        let scene_position = world_from_screen_coord(sample_uv, scene_depth);
+        let scene_distance = distance(in_camera.position.xyz, scene_position);
+        let range_check    = smoothstep(0.0, 1.0, radius / max(abs(distance_to_camera - scene_distance), 1e-4));
+        if scene_distance <= distance(in_camera.position.xyz, sample_position) - bias {
+            occlusion += range_check;
+        }
+    }
+
+    return vec4<f32>(1.0 - occlusion / f32(max(sample_count, 1)), 0.0, 0.0, 1.0);
+}
@@ -0,0 +1,147 @@
+struct Camera {
+    world_to_ndc: mat4x4<f32>,
+    ndc_to_world: mat4x4<f32>,
+    position: vec4<f32>
+}
+struct Ssr {
+    // x: the maximum number of steps, y: the stride, z: the fade distance, w: the number of binary search steps
+    params: vec4<f32>
+}
+
+@group(0) @binding(0) var in_depth_texture: texture_depth_2d;
+@group(0) @binding(1) var in_hdr_texture: texture_2d<f32>;
+@group(0) @binding(2) var in_albedo_texture: texture_2d<f32>;   // rgb: albedo, a: roughness
+@group(0) @binding(3) var in_normal_texture: texture_2d<f32>;   // xyz: normal, w: specular
+@group(0) @binding(4) var in_emissive_texture: texture_2d<f32>; // rgb: emissive, a: metallic
+// Pre-integrated GGX BRDF. u: dot(N, V), v: roughness. r: scale of F0, g: bias
+@group(0) @binding(5) var in_brdf_lut_texture: texture_2d<f32>;
+@group(0) @binding(6) var in_brdf_lut_sampler: sampler;
+@group(0) @binding(7) var<uniform> in_camera: Camera;
+@group(0) @binding(8) var<uniform> in_ssr: Ssr;
+@group(0) @binding(9) var out_reflections: texture_storage_2d<rgba16float, write>;
+
+// Offset of the ray origin along the normal, to avoid hitting the reflecting surface
+const NORMAL_OFFSET: f32 = 0.01;
+
+// World space position of a pixel at a depth
+fn world_from_screen_coord(uv: vec2<f32>, depth: f32) -> vec3<f32> {
+    let ndc_position   = vec4<f32>(uv.x * 2.0 - 1.0, (1 - uv.y) * 2.0 - 1.0, depth, 1.0);
+    let world_position = in_camera.ndc_to_world * ndc_position;
+    return world_position.xyz / world_position.w;
+}
+
+// Screen coordinates of a world space position, outside of [0, 1] when it is off screen
+fn screen_from_world(position: vec3<f32>) -> vec2<f32> {
+    let clip_position = in_camera.world_to_ndc * vec4<f32>(position, 1.0);
+    if clip_position.w <= 0.0 { // Behind the camera
+        return vec2<f32>(-1.0);
+    }
+    let ndc_position = clip_position.xy / clip_position.w;
+    return vec2<f32>(ndc_position.x * 0.5 + 0.5, 1.0 - (ndc_position.y * 0.5 + 0.5));
+}
+
+// Difference between the distances to the camera of a position and of the scene surface in front of it.
+// Positive when the position is behind the surface. The uv is set to the screen coordinates of the position.
+fn depth_difference(position: vec3<f32>, size: vec2<u32>, uv: ptr<function, vec2<f32>>) -> f32 {
+    *uv = screen_from_world(position);
+    if any(*uv < vec2<f32>(0.0)) || any(*uv >= vec2<f32>(1.0)) {
+        return -1.0;
+    }
+    let depth = textureLoad(in_depth_texture, vec2<i32>(*uv * vec2<f32>(size)), 0);
+    if depth == 1.0 { // Background
+        return -1.0;
+    }
+    let surface = world_from_screen_coord(*uv, depth);
+    return length(position - in_camera.position.xyz) - length(surface - in_camera.position.xyz);
+}
+
+@compute @workgroup_size(8, 8, 1)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let size = textureDimensions(out_reflections);
+    if (id.x >= size.x || id.y >= size.y) {
+        return;
+    }
+    let pixel = vec2<i32>(id.xy);
+
+    // Skip the background
+    let depth = textureLoad(in_depth_texture, pixel, 0);
+    if depth == 1.0 {
+        textureStore(out_reflections, pixel, vec4<f32>(0.0));
+        return;
+    }
+
+    // Read the G-Buffer
+    let uv = (vec2<f32>(id.xy) + 0.5) / vec2<f32>(size);
+    let position = world_from_screen_coord(uv, depth);
+    let g_albedo_raw = textureLoad(in_albedo_texture, pixel, 0);
+    let g_norm_raw = textureLoad(in_normal_texture, pixel, 0);
+    let g_normal = normalize(g_norm_raw.xyz);
+    let g_metallic = textureLoad(in_emissive_texture, pixel, 0).a;
+    let roughness = clamp(g_albedo_raw.a, 0.05, 1.0);
+
+    // Fresnel of the surface, as the specular of the lighting pass
+    let view_dir = normalize(in_camera.position.xyz - position);
+    let n_dot_v = clamp(dot(g_normal, view_dir), 0.0, 1.0);
+    let env_brdf = textureSampleLevel(in_brdf_lut_texture, in_brdf_lut_sampler, vec2<f32>(n_dot_v, roughness), 0.0).rg;
+    let f0 = mix(vec3<f32>(g_norm_raw.w), g_albedo_raw.rgb * g_norm_raw.w, g_metallic);
+    let fresnel = f0 * env_brdf.x + env_brdf.y;
+
+    // Rough surfaces reflect a blurred scene, which is not traced
+    let smoothness = (1.0 - roughness) * (1.0 - roughness);
+    if smoothness <= 0.0 || all(fresnel <= vec3<f32>(0.0)) {
+        textureStore(out_reflections, pixel, vec4<f32>(0.0));
+        return;
+    }
+
+    // March the reflected ray until it passes behind a surface, thinner than a step
+    let max_iterations = i32(in_ssr.params.x);
+    let stride = in_ssr.params.y;
+    let ray_dir = reflect(-view_dir, g_normal);
+    let origin = position + g_normal * NORMAL_OFFSET;
+    var hit_uv = vec2<f32>(0.0);
+    var previous = 0.0;
+    var hit_distance = -1.0;
+    for (var i = 1; i <= max_iterations; i = i + 1) {
+        let t = f32(i) * stride;
+        var sample_uv = vec2<f32>(0.0);
+        let difference = depth_difference(origin + ray_dir * t, size, &sample_uv);
+        if any(sample_uv < vec2<f32>(0.0)) || any(sample_uv >= vec2<f32>(1.0)) { // The ray left the screen
+            break;
+        }
+        if difference > 0.0 && difference < stride {
+            hit_distance = t;
+            hit_uv = sample_uv;
+            break;
+        }
+        previous = t;
+    }
+    if hit_distance < 0.0 {
+        textureStore(out_reflections, pixel, vec4<f32>(0.0));
+        return;
+    }
+
+    // Refine the intersection between the last step in front of the surface and the first behind it
+    var low = previous;
+    var high = hit_distance;
+    for (var i = 0; i < i32(in_ssr.params.w); i = i + 1) {
+        let middle = (low + high) * 0.5;
+        var sample_uv = vec2<f32>(0.0);
+        let difference = depth_difference(origin + ray_dir * middle, size, &sample_uv);
+        if difference > 0.0 && difference < stride {
+            high = middle;
+            hit_uv = sample_uv;
+        } else {
+            low = middle;
+        }
+    }
+
+    // Fade the reflections along the ray and near the edges of the screen
+    let distance_fade = 1.0 - clamp(high / in_ssr.params.z, 0.0, 1.0);
+    let edge = min(hit_uv, vec2<f32>(1.0) - hit_uv);
+    let edge_fade = smoothstep(0.0, 0.1, min(edge.x, edge.y));
+
+    // Reflected light, weighted by the fresnel
+    let reflected = textureLoad(in_hdr_texture, vec2<i32>(hit_uv * vec2<f32>(size)), 0).rgb;
+    let weight = fresnel * smoothness * distance_fade * edge_fade;
+    textureStore(out_reflections, pixel, vec4<f32>(reflected * weight, 1.0));
+}
@@ -0,0 +1,11 @@
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>
+};
+
+@group(0) @binding(0) var in_reflections_texture: texture_2d<f32>;
+
+// Output the reflected light, added to the hdr color
+@fragment
+fn main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(textureLoad(in_reflections_texture, vec2<i32>(in.clip_position.xy), 0).rgb, 0.0);
+}
@@ -0,0 +1,192 @@
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coord:    vec2<f32>,
+    @location(1) normal_world: vec3<f32>, // Normal in world space
+    @location(2) position_world: vec3<f32>, // Position in world space
+    @location(3) tex_coord_lightmap: vec2<f32>, // UV1, used for the lightmap
+    @location(4) @interpolate(flat) material_index: u32, // Index in the materials buffer
+    @location(5) tangent_world: vec4<f32> // Tangent in world space, with the sign of the bitangent
+};
+
+struct FragOutput {
+    @location(0) accum:     vec4<f32>, // rgb: weighted premultiplied color, a: weighted opacity
+    @location(1) revealage: vec4<f32>  // r: opacity, multiplied as transparency by the blending
+};
+
+struct Camera {
+    world_to_ndc: mat4x4<f32>,
+    ndc_to_world: mat4x4<f32>,
+    position: vec4<f32>
+}
+@group(0) @binding(0) var<uniform> in_camera: Camera;
+
+// Material description, indexed by the material index of the instance
+struct PbrMaterial {
+    flags:    vec4<f32>, // x: has_albedo, y: has_specular, z: has_lightmap, w: has_normal
+    albedo:   vec4<f32>,
+    uv_transform: vec4<f32>, // xy: uv scale, zw: uv offset
+    emissive: vec4<f32>, // rgb: emissive color, w: has_emissive
+    specular: f32,
+    lightmap_intensity: f32,
+    roughness: f32,
+    metallic: f32,
+    has_roughness_metallic: f32,
+    has_ao: f32,
+    ao_strength: f32,
+    alpha_cutoff: f32
+};
+@group(1) @binding(1) var<storage> in_materials: array<PbrMaterial>;
+@group(2) @binding(1) var in_albedo_texture: texture_2d<f32>;
+@group(2) @binding(2) var in_albedo_sampler: sampler;
+@group(2) @binding(3) var in_specular_texture: texture_2d<f32>;
+@group(2) @binding(4) var in_specular_sampler: sampler;
+@group(2) @binding(5) var in_lightmap_texture: texture_2d<f32>;
+@group(2) @binding(6) var in_lightmap_sampler: sampler;
+@group(2) @binding(7) var in_normal_texture: texture_2d<f32>;
+@group(2) @binding(8) var in_normal_sampler: sampler;
+@group(2) @binding(9) var in_emissive_texture: texture_2d<f32>;
+@group(2) @binding(10) var in_emissive_sampler: sampler;
+@group(2) @binding(11) var in_roughness_metallic_texture: texture_2d<f32>;
+@group(2) @binding(12) var in_roughness_metallic_sampler: sampler;
+@group(2) @binding(13) var in_ao_texture: texture_2d<f32>;
+@group(2) @binding(14) var in_ao_sampler: sampler;
+
+struct Light {
+    /// World space position of the directional light for xyz. If it is the first element, the w component is the number of lights.
+    position_number: vec4<f32>,
+    /// World space direction of the light. The w component is the type of the light: 0 for directional, 1 for point, 2 for spot.
+    direction_type:  vec4<f32>,
+    /// Ambient color of the light. The w component is the constant attenuation factor if the light is a point light. It is the cos of the inner cut-off angle in radians if the light is a spot light.
+    ambient_const:   vec4<f32>,
+    /// Diffuse color of the light. The w component is the linear attenuation factor if the light is a point light. It is the cos of the outer cut-off angle in radians if the light is a spot light.
+    diffuse_linea:   vec4<f32>,
+    /// Specular color of the light. The w component is the quadratic attenuation factor if the light is a point light.
+    specular_quadr:  vec4<f32>,
+    /// Inner and outer cut-off angles in radians if the light is a spot light.
+    cut_off:         vec4<f32>
+};
+@group(3) @binding(0) var<storage> in_lights: array<Light>;
+
+// Weight of a fragment, favoring the close and opaque fragments (McGuire and Bavoil, 2013)
+fn weight(depth: f32, alpha: f32) -> f32 {
+    let a = min(1.0, alpha * 10.0) + 0.01;
+    let b = 1.0 - depth * 0.9;
+    return clamp(a * a * a * 1e8 * b * b * b, 1e-2, 3e3);
+}
+
+@fragment
+fn main(in: VertexOutput, @builtin(front_facing) front_facing: bool) -> FragOutput {
+    var out: FragOutput;
+    let in_material = in_materials[in.material_index];
+
+    // Tile the textures using the material uv transform, as in the gbuffer pass
+    let tex_coord = in.tex_coord * in_material.uv_transform.xy + in_material.uv_transform.zw;
+    let tex_coord_dx = dpdx(tex_coord);
+    let tex_coord_dy = dpdy(tex_coord);
+
+    // Sample the textures in uniform control flow, as the material flags are not uniform
+    let albedo_sample = textureSampleGrad(in_albedo_texture, in_albedo_sampler, fract(tex_coord), tex_coord_dx, tex_coord_dy);
+    let specular_sample = textureSampleGrad(in_specular_texture, in_specular_sampler, fract(tex_coord), tex_coord_dx, tex_coord_dy).r;
+    let lightmap_sample = textureSample(in_lightmap_texture, in_lightmap_sampler, in.tex_coord_lightmap).rgb;
+    let normal_sample = textureSampleGrad(in_normal_texture, in_normal_sampler, fract(tex_coord), tex_coord_dx, tex_coord_dy).xyz;
+    let emissive_sample = textureSampleGrad(in_emissive_texture, in_emissive_sampler, fract(tex_coord), tex_coord_dx, tex_coord_dy).rgb;
+    let roughness_metallic_sample = textureSampleGrad(in_roughness_metallic_texture, in_roughness_metallic_sampler, fract(tex_coord), tex_coord_dx, tex_coord_dy);
+    let ao_sample = textureSampleGrad(in_ao_texture, in_ao_sampler, fract(tex_coord), tex_coord_dx, tex_coord_dy).r;
+
+    // Perturb the normal with the tangent space normal map, if the mesh has tangents
+    var normal = normalize(in.normal_world);
+    let tangent_length = length(in.tangent_world.xyz);
+    if (in_material.flags.w == 1.0 && tangent_length > 0.0) {
+        let tangent = normalize(in.tangent_world.xyz - normal * dot(normal, in.tangent_world.xyz));
+        let bitangent = cross(normal, tangent) * sign(in.tangent_world.w);
+        normal = normalize(mat3x3<f32>(tangent, bitangent, normal) * (normal_sample * 2.0 - 1.0));
+    }
+    if (!front_facing) {
+        normal = -normal;
+    }
+
+    // Material parameters
+    var albedo = in_material.albedo;
+    if (in_material.flags.x == 1.0) {
+        albedo = albedo_sample;
+    }
+    var specular = in_material.specular;
+    if (in_material.flags.y == 1.0) {
+        specular = specular_sample;
+    }
+    var roughness = in_material.roughness;
+    var metallic = in_material.metallic;
+    if (in_material.has_roughness_metallic == 1.0) {
+        roughness *= roughness_metallic_sample.g;
+        metallic *= roughness_metallic_sample.b;
+    }
+    var lightmap = vec3<f32>(1.0);
+    if (in_material.flags.z == 1.0) {
+        lightmap = lightmap_sample * in_material.lightmap_intensity;
+    }
+    if (in_material.has_ao == 1.0) {
+        lightmap *= mix(1.0, ao_sample, in_material.ao_strength);
+    }
+    var emissive = in_material.emissive.rgb;
+    if (in_material.emissive.w == 1.0) {
+        emissive *= emissive_sample;
+    }
+
+    // General parameters, as in the lighting pass
+    roughness = clamp(roughness, 0.05, 1.0);
+    let shininess = 2.0 / pow(roughness, 4.0) - 2.0;
+    let view_dir  = normalize(in_camera.position.xyz - in.position_world);
+    let spec_color = mix(vec3<f32>(specular), albedo.rgb * specular, metallic);
+    let diffuse_color = albedo.rgb * (1.0 - metallic);
+
+    // Compute the lighting, without the shadows
+    let lights_count = i32(in_lights[0].position_number.w);
+    var transmitted = vec3<f32>(0.0);
+    for (var i = 0; i < lights_count; i = i + 1) {
+        let light = in_lights[i];
+        let light_type = i32(light.direction_type.w);
+
+        // Light direction
+        var light_dir = -normalize(light.direction_type.xyz);
+        if light_type == 1 || light_type == 2 { // Point light or spot light
+            light_dir = normalize(light.position_number.xyz - in.position_world);
+        }
+
+        // Diffused and specular
+        let light_angle = max(dot(normal, light_dir), 0.0);
+        let halfway_dir = normalize(light_dir + view_dir);
+        let spec_value  = pow(max(dot(normal, halfway_dir), 0.0), shininess);
+
+        let ambient  = albedo.rgb * lightmap * light.ambient_const.rgb;
+        var diffused = diffuse_color * light_angle * light.diffuse_linea.rgb;
+        var specular = spec_color * spec_value * light.specular_quadr.rgb;
+
+        // Point light or spot light attenuation
+        if light_type == 1 || light_type == 2 {
+            let distance = length(light.position_number.xyz - in.position_world);
+            let attenuation = 1.0 / (light.ambient_const.w
+                + light.diffuse_linea.w * distance
+                + light.specular_quadr.w * distance * distance);
+            diffused *= attenuation;
+            specular *= attenuation;
+        }
+
+        // Spot light cone
+        if light_type == 2 {
+            let theta     = dot(-light_dir, normalize(light.direction_type.xyz));
+            let intensity = smoothstep(light.cut_off.y, light.cut_off.x, theta);
+            diffused *= intensity;
+            specular *= intensity;
+        }
+        transmitted += ambient + diffused + specular;
+    }
+    transmitted += emissive;
+
+    // Accumulate the premultiplied color, weighted by the depth and the opacity
+    let alpha = clamp(albedo.a, 0.0, 1.0);
+    let w = weight(in.clip_position.z, alpha);
+    out.accum = vec4<f32>(transmitted * alpha, alpha) * w;
+    out.revealage = vec4<f32>(alpha);
+
+    return out;
+}
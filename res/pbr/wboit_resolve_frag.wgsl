@@ -0,0 +1,20 @@
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>
+};
+
+@group(0) @binding(0) var in_accum_texture:     texture_2d<f32>;
+@group(0) @binding(1) var in_revealage_texture: texture_2d<f32>;
+
+// Average the accumulated colors, blended over the hdr color with the opacity of the transparent fragments
+@fragment
+fn main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let coord = vec2<i32>(in.clip_position.xy);
+    let revealage = textureLoad(in_revealage_texture, coord, 0).r;
+    if (revealage >= 1.0) { // No transparent fragment
+        discard;
+    }
+    let accum = textureLoad(in_accum_texture, coord, 0);
+    let color = accum.rgb / max(accum.a, 1e-5);
+
+    return vec4<f32>(color, 1.0 - revealage);
+}
@@ -0,0 +1,99 @@
+struct Skybox {
+    // xyz: the normalized sun direction, w: the turbidity
+    sun_direction_turbidity: vec4<f32>,
+    // x: 0 for the texture, 1 for the procedural sky
+    params: vec4<f32>
+};
+@group(1) @binding(0) var in_texture: texture_2d<f32>;
+@group(1) @binding(1) var in_sampler: sampler;
+@group(1) @binding(2) var<uniform> skybox: Skybox;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) view_direction: vec3<f32>
+};
+
+const PI: f32 = 3.14159265359;
+const SUN_ANGULAR_RADIUS: f32 = 0.0047;
+const SUN_INTENSITY: f32 = 20.0;
+
+// Sample the equirectangular environment map in a direction
+fn equirectangular(direction: vec3<f32>) -> vec3<f32> {
+    let uv = vec2<f32>(
+        atan2(direction.z, direction.x) / (2.0 * PI) + 0.5,
+        acos(clamp(direction.y, -1.0, 1.0)) / PI
+    );
+    return textureSampleLevel(in_texture, in_sampler, uv, 0.0).rgb;
+}
+
+// Perez sky distribution, for the angle to the zenith and the angle to the sun
+fn perez(cos_theta: f32, gamma: f32, cos_gamma: f32, a: f32, b: f32, c: f32, d: f32, e: f32) -> f32 {
+    return (1.0 + a * exp(b / cos_theta)) * (1.0 + c * exp(d * gamma) + e * cos_gamma * cos_gamma);
+}
+
+// Preetham analytic sky model, in linear rgb
+fn atmosphere(direction: vec3<f32>) -> vec3<f32> {
+    let sun = skybox.sun_direction_turbidity.xyz;
+    let t = skybox.sun_direction_turbidity.w;
+
+    // Angles to the zenith and to the sun, clamped to the horizon
+    let theta_s = min(acos(clamp(sun.y, -1.0, 1.0)), PI / 2.0 - 0.01);
+    let cos_theta = max(direction.y, 0.01);
+    let cos_gamma = clamp(dot(direction, sun), -1.0, 1.0);
+    let gamma = acos(cos_gamma);
+
+    // Zenith luminance (kcd/m2) and chromaticity
+    let chi = (4.0 / 9.0 - t / 120.0) * (PI - 2.0 * theta_s);
+    let zenith_luminance = max((4.0453 * t - 4.9710) * tan(chi) - 0.2155 * t + 2.4192, 0.0);
+    let theta_s2 = theta_s * theta_s;
+    let theta_s3 = theta_s2 * theta_s;
+    let zenith_x = t * t * (0.00166 * theta_s3 - 0.00375 * theta_s2 + 0.00209 * theta_s)
+        + t * (-0.02903 * theta_s3 + 0.06377 * theta_s2 - 0.03202 * theta_s + 0.00394)
+        + (0.11693 * theta_s3 - 0.21196 * theta_s2 + 0.06052 * theta_s + 0.25886);
+    let zenith_y = t * t * (0.00275 * theta_s3 - 0.00610 * theta_s2 + 0.00317 * theta_s)
+        + t * (-0.04214 * theta_s3 + 0.08970 * theta_s2 - 0.04153 * theta_s + 0.00516)
+        + (0.15346 * theta_s3 - 0.26756 * theta_s2 + 0.06670 * theta_s + 0.26688);
+
+    // Distribution coefficients, depending on the turbidity
+    let cy = array<f32, 5>(0.1787 * t - 1.4630, -0.3554 * t + 0.4275, -0.0227 * t + 5.3251, 0.1206 * t - 2.5771, -0.0670 * t + 0.3703);
+    let cx = array<f32, 5>(-0.0193 * t - 0.2592, -0.0665 * t + 0.0008, -0.0004 * t + 0.2125, -0.0641 * t - 0.8989, -0.0033 * t + 0.0452);
+    let cc = array<f32, 5>(-0.0167 * t - 0.2608, -0.0950 * t + 0.0092, -0.0079 * t + 0.2102, -0.0441 * t - 1.6537, -0.0109 * t + 0.0529);
+
+    // Luminance and chromaticity in the view direction, relative to the zenith
+    let cos_theta_s = cos(theta_s);
+    let luminance = zenith_luminance
+        * perez(cos_theta, gamma, cos_gamma, cy[0], cy[1], cy[2], cy[3], cy[4])
+        / perez(1.0, theta_s, cos_theta_s, cy[0], cy[1], cy[2], cy[3], cy[4]);
+    let x = zenith_x
+        * perez(cos_theta, gamma, cos_gamma, cx[0], cx[1], cx[2], cx[3], cx[4])
+        / perez(1.0, theta_s, cos_theta_s, cx[0], cx[1], cx[2], cx[3], cx[4]);
+    let y = zenith_y
+        * perez(cos_theta, gamma, cos_gamma, cc[0], cc[1], cc[2], cc[3], cc[4])
+        / perez(1.0, theta_s, cos_theta_s, cc[0], cc[1], cc[2], cc[3], cc[4]);
+
+    // xyY to linear rgb, scaling the luminance to the range of the scene lights
+    let big_y = luminance * 0.05;
+    let xyz = vec3<f32>(x / y * big_y, big_y, (1.0 - x - y) / y * big_y);
+    var color = max(vec3<f32>(
+         3.2406 * xyz.x - 1.5372 * xyz.y - 0.4986 * xyz.z,
+        -0.9689 * xyz.x + 1.8758 * xyz.y + 0.0415 * xyz.z,
+         0.0557 * xyz.x - 0.2040 * xyz.y + 1.0570 * xyz.z
+    ), vec3<f32>(0.0));
+
+    // Sun disk, above the horizon
+    if gamma < SUN_ANGULAR_RADIUS && direction.y > 0.0 {
+        color += vec3<f32>(SUN_INTENSITY);
+    }
+
+    // Fade to night when the sun goes below the horizon
+    return color * smoothstep(-0.1, 0.1, sun.y);
+}
+
+@fragment
+fn main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let direction = normalize(in.view_direction);
+    if skybox.params.x > 0.5 {
+        return vec4<f32>(atmosphere(direction), 1.0);
+    }
+    return vec4<f32>(equirectangular(direction), 1.0);
+}
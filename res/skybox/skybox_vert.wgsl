@@ -0,0 +1,27 @@
+struct Camera {
+    world_to_ndc: mat4x4<f32>,
+    ndc_to_world: mat4x4<f32>,
+    position: vec4<f32>
+};
+@group(0) @binding(0) var<uniform> camera: Camera;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) view_direction: vec3<f32>
+};
+
+// Full screen triangle at the far plane, generated from the vertex index
+@vertex
+fn main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    var out: VertexOutput;
+
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    let ndc = uv * 2.0 - 1.0;
+    out.clip_position = vec4<f32>(ndc, 1.0, 1.0);
+
+    // Reconstruct the view direction from a point of the pixel ray
+    let world_position = camera.ndc_to_world * vec4<f32>(ndc, 0.5, 1.0);
+    out.view_direction = world_position.xyz / world_position.w - camera.position.xyz;
+
+    return out;
+}
@@ -0,0 +1,69 @@
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>
+};
+
+@group(0) @binding(0) var in_hdr_texture: texture_2d<f32>;
+struct ToneMapping {
+    params: vec4<f32> // x: operator, y: exposure, z: max white, w: srgb encode
+}
+@group(0) @binding(1) var<uniform> in_tonemapping: ToneMapping;
+
+// Reinhard operator reaching 1 for the max white brightness
+fn reinhard_extended(color: vec3<f32>, max_white: f32) -> vec3<f32> {
+    return color * (1.0 + color / (max_white * max_white)) / (1.0 + color);
+}
+
+// Fit of the ACES filmic curve by Krzysztof Narkowicz
+fn aces(color: vec3<f32>) -> vec3<f32> {
+    let a = 2.51;
+    let b = 0.03;
+    let c = 2.43;
+    let d = 0.59;
+    let e = 0.14;
+    return (color * (a * color + b)) / (color * (c * color + d) + e);
+}
+
+// Filmic curve of John Hable
+fn uncharted2_curve(x: vec3<f32>) -> vec3<f32> {
+    let a = 0.15;
+    let b = 0.50;
+    let c = 0.10;
+    let d = 0.20;
+    let e = 0.02;
+    let f = 0.30;
+    return ((x * (a * x + c * b) + d * e) / (x * (a * x + b) + d * f)) - e / f;
+}
+fn uncharted2(color: vec3<f32>) -> vec3<f32> {
+    let white = 11.2;
+    let exposure_bias = 2.0;
+    return uncharted2_curve(color * exposure_bias) / uncharted2_curve(vec3<f32>(white));
+}
+
+// Encode a linear color to sRGB
+fn srgb_encode(color: vec3<f32>) -> vec3<f32> {
+    let low  = color * 12.92;
+    let high = 1.055 * pow(color, vec3<f32>(1.0 / 2.4)) - 0.055;
+    return select(high, low, color <= vec3<f32>(0.0031308));
+}
+
+// Tone map the hdr color
+@fragment
+fn main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let hdr = textureLoad(in_hdr_texture, vec2<i32>(in.clip_position.xy), 0).rgb;
+    let color = max(hdr * in_tonemapping.params.y, vec3<f32>(0.0));
+
+    var mapped: vec3<f32>;
+    switch u32(in_tonemapping.params.x) {
+        case 1u: { mapped = color / (1.0 + color); }
+        case 2u: { mapped = reinhard_extended(color, in_tonemapping.params.z); }
+        case 3u: { mapped = aces(color); }
+        case 4u: { mapped = uncharted2(color); }
+        default: { mapped = color; }
+    }
+    mapped = clamp(mapped, vec3<f32>(0.0), vec3<f32>(1.0));
+
+    if (in_tonemapping.params.w > 0.5) {
+        mapped = srgb_encode(mapped);
+    }
+    return vec4<f32>(mapped, 1.0);
+}
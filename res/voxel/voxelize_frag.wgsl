@@ -0,0 +1,44 @@
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) grid_position: vec3<f32> // Position in the grid between 0 and 1
+};
+
+const VOXEL_GRID_SIZE: u32 = 128u;
+
+// Voxel grid, one bit per voxel
+@group(1) @binding(0) var<storage, read_write> out_voxels: array<atomic<u32>>;
+
+// Voxel grid bounds and projection axis
+struct VoxelGrid {
+    center_extent: vec4<f32>, // xyz: world space center, w: half extent
+    axis:          vec4<u32>  // x: projection axis (0: x, 1: y, 2: z)
+}
+var<push_constant> in_grid: VoxelGrid;
+
+
+@fragment
+fn main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // Only keep the triangles whose dominant axis is the projection axis, so each triangle is rasterized once
+    let normal = abs(cross(dpdx(in.grid_position), dpdy(in.grid_position)));
+    var dominant_axis = 2u;
+    if (normal.x >= normal.y && normal.x >= normal.z) {
+        dominant_axis = 0u;
+    } else if (normal.y >= normal.z) {
+        dominant_axis = 1u;
+    }
+    if (dominant_axis != in_grid.axis.x) {
+        discard;
+    }
+
+    // Discard the fragments outside of the grid
+    if (any(in.grid_position < vec3<f32>(0.0)) || any(in.grid_position >= vec3<f32>(1.0))) {
+        discard;
+    }
+
+    // Fill the voxel
+    let voxel = min(vec3<u32>(in.grid_position * f32(VOXEL_GRID_SIZE)), vec3<u32>(VOXEL_GRID_SIZE - 1u));
+    let index = voxel.x + voxel.y * VOXEL_GRID_SIZE + voxel.z * VOXEL_GRID_SIZE * VOXEL_GRID_SIZE;
+    atomicOr(&out_voxels[index / 32u], 1u << (index % 32u));
+
+    return vec4<f32>(1.0);
+}
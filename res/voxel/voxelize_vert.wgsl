@@ -0,0 +1,43 @@
+struct ModelInput {
+    @location(0) position:  vec3<f32>,
+    @location(1) tex_coord: vec2<f32>,
+    @location(2) normal:    vec3<f32>
+};
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) grid_position: vec3<f32> // Position in the grid between 0 and 1
+};
+
+// Object to world space transformation ssbo
+struct ObjectToWorld {
+    obj_to_world:  mat4x4<f32>,
+    flags:         vec4<u32>
+}
+@group(0) @binding(0) var<storage> in_model: array<ObjectToWorld>;
+
+// Voxel grid bounds and projection axis
+struct VoxelGrid {
+    center_extent: vec4<f32>, // xyz: world space center, w: half extent
+    axis:          vec4<u32>  // x: projection axis (0: x, 1: y, 2: z)
+}
+var<push_constant> in_grid: VoxelGrid;
+
+
+@vertex
+fn main(@builtin(instance_index) instance: u32, model: ModelInput) -> VertexOutput {
+    var out: VertexOutput;
+
+    // Project the vertex along the current axis of the grid
+    let world_position = in_model[instance].obj_to_world * vec4<f32>(model.position, 1.0);
+    let grid_position = (world_position.xyz - in_grid.center_extent.xyz) / (2.0 * in_grid.center_extent.w) + 0.5;
+    var projected = grid_position;
+    if (in_grid.axis.x == 0u) {
+        projected = grid_position.yzx;
+    } else if (in_grid.axis.x == 1u) {
+        projected = grid_position.zxy;
+    }
+    out.clip_position = vec4<f32>(projected.xy * 2.0 - 1.0, projected.z, 1.0);
+    out.grid_position = grid_position;
+
+    return out;
+}